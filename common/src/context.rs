@@ -0,0 +1,181 @@
+use alloc::vec::Vec;
+
+use crate::ExecutionModel;
+
+/// Architecture of the device that the firmware (and so the code filling in
+/// the context structs) is compiled for. The programs themselves are always
+/// compiled for the 64-bit eBPF target, which is why the layouts need to be
+/// pinned down explicitly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TargetArch {
+    pub name: &'static str,
+    /// Size of the pointers and of `size_t` in bytes.
+    pub word_size: usize,
+    /// Alignment of the 64-bit integers inside of structs, it is only 4 bytes
+    /// on e.g. 32-bit x86 which RIOT native is built for.
+    pub u64_align: usize,
+    pub big_endian: bool,
+}
+
+pub const TARGET_ARCHS: &[TargetArch] = &[
+    TargetArch {
+        name: "native",
+        word_size: 4,
+        u64_align: 4,
+        big_endian: false,
+    },
+    TargetArch {
+        name: "native64",
+        word_size: 8,
+        u64_align: 8,
+        big_endian: false,
+    },
+    TargetArch {
+        name: "cortex-m",
+        word_size: 4,
+        u64_align: 8,
+        big_endian: false,
+    },
+    TargetArch {
+        name: "riscv32",
+        word_size: 4,
+        u64_align: 8,
+        big_endian: false,
+    },
+];
+
+impl TargetArch {
+    pub fn find(name: &str) -> Option<&'static TargetArch> {
+        TARGET_ARCHS.iter().find(|arch| arch.name == name)
+    }
+}
+
+/// Type of a field of the context struct.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextFieldType {
+    U8,
+    U16,
+    U32,
+    U64,
+    /// `size_t`, its size depends on the word size of the target.
+    Size,
+    /// Pointer widened to 64 bits (see `__bpf_shared_ptr` in `shared.h`) so
+    /// that it has the same size on the device and in the eBPF program. The
+    /// string is the C type of the pointer as seen by the program.
+    SharedPtr(&'static str),
+}
+
+impl ContextFieldType {
+    /// Size and alignment of the field on the target.
+    pub fn size_align(&self, arch: &TargetArch) -> (usize, usize) {
+        match self {
+            ContextFieldType::U8 => (1, 1),
+            ContextFieldType::U16 => (2, 2),
+            ContextFieldType::U32 => (4, 4),
+            ContextFieldType::U64 => (8, arch.u64_align),
+            ContextFieldType::Size => (arch.word_size, arch.word_size),
+            ContextFieldType::SharedPtr(_) => (8, 8),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextField {
+    pub name: &'static str,
+    pub field_type: ContextFieldType,
+    pub description: &'static str,
+}
+
+/// Layout of the context struct that the VM passes to the program in r1.
+/// This is the single definition that both the firmware and the generated
+/// C headers used by the programs are derived from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextLayout {
+    /// Name of the C type, e.g. `bpf_coap_ctx_t`.
+    pub name: &'static str,
+    pub fields: &'static [ContextField],
+}
+
+pub const COAP_CONTEXT: ContextLayout = ContextLayout {
+    name: "bpf_coap_ctx_t",
+    fields: &[
+        ContextField {
+            name: "pkt",
+            field_type: ContextFieldType::SharedPtr("void *"),
+            description: "Opaque pointer to the coap_pkt_t struct",
+        },
+        ContextField {
+            name: "buf",
+            field_type: ContextFieldType::SharedPtr("uint8_t *"),
+            description: "Packet buffer",
+        },
+        ContextField {
+            name: "buf_len",
+            field_type: ContextFieldType::Size,
+            description: "Packet buffer length",
+        },
+    ],
+};
+
+impl ContextLayout {
+    /// Byte offsets of the fields on the target, following the C rules of
+    /// laying out the struct members.
+    pub fn offsets(&self, arch: &TargetArch) -> Vec<usize> {
+        let mut offset = 0;
+        self.fields
+            .iter()
+            .map(|field| {
+                let (size, align) = field.field_type.size_align(arch);
+                offset = offset.next_multiple_of(align);
+                let field_offset = offset;
+                offset += size;
+                field_offset
+            })
+            .collect()
+    }
+
+    /// Size of the whole struct on the target including the trailing padding.
+    pub fn size(&self, arch: &TargetArch) -> usize {
+        let align = self
+            .fields
+            .iter()
+            .map(|field| field.field_type.size_align(arch).1)
+            .max()
+            .unwrap_or(1);
+        let end = self
+            .fields
+            .iter()
+            .zip(self.offsets(arch))
+            .map(|(field, offset)| offset + field.field_type.size_align(arch).0)
+            .max()
+            .unwrap_or(0);
+        end.next_multiple_of(align)
+    }
+}
+
+impl ExecutionModel {
+    /// Layout of the context passed to the programs executed using the model,
+    /// None if the programs don't get any context.
+    pub fn context_layout(&self) -> Option<ContextLayout> {
+        match self {
+            ExecutionModel::WithAccessToCoapPacket => Some(COAP_CONTEXT),
+            ExecutionModel::ShortLived | ExecutionModel::LongRunning => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_t_follows_the_word_size_of_the_target() {
+        let native = TargetArch::find("native").unwrap();
+        let native64 = TargetArch::find("native64").unwrap();
+
+        assert_eq!(COAP_CONTEXT.offsets(native), [0, 8, 16]);
+        assert_eq!(COAP_CONTEXT.size(native), 24);
+        assert_eq!(COAP_CONTEXT.offsets(native64), [0, 8, 16]);
+        assert_eq!(COAP_CONTEXT.size(native64), 24);
+    }
+}
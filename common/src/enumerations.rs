@@ -34,6 +34,9 @@ pub struct VMConfiguration {
     /// Controlls whether the jitted program is to be compiled preflight
     /// or loaded from jit program storage.
     pub jit_compile: bool,
+    /// Storage backend holding the SUIT storage slot of the program.
+    #[serde(default)]
+    pub storage: StorageBackend,
 }
 
 impl VMConfiguration {
@@ -54,9 +57,15 @@ impl VMConfiguration {
             helper_access_list_source,
             jit,
             jit_compile,
+            storage: StorageBackend::Ram,
         }
     }
 
+    pub fn with_storage(mut self, storage: StorageBackend) -> Self {
+        self.storage = storage;
+        self
+    }
+
     /// Encodes the VM configuration into a u8. The reason we need this is that
     /// RIOT message passing IPC infrastructure limits the size of the transported
     /// messages to 32 bits. In order to fully specify a given VM execution,
@@ -81,6 +90,8 @@ impl VMConfiguration {
     /// - bit 10: The next bit specifies whether we should use jit-compiled programs.
     /// - bit 11: The next bit specifies if we should run the jit-compilation or
     ///   use one of the pre-compiled programs that are present in the jit storage.
+    /// - bit 12: The next bit specifies the storage backend of the SUIT storage
+    ///   slot, 0 corresponds to the RAM regions and 1 to the flash regions.
     ///
     /// # Example
     /// ```
@@ -107,6 +118,7 @@ impl VMConfiguration {
         encoding |= (self.helper_access_list_source as u16 & 0b1) << 9;
         encoding |= (self.jit as u16 & 0b1) << 10;
         encoding |= (self.jit_compile as u16 & 0b1) << 11;
+        encoding |= (self.storage as u16 & 0b1) << 12;
         encoding
     }
 
@@ -122,6 +134,7 @@ impl VMConfiguration {
             helper_access_list_source: HelperAccessListSource::from(((encoding >> 9) & 0b1) as u8),
             jit: ((encoding >> 10) & 0b1) == 1,
             jit_compile: ((encoding >> 11) & 0b1) == 1,
+            storage: StorageBackend::from(((encoding >> 12) & 0b1) as u8),
         }
     }
 }
@@ -208,6 +221,105 @@ impl FromStr for BinaryFileLayout {
     }
 }
 
+/// Sizes of the structures written by the post-processing step (see the
+/// micro-bpf-elf-utils crate), needed to tell the two header layouts apart.
+const FC_HEADER_SIZE: usize = 28;
+const EXTENDED_HEADER_SIZE: usize = 32;
+const SYMBOL_SIZE: usize = 6;
+const RELOCATED_CALL_SIZE: usize = 8;
+const HEADER_MAGIC: u32 = 123;
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const INSTRUCTION_SIZE: usize = 8;
+
+impl BinaryFileLayout {
+    /// Identifies the layout of an artifact by inspecting its contents.
+    ///
+    /// The object files are recognised by the ELF magic number. Both header
+    /// layouts start with the same magic number and section lengths, the
+    /// Femto-Containers binary ends right after the function symbols whereas
+    /// the extended header is followed by the relocated calls, the allowed
+    /// helpers and the optional metadata. Anything else that consists of
+    /// whole instructions is assumed to be a bare .text section. Returns None
+    /// if the layout can't be determined.
+    pub fn detect(bytes: &[u8]) -> Option<BinaryFileLayout> {
+        if bytes.starts_with(ELF_MAGIC) {
+            return Some(BinaryFileLayout::RawObjectFile);
+        }
+
+        let read_u32 = |offset: usize| -> Option<usize> {
+            let word = bytes.get(offset..offset + 4)?;
+            Some(u32::from_le_bytes(word.try_into().ok()?) as usize)
+        };
+        if bytes.len() >= FC_HEADER_SIZE && read_u32(0) == Some(HEADER_MAGIC as usize) {
+            let sections = read_u32(12)? + read_u32(16)? + read_u32(20)?;
+            let symbols = read_u32(24)? * SYMBOL_SIZE;
+            if FC_HEADER_SIZE + sections + symbols == bytes.len() {
+                return Some(BinaryFileLayout::FemtoContainersHeader);
+            }
+            let calls = read_u32(28)? * RELOCATED_CALL_SIZE;
+            if EXTENDED_HEADER_SIZE + sections + symbols + calls <= bytes.len() {
+                return Some(BinaryFileLayout::ExtendedHeader);
+            }
+        }
+
+        if !bytes.is_empty() && bytes.len() % INSTRUCTION_SIZE == 0 {
+            return Some(BinaryFileLayout::OnlyTextSection);
+        }
+        None
+    }
+}
+
+/// Storage backends of the SUIT storage slots supported by the firmware. The
+/// programs in the RAM regions are lost when the device reboots, the ones in
+/// the flash regions survive it at the cost of slower writes and a limited
+/// number of erase cycles.
+#[repr(u8)]
+#[derive(Eq, PartialEq, Debug, Deserialize, Serialize, Copy, Clone, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    #[default]
+    Ram = 0,
+    Flash = 1,
+}
+
+impl StorageBackend {
+    /// Identifier of the SUIT manifest component installed into the slot of
+    /// this backend, the parts are separated by ':'.
+    pub fn component_id(&self, slot: usize) -> String {
+        format!("{}:{}", self, slot)
+    }
+}
+
+impl fmt::Display for StorageBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StorageBackend::Ram => write!(f, "ram"),
+            StorageBackend::Flash => write!(f, "flash"),
+        }
+    }
+}
+
+impl FromStr for StorageBackend {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ram" => Ok(StorageBackend::Ram),
+            "flash" => Ok(StorageBackend::Flash),
+            _ => Err(format!("Unknown storage backend: {}", s)),
+        }
+    }
+}
+
+impl From<u8> for StorageBackend {
+    fn from(val: u8) -> Self {
+        match val {
+            0 => StorageBackend::Ram,
+            1 => StorageBackend::Flash,
+            _ => panic!("Unknown storage backend: {}", val),
+        }
+    }
+}
+
 impl From<u8> for BinaryFileLayout {
     fn from(val: u8) -> Self {
         match val {
@@ -220,7 +332,7 @@ impl From<u8> for BinaryFileLayout {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ExecutionModel {
     /// The VM instance is spawned in the thread that is handling the network
     /// request to execute the VM, the programs running using this model should be
@@ -331,6 +443,53 @@ impl From<u8> for HelperAccessListSource {
     }
 }
 
+/// Hooks of the network stack of the device that a deployed program can be
+/// attached to. Instead of being executed on request, the attached program
+/// runs on every packet passing through the hook with the packet as its
+/// context, the packet is dropped if the program returns 0.
+#[derive(Eq, PartialEq, Debug, Deserialize, Serialize, Copy, Clone, Sequence)]
+pub enum NetworkHook {
+    /// Packets received by the GNRC network interface, before they are
+    /// passed to the network layer.
+    #[serde(rename = "netif-rx")]
+    NetifRx,
+    /// Packets handed to the GNRC network interface for sending.
+    #[serde(rename = "netif-tx")]
+    NetifTx,
+}
+
+impl NetworkHook {
+    /// Name of the hook used in the resources of the device, e.g.
+    /// `/hooks/netif-rx/attach`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            NetworkHook::NetifRx => "netif-rx",
+            NetworkHook::NetifTx => "netif-tx",
+        }
+    }
+}
+
+impl FromStr for NetworkHook {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "netif-rx" => Ok(NetworkHook::NetifRx),
+            "netif-tx" => Ok(NetworkHook::NetifTx),
+            _ => Err(format!(
+                "Unknown network hook: {}, expected netif-rx or netif-tx",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for NetworkHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -351,6 +510,60 @@ mod tests {
         let decoded = VMConfiguration::decode(encoded);
 
         assert_eq!(configuration, decoded);
+
+        let configuration = configuration.with_storage(StorageBackend::Flash);
+        assert_eq!(
+            configuration,
+            VMConfiguration::decode(configuration.encode())
+        );
+    }
+
+    #[test]
+    fn binary_layout_is_detected_from_the_header() {
+        let header = |words: &[u32]| {
+            words
+                .iter()
+                .flat_map(|word| word.to_le_bytes())
+                .collect::<alloc::vec::Vec<u8>>()
+        };
+        let text = [0x95, 0, 0, 0, 0, 0, 0, 0];
+
+        // magic, version, flags, data, rodata, text, functions
+        let mut femto_containers = header(&[123, 0, 0, 0, 0, 8, 1]);
+        femto_containers.extend(text);
+        femto_containers.extend([0; SYMBOL_SIZE]);
+        assert_eq!(
+            BinaryFileLayout::detect(&femto_containers),
+            Some(BinaryFileLayout::FemtoContainersHeader)
+        );
+
+        // ... followed by the number of relocated calls and the helpers.
+        let mut extended = header(&[123, 0, 0, 0, 0, 8, 1, 0]);
+        extended.extend(text);
+        extended.extend([0; SYMBOL_SIZE]);
+        extended.extend([1, 2, 3]);
+        assert_eq!(
+            BinaryFileLayout::detect(&extended),
+            Some(BinaryFileLayout::ExtendedHeader)
+        );
+
+        assert_eq!(
+            BinaryFileLayout::detect(b"\x7fELF\x02\x01\x01"),
+            Some(BinaryFileLayout::RawObjectFile)
+        );
+        assert_eq!(
+            BinaryFileLayout::detect(&text),
+            Some(BinaryFileLayout::OnlyTextSection)
+        );
+        assert_eq!(BinaryFileLayout::detect(&[1, 2, 3]), None);
+    }
+
+    #[test]
+    fn network_hooks_parse_from_their_names() {
+        for hook in enum_iterator::all::<NetworkHook>() {
+            assert_eq!(NetworkHook::from_str(hook.name()), Ok(hook));
+        }
+        assert!(NetworkHook::from_str("NetifRx").is_err());
     }
 }
 
@@ -361,6 +574,10 @@ mod tests {
 ///
 /// In case of the helper functions that were implemented for the FemtoContainer
 /// VM, we use the same set of IDs for compatibility.
+///
+/// The helper bitmap of the execution requests (see
+/// [`crate::encode_helper_bitmap`]) identifies the helpers by their IDs, so
+/// the ID of a removed helper mustn't be reused.
 #[allow(non_camel_case_types)]
 #[derive(Debug, Copy, Clone, Sequence, FromPrimitive, PartialEq, Eq, PartialOrd, Ord)]
 pub enum HelperFunctionID {
@@ -412,6 +629,9 @@ pub enum HelperFunctionID {
     BPF_HD44780_PRINT = 0x82,
     BPF_HD44780_SET_CURSOR = 0x83,
     BPF_KEYPAD_GET_INPUT = 0x84,
+
+    /* Return values */
+    BPF_SET_RETURN_BUFFER_IDX = 0x90,
 }
 
 impl Into<u32> for HelperFunctionID {
@@ -3,9 +3,13 @@
 extern crate alloc;
 extern crate num;
 extern crate num_derive;
+mod context;
 mod enumerations;
 mod requests;
+mod responses;
 
 
+pub use context::*;
 pub use enumerations::*;
 pub use requests::*;
+pub use responses::*;
@@ -47,6 +47,55 @@ pub struct VMExecutionRequest {
     /// represented by u8 values, which means that currently we can have up to
     /// 256 helper functions.
     pub allowed_helpers: Vec<HelperFunctionID>,
+    /// Maximum number of instructions that the program is allowed to execute,
+    /// the VM stops the program once the limit is exhausted. Unlimited if None.
+    pub gas_limit: Option<u64>,
+    /// Handles of the keys in the keystore of the device that the program
+    /// uses with the crypto helpers. They are passed to the program as the
+    /// execution arguments, in the given order.
+    pub key_handles: Vec<u32>,
+    /// Seed of the random number generator that the program draws from
+    /// during the execution. If None, the device picks the seed itself and
+    /// reports it in the response.
+    pub seed: Option<u64>,
+    /// Asks the device to record each helper call made by the program (see
+    /// [`crate::HelperCall`]) and include the log in the response.
+    pub trace_helpers: bool,
+    /// Describes the arguments buffer which follows the encoded request in
+    /// the payload, see [`ArgumentsEncoding`].
+    pub arguments: Option<ArgumentsEncoding>,
+    /// Offset (in instructions) from the start of the .text section of the
+    /// function that the VM starts executing, the first instruction if None.
+    /// Only supported by the devices reporting
+    /// [`crate::CAPABILITY_MULTI_ENTRY`].
+    pub entry: Option<u32>,
+    /// Encode the allowed helpers as a bitmap (see [`encode_helper_bitmap`])
+    /// instead of the list of their IDs. Only supported by the devices
+    /// reporting [`crate::CAPABILITY_HELPER_BITMAP`].
+    pub helper_bitmap: bool,
+}
+
+/// Content-coding of the execution arguments, the devices only accept the
+/// compressed arguments if they report
+/// [`crate::CAPABILITY_ARGUMENT_COMPRESSION`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ContentCoding {
+    Identity,
+    /// Raw deflate stream (RFC 1951).
+    Deflate,
+}
+
+/// The execution arguments (e.g. the weights of a TinyML model) follow the
+/// encoded request in the payload of the execution request, separated by a
+/// newline. They are a CBOR byte string, optionally compressed using the
+/// content-coding, which the device decodes before passing the bytes to the
+/// program.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArgumentsEncoding {
+    pub coding: ContentCoding,
+    /// Length of the CBOR byte string once the content-coding is removed,
+    /// it allows the device to allocate the buffer up front.
+    pub length: u32,
 }
 
 impl VMExecutionRequest {
@@ -54,9 +103,51 @@ impl VMExecutionRequest {
         VMExecutionRequest {
             configuration,
             allowed_helpers,
+            gas_limit: None,
+            key_handles: Vec::new(),
+            seed: None,
+            trace_helpers: false,
+            arguments: None,
+            entry: None,
+            helper_bitmap: false,
         }
     }
 
+    pub fn with_gas_limit(mut self, gas_limit: Option<u64>) -> Self {
+        self.gas_limit = gas_limit;
+        self
+    }
+
+    pub fn with_key_handles(mut self, key_handles: Vec<u32>) -> Self {
+        self.key_handles = key_handles;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: Option<u64>) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_helper_tracing(mut self, trace_helpers: bool) -> Self {
+        self.trace_helpers = trace_helpers;
+        self
+    }
+
+    pub fn with_arguments(mut self, arguments: Option<ArgumentsEncoding>) -> Self {
+        self.arguments = arguments;
+        self
+    }
+
+    pub fn with_entry(mut self, entry: Option<u32>) -> Self {
+        self.entry = entry;
+        self
+    }
+
+    pub fn with_helper_bitmap(mut self, helper_bitmap: bool) -> Self {
+        self.helper_bitmap = helper_bitmap;
+        self
+    }
+
     /// Because of the request entity size constraints when sending the CoAP packets
     /// we need to encode the request into a compact message format to allow for
     /// specifying the highest possible number of helper functions.
@@ -69,17 +160,147 @@ impl VMExecutionRequest {
     /// 2 characters long). The first u8 is used for the VM configuration and
     /// the following 52 represent the vector of helper IDs that should be
     /// during the program execution
+    ///
+    /// If the helpers are encoded as a bitmap, the configuration is followed
+    /// by '*' and the hex-encoded bytes of the bitmap instead. Its length
+    /// depends on the highest allowed helper ID rather than on the number of
+    /// the helpers, e.g. 19 bytes when all helpers are allowed.
+    ///
+    /// The gas limit (if any) is appended at the end as a hex-encoded number
+    /// separated by '|', so that the requests without it stay the same.
+    /// The key handles (if any) follow after '#' as comma-separated hex numbers.
+    /// The seed (if any) follows as a hex-encoded number after '@'.
+    /// The arguments (if any) are described after '&' by their content-coding
+    /// ('i' for identity, 'd' for deflate) and the hex-encoded length.
+    /// The entry offset (if any) follows as a hex-encoded number after '^'.
+    /// Requests asking for the helper calls to be traced end with '~'.
     pub fn encode(&self) -> String {
         let mut encoding = format!("{:04x}", self.configuration.encode());
 
-        for helper in &self.allowed_helpers {
-            encoding.push_str(&format!("{:02x}", *helper as u8));
+        if self.helper_bitmap {
+            encoding.push('*');
+            for byte in encode_helper_bitmap(&self.allowed_helpers) {
+                encoding.push_str(&format!("{:02x}", byte));
+            }
+        } else {
+            for helper in &self.allowed_helpers {
+                encoding.push_str(&format!("{:02x}", *helper as u8));
+            }
+        }
+
+        if let Some(gas_limit) = self.gas_limit {
+            encoding.push_str(&format!("|{:x}", gas_limit));
+        }
+
+        if !self.key_handles.is_empty() {
+            let handles = self
+                .key_handles
+                .iter()
+                .map(|handle| format!("{:x}", handle))
+                .collect::<Vec<String>>();
+            encoding.push_str(&format!("#{}", handles.join(",")));
+        }
+
+        if let Some(seed) = self.seed {
+            encoding.push_str(&format!("@{:x}", seed));
+        }
+
+        if let Some(arguments) = self.arguments {
+            let coding = match arguments.coding {
+                ContentCoding::Identity => 'i',
+                ContentCoding::Deflate => 'd',
+            };
+            encoding.push_str(&format!("&{}{:x}", coding, arguments.length));
+        }
+
+        if let Some(entry) = self.entry {
+            encoding.push_str(&format!("^{:x}", entry));
+        }
+
+        if self.trace_helpers {
+            encoding.push('~');
         }
 
         encoding
     }
 
     pub fn decode(data: String) -> Result<VMExecutionRequest, String> {
+        let (data, trace_helpers) = match data.strip_suffix('~') {
+            Some(data) => (data.to_string(), true),
+            None => (data, false),
+        };
+
+        let (data, entry) = match data.split_once('^') {
+            Some((data, entry)) => (
+                data.to_string(),
+                Some(
+                    u32::from_str_radix(entry, 16)
+                        .map_err(|e| format!("Unable to parse the entry offset: {}", e))?,
+                ),
+            ),
+            None => (data, None),
+        };
+
+        let (data, arguments) = match data.split_once('&') {
+            Some((data, arguments)) => {
+                let coding = match arguments.chars().next() {
+                    Some('i') => ContentCoding::Identity,
+                    Some('d') => ContentCoding::Deflate,
+                    _ => return Err(format!("Unknown arguments coding: {}", arguments)),
+                };
+                let length = u32::from_str_radix(&arguments[1..], 16)
+                    .map_err(|e| format!("Unable to parse the arguments length: {}", e))?;
+                (data.to_string(), Some(ArgumentsEncoding { coding, length }))
+            }
+            None => (data, None),
+        };
+
+        let (data, seed) = match data.split_once('@') {
+            Some((data, seed)) => (
+                data.to_string(),
+                Some(
+                    u64::from_str_radix(seed, 16)
+                        .map_err(|e| format!("Unable to parse the seed: {}", e))?,
+                ),
+            ),
+            None => (data, None),
+        };
+
+        let (data, key_handles) = match data.split_once('#') {
+            Some((data, handles)) => (
+                data.to_string(),
+                handles
+                    .split(',')
+                    .map(|handle| u32::from_str_radix(handle, 16))
+                    .collect::<Result<Vec<u32>, ParseIntError>>()
+                    .map_err(|e| format!("Unable to parse the key handles: {}", e))?,
+            ),
+            None => (data, Vec::new()),
+        };
+
+        let (data, gas_limit) = match data.split_once('|') {
+            Some((data, gas_limit)) => (
+                data.to_string(),
+                Some(
+                    u64::from_str_radix(gas_limit, 16)
+                        .map_err(|e| format!("Unable to parse the gas limit: {}", e))?,
+                ),
+            ),
+            None => (data, None),
+        };
+
+        let (data, helper_bitmap) = match data.split_once('*') {
+            Some((configuration, _)) if configuration.len() != 4 => {
+                return Err(format!("Invalid execution request encoding: {}", data))
+            }
+            Some((configuration, bitmap)) => (format!("{}{}", configuration, bitmap), true),
+            None => (data, false),
+        };
+
+        if data.len() < 4 || data.len() % 2 != 0 || !data.is_ascii() {
+            return Err(format!("Invalid execution request encoding: {}", data));
+        }
+
         let encoded_configuration = u16::from_str_radix(&data[0..4], 16).map_err(|e| {
             format!(
                 "Unable to parse the vm configuration from the encoded string: {}",
@@ -95,18 +316,53 @@ impl VMExecutionRequest {
             .collect::<Result<Vec<u8>, ParseIntError>>()
             .map_err(|e| format!("Unable to parse: {}", e))?;
 
-        let allowed_helpers = allowed_helpers_ids
-            .into_iter()
-            .filter_map(|id| num::FromPrimitive::from_u8(id))
-            .collect();
+        let allowed_helpers = if helper_bitmap {
+            decode_helper_bitmap(&allowed_helpers_ids)
+        } else {
+            allowed_helpers_ids
+                .into_iter()
+                .filter_map(|id| num::FromPrimitive::from_u8(id))
+                .collect()
+        };
 
         Ok(VMExecutionRequest {
             configuration,
             allowed_helpers,
+            gas_limit,
+            key_handles,
+            seed,
+            trace_helpers,
+            arguments,
+            entry,
+            helper_bitmap,
         })
     }
 }
 
+/// Encodes the set of helpers as a bitmap, bit `i` (starting from the least
+/// significant bit of the first byte) is set if the helper with ID `i` (see
+/// [`HelperFunctionID`]) is allowed. The trailing zero bytes are omitted.
+pub fn encode_helper_bitmap(helpers: &[HelperFunctionID]) -> Vec<u8> {
+    let mut bitmap = Vec::new();
+    for helper in helpers {
+        let id = *helper as usize;
+        if bitmap.len() <= id / 8 {
+            bitmap.resize(id / 8 + 1, 0);
+        }
+        bitmap[id / 8] |= 1 << (id % 8);
+    }
+    bitmap
+}
+
+/// Inverse of [`encode_helper_bitmap`], the helpers are returned in the
+/// order of their IDs. The bits of the unknown helper IDs are ignored.
+pub fn decode_helper_bitmap(bitmap: &[u8]) -> Vec<HelperFunctionID> {
+    (0..bitmap.len() * 8)
+        .filter(|id| bitmap[id / 8] & (1 << (id % 8)) != 0)
+        .filter_map(num::FromPrimitive::from_usize)
+        .collect()
+}
+
 /// Models the request that is sent to the target device to pull a specified
 /// binary file from the CoAP fileserver.
 /// The handler expects to get a request which consists of the IPv6 address of
@@ -181,3 +437,108 @@ impl SuitPullRequest {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use enum_iterator::all;
+
+    use super::*;
+    use crate::{BinaryFileLayout, HelperAccessListSource, HelperAccessVerification, TargetVM};
+
+    #[test]
+    fn execution_request_round_trips_with_all_trailers() {
+        let configuration = VMConfiguration::new(
+            TargetVM::Rbpf,
+            1,
+            BinaryFileLayout::ExtendedHeader,
+            HelperAccessVerification::AheadOfTime,
+            HelperAccessListSource::ExecuteRequest,
+            false,
+            false,
+        );
+        let request =
+            VMExecutionRequest::new(configuration, vec![HelperFunctionID::BPF_PRINTF_IDX])
+                .with_gas_limit(Some(1000))
+                .with_key_handles(vec![3, 17])
+                .with_seed(Some(0xdeadbeef))
+                .with_arguments(Some(ArgumentsEncoding {
+                    coding: ContentCoding::Deflate,
+                    length: 4096,
+                }))
+                .with_entry(Some(0x2a))
+                .with_helper_tracing(true);
+
+        let decoded = VMExecutionRequest::decode(request.encode()).unwrap();
+        assert_eq!(decoded.configuration, configuration);
+        assert_eq!(decoded.allowed_helpers, request.allowed_helpers);
+        assert_eq!(decoded.gas_limit, Some(1000));
+        assert_eq!(decoded.key_handles, vec![3, 17]);
+        assert_eq!(decoded.seed, Some(0xdeadbeef));
+        assert_eq!(decoded.arguments, request.arguments);
+        assert_eq!(decoded.entry, Some(0x2a));
+        assert!(decoded.trace_helpers);
+    }
+
+    #[test]
+    fn truncated_execution_request_is_rejected() {
+        assert!(VMExecutionRequest::decode("".to_string()).is_err());
+        assert!(VMExecutionRequest::decode("001".to_string()).is_err());
+        assert!(VMExecutionRequest::decode("00010".to_string()).is_err());
+        assert!(VMExecutionRequest::decode("0001|".to_string()).is_err());
+        assert!(VMExecutionRequest::decode("0001&x10".to_string()).is_err());
+        assert!(VMExecutionRequest::decode("0001^".to_string()).is_err());
+        assert!(VMExecutionRequest::decode("00*01".to_string()).is_err());
+        assert!(VMExecutionRequest::decode("0001*0".to_string()).is_err());
+    }
+
+    #[test]
+    fn allowed_helpers_round_trip_in_both_encodings() {
+        let configuration = VMConfiguration::new(
+            TargetVM::Rbpf,
+            0,
+            BinaryFileLayout::ExtendedHeader,
+            HelperAccessVerification::Runtime,
+            HelperAccessListSource::ExecuteRequest,
+            false,
+            false,
+        );
+        // The bitmap is decoded in the order of the helper IDs.
+        let mut all_helpers = all::<HelperFunctionID>().collect::<Vec<_>>();
+        all_helpers.sort();
+        let some_helpers = vec![
+            HelperFunctionID::BPF_PRINTF_IDX,
+            HelperFunctionID::BPF_STORE_GLOBAL_IDX,
+        ];
+
+        for helpers in [vec![], some_helpers, all_helpers.clone()] {
+            let list =
+                VMExecutionRequest::new(configuration, helpers.clone()).with_gas_limit(Some(7));
+            let bitmap = list.clone().with_helper_bitmap(true);
+            for request in [list, bitmap] {
+                let decoded = VMExecutionRequest::decode(request.encode()).unwrap();
+                assert_eq!(decoded.allowed_helpers, helpers);
+                assert_eq!(decoded.helper_bitmap, request.helper_bitmap);
+                assert_eq!(decoded.gas_limit, Some(7));
+            }
+        }
+
+        let request = VMExecutionRequest::new(configuration, all_helpers.clone());
+        let bitmap = request.clone().with_helper_bitmap(true).encode();
+        let highest_id = *all_helpers.last().unwrap() as usize;
+        assert_eq!(bitmap.len(), 4 + 1 + 2 * (highest_id / 8 + 1));
+        assert!(bitmap.len() < request.encode().len());
+    }
+
+    #[test]
+    fn helper_bitmap_is_indexed_by_helper_id() {
+        let helpers = [
+            HelperFunctionID::BPF_PRINTF_IDX,
+            HelperFunctionID::BPF_STORE_GLOBAL_IDX,
+        ];
+        assert_eq!(encode_helper_bitmap(&helpers), vec![0x02, 0x00, 0x02]);
+        assert_eq!(decode_helper_bitmap(&[0x02, 0x00, 0x02]), helpers);
+        // ID 0 isn't assigned to any helper.
+        assert_eq!(decode_helper_bitmap(&[0x01]), vec![]);
+    }
+}
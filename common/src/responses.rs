@@ -0,0 +1,366 @@
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::TargetVM;
+
+/// Maximum size of the buffer that a program can return using the
+/// `bpf_set_return_buffer` helper (see [`crate::HelperFunctionID`]). The
+/// buffer is sent back hex-encoded, so this needs to fit into a single CoAP
+/// response together with the rest of the execution response.
+pub const MAX_RETURN_BUFFER_SIZE: usize = 256;
+
+/// Response sent by the device once the execution of a program has finished.
+///
+/// # Example
+/// The programs returning a single value produce:
+/// `{"execution_time": 10, "result": 0}`
+/// whereas the ones which have set a return buffer produce:
+/// `{"execution_time": 10, "result": 0, "buffer": {"len": 4, "data": "0a000b00"}}`
+/// If the request specified a gas limit, the number of executed instructions
+/// is included as well: `{"execution_time": 10, "result": 0, "instructions": 42}`
+/// The programs drawing random numbers also report the seed of the generator:
+/// `{"execution_time": 10, "result": 7, "seed": 1234}`
+/// If the request asked for the helper calls to be traced, they are listed
+/// in the order they were made:
+/// `{"execution_time": 10, "result": 0, "trace": [{"id": 6, "args": [1, 0, 0, 0, 0], "ret": 0}]}`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionResponse {
+    pub execution_time: u32,
+    /// Return value of the program.
+    pub result: i64,
+    /// Buffer set by the program using the `bpf_set_return_buffer` helper.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buffer: Option<ReturnBuffer>,
+    /// Number of instructions executed by the VM, it doesn't depend on the
+    /// clock frequency of the device as opposed to the execution time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<u64>,
+    /// Seed of the random number generator used in the execution, passing it
+    /// in the next request reproduces the execution exactly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Helper calls made by the program, only reported if the request asked
+    /// for them to be traced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace: Option<Vec<HelperCall>>,
+    /// Content-Format that the device used for the response, it is filled in
+    /// by the client and never sent by the device.
+    #[serde(default, skip_serializing_if = "ContentFormat::is_json")]
+    pub content_format: ContentFormat,
+}
+
+/// Single invocation of a helper function recorded by the device while
+/// tracing the execution.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HelperCall {
+    /// ID of the helper, see [`crate::HelperFunctionID`].
+    pub id: u8,
+    /// Values of the argument registers r1-r5 at the time of the call.
+    pub args: Vec<u64>,
+    /// Value returned by the helper in r0.
+    pub ret: u64,
+}
+
+/// Content-Formats of the execution responses that the client can accept,
+/// the values are the ones from the CoAP Content-Formats registry. The client
+/// advertises them using the Accept option so that the firmware can change
+/// the wire format without breaking the older versions of the tool.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContentFormat {
+    #[default]
+    #[serde(rename = "json")]
+    Json = 50,
+    #[serde(rename = "cbor")]
+    Cbor = 60,
+    /// The fixed-size fields followed by the returned buffer, see
+    /// [`ExecutionResponse::decode_octet_stream`].
+    #[serde(rename = "octet-stream")]
+    OctetStream = 42,
+}
+
+impl ContentFormat {
+    pub fn is_json(&self) -> bool {
+        *self == ContentFormat::Json
+    }
+}
+
+impl FromStr for ContentFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ContentFormat::Json),
+            "cbor" => Ok(ContentFormat::Cbor),
+            "octet-stream" => Ok(ContentFormat::OctetStream),
+            _ => Err(format!("Unknown content format: {}", s)),
+        }
+    }
+}
+
+/// Variable-size output of a program (e.g. the output of an FFT) which
+/// doesn't fit into a single return value.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReturnBuffer {
+    /// Length of the buffer in bytes.
+    pub len: usize,
+    /// Contents of the buffer, hex-encoded.
+    pub data: String,
+}
+
+impl ReturnBuffer {
+    pub fn new(bytes: &[u8]) -> Self {
+        ReturnBuffer {
+            len: bytes.len(),
+            data: bytes.iter().map(|byte| format!("{:02x}", byte)).collect(),
+        }
+    }
+
+    /// Decodes the raw bytes of the buffer.
+    pub fn bytes(&self) -> Result<Vec<u8>, String> {
+        if self.data.len() != 2 * self.len || !self.data.is_ascii() {
+            return Err(format!(
+                "The buffer data doesn't match its length of {} bytes",
+                self.len
+            ));
+        }
+        (0..self.data.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&self.data[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|e| format!("Unable to parse the buffer: {}", e))
+    }
+
+    /// Decodes the buffer as an array of little-endian values, e.g.
+    /// `buffer.decode::<i16>()` for a program returning `int16_t[]`.
+    pub fn decode<T: BufferElement>(&self) -> Result<Vec<T>, String> {
+        let bytes = self.bytes()?;
+        if bytes.len() % T::SIZE != 0 {
+            return Err(format!(
+                "The buffer length {} isn't a multiple of the element size {}",
+                bytes.len(),
+                T::SIZE
+            ));
+        }
+        Ok(bytes.chunks_exact(T::SIZE).map(T::from_le_slice).collect())
+    }
+}
+
+/// Element type of the arrays that the return buffers can be decoded into.
+pub trait BufferElement: Sized {
+    const SIZE: usize;
+    fn from_le_slice(bytes: &[u8]) -> Self;
+}
+
+macro_rules! buffer_elements {
+    ($($t:ty),*) => {
+        $(
+            impl BufferElement for $t {
+                const SIZE: usize = core::mem::size_of::<$t>();
+                fn from_le_slice(bytes: &[u8]) -> Self {
+                    <$t>::from_le_bytes(bytes.try_into().unwrap())
+                }
+            }
+        )*
+    };
+}
+
+buffer_elements!(u8, i8, u16, i16, u32, i32, u64, i64);
+
+impl ExecutionResponse {
+    pub fn decode(data: &str) -> Result<ExecutionResponse, String> {
+        serde_json_core::from_str::<ExecutionResponse>(data)
+            .map(|(response, _)| response)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Decodes the compact binary response: the execution time (u32) and the
+    /// result (i64), both little-endian, followed by the returned buffer if
+    /// the program has set one.
+    pub fn decode_octet_stream(data: &[u8]) -> Result<ExecutionResponse, String> {
+        if data.len() < 12 {
+            return Err(format!(
+                "The binary response is too short: {} bytes",
+                data.len()
+            ));
+        }
+        let (execution_time, rest) = data.split_at(4);
+        let (result, buffer) = rest.split_at(8);
+        Ok(ExecutionResponse {
+            execution_time: u32::from_le_slice(execution_time),
+            result: i64::from_le_slice(result),
+            buffer: (!buffer.is_empty()).then(|| ReturnBuffer::new(buffer)),
+            instructions: None,
+            seed: None,
+            trace: None,
+            content_format: ContentFormat::OctetStream,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffer_decodes_into_typed_array() {
+        let values: [i16; 3] = [-2, 10, 300];
+        let bytes = values
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<u8>>();
+        let buffer = ReturnBuffer::new(&bytes);
+        assert_eq!(buffer.decode::<i16>().unwrap(), values);
+        assert!(buffer.decode::<u32>().is_err());
+    }
+
+    #[test]
+    fn octet_stream_response_decodes_fields_and_buffer() {
+        let mut data = 10u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&(-3i64).to_le_bytes());
+        data.extend_from_slice(&[0x0a, 0x0b]);
+
+        let response = ExecutionResponse::decode_octet_stream(&data).unwrap();
+        assert_eq!(response.execution_time, 10);
+        assert_eq!(response.result, -3);
+        assert_eq!(response.buffer, Some(ReturnBuffer::new(&[0x0a, 0x0b])));
+        assert_eq!(response.content_format, ContentFormat::OctetStream);
+        assert!(ExecutionResponse::decode_octet_stream(&data[..8]).is_err());
+    }
+
+    #[test]
+    fn device_info_lists_available_vms() {
+        let info = DeviceInfoResponse {
+            vms: alloc::vec!["rBPF".to_string(), "Unknown".to_string()],
+            ..Default::default()
+        };
+        assert!(info.has_vm(TargetVM::Rbpf));
+        assert!(!info.has_vm(TargetVM::FemtoContainer));
+    }
+}
+
+/// The firmware is able to decompress the .rodata section of the binaries
+/// using the `ExtendedHeader` layout, see `micro_bpf_elf_utils::compress_rodata`.
+pub const CAPABILITY_RODATA_COMPRESSION: u32 = 0x1;
+
+/// The firmware inflates the execution arguments compressed using
+/// [`crate::ContentCoding::Deflate`] before passing them to the program. The
+/// arguments sent to the devices without it are left uncompressed.
+pub const CAPABILITY_ARGUMENT_COMPRESSION: u32 = 0x2;
+
+/// The firmware starts executing the program from the entry offset given in
+/// the execution request (see [`crate::VMExecutionRequest::entry`]), which
+/// allows a single binary to expose multiple entry functions.
+pub const CAPABILITY_MULTI_ENTRY: u32 = 0x4;
+
+/// The SUIT worker of the firmware decompresses the payloads compressed
+/// using zlib while fetching them (see the compression info of the manifest).
+pub const CAPABILITY_ARTIFACT_DEFLATE: u32 = 0x8;
+
+/// Same as [`CAPABILITY_ARTIFACT_DEFLATE`] for the payloads compressed using
+/// zstd.
+pub const CAPABILITY_ARTIFACT_ZSTD: u32 = 0x10;
+
+/// The firmware is able to attach the deployed programs to the hooks of its
+/// network stack (see [`crate::NetworkHook`]).
+pub const CAPABILITY_NETWORK_HOOKS: u32 = 0x20;
+
+/// The firmware decodes the allowed helpers of the execution requests sent
+/// as a bitmap (see [`crate::VMExecutionRequest::helper_bitmap`]).
+pub const CAPABILITY_HELPER_BITMAP: u32 = 0x40;
+
+/// Optional features supported by the firmware, reported by the device in
+/// response to `GET /capabilities`.
+///
+/// # Example
+/// `{"flags": 1}`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CapabilitiesResponse {
+    /// Bitmask of the supported features, e.g. [`CAPABILITY_RODATA_COMPRESSION`].
+    pub flags: u32,
+}
+
+impl CapabilitiesResponse {
+    pub fn supports(&self, capability: u32) -> bool {
+        self.flags & capability == capability
+    }
+}
+
+/// Runtime information about the device, reported in response to
+/// `GET /info`.
+///
+/// # Example
+/// `{"firmware": "0.4.0", "riot": "2024.04", "vms": ["rBPF", "FemtoContainer"],
+/// "slots": [2048, 2048], "free_ram": 10240, "clock_hz": 180000000, "uptime": 3600}`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct DeviceInfoResponse {
+    /// Version of the micro-bpf firmware.
+    pub firmware: String,
+    /// Release of RIOT that the firmware was built against.
+    pub riot: String,
+    /// VMs compiled into the firmware, named the same as [`TargetVM`] (e.g.
+    /// "rBPF").
+    pub vms: Vec<String>,
+    /// Sizes of the SUIT storage slots in bytes.
+    pub slots: Vec<u32>,
+    /// Free heap memory in bytes.
+    pub free_ram: u32,
+    /// Frequency of the CPU core in Hz.
+    pub clock_hz: u32,
+    /// Seconds since the device booted.
+    pub uptime: u64,
+}
+
+impl DeviceInfoResponse {
+    /// Checks whether the firmware includes the VM, e.g. so that the tests
+    /// targeting the Femto-Containers VM can be skipped on devices without it.
+    pub fn has_vm(&self, vm: TargetVM) -> bool {
+        self.vms
+            .iter()
+            .any(|name| TargetVM::from_str(name).is_ok_and(|v| v == vm))
+    }
+}
+
+/// Statistics of the program attached to a network hook, collected by the
+/// device since the program was attached.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HookCounters {
+    /// Number of packets that the program was run on.
+    pub invocations: u64,
+    /// Packets that the program let through (returned a non-zero value).
+    pub accepted: u64,
+    /// Packets that the program dropped (returned 0).
+    pub dropped: u64,
+    /// Runs that the VM aborted, the packets are let through.
+    pub errors: u64,
+    /// Value returned by the most recent run of the program.
+    pub last_result: i64,
+}
+
+/// State of a network hook of the device, reported in response to
+/// `GET /hooks`.
+///
+/// # Example
+/// `{"hook": "netif-rx", "slot": 0, "counters": {"invocations": 120,
+/// "accepted": 118, "dropped": 2, "errors": 0, "last_result": 1},
+/// "storage": {"1": 42}}`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct HookInfo {
+    /// Name of the hook, e.g. "netif-rx".
+    pub hook: String,
+    /// SUIT storage slot of the attached program, none if the hook is free.
+    pub slot: Option<usize>,
+    #[serde(default)]
+    pub counters: HookCounters,
+    /// Values that the attached program wrote into its local storage using
+    /// `bpf_store_local`, which is how it reports results beyond the
+    /// counters (e.g. per-port packet counts).
+    #[serde(default)]
+    pub storage: BTreeMap<u32, u32>,
+}
@@ -0,0 +1,56 @@
+use alloc::string::{String, ToString};
+use micro_bpf_common::BinaryFileLayout;
+
+use crate::{
+    common::extract_section, extended_relocations::HEADER_SIZE,
+    femtocontainer_relocations::FC_HEADER_SIZE,
+};
+
+/// Extracts the bytecode of the program (i.e. the contents of its `.text`
+/// section) from a binary that was produced by the post-processing step for
+/// the given binary file layout.
+///
+/// This is the inverse operation of the post-processing and it allows for
+/// inspecting the artifacts after they have been generated, e.g. for comparing
+/// two compiled versions of the same program.
+pub fn extract_text_section<'a>(
+    program: &'a [u8],
+    layout: BinaryFileLayout,
+) -> Result<&'a [u8], String> {
+    match layout {
+        BinaryFileLayout::OnlyTextSection => Ok(program),
+        BinaryFileLayout::FemtoContainersHeader => text_after_header(program, FC_HEADER_SIZE),
+        BinaryFileLayout::ExtendedHeader => text_after_header(program, HEADER_SIZE),
+        BinaryFileLayout::RawObjectFile => extract_section(".text", program),
+    }
+}
+
+/// Both header layouts start with the same fields: magic, version, flags and
+/// the lengths of the .data, .rodata and .text sections. The sections are
+/// placed immediately after the header in that order.
+fn text_after_header(program: &[u8], header_size: usize) -> Result<&[u8], String> {
+    if program.len() < header_size {
+        return Err("The binary is too short to contain the header".to_string());
+    }
+
+    let read_u32 = |offset: usize| {
+        u32::from_le_bytes([
+            program[offset],
+            program[offset + 1],
+            program[offset + 2],
+            program[offset + 3],
+        ]) as usize
+    };
+
+    let data_len = read_u32(12);
+    let rodata_len = read_u32(16);
+    let text_len = read_u32(20);
+
+    let text_start = header_size + data_len + rodata_len;
+    let text_end = text_start + text_len;
+    if text_end > program.len() {
+        return Err("The section lengths in the header exceed the binary size".to_string());
+    }
+
+    Ok(&program[text_start..text_end])
+}
@@ -1,7 +1,9 @@
+use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use goblin::container::{Container, Endian};
 use goblin::elf::{Elf, Reloc, SectionHeader};
+use goblin::elf64::sym::STT_FUNC;
 use log::{debug, log_enabled, Level};
 
 pub const INSTRUCTION_SIZE: usize = 8;
@@ -10,6 +12,13 @@ const SYMBOL_SIZE: usize = 6;
 pub const LDDW_INSTRUCTION_SIZE: usize = 16;
 pub const LDDW_OPCODE: u32 = 0x18;
 pub const CALL_OPCODE: u32 = 0x85;
+/// Unconditional jump, the offset is relative to the next instruction.
+pub const JA_OPCODE: u8 = 0x05;
+
+/// Offsets of the section lengths in the header of the `ExtendedHeader`
+/// binaries (see [`crate::extended_relocations::Header`]).
+pub const DATA_LEN_OFFSET: usize = 12;
+pub const RODATA_LEN_OFFSET: usize = 16;
 
 /// A symbol struct represents a function.
 #[repr(C, packed)]
@@ -114,6 +123,59 @@ pub fn get_section_bytes(section_name: &str, binary: &Elf<'_>, binary_buffer: &[
     section_bytes
 }
 
+/// The VMs start executing the program at the beginning of the .text
+/// section. In order to start from a different function, a jump to it is
+/// inserted at the start of the section. The calls and jumps in the program
+/// are PC-relative, so they don't change, but the offsets into the .text
+/// section recorded elsewhere in the binary need to be shifted by the
+/// returned number of bytes. Nothing is inserted if the entry function is
+/// already the first one.
+pub fn prepend_entry_jump(
+    text: &mut Vec<u8>,
+    binary: &Elf<'_>,
+    entry: &str,
+) -> Result<usize, String> {
+    let offset = function_offset(binary, entry)?;
+    if offset == 0 {
+        return Ok(0);
+    }
+    let jump = i16::try_from(offset / INSTRUCTION_SIZE)
+        .map_err(|_| format!("The entry function {} is too far to jump to", entry))?;
+    debug!(
+        "Inserting a jump to the entry function {} at {}",
+        entry, offset
+    );
+
+    let mut jumped = alloc::vec![0; INSTRUCTION_SIZE];
+    jumped[0] = JA_OPCODE;
+    jumped[2..4].copy_from_slice(&jump.to_le_bytes());
+    jumped.extend_from_slice(text);
+    *text = jumped;
+    Ok(INSTRUCTION_SIZE)
+}
+
+/// Returns the offset (in bytes) of the function from the start of the .text
+/// section.
+pub fn function_offset(binary: &Elf<'_>, function: &str) -> Result<usize, String> {
+    binary
+        .syms
+        .iter()
+        .find(|symbol| {
+            symbol.st_type() == STT_FUNC
+                && binary.strtab.get_at(symbol.st_name) == Some(function)
+                && binary
+                    .section_headers
+                    .get(symbol.st_shndx)
+                    .and_then(|section| binary.shdr_strtab.get_at(section.sh_name))
+                    == Some(".text")
+        })
+        .map(|symbol| symbol.st_value as usize)
+        .ok_or(format!(
+            "The function {} isn't defined in the .text section",
+            function
+        ))
+}
+
 pub fn find_relocations(binary: &Elf<'_>, buffer: &[u8]) -> Vec<(usize, Reloc)> {
     let mut relocations = alloc::vec![];
 
@@ -144,3 +206,17 @@ pub fn round_section_length(section: &mut Vec<u8>) {
         section.extend(alloc::vec![0; padding]);
     }
 }
+
+/// Reads the little-endian u32 at the offset, e.g. a field of the header.
+pub fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+pub fn write_u32(bytes: &mut [u8], offset: usize, value: u32) {
+    bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
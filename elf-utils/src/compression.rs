@@ -0,0 +1,308 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use log::debug;
+
+use crate::{
+    common::{read_u32, round_section_length, write_u32, DATA_LEN_OFFSET, RODATA_LEN_OFFSET},
+    extended_relocations::HEADER_SIZE,
+};
+
+/// Flag set in the header of the `ExtendedHeader` binaries whose .rodata
+/// section has been compressed using [`compress_rodata`].
+pub const RODATA_COMPRESSION_FLAG: u32 = 0x2;
+
+/// Zero runs shorter than this are kept in the compressed data, as the
+/// segment needed to skip them would take more space than the zeros.
+const MIN_ZERO_GAP: usize = 8;
+const SEGMENT_SIZE: usize = 6;
+/// Decompressed length (u32) followed by the number of segments (u16) and
+/// two reserved bytes.
+const MAP_HEADER_SIZE: usize = 8;
+
+const FLAGS_OFFSET: usize = 8;
+
+/// Sizes of the .rodata section before and after the compression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionStats {
+    /// Length of the original .rodata section.
+    pub original_len: usize,
+    /// Length of the compressed section including the decompression map.
+    pub compressed_len: usize,
+}
+
+impl CompressionStats {
+    /// Number of bytes saved by the compression.
+    pub fn saved(&self) -> usize {
+        self.original_len.saturating_sub(self.compressed_len)
+    }
+}
+
+/// Copies `len` bytes from the offset `src` in the compressed data to the
+/// offset `dst` in the decompressed .rodata section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    dst: u16,
+    src: u16,
+    len: u16,
+}
+
+/// Compresses the .rodata section of a binary produced using the
+/// `ExtendedHeader` layout. The programs using many strings and zero-filled
+/// constant arrays can otherwise exceed the size of the SUIT storage slots.
+///
+/// The compression is designed so that the decompression on the device is
+/// trivial and doesn't need any additional memory apart from the
+/// decompressed section itself:
+/// - the runs of zeros are dropped, the section is zero-initialised when
+///   decompressing,
+/// - the repeated chunks (e.g. string literals used from different
+///   functions) are stored once.
+///
+/// The compressed section starts with the decompression map: the length of the
+/// decompressed section (u32), number of segments (u16), two reserved bytes and
+/// the segments (dst: u16, src: u16, len: u16), followed by the compressed
+/// bytes. The header then stores the length of the compressed section and
+/// has the [`RODATA_COMPRESSION_FLAG`] set so the remaining parts of the
+/// binary stay at the offsets expected by the loader.
+///
+/// Returns None if the compression doesn't reduce the size of the section.
+pub fn compress_rodata(binary: &[u8]) -> Result<Option<(Vec<u8>, CompressionStats)>, String> {
+    if binary.len() < HEADER_SIZE {
+        return Err("The binary is too short to contain the header".to_string());
+    }
+
+    let flags = read_u32(binary, FLAGS_OFFSET);
+    if flags & RODATA_COMPRESSION_FLAG != 0 {
+        return Err("The .rodata section of the binary is already compressed".to_string());
+    }
+
+    let rodata_start = HEADER_SIZE + read_u32(binary, DATA_LEN_OFFSET) as usize;
+    let rodata_len = read_u32(binary, RODATA_LEN_OFFSET) as usize;
+    let Some(rodata) = binary.get(rodata_start..rodata_start + rodata_len) else {
+        return Err("The .rodata length in the header exceeds the binary size".to_string());
+    };
+    if rodata_len > u16::MAX as usize {
+        return Err("The .rodata section is too large to be compressed".to_string());
+    }
+
+    let mut section = encode(rodata);
+    round_section_length(&mut section);
+    if section.len() >= rodata_len {
+        debug!(
+            "Compression wouldn't reduce the .rodata size ({} -> {} bytes)",
+            rodata_len,
+            section.len()
+        );
+        return Ok(None);
+    }
+
+    let stats = CompressionStats {
+        original_len: rodata_len,
+        compressed_len: section.len(),
+    };
+    debug!("Compressed the .rodata section: {:?}", stats);
+
+    let mut compressed = Vec::with_capacity(binary.len() - stats.saved());
+    compressed.extend_from_slice(&binary[..rodata_start]);
+    compressed.extend_from_slice(&section);
+    compressed.extend_from_slice(&binary[rodata_start + rodata_len..]);
+    write_u32(
+        &mut compressed,
+        FLAGS_OFFSET,
+        flags | RODATA_COMPRESSION_FLAG,
+    );
+    write_u32(&mut compressed, RODATA_LEN_OFFSET, section.len() as u32);
+    Ok(Some((compressed, stats)))
+}
+
+/// Restores the .rodata section compressed by [`compress_rodata`], this
+/// mirrors what the loader on the device does.
+pub fn decompress_rodata(section: &[u8]) -> Result<Vec<u8>, String> {
+    if section.len() < MAP_HEADER_SIZE {
+        return Err("The compressed section is too short".to_string());
+    }
+    let decompressed_len = read_u32(section, 0) as usize;
+    let segment_count = u16::from_le_bytes([section[4], section[5]]) as usize;
+    let data_start = MAP_HEADER_SIZE + segment_count * SEGMENT_SIZE;
+    let Some(data) = section.get(data_start..) else {
+        return Err("The decompression map exceeds the section size".to_string());
+    };
+
+    let mut rodata = alloc::vec![0; decompressed_len];
+    for i in 0..segment_count {
+        let offset = MAP_HEADER_SIZE + i * SEGMENT_SIZE;
+        let field = |n: usize| {
+            u16::from_le_bytes([section[offset + 2 * n], section[offset + 2 * n + 1]]) as usize
+        };
+        let (dst, src, len) = (field(0), field(1), field(2));
+        let (Some(source), Some(destination)) =
+            (data.get(src..src + len), rodata.get_mut(dst..dst + len))
+        else {
+            return Err(alloc::format!("Segment {} is out of bounds", i));
+        };
+        destination.copy_from_slice(source);
+    }
+    Ok(rodata)
+}
+
+fn encode(rodata: &[u8]) -> Vec<u8> {
+    let mut data: Vec<u8> = Vec::new();
+    let mut segments: Vec<Segment> = Vec::new();
+
+    for (start, end) in nonzero_runs(rodata) {
+        let chunk = &rodata[start..end];
+        let src = match find(&data, chunk) {
+            Some(src) => src,
+            None => {
+                data.extend_from_slice(chunk);
+                data.len() - chunk.len()
+            }
+        };
+        segments.push(Segment {
+            dst: start as u16,
+            src: src as u16,
+            len: chunk.len() as u16,
+        });
+    }
+
+    let mut section = Vec::with_capacity(MAP_HEADER_SIZE + segments.len() * SEGMENT_SIZE);
+    section.extend_from_slice(&(rodata.len() as u32).to_le_bytes());
+    section.extend_from_slice(&(segments.len() as u16).to_le_bytes());
+    section.extend_from_slice(&[0, 0]);
+    for segment in segments {
+        section.extend_from_slice(&segment.dst.to_le_bytes());
+        section.extend_from_slice(&segment.src.to_le_bytes());
+        section.extend_from_slice(&segment.len.to_le_bytes());
+    }
+    section.extend(data);
+    section
+}
+
+/// Splits the section into the ranges of non-zero bytes, the ranges separated
+/// by fewer than [`MIN_ZERO_GAP`] zeros are merged.
+fn nonzero_runs(rodata: &[u8]) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    let mut position = 0;
+    while position < rodata.len() {
+        if rodata[position] == 0 {
+            position += 1;
+            continue;
+        }
+        let start = position;
+        while position < rodata.len() && rodata[position] != 0 {
+            position += 1;
+        }
+        match runs.last_mut() {
+            Some(last) if start - last.1 < MIN_ZERO_GAP => last.1 = position,
+            _ => runs.push((start, position)),
+        }
+    }
+    runs
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    /// Assembles an `ExtendedHeader` binary with the given .rodata section,
+    /// the sections following it are represented by a single exit
+    /// instruction.
+    fn binary(rodata: &[u8]) -> Vec<u8> {
+        let mut binary = vec![0; HEADER_SIZE];
+        write_u32(&mut binary, DATA_LEN_OFFSET, 8);
+        write_u32(&mut binary, RODATA_LEN_OFFSET, rodata.len() as u32);
+        binary.extend_from_slice(&[1; 8]);
+        binary.extend_from_slice(rodata);
+        binary.extend_from_slice(&[0x95, 0, 0, 0, 0, 0, 0, 0]);
+        binary
+    }
+
+    fn compressed_section(binary: &[u8]) -> &[u8] {
+        let start = HEADER_SIZE + read_u32(binary, DATA_LEN_OFFSET) as usize;
+        &binary[start..start + read_u32(binary, RODATA_LEN_OFFSET) as usize]
+    }
+
+    #[test]
+    fn compressed_rodata_round_trips() {
+        let rodata = [
+            &b"hello"[..],
+            &[0; 3],
+            b"world",
+            &[0; 40],
+            b"hello",
+            &[0; 20],
+            b"world",
+            &[0; 16],
+        ]
+        .concat();
+        let program = binary(&rodata);
+
+        let (compressed, stats) = compress_rodata(&program).unwrap().unwrap();
+
+        assert_eq!(
+            decompress_rodata(compressed_section(&compressed)),
+            Ok(rodata.clone())
+        );
+        assert_eq!(stats.original_len, rodata.len());
+        assert_eq!(stats.compressed_len, compressed_section(&compressed).len());
+        assert_ne!(
+            read_u32(&compressed, FLAGS_OFFSET) & RODATA_COMPRESSION_FLAG,
+            0
+        );
+        assert_eq!(compressed.len(), program.len() - stats.saved());
+        assert!(compressed.ends_with(&program[HEADER_SIZE + 8 + rodata.len()..]));
+    }
+
+    #[test]
+    fn only_zero_gaps_of_at_least_the_minimum_length_are_dropped() {
+        let short_gap = [&b"ab"[..], &[0; MIN_ZERO_GAP - 1], b"cd"].concat();
+        let long_gap = [&b"ab"[..], &[0; MIN_ZERO_GAP], b"cd"].concat();
+
+        assert_eq!(nonzero_runs(&short_gap), vec![(0, MIN_ZERO_GAP + 3)]);
+        assert_eq!(
+            nonzero_runs(&long_gap),
+            vec![(0, 2), (MIN_ZERO_GAP + 2, MIN_ZERO_GAP + 4)]
+        );
+        for rodata in [short_gap, long_gap] {
+            assert_eq!(decompress_rodata(&encode(&rodata)), Ok(rodata));
+        }
+    }
+
+    #[test]
+    fn repeated_chunks_are_stored_once() {
+        let rodata = [&b"repeated"[..], &[0; 16], b"repeated"].concat();
+
+        let section = encode(&rodata);
+
+        // Two segments referring to the same bytes.
+        assert_eq!(
+            section.len(),
+            MAP_HEADER_SIZE + 2 * SEGMENT_SIZE + b"repeated".len()
+        );
+        assert_eq!(decompress_rodata(&section), Ok(rodata));
+    }
+
+    #[test]
+    fn already_compressed_binary_is_rejected() {
+        let (compressed, _) = compress_rodata(&binary(&[0; 64])).unwrap().unwrap();
+
+        assert!(compress_rodata(&compressed).is_err());
+    }
+
+    #[test]
+    fn incompressible_rodata_is_left_unchanged() {
+        let rodata = (1..=16).collect::<Vec<u8>>();
+
+        assert_eq!(compress_rodata(&binary(&rodata)), Ok(None));
+    }
+}
@@ -0,0 +1,429 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use log::debug;
+
+use crate::{
+    common::{
+        read_u32, write_u32, DATA_LEN_OFFSET, INSTRUCTION_SIZE, LDDW_OPCODE, RODATA_LEN_OFFSET,
+    },
+    extended_relocations::HEADER_SIZE,
+    femtocontainer_relocations::{FC_LDDWD_OPCODE, FC_LDDWR_OPCODE},
+    model::RELOCATED_CALL_SIZE,
+};
+
+const TEXT_LEN_OFFSET: usize = 20;
+const FUNCTIONS_LEN_OFFSET: usize = 24;
+const RELOCATED_CALLS_OFFSET: usize = 28;
+const SYMBOL_SIZE: usize = 6;
+/// Offset of the location of the function within the symbol struct.
+const SYMBOL_LOCATION_OFFSET: usize = 4;
+
+const CLASS_MASK: u8 = 0x07;
+const CLASS_JMP: u8 = 0x05;
+const CLASS_JMP32: u8 = 0x06;
+const CALL_OPCODE: u8 = 0x85;
+const EXIT_OPCODE: u8 = 0x95;
+/// Source register value marking the calls of the functions defined in the
+/// program (as opposed to the helper calls).
+const PSEUDO_CALL: u8 = 1;
+
+/// Sizes of the .text section before and after removing the unreachable
+/// functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadCodeStats {
+    /// Number of the functions that were removed.
+    pub removed_functions: usize,
+    /// Length of the original .text section.
+    pub original_len: usize,
+    /// Length of the .text section without the unreachable functions.
+    pub text_len: usize,
+}
+
+impl DeadCodeStats {
+    /// Number of bytes saved by removing the unreachable functions.
+    pub fn saved(&self) -> usize {
+        self.original_len.saturating_sub(self.text_len)
+    }
+}
+
+/// Removes the functions which can't be reached from the entry point (the
+/// start of the .text section) from a binary produced using the
+/// `ExtendedHeader` layout. The compiler keeps all global functions in the
+/// object file even if the program never calls them (e.g. the unused
+/// functions of a shared header), which wastes the scarce space of the SUIT
+/// storage slots.
+///
+/// The .text section is split into functions at the entry point, the
+/// function symbols and the targets of all calls. The functions called
+/// (directly or through the relocated calls) from the reachable ones are
+/// reachable themselves. The unreachable functions are cut out and the
+/// offsets of the PC-relative calls, the relocated calls and the function
+/// symbols are adjusted to the new layout. The names of the removed
+/// functions are left in the .rodata section so that the offsets into it
+/// don't change.
+///
+/// Returns None if all functions are reachable.
+pub fn eliminate_dead_code(binary: &[u8]) -> Result<Option<(Vec<u8>, DeadCodeStats)>, String> {
+    if binary.len() < HEADER_SIZE {
+        return Err("The binary is too short to contain the header".to_string());
+    }
+
+    let text_start = HEADER_SIZE
+        + read_u32(binary, DATA_LEN_OFFSET) as usize
+        + read_u32(binary, RODATA_LEN_OFFSET) as usize;
+    let text_len = read_u32(binary, TEXT_LEN_OFFSET) as usize;
+    let functions_start = text_start + text_len;
+    let functions_len = read_u32(binary, FUNCTIONS_LEN_OFFSET) as usize;
+    let calls_start = functions_start + functions_len * SYMBOL_SIZE;
+    let calls_len = read_u32(binary, RELOCATED_CALLS_OFFSET) as usize;
+    let calls_end = calls_start + calls_len * RELOCATED_CALL_SIZE;
+    if calls_end > binary.len() || text_len % INSTRUCTION_SIZE != 0 {
+        return Err("The section lengths in the header don't match the binary".to_string());
+    }
+    let text = &binary[text_start..functions_start];
+    if text.is_empty() {
+        return Ok(None);
+    }
+
+    // Relocated calls: offset of the call instruction -> offset of the function.
+    let relocated_calls = (0..calls_len)
+        .map(|i| {
+            let offset = calls_start + i * RELOCATED_CALL_SIZE;
+            (
+                read_u32(binary, offset) as usize,
+                read_u32(binary, offset + 4) as usize,
+            )
+        })
+        .collect::<BTreeMap<usize, usize>>();
+    let symbols = (0..functions_len)
+        .map(|i| {
+            read_u16(
+                binary,
+                functions_start + i * SYMBOL_SIZE + SYMBOL_LOCATION_OFFSET,
+            )
+        })
+        .collect::<Vec<usize>>();
+
+    let calls = find_calls(text, &relocated_calls)?;
+    let mut starts = BTreeSet::from([0]);
+    starts.extend(symbols.iter().copied());
+    starts.extend(calls.values().copied());
+    if let Some(start) = starts
+        .iter()
+        .find(|start| **start >= text_len || **start % INSTRUCTION_SIZE != 0)
+    {
+        return Err(format!("Invalid function offset {} in .text", start));
+    }
+    let starts = starts.into_iter().collect::<Vec<usize>>();
+    let function_of = |offset: usize| starts.partition_point(|start| *start <= offset) - 1;
+    let end_of = |function: usize| starts.get(function + 1).copied().unwrap_or(text_len);
+
+    check_jumps(text, |from, to| function_of(from) == function_of(to))?;
+
+    let mut reachable = BTreeSet::from([0]);
+    let mut pending = alloc::vec![0];
+    while let Some(function) = pending.pop() {
+        for (_, target) in calls.range(starts[function]..end_of(function)) {
+            let callee = function_of(*target);
+            if reachable.insert(callee) {
+                pending.push(callee);
+            }
+        }
+    }
+    if reachable.len() == starts.len() {
+        debug!("All {} functions are reachable", starts.len());
+        return Ok(None);
+    }
+
+    // Number of bytes removed before each of the kept functions.
+    let mut removed_before = BTreeMap::new();
+    let mut removed = 0;
+    for (function, start) in starts.iter().enumerate() {
+        if reachable.contains(&function) {
+            removed_before.insert(function, removed);
+        } else {
+            debug!(
+                "Removing the unreachable function at {}..{}",
+                start,
+                end_of(function)
+            );
+            removed += end_of(function) - start;
+        }
+    }
+    let relocate = |offset: usize| -> Option<usize> {
+        removed_before
+            .get(&function_of(offset))
+            .map(|removed| offset - removed)
+    };
+
+    let mut new_text = Vec::with_capacity(text_len - removed);
+    for function in &reachable {
+        new_text.extend_from_slice(&text[starts[*function]..end_of(*function)]);
+    }
+    for (offset, target) in &calls {
+        if relocated_calls.contains_key(offset) {
+            continue;
+        }
+        let (Some(offset), Some(target)) = (relocate(*offset), relocate(*target)) else {
+            continue;
+        };
+        let immediate = (target as i64 - offset as i64) / INSTRUCTION_SIZE as i64 - 1;
+        new_text[offset + 4..offset + 8].copy_from_slice(&(immediate as i32).to_le_bytes());
+    }
+
+    let mut new_functions = Vec::new();
+    for (i, location) in symbols.iter().enumerate() {
+        let offset = functions_start + i * SYMBOL_SIZE;
+        if let Some(location) = relocate(*location) {
+            new_functions.extend_from_slice(&binary[offset..offset + SYMBOL_LOCATION_OFFSET]);
+            new_functions.extend_from_slice(&(location as u16).to_le_bytes());
+        }
+    }
+    let mut new_calls = Vec::new();
+    for (offset, target) in &relocated_calls {
+        if let (Some(offset), Some(target)) = (relocate(*offset), relocate(*target)) {
+            new_calls.extend_from_slice(&(offset as u32).to_le_bytes());
+            new_calls.extend_from_slice(&(target as u32).to_le_bytes());
+        }
+    }
+
+    let stats = DeadCodeStats {
+        removed_functions: starts.len() - reachable.len(),
+        original_len: text_len,
+        text_len: new_text.len(),
+    };
+    debug!("Eliminated the dead code: {:?}", stats);
+
+    let mut output = Vec::with_capacity(binary.len() - stats.saved());
+    output.extend_from_slice(&binary[..text_start]);
+    output.extend_from_slice(&new_text);
+    output.extend_from_slice(&new_functions);
+    output.extend_from_slice(&new_calls);
+    output.extend_from_slice(&binary[calls_end..]);
+    write_u32(&mut output, TEXT_LEN_OFFSET, new_text.len() as u32);
+    write_u32(
+        &mut output,
+        FUNCTIONS_LEN_OFFSET,
+        (new_functions.len() / SYMBOL_SIZE) as u32,
+    );
+    write_u32(
+        &mut output,
+        RELOCATED_CALLS_OFFSET,
+        (new_calls.len() / RELOCATED_CALL_SIZE) as u32,
+    );
+    Ok(Some((output, stats)))
+}
+
+/// Collects the calls of the functions defined in the program: offset of the
+/// call instruction -> offset of the called function. The relocated calls
+/// don't have the target encoded in the instruction, it is taken from the
+/// relocation instead.
+fn find_calls(
+    text: &[u8],
+    relocated_calls: &BTreeMap<usize, usize>,
+) -> Result<BTreeMap<usize, usize>, String> {
+    let mut calls = BTreeMap::new();
+    for offset in instruction_offsets(text) {
+        let instruction = &text[offset..offset + INSTRUCTION_SIZE];
+        let is_pseudo_call = instruction[0] == CALL_OPCODE && instruction[1] >> 4 == PSEUDO_CALL;
+        if !is_pseudo_call && !relocated_calls.contains_key(&offset) {
+            continue;
+        }
+        let target = match relocated_calls.get(&offset) {
+            Some(target) => *target as i64,
+            None => {
+                let immediate = i32::from_le_bytes([
+                    instruction[4],
+                    instruction[5],
+                    instruction[6],
+                    instruction[7],
+                ]);
+                offset as i64 + (immediate as i64 + 1) * INSTRUCTION_SIZE as i64
+            }
+        };
+        if target < 0 || target as usize >= text.len() {
+            return Err(format!(
+                "The call at {} targets {} outside of .text",
+                offset, target
+            ));
+        }
+        calls.insert(offset, target as usize);
+    }
+    Ok(calls)
+}
+
+/// Checks that the jumps don't cross the function boundaries, otherwise the
+/// functions can't be removed independently.
+fn check_jumps(text: &[u8], same_function: impl Fn(usize, usize) -> bool) -> Result<(), String> {
+    for offset in instruction_offsets(text) {
+        let opcode = text[offset];
+        let class = opcode & CLASS_MASK;
+        if (class != CLASS_JMP && class != CLASS_JMP32)
+            || opcode == CALL_OPCODE
+            || opcode == EXIT_OPCODE
+        {
+            continue;
+        }
+        let jump = i16::from_le_bytes([text[offset + 2], text[offset + 3]]) as i64;
+        let target = offset as i64 + (jump + 1) * INSTRUCTION_SIZE as i64;
+        if target < 0 || target as usize >= text.len() || !same_function(offset, target as usize) {
+            return Err(format!(
+                "The jump at {} leaves the function, the dead code can't be eliminated",
+                offset
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Offsets of the instructions in the .text section, the wide load
+/// instructions occupy two slots.
+fn instruction_offsets(text: &[u8]) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut offset = 0;
+    while offset + INSTRUCTION_SIZE <= text.len() {
+        offsets.push(offset);
+        let opcode = text[offset] as u32;
+        offset += if opcode == LDDW_OPCODE || opcode == FC_LDDWD_OPCODE || opcode == FC_LDDWR_OPCODE
+        {
+            2 * INSTRUCTION_SIZE
+        } else {
+            INSTRUCTION_SIZE
+        };
+    }
+    offsets
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> usize {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]]) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    const MOV_IMM_OPCODE: u8 = 0xb7;
+    const JA_OPCODE: u8 = 0x05;
+
+    fn instruction(opcode: u8, registers: u8, offset: i16, immediate: i32) -> Vec<u8> {
+        let mut instruction = vec![opcode, registers];
+        instruction.extend_from_slice(&offset.to_le_bytes());
+        instruction.extend_from_slice(&immediate.to_le_bytes());
+        instruction
+    }
+
+    fn exit() -> Vec<u8> {
+        instruction(EXIT_OPCODE, 0, 0, 0)
+    }
+
+    fn mov(value: i32) -> Vec<u8> {
+        instruction(MOV_IMM_OPCODE, 0, 0, value)
+    }
+
+    fn call(immediate: i32) -> Vec<u8> {
+        instruction(CALL_OPCODE, PSEUDO_CALL << 4, 0, immediate)
+    }
+
+    /// Assembles an `ExtendedHeader` binary, the symbols are given as
+    /// (name offset, location) and the relocated calls as (instruction
+    /// offset, function offset).
+    fn binary(text: &[Vec<u8>], symbols: &[(u16, u16)], relocated_calls: &[(u32, u32)]) -> Vec<u8> {
+        let text = text.concat();
+        let mut binary = vec![0; HEADER_SIZE];
+        write_u32(&mut binary, DATA_LEN_OFFSET, 8);
+        write_u32(&mut binary, RODATA_LEN_OFFSET, 8);
+        write_u32(&mut binary, TEXT_LEN_OFFSET, text.len() as u32);
+        write_u32(&mut binary, FUNCTIONS_LEN_OFFSET, symbols.len() as u32);
+        write_u32(
+            &mut binary,
+            RELOCATED_CALLS_OFFSET,
+            relocated_calls.len() as u32,
+        );
+        binary.extend_from_slice(&[1; 8]);
+        binary.extend_from_slice(&[2; 8]);
+        binary.extend_from_slice(&text);
+        for (name_offset, location) in symbols {
+            binary.extend_from_slice(&name_offset.to_le_bytes());
+            binary.extend_from_slice(&[0, 0]);
+            binary.extend_from_slice(&location.to_le_bytes());
+        }
+        for (instruction_offset, function_offset) in relocated_calls {
+            binary.extend_from_slice(&instruction_offset.to_le_bytes());
+            binary.extend_from_slice(&function_offset.to_le_bytes());
+        }
+        // Allowed helpers
+        binary.extend_from_slice(&[1, 2, 3]);
+        binary
+    }
+
+    #[test]
+    fn unreachable_function_is_removed_and_offsets_are_adjusted() {
+        let program = binary(
+            &[
+                // main: calls third
+                call(3),
+                exit(),
+                // second: never called
+                mov(2),
+                exit(),
+                // third: calls fourth through a relocated call
+                call(-1),
+                exit(),
+                // fourth
+                mov(1),
+                exit(),
+            ],
+            &[(0, 0), (5, 16), (12, 32), (18, 48)],
+            &[(32, 48)],
+        );
+
+        let (output, stats) = eliminate_dead_code(&program).unwrap().unwrap();
+
+        let expected = binary(
+            &[call(1), exit(), call(-1), exit(), mov(1), exit()],
+            &[(0, 0), (12, 16), (18, 32)],
+            &[(16, 32)],
+        );
+        assert_eq!(output, expected);
+        assert_eq!(
+            stats,
+            DeadCodeStats {
+                removed_functions: 1,
+                original_len: 64,
+                text_len: 48,
+            }
+        );
+        assert_eq!(stats.saved(), 16);
+    }
+
+    #[test]
+    fn jump_across_functions_is_rejected() {
+        let program = binary(
+            &[
+                instruction(JA_OPCODE, 0, 1, 0),
+                exit(),
+                // second: the target of the jump
+                exit(),
+            ],
+            &[(0, 0), (5, 16)],
+            &[],
+        );
+
+        let error = eliminate_dead_code(&program).unwrap_err();
+
+        assert!(error.contains("leaves the function"), "{}", error);
+    }
+
+    #[test]
+    fn binary_with_all_functions_reachable_is_left_unchanged() {
+        let program = binary(&[call(1), exit(), mov(1), exit()], &[(0, 0), (5, 16)], &[]);
+
+        assert_eq!(eliminate_dead_code(&program), Ok(None));
+    }
+}
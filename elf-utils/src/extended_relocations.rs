@@ -12,10 +12,11 @@ use log::debug;
 
 use crate::{
     common::{
-        find_relocations, get_section_bytes, get_section_header, round_section_length, Symbol,
-        LDDW_OPCODE,
+        find_relocations, get_section_bytes, get_section_header, prepend_entry_jump,
+        round_section_length, Symbol, LDDW_OPCODE,
     },
     femtocontainer_relocations::{FC_LDDWD_OPCODE, FC_LDDWR_OPCODE},
+    metadata::{ProgramMetadata, METADATA_FLAG},
     model::{Lddw, RelocatedCall},
 };
 
@@ -23,10 +24,14 @@ use crate::{
 /// - Header: Contains the information about the lengths of the remaining sections
 ///   functions and read-only data. See [`Header`] for more details
 /// - Data section
-/// - Read-only data section
+/// - Read-only data section (compressed if the header has the
+///   [`crate::RODATA_COMPRESSION_FLAG`] set, see [`crate::compress_rodata`])
 /// - Text section: Contains the code of the main entrypoint and the other functions
 /// - Symbol structs: TODO: figure out why we need this
 /// - Relocated function calls: custom metadata specifying how function calls should be relocated
+/// - Allowed helpers: indices of the helper functions that the program can call
+/// - Program metadata (optional): see [`ProgramMetadata`], its presence is
+///   indicated by the [`METADATA_FLAG`] in the header
 struct Binary {
     header: Header,
     data: Vec<u8>,
@@ -35,6 +40,7 @@ struct Binary {
     functions: Vec<Symbol>,
     relocated_calls: Vec<RelocatedCall>,
     allowed_helpers: Vec<u8>,
+    metadata: Option<ProgramMetadata>,
 }
 
 pub const HEADER_SIZE: usize = 32;
@@ -62,6 +68,9 @@ impl Into<Vec<u8>> for Binary {
         }
 
         binary.extend(self.allowed_helpers);
+        if let Some(metadata) = self.metadata {
+            binary.extend(metadata.encode());
+        }
         binary
     }
 }
@@ -107,6 +116,29 @@ pub fn assemble_binary(program: &[u8]) -> Result<Vec<u8>, String> {
 pub fn assemble_binary_specifying_helpers(
     program: &[u8],
     allowed_helpers: Vec<u8>,
+) -> Result<Vec<u8>, String> {
+    assemble_binary_with_metadata(program, allowed_helpers, None)
+}
+
+/// Applies the same modifications as [`assemble_binary_specifying_helpers`]
+/// and additionally embeds the program metadata (name, version, build hash)
+/// into the binary so that it can be reported back by the device.
+pub fn assemble_binary_with_metadata(
+    program: &[u8],
+    allowed_helpers: Vec<u8>,
+    metadata: Option<ProgramMetadata>,
+) -> Result<Vec<u8>, String> {
+    assemble_binary_with_entry(program, allowed_helpers, metadata, None)
+}
+
+/// Applies the same modifications as [`assemble_binary_with_metadata`], the
+/// program starts executing from the `entry` function instead of the first
+/// one in the .text section (see [`prepend_entry_jump`]).
+pub fn assemble_binary_with_entry(
+    program: &[u8],
+    allowed_helpers: Vec<u8>,
+    metadata: Option<ProgramMetadata>,
+    entry: Option<&str>,
 ) -> Result<Vec<u8>, String> {
     let Ok(binary) = goblin::elf::Elf::parse(&program) else {
         return Err("Failed to parse the ELF binary".to_string());
@@ -128,12 +160,23 @@ pub fn assemble_binary_specifying_helpers(
     // about the offsets at which the function names are stored.
     // This is maintained for compatibility with the rbpf bytecode patching
     // script. It isn't actually used by their VM.
-    let symbol_structs: Vec<Symbol> = extract_function_symbols(&mut rodata, &binary);
+    let mut symbol_structs: Vec<Symbol> = extract_function_symbols(&mut rodata, &binary);
 
-    let relocated_calls: Vec<RelocatedCall> = find_relocated_calls(&binary, &program);
+    let mut relocated_calls: Vec<RelocatedCall> = find_relocated_calls(&binary, &program);
 
     resolve_rodata_relocations(&mut text, &binary, &program, &str_section_offsets);
 
+    if let Some(entry) = entry {
+        let shift = prepend_entry_jump(&mut text, &binary, entry)?;
+        for symbol in symbol_structs.iter_mut() {
+            symbol.location_offset += shift as u16;
+        }
+        for call in relocated_calls.iter_mut() {
+            call.instruction_offset += shift as u32;
+            call.function_text_offset += shift as u32;
+        }
+    }
+
     round_section_length(&mut data);
     round_section_length(&mut rodata);
 
@@ -141,7 +184,7 @@ pub fn assemble_binary_specifying_helpers(
     let header = Header {
         magic: 123,
         version: 0,
-        flags: 0,
+        flags: if metadata.is_some() { METADATA_FLAG } else { 0 },
         data_len: data.len() as u32,
         rodata_len: rodata.len() as u32,
         text_len: text.len() as u32,
@@ -157,6 +200,7 @@ pub fn assemble_binary_specifying_helpers(
         functions: symbol_structs,
         relocated_calls,
         allowed_helpers: allowed_helpers.clone(),
+        metadata,
     };
 
     let assembled_program: Vec<u8> = output_binary.into();
@@ -291,7 +335,9 @@ pub fn resolve_rodata_relocations(
 }
 
 /// Responsible for extracting the allowed helper function indices that are
-/// specified at the end of the program binary.
+/// specified at the end of the program binary. The program metadata which
+/// follows the helpers if the header has the [`METADATA_FLAG`] set isn't
+/// included.
 ///
 /// Note: This can only be used if the input slice of bytes comes from a program
 /// which has been preprocessed with the [`micro_bpf_common::BinaryFileLayout:ExtendedHeader`]
@@ -311,8 +357,15 @@ pub fn extract_allowed_helpers(prog: &[u8]) -> Vec<u8> {
             + (*header).functions_len * FUNCTION_STRUCT_SIZE
             + (*header).relocated_calls * RELOCATED_CALL_STRUCT_SIZE;
 
+        let allowed_helpers_end = if (*header).flags & METADATA_FLAG != 0 {
+            let trailer_len = ProgramMetadata::trailer_len(prog).unwrap_or(0);
+            prog.len().saturating_sub(trailer_len)
+        } else {
+            prog.len()
+        };
+
         let mut allowed_helpers = Vec::new();
-        for byte in &prog[allowed_helpers_offset as usize..] {
+        for byte in &prog[allowed_helpers_offset as usize..allowed_helpers_end] {
             allowed_helpers.push(*byte);
         }
         debug!("Allowed helpers: {:?}", allowed_helpers);
@@ -321,6 +374,22 @@ pub fn extract_allowed_helpers(prog: &[u8]) -> Vec<u8> {
     }
 }
 
+/// Extracts the program metadata embedded at the end of the binary, returns
+/// None if the header doesn't have the [`METADATA_FLAG`] set or the metadata
+/// is malformed.
+///
+/// Note: the same restrictions as for [`extract_allowed_helpers`] apply.
+pub fn extract_program_metadata(prog: &[u8]) -> Option<ProgramMetadata> {
+    if prog.len() < HEADER_SIZE {
+        return None;
+    }
+    let flags = unsafe { (*(prog.as_ptr() as *const Header)).flags };
+    if flags & METADATA_FLAG == 0 {
+        return None;
+    }
+    ProgramMetadata::decode_trailer(prog)
+}
+
 pub fn patch_text(
     text: &mut [u8],
     binary: &Elf<'_>,
@@ -383,3 +452,59 @@ pub fn patch_text(
 
     text[reloc.r_offset as usize..reloc.r_offset as usize + 16].copy_from_slice((&instr).into());
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    fn binary_with_metadata(metadata: Option<ProgramMetadata>) -> Vec<u8> {
+        let text = vec![0x95, 0, 0, 0, 0, 0, 0, 0];
+        Binary {
+            header: Header {
+                magic: 123,
+                version: 0,
+                flags: if metadata.is_some() { METADATA_FLAG } else { 0 },
+                data_len: 8,
+                rodata_len: 8,
+                text_len: text.len() as u32,
+                functions_len: 1,
+                relocated_calls: 1,
+            },
+            data: vec![1; 8],
+            rodata: vec![2; 8],
+            text,
+            functions: vec![Symbol {
+                name_offset: 0,
+                flags: 0,
+                location_offset: 0,
+            }],
+            relocated_calls: vec![RelocatedCall {
+                instruction_offset: 0,
+                function_text_offset: 0,
+            }],
+            allowed_helpers: vec![1, 2, 17],
+            metadata,
+        }
+        .into()
+    }
+
+    #[test]
+    fn metadata_round_trips_without_changing_the_allowed_helpers() {
+        let metadata = ProgramMetadata::new("counter", "1.2.0", b"object file")
+            .with_revisions(Some(String::from("abc123-dirty")), None);
+        let binary = binary_with_metadata(Some(metadata.clone()));
+
+        assert_eq!(extract_allowed_helpers(&binary), vec![1, 2, 17]);
+        assert_eq!(extract_program_metadata(&binary), Some(metadata));
+    }
+
+    #[test]
+    fn binary_without_metadata_only_ends_with_the_allowed_helpers() {
+        let binary = binary_with_metadata(None);
+
+        assert_eq!(extract_allowed_helpers(&binary), vec![1, 2, 17]);
+        assert_eq!(extract_program_metadata(&binary), None);
+    }
+}
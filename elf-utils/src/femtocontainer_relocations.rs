@@ -30,7 +30,7 @@ use goblin::{
 use log::debug;
 
 use crate::{
-    common::{get_section_bytes, round_section_length, Symbol},
+    common::{get_section_bytes, prepend_entry_jump, round_section_length, Symbol},
     extended_relocations::{append_string_literals, resolve_rodata_relocations},
 };
 
@@ -106,6 +106,16 @@ impl Into<Vec<u8>> for FCBinary {
 /// bespoke format that is used by the Femto-Container implementation of the
 /// eBPF VM. Refer to [`FCBinary`] for more details.
 pub fn assemble_femtocontainer_binary(program: &[u8]) -> Result<Vec<u8>, String> {
+    assemble_femtocontainer_binary_with_entry(program, None)
+}
+
+/// Same as [`assemble_femtocontainer_binary`], the program starts executing
+/// from the `entry` function instead of the first one in the .text section
+/// (see [`prepend_entry_jump`]).
+pub fn assemble_femtocontainer_binary_with_entry(
+    program: &[u8],
+    entry: Option<&str>,
+) -> Result<Vec<u8>, String> {
     let Ok(binary) = goblin::elf::Elf::parse(&program) else {
         return Err("Failed to parse the ELF binary".to_string());
     };
@@ -126,10 +136,17 @@ pub fn assemble_femtocontainer_binary(program: &[u8]) -> Result<Vec<u8>, String>
     // about the offsets at which the function names are stored.
     // This is maintained for compatibility with the bytecode patching
     // script used by FemtoContainers. It isn't actually used by their VM.
-    let symbol_structs: Vec<Symbol> = extract_function_symbols(&mut rodata, &binary);
+    let mut symbol_structs: Vec<Symbol> = extract_function_symbols(&mut rodata, &binary);
 
     resolve_rodata_relocations(&mut text, &binary, &program, &str_section_offsets);
 
+    if let Some(entry) = entry {
+        let shift = prepend_entry_jump(&mut text, &binary, entry)?;
+        for symbol in symbol_structs.iter_mut() {
+            symbol.location_offset += shift as u16;
+        }
+    }
+
     round_section_length(&mut data);
     round_section_length(&mut rodata);
 
@@ -42,17 +42,33 @@
 extern crate alloc;
 extern crate rbpf;
 
+mod artifact;
 mod common;
+mod compression;
+mod dead_code;
 mod extended_relocations;
 mod femtocontainer_relocations;
+mod metadata;
 mod model;
 mod relocation_resolution;
 
 // Only the below functions are exposed to the users of this library.
+pub use artifact::extract_text_section;
 pub use common::debug_print_program_bytes;
 pub use common::extract_section;
+pub use common::function_offset;
+pub use common::prepend_entry_jump;
+pub use compression::{
+    compress_rodata, decompress_rodata, CompressionStats, RODATA_COMPRESSION_FLAG,
+};
+pub use dead_code::{eliminate_dead_code, DeadCodeStats};
 pub use extended_relocations::assemble_binary;
 pub use extended_relocations::assemble_binary_specifying_helpers;
+pub use extended_relocations::assemble_binary_with_entry;
+pub use extended_relocations::assemble_binary_with_metadata;
 pub use extended_relocations::extract_allowed_helpers;
+pub use extended_relocations::extract_program_metadata;
 pub use femtocontainer_relocations::assemble_femtocontainer_binary;
+pub use femtocontainer_relocations::assemble_femtocontainer_binary_with_entry;
+pub use metadata::{ProgramMetadata, METADATA_FLAG};
 pub use relocation_resolution::resolve_relocations;
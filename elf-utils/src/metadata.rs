@@ -0,0 +1,133 @@
+use alloc::{string::String, vec::Vec};
+
+/// Flag set in the header of the `ExtendedHeader` binaries which carry the
+/// program metadata block at the end of the binary.
+pub const METADATA_FLAG: u32 = 0x1;
+
+const TLV_NAME: u8 = 1;
+const TLV_VERSION: u8 = 2;
+const TLV_BUILD_HASH: u8 = 3;
+const TLV_SOURCE_REVISION: u8 = 4;
+const TLV_ROOT_REVISION: u8 = 5;
+
+/// Human-readable information about the program embedded in the binary so
+/// that the devices can report what is stored in their SUIT storage slots.
+///
+/// The metadata is encoded as a sequence of TLV (type: u8, length: u8, value)
+/// entries followed by the total length of the entries (u16, little endian).
+/// It is appended after the allowed helpers list, the trailing length allows
+/// the loader to find the start of the block when reading from the end of
+/// the binary.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ProgramMetadata {
+    /// Name of the program.
+    pub name: String,
+    /// Semantic version of the program.
+    pub version: String,
+    /// Hash of the object file that the binary was produced from.
+    pub build_hash: u64,
+    /// Git revision of the repository containing the source of the program,
+    /// suffixed with `-dirty` if it had uncommitted changes.
+    pub source_revision: Option<String>,
+    /// Git revision of the micro-bpf repository (RIOT and the firmware)
+    /// that the program was built and signed with.
+    pub root_revision: Option<String>,
+}
+
+impl ProgramMetadata {
+    /// Creates the metadata of a program, the build hash is computed from the
+    /// contents of its object file.
+    pub fn new(name: &str, version: &str, object_file: &[u8]) -> Self {
+        ProgramMetadata {
+            name: String::from(name),
+            version: String::from(version),
+            build_hash: fnv1a(object_file),
+            source_revision: None,
+            root_revision: None,
+        }
+    }
+
+    /// Records the git revisions that the program was built from, the
+    /// entries are only encoded if the revisions are known.
+    pub fn with_revisions(
+        mut self,
+        source_revision: Option<String>,
+        root_revision: Option<String>,
+    ) -> Self {
+        self.source_revision = source_revision;
+        self.root_revision = root_revision;
+        self
+    }
+
+    /// Encodes the metadata into the TLV block appended to the binary.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut block = Vec::new();
+        let mut push_entry = |tlv_type: u8, value: &[u8]| {
+            // Values longer than 255 bytes are truncated.
+            let value = &value[..value.len().min(u8::MAX as usize)];
+            block.push(tlv_type);
+            block.push(value.len() as u8);
+            block.extend_from_slice(value);
+        };
+
+        push_entry(TLV_NAME, self.name.as_bytes());
+        push_entry(TLV_VERSION, self.version.as_bytes());
+        push_entry(TLV_BUILD_HASH, &self.build_hash.to_le_bytes());
+        if let Some(revision) = &self.source_revision {
+            push_entry(TLV_SOURCE_REVISION, revision.as_bytes());
+        }
+        if let Some(revision) = &self.root_revision {
+            push_entry(TLV_ROOT_REVISION, revision.as_bytes());
+        }
+
+        let len = block.len() as u16;
+        block.extend_from_slice(&len.to_le_bytes());
+        block
+    }
+
+    /// Returns the length of the metadata block located at the end of the
+    /// binary including its trailing length field. Returns None if the
+    /// binary is too short to contain the block.
+    pub fn trailer_len(binary: &[u8]) -> Option<usize> {
+        let len_offset = binary.len().checked_sub(2)?;
+        let len = u16::from_le_bytes([binary[len_offset], binary[len_offset + 1]]) as usize;
+        (len <= len_offset).then_some(len + 2)
+    }
+
+    /// Decodes the metadata block located at the end of the binary. Returns
+    /// None if the block is malformed.
+    pub fn decode_trailer(binary: &[u8]) -> Option<Self> {
+        let trailer_len = Self::trailer_len(binary)?;
+        let len_offset = binary.len() - 2;
+        let mut entries = &binary[binary.len() - trailer_len..len_offset];
+        let mut metadata = ProgramMetadata::default();
+        while entries.len() >= 2 {
+            let (tlv_type, value_len) = (entries[0], entries[1] as usize);
+            let value = entries.get(2..2 + value_len)?;
+            match tlv_type {
+                TLV_NAME => metadata.name = String::from_utf8(value.to_vec()).ok()?,
+                TLV_VERSION => metadata.version = String::from_utf8(value.to_vec()).ok()?,
+                TLV_BUILD_HASH => metadata.build_hash = u64::from_le_bytes(value.try_into().ok()?),
+                TLV_SOURCE_REVISION => {
+                    metadata.source_revision = Some(String::from_utf8(value.to_vec()).ok()?)
+                }
+                TLV_ROOT_REVISION => {
+                    metadata.root_revision = Some(String::from_utf8(value.to_vec()).ok()?)
+                }
+                // Unknown entries are skipped so that new fields can be added
+                // without breaking the older readers.
+                _ => {}
+            }
+            entries = &entries[2 + value_len..];
+        }
+        Some(metadata)
+    }
+}
+
+/// 64-bit FNV-1a hash, it is stable across toolchain versions as opposed to
+/// the default hasher of the standard library.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
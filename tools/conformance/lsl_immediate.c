@@ -0,0 +1,6 @@
+// TEST_RESULT: 52
+#include <stdint.h>
+int lsl_immediate(void *ctx) {
+    volatile int x = 13;
+    return x << 2;
+}
@@ -0,0 +1,12 @@
+#include "helpers.h"
+#include "microbench.h"
+#include <stdint.h>
+// Loop without any helper calls, its execution time is subtracted from the
+// other microbenchmarks so that only the overhead of the helper calls remains.
+int baseline(void *ctx) {
+    volatile uint32_t result = 0;
+    for (uint32_t i = 0; i < CALLS; i++) {
+        result += i;
+    }
+    return result;
+}
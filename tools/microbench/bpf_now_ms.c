@@ -0,0 +1,10 @@
+#include "helpers.h"
+#include "microbench.h"
+#include <stdint.h>
+int bench_now_ms(void *ctx) {
+    volatile uint32_t result = 0;
+    for (uint32_t i = 0; i < CALLS; i++) {
+        result += bpf_now_ms();
+    }
+    return result;
+}
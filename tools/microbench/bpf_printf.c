@@ -0,0 +1,11 @@
+#include "helpers.h"
+#include "microbench.h"
+#include <stdint.h>
+int bench_printf(void *ctx) {
+    volatile uint32_t result = 0;
+    for (uint32_t i = 0; i < CALLS; i++) {
+        print("%d\n", i);
+        result += i;
+    }
+    return result;
+}
@@ -0,0 +1,18 @@
+#include "helpers.h"
+#include "microbench.h"
+#include <stdint.h>
+// Reads the first SAUL device of the board, the lookup isn't measured as it
+// happens only once before the loop.
+int bench_saul_reg_read(void *ctx) {
+    bpf_saul_reg_t *dev = bpf_saul_reg_find_nth(0);
+    if (!dev) {
+        return -1;
+    }
+    phydat_t data;
+    volatile uint32_t result = 0;
+    for (uint32_t i = 0; i < CALLS; i++) {
+        bpf_saul_reg_read(dev, &data);
+        result += data.val[0];
+    }
+    return result;
+}
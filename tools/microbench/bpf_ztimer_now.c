@@ -0,0 +1,10 @@
+#include "helpers.h"
+#include "microbench.h"
+#include <stdint.h>
+int bench_ztimer_now(void *ctx) {
+    volatile uint32_t result = 0;
+    for (uint32_t i = 0; i < CALLS; i++) {
+        result += bpf_ztimer_now();
+    }
+    return result;
+}
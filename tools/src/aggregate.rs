@@ -0,0 +1,146 @@
+use std::{collections::BTreeMap, fmt};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::fleet::FleetResult;
+
+/// Statistics of a single numeric field of the responses.
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct FieldStats {
+    pub count: usize,
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Width of the buckets of the histogram.
+    pub bucket_width: f64,
+    /// Number of values falling into each bucket, keyed by the index of the
+    /// bucket so that they are ordered numerically. The lower bound of the
+    /// bucket is its index times the `bucket_width`.
+    pub histogram: BTreeMap<i64, usize>,
+}
+
+impl FieldStats {
+    fn from_values(values: &[f64], bucket_width: f64) -> Self {
+        if values.is_empty() {
+            return FieldStats::default();
+        }
+
+        let mut histogram = BTreeMap::new();
+        for value in values {
+            let bucket = (value / bucket_width).floor() as i64;
+            *histogram.entry(bucket).or_insert(0) += 1;
+        }
+
+        FieldStats {
+            count: values.len(),
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            min: values.iter().cloned().fold(f64::INFINITY, f64::min),
+            max: values.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+            bucket_width,
+            histogram,
+        }
+    }
+}
+
+/// Combined report of a fleet-wide execution.
+#[derive(Debug, Serialize)]
+pub struct FleetReport {
+    /// Number of executions that failed or returned unparseable responses.
+    pub failures: usize,
+    /// Statistics of each field computed over the responses of a single device.
+    pub devices: BTreeMap<String, BTreeMap<String, FieldStats>>,
+    /// Statistics of each field computed over the responses of all devices.
+    pub overall: BTreeMap<String, FieldStats>,
+}
+
+/// Aggregates the responses collected from the fleet. The responses are JSON
+/// objects (e.g. `{"execution_time": 10, "result": 0}`), the statistics are
+/// computed for each of the specified numeric fields.
+pub fn aggregate(results: &[FleetResult], fields: &[String], bucket_width: f64) -> FleetReport {
+    let mut failures = 0;
+    let mut per_device: BTreeMap<String, BTreeMap<String, Vec<f64>>> = BTreeMap::new();
+    let mut overall: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+
+    for result in results {
+        let Some(response) = result
+            .response
+            .as_ref()
+            .ok()
+            .and_then(|r| serde_json::from_str::<Value>(r).ok())
+        else {
+            failures += 1;
+            continue;
+        };
+
+        let device = per_device
+            .entry(result.device.riot_ipv6_addr.clone())
+            .or_default();
+        for field in fields {
+            if let Some(value) = response[field].as_f64() {
+                device.entry(field.clone()).or_default().push(value);
+                overall.entry(field.clone()).or_default().push(value);
+            }
+        }
+    }
+
+    let stats = |values: BTreeMap<String, Vec<f64>>| {
+        values
+            .into_iter()
+            .map(|(field, values)| (field, FieldStats::from_values(&values, bucket_width)))
+            .collect::<BTreeMap<String, FieldStats>>()
+    };
+
+    FleetReport {
+        failures,
+        devices: per_device
+            .into_iter()
+            .map(|(device, values)| (device, stats(values)))
+            .collect(),
+        overall: stats(overall),
+    }
+}
+
+impl fmt::Display for FieldStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "count: {}, mean: {:.2}, min: {}, max: {}",
+            self.count, self.mean, self.min, self.max
+        )
+    }
+}
+
+impl fmt::Display for FleetReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (device, fields) in &self.devices {
+            writeln!(f, "{}", device)?;
+            for (field, stats) in fields {
+                writeln!(f, "  {}: {}", field, stats)?;
+            }
+        }
+
+        writeln!(f, "Overall ({} failed executions)", self.failures)?;
+        for (field, stats) in &self.overall {
+            writeln!(f, "  {}: {}", field, stats)?;
+            for (bucket, count) in &stats.histogram {
+                let lower_bound = *bucket as f64 * stats.bucket_width;
+                writeln!(f, "    {:>10} | {}", lower_bound, "#".repeat(*count))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_are_ordered_numerically() {
+        let stats = FieldStats::from_values(&[100.0, -5.0, 25.0, 10.0, 12.0], 10.0);
+
+        let buckets = stats.histogram.into_iter().collect::<Vec<(i64, usize)>>();
+        assert_eq!(buckets, vec![(-1, 1), (1, 2), (2, 1), (10, 1)]);
+    }
+}
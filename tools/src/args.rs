@@ -1,4 +1,5 @@
 use clap::{Parser, Subcommand};
+use clap_complete::Shell;
 
 #[derive(Debug, Subcommand, PartialEq, Eq)]
 pub enum Action {
@@ -37,6 +38,22 @@ pub enum Action {
         /// verified
         #[arg(long, default_value_t = String::from("Runtime"))]
         helper_access_verification: String,
+        /// Name of the program embedded in the ExtendedHeader binary, defaults
+        /// to the name of the object file.
+        #[arg(long)]
+        program_name: Option<String>,
+        /// Version of the program embedded in the ExtendedHeader binary. The
+        /// metadata is only embedded if either the name or the version is set.
+        #[arg(long)]
+        program_version: Option<String>,
+        /// Remove the functions that can't be reached from the entry point
+        /// of the program (only the ExtendedHeader layout).
+        #[arg(long)]
+        eliminate_dead_code: bool,
+        /// Name of the function that the program starts executing from,
+        /// defaults to the first function of the .text section.
+        #[arg(long)]
+        entry: Option<String>,
     },
     /// Sign the eBPF binary for SUIT update protocol. Generates  the manifest,
     /// signs it and places all files in the CoAP fileserver root directory.
@@ -64,6 +81,23 @@ pub enum Action {
         /// bo be loaded.
         #[arg(long, short, default_value_t = 0)]
         suit_storage_slot: i32,
+
+        /// Storage backend of the SUIT storage slot, the programs stored in
+        /// the flash regions survive the device reboots.
+        /// Available options: ram, flash
+        #[arg(long, default_value_t = String::from("ram"))]
+        storage: String,
+
+        /// Sequence number of the generated SUIT manifest, defaults to the
+        /// current timestamp.
+        #[arg(long = "seq")]
+        sequence_number: Option<u64>,
+
+        /// Serve the binary compressed and advertise the compression in the
+        /// manifest, only for the devices able to decompress it.
+        /// Available options: deflate, zstd
+        #[arg(long)]
+        compression: Option<String>,
     },
 
     /// Sends a request to the RIOT instance to fetch the new signed binary
@@ -85,7 +119,8 @@ pub enum Action {
 
         /// Network interface of the machine hosting the CoAP fileserver.
         /// Used to find the IPv6 address of the fileserver.
-        #[arg(long, default_value_t = String::from("wlan0"))]
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
         host_network_interface: String,
 
         /// Network interface of the RIOT instance
@@ -106,6 +141,12 @@ pub enum Action {
         #[arg(long, short, default_value_t = 0)]
         suit_storage_slot: i32,
 
+        /// Storage backend of the SUIT storage slot, the programs stored in
+        /// the flash regions survive the device reboots.
+        /// Available options: ram, flash
+        #[arg(long, default_value_t = String::from("ram"))]
+        storage: String,
+
         /// Controlls which indices of helpers are made available to the VM
         #[clap(long, long, value_parser, num_args = 1.., value_delimiter = ' ')]
         helper_indices: Vec<u8>,
@@ -119,11 +160,21 @@ pub enum Action {
 
         #[arg(long, default_value_t = false)]
         erase: bool,
+
+        /// Directory containing the per-device OSCORE security contexts. If
+        /// specified, the requests are protected end-to-end using OSCORE.
+        #[arg(long)]
+        oscore_context_dir: Option<String>,
+
+        /// Access token attached to the requests sent to the RIOT instance.
+        #[arg(long)]
+        access_token: Option<String>,
     },
     /// Compiles, signs and initiates firmware pull in one step.
     Deploy {
-        /// Name of the file containing the eBPF source code.
-        #[arg(long)]
+        /// Name of the file containing the eBPF source code, not needed when
+        /// resuming a deployment.
+        #[arg(long, required_unless_present = "resume", default_value_t = String::new())]
         bpf_source_file: String,
 
         /// Directory for the object files
@@ -131,17 +182,22 @@ pub enum Action {
         out_dir: String,
 
         /// Target version of the eBPF vm. Available options: Femto-Containers, rBPF
-        #[arg(long, default_value_t = String::from("rBPF"))]
-        target: String,
+        /// Defaults to rBPF, unless the build configuration of the firmware
+        /// only enables the Femto-Containers VM.
+        #[arg(long)]
+        target: Option<String>,
 
         /// Layout of the binary file that the VM should expect.
         /// Available options: OnlyTextSection, FemtoContainersHeader, ExtendedHeader, RawObjectFile,
-        #[arg(long, default_value_t = String::from("ExtendedHeader"))]
-        binary_layout: String,
+        /// Defaults to ExtendedHeader, unless the target VM or the build
+        /// configuration of the firmware require a different one.
+        #[arg(long)]
+        binary_layout: Option<String>,
 
         /// Network interface of the machine hosting the CoAP fileserver.
         /// Used to find the IPv6 address of the fileserver.
-        #[arg(long, default_value_t = String::from("wlan0"))]
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
         host_network_interface: String,
 
         /// Network interface of the RIOT instance
@@ -157,10 +213,17 @@ pub enum Action {
         #[arg(long, default_value_t = String::from("coaproot"))]
         coaproot_dir: String,
 
-        /// SUIT storage slot (0 or 1) where the signed binary blob is intended
-        /// bo be loaded.
-        #[arg(long, short, default_value_t = 0)]
-        suit_storage_slot: i32,
+        /// SUIT storage slot where the signed binary blob is intended to be
+        /// loaded. Use "auto" to pick the first free slot on the device or
+        /// "auto:lru" to fall back to the least-recently-used one.
+        #[arg(long, short, visible_alias = "slot", default_value_t = String::from("0"))]
+        suit_storage_slot: String,
+
+        /// Storage backend of the SUIT storage slot, the programs stored in
+        /// the flash regions survive the device reboots.
+        /// Available options: ram, flash
+        #[arg(long, default_value_t = String::from("ram"))]
+        storage: String,
 
         /// IPv6 address of the RIOT instance.
         // We set the default value so that the argument doesn't need to be specified when
@@ -177,6 +240,12 @@ pub enum Action {
         #[clap(long, long, value_parser, num_args = 1.., value_delimiter = ' ')]
         helper_indices: Vec<u8>,
 
+        /// Name of the helper profile from the `[helper_profiles]` section of
+        /// the config file, its helpers are made available in addition to
+        /// the ones listed in --helper-indices.
+        #[arg(long)]
+        helpers: Option<String>,
+
         /// Controlls the pipeline stage at which the helpers need to be
         /// verified
         #[arg(long, default_value_t = String::from("Runtime"))]
@@ -185,6 +254,168 @@ pub enum Action {
         helper_access_list_source: String,
         #[arg(long, default_value_t = false)]
         erase: bool,
+
+        /// Directory containing the per-device OSCORE security contexts. If
+        /// specified, the requests are protected end-to-end using OSCORE.
+        #[arg(long)]
+        oscore_context_dir: Option<String>,
+
+        /// Access token attached to the requests sent to the RIOT instance.
+        #[arg(long)]
+        access_token: Option<String>,
+
+        /// Compile the program and generate the manifest locally, but only
+        /// print the requests that would be sent to the device.
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+
+        /// Sequence number of the generated SUIT manifest. By default, the
+        /// last sequence number recorded for the device gets incremented.
+        #[arg(long = "seq")]
+        sequence_number: Option<u64>,
+
+        /// Use the specified sequence number even if it isn't greater than
+        /// the one recorded for the device.
+        #[arg(long, default_value_t = false)]
+        force_seq: bool,
+
+        /// Additional programs delivered in the same update as separate
+        /// components of one SUIT manifest, specified as <source file>:<slot>.
+        #[arg(long = "bundle")]
+        bundled_programs: Vec<String>,
+
+        /// Where the artifacts are published for the device to fetch them:
+        /// a local directory served by the CoAP file server of the host or a
+        /// coap://[<address>]/<path> url. The devices only fetch over CoAP,
+        /// so the http(s):// and s3:// urls are rejected. Defaults to
+        /// serving them from the CoAP root directory.
+        #[arg(long)]
+        publish: Option<String>,
+
+        /// Deploy the program in several binary layouts at once into
+        /// consecutive SUIT storage slots, e.g. --layouts all or
+        /// --layouts OnlyTextSection,ExtendedHeader. The program is compiled
+        /// only once.
+        #[arg(long, value_delimiter = ',')]
+        layouts: Vec<String>,
+
+        /// Print how long each phase of the deployment took, e.g. to see
+        /// whether a slow deployment is bound by the toolchain or the network.
+        #[arg(long = "timings")]
+        show_timings: bool,
+
+        /// Abort the deployment if the lint finds any problems, by default
+        /// only the errors abort it and the warnings are printed.
+        #[arg(long)]
+        deny_warnings: bool,
+
+        /// Compress the .rodata section of the binary (only the ExtendedHeader
+        /// layout) if the firmware of the device supports it, e.g. when the
+        /// strings used by the program don't fit into the SUIT storage slot.
+        #[arg(long)]
+        compress_rodata: bool,
+
+        /// Serve the binary compressed so that the device fetches fewer bytes,
+        /// the device advertising no support for the compression fetches it
+        /// uncompressed. Available options: deflate, zstd
+        #[arg(long)]
+        artifact_compression: Option<String>,
+
+        /// Remove the functions that can't be reached from the entry point
+        /// of the program (only the ExtendedHeader layout), e.g. the unused
+        /// functions of shared headers, to save space in the slot.
+        #[arg(long)]
+        eliminate_dead_code: bool,
+
+        /// Embed the name, version and build provenance of the program into
+        /// the binary (only the ExtendedHeader layout) so that the device
+        /// reports them when listing its SUIT storage slots. The firmware of
+        /// the device needs to support the metadata block.
+        #[arg(long)]
+        embed_metadata: bool,
+
+        /// Name of the function that the program starts executing from,
+        /// defaults to the first function of the .text section. Not
+        /// supported by the RawObjectFile layout. If given multiple times,
+        /// the first one is the default entry and the others can be
+        /// selected when executing the program (see `execute --entry`), so
+        /// that several handlers share the code deployed in one slot.
+        #[arg(long = "entry")]
+        entries: Vec<String>,
+
+        /// Resume the last deployment which failed after the program was
+        /// signed (e.g. because the device couldn't be reached), the
+        /// completed steps aren't repeated.
+        #[arg(long)]
+        resume: bool,
+    },
+    /// Compiles the program and runs it locally in the rbpf VM with stub
+    /// implementations of the RIOT helpers, no device is needed.
+    Simulate {
+        /// Name of the file containing the eBPF source code.
+        #[arg(long)]
+        bpf_source_file: String,
+
+        /// Directory for the object files
+        #[arg(long, default_value_t = String::from("./out"))]
+        out_dir: String,
+
+        /// Helpers that the program is allowed to call, all by default.
+        #[clap(long, long, value_parser, num_args = 1.., value_delimiter = ' ')]
+        helper_indices: Vec<u8>,
+
+        /// Overrides the return value of a helper, specified as
+        /// <helper index>=<value>, e.g. --stub 0x34=2150
+        #[arg(long = "stub")]
+        stubs: Vec<String>,
+
+        /// Fake sensor readings returned by the SAUL helpers in a round-robin
+        /// fashion, e.g. --sensor-readings 2150,2175,2200
+        #[arg(long, value_delimiter = ',')]
+        sensor_readings: Vec<u64>,
+
+        /// File with the contents of the memory passed to the program (e.g.
+        /// a captured CoAP packet).
+        #[arg(long)]
+        context_file: Option<String>,
+
+        /// Names a part of the context in the diagnostics of the
+        /// out-of-bounds accesses, specified as <name>:<offset>:<length>,
+        /// e.g. --region header:0:4
+        #[arg(long = "region")]
+        regions: Vec<String>,
+
+        /// TOML file scripting the results of the helpers, e.g. a sequence
+        /// of sensor readings or a clock advancing by fixed steps.
+        #[arg(long)]
+        scenario: Option<String>,
+    },
+    /// Compiles the program and checks it for constructs known to break on
+    /// the device VMs (64-bit division, large stack arrays, unaligned accesses,
+    /// calls to helpers outside of the allowed set).
+    Lint {
+        /// Name of the file containing the eBPF source code.
+        #[arg(long)]
+        bpf_source_file: String,
+
+        /// Directory for the object files
+        #[arg(long, default_value_t = String::from("./out"))]
+        out_dir: String,
+
+        /// Target version of the eBPF vm. Available options: Femto-Containers, rBPF
+        #[arg(long, default_value_t = String::from("rBPF"))]
+        target: String,
+
+        /// Helpers that the program is allowed to call, all by default.
+        #[clap(long, long, value_parser, num_args = 1.., value_delimiter = ' ')]
+        helper_indices: Vec<u8>,
+
+        #[arg(short)]
+        jit: bool,
+
+        /// Fail if the lint finds any warnings.
+        #[arg(long)]
+        deny_warnings: bool,
     },
     /// Sends a request to the RIOT instance to execute the loaded eBPF bytecode
     /// from a specified SUIT storage slot.
@@ -194,13 +425,17 @@ pub enum Action {
         riot_ipv6_addr: String,
 
         /// Target version of the eBPF vm. Available options: Femto-Containers, rBPF
-        #[arg(long, default_value_t = String::from("rBPF"))]
-        target: String,
+        /// Defaults to rBPF, unless the build configuration of the firmware
+        /// only enables the Femto-Containers VM.
+        #[arg(long)]
+        target: Option<String>,
 
         /// Layout of the binary file that the VM should expect.
         /// Available options: OnlyTextSection, FemtoContainersHeader, ExtendedHeader, RawObjectFile,
-        #[arg(long, default_value_t = String::from("ExtendedHeader"))]
-        binary_layout: String,
+        /// Defaults to ExtendedHeader, unless the target VM or the build
+        /// configuration of the firmware require a different one.
+        #[arg(long)]
+        binary_layout: Option<String>,
 
         /// SUIT storage slot (0 or 1) where the signed binary blob is intended
         /// bo be loaded.
@@ -209,7 +444,8 @@ pub enum Action {
 
         /// Network interface of the machine hosting the CoAP fileserver.
         /// Used to find the IPv6 address of the fileserver.
-        #[arg(long, default_value_t = String::from("wlan0"))]
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
         host_network_interface: String,
 
         /// Which execution model should be used by the vm, avaliable options: ShortLived,
@@ -221,6 +457,12 @@ pub enum Action {
         /// Controlls which indices of helpers are made available to the VM
         #[clap(long, long, value_parser, num_args = 1.., value_delimiter = ' ')]
         helper_indices: Vec<u8>,
+
+        /// Name of the helper profile from the `[helper_profiles]` section of
+        /// the config file, its helpers are made available in addition to
+        /// the ones listed in --helper-indices.
+        #[arg(long)]
+        helpers: Option<String>,
         /// Controlls the pipeline stage at which the helpers need to be
         /// verified
         #[arg(long, default_value_t = String::from("Runtime"))]
@@ -234,23 +476,1647 @@ pub enum Action {
         jit_compile: bool,
         #[arg(short)]
         benchmark: bool,
+        /// If the requested VM (or its JIT) isn't available in the firmware of
+        /// the device, execute the program on the rBPF interpreter instead.
+        #[arg(long)]
+        fallback_to_interpreter: bool,
+
+        /// Directory containing the per-device OSCORE security contexts. If
+        /// specified, the requests are protected end-to-end using OSCORE.
+        #[arg(long)]
+        oscore_context_dir: Option<String>,
+
+        /// Access token attached to the requests sent to the RIOT instance.
+        #[arg(long)]
+        access_token: Option<String>,
+
+        /// Only print the request that would be sent to the device.
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+
+        /// Host-side post-processing stages that the response is piped through,
+        /// e.g. --stage scale:result:0.01 --stage threshold:result::30
+        /// --stage alert-webhook:http://localhost:8080/alerts
+        #[arg(long = "stage")]
+        stages: Vec<String>,
+
+        /// Print a summary of the execution (program, slot, layout, VM,
+        /// helpers, execution time and result) instead of the raw response.
+        #[arg(long)]
+        report: bool,
+
+        /// Hex-encoded bytes injected into the payload of the packet that the
+        /// program sees, only for the WithAccessToCoapPacket execution model.
+        #[arg(long, conflicts_with = "packet_payload_file")]
+        packet_payload: Option<String>,
+
+        /// File with the raw bytes injected into the payload of the packet.
+        #[arg(long)]
+        packet_payload_file: Option<String>,
+
+        /// Uri-Query option added to the packet, e.g. --packet-query sensor=2
+        #[arg(long = "packet-query")]
+        packet_queries: Vec<String>,
+
+        /// File with the raw bytes passed to the program as its execution
+        /// arguments, e.g. the weights of a TinyML model.
+        #[arg(long)]
+        arguments_file: Option<String>,
+
+        /// Compress the execution arguments using deflate, only if the device
+        /// reports that it is able to decompress them.
+        #[arg(long, requires = "arguments_file")]
+        compress_arguments: bool,
+
+        /// Name of the entry function exported by the program (see
+        /// `deploy --entry`) that the execution starts from, the default
+        /// entry if not given.
+        #[arg(long)]
+        entry: Option<String>,
+
+        /// Decode the buffer returned by the program (see bpf_set_return_buffer)
+        /// as an array of the given type: u8, i8, u16, i16, u32, i32, u64, i64
+        #[arg(long)]
+        buffer_type: Option<String>,
+
+        /// Maximum number of instructions the program is allowed to execute.
+        /// The response then reports the number of executed instructions,
+        /// which allows for comparing programs independently of the clock
+        /// frequency of the device.
+        #[arg(long)]
+        gas_limit: Option<u64>,
+
+        /// Fails with a distinct exit code (5) if the value returned by the
+        /// program differs from the expected one.
+        #[arg(long)]
+        expect_result: Option<i64>,
+
+        /// Handle of a key in the keystore of the device passed to the
+        /// program as an execution argument (for the crypto helpers), can be
+        /// repeated. The keys are checked to exist before the execution.
+        #[arg(long = "key-handle")]
+        key_handles: Vec<u32>,
+
+        /// Seed of the random number generator that the program draws from.
+        /// The seed used by each execution is included in its response, so
+        /// passing it again reproduces the execution exactly.
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Asks the device to log each helper call made by the program
+        /// (helper ID, arguments and return value) and prints the log with
+        /// the helpers named. Useful when the program behaves differently on
+        /// the device than in the simulation.
+        #[arg(long)]
+        trace_helpers: bool,
+
+        /// If the device is busy (5.03), wait in the client-side queue of the
+        /// device for at most this many seconds instead of failing, see the
+        /// `queue` command. Defaults to QUEUE_TIMEOUT of the device profile.
+        #[arg(long)]
+        queue_timeout: Option<u64>,
 
+        /// Content-Formats of the response accepted from the device in the
+        /// order of preference, e.g. --accept cbor,json. Available options:
+        /// json, cbor, octet-stream. If it isn't specified, the device
+        /// responds using JSON.
+        #[arg(long, value_delimiter = ',')]
+        accept: Vec<String>,
+
+        /// Directory containing the pinned public keys of the devices. If the
+        /// key of the device is pinned, its responses need to be signed by it.
+        #[arg(long)]
+        identity_dir: Option<String>,
     },
-}
+    /// Executes several programs one after another, the buffer returned by
+    /// each of them is passed to the next one in the payload of its packet.
+    Chain {
+        /// Programs of the chain in the order of execution, given as
+        /// `<slot>[@<ipv6 address>]`, e.g. `--step 0 --step 1@fe80::2`.
+        #[arg(long = "step", required = true)]
+        steps: Vec<String>,
 
-/// Tools for compiling, signing, loading and executing eBPF programs for
-/// micro_bpf.
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
-pub struct Args {
-    /// The command that is to be performed. Available options: Compile, Sign,
-    /// Pull, Execute.
-    #[command(subcommand)]
-    pub command: Action,
+        /// Target version of the eBPF vm. Available options: Femto-Containers, rBPF
+        #[arg(long, default_value_t = String::from("rBPF"))]
+        target: String,
 
-    /// Use the .env file to configure missing arguments. Defaults to sourcing
-    /// the ".env" file, the loaded file can be overridden by setting the
-    /// environment variable "DOTENV".
-    #[arg(long, default_value_t = false)]
-    pub use_env: bool,
+        /// Layout of the binary file that the VM should expect.
+        /// Available options: OnlyTextSection, FemtoContainersHeader, ExtendedHeader, RawObjectFile,
+        #[arg(long, default_value_t = String::from("ExtendedHeader"))]
+        binary_layout: String,
+
+        #[arg(short)]
+        jit: bool,
+
+        /// Framing of the state passed between the programs.
+        /// Available options: raw, length-prefixed
+        #[arg(long, default_value_t = String::from("raw"))]
+        state_encoding: String,
+
+        /// Hex-encoded state passed to the first program.
+        #[arg(long)]
+        initial_state: Option<String>,
+
+        /// IPv6 address of the device executing the steps which don't specify
+        /// one.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+
+        /// Network interface of the machine hosting the CoAP fileserver.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+
+        /// Directory containing the per-device OSCORE security contexts. If
+        /// specified, the requests are protected end-to-end using OSCORE.
+        #[arg(long)]
+        oscore_context_dir: Option<String>,
+
+        /// Access token attached to the requests sent to the RIOT instance.
+        #[arg(long)]
+        access_token: Option<String>,
+    },
+    /// Lists the programs stored in the SUIT storage slots of the device.
+    List {
+        /// IPv6 address of the RIOT instance.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+
+        /// Network interface of the machine hosting the CoAP fileserver.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+    },
+    /// Copies the raw contents of the SUIT storage slots between the device
+    /// and the host, e.g. to clone the programs of a reference device.
+    Slot {
+        #[command(subcommand)]
+        action: SlotAction,
+    },
+    /// Inspects the executions waiting for the busy devices, see
+    /// `execute --queue-timeout`.
+    Queue {
+        #[command(subcommand)]
+        action: QueueAction,
+    },
+    /// Queries the history of the execution results recorded by `execute`.
+    Results {
+        #[command(subcommand)]
+        action: ResultsAction,
+    },
+    /// Repeatedly executes the program loaded in the SUIT storage slot and
+    /// reports the latency and (optionally) the energy per execution.
+    Bench {
+        /// IPv6 address of the RIOT instance.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+
+        /// Network interface of the machine hosting the CoAP fileserver.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+
+        /// Target version of the eBPF vm. Available options: Femto-Containers, rBPF
+        #[arg(long, default_value_t = String::from("rBPF"))]
+        target: String,
+
+        /// Layout of the binary file that the VM should expect.
+        /// Available options: OnlyTextSection, FemtoContainersHeader, ExtendedHeader, RawObjectFile,
+        #[arg(long, default_value_t = String::from("ExtendedHeader"))]
+        binary_layout: String,
+
+        /// SUIT storage slot of the benchmarked program.
+        #[arg(long, short, default_value_t = 0)]
+        suit_storage_slot: usize,
+
+        /// Controlls which indices of helpers are made available to the VM
+        #[clap(long, long, value_parser, num_args = 1.., value_delimiter = ' ')]
+        helper_indices: Vec<u8>,
+
+        #[arg(short)]
+        jit: bool,
+
+        /// Number of benchmarked executions.
+        #[arg(long, default_value_t = 10)]
+        iterations: usize,
+
+        /// Script controlling the power measurement instrument, it is invoked
+        /// as `<script> start` and `<script> stop`, see [`crate::power::ScriptProbe`].
+        #[arg(long)]
+        power_probe: Option<String>,
+
+        /// Milliseconds over which the idle power consumption is measured.
+        #[arg(long, default_value_t = 1000)]
+        idle_window: u64,
+
+        /// Append each result to the given file as newline-delimited JSON
+        /// (with the timestamp and the device address) as soon as it is
+        /// collected, see [`crate::record::ResultRecorder`].
+        #[arg(long)]
+        record: Option<String>,
+
+        /// Size in MiB after which the file with the recorded results is
+        /// rotated, the last 5 rotated files are kept.
+        #[arg(long, default_value_t = 64)]
+        record_max_size: u64,
+
+        #[command(subcommand)]
+        mode: Option<BenchMode>,
+    },
+    /// Sends execution requests for the program loaded in the SUIT storage
+    /// slot at an increasing rate until the device starts failing, and
+    /// reports the maximum sustainable rate and the kinds of the errors.
+    Stress {
+        /// IPv6 address of the RIOT instance.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+
+        /// Network interface of the machine hosting the CoAP fileserver.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+
+        /// Target version of the eBPF vm. Available options: Femto-Containers, rBPF
+        #[arg(long, default_value_t = String::from("rBPF"))]
+        target: String,
+
+        /// Layout of the binary file that the VM should expect.
+        /// Available options: OnlyTextSection, FemtoContainersHeader, ExtendedHeader, RawObjectFile,
+        #[arg(long, default_value_t = String::from("ExtendedHeader"))]
+        binary_layout: String,
+
+        /// SUIT storage slot of the executed program.
+        #[arg(long, short, default_value_t = 0)]
+        suit_storage_slot: usize,
+
+        /// Controlls which indices of helpers are made available to the VM
+        #[clap(long, long, value_parser, num_args = 1.., value_delimiter = ' ')]
+        helper_indices: Vec<u8>,
+
+        #[arg(short)]
+        jit: bool,
+
+        /// Requests per second sent in the first step.
+        #[arg(long, default_value_t = 1)]
+        start_rate: u32,
+
+        /// Increase of the request rate (per second) after each step.
+        #[arg(long, default_value_t = 1)]
+        rate_step: u32,
+
+        /// Request rate (per second) at which the test stops even if the
+        /// device keeps up.
+        #[arg(long, default_value_t = 50)]
+        max_rate: u32,
+
+        /// Seconds for which the requests are sent at each rate.
+        #[arg(long, default_value_t = 10)]
+        step_duration: u64,
+
+        /// Percentage of the failed requests above which the rate isn't
+        /// considered sustainable.
+        #[arg(long, default_value_t = 5)]
+        error_threshold: u32,
+
+        /// Stop sending the requests after this many failures in a row so
+        /// that the firmware isn't overwhelmed beyond recovery.
+        #[arg(long, default_value_t = 5)]
+        max_consecutive_failures: usize,
+    },
+    /// Creates an OSCORE security context for a given device in the per-device
+    /// security context store.
+    OscoreContext {
+        /// IPv6 address of the RIOT instance that the context is created for.
+        #[arg(long)]
+        riot_ipv6_addr: String,
+
+        /// Directory containing the per-device OSCORE security contexts.
+        #[arg(long, default_value_t = String::from("oscore"))]
+        oscore_context_dir: String,
+
+        /// Hex-encoded sender ID used by the tool.
+        #[arg(long, default_value_t = String::from("01"))]
+        sender_id: String,
+
+        /// Hex-encoded recipient ID used by the device.
+        #[arg(long, default_value_t = String::from("00"))]
+        recipient_id: String,
+
+        /// Hex-encoded master secret shared with the device.
+        #[arg(long)]
+        master_secret: String,
+    },
+    /// Pins the identity of a device to its public key, the responses of the
+    /// device are then only accepted if they are signed by that key.
+    PinIdentity {
+        /// IPv6 address of the RIOT instance whose identity is pinned.
+        #[arg(long)]
+        riot_ipv6_addr: String,
+
+        /// DER-encoded ed25519 public key of the device.
+        #[arg(long)]
+        public_key: String,
+
+        /// Directory containing the pinned public keys of the devices.
+        #[arg(long, default_value_t = String::from("identities"))]
+        identity_dir: String,
+    },
+    /// Compares two compiled artifacts instruction-by-instruction and reports
+    /// added/removed/changed instructions and size deltas.
+    Diff {
+        /// The binary produced by the previous version of the program.
+        #[arg(long)]
+        old_binary: String,
+
+        /// The binary produced by the new version of the program.
+        #[arg(long)]
+        new_binary: String,
+
+        /// Layout of the two compared binary files.
+        /// Available options: OnlyTextSection, FemtoContainersHeader, ExtendedHeader, RawObjectFile, auto
+        #[arg(long, default_value_t = String::from("auto"))]
+        binary_layout: String,
+    },
+    /// Lifts the bytecode of a compiled artifact (e.g. a slot backup of a
+    /// program whose source was lost) into C-like pseudocode.
+    Decompile {
+        /// The binary containing the program.
+        #[arg(long)]
+        binary_file: String,
+
+        /// Layout of the binary file.
+        /// Available options: OnlyTextSection, FemtoContainersHeader, ExtendedHeader, RawObjectFile, auto
+        #[arg(long, default_value_t = String::from("auto"))]
+        binary_layout: String,
+    },
+    /// Generates the C header defining the context struct that the firmware
+    /// passes to the programs using the given execution model.
+    GenContext {
+        /// Available options: WithAccessToCoapPacket
+        #[arg(long, default_value_t = String::from("WithAccessToCoapPacket"))]
+        execution_model: String,
+
+        /// Architecture the firmware is built for, it determines the size of
+        /// size_t and the alignment of the fields.
+        /// Available options: native, native64, cortex-m, riscv32
+        #[arg(long, default_value_t = String::from("native"))]
+        arch: String,
+
+        /// File where the header should be written, printed to stdout if not
+        /// specified.
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Recovers the device without physical access to the board, e.g. when a
+    /// long-running program wedges the VM.
+    Device {
+        #[command(subcommand)]
+        action: DeviceAction,
+    },
+    /// Attaches the deployed programs to the hooks of the network stack of
+    /// the device (e.g. as packet filters) and retrieves their results.
+    Hook {
+        #[command(subcommand)]
+        action: HookAction,
+    },
+    /// Manages the key material used by the programs through the crypto
+    /// helpers of the device.
+    Keystore {
+        #[command(subcommand)]
+        action: KeystoreAction,
+    },
+    /// Checks which helpers are compiled into the firmware of the device.
+    Helpers {
+        #[command(subcommand)]
+        action: HelpersAction,
+    },
+    /// Obtains access tokens from the authorization server of the testbed and
+    /// stores them in the .env file of the current device profile.
+    Token {
+        #[command(subcommand)]
+        action: TokenAction,
+    },
+    /// Runs the built-in corpus of conformance programs against the device
+    /// configured in the .env file of the current device profile.
+    Conformance {
+        #[command(subcommand)]
+        action: ConformanceAction,
+    },
+    /// Operates on a fleet of devices listed in a fleet file.
+    Fleet {
+        #[command(subcommand)]
+        action: FleetAction,
+    },
+    /// Inspects the audit log of the deployments and executions performed on
+    /// the devices (written if MIBPF_AUDIT_LOG is set).
+    Audit {
+        #[command(subcommand)]
+        action: AuditAction,
+    },
+    /// Manages the keys used for signing the SUIT manifests.
+    Keys {
+        #[command(subcommand)]
+        action: KeysAction,
+    },
+    /// Monitors a long-running program on the device configured in the .env
+    /// file of the current device profile and restarts it if it dies.
+    Watchdog {
+        /// SUIT storage slot of the monitored long-running program.
+        #[arg(long, short, default_value_t = 0)]
+        suit_storage_slot: usize,
+
+        /// Target version of the eBPF vm. Available options: Femto-Containers, rBPF
+        #[arg(long, default_value_t = String::from("rBPF"))]
+        target: String,
+
+        /// Layout of the binary file that the VM should expect.
+        /// Available options: OnlyTextSection, FemtoContainersHeader, ExtendedHeader, RawObjectFile,
+        #[arg(long, default_value_t = String::from("ExtendedHeader"))]
+        binary_layout: String,
+
+        /// Seconds between two consecutive health probes.
+        #[arg(long, default_value_t = 10)]
+        interval: u64,
+
+        /// Number of consecutive failed probes after which the program is
+        /// restarted.
+        #[arg(long, default_value_t = 3)]
+        failure_threshold: u32,
+
+        /// Maximum number of restarts, unlimited if not specified.
+        #[arg(long)]
+        max_restarts: Option<u32>,
+
+        /// Webhook notified whenever the program dies or gets restarted.
+        #[arg(long)]
+        notify_url: Option<String>,
+
+        /// Source file of the program, if specified the program is redeployed
+        /// before being restarted.
+        #[arg(long)]
+        redeploy: Option<String>,
+
+        #[arg(short)]
+        jit: bool,
+
+        /// Append each result to the given file as newline-delimited JSON
+        /// (with the timestamp and the device address) as soon as it is
+        /// collected, see [`crate::record::ResultRecorder`].
+        #[arg(long)]
+        record: Option<String>,
+
+        /// Size in MiB after which the file with the recorded results is
+        /// rotated, the last 5 rotated files are kept.
+        #[arg(long, default_value_t = 64)]
+        record_max_size: u64,
+    },
+    /// Deploys a sensing program onto the device configured in the .env file
+    /// of the current device profile, runs it periodically and writes the
+    /// samples that it prints into a time-series file.
+    Collect {
+        /// Source file of the sensing program. It reports the readings by
+        /// printing lines of `key=value` pairs or flat JSON objects.
+        #[arg(long)]
+        bpf_source_file: String,
+
+        /// Target version of the eBPF vm. Available options: Femto-Containers, rBPF
+        #[arg(long, default_value_t = String::from("rBPF"))]
+        target: String,
+
+        /// Layout of the binary file that the VM should expect.
+        /// Available options: OnlyTextSection, FemtoContainersHeader, ExtendedHeader, RawObjectFile,
+        #[arg(long, default_value_t = String::from("ExtendedHeader"))]
+        binary_layout: String,
+
+        /// SUIT storage slot that the sensing program is deployed into.
+        #[arg(long, short, default_value_t = 0)]
+        suit_storage_slot: usize,
+
+        #[arg(short)]
+        jit: bool,
+
+        /// Milliseconds between two consecutive runs of the program.
+        #[arg(long, default_value_t = 1000)]
+        period: u64,
+
+        /// Milliseconds between fetching the output of the program.
+        #[arg(long, default_value_t = 1000)]
+        poll_interval: u64,
+
+        /// Seconds after which the collection stops, it runs until
+        /// interrupted if not specified.
+        #[arg(long)]
+        duration: Option<u64>,
+
+        /// Additional devices (IPv6 addresses reachable through the same
+        /// host interface) that the program is deployed onto.
+        #[arg(long = "device")]
+        devices: Vec<String>,
+
+        /// Format of the samples. Available options: csv, influx
+        #[arg(long, default_value_t = String::from("csv"))]
+        format: String,
+
+        /// Name of the measurement when writing the InfluxDB line protocol.
+        #[arg(long, default_value_t = String::from("mibpf"))]
+        measurement: String,
+
+        /// File that the samples are appended to, printed to stdout if not
+        /// specified.
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Interactive dashboard showing the devices, the programs in their
+    /// slots, the last execution results and the live logs, the selected
+    /// slot can be executed, stopped and redeployed from it.
+    #[cfg(feature = "tui")]
+    Tui {
+        /// File listing the devices shown in the dashboard, one
+        /// <ipv6 address>%<interface> per line. Defaults to the device
+        /// configured in the .env file. The devices added to or removed from
+        /// the file are picked up while the dashboard is running.
+        #[arg(long)]
+        fleet_file: Option<String>,
+
+        /// Seconds between two consecutive polls of each device.
+        #[arg(long, default_value_t = 2)]
+        refresh: u64,
+
+        /// Source file of the program deployed into the selected slot when
+        /// pressing `d`.
+        #[arg(long)]
+        deploy: Option<String>,
+
+        /// Target version of the eBPF vm. Available options: Femto-Containers, rBPF
+        #[arg(long, default_value_t = String::from("rBPF"))]
+        target: String,
+
+        /// Layout of the binary file that the VM should expect.
+        /// Available options: OnlyTextSection, FemtoContainersHeader, ExtendedHeader, RawObjectFile,
+        #[arg(long, default_value_t = String::from("ExtendedHeader"))]
+        binary_layout: String,
+
+        #[arg(short)]
+        jit: bool,
+    },
+    /// Prints the log lines printed by the programs running on the device.
+    Logs {
+        /// IPv6 address of the RIOT instance.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+
+        /// Network interface of the machine hosting the CoAP fileserver.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+
+        /// Only print the lines logged by the program in the given SUIT storage
+        /// slot.
+        #[arg(long, short)]
+        suit_storage_slot: Option<usize>,
+
+        /// Keep polling the device for new log lines.
+        #[arg(long, short)]
+        follow: bool,
+
+        /// Milliseconds between two consecutive polls when following the logs.
+        #[arg(long, default_value_t = 1000)]
+        interval: u64,
+    },
+    /// Runs a UDP proxy between the client and the device which injects packet
+    /// loss, duplication, reordering and delay into the traffic. The requests
+    /// need to be addressed to the proxy instead of the device.
+    Proxy {
+        /// Address the proxy listens on for the requests of the client.
+        #[arg(long, default_value_t = String::from("[::1]:5683"))]
+        listen: String,
+
+        /// IPv6 address of the RIOT instance.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+
+        /// Port of the CoAP server of the RIOT instance.
+        #[arg(long, default_value_t = 5683)]
+        riot_port: u16,
+
+        /// Network interface used to reach the RIOT instance.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+
+        /// Probability (0.0 - 1.0) that a datagram is dropped.
+        #[arg(long, default_value_t = String::from("0"))]
+        loss: String,
+
+        /// Probability that a datagram is delivered twice.
+        #[arg(long, default_value_t = String::from("0"))]
+        duplicate: String,
+
+        /// Probability that a datagram is overtaken by the ones sent after it.
+        #[arg(long, default_value_t = String::from("0"))]
+        reorder: String,
+
+        /// Delay added to every datagram in milliseconds.
+        #[arg(long, default_value_t = 0)]
+        delay_ms: u64,
+
+        /// Maximum random delay added on top of --delay-ms in milliseconds.
+        #[arg(long, default_value_t = 0)]
+        jitter_ms: u64,
+
+        /// Seed of the fault injection, allows for reproducing a run.
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// TOML file assigning the slots of the shared devices to the users,
+        /// the requests using the slots of other users are rejected, see
+        /// [`crate::quota::SlotPolicy`].
+        #[arg(long)]
+        slot_policy: Option<String>,
+
+        /// Tag of the device behind the proxy (e.g. `shared`) selecting the
+        /// quotas of the slot policy that apply to it, can be repeated.
+        #[arg(long = "device-tag")]
+        device_tags: Vec<String>,
+    },
+    /// Serves the local verification pipeline (layout constraints, lint,
+    /// post-processing and the ahead-of-time helper check) over HTTP, so
+    /// that the CI of other projects can check their object files for
+    /// compatibility without installing the toolchain.
+    VerifyServer {
+        /// Address the server listens on.
+        #[arg(long, default_value_t = String::from("[::1]:8080"))]
+        listen: String,
+
+        /// Directory where the transformed artifacts are stored and served
+        /// from.
+        #[arg(long, default_value_t = String::from("verify-server-out"))]
+        work_dir: String,
+    },
+    /// Runs the evaluation experiment described by a YAML spec against the
+    /// devices of the current device profile (or the ones listed in the
+    /// spec) and emits the aggregate tables.
+    Experiment {
+        /// Path to the YAML file describing the experiment.
+        spec: String,
+
+        /// Directory where the raw data and the tables of the run are stored.
+        #[arg(long, default_value_t = String::from("experiments"))]
+        output_dir: String,
+
+        /// Print the requests instead of sending them.
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+    /// Benchmarks the programs using an experiment preset from the config
+    /// file, e.g. `run --preset jit-comparison program.c`, see
+    /// [`crate::experiment::Preset`].
+    Run {
+        /// Name of the `[preset.<name>]` section of the config file.
+        #[arg(long)]
+        preset: String,
+
+        /// Source files of the benchmarked programs.
+        #[arg(required = true)]
+        programs: Vec<String>,
+
+        /// Directory where the raw data and the tables of the run are stored.
+        #[arg(long, default_value_t = String::from("experiments"))]
+        output_dir: String,
+
+        /// Print the requests instead of sending them.
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+    /// Executes the already deployed programs in every combination of the
+    /// parameter grid of a YAML spec (slots x argument values x helper sets)
+    /// and emits the results as a long-format CSV, see
+    /// [`crate::sweep::SweepSpec`].
+    Sweep {
+        /// Path to the YAML file describing the execution template and the
+        /// parameter grid.
+        spec: String,
+
+        /// File that the CSV is written to, printed if not specified.
+        #[arg(long)]
+        output: Option<String>,
+
+        /// Print the requests instead of sending them.
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+    /// Builds the micro_bpf firmware and flashes it onto the board using the
+    /// RIOT build system, optionally waiting for the device to come online.
+    Flash {
+        /// Name of the target board, passed to make as BOARD.
+        #[arg(long, default_value_t = String::from("native"))]
+        board_name: String,
+
+        /// Root directory of the mibpf repository.
+        #[arg(long, default_value_t = String::from("."))]
+        mibpf_root_dir: String,
+
+        /// Directory of the RIOT application, defaults to <mibpf-root-dir>/src.
+        #[arg(long)]
+        app_dir: Option<String>,
+
+        /// Serial port of the board, passed to make as PORT.
+        #[arg(long)]
+        port: Option<String>,
+
+        /// Additional argument passed to make, e.g. --make-arg PROGRAMMER=openocd
+        #[arg(long = "make-arg")]
+        make_args: Vec<String>,
+
+        /// Wait until the device responds on the network after flashing.
+        #[arg(long, default_value_t = false)]
+        wait: bool,
+
+        /// Seconds to wait for the device to appear on the network.
+        #[arg(long, default_value_t = 60)]
+        wait_timeout: u64,
+
+        /// IPv6 address of the RIOT instance, used when waiting for it.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+
+        /// Network interface of the machine hosting the CoAP fileserver.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+    },
+    /// Inspects and modifies the configuration of the current device profile.
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Generates the shell completion script and prints it to stdout, e.g.
+    /// `micro-bpf-tools completions bash > /etc/bash_completion.d/micro-bpf-tools`
+    /// The bash and fish scripts also complete the device profiles, device
+    /// addresses, slots, test programs and helper profiles.
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Lists the values of the given kind for the shell completion scripts.
+    /// Available options: profiles, devices, slots, programs, helper-profiles
+    #[command(hide = true)]
+    Complete {
+        kind: String,
+
+        /// Follow each value with a tab and its description (fish format).
+        #[arg(long)]
+        descriptions: bool,
+    },
+    /// Generates the man pages of the tool and all of its subcommands.
+    Manpages {
+        /// Directory where the man pages are written.
+        #[arg(long, default_value_t = String::from("man"))]
+        out_dir: String,
+    },
+}
+
+#[derive(Debug, Subcommand, PartialEq, Eq)]
+pub enum ConfigAction {
+    /// Prints the values of the variables of the current device profile.
+    Show {
+        /// Also print which configuration layer (default, config.toml, .env,
+        /// process environment or --set) each value was taken from.
+        #[arg(long)]
+        resolved: bool,
+    },
+    /// Sets the value of a variable in the .env file of the current device
+    /// profile, e.g. `config set RIOT_INSTANCE_IP fe80::1`
+    Set { key: String, value: String },
+}
+
+#[derive(Debug, Subcommand, PartialEq, Eq)]
+pub enum TokenAction {
+    /// Requests a new access token for the device.
+    Issue {
+        /// Url of the authorization server, e.g. coap://[fe80::1%tapbr0]
+        #[arg(long)]
+        authorization_server: String,
+
+        /// Identifier of the user requesting the token.
+        #[arg(long)]
+        client_id: String,
+
+        /// Secret used to authenticate the user.
+        #[arg(long)]
+        client_secret: String,
+
+        /// IPv6 address of the RIOT instance that the token is issued for.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+    },
+    /// Exchanges the refresh token stored in the device profile for a new
+    /// access token.
+    Refresh {
+        /// Url of the authorization server, e.g. coap://[fe80::1%tapbr0]
+        #[arg(long)]
+        authorization_server: String,
+    },
+}
+
+#[derive(Debug, Subcommand, PartialEq, Eq)]
+pub enum FleetAction {
+    /// Executes the program loaded in the SUIT storage slot at the same
+    /// instant on all devices of the fleet and collects the results.
+    Execute {
+        /// File listing the devices of the fleet, one <ipv6 address>%<interface>
+        /// per line.
+        #[arg(long, default_value_t = String::from("fleet.txt"))]
+        fleet_file: String,
+
+        /// Network interface used for the devices that don't specify one.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+
+        /// Target version of the eBPF vm. Available options: Femto-Containers, rBPF
+        #[arg(long, default_value_t = String::from("rBPF"))]
+        target: String,
+
+        /// Layout of the binary file that the VM should expect.
+        /// Available options: OnlyTextSection, FemtoContainersHeader, ExtendedHeader, RawObjectFile,
+        #[arg(long, default_value_t = String::from("ExtendedHeader"))]
+        binary_layout: String,
+
+        /// SUIT storage slot of the executed program.
+        #[arg(long, short, default_value_t = 0)]
+        suit_storage_slot: usize,
+
+        /// Controlls which indices of helpers are made available to the VM
+        #[clap(long, long, value_parser, num_args = 1.., value_delimiter = ' ')]
+        helper_indices: Vec<u8>,
+
+        /// Milliseconds between distributing the schedule and the execution.
+        #[arg(long, default_value_t = 2000)]
+        lead_time: u64,
+
+        /// Milliseconds to wait after the trigger before collecting the results.
+        #[arg(long, default_value_t = 500)]
+        collection_delay: u64,
+
+        #[arg(short)]
+        jit: bool,
+
+        /// Number of times the synchronized execution is repeated.
+        #[arg(long, default_value_t = 1)]
+        rounds: usize,
+
+        /// Numeric fields of the responses that the statistics are computed for.
+        #[arg(long = "field", default_values_t = [String::from("result"), String::from("execution_time")])]
+        fields: Vec<String>,
+
+        /// Width of the buckets of the value histograms.
+        #[arg(long, default_value_t = String::from("1"))]
+        bucket_width: String,
+
+        /// Print the raw responses of the devices instead of the combined report.
+        #[arg(long, default_value_t = false)]
+        raw: bool,
+
+        /// Print the combined report as JSON.
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Deploys the program to the devices of the fleet in batches, each batch
+    /// is validated by executing the program before the rollout proceeds to
+    /// the next one. The build and signing settings are taken from the .env
+    /// file of the current device profile.
+    Deploy {
+        /// File listing the devices of the fleet, either a plain fleet file
+        /// or a TOML fleet config.
+        #[arg(long, default_value_t = String::from("fleet.txt"))]
+        fleet_file: String,
+
+        /// Source file of the deployed program.
+        #[arg(long)]
+        bpf_source_file: String,
+
+        /// SUIT storage slot that the program is deployed into.
+        #[arg(long, short, default_value_t = 0)]
+        suit_storage_slot: usize,
+
+        /// Storage backend of the SUIT storage slot.
+        /// Available options: ram, flash
+        #[arg(long, default_value_t = String::from("ram"))]
+        storage: String,
+
+        /// Target version of the eBPF vm, defaults to the one the firmware
+        /// was built with. Available options: FemtoContainer, rBPF
+        #[arg(long)]
+        target: Option<String>,
+
+        /// Layout of the binary file that the VM should expect, defaults to
+        /// one supported by the firmware and the target VM.
+        /// Available options: OnlyTextSection, FemtoContainersHeader, ExtendedHeader, RawObjectFile,
+        #[arg(long)]
+        binary_layout: Option<String>,
+
+        /// Order of the deployments: `all` deploys to all devices at once,
+        /// `canary:10%` to the given share of the fleet first and
+        /// `rolling:2` in batches of the given size.
+        #[arg(long, default_value_t = String::from("all"))]
+        strategy: String,
+
+        /// Result that the program needs to return in the validation
+        /// execution, defaults to the result returned on the first device.
+        #[arg(long)]
+        expected_result: Option<i64>,
+    },
+    /// Monitors the devices of the fleet and re-deploys the programs marked
+    /// as `persistent = true` in the fleet config after a device reboots and
+    /// loses the contents of its RAM slots. The build and signing settings
+    /// are taken from the .env file of the current device profile.
+    Supervise {
+        /// TOML fleet config listing the devices and their programs.
+        #[arg(long, default_value_t = String::from("fleet.toml"))]
+        fleet_file: String,
+
+        /// Seconds between two consecutive probes of the devices.
+        #[arg(long, default_value_t = 10)]
+        interval: u64,
+
+        /// Webhook notified whenever a device reboots or a program gets
+        /// re-deployed.
+        #[arg(long)]
+        notify_url: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand, PartialEq, Eq)]
+pub enum AuditAction {
+    /// Prints the entries of the audit log, the most recent ones last.
+    Show {
+        /// Path to the audit log, defaults to $MIBPF_AUDIT_LOG.
+        #[arg(long)]
+        log: Option<String>,
+
+        /// Only show the entries concerning the device with this address.
+        #[arg(long)]
+        device: Option<String>,
+
+        /// Only show the given number of the most recent entries.
+        #[arg(long)]
+        limit: Option<usize>,
+
+        /// Check that the log wasn't tampered with: the hash chain linking
+        /// the entries needs to be intact and the signatures valid.
+        #[arg(long, default_value_t = false)]
+        verify: bool,
+
+        /// Directory of the key store with the public keys of the signers,
+        /// defaults to $MIBPF_KEY_DIR or "keys".
+        #[arg(long)]
+        key_dir: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand, PartialEq, Eq)]
+pub enum KeysAction {
+    /// Generates a new signing keypair.
+    Generate {
+        /// Name of the generated key.
+        #[arg(long, default_value_t = String::from("default"))]
+        name: String,
+
+        /// Directory of the key store, defaults to $MIBPF_KEY_DIR or "keys".
+        #[arg(long)]
+        key_dir: Option<String>,
+
+        /// Use the generated key for signing all subsequent manifests.
+        #[arg(long, default_value_t = false)]
+        activate: bool,
+    },
+    /// Generates a new signing keypair, activates it and re-signs the
+    /// manifests present in the CoAP root directory.
+    Rotate {
+        /// Name of the new key, defaults to key-<timestamp>.
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Directory of the key store, defaults to $MIBPF_KEY_DIR or "keys".
+        #[arg(long)]
+        key_dir: Option<String>,
+
+        /// Name of the coaproot directory containing the signed manifests.
+        #[arg(long, default_value_t = String::from("coaproot"))]
+        coaproot_dir: String,
+    },
+    /// Installs the public key as the trust anchor on the device.
+    Provision {
+        /// Name of the provisioned key, defaults to the active one.
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Directory of the key store, defaults to $MIBPF_KEY_DIR or "keys".
+        #[arg(long)]
+        key_dir: Option<String>,
+
+        /// IPv6 address of the RIOT instance.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+
+        /// Network interface of the machine hosting the CoAP fileserver.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+
+        /// Directory containing the per-device OSCORE security contexts. If
+        /// specified, the requests are protected end-to-end using OSCORE.
+        #[arg(long)]
+        oscore_context_dir: Option<String>,
+
+        /// Access token attached to the requests sent to the RIOT instance.
+        #[arg(long)]
+        access_token: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand, PartialEq, Eq)]
+pub enum BenchMode {
+    /// Measures the per-call overhead of the helpers using the built-in
+    /// microbenchmarks on the device configured in the .env file of the
+    /// current device profile.
+    Helpers {
+        /// VMs that the microbenchmarks are executed on, given as
+        /// <target>[:jit], e.g. --vm rBPF:jit
+        #[arg(long = "vm", default_values_t = [String::from("rBPF"), String::from("rBPF:jit"), String::from("FemtoContainer")])]
+        vms: Vec<String>,
+
+        /// Layout of the binary file that the VM should expect.
+        /// Available options: OnlyTextSection, FemtoContainersHeader, ExtendedHeader, RawObjectFile,
+        #[arg(long, default_value_t = String::from("ExtendedHeader"))]
+        binary_layout: String,
+
+        /// Number of benchmarked executions of each microbenchmark.
+        #[arg(long, default_value_t = 10)]
+        iterations: usize,
+
+        /// Directory that the microbenchmarks are written to and compiled in.
+        #[arg(long, default_value_t = String::from("./microbench"))]
+        work_dir: String,
+    },
+}
+
+#[derive(Debug, Subcommand, PartialEq, Eq)]
+pub enum DeviceAction {
+    /// Stops all running VMs and reboots the device.
+    Reboot {
+        /// Wait until the device responds on the network after rebooting.
+        #[arg(long, default_value_t = false)]
+        wait: bool,
+
+        /// Seconds to wait for the device to appear on the network.
+        #[arg(long, default_value_t = 60)]
+        wait_timeout: u64,
+
+        /// IPv6 address of the RIOT instance.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+
+        /// Network interface of the machine hosting the CoAP fileserver.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+
+        /// Directory containing the per-device OSCORE security contexts. If
+        /// specified, the requests are protected end-to-end using OSCORE.
+        #[arg(long)]
+        oscore_context_dir: Option<String>,
+
+        /// Access token attached to the requests sent to the RIOT instance.
+        #[arg(long)]
+        access_token: Option<String>,
+    },
+    /// Stops the long-running VM without rebooting the device.
+    ResetVm {
+        /// SUIT storage slot of the program executed by the VM, all VMs are
+        /// stopped if not specified.
+        #[arg(long)]
+        slot: Option<usize>,
+
+        /// IPv6 address of the RIOT instance.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+
+        /// Network interface of the machine hosting the CoAP fileserver.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+
+        /// Directory containing the per-device OSCORE security contexts. If
+        /// specified, the requests are protected end-to-end using OSCORE.
+        #[arg(long)]
+        oscore_context_dir: Option<String>,
+
+        /// Access token attached to the requests sent to the RIOT instance.
+        #[arg(long)]
+        access_token: Option<String>,
+    },
+    /// Prints the runtime information about the device: firmware version,
+    /// RIOT release, available VMs, storage slots, free RAM, clock frequency
+    /// and uptime.
+    Info {
+        /// IPv6 address of the RIOT instance.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+
+        /// Network interface of the machine hosting the CoAP fileserver.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+
+        /// Directory containing the per-device OSCORE security contexts. If
+        /// specified, the requests are protected end-to-end using OSCORE.
+        #[arg(long)]
+        oscore_context_dir: Option<String>,
+
+        /// Access token attached to the requests sent to the RIOT instance.
+        #[arg(long)]
+        access_token: Option<String>,
+
+        /// Print the info as JSON.
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Looks up the IPv6 address of a device in the CoRE Resource Directory
+    /// that it registered with.
+    Lookup {
+        /// Endpoint name that the device registered under.
+        endpoint: String,
+
+        /// Base URI of the resource directory, e.g. coap://[2001:db8::1].
+        /// Defaults to RESOURCE_DIRECTORY of the device profile.
+        #[arg(long)]
+        resource_directory: Option<String>,
+
+        /// CoAP forward proxy used to reach the resource directory.
+        /// Defaults to COAP_PROXY of the device profile.
+        #[arg(long)]
+        proxy: Option<String>,
+
+        /// Store the address as RIOT_INSTANCE_IP in the .env file of the
+        /// current device profile.
+        #[arg(long, default_value_t = false)]
+        save: bool,
+    },
+}
+
+#[derive(Debug, Subcommand, PartialEq, Eq)]
+pub enum HookAction {
+    /// Attaches the program deployed in the SUIT storage slot to a hook of
+    /// the network stack of the device, it then runs on every packet passing
+    /// through the hook (and drops it by returning 0) instead of on request.
+    Attach {
+        /// Hook of the network stack, available options: netif-rx, netif-tx
+        #[arg(long)]
+        hook: String,
+
+        /// SUIT storage slot containing the deployed program.
+        #[arg(long, short, default_value_t = 0)]
+        suit_storage_slot: usize,
+
+        /// Target version of the eBPF vm. Available options: FemtoContainer, rBPF
+        #[arg(long, default_value_t = String::from("rBPF"))]
+        target: String,
+
+        /// Layout of the deployed binary.
+        /// Available options: OnlyTextSection, FemtoContainersHeader, ExtendedHeader, RawObjectFile
+        #[arg(long, default_value_t = String::from("ExtendedHeader"))]
+        binary_layout: String,
+
+        /// Controlls which indices of helpers are made available to the VM
+        #[clap(long, long, value_parser, num_args = 1.., value_delimiter = ' ')]
+        helper_indices: Vec<u8>,
+
+        /// Controlls the pipeline stage at which the helpers need to be
+        /// verified
+        #[arg(long, default_value_t = String::from("Runtime"))]
+        helper_access_verification: String,
+
+        #[arg(short)]
+        jit: bool,
+
+        /// IPv6 address of the RIOT instance.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+
+        /// Network interface of the machine hosting the CoAP fileserver.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+
+        /// Directory containing the per-device OSCORE security contexts. If
+        /// specified, the requests are protected end-to-end using OSCORE.
+        #[arg(long)]
+        oscore_context_dir: Option<String>,
+
+        /// Access token attached to the requests sent to the RIOT instance.
+        #[arg(long)]
+        access_token: Option<String>,
+    },
+    /// Detaches the program from the hook of the network stack, the program
+    /// stays in its SUIT storage slot.
+    Detach {
+        /// Hook of the network stack, available options: netif-rx, netif-tx
+        #[arg(long)]
+        hook: String,
+
+        /// IPv6 address of the RIOT instance.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+
+        /// Network interface of the machine hosting the CoAP fileserver.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+
+        /// Directory containing the per-device OSCORE security contexts. If
+        /// specified, the requests are protected end-to-end using OSCORE.
+        #[arg(long)]
+        oscore_context_dir: Option<String>,
+
+        /// Access token attached to the requests sent to the RIOT instance.
+        #[arg(long)]
+        access_token: Option<String>,
+    },
+    /// Lists the hooks of the network stack with the attached programs,
+    /// their counters (invocations, accepted and dropped packets) and the
+    /// values they wrote into their storage.
+    List {
+        /// Only show the given hook.
+        #[arg(long)]
+        hook: Option<String>,
+
+        /// Print the listing as JSON.
+        #[arg(long, default_value_t = false)]
+        json: bool,
+
+        /// IPv6 address of the RIOT instance.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+
+        /// Network interface of the machine hosting the CoAP fileserver.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+
+        /// Directory containing the per-device OSCORE security contexts. If
+        /// specified, the requests are protected end-to-end using OSCORE.
+        #[arg(long)]
+        oscore_context_dir: Option<String>,
+
+        /// Access token attached to the requests sent to the RIOT instance.
+        #[arg(long)]
+        access_token: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand, PartialEq, Eq)]
+pub enum KeystoreAction {
+    /// Stores the key material in the keystore of the device. The material
+    /// is sent in the request, so OSCORE should be used to protect it.
+    Provision {
+        /// Handle under which the key is stored, the programs refer to the
+        /// key using it (see `execute --key-handle`).
+        #[arg(long)]
+        handle: u32,
+
+        /// Type of the key: aes-128, aes-256, hmac-sha256, ecdsa-p256
+        #[arg(long)]
+        key_type: String,
+
+        /// File containing the raw key material.
+        #[arg(long)]
+        key_file: String,
+
+        /// IPv6 address of the RIOT instance.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+
+        /// Network interface of the machine hosting the CoAP fileserver.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+
+        /// Directory containing the per-device OSCORE security contexts. If
+        /// specified, the requests are protected end-to-end using OSCORE.
+        #[arg(long)]
+        oscore_context_dir: Option<String>,
+
+        /// Access token attached to the requests sent to the RIOT instance.
+        #[arg(long)]
+        access_token: Option<String>,
+    },
+    /// Lists the handles and types of the keys stored on the device.
+    List {
+        /// IPv6 address of the RIOT instance.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+
+        /// Network interface of the machine hosting the CoAP fileserver.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+
+        /// Directory containing the per-device OSCORE security contexts. If
+        /// specified, the requests are protected end-to-end using OSCORE.
+        #[arg(long)]
+        oscore_context_dir: Option<String>,
+
+        /// Access token attached to the requests sent to the RIOT instance.
+        #[arg(long)]
+        access_token: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand, PartialEq, Eq)]
+pub enum HelpersAction {
+    /// Asks the device which helpers are available in its firmware and
+    /// checks whether the program can be executed on it.
+    Probe {
+        /// The binary containing the program.
+        #[arg(long)]
+        binary_file: String,
+
+        /// Layout of the binary file.
+        /// Available options: OnlyTextSection, FemtoContainersHeader, ExtendedHeader, RawObjectFile, auto
+        #[arg(long, default_value_t = String::from("auto"))]
+        binary_layout: String,
+
+        /// IPv6 address of the RIOT instance.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+
+        /// Network interface of the machine hosting the CoAP fileserver.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+
+        /// Directory containing the per-device OSCORE security contexts. If
+        /// specified, the requests are protected end-to-end using OSCORE.
+        #[arg(long)]
+        oscore_context_dir: Option<String>,
+
+        /// Access token attached to the requests sent to the RIOT instance.
+        #[arg(long)]
+        access_token: Option<String>,
+    },
+    /// Regenerates the helper IDs known to the tools (`HelperFunctionID`)
+    /// from the helper registration table of the firmware, failing if any
+    /// of the IDs clash.
+    Sync {
+        /// Root directory of the mibpf repository, searched for the headers
+        /// defining the helper IDs (BPF_FUNC_*).
+        #[arg(long, default_value_t = String::from(".."))]
+        mibpf_root_dir: String,
+
+        /// Header with the registration table, if the search picks up the
+        /// wrong ones.
+        #[arg(long)]
+        header: Vec<String>,
+
+        /// Source file of the helper catalogue that gets regenerated.
+        #[arg(long, default_value_t = String::from("common/src/enumerations.rs"))]
+        catalogue: String,
+
+        /// Only report the differences and fail if the catalogue is out of
+        /// sync, without modifying it.
+        #[arg(long, default_value_t = false)]
+        check: bool,
+    },
+}
+
+#[derive(Debug, Subcommand, PartialEq, Eq)]
+pub enum QueueAction {
+    /// Lists the queued executions in the order they will be served.
+    List {
+        /// Only list the queue of the device with the given IPv6 address.
+        #[arg(long)]
+        riot_ipv6_addr: Option<String>,
+    },
+    /// Removes the execution from the queue, the waiting command fails as
+    /// cancelled.
+    Cancel {
+        /// Ticket of the execution, as printed by `queue list`.
+        ticket: String,
+    },
+}
+
+#[derive(Debug, Subcommand, PartialEq, Eq)]
+pub enum ResultsAction {
+    /// Lists the recorded executions matching the filters, followed by the
+    /// statistics of the execution times of each version of the programs.
+    Query {
+        /// Only include the executions on the device with the given IPv6
+        /// address.
+        #[arg(long)]
+        riot_ipv6_addr: Option<String>,
+
+        /// Only include the executions of the program, given either as its
+        /// source file or the prefix of its hash.
+        #[arg(long)]
+        program: Option<String>,
+
+        /// Start of the date range, either YYYY-MM-DD (UTC) or seconds since
+        /// the UNIX epoch.
+        #[arg(long)]
+        since: Option<String>,
+
+        /// End of the date range (inclusive), same format as --since.
+        #[arg(long)]
+        until: Option<String>,
+
+        /// Only print the statistics, not the individual executions.
+        #[arg(long, default_value_t = false)]
+        summary: bool,
+    },
+}
+
+#[derive(Debug, Subcommand, PartialEq, Eq)]
+pub enum SlotAction {
+    /// Downloads the contents of the SUIT storage slot into a file.
+    Backup {
+        /// SUIT storage slot to download.
+        #[arg(long, default_value_t = 0)]
+        slot: usize,
+
+        /// File that the contents of the slot are written to.
+        #[arg(long)]
+        out: String,
+
+        /// IPv6 address of the RIOT instance.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+
+        /// Network interface of the machine hosting the CoAP fileserver.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+
+        /// Directory containing the per-device OSCORE security contexts. If
+        /// specified, the requests are protected end-to-end using OSCORE.
+        #[arg(long)]
+        oscore_context_dir: Option<String>,
+
+        /// Access token attached to the requests sent to the RIOT instance.
+        #[arg(long)]
+        access_token: Option<String>,
+    },
+    /// Uploads a file produced by `slot backup` into the SUIT storage slot.
+    Restore {
+        /// SUIT storage slot to write into.
+        #[arg(long, default_value_t = 0)]
+        slot: usize,
+
+        /// File with the contents of the slot.
+        #[arg(long = "in")]
+        input: String,
+
+        /// IPv6 address of the RIOT instance.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+
+        /// Network interface of the machine hosting the CoAP fileserver.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+
+        /// Directory containing the per-device OSCORE security contexts. If
+        /// specified, the requests are protected end-to-end using OSCORE.
+        #[arg(long)]
+        oscore_context_dir: Option<String>,
+
+        /// Access token attached to the requests sent to the RIOT instance.
+        #[arg(long)]
+        access_token: Option<String>,
+
+        /// Print the requests instead of sending them.
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+    /// Shows how many times each slot was deployed to (according to the
+    /// local registry) and the erase cycles of its flash pages if the device
+    /// reports them.
+    Stats {
+        /// IPv6 address of the RIOT instance.
+        #[arg(long, default_value_t = String::from(""))]
+        riot_ipv6_addr: String,
+
+        /// Network interface of the machine hosting the CoAP fileserver.
+        /// `auto` derives it from the address of the device.
+        #[arg(long, default_value_t = String::from("auto"))]
+        host_network_interface: String,
+
+        /// Directory containing the per-device OSCORE security contexts. If
+        /// specified, the requests are protected end-to-end using OSCORE.
+        #[arg(long)]
+        oscore_context_dir: Option<String>,
+
+        /// Access token attached to the requests sent to the RIOT instance.
+        #[arg(long)]
+        access_token: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand, PartialEq, Eq)]
+pub enum ConformanceAction {
+    /// Lists the programs included in the conformance corpus.
+    List,
+    /// Deploys and executes the conformance programs and prints a scorecard.
+    Run {
+        /// Target version of the eBPF vm. Available options: Femto-Containers, rBPF
+        #[arg(long, default_value_t = String::from("rBPF"))]
+        target: String,
+
+        /// Layout of the binary file that the VM should expect.
+        /// Available options: OnlyTextSection, FemtoContainersHeader, ExtendedHeader, RawObjectFile,
+        #[arg(long, default_value_t = String::from("ExtendedHeader"))]
+        binary_layout: String,
+
+        /// Only run the programs from the given categories. Available options:
+        /// arithmetic, branching, memory, helpers, packet
+        #[arg(long = "category")]
+        categories: Vec<String>,
+
+        /// Directory where the conformance programs are written and compiled.
+        #[arg(long, default_value_t = String::from("conformance-out"))]
+        work_dir: String,
+
+        #[arg(short)]
+        jit: bool,
+    },
+    /// Checks the contracts of the helpers (return values and side effects)
+    /// by executing a generated program for each of them on the device and
+    /// prints the helper conformance report of its firmware.
+    ContractTest {
+        /// Target version of the eBPF vm. Available options: Femto-Containers, rBPF
+        #[arg(long, default_value_t = String::from("rBPF"))]
+        target: String,
+
+        /// Layout of the binary file that the VM should expect.
+        /// Available options: OnlyTextSection, FemtoContainersHeader, ExtendedHeader, RawObjectFile,
+        #[arg(long, default_value_t = String::from("ExtendedHeader"))]
+        binary_layout: String,
+
+        /// Only check the contracts of the given helpers, e.g. --helper memcpy
+        #[arg(long = "helper")]
+        helpers: Vec<String>,
+
+        /// Directory where the contract programs are written and compiled.
+        #[arg(long, default_value_t = String::from("contracts-out"))]
+        work_dir: String,
+
+        /// File that the report is written to in addition to printing it,
+        /// e.g. to keep the reports of the firmware versions side by side.
+        #[arg(long)]
+        output: Option<String>,
+
+        #[arg(short)]
+        jit: bool,
+    },
+}
+
+/// Tools for compiling, signing, loading and executing eBPF programs for
+/// micro_bpf.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+pub struct Args {
+    /// The command that is to be performed, run `help` to list all available
+    /// commands.
+    #[command(subcommand)]
+    pub command: Action,
+
+    /// Use the .env file to configure missing arguments. Defaults to sourcing
+    /// the ".env" file, the loaded file can be overridden by setting the
+    /// environment variable "DOTENV".
+    #[arg(long, default_value_t = false)]
+    pub use_env: bool,
+
+    /// Overrides a variable of the device profile, e.g. --set HOST_NET_IF=tap0
+    /// The overrides take precedence over the .env file and config.toml.
+    #[arg(long = "set", global = true)]
+    pub overrides: Vec<String>,
+
+    /// Machine mode for CI pipelines: disables the logging and reports the
+    /// errors as a single line of JSON on stderr. The exit code reflects the
+    /// class of the failure: 2 compilation, 3 deployment, 4 execution,
+    /// 5 assertion mismatch, 130 cancelled and 1 for anything else.
+    #[arg(long, global = true)]
+    pub quiet: bool,
+
+    /// HTTP endpoint that a JSON summary of each deployment, execution and
+    /// benchmark result is posted to, e.g. a Slack incoming webhook. Falls
+    /// back to WEBHOOK_URL of the device profile when --use-env is set.
+    #[arg(long, global = true)]
+    pub webhook: Option<String>,
+
+    /// Name of the device profile to use, selects the `.env-<name>` file
+    /// instead of the one given by DOTENV.
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
 }
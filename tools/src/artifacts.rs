@@ -0,0 +1,207 @@
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use log::debug;
+use micro_bpf_common::BinaryFileLayout;
+
+use crate::{deploy::ALL_LAYOUTS, environment::Environment};
+
+/// Controls where the artifacts produced during a deployment are written to
+/// and how they are named, so that the callers don't need to know about the
+/// conventions of the individual steps.
+///
+/// - the object files produced by the compiler go into `object_dir`,
+/// - the binaries are assembled in `binary_dir` and then moved into
+///   `manifest_dir` by the signing step,
+/// - `manifest_dir` is the root of the CoAP fileserver which the device
+///   fetches the signed manifests and binaries from.
+#[derive(Debug, Clone)]
+pub struct ArtifactStore {
+    pub object_dir: String,
+    pub binary_dir: String,
+    pub manifest_dir: String,
+    /// Artifacts in the object and binary directories which haven't been
+    /// modified for longer than this are removed by [`Self::clean_stale`].
+    pub max_age: Option<Duration>,
+}
+
+impl ArtifactStore {
+    /// Creates the store using the default layout, the binaries are
+    /// assembled in the current working directory.
+    pub fn new(object_dir: &str, manifest_dir: &str) -> Self {
+        ArtifactStore {
+            object_dir: object_dir.to_string(),
+            binary_dir: ".".to_string(),
+            manifest_dir: manifest_dir.to_string(),
+            max_age: None,
+        }
+    }
+
+    /// Creates the store configured by the device profile (OUT_DIR,
+    /// BINARY_DIR, COAP_ROOT_DIR and ARTIFACT_MAX_AGE_HOURS).
+    pub fn from_env(environment: &Environment) -> Self {
+        ArtifactStore {
+            object_dir: environment.out_dir.clone(),
+            binary_dir: environment.binary_dir.clone(),
+            manifest_dir: environment.coap_root_dir.clone(),
+            max_age: environment
+                .artifact_max_age_hours
+                .map(|hours| Duration::from_secs(hours * 3600)),
+        }
+    }
+
+    /// Object file that the compiler produces for the source file.
+    pub fn object_file(&self, bpf_source_file: &str) -> Result<String, String> {
+        get_object_file_name(bpf_source_file, &self.object_dir)
+    }
+
+    /// Binary deployed into the given SUIT storage slot.
+    pub fn slot_binary(&self, suit_storage_slot: usize) -> String {
+        format!("{}/program{}.bin", self.binary_dir, suit_storage_slot)
+    }
+
+    /// Binary of the program in the given layout, used when the program is
+    /// built in several layouts at once.
+    pub fn layout_binary(
+        &self,
+        bpf_source_file: &str,
+        layout: BinaryFileLayout,
+    ) -> Result<String, String> {
+        let object_file = self.object_file(bpf_source_file)?;
+        Ok(format!(
+            "{}.{:?}.bin",
+            object_file.trim_end_matches(".o"),
+            layout
+        ))
+    }
+
+    /// File name of the signed manifest delivering the given slot.
+    pub fn manifest(&self, suit_storage_slot: usize) -> String {
+        format!("suit_manifest{}.signed", suit_storage_slot)
+    }
+
+    /// File recording the progress of the last unfinished deployment, see
+    /// [`crate::deploy::DeployState`]. It is kept next to the binaries so that
+    /// the file server doesn't serve it.
+    pub fn deploy_state(&self) -> String {
+        format!("{}/.deploy-state.json", self.binary_dir)
+    }
+
+    /// Path of an artifact once it has been moved into the CoAP root.
+    pub fn served(&self, artifact: &str) -> String {
+        let file_name = artifact.split('/').last().unwrap_or(artifact);
+        format!("{}/{}", self.manifest_dir, file_name)
+    }
+
+    /// Removes the object files and binaries which are older than `max_age`
+    /// (nothing is removed if it isn't set). Only the files named by the
+    /// store are removed: the object files and the layout binaries (see
+    /// [`Self::layout_binary`]) in `object_dir` and the slot binaries (see
+    /// [`Self::slot_binary`]) in `binary_dir`. The CoAP root is left untouched
+    /// as the devices could still be fetching from it. Returns the number of
+    /// removed files.
+    pub fn clean_stale(&self) -> Result<usize, String> {
+        let Some(max_age) = self.max_age else {
+            return Ok(0);
+        };
+
+        let mut removed = 0;
+        let dirs: [(&str, fn(&str) -> bool); 2] = [
+            (self.object_dir.as_str(), is_object_artifact),
+            (self.binary_dir.as_str(), is_slot_binary),
+        ];
+        for (dir, is_artifact) in dirs {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            for path in entries.filter_map(Result::ok).map(|entry| entry.path()) {
+                let named_by_store = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(is_artifact);
+                if !path.is_file() || !named_by_store || !is_older_than(&path, max_age) {
+                    continue;
+                }
+                fs::remove_file(&path)
+                    .map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+                debug!("Removed the stale artifact {}", path.display());
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+pub fn get_object_file_name(bpf_source_file: &str, out_dir: &str) -> Result<String, String> {
+    let base_name = bpf_source_file.split("/").last().unwrap().split(".").nth(0);
+
+    return match base_name {
+        Some(name) => Ok(format!("{}/{}.o", out_dir, name)),
+        None => Err("File not found: You need to provide the .c source file".to_string()),
+    };
+}
+
+/// Object files and the binaries built from them in the individual layouts,
+/// e.g. `counter.o` and `counter.ExtendedHeader.bin`.
+fn is_object_artifact(file_name: &str) -> bool {
+    file_name.ends_with(".o")
+        || ALL_LAYOUTS
+            .iter()
+            .any(|layout| file_name.ends_with(&format!(".{:?}.bin", layout)))
+}
+
+/// Binaries deployed into the SUIT storage slots, e.g. `program2.bin`. The
+/// binary directory defaults to the working directory which can contain
+/// anything else, so the other binaries are left alone.
+fn is_slot_binary(file_name: &str) -> bool {
+    file_name
+        .strip_prefix("program")
+        .and_then(|name| name.strip_suffix(".bin"))
+        .is_some_and(|slot| !slot.is_empty() && slot.bytes().all(|b| b.is_ascii_digit()))
+}
+
+fn is_older_than(path: &Path, max_age: Duration) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age > max_age)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_artifacts_named_by_the_store_are_cleaned() {
+        let store = ArtifactStore::new("out", "coaproot");
+        let slot_binary = store.slot_binary(12);
+        let layout_binary = store
+            .layout_binary("bpf/counter.c", BinaryFileLayout::ExtendedHeader)
+            .unwrap();
+
+        assert!(is_slot_binary(
+            Path::new(&slot_binary)
+                .file_name()
+                .unwrap()
+                .to_str()
+                .unwrap()
+        ));
+        assert!(is_object_artifact(
+            Path::new(&layout_binary)
+                .file_name()
+                .unwrap()
+                .to_str()
+                .unwrap()
+        ));
+        assert!(is_object_artifact("counter.o"));
+
+        assert!(!is_slot_binary("program.bin"));
+        assert!(!is_slot_binary("programmer.bin"));
+        assert!(!is_slot_binary("firmware.bin"));
+        assert!(!is_object_artifact("firmware.bin"));
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    env, fmt, fs,
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use ed25519_dalek::Signer;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    keys::{path_str, verify_signature, KeyStore},
+    registry::now,
+    suit::load_signing_key,
+};
+
+/// Hash linking the first entry of the log.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Single operation performed on a device, e.g. a deployment or an execution.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditEntry {
+    /// Seconds since the UNIX epoch.
+    pub timestamp: u64,
+    /// Name of the user who ran the command.
+    pub user: String,
+    pub action: String,
+    pub device: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub program: Option<String>,
+    /// "ok" or the error message of the failed command.
+    pub outcome: String,
+    /// SHA-256 of the previous line of the log, so that removing or editing
+    /// the entries breaks the chain.
+    pub previous: String,
+    /// Name of the key in the [`KeyStore`] that signed the entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signer: Option<String>,
+    /// Hex-encoded ed25519 signature of the entry without this field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(action: &str, device: &str, outcome: &Result<(), String>) -> Self {
+        AuditEntry {
+            timestamp: now(),
+            user: env::var("USER").unwrap_or_else(|_| "unknown".to_string()),
+            action: action.to_string(),
+            device: device.to_string(),
+            slot: None,
+            program: None,
+            outcome: match outcome {
+                Ok(()) => "ok".to_string(),
+                Err(e) => e.lines().next().unwrap_or_default().to_string(),
+            },
+            previous: String::new(),
+            signer: None,
+            signature: None,
+        }
+    }
+
+    pub fn with_slot(mut self, slot: impl ToString) -> Self {
+        self.slot = Some(slot.to_string());
+        self
+    }
+
+    pub fn with_program(mut self, program: &str) -> Self {
+        self.program = Some(program.to_string());
+        self
+    }
+
+    /// Bytes covered by the signature: the JSON encoding of the entry
+    /// without the signature itself.
+    fn signed_bytes(&self) -> Vec<u8> {
+        let unsigned = AuditEntry {
+            signature: None,
+            ..self.clone()
+        };
+        serde_json::to_vec(&unsigned).unwrap()
+    }
+}
+
+/// Problem found while verifying the log.
+#[derive(Debug)]
+pub struct AuditIssue {
+    /// Line of the log (starting from 1).
+    pub line: usize,
+    pub problem: String,
+}
+
+impl fmt::Display for AuditIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.problem)
+    }
+}
+
+/// Append-only log of the operations performed on the devices of a shared
+/// testbed, one JSON entry per line. Each entry records the hash of the
+/// previous line, and is optionally signed with a key from the [`KeyStore`],
+/// which makes it possible to find out who left a board in a broken state.
+///
+/// The log is only written if the environment variable "MIBPF_AUDIT_LOG" is
+/// set, the entries are signed if "MIBPF_AUDIT_KEY" names a key in the store.
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(path: &str) -> Self {
+        AuditLog {
+            path: PathBuf::from(path),
+        }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        env::var("MIBPF_AUDIT_LOG")
+            .ok()
+            .map(|path| AuditLog::new(&path))
+    }
+
+    /// Name of the key used for signing the entries.
+    pub fn signing_key() -> Option<String> {
+        env::var("MIBPF_AUDIT_KEY")
+            .ok()
+            .filter(|key| !key.is_empty())
+    }
+
+    /// Links the entry to the end of the log, signs it if a signing key is
+    /// given and appends it.
+    pub fn append(
+        &self,
+        mut entry: AuditEntry,
+        signer: Option<(&KeyStore, &str)>,
+    ) -> Result<(), String> {
+        let path = path_str(&self.path)?;
+        let contents = fs::read_to_string(&self.path).unwrap_or_default();
+        entry.previous = match contents.lines().last() {
+            Some(line) => hash(line),
+            None => GENESIS_HASH.to_string(),
+        };
+
+        if let Some((keys, name)) = signer {
+            entry.signer = Some(name.to_string());
+            entry.signature = Some(sign(&entry.signed_bytes(), &keys.private_key(name))?);
+        }
+
+        let line = serde_json::to_string(&entry).unwrap() + "\n";
+        debug!("Appending the audit entry: {}", line.trim());
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|e| format!("Failed to append to the audit log {}: {}", path, e))
+    }
+
+    pub fn entries(&self) -> Result<Vec<AuditEntry>, String> {
+        let path = path_str(&self.path)?;
+        let Ok(contents) = fs::read_to_string(&self.path) else {
+            return Ok(vec![]);
+        };
+        contents
+            .lines()
+            .enumerate()
+            .map(|(i, line)| {
+                serde_json::from_str::<AuditEntry>(line)
+                    .map_err(|e| format!("Invalid entry at {}:{}: {}", path, i + 1, e))
+            })
+            .collect()
+    }
+
+    /// Checks that the hash chain is intact and that the signatures of the
+    /// signed entries are valid.
+    pub fn verify(&self, keys: &KeyStore) -> Result<Vec<AuditIssue>, String> {
+        let contents = fs::read_to_string(&self.path).unwrap_or_default();
+        let mut issues = vec![];
+        let mut previous = GENESIS_HASH.to_string();
+        for (i, line) in contents.lines().enumerate() {
+            let mut issue = |problem: String| {
+                issues.push(AuditIssue {
+                    line: i + 1,
+                    problem,
+                })
+            };
+            match serde_json::from_str::<AuditEntry>(line) {
+                Ok(entry) => {
+                    if entry.previous != previous {
+                        issue("the hash of the previous entry doesn't match".to_string());
+                    }
+                    match (&entry.signer, &entry.signature) {
+                        (Some(signer), Some(signature)) => {
+                            let public_key = keys.public_key(signer);
+                            if let Err(e) =
+                                verify_signature(&entry.signed_bytes(), signature, &public_key)
+                            {
+                                issue(format!("invalid signature of {}: {}", signer, e));
+                            }
+                        }
+                        (None, None) => {}
+                        _ => issue("incomplete signature".to_string()),
+                    }
+                }
+                Err(e) => issue(format!("invalid entry: {}", e)),
+            }
+            previous = hash(line);
+        }
+        Ok(issues)
+    }
+}
+
+fn hash(line: &str) -> String {
+    format!("{:x}", Sha256::digest(line.as_bytes()))
+}
+
+fn sign(message: &[u8], private_key: &Path) -> Result<String, String> {
+    let key = load_signing_key(path_str(private_key)?)?;
+    Ok(key
+        .sign(message)
+        .to_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+impl fmt::Display for AuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<12} {:<12} {:<8} {:<28} {:<6} {:<32} {}",
+            self.timestamp,
+            self.user,
+            self.action,
+            self.device,
+            self.slot.as_deref().unwrap_or("-"),
+            self.program.as_deref().unwrap_or("-"),
+            self.outcome
+        )?;
+        if let Some(signer) = &self.signer {
+            write!(f, " (signed by {})", signer)?;
+        }
+        Ok(())
+    }
+}
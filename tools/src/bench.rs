@@ -0,0 +1,196 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use micro_bpf_common::{
+    BinaryFileLayout, ExecutionModel, HelperAccessListSource, HelperAccessVerification, TargetVM,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    execute::{execute, ExecuteOptions},
+    power::PowerProbe,
+    record::ResultRecorder,
+    transport::TransportOptions,
+};
+
+/// Timings reported by the benchmark endpoint of the device.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+pub struct BenchmarkTimes {
+    #[serde(rename(deserialize = "total"))]
+    pub total_time: u32,
+    #[serde(rename(deserialize = "load"))]
+    pub load_time: u32,
+    #[serde(rename(deserialize = "verif"))]
+    pub verification_time: u32,
+    #[serde(rename(deserialize = "exec"))]
+    pub execution_time: u32,
+    #[serde(rename(deserialize = "prog"))]
+    pub program_size: u32,
+    pub result: i32,
+}
+
+/// Single benchmarked execution.
+#[derive(Debug, Clone)]
+pub struct BenchSample {
+    pub times: BenchmarkTimes,
+    /// Time between sending the request and receiving the response.
+    pub round_trip: Duration,
+    /// Energy caused by the execution (with the idle consumption subtracted)
+    /// in microjoules, only available if a power probe is used.
+    pub energy_uj: Option<f64>,
+}
+
+#[derive(Debug)]
+pub struct BenchReport {
+    pub samples: Vec<BenchSample>,
+    /// Average power of the idle device in milliwatts.
+    pub idle_power_mw: Option<f64>,
+}
+
+/// Executes the program loaded in the SUIT storage slot repeatedly using the
+/// benchmark endpoint of the device. If a power probe is specified, the
+/// energy consumption is measured around each execution. If a recorder is
+/// specified, each sample is recorded as soon as it is collected.
+pub async fn bench(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    target: TargetVM,
+    binary_layout: BinaryFileLayout,
+    suit_storage_slot: usize,
+    helper_indices: &[u8],
+    jit: bool,
+    iterations: usize,
+    mut probe: Option<&mut dyn PowerProbe>,
+    idle_window: Duration,
+    mut recorder: Option<&mut ResultRecorder>,
+    transport: &TransportOptions,
+) -> Result<BenchReport, String> {
+    let idle_power_mw = match probe.as_deref_mut() {
+        Some(probe) => Some(probe.measure_idle(idle_window)?.average_power_mw()),
+        None => None,
+    };
+
+    let mut samples = vec![];
+    for _ in 0..iterations {
+        if let Some(probe) = probe.as_deref_mut() {
+            probe.start()?;
+        }
+
+        let start = Instant::now();
+        let result = execute(
+            riot_ipv6_addr,
+            target,
+            binary_layout,
+            suit_storage_slot,
+            host_network_interface,
+            ExecutionModel::ShortLived,
+            HelperAccessVerification::AheadOfTime,
+            HelperAccessListSource::ExecuteRequest,
+            helper_indices,
+            jit,
+            &ExecuteOptions {
+                jit_compile: jit,
+                benchmark: true,
+                ..Default::default()
+            },
+            transport,
+        )
+        .await;
+        let round_trip = start.elapsed();
+
+        // The probe is stopped before the errors are propagated so that it
+        // isn't left running when the execution fails.
+        let measurement = probe.as_deref_mut().map(|probe| probe.stop());
+        let response = result?;
+        let energy_uj = match (measurement.transpose()?, idle_power_mw) {
+            (Some(measurement), Some(idle_power_mw)) => {
+                Some(measurement.energy_uj - idle_power_mw * measurement.duration_ms)
+            }
+            _ => None,
+        };
+
+        let times = serde_json::from_str::<BenchmarkTimes>(&response)
+            .map_err(|e| format!("Failed to parse the benchmark response: {}", e))?;
+
+        if let Some(recorder) = recorder.as_deref_mut() {
+            let sample = json!({
+                "slot": suit_storage_slot,
+                "times": times,
+                "round_trip_ms": round_trip.as_millis() as u64,
+                "energy_uj": energy_uj,
+            });
+            recorder.record(riot_ipv6_addr, "bench", sample)?;
+        }
+
+        samples.push(BenchSample {
+            times,
+            round_trip,
+            energy_uj,
+        });
+    }
+
+    Ok(BenchReport {
+        samples,
+        idle_power_mw,
+    })
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> f64 {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+    if count == 0 {
+        0.0
+    } else {
+        sum / count as f64
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, sample) in self.samples.iter().enumerate() {
+            write!(
+                f,
+                "{:4}: total: {}, exec: {}, round trip: {}ms",
+                i,
+                sample.times.total_time,
+                sample.times.execution_time,
+                sample.round_trip.as_millis()
+            )?;
+            match sample.energy_uj {
+                Some(energy) => writeln!(f, ", energy: {:.2}uJ", energy)?,
+                None => writeln!(f)?,
+            }
+        }
+
+        writeln!(
+            f,
+            "Mean total time: {:.2}",
+            mean(self.samples.iter().map(|s| s.times.total_time as f64))
+        )?;
+        writeln!(
+            f,
+            "Mean execution time: {:.2}",
+            mean(self.samples.iter().map(|s| s.times.execution_time as f64))
+        )?;
+        write!(
+            f,
+            "Mean round trip: {:.2}ms",
+            mean(
+                self.samples
+                    .iter()
+                    .map(|s| s.round_trip.as_secs_f64() * 1000.0)
+            )
+        )?;
+        if let Some(idle_power_mw) = self.idle_power_mw {
+            write!(
+                f,
+                "\nIdle power: {:.3}mW\nMean energy per execution: {:.2}uJ",
+                idle_power_mw,
+                mean(self.samples.iter().filter_map(|s| s.energy_uj))
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt;
+
+// This module contains a minimal decoder of the eBPF bytecode which is used
+// by the tools that inspect compiled artifacts (e.g. diffing two versions of
+// the same program). We don't use the rbpf disassembler here because it
+// doesn't know about the custom LDDW instructions that were introduced by
+// Femto-Containers.
+
+pub const INSTRUCTION_SIZE: usize = 8;
+
+pub const LDDW_OPCODE: u8 = 0x18;
+/// Load from the .data section, see [`micro_bpf_elf_utils`] relocations.
+pub const LDDWD_OPCODE: u8 = 0xB8;
+/// Load from the .rodata section, see [`micro_bpf_elf_utils`] relocations.
+pub const LDDWR_OPCODE: u8 = 0xD8;
+pub const CALL_OPCODE: u8 = 0x85;
+pub const EXIT_OPCODE: u8 = 0x95;
+
+const CLASS_LD: u8 = 0x00;
+const CLASS_LDX: u8 = 0x01;
+const CLASS_ST: u8 = 0x02;
+const CLASS_STX: u8 = 0x03;
+const CLASS_ALU: u8 = 0x04;
+const CLASS_JMP: u8 = 0x05;
+const CLASS_JMP32: u8 = 0x06;
+const CLASS_ALU64: u8 = 0x07;
+
+/// A single decoded eBPF instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    /// Index of the instruction slot inside of the .text section. Note that
+    /// the wide LDDW instructions occupy two slots.
+    pub index: usize,
+    pub opcode: u8,
+    pub dst: u8,
+    pub src: u8,
+    pub offset: i16,
+    /// For the LDDW instructions this contains the full 64-bit immediate.
+    pub immediate: i64,
+}
+
+impl Instruction {
+    pub fn is_wide(&self) -> bool {
+        is_wide_opcode(self.opcode)
+    }
+
+    /// Returns the number of bytes that the instruction occupies in the program.
+    pub fn size(&self) -> usize {
+        if self.is_wide() {
+            2 * INSTRUCTION_SIZE
+        } else {
+            INSTRUCTION_SIZE
+        }
+    }
+
+    /// Helper calls use the immediate operand to specify the helper ID, whereas
+    /// the calls to functions defined in the program set the src register.
+    pub fn is_helper_call(&self) -> bool {
+        self.opcode == CALL_OPCODE && self.src == 0
+    }
+
+    pub fn mnemonic(&self) -> String {
+        mnemonic(self.opcode)
+    }
+}
+
+fn is_wide_opcode(opcode: u8) -> bool {
+    opcode == LDDW_OPCODE || opcode == LDDWD_OPCODE || opcode == LDDWR_OPCODE
+}
+
+/// Decodes the contents of the .text section into a list of instructions.
+pub fn decode(text: &[u8]) -> Result<Vec<Instruction>, String> {
+    let mut instructions = vec![];
+    let mut offset = 0;
+
+    while offset + INSTRUCTION_SIZE <= text.len() {
+        let bytes = &text[offset..offset + INSTRUCTION_SIZE];
+        let opcode = bytes[0];
+        let mut immediate = i32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as i64;
+
+        if is_wide_opcode(opcode) {
+            if offset + 2 * INSTRUCTION_SIZE > text.len() {
+                return Err(format!("Truncated LDDW instruction at offset {}", offset));
+            }
+            let high = &text[offset + 12..offset + 16];
+            let high = u32::from_le_bytes([high[0], high[1], high[2], high[3]]) as u64;
+            immediate = ((immediate as u32 as u64) | (high << 32)) as i64;
+        }
+
+        let instruction = Instruction {
+            index: offset / INSTRUCTION_SIZE,
+            opcode,
+            dst: bytes[1] & 0x0f,
+            src: bytes[1] >> 4,
+            offset: i16::from_le_bytes([bytes[2], bytes[3]]),
+            immediate,
+        };
+        offset += instruction.size();
+        instructions.push(instruction);
+    }
+
+    if offset != text.len() {
+        return Err(format!(
+            "The program length {} is not a multiple of the instruction size",
+            text.len()
+        ));
+    }
+
+    Ok(instructions)
+}
+
+fn size_suffix(opcode: u8) -> &'static str {
+    match opcode & 0x18 {
+        0x00 => "w",
+        0x08 => "h",
+        0x10 => "b",
+        _ => "dw",
+    }
+}
+
+fn mnemonic(opcode: u8) -> String {
+    match opcode {
+        LDDW_OPCODE => return "lddw".to_string(),
+        LDDWD_OPCODE => return "lddwd".to_string(),
+        LDDWR_OPCODE => return "lddwr".to_string(),
+        _ => {}
+    }
+
+    match opcode & 0x07 {
+        CLASS_LD => format!("ld{}", size_suffix(opcode)),
+        CLASS_LDX => format!("ldx{}", size_suffix(opcode)),
+        CLASS_ST => format!("st{}", size_suffix(opcode)),
+        CLASS_STX => format!("stx{}", size_suffix(opcode)),
+        class @ (CLASS_ALU | CLASS_ALU64) => {
+            let name = match opcode & 0xf0 {
+                0x00 => "add",
+                0x10 => "sub",
+                0x20 => "mul",
+                0x30 => "div",
+                0x40 => "or",
+                0x50 => "and",
+                0x60 => "lsh",
+                0x70 => "rsh",
+                0x80 => "neg",
+                0x90 => "mod",
+                0xa0 => "xor",
+                0xb0 => "mov",
+                0xc0 => "arsh",
+                0xd0 => "be",
+                _ => "unknown",
+            };
+            let width = if class == CLASS_ALU { "32" } else { "64" };
+            format!("{}{}", name, width)
+        }
+        class @ (CLASS_JMP | CLASS_JMP32) => {
+            let name = match opcode & 0xf0 {
+                0x00 => "ja",
+                0x10 => "jeq",
+                0x20 => "jgt",
+                0x30 => "jge",
+                0x40 => "jset",
+                0x50 => "jne",
+                0x60 => "jsgt",
+                0x70 => "jsge",
+                0x80 => "call",
+                0x90 => "exit",
+                0xa0 => "jlt",
+                0xb0 => "jle",
+                0xc0 => "jslt",
+                0xd0 => "jsle",
+                _ => "unknown",
+            };
+            if class == CLASS_JMP32 {
+                format!("{}32", name)
+            } else {
+                name.to_string()
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.mnemonic();
+        // The source bit specifies whether the second operand is a register.
+        let uses_src_register = self.opcode & 0x08 != 0;
+
+        if self.is_wide() {
+            return write!(f, "{} r{}, {:#x}", name, self.dst, self.immediate);
+        }
+
+        match self.opcode & 0x07 {
+            CLASS_LDX => write!(f, "{} r{}, [r{}{:+}]", name, self.dst, self.src, self.offset),
+            CLASS_ST => write!(f, "{} [r{}{:+}], {}", name, self.dst, self.offset, self.immediate),
+            CLASS_STX => write!(f, "{} [r{}{:+}], r{}", name, self.dst, self.offset, self.src),
+            CLASS_ALU | CLASS_ALU64 => match self.opcode & 0xf0 {
+                0x80 => write!(f, "{} r{}", name, self.dst),
+                _ if uses_src_register => write!(f, "{} r{}, r{}", name, self.dst, self.src),
+                _ => write!(f, "{} r{}, {}", name, self.dst, self.immediate),
+            },
+            CLASS_JMP | CLASS_JMP32 => match self.opcode & 0xf0 {
+                0x00 => write!(f, "{} {:+}", name, self.offset),
+                0x80 if self.is_helper_call() => write!(f, "{} {}", name, self.immediate),
+                0x80 => write!(f, "{} local {}", name, self.immediate),
+                0x90 => write!(f, "{}", name),
+                _ if uses_src_register => write!(
+                    f,
+                    "{} r{}, r{}, {:+}",
+                    name, self.dst, self.src, self.offset
+                ),
+                _ => write!(
+                    f,
+                    "{} r{}, {}, {:+}",
+                    name, self.dst, self.immediate, self.offset
+                ),
+            },
+            _ => write!(f, "{} {:#x}", name, self.immediate),
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+use log::debug;
+use micro_bpf_common::CapabilitiesResponse;
+
+use crate::transport::{send_request, TransportOptions};
+
+/// Queries the optional features supported by the firmware of the device,
+/// see [`CapabilitiesResponse`]. The firmware versions which predate the
+/// capability flags don't serve the resource (4.04 Not Found), in which case
+/// no capabilities are reported.
+pub fn query_capabilities(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    transport: &TransportOptions,
+) -> Result<CapabilitiesResponse, String> {
+    let url = format!(
+        "coap://[{}%{}]/capabilities",
+        riot_ipv6_addr, host_network_interface
+    );
+    debug!("Querying the device capabilities: {}", url);
+
+    let output = send_request("GET", &url, None, transport)?;
+    if transport.dry_run {
+        return Ok(CapabilitiesResponse::default());
+    }
+
+    if output.stderr.len() > 0 {
+        let stderr = String::from_utf8(output.stderr)
+            .map_err(|e| format!("Failed to parse the stderr: {}", e))?;
+        if stderr.contains("4.04") {
+            debug!("The device doesn't report its capabilities");
+            return Ok(CapabilitiesResponse::default());
+        }
+        Err(format!("aiocoap-client failed with: {}", stderr))?
+    }
+
+    let response = String::from_utf8(output.stdout)
+        .map_err(|e| format!("Failed to parse the response: {}", e))?;
+
+    serde_json::from_str::<CapabilitiesResponse>(&response)
+        .map_err(|e| format!("Failed to parse the device capabilities: {}", e))
+}
+
+fn cache() -> &'static Mutex<HashMap<String, CapabilitiesResponse>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, CapabilitiesResponse>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Same as [`query_capabilities`], but each device is only queried once per
+/// process, so that the loops executing the same program repeatedly (e.g.
+/// bench, sweep) don't send an additional request for every execution. The
+/// capabilities reported in the dry-run mode aren't cached.
+pub fn cached_capabilities(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    transport: &TransportOptions,
+) -> Result<CapabilitiesResponse, String> {
+    if let Some(capabilities) = cache().lock().unwrap().get(riot_ipv6_addr) {
+        return Ok(*capabilities);
+    }
+    let capabilities = query_capabilities(riot_ipv6_addr, host_network_interface, transport)?;
+    if !transport.dry_run {
+        cache()
+            .lock()
+            .unwrap()
+            .insert(riot_ipv6_addr.to_string(), capabilities);
+    }
+    Ok(capabilities)
+}
+
+/// Drops the cached capabilities of the device, e.g. after it was flashed
+/// with a different firmware.
+pub fn forget_capabilities(riot_ipv6_addr: &str) {
+    cache().lock().unwrap().remove(riot_ipv6_addr);
+}
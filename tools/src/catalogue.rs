@@ -0,0 +1,222 @@
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use enum_iterator::all;
+use log::debug;
+use micro_bpf_common::HelperFunctionID;
+
+/// Prefix of the helper IDs in the registration table of the firmware, e.g.
+/// `BPF_FUNC_BPF_PRINTF = 0x01,`
+const FIRMWARE_PREFIX: &str = "BPF_FUNC_";
+
+/// Directories which never contain the registration table of the firmware,
+/// the tools keep their own copies of the header for compiling the test
+/// programs and those must not be mistaken for the source of truth.
+const SKIPPED_DIRECTORIES: &[&str] = &[".git", "target", "tools", "build", "bin"];
+
+/// Helper ID defined in the registration table of the firmware.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HelperDefinition {
+    /// Name without the `BPF_FUNC_` prefix, e.g. `BPF_PRINTF`.
+    pub name: String,
+    pub id: u8,
+    /// Location of the definition, `<file>:<line>`.
+    pub source: String,
+}
+
+/// Difference between the helpers of the firmware and the client-side
+/// catalogue ([`HelperFunctionID`]).
+#[derive(Debug, Default)]
+pub struct CatalogueDiff {
+    /// Helpers of the firmware missing from the catalogue.
+    pub added: Vec<HelperDefinition>,
+    /// Helpers of the catalogue which the firmware doesn't define.
+    pub removed: Vec<HelperFunctionID>,
+}
+
+impl CatalogueDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl fmt::Display for CatalogueDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "The helper catalogue is in sync with the firmware");
+        }
+        for helper in &self.added {
+            writeln!(
+                f,
+                "+ {:#04x} {} ({})",
+                helper.id, helper.name, helper.source
+            )?;
+        }
+        for helper in &self.removed {
+            writeln!(f, "- {:#04x} {:?}", *helper as u8, helper)?;
+        }
+        Ok(())
+    }
+}
+
+/// Finds the headers of the firmware defining the helper IDs, i.e. the ones
+/// containing `BPF_FUNC_<name> = <id>` entries.
+pub fn find_helper_tables(mibpf_root_dir: &str) -> Result<Vec<PathBuf>, String> {
+    let mut tables = vec![];
+    let mut pending = vec![PathBuf::from(mibpf_root_dir)];
+    while let Some(dir) = pending.pop() {
+        let entries = fs::read_dir(&dir)
+            .map_err(|e| format!("Failed to read the directory {}: {}", dir.display(), e))?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            if path.is_dir() {
+                if !SKIPPED_DIRECTORIES.contains(&name.as_str()) {
+                    pending.push(path);
+                }
+            } else if name.ends_with(".h") {
+                let contents = fs::read_to_string(&path).unwrap_or_default();
+                if !parse_helper_table(&contents, &path).is_empty() {
+                    tables.push(path);
+                }
+            }
+        }
+    }
+    tables.sort();
+    if tables.is_empty() {
+        return Err(format!(
+            "No helper registration table (BPF_FUNC_* definitions) found in {}",
+            mibpf_root_dir
+        ));
+    }
+    Ok(tables)
+}
+
+/// Extracts the helper IDs from the enum registering the helpers in the
+/// firmware header.
+pub fn parse_helper_table(contents: &str, path: &Path) -> Vec<HelperDefinition> {
+    contents
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let line = line.trim();
+            let (name, value) = line.strip_prefix(FIRMWARE_PREFIX)?.split_once('=')?;
+            let value = value.trim().trim_end_matches(',').trim();
+            let id = match value.strip_prefix("0x") {
+                Some(hex) => u8::from_str_radix(hex, 16).ok()?,
+                None => value.parse::<u8>().ok()?,
+            };
+            Some(HelperDefinition {
+                name: name.trim().to_string(),
+                id,
+                source: format!("{}:{}", path.display(), i + 1),
+            })
+        })
+        .collect()
+}
+
+/// Loads the helper definitions from all registration tables and fails if
+/// two helpers share an ID or the same helper is given different IDs.
+pub fn load_helper_definitions(tables: &[PathBuf]) -> Result<Vec<HelperDefinition>, String> {
+    let mut by_id: BTreeMap<u8, HelperDefinition> = BTreeMap::new();
+    let mut by_name: BTreeMap<String, HelperDefinition> = BTreeMap::new();
+    let mut clashes = vec![];
+
+    for table in tables {
+        let contents = fs::read_to_string(table)
+            .map_err(|e| format!("Failed to read {}: {}", table.display(), e))?;
+        for helper in parse_helper_table(&contents, table) {
+            debug!("Found the helper {:?}", helper);
+            if let Some(other) = by_id.get(&helper.id) {
+                if other.name != helper.name {
+                    clashes.push(format!(
+                        "{:#04x} is used by both {} ({}) and {} ({})",
+                        helper.id, other.name, other.source, helper.name, helper.source
+                    ));
+                }
+            }
+            if let Some(other) = by_name.get(&helper.name) {
+                if other.id != helper.id {
+                    clashes.push(format!(
+                        "{} is defined as both {:#04x} ({}) and {:#04x} ({})",
+                        helper.name, other.id, other.source, helper.id, helper.source
+                    ));
+                }
+            }
+            by_id.entry(helper.id).or_insert_with(|| helper.clone());
+            by_name.entry(helper.name.clone()).or_insert(helper);
+        }
+    }
+
+    if !clashes.is_empty() {
+        return Err(format!(
+            "Conflicting helper IDs in the firmware:\n{}",
+            clashes.join("\n")
+        ));
+    }
+    Ok(by_id.into_values().collect())
+}
+
+/// Compares the helpers of the firmware with the client-side catalogue by
+/// their IDs.
+pub fn diff_catalogue(helpers: &[HelperDefinition]) -> CatalogueDiff {
+    let known = all::<HelperFunctionID>().collect::<Vec<HelperFunctionID>>();
+    CatalogueDiff {
+        added: helpers
+            .iter()
+            .filter(|helper| !known.iter().any(|known| *known as u8 == helper.id))
+            .cloned()
+            .collect(),
+        removed: known
+            .into_iter()
+            .filter(|known| !helpers.iter().any(|helper| helper.id == *known as u8))
+            .collect(),
+    }
+}
+
+/// Generates the variants of [`HelperFunctionID`] for the firmware helpers.
+/// The helpers already present in the catalogue keep their names so that
+/// the code using them doesn't break, the new ones are named after the
+/// firmware definition with the `_IDX` suffix.
+pub fn generate_catalogue(helpers: &[HelperDefinition]) -> String {
+    helpers
+        .iter()
+        .map(|helper| {
+            let known: Option<HelperFunctionID> = num::FromPrimitive::from_u8(helper.id);
+            let name = match known {
+                Some(known) => format!("{:?}", known),
+                None if helper.name.starts_with("BPF_") => format!("{}_IDX", helper.name),
+                None => format!("BPF_{}_IDX", helper.name),
+            };
+            format!("    {} = {:#04x},\n", name, helper.id)
+        })
+        .collect()
+}
+
+/// Replaces the variants of the `HelperFunctionID` enum in the source file of
+/// the catalogue (`common/src/enumerations.rs`) with the generated ones.
+pub fn regenerate_catalogue(catalogue: &str, helpers: &[HelperDefinition]) -> Result<(), String> {
+    let source = fs::read_to_string(catalogue)
+        .map_err(|e| format!("Failed to read the catalogue {}: {}", catalogue, e))?;
+    let declaration = "pub enum HelperFunctionID {\n";
+    let start = source
+        .find(declaration)
+        .map(|i| i + declaration.len())
+        .ok_or_else(|| format!("No HelperFunctionID enum found in {}", catalogue))?;
+    let end = source[start..]
+        .find("\n}")
+        .map(|i| start + i + 1)
+        .ok_or_else(|| format!("Unterminated HelperFunctionID enum in {}", catalogue))?;
+
+    let regenerated = format!(
+        "{}{}{}",
+        &source[..start],
+        generate_catalogue(helpers),
+        &source[end..]
+    );
+    fs::write(catalogue, regenerated)
+        .map_err(|e| format!("Failed to write the catalogue {}: {}", catalogue, e))
+}
@@ -0,0 +1,167 @@
+use std::{fmt, str::FromStr};
+
+use enum_iterator::all;
+use log::debug;
+use micro_bpf_common::{
+    BinaryFileLayout, ExecutionModel, ExecutionResponse, HelperAccessListSource,
+    HelperAccessVerification, HelperFunctionID, TargetVM,
+};
+
+use crate::{
+    execute::{execute, ExecuteOptions, PacketInjection},
+    transport::TransportOptions,
+};
+
+/// Controls how the state returned by a program is framed before it is
+/// passed to the next program of the chain. The programs only see the raw
+/// payload of the packet, so the framing is up to the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateEncoding {
+    /// The returned buffer is passed as is.
+    Raw,
+    /// The buffer is preceded by its length as a little-endian u32, so that
+    /// the program can tell the state apart from the rest of the payload.
+    LengthPrefixed,
+}
+
+impl FromStr for StateEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(StateEncoding::Raw),
+            "length-prefixed" => Ok(StateEncoding::LengthPrefixed),
+            _ => Err(format!("Unknown state encoding: {}", s)),
+        }
+    }
+}
+
+impl StateEncoding {
+    pub fn encode(&self, state: &[u8]) -> Vec<u8> {
+        match self {
+            StateEncoding::Raw => state.to_vec(),
+            StateEncoding::LengthPrefixed => (state.len() as u32)
+                .to_le_bytes()
+                .iter()
+                .chain(state)
+                .copied()
+                .collect(),
+        }
+    }
+}
+
+/// A single program of the chain, the steps can be executed on different
+/// devices.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainStep {
+    pub riot_ipv6_addr: String,
+    pub host_network_interface: String,
+    pub suit_storage_slot: usize,
+}
+
+impl ChainStep {
+    /// Parses the step given as `<slot>[@<ipv6 address>]`, the default device
+    /// is used if the address is omitted.
+    pub fn parse(
+        spec: &str,
+        default_riot_ipv6_addr: &str,
+        host_network_interface: &str,
+    ) -> Result<Self, String> {
+        let (slot, device) = spec
+            .split_once('@')
+            .unwrap_or((spec, default_riot_ipv6_addr));
+        let suit_storage_slot = slot
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid SUIT storage slot in the chain step: {}", spec))?;
+        Ok(ChainStep {
+            riot_ipv6_addr: device.to_string(),
+            host_network_interface: host_network_interface.to_string(),
+            suit_storage_slot,
+        })
+    }
+}
+
+impl fmt::Display for ChainStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "slot {}@{}", self.suit_storage_slot, self.riot_ipv6_addr)
+    }
+}
+
+/// Executes the programs one after another, the buffer returned by each of
+/// them (using `bpf_set_return_buffer`) is the state passed to the next one
+/// in the payload of its packet. This allows for splitting a computation
+/// which doesn't fit onto a single constrained device. The first program
+/// receives the initial state if it isn't empty.
+///
+/// Returns the responses of all steps, the state returned by the last one
+/// is the result of the chain.
+pub async fn execute_chain(
+    steps: &[ChainStep],
+    target: TargetVM,
+    binary_layout: BinaryFileLayout,
+    jit: bool,
+    encoding: StateEncoding,
+    initial_state: &[u8],
+    transport: &TransportOptions,
+) -> Result<Vec<ExecutionResponse>, String> {
+    let helpers = all::<HelperFunctionID>()
+        .map(|h| h as u8)
+        .collect::<Vec<u8>>();
+
+    let mut state = initial_state.to_vec();
+    let mut responses = vec![];
+    for (i, step) in steps.iter().enumerate() {
+        transport.check_cancelled()?;
+        let packet = (i > 0 || !state.is_empty()).then(|| PacketInjection {
+            payload: encoding.encode(&state),
+            queries: vec![],
+        });
+        debug!(
+            "Executing the chain step {} ({}) with {} bytes of state",
+            i,
+            step,
+            state.len()
+        );
+
+        let response = execute(
+            &step.riot_ipv6_addr,
+            target,
+            binary_layout,
+            step.suit_storage_slot,
+            &step.host_network_interface,
+            ExecutionModel::WithAccessToCoapPacket,
+            HelperAccessVerification::AheadOfTime,
+            HelperAccessListSource::ExecuteRequest,
+            &helpers,
+            jit,
+            &ExecuteOptions {
+                jit_compile: jit,
+                packet: packet.as_ref(),
+                ..Default::default()
+            },
+            transport,
+        )
+        .await
+        .map_err(|e| format!("Chain step {} ({}) failed: {}", i, step, e))?;
+        if transport.dry_run {
+            continue;
+        }
+
+        let response =
+            serde_json::from_str::<ExecutionResponse>(response.trim_matches(char::from(0)))
+                .map_err(|e| format!("Failed to parse the response of step {}: {}", i, e))?;
+        state = match &response.buffer {
+            Some(buffer) => buffer.bytes()?,
+            // Only the last program doesn't need to return any state.
+            None if i + 1 < steps.len() => {
+                return Err(format!(
+                    "Chain step {} ({}) didn't return any state for the next step",
+                    i, step
+                ))
+            }
+            None => vec![],
+        };
+        responses.push(response);
+    }
+    Ok(responses)
+}
@@ -0,0 +1,409 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    str::FromStr,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use enum_iterator::all;
+use log::{debug, info, warn};
+use micro_bpf_common::{
+    BinaryFileLayout, ExecutionModel, HelperAccessListSource, HelperAccessVerification,
+    HelperFunctionID, StorageBackend, TargetVM,
+};
+
+use crate::{
+    artifacts::ArtifactStore,
+    deploy::{deploy, DeployOptions},
+    environment::Environment,
+    execute::{execute, ExecuteOptions},
+    logs::fetch_logs,
+    publish::LocalPublisher,
+    slots::SuitSlot,
+    transport::{is_cancelled, send_request, TransportOptions},
+};
+
+#[cfg(feature = "telemetry")]
+use crate::telemetry::TelemetrySink;
+
+/// Format of the file that the collected samples are written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+    /// One row per field: `timestamp_ms,device,field,value`.
+    Csv,
+    /// InfluxDB line protocol, one line per sample with the device and the
+    /// slot as tags: `sensors,device=fe80::1,slot=0 temp=21.5 1718000000000000000`
+    Influx,
+}
+
+impl FromStr for SampleFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(SampleFormat::Csv),
+            "influx" => Ok(SampleFormat::Influx),
+            _ => Err(format!("Unknown sample format: {}", s)),
+        }
+    }
+}
+
+/// A single reading produced by the sensing program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    /// Time when the tool received the sample, the devices don't have a
+    /// synchronised clock.
+    pub timestamp_ms: u64,
+    pub device: String,
+    pub fields: Vec<(String, f64)>,
+}
+
+/// Parses a line printed by the sensing program (e.g. using `bpf_printf`)
+/// into the fields of a sample. The line is either a flat JSON object with
+/// numeric values: `{"temp": 21.5, "humidity": 40}`, or a list of key-value
+/// pairs: `temp=21.5 humidity=40`. Returns None for the lines which don't
+/// contain any readings so that the program can still print other messages.
+pub fn parse_sample(line: &str) -> Option<Vec<(String, f64)>> {
+    let line = line.trim();
+    let fields = if line.starts_with('{') {
+        let object =
+            serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(line).ok()?;
+        object
+            .into_iter()
+            .filter_map(|(key, value)| value.as_f64().map(|value| (key, value)))
+            .collect::<Vec<(String, f64)>>()
+    } else {
+        line.split_whitespace()
+            .map(|pair| {
+                let (key, value) = pair.split_once('=')?;
+                Some((key.to_string(), value.parse::<f64>().ok()?))
+            })
+            .collect::<Option<Vec<(String, f64)>>>()?
+    };
+    (!fields.is_empty()).then_some(fields)
+}
+
+/// Appends the collected samples to a file (or stdout) in the given format.
+/// Each sample is flushed as soon as it is written so that a long collection
+/// can be inspected (or imported) while it is still running.
+pub struct SampleWriter {
+    format: SampleFormat,
+    /// Name of the InfluxDB measurement.
+    measurement: String,
+    slot: usize,
+    output: Box<dyn Write>,
+    /// Store that the samples are also written to, see [`Self::with_sink`].
+    #[cfg(feature = "telemetry")]
+    sink: Option<TelemetrySink>,
+}
+
+impl SampleWriter {
+    /// Opens the output file for appending, the samples are printed to stdout
+    /// if no file is specified.
+    pub fn open(
+        path: Option<&str>,
+        format: SampleFormat,
+        measurement: &str,
+        slot: usize,
+    ) -> Result<Self, String> {
+        let (output, is_empty): (Box<dyn Write>, bool) = match path {
+            Some(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| format!("Failed to open {}: {}", path, e))?;
+                let is_empty = file.metadata().map(|m| m.len() == 0).unwrap_or(true);
+                (Box::new(file), is_empty)
+            }
+            None => (Box::new(io::stdout()), true),
+        };
+
+        let mut writer = SampleWriter {
+            format,
+            measurement: measurement.to_string(),
+            slot,
+            output,
+            #[cfg(feature = "telemetry")]
+            sink: None,
+        };
+        if format == SampleFormat::Csv && is_empty {
+            writer.write_line("timestamp_ms,device,field,value")?;
+        }
+        Ok(writer)
+    }
+
+    /// Writes the samples into the time-series store as well. The samples
+    /// are still written into the file, so a failure to reach the store only
+    /// produces a warning.
+    #[cfg(feature = "telemetry")]
+    pub fn with_sink(mut self, sink: Option<TelemetrySink>) -> Self {
+        self.sink = sink;
+        self
+    }
+
+    pub fn write(&mut self, sample: &Sample) -> Result<(), String> {
+        #[cfg(feature = "telemetry")]
+        if let Some(sink) = &self.sink {
+            let samples = std::slice::from_ref(sample);
+            if let Err(e) = sink.write_samples(&self.measurement, self.slot, samples) {
+                warn!("Failed to write the sample into the telemetry sink: {}", e);
+            }
+        }
+
+        match self.format {
+            SampleFormat::Csv => {
+                for (field, value) in &sample.fields {
+                    let line = format!(
+                        "{},{},{},{}",
+                        sample.timestamp_ms, sample.device, field, value
+                    );
+                    self.write_line(&line)?;
+                }
+                Ok(())
+            }
+            SampleFormat::Influx => {
+                let slot = self.slot.to_string();
+                let line = influx_line(
+                    &self.measurement,
+                    &[("device", &sample.device), ("slot", &slot)],
+                    &sample.fields,
+                    sample.timestamp_ms,
+                );
+                self.write_line(&line)
+            }
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.output, "{}", line)
+            .and_then(|_| self.output.flush())
+            .map_err(|e| format!("Failed to write the sample: {}", e))
+    }
+}
+
+/// Formats a single point of the InfluxDB line protocol.
+pub(crate) fn influx_line(
+    measurement: &str,
+    tags: &[(&str, &str)],
+    fields: &[(String, f64)],
+    timestamp_ms: u64,
+) -> String {
+    let tags = tags
+        .iter()
+        .map(|(tag, value)| format!(",{}={}", escape_influx(tag), escape_influx(value)))
+        .collect::<String>();
+    let fields = fields
+        .iter()
+        .map(|(field, value)| format!("{}={}", escape_influx(field), value))
+        .collect::<Vec<String>>();
+    format!(
+        "{}{} {} {}",
+        escape_influx(measurement),
+        tags,
+        fields.join(","),
+        timestamp_ms * 1_000_000
+    )
+}
+
+/// Escapes the characters that have a special meaning in the names and tag
+/// values of the line protocol.
+fn escape_influx(s: &str) -> String {
+    s.replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+/// Controls which program is deployed by [`collect`] and how often it runs.
+#[derive(Debug, Clone)]
+pub struct CollectConfig {
+    /// Source file of the sensing program.
+    pub source: String,
+    pub target: TargetVM,
+    pub binary_layout: BinaryFileLayout,
+    pub jit: bool,
+    pub suit_storage_slot: usize,
+    /// How often the firmware runs the program.
+    pub period: Duration,
+    /// How often the output of the program is fetched from the devices.
+    pub poll_interval: Duration,
+    /// The collection stops after this time, it runs until cancelled (e.g. by
+    /// Ctrl-C) if not specified.
+    pub duration: Option<Duration>,
+}
+
+/// Deploys the sensing program onto the device described by the environment
+/// and the additional devices reachable through the same host interface,
+/// schedules it to run periodically as a long-running program and writes the
+/// samples it prints until the collection is over. Returns the number of collected samples.
+pub async fn collect(
+    environment: &Environment,
+    devices: &[String],
+    config: &CollectConfig,
+    writer: &mut SampleWriter,
+    transport: &TransportOptions,
+) -> Result<usize, String> {
+    let devices = std::iter::once(environment.riot_instance_ip.clone())
+        .chain(devices.iter().cloned())
+        .collect::<Vec<String>>();
+
+    for device in &devices {
+        let environment = Environment {
+            riot_instance_ip: device.clone(),
+            ..environment.clone()
+        };
+        start_program(&environment, config, transport).await?;
+        info!(
+            "Collecting from {} (slot {}) every {}ms",
+            device,
+            config.suit_storage_slot,
+            config.period.as_millis()
+        );
+    }
+
+    // The log lines which were printed before the program got started aren't
+    // samples, so the first batch of each device is skipped.
+    let mut since = vec![None; devices.len()];
+    let mut collected = 0;
+    let start = Instant::now();
+    loop {
+        for (device, since) in devices.iter().zip(since.iter_mut()) {
+            let batch = match fetch_logs(
+                device,
+                &environment.host_net_if,
+                Some(config.suit_storage_slot),
+                since.unwrap_or(0),
+                transport,
+            ) {
+                Err(e) if is_cancelled(&e) => return Ok(collected),
+                // A device which is temporarily unreachable shouldn't stop the
+                // collection from the other ones.
+                Err(e) => {
+                    warn!("Failed to fetch the samples from {}: {}", device, e);
+                    continue;
+                }
+                Ok(batch) => batch,
+            };
+            if since.is_some() {
+                let timestamp_ms = now_ms();
+                for line in &batch.lines {
+                    let Some(fields) = parse_sample(line) else {
+                        debug!("Skipping the line from {}: {}", device, line);
+                        continue;
+                    };
+                    writer.write(&Sample {
+                        timestamp_ms,
+                        device: device.clone(),
+                        fields,
+                    })?;
+                    collected += 1;
+                }
+            }
+            *since = Some(batch.next);
+        }
+
+        if transport.dry_run || config.duration.is_some_and(|d| start.elapsed() >= d) {
+            return Ok(collected);
+        }
+        thread::sleep(config.poll_interval);
+        if transport.check_cancelled().is_err() {
+            return Ok(collected);
+        }
+    }
+}
+
+async fn start_program(
+    environment: &Environment,
+    config: &CollectConfig,
+    transport: &TransportOptions,
+) -> Result<(), String> {
+    let helpers = all::<HelperFunctionID>()
+        .map(|h| h as u8)
+        .collect::<Vec<u8>>();
+
+    deploy(
+        &config.source,
+        &ArtifactStore::from_env(environment),
+        config.target,
+        config.binary_layout,
+        SuitSlot::Fixed(config.suit_storage_slot),
+        StorageBackend::Ram,
+        &environment.riot_instance_net_if,
+        &environment.riot_instance_ip,
+        &environment.host_net_if,
+        &environment.host_ip,
+        &environment.board_name,
+        Some(&environment.micro_bpf_root_dir),
+        helpers.clone(),
+        HelperAccessVerification::AheadOfTime,
+        HelperAccessListSource::ExecuteRequest,
+        &DeployOptions::default(),
+        &LocalPublisher::new(&environment.coap_root_dir),
+        transport,
+    )
+    .await?;
+
+    execute(
+        &environment.riot_instance_ip,
+        config.target,
+        config.binary_layout,
+        config.suit_storage_slot,
+        &environment.host_net_if,
+        ExecutionModel::LongRunning,
+        HelperAccessVerification::AheadOfTime,
+        HelperAccessListSource::ExecuteRequest,
+        &helpers,
+        config.jit,
+        &ExecuteOptions {
+            jit_compile: config.jit,
+            ..Default::default()
+        },
+        transport,
+    )
+    .await?;
+
+    schedule(
+        &environment.riot_instance_ip,
+        &environment.host_net_if,
+        config.suit_storage_slot,
+        config.period,
+        transport,
+    )
+}
+
+/// Asks the firmware to re-run the long-running program from the slot with
+/// the given period by sending a POST request to
+/// `/long-running/schedule?slot=<slot>&period=<ms>`. This way the sensing
+/// program doesn't need to implement the waiting loop itself.
+pub fn schedule(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    suit_storage_slot: usize,
+    period: Duration,
+    transport: &TransportOptions,
+) -> Result<(), String> {
+    let url = format!(
+        "coap://[{}%{}]/long-running/schedule?slot={}&period={}",
+        riot_ipv6_addr,
+        host_network_interface,
+        suit_storage_slot,
+        period.as_millis()
+    );
+    debug!("Scheduling the program: {}", url);
+
+    let output = send_request("POST", &url, None, transport)?;
+    if !output.stderr.is_empty() {
+        let stderr = String::from_utf8(output.stderr)
+            .map_err(|e| format!("Failed to parse the stderr: {}", e))?;
+        Err(format!("aiocoap-client failed with: {}", stderr))?
+    }
+    Ok(())
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
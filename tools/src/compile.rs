@@ -5,6 +5,10 @@ use std::{path::PathBuf, process::Command};
 
 use log::debug;
 
+/// Error reported when the RIOT build system fails to compile the program,
+/// the commands compiling it as one of their steps (e.g. deploy) propagate it.
+pub const COMPILATION_FAILED: &str = "Failed to compile the eBPF bytecode";
+
 /// The compilation process uses the RIOT build system for compiling ebpf
 /// programs. It uses the rbpf.inc.mk makefile which compiles the source
 /// files and applies the Femto-Containers gen_rbf script to them producing
@@ -37,7 +41,7 @@ pub fn compile(
     let source_dir_name = source_directory.to_str().unwrap();
 
     let Ok(output) = compile_with_riot_build_system(file_name, source_dir_name) else {
-        return Err("Failed to compile the eBPF bytecode.".to_string());
+        return Err(format!("{}.", COMPILATION_FAILED));
     };
 
     debug!("Compilation command output: \n{:?}", output);
@@ -0,0 +1,188 @@
+use std::{collections::BTreeMap, fs, path::Path, str::FromStr};
+
+use clap_complete::Shell;
+
+use crate::{
+    environment::{config_file_path, load_env},
+    registry::Registry,
+};
+
+/// Prefix of the .env files of the device profiles, e.g. `.env-nucleo`.
+const PROFILE_PREFIX: &str = ".env-";
+
+/// Directory containing the test programs of the tools.
+const TEST_SOURCES_DIR: &str = "tests/test-sources";
+
+/// Values of the command line arguments which depend on the configuration
+/// and the state of the devices, and so can't be listed in the completion
+/// scripts generated by clap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    /// Names of the device profiles (`.env-<name>` files).
+    Profiles,
+    /// Addresses of the devices from the registry and the device profiles.
+    Devices,
+    /// SUIT storage slots recorded in the registry, described by the
+    /// programs deployed into them.
+    Slots,
+    /// Source files of the test programs.
+    Programs,
+    /// Helper profiles from the config file.
+    HelperProfiles,
+}
+
+impl FromStr for CompletionKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "profiles" => Ok(CompletionKind::Profiles),
+            "devices" => Ok(CompletionKind::Devices),
+            "slots" => Ok(CompletionKind::Slots),
+            "programs" => Ok(CompletionKind::Programs),
+            "helper-profiles" => Ok(CompletionKind::HelperProfiles),
+            _ => Err(format!("Unknown completion kind: {}", s)),
+        }
+    }
+}
+
+/// Options completed dynamically together with the kind of their values.
+const COMPLETED_OPTIONS: &[(&str, &str)] = &[
+    ("profile", "profiles"),
+    ("riot-ipv6-addr", "devices"),
+    ("suit-storage-slot", "slots"),
+    ("bpf-source-file", "programs"),
+    ("helpers", "helper-profiles"),
+];
+
+/// Lists the completion candidates of the given kind together with their
+/// descriptions (possibly empty). Completion must never fail, so anything
+/// that can't be read is skipped.
+pub fn candidates(kind: CompletionKind) -> Vec<(String, String)> {
+    let mut candidates = BTreeMap::new();
+    match kind {
+        CompletionKind::Profiles => {
+            for name in profile_files() {
+                let profile = name.trim_start_matches(PROFILE_PREFIX).to_string();
+                candidates.insert(profile, name);
+            }
+        }
+        CompletionKind::Devices => {
+            for name in profile_files() {
+                let contents = fs::read_to_string(&name).unwrap_or_default();
+                if let Some(ip) = profile_variable(&contents, "RIOT_INSTANCE_IP") {
+                    let profile = name.trim_start_matches(PROFILE_PREFIX);
+                    candidates.insert(ip, format!("profile {}", profile));
+                }
+            }
+            for ip in Registry::load().unwrap_or_default().devices.into_keys() {
+                candidates.entry(ip).or_insert_with(String::new);
+            }
+        }
+        CompletionKind::Slots => {
+            // The slots of all devices are listed, the device isn't known at
+            // the time of completing the slot.
+            let registry = Registry::load().unwrap_or_default();
+            for device in registry.devices.values() {
+                for (slot, record) in &device.slots {
+                    candidates.insert(slot.to_string(), record.program.clone());
+                }
+            }
+        }
+        CompletionKind::Programs => {
+            for dir in [load_env().src_dir, TEST_SOURCES_DIR.to_string()] {
+                let Ok(entries) = fs::read_dir(&dir) else {
+                    continue;
+                };
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    if path.extension().is_some_and(|ext| ext == "c") {
+                        candidates.insert(path.display().to_string(), String::new());
+                    }
+                }
+            }
+        }
+        CompletionKind::HelperProfiles => {
+            let contents = fs::read_to_string(config_file_path()).unwrap_or_default();
+            let table = contents.parse::<toml::Table>().unwrap_or_default();
+            if let Some(profiles) = table.get("helper_profiles").and_then(|p| p.as_table()) {
+                for (name, helpers) in profiles {
+                    candidates.insert(name.clone(), helpers.to_string());
+                }
+            }
+        }
+    }
+    candidates.into_iter().collect()
+}
+
+fn profile_files() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(".") else {
+        return vec![];
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| name.starts_with(PROFILE_PREFIX) && Path::new(name).is_file())
+        .collect()
+}
+
+fn profile_variable(contents: &str, key: &str) -> Option<String> {
+    contents.lines().find_map(|line| {
+        let (name, value) = line.trim().split_once('=')?;
+        (name.trim() == key).then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// Extends the completion script generated by clap so that the values of
+/// the [`COMPLETED_OPTIONS`] are completed by calling the hidden `complete`
+/// subcommand of the tool. Only bash and fish are supported, the scripts for
+/// the other shells are returned unchanged.
+pub fn with_dynamic_completion(shell: Shell, name: &str, script: String) -> String {
+    match shell {
+        Shell::Bash => {
+            // The function generated by clap is wrapped, it completes the
+            // options whose values aren't known dynamically.
+            let Some(generated) = script
+                .lines()
+                .rev()
+                .find_map(|line| line.strip_prefix("complete -F "))
+                .and_then(|line| line.split_whitespace().next())
+                .map(str::to_string)
+            else {
+                return script;
+            };
+            let cases = COMPLETED_OPTIONS
+                .iter()
+                .map(|(option, kind)| format!("        --{}) kind={} ;;\n", option, kind))
+                .collect::<String>();
+            format!(
+                "{script}
+_{fn_name}_dynamic() {{
+    local cur prev kind
+    cur=\"${{COMP_WORDS[COMP_CWORD]}}\"
+    prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"
+    case \"${{prev}}\" in
+{cases}        *) {generated} \"$@\"; return ;;
+    esac
+    COMPREPLY=($(compgen -W \"$({name} complete ${{kind}} 2>/dev/null)\" -- \"${{cur}}\"))
+}}
+complete -F _{fn_name}_dynamic -o bashdefault -o default {name}
+",
+                fn_name = name.replace('-', "_"),
+            )
+        }
+        Shell::Fish => {
+            let completions = COMPLETED_OPTIONS
+                .iter()
+                .map(|(option, kind)| {
+                    format!(
+                        "complete -c {} -l {} -f -a \"({} complete --descriptions {})\"\n",
+                        name, option, name, kind
+                    )
+                })
+                .collect::<String>();
+            format!("{}{}", script, completions)
+        }
+        _ => script,
+    }
+}
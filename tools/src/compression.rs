@@ -0,0 +1,99 @@
+use std::{fmt, io::Write, str::FromStr};
+
+use flate2::{write::ZlibEncoder, Compression};
+use log::{info, warn};
+use micro_bpf_common::{CAPABILITY_ARTIFACT_DEFLATE, CAPABILITY_ARTIFACT_ZSTD};
+
+use crate::{capabilities::query_capabilities, transport::TransportOptions};
+
+/// Compression of the binaries fetched by the devices. The compressed binary
+/// is served next to the original one and the SUIT manifest tells the device
+/// to decompress it while fetching, so fewer bytes are sent over 6LoWPAN.
+/// The digest in the manifest is still the one of the uncompressed binary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtifactCompression {
+    /// Deflate stream with the zlib wrapper (RFC 1950).
+    Deflate,
+    Zstd,
+}
+
+impl FromStr for ArtifactCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "deflate" => Ok(ArtifactCompression::Deflate),
+            "zstd" => Ok(ArtifactCompression::Zstd),
+            _ => Err(format!(
+                "Unknown artifact compression: {}, expected deflate or zstd",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ArtifactCompression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArtifactCompression::Deflate => write!(f, "deflate"),
+            ArtifactCompression::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+impl ArtifactCompression {
+    /// Capability flag of the firmware able to decompress the artifacts.
+    pub fn capability(&self) -> u32 {
+        match self {
+            ArtifactCompression::Deflate => CAPABILITY_ARTIFACT_DEFLATE,
+            ArtifactCompression::Zstd => CAPABILITY_ARTIFACT_ZSTD,
+        }
+    }
+
+    /// Extension appended to the name of the compressed artifact.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArtifactCompression::Deflate => "zz",
+            ArtifactCompression::Zstd => "zst",
+        }
+    }
+
+    pub fn compress(&self, bytes: &[u8]) -> Result<Vec<u8>, String> {
+        let compressed = match self {
+            ArtifactCompression::Deflate => {
+                let mut encoder = ZlibEncoder::new(vec![], Compression::best());
+                encoder.write_all(bytes).and_then(|_| encoder.finish())
+            }
+            ArtifactCompression::Zstd => zstd::encode_all(bytes, zstd::DEFAULT_COMPRESSION_LEVEL),
+        };
+        compressed.map_err(|e| format!("Failed to compress the artifact using {}: {}", self, e))
+    }
+}
+
+/// Checks whether the device is able to decompress the artifacts compressed
+/// using the requested compression. The devices which aren't fetch the
+/// artifacts uncompressed.
+pub fn negotiate_compression(
+    requested: Option<ArtifactCompression>,
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    transport: &TransportOptions,
+) -> Result<Option<ArtifactCompression>, String> {
+    let Some(compression) = requested else {
+        return Ok(None);
+    };
+    let capabilities = query_capabilities(riot_ipv6_addr, host_network_interface, transport)?;
+    if transport.dry_run {
+        info!(
+            "[dry-run] assuming that the device supports {} compressed artifacts",
+            compression
+        );
+    } else if !capabilities.supports(compression.capability()) {
+        warn!(
+            "The device doesn't support {} compressed artifacts, deploying uncompressed",
+            compression
+        );
+        return Ok(None);
+    }
+    Ok(Some(compression))
+}
@@ -0,0 +1,389 @@
+use std::{fmt, fs, str::FromStr, thread, time::Duration};
+
+use enum_iterator::all;
+use log::debug;
+use micro_bpf_common::{
+    BinaryFileLayout, ExecutionModel, HelperAccessListSource, HelperAccessVerification,
+    HelperFunctionID, StorageBackend, TargetVM,
+};
+use serde::Deserialize;
+
+use crate::{
+    artifacts::ArtifactStore,
+    deploy::{deploy, DeployOptions},
+    environment::Environment,
+    execute::{execute, ExecuteOptions},
+    publish::LocalPublisher,
+    slots::SuitSlot,
+    transport::TransportOptions,
+};
+
+/// Groups of VM functionality exercised by the conformance programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    Arithmetic,
+    Branching,
+    Memory,
+    HelperCalls,
+    PacketAccess,
+}
+
+impl FromStr for Category {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "arithmetic" => Ok(Category::Arithmetic),
+            "branching" => Ok(Category::Branching),
+            "memory" => Ok(Category::Memory),
+            "helpers" => Ok(Category::HelperCalls),
+            "packet" => Ok(Category::PacketAccess),
+            _ => Err(format!("Unknown conformance category: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Category::Arithmetic => "arithmetic",
+            Category::Branching => "branching",
+            Category::Memory => "memory",
+            Category::HelperCalls => "helpers",
+            Category::PacketAccess => "packet",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A single program of the built-in conformance corpus. Same as the test
+/// sources, the first line of each program is the annotation specifying
+/// the expected result: `// TEST_RESULT: 123`
+pub struct ConformanceProgram {
+    pub name: &'static str,
+    pub category: Category,
+    pub source: &'static str,
+}
+
+impl ConformanceProgram {
+    pub fn file_name(&self) -> String {
+        format!("{}.c", self.name)
+    }
+
+    pub fn expected_result(&self) -> &'static str {
+        let first_line = self.source.lines().next().unwrap_or_default();
+        first_line.trim_start_matches("// TEST_RESULT:").trim()
+    }
+
+    /// Programs testing packet access format the CoAP response themselves,
+    /// all other ones are executed using the short-lived execution model.
+    pub fn execution_model(&self) -> ExecutionModel {
+        match self.category {
+            Category::PacketAccess => ExecutionModel::WithAccessToCoapPacket,
+            _ => ExecutionModel::ShortLived,
+        }
+    }
+}
+
+macro_rules! program {
+    ($name:literal, $category:ident) => {
+        ConformanceProgram {
+            name: $name,
+            category: Category::$category,
+            source: include_str!(concat!("../conformance/", $name, ".c")),
+        }
+    };
+}
+
+/// The conformance corpus is embedded in the binary so that firmware ports
+/// can be validated without access to the test sources of this repository.
+pub const PROGRAMS: &[ConformanceProgram] = &[
+    program!("add_immediate", Arithmetic),
+    program!("multiply_reg", Arithmetic),
+    program!("divide_immediate", Arithmetic),
+    program!("mod_reg", Arithmetic),
+    program!("lsl_immediate", Arithmetic),
+    program!("jump_eq", Branching),
+    program!("jump_slt", Branching),
+    program!("for_loop", Branching),
+    program!("while_loop", Branching),
+    program!("load_store_byte_immediate", Memory),
+    program!("load_store_halfword_immediate", Memory),
+    program!("load_store_word_immediate", Memory),
+    program!("rodata", Memory),
+    program!("data_relocations", Memory),
+    program!("helper_call", HelperCalls),
+    program!("helper_call_five_args", HelperCalls),
+    program!("bpf_strlen", HelperCalls),
+    program!("bpf_fmt_u32_dec", HelperCalls),
+    program!("gcoap_response_format", PacketAccess),
+];
+
+const HEADERS: &[(&str, &str)] = &[
+    ("helpers.h", include_str!("../conformance/helpers.h")),
+    ("shared.h", include_str!("../conformance/shared.h")),
+];
+
+/// When deploying to physical boards, we need to give them some time to
+/// fetch the firmware before requesting execution.
+const FIRMWARE_PULL_TIMEOUT: u64 = 1;
+
+/// Outcome of running a single conformance program.
+pub struct ConformanceResult {
+    pub program: &'static ConformanceProgram,
+    /// Error message describing the failure, None if the program passed.
+    pub failure: Option<String>,
+}
+
+impl ConformanceResult {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+pub struct Scorecard {
+    pub results: Vec<ConformanceResult>,
+}
+
+impl Scorecard {
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|r| r.passed()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.passed()
+    }
+}
+
+impl fmt::Display for Scorecard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut categories = self
+            .results
+            .iter()
+            .map(|r| r.program.category)
+            .collect::<Vec<Category>>();
+        categories.dedup();
+
+        for category in categories {
+            let results = self
+                .results
+                .iter()
+                .filter(|r| r.program.category == category)
+                .collect::<Vec<&ConformanceResult>>();
+            let passed = results.iter().filter(|r| r.passed()).count();
+            writeln!(f, "{:<12} {}/{}", category, passed, results.len())?;
+            for result in results {
+                match &result.failure {
+                    None => writeln!(f, "  PASS {}", result.program.name)?,
+                    Some(e) => writeln!(f, "  FAIL {}: {}", result.program.name, e)?,
+                }
+            }
+        }
+        write!(f, "Total: {}/{} passed", self.passed(), self.results.len())
+    }
+}
+
+/// Writes the conformance corpus together with the Makefile pointing to the
+/// RIOT build system into the working directory so that the programs can be
+/// compiled in the same way as any other program, see [`crate::compile`].
+pub fn write_corpus(work_dir: &str, micro_bpf_root_dir: &str) -> Result<(), String> {
+    write_sources(
+        work_dir,
+        micro_bpf_root_dir,
+        PROGRAMS
+            .iter()
+            .map(|p| (p.file_name(), p.source.to_string())),
+    )
+}
+
+/// Writes the given sources next to the helper headers and the Makefile
+/// pointing to the RIOT build system.
+pub fn write_sources(
+    work_dir: &str,
+    micro_bpf_root_dir: &str,
+    sources: impl Iterator<Item = (String, String)>,
+) -> Result<(), String> {
+    fs::create_dir_all(work_dir)
+        .map_err(|e| format!("Failed to create the directory {}: {}", work_dir, e))?;
+
+    let makefile = format!(
+        "RIOTBASE ?= {}/RIOT\ninclude $(RIOTBASE)/makefiles/rbpf.inc.mk\n",
+        fs::canonicalize(micro_bpf_root_dir)
+            .map_err(|e| format!("Invalid micro_bpf root directory: {}", e))?
+            .display()
+    );
+
+    let files = HEADERS
+        .iter()
+        .map(|(name, contents)| (name.to_string(), contents.to_string()))
+        .chain(sources)
+        .chain(std::iter::once(("Makefile".to_string(), makefile)));
+
+    for (name, contents) in files {
+        let path = format!("{}/{}", work_dir, name);
+        fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    }
+    Ok(())
+}
+
+/// Deploys and executes all conformance programs belonging to the selected
+/// categories (all of them if none are specified) on the device described by
+/// the environment and collects the results into a scorecard.
+pub async fn run_conformance(
+    environment: &Environment,
+    work_dir: &str,
+    target: TargetVM,
+    binary_layout: BinaryFileLayout,
+    categories: &[Category],
+    jit: bool,
+    transport: &TransportOptions,
+) -> Result<Scorecard, String> {
+    write_corpus(work_dir, &environment.micro_bpf_root_dir)?;
+
+    let mut results = vec![];
+    for program in PROGRAMS {
+        if !categories.is_empty() && !categories.contains(&program.category) {
+            continue;
+        }
+        debug!("Running the conformance program: {}", program.name);
+        let failure = run_program(
+            program,
+            environment,
+            work_dir,
+            target,
+            binary_layout,
+            jit,
+            transport,
+        )
+        .await
+        .err();
+        results.push(ConformanceResult { program, failure });
+    }
+
+    Ok(Scorecard { results })
+}
+
+async fn run_program(
+    program: &ConformanceProgram,
+    environment: &Environment,
+    work_dir: &str,
+    target: TargetVM,
+    binary_layout: BinaryFileLayout,
+    jit: bool,
+    transport: &TransportOptions,
+) -> Result<(), String> {
+    let Some(actual) = run_source(
+        &format!("{}/{}", work_dir, program.file_name()),
+        program.execution_model(),
+        environment,
+        work_dir,
+        target,
+        binary_layout,
+        jit,
+        transport,
+    )
+    .await?
+    else {
+        return Ok(());
+    };
+
+    if actual != program.expected_result() {
+        return Err(format!(
+            "expected {}, got {}",
+            program.expected_result(),
+            actual
+        ));
+    }
+    Ok(())
+}
+
+/// Deploys the program written into the working directory with access to all
+/// helpers, executes it and returns its result (the response itself for the
+/// programs with access to the CoAP packet). Dry runs return None.
+pub async fn run_source(
+    source_file: &str,
+    execution_model: ExecutionModel,
+    environment: &Environment,
+    work_dir: &str,
+    target: TargetVM,
+    binary_layout: BinaryFileLayout,
+    jit: bool,
+    transport: &TransportOptions,
+) -> Result<Option<String>, String> {
+    let helpers = all::<HelperFunctionID>()
+        .map(|h| h as u8)
+        .collect::<Vec<u8>>();
+
+    let (slot, _) = deploy(
+        source_file,
+        &ArtifactStore {
+            object_dir: format!("{}/out", work_dir),
+            ..ArtifactStore::from_env(environment)
+        },
+        target,
+        binary_layout,
+        SuitSlot::Fixed(0),
+        StorageBackend::Ram,
+        &environment.riot_instance_net_if,
+        &environment.riot_instance_ip,
+        &environment.host_net_if,
+        &environment.host_ip,
+        &environment.board_name,
+        Some(&environment.micro_bpf_root_dir),
+        helpers.clone(),
+        HelperAccessVerification::AheadOfTime,
+        HelperAccessListSource::ExecuteRequest,
+        &DeployOptions {
+            erase: true,
+            ..Default::default()
+        },
+        &LocalPublisher::new(&environment.coap_root_dir),
+        transport,
+    )
+    .await?;
+
+    if environment.board_name != "native" {
+        thread::sleep(Duration::from_secs(FIRMWARE_PULL_TIMEOUT));
+    }
+
+    let response = execute(
+        &environment.riot_instance_ip,
+        target,
+        binary_layout,
+        slot,
+        &environment.host_net_if,
+        execution_model,
+        HelperAccessVerification::AheadOfTime,
+        HelperAccessListSource::ExecuteRequest,
+        &helpers,
+        jit,
+        &ExecuteOptions {
+            jit_compile: jit,
+            ..Default::default()
+        },
+        transport,
+    )
+    .await?;
+
+    if transport.dry_run {
+        return Ok(None);
+    }
+
+    let actual = match execution_model {
+        ExecutionModel::ShortLived => {
+            // Short lived executions always return responses of this form:
+            // {"execution_time": 10, "result": 0}
+            #[derive(Deserialize)]
+            struct Response {
+                result: i32,
+            }
+            serde_json::from_str::<Response>(&response)
+                .map_err(|e| format!("Failed to parse the json response: {}", e))?
+                .result
+                .to_string()
+        }
+        // We need to remove the null terminator that we get in the response
+        _ => response.trim_matches(char::from(0)).to_string(),
+    };
+    Ok(Some(actual))
+}
@@ -0,0 +1,124 @@
+use std::fmt::Write;
+
+use micro_bpf_common::{ContextFieldType, ContextLayout, ExecutionModel, TargetArch};
+
+/// Generates the C header with the definition of the context struct that the
+/// programs executed using the given model receive from the firmware built
+/// for the target architecture.
+///
+/// The eBPF programs are compiled for a 64-bit target, so instead of relying
+/// on the compiler to lay out the struct, all fields are given explicit
+/// fixed-width types and the padding inserted by the firmware compiler is
+/// spelled out. The offsets are then checked at compile time so that a
+/// mismatch between the header and the firmware fails the build of the
+/// program instead of making it read garbage.
+pub fn generate_context_header(
+    execution_model: ExecutionModel,
+    arch: &TargetArch,
+) -> Result<String, String> {
+    let Some(layout) = execution_model.context_layout() else {
+        return Err(format!(
+            "The programs using the {:?} execution model don't receive any context",
+            execution_model
+        ));
+    };
+
+    let guard = format!("{}_H", layout.name.to_uppercase());
+    let byte_order = if arch.big_endian {
+        "__ORDER_BIG_ENDIAN__"
+    } else {
+        "__ORDER_LITTLE_ENDIAN__"
+    };
+
+    let mut header = String::new();
+    let _ = writeln!(
+        header,
+        "/* Generated by `gen-context` for the {} target, do not edit. */\n",
+        arch.name
+    );
+    let _ = writeln!(header, "#ifndef {}\n#define {}\n", guard, guard);
+    let _ = writeln!(header, "#include <stddef.h>\n#include <stdint.h>\n");
+    let _ = writeln!(header, "#if __BYTE_ORDER__ != {}", byte_order);
+    let _ = writeln!(
+        header,
+        "#error \"{} is {}, the program needs to be compiled for the same byte order\"",
+        arch.name,
+        if arch.big_endian {
+            "big-endian"
+        } else {
+            "little-endian"
+        }
+    );
+    let _ = writeln!(header, "#endif\n");
+    header.push_str(&struct_definition(&layout, arch)?);
+    let _ = writeln!(header, "\n#endif /* {} */", guard);
+    Ok(header)
+}
+
+fn struct_definition(layout: &ContextLayout, arch: &TargetArch) -> Result<String, String> {
+    let offsets = layout.offsets(arch);
+    let mut definition = String::new();
+    let mut asserts = String::new();
+    let mut end = 0;
+
+    let _ = writeln!(definition, "typedef struct __attribute__((packed)) {{");
+    for (field, offset) in layout.fields.iter().zip(&offsets) {
+        if *offset > end {
+            let _ = writeln!(definition, "  uint8_t _pad{}[{}];", end, offset - end);
+        }
+        let _ = writeln!(definition, "  /* {} */", field.description);
+        let declaration = match field.field_type {
+            ContextFieldType::SharedPtr(pointee) => {
+                // The firmware only writes the low word of the pointer, on
+                // big-endian targets it would end up in the high half of
+                // what the program reads.
+                if arch.big_endian && arch.word_size < 8 {
+                    return Err(format!(
+                        "The pointer field {} of {} can't be shared with the programs \
+                         on the big-endian {} target",
+                        field.name, layout.name, arch.name
+                    ));
+                }
+                format!(
+                    "union {{\n    {}{};\n    uint64_t : 64;\n  }}",
+                    pointee, field.name
+                )
+            }
+            field_type => format!("{} {}", c_type(field_type, arch), field.name),
+        };
+        let _ = writeln!(definition, "  {};", declaration);
+        let _ = writeln!(
+            asserts,
+            "_Static_assert(offsetof({}, {}) == {}, \"{}.{} is at the wrong offset\");",
+            layout.name, field.name, offset, layout.name, field.name
+        );
+        end = offset + field.field_type.size_align(arch).0;
+    }
+    let size = layout.size(arch);
+    if size > end {
+        let _ = writeln!(definition, "  uint8_t _pad{}[{}];", end, size - end);
+    }
+    let _ = writeln!(definition, "}} {};\n", layout.name);
+
+    definition.push_str(&asserts);
+    let _ = writeln!(
+        definition,
+        "_Static_assert(sizeof({}) == {}, \"{} has the wrong size\");",
+        layout.name, size, layout.name
+    );
+    Ok(definition)
+}
+
+fn c_type(field_type: ContextFieldType, arch: &TargetArch) -> &'static str {
+    match field_type {
+        ContextFieldType::U8 => "uint8_t",
+        ContextFieldType::U16 => "uint16_t",
+        ContextFieldType::U32 => "uint32_t",
+        ContextFieldType::U64 => "uint64_t",
+        // The program sees a 64-bit size_t, so the width of the firmware one
+        // has to be spelled out.
+        ContextFieldType::Size if arch.word_size == 8 => "uint64_t",
+        ContextFieldType::Size => "uint32_t",
+        ContextFieldType::SharedPtr(_) => "uint64_t",
+    }
+}
@@ -0,0 +1,370 @@
+use std::fmt;
+
+use enum_iterator::all;
+use log::{debug, warn};
+use micro_bpf_common::{BinaryFileLayout, ExecutionModel, HelperFunctionID, TargetVM};
+
+use crate::{
+    conformance::{run_source, write_sources},
+    device::query_device_info,
+    environment::Environment,
+    helpers::query_helpers,
+    transport::TransportOptions,
+};
+
+/// Invariant that the value returned by a contract program has to satisfy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Invariant {
+    Equals(i64),
+    NotEquals(i64),
+    /// Inclusive range of the allowed values.
+    Range(i64, i64),
+}
+
+impl Invariant {
+    pub fn holds(&self, value: i64) -> bool {
+        match self {
+            Invariant::Equals(expected) => value == *expected,
+            Invariant::NotEquals(unexpected) => value != *unexpected,
+            Invariant::Range(min, max) => (*min..=*max).contains(&value),
+        }
+    }
+}
+
+impl fmt::Display for Invariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Invariant::Equals(expected) => write!(f, "== {}", expected),
+            Invariant::NotEquals(unexpected) => write!(f, "!= {}", unexpected),
+            Invariant::Range(min, max) => write!(f, "in [{}, {}]", min, max),
+        }
+    }
+}
+
+/// Property of a helper checked by executing a tiny program calling it on
+/// the device. The side effects of the helper (e.g. the value written by
+/// `bpf_store_global`) are checked by the program itself, which reduces them
+/// into the returned value.
+pub struct HelperContract {
+    pub helper: HelperFunctionID,
+    pub name: &'static str,
+    /// Body of the contract program, it has access to the helpers declared
+    /// in `helpers.h` and returns the value checked by the invariant.
+    pub body: &'static str,
+    pub invariant: Invariant,
+}
+
+impl HelperContract {
+    pub fn file_name(&self) -> String {
+        format!("contract_{}.c", self.name)
+    }
+
+    /// Generates the source of the contract program.
+    pub fn source(&self) -> String {
+        format!(
+            "#include \"helpers.h\"\n\nint contract(void *ctx)\n{{\n{}\n}}\n",
+            self.body
+        )
+    }
+}
+
+/// Contracts of the helpers which can be exercised without any hardware
+/// attached to the device. The helpers driving the peripherals (GPIO, the
+/// display, the keypad) and the ones requiring access to the CoAP packet are
+/// left out, see [`untested_helpers`].
+pub const CONTRACTS: &[HelperContract] = &[
+    HelperContract {
+        helper: HelperFunctionID::BPF_PRINTF_IDX,
+        name: "printf_preserves_locals",
+        body: "    uint32_t value = 7;\n    print(\"contract %d\\n\", value);\n    return value;",
+        invariant: Invariant::Equals(7),
+    },
+    HelperContract {
+        helper: HelperFunctionID::BPF_DEBUG_PRINT_IDX,
+        name: "debug_print_returns",
+        body: "    bpf_print_debug(42);\n    return 1;",
+        invariant: Invariant::Equals(1),
+    },
+    HelperContract {
+        helper: HelperFunctionID::BPF_MEMCPY_IDX,
+        name: "memcpy_copies_all_bytes",
+        body: "    char src[] = \"contract\";\n    char dst[9] = {0};\n    \
+               bpf_memcpy(dst, src, sizeof(src));\n    int copied = 0;\n    \
+               for (int i = 0; i < sizeof(src); i++) {\n        \
+               copied += dst[i] == src[i];\n    }\n    return copied;",
+        invariant: Invariant::Equals(9),
+    },
+    HelperContract {
+        helper: HelperFunctionID::BPF_STORE_LOCAL_IDX,
+        name: "store_local_succeeds",
+        body: "    return bpf_store_local(7, 1234);",
+        invariant: Invariant::Equals(0),
+    },
+    HelperContract {
+        helper: HelperFunctionID::BPF_FETCH_LOCAL_IDX,
+        name: "fetch_local_reads_stored_value",
+        body: "    uint32_t value = 0;\n    bpf_store_local(7, 1234);\n    \
+               bpf_fetch_local(7, &value);\n    return value;",
+        invariant: Invariant::Equals(1234),
+    },
+    HelperContract {
+        helper: HelperFunctionID::BPF_STORE_GLOBAL_IDX,
+        name: "store_global_succeeds",
+        body: "    return bpf_store_global(7, 4321);",
+        invariant: Invariant::Equals(0),
+    },
+    HelperContract {
+        helper: HelperFunctionID::BPF_FETCH_GLOBAL_IDX,
+        name: "fetch_global_reads_stored_value",
+        body: "    uint32_t value = 0;\n    bpf_store_global(7, 4321);\n    \
+               bpf_fetch_global(7, &value);\n    return value;",
+        invariant: Invariant::Equals(4321),
+    },
+    HelperContract {
+        helper: HelperFunctionID::BPF_SAUL_REG_FIND_NTH_IDX,
+        name: "saul_find_nth_out_of_range_is_null",
+        body: "    return bpf_saul_reg_find_nth(1000) == 0;",
+        invariant: Invariant::Equals(1),
+    },
+    HelperContract {
+        helper: HelperFunctionID::BPF_SAUL_REG_FIND_TYPE_IDX,
+        name: "saul_find_unknown_type_is_null",
+        body: "    return bpf_saul_reg_find_type(0xff) == 0;",
+        invariant: Invariant::Equals(1),
+    },
+    HelperContract {
+        helper: HelperFunctionID::BPF_STRLEN_IDX,
+        name: "strlen_counts_characters",
+        body: "    char str[] = \"contract\";\n    return bpf_strlen(str);",
+        invariant: Invariant::Equals(8),
+    },
+    HelperContract {
+        helper: HelperFunctionID::BPF_STRLEN_IDX,
+        name: "strlen_of_empty_string",
+        body: "    char str[] = \"\";\n    return bpf_strlen(str);",
+        invariant: Invariant::Equals(0),
+    },
+    HelperContract {
+        helper: HelperFunctionID::BPF_FMT_S16_DFP_IDX,
+        name: "fmt_s16_dfp_length",
+        body: "    char out[8];\n    return bpf_fmt_s16_dfp(out, -12345, 2);",
+        invariant: Invariant::Equals(7),
+    },
+    HelperContract {
+        helper: HelperFunctionID::BPF_FMT_U32_DEC_IDX,
+        name: "fmt_u32_dec_max_length",
+        body: "    char out[11];\n    return bpf_fmt_u32_dec(out, 4294967295);",
+        invariant: Invariant::Equals(10),
+    },
+    HelperContract {
+        helper: HelperFunctionID::BPF_FMT_U32_DEC_IDX,
+        name: "fmt_u32_dec_writes_digits",
+        body: "    char out[11];\n    size_t len = bpf_fmt_u32_dec(out, 907);\n    \
+               return len == 3 && out[0] == '9' && out[1] == '0' && out[2] == '7';",
+        invariant: Invariant::Equals(1),
+    },
+    HelperContract {
+        helper: HelperFunctionID::BPF_NOW_MS_IDX,
+        name: "now_ms_is_monotonic",
+        body: "    uint32_t first = bpf_now_ms();\n    uint32_t second = bpf_now_ms();\n    \
+               return second >= first;",
+        invariant: Invariant::Equals(1),
+    },
+    HelperContract {
+        helper: HelperFunctionID::BPF_ZTIMER_NOW_IDX,
+        name: "ztimer_now_is_monotonic",
+        body: "    uint32_t first = bpf_ztimer_now();\n    \
+               uint32_t second = bpf_ztimer_now();\n    return second >= first;",
+        invariant: Invariant::Equals(1),
+    },
+    HelperContract {
+        helper: HelperFunctionID::BPF_PERIODIC_WAKEUP_IDX,
+        name: "periodic_wakeup_advances_last_wakeup",
+        body: "    uint32_t start = bpf_ztimer_now();\n    uint32_t last = start;\n    \
+               bpf_ztimer_periodic_wakeup(&last, 5);\n    return last - start;",
+        invariant: Invariant::Equals(5),
+    },
+    HelperContract {
+        helper: HelperFunctionID::BPF_SET_RETURN_BUFFER_IDX,
+        name: "set_return_buffer_accepts_small_buffer",
+        body: "    char data[4] = {1, 2, 3, 4};\n    return bpf_set_return_buffer(data, 4);",
+        invariant: Invariant::Equals(0),
+    },
+    HelperContract {
+        helper: HelperFunctionID::BPF_SET_RETURN_BUFFER_IDX,
+        name: "set_return_buffer_rejects_oversized_buffer",
+        body: "    char data[300] = {0};\n    return bpf_set_return_buffer(data, 300);",
+        invariant: Invariant::NotEquals(0),
+    },
+];
+
+/// Helpers of the catalogue which none of the contracts exercise.
+pub fn untested_helpers() -> Vec<HelperFunctionID> {
+    all::<HelperFunctionID>()
+        .filter(|helper| !CONTRACTS.iter().any(|c| c.helper == *helper))
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContractOutcome {
+    /// The returned value satisfied the invariant.
+    Held(i64),
+    Violated(String),
+    /// The contract wasn't checked, e.g. because the firmware was built
+    /// without the helper.
+    Skipped(String),
+}
+
+pub struct ContractResult {
+    pub contract: &'static HelperContract,
+    pub outcome: ContractOutcome,
+}
+
+/// Conformance of the helpers of a firmware with their contracts.
+pub struct ContractReport {
+    /// Version of the firmware as reported by the device, None if the device
+    /// didn't provide it.
+    pub firmware: Option<String>,
+    pub results: Vec<ContractResult>,
+}
+
+impl ContractReport {
+    pub fn violated(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|r| matches!(r.outcome, ContractOutcome::Violated(_)))
+            .count()
+    }
+}
+
+impl fmt::Display for ContractReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Helper contracts of firmware {}",
+            self.firmware.as_deref().unwrap_or("unknown")
+        )?;
+        let mut helpers = self
+            .results
+            .iter()
+            .map(|r| r.contract.helper)
+            .collect::<Vec<HelperFunctionID>>();
+        helpers.dedup();
+
+        for helper in helpers {
+            writeln!(f, "{:?} ({:#04x})", helper, helper as u8)?;
+            for result in self.results.iter().filter(|r| r.contract.helper == helper) {
+                let name = result.contract.name;
+                match &result.outcome {
+                    ContractOutcome::Held(value) => writeln!(f, "  HOLD {} ({})", name, value)?,
+                    ContractOutcome::Violated(e) => writeln!(f, "  VIOLATED {}: {}", name, e)?,
+                    ContractOutcome::Skipped(reason) => writeln!(f, "  SKIP {}: {}", name, reason)?,
+                }
+            }
+        }
+
+        let untested = untested_helpers()
+            .iter()
+            .map(|helper| format!("{:?}", helper))
+            .collect::<Vec<String>>();
+        if !untested.is_empty() {
+            writeln!(f, "Helpers without contracts: {}", untested.join(", "))?;
+        }
+        let held = self
+            .results
+            .iter()
+            .filter(|r| matches!(r.outcome, ContractOutcome::Held(_)))
+            .count();
+        write!(
+            f,
+            "Total: {} held, {} violated, {} skipped",
+            held,
+            self.violated(),
+            self.results.len() - held - self.violated()
+        )
+    }
+}
+
+/// Runs the contracts of the selected helpers (their IDs, all of them if none
+/// are specified) against the device described by the environment. The contracts
+/// of the helpers that the firmware doesn't include are skipped.
+pub async fn run_contracts(
+    environment: &Environment,
+    work_dir: &str,
+    target: TargetVM,
+    binary_layout: BinaryFileLayout,
+    helpers: &[u8],
+    jit: bool,
+    transport: &TransportOptions,
+) -> Result<ContractReport, String> {
+    let contracts = CONTRACTS
+        .iter()
+        .filter(|c| helpers.is_empty() || helpers.contains(&(c.helper as u8)))
+        .collect::<Vec<&'static HelperContract>>();
+    write_sources(
+        work_dir,
+        &environment.micro_bpf_root_dir,
+        contracts.iter().map(|c| (c.file_name(), c.source())),
+    )?;
+
+    let (ip, net_if) = (&environment.riot_instance_ip, &environment.host_net_if);
+    // Dry runs don't get any responses, all helpers are assumed available.
+    let available = if transport.dry_run {
+        None
+    } else {
+        Some(query_helpers(ip, net_if, transport)?)
+    };
+    let firmware = match query_device_info(ip, net_if, transport) {
+        Ok(_) if transport.dry_run => None,
+        Ok(info) => Some(format!("{} (RIOT {})", info.firmware, info.riot)),
+        Err(e) => {
+            warn!("Failed to query the firmware version: {}", e);
+            None
+        }
+    };
+
+    let mut results = vec![];
+    for contract in contracts {
+        if available
+            .as_ref()
+            .is_some_and(|available| !available.contains(&(contract.helper as u8)))
+        {
+            results.push(ContractResult {
+                contract,
+                outcome: ContractOutcome::Skipped("not in the firmware".to_string()),
+            });
+            continue;
+        }
+        debug!("Checking the helper contract: {}", contract.name);
+        let outcome = match run_source(
+            &format!("{}/{}", work_dir, contract.file_name()),
+            ExecutionModel::ShortLived,
+            environment,
+            work_dir,
+            target,
+            binary_layout,
+            jit,
+            transport,
+        )
+        .await
+        {
+            Ok(None) => ContractOutcome::Skipped("dry run".to_string()),
+            Ok(Some(result)) => check(contract, &result),
+            Err(e) => ContractOutcome::Violated(e),
+        };
+        results.push(ContractResult { contract, outcome });
+    }
+
+    Ok(ContractReport { firmware, results })
+}
+
+fn check(contract: &HelperContract, result: &str) -> ContractOutcome {
+    match result.parse::<i64>() {
+        Ok(value) if contract.invariant.holds(value) => ContractOutcome::Held(value),
+        Ok(value) => ContractOutcome::Violated(format!(
+            "returned {}, expected {}",
+            value, contract.invariant
+        )),
+        Err(e) => ContractOutcome::Violated(format!("invalid result {}: {}", result, e)),
+    }
+}
@@ -0,0 +1,557 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+};
+
+use micro_bpf_common::{BinaryFileLayout, HelperFunctionID};
+use micro_bpf_elf_utils::extract_text_section;
+
+use crate::{
+    bytecode::{decode, Instruction, CALL_OPCODE, EXIT_OPCODE, LDDWD_OPCODE, LDDWR_OPCODE},
+    postprocessing::read_bytes_from_file,
+};
+
+const CLASS_LDX: u8 = 0x01;
+const CLASS_ST: u8 = 0x02;
+const CLASS_STX: u8 = 0x03;
+const CLASS_ALU: u8 = 0x04;
+const CLASS_JMP: u8 = 0x05;
+const CLASS_JMP32: u8 = 0x06;
+const CLASS_ALU64: u8 = 0x07;
+
+/// Mode of the atomic add instructions (`lock *(u64 *)(r1 + 0) += r2`).
+const MODE_ATOMIC: u8 = 0xc0;
+
+/// A single line of the pseudocode.
+#[derive(Debug, Clone)]
+struct Line {
+    /// Position of the instruction that the line was produced from, used for
+    /// placing the labels of the jump targets. The lines closing a block
+    /// don't have one.
+    position: Option<usize>,
+    depth: usize,
+    text: String,
+}
+
+/// A function of the program, the first one is the entry point and the
+/// others are the functions called using bpf-to-bpf calls.
+#[derive(Debug, Clone)]
+pub struct DecompiledFunction {
+    pub name: String,
+    lines: Vec<Line>,
+    /// Positions of the instructions that are targets of a `goto`.
+    labels: HashSet<usize>,
+    /// Instruction slot of each position, the labels are named after them.
+    slots: Vec<usize>,
+}
+
+/// C-like pseudocode of a program, see [`decompile`].
+#[derive(Debug, Clone)]
+pub struct Pseudocode {
+    pub functions: Vec<DecompiledFunction>,
+}
+
+/// Lifts the program contained in the binary (e.g. a slot backup) into C-like
+/// pseudocode.
+pub fn decompile_artifact(binary: &str, layout: BinaryFileLayout) -> Result<Pseudocode, String> {
+    let bytes = read_bytes_from_file(binary);
+    let text = extract_text_section(&bytes, layout)?;
+    decompile(text)
+}
+
+/// Lifts the eBPF bytecode into C-like pseudocode. The registers are kept as
+/// variables (r0 - r10), the helper calls are shown using the names of the
+/// helpers and the control flow is recovered into `if`/`else` blocks and
+/// `do`/`while` loops where the jumps form such structures, the remaining
+/// jumps are shown as `goto`s.
+pub fn decompile(text: &[u8]) -> Result<Pseudocode, String> {
+    let instructions = decode(text)?;
+    let positions = instructions
+        .iter()
+        .enumerate()
+        .map(|(position, instruction)| (instruction.index, position))
+        .collect::<HashMap<usize, usize>>();
+
+    // The bpf-to-bpf calls specify the target relative to the next
+    // instruction, same as the jumps.
+    let mut function_starts = BTreeSet::from([0]);
+    for instruction in &instructions {
+        if instruction.opcode == CALL_OPCODE && instruction.src != 0 {
+            let target = instruction.index as i64 + 1 + instruction.immediate;
+            if let Some(position) = positions.get(&(target as usize)) {
+                function_starts.insert(*position);
+            }
+        }
+    }
+
+    let starts = function_starts.into_iter().collect::<Vec<usize>>();
+    let functions = starts
+        .iter()
+        .enumerate()
+        .map(|(i, start)| {
+            let end = starts.get(i + 1).copied().unwrap_or(instructions.len());
+            let name = if i == 0 {
+                "prog".to_string()
+            } else {
+                function_name(instructions[*start].index)
+            };
+            Decompiler::new(
+                &instructions[*start..end],
+                &instructions,
+                *start,
+                &positions,
+            )
+            .run(name)
+        })
+        .collect();
+
+    Ok(Pseudocode { functions })
+}
+
+fn function_name(slot: usize) -> String {
+    format!("func_{}", slot)
+}
+
+struct Decompiler<'a> {
+    /// Instructions of the decompiled function.
+    instructions: &'a [Instruction],
+    /// All instructions of the program, used for resolving the targets.
+    program: &'a [Instruction],
+    /// Position of the first instruction of the function in the program.
+    offset: usize,
+    positions: &'a HashMap<usize, usize>,
+    lines: Vec<Line>,
+    labels: HashSet<usize>,
+}
+
+impl<'a> Decompiler<'a> {
+    fn new(
+        instructions: &'a [Instruction],
+        program: &'a [Instruction],
+        offset: usize,
+        positions: &'a HashMap<usize, usize>,
+    ) -> Self {
+        Decompiler {
+            instructions,
+            program,
+            offset,
+            positions,
+            lines: vec![],
+            labels: HashSet::new(),
+        }
+    }
+
+    fn run(mut self, name: String) -> DecompiledFunction {
+        self.structure(0, self.instructions.len(), 1);
+        DecompiledFunction {
+            name,
+            lines: self.lines,
+            labels: self.labels,
+            slots: self.instructions.iter().map(|i| i.index).collect(),
+        }
+    }
+
+    /// Position (relative to the function) of the target of the jump at the
+    /// given position.
+    fn jump_target(&self, position: usize) -> Option<usize> {
+        let instruction = &self.instructions[position];
+        let target = instruction.index as i64 + 1 + instruction.offset as i64;
+        self.positions
+            .get(&(target as usize))
+            .and_then(|p| p.checked_sub(self.offset))
+            .filter(|p| *p <= self.instructions.len())
+    }
+
+    fn is_jump(&self, position: usize) -> bool {
+        let opcode = self.instructions[position].opcode;
+        matches!(opcode & 0x07, CLASS_JMP | CLASS_JMP32)
+            && opcode != CALL_OPCODE
+            && opcode != EXIT_OPCODE
+    }
+
+    fn is_conditional_jump(&self, position: usize) -> bool {
+        self.is_jump(position) && self.instructions[position].opcode & 0xf0 != 0x00
+    }
+
+    /// Checks that no jump from outside of the region lands strictly inside
+    /// of it, i.e. that it can only be entered at its first instruction.
+    fn is_self_contained(&self, start: usize, end: usize) -> bool {
+        (0..self.instructions.len())
+            .filter(|p| (*p < start || *p >= end) && self.is_jump(*p))
+            .filter_map(|p| self.jump_target(p))
+            .all(|target| target <= start || target >= end)
+    }
+
+    fn is_jump_target(&self, position: usize) -> bool {
+        (0..self.instructions.len())
+            .filter(|p| self.is_jump(*p))
+            .any(|p| self.jump_target(p) == Some(position))
+    }
+
+    /// Finds the last jump in the region which jumps back to its start.
+    fn back_edge(&self, start: usize, end: usize) -> Option<usize> {
+        (start..end)
+            .rev()
+            .find(|p| self.is_jump(*p) && self.jump_target(*p) == Some(start))
+    }
+
+    fn emit(&mut self, position: Option<usize>, depth: usize, text: String) {
+        self.lines.push(Line {
+            position,
+            depth,
+            text,
+        });
+    }
+
+    fn goto(&mut self, target: Option<usize>) -> String {
+        match target {
+            Some(target) if target < self.instructions.len() => {
+                self.labels.insert(target);
+                format!("goto L{};", self.instructions[target].index)
+            }
+            // Jumping past the last instruction can only happen in malformed
+            // programs.
+            _ => "goto <invalid>;".to_string(),
+        }
+    }
+
+    fn structure(&mut self, start: usize, end: usize, depth: usize) {
+        let mut position = start;
+        while position < end {
+            if let Some(source) = self.back_edge(position, end) {
+                if self.is_self_contained(position, source + 1) {
+                    if self.is_conditional_jump(source) {
+                        let condition = Condition::new(&self.instructions[source]);
+                        self.emit(Some(position), depth, "do {".to_string());
+                        self.structure(position, source, depth + 1);
+                        self.emit(None, depth, format!("}} while ({});", condition));
+                    } else {
+                        self.emit(Some(position), depth, "while (true) {".to_string());
+                        self.structure(position, source, depth + 1);
+                        self.emit(None, depth, "}".to_string());
+                    }
+                    position = source + 1;
+                    continue;
+                }
+            }
+
+            let target = self.jump_target(position);
+            if self.is_conditional_jump(position) {
+                if let Some(target) = target.filter(|t| *t > position && *t <= end) {
+                    if self.is_self_contained(position + 1, target) {
+                        position = self.structure_if(position, target, end, depth);
+                        continue;
+                    }
+                }
+            }
+
+            let text = if self.is_jump(position) {
+                match self.is_conditional_jump(position) {
+                    true => format!(
+                        "if ({}) {}",
+                        Condition::new(&self.instructions[position]),
+                        self.goto(target)
+                    ),
+                    false => self.goto(target),
+                }
+            } else {
+                self.statement(position)
+            };
+            self.emit(Some(position), depth, text);
+            position += 1;
+        }
+    }
+
+    /// Emits the `if` (and possibly `else`) block of the conditional jump at
+    /// `position` which skips the instructions up to `target`. Returns the
+    /// position following the emitted blocks.
+    fn structure_if(&mut self, position: usize, target: usize, end: usize, depth: usize) -> usize {
+        let condition = Condition::new(&self.instructions[position]).negate();
+        self.emit(Some(position), depth, format!("if ({}) {{", condition));
+
+        // The then-block ending with an unconditional jump over the following
+        // instructions means that they form the else-block.
+        let last = target - 1;
+        let else_end = match self.jump_target(last) {
+            Some(else_end)
+                if last > position
+                    && self.is_jump(last)
+                    && !self.is_conditional_jump(last)
+                    && else_end > target
+                    && else_end <= end
+                    && self.is_self_contained(target, else_end) =>
+            {
+                Some(else_end)
+            }
+            _ => None,
+        };
+
+        match else_end {
+            Some(else_end) => {
+                self.structure(position + 1, last, depth + 1);
+                self.emit(Some(target), depth, "} else {".to_string());
+                self.structure(target, else_end, depth + 1);
+                self.emit(None, depth, "}".to_string());
+                else_end
+            }
+            None => {
+                self.structure(position + 1, target, depth + 1);
+                self.emit(None, depth, "}".to_string());
+                target
+            }
+        }
+    }
+
+    /// Translates a non-jump instruction into a statement.
+    fn statement(&self, position: usize) -> String {
+        let instruction = &self.instructions[position];
+        let dst = format!("r{}", instruction.dst);
+        let src = format!("r{}", instruction.src);
+        let uses_src_register = instruction.opcode & 0x08 != 0;
+        let operand = if uses_src_register {
+            src.clone()
+        } else {
+            instruction.immediate.to_string()
+        };
+
+        if instruction.is_wide() {
+            return match instruction.opcode {
+                LDDWD_OPCODE => format!("{} = &data[{:#x}];", dst, instruction.immediate),
+                LDDWR_OPCODE => format!("{} = &rodata[{:#x}];", dst, instruction.immediate),
+                _ => format!("{} = {:#x};", dst, instruction.immediate),
+            };
+        }
+
+        match instruction.opcode & 0x07 {
+            CLASS_LDX => format!(
+                "{} = *({} *)({});",
+                dst,
+                access_type(instruction.opcode),
+                address(&src, instruction.offset)
+            ),
+            CLASS_ST => format!(
+                "*({} *)({}) = {};",
+                access_type(instruction.opcode),
+                address(&dst, instruction.offset),
+                instruction.immediate
+            ),
+            CLASS_STX if instruction.opcode & 0xe0 == MODE_ATOMIC => format!(
+                "lock *({} *)({}) += {};",
+                access_type(instruction.opcode),
+                address(&dst, instruction.offset),
+                src
+            ),
+            CLASS_STX => format!(
+                "*({} *)({}) = {};",
+                access_type(instruction.opcode),
+                address(&dst, instruction.offset),
+                src
+            ),
+            class @ (CLASS_ALU | CLASS_ALU64) => {
+                let expression = match instruction.opcode & 0xf0 {
+                    0x80 => format!("-{}", dst),
+                    0xb0 => operand,
+                    0xc0 => format!("(s64){} >> {}", dst, operand),
+                    0xd0 => {
+                        let order = if uses_src_register { "be" } else { "le" };
+                        format!("{}{}({})", order, instruction.immediate, dst)
+                    }
+                    op => format!("{} {} {}", dst, alu_operator(op), operand),
+                };
+                if class == CLASS_ALU {
+                    format!("{} = (u32)({});", dst, expression)
+                } else {
+                    format!("{} = {};", dst, expression)
+                }
+            }
+            CLASS_JMP if instruction.opcode == EXIT_OPCODE => "return r0;".to_string(),
+            CLASS_JMP if instruction.is_helper_call() => {
+                let arguments = (1..=self.argument_count(position))
+                    .map(|r| format!("r{}", r))
+                    .collect::<Vec<String>>();
+                format!(
+                    "r0 = {}({});",
+                    helper_name(instruction.immediate),
+                    arguments.join(", ")
+                )
+            }
+            CLASS_JMP if instruction.opcode == CALL_OPCODE => {
+                let target = instruction.index as i64 + 1 + instruction.immediate;
+                let name = match self.positions.get(&(target as usize)) {
+                    Some(p) => function_name(self.program[*p].index),
+                    None => format!("<invalid call {}>", instruction.immediate),
+                };
+                format!("r0 = {}(r1, r2, r3, r4, r5);", name)
+            }
+            _ => format!("/* {} */", instruction),
+        }
+    }
+
+    /// The number of arguments of a helper call isn't encoded in the
+    /// bytecode, we approximate it using the highest argument register
+    /// written to since the start of the basic block (or the previous call).
+    fn argument_count(&self, call: usize) -> usize {
+        let mut count = 0;
+        for position in (0..call).rev() {
+            let instruction = &self.instructions[position];
+            if self.is_jump(position)
+                || instruction.opcode == CALL_OPCODE
+                || instruction.opcode == EXIT_OPCODE
+                || self.is_jump_target(position + 1)
+            {
+                break;
+            }
+            let writes_register = instruction.is_wide()
+                || matches!(
+                    instruction.opcode & 0x07,
+                    CLASS_LDX | CLASS_ALU | CLASS_ALU64
+                );
+            if writes_register && (1..=5).contains(&instruction.dst) {
+                count = count.max(instruction.dst as usize);
+            }
+        }
+        count
+    }
+}
+
+/// Condition of a conditional jump.
+struct Condition {
+    lhs: String,
+    rhs: String,
+    /// Operation encoded in the upper bits of the opcode.
+    operation: u8,
+    is_32bit: bool,
+    negated: bool,
+}
+
+impl Condition {
+    fn new(instruction: &Instruction) -> Self {
+        let rhs = if instruction.opcode & 0x08 != 0 {
+            format!("r{}", instruction.src)
+        } else {
+            instruction.immediate.to_string()
+        };
+        Condition {
+            lhs: format!("r{}", instruction.dst),
+            rhs,
+            operation: instruction.opcode & 0xf0,
+            is_32bit: instruction.opcode & 0x07 == CLASS_JMP32,
+            negated: false,
+        }
+    }
+
+    fn negate(mut self) -> Self {
+        self.negated = !self.negated;
+        self
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Each operator together with its negation.
+        let (operator, negation, signed) = match self.operation {
+            0x10 => ("==", "!=", false),
+            0x20 => (">", "<=", false),
+            0x30 => (">=", "<", false),
+            0x50 => ("!=", "==", false),
+            0x60 => (">", "<=", true),
+            0x70 => (">=", "<", true),
+            0xa0 => ("<", ">=", false),
+            0xb0 => ("<=", ">", false),
+            0xc0 => ("<", ">=", true),
+            0xd0 => ("<=", ">", true),
+            // jset
+            _ => {
+                let operator = if self.negated { "==" } else { "!=" };
+                return write!(f, "({} & {}) {} 0", self.lhs, self.rhs, operator);
+            }
+        };
+        let operator = if self.negated { negation } else { operator };
+        let cast = match (signed, self.is_32bit) {
+            (true, true) => "(s32)",
+            (true, false) => "(s64)",
+            (false, true) => "(u32)",
+            (false, false) => "",
+        };
+        write!(f, "{}{} {} {}{}", cast, self.lhs, operator, cast, self.rhs)
+    }
+}
+
+fn alu_operator(operation: u8) -> &'static str {
+    match operation {
+        0x00 => "+",
+        0x10 => "-",
+        0x20 => "*",
+        0x30 => "/",
+        0x40 => "|",
+        0x50 => "&",
+        0x60 => "<<",
+        0x70 => ">>",
+        0x90 => "%",
+        0xa0 => "^",
+        _ => "?",
+    }
+}
+
+fn access_type(opcode: u8) -> &'static str {
+    match opcode & 0x18 {
+        0x00 => "u32",
+        0x08 => "u16",
+        0x10 => "u8",
+        _ => "u64",
+    }
+}
+
+fn address(register: &str, offset: i16) -> String {
+    match offset {
+        0 => register.to_string(),
+        offset if offset < 0 => format!("{} - {}", register, -(offset as i32)),
+        offset => format!("{} + {}", register, offset),
+    }
+}
+
+/// Converts the helper ID into the name of the function used in the C
+/// sources, e.g. BPF_PRINTF_IDX into bpf_printf.
+fn helper_name(id: i64) -> String {
+    let helper: Option<HelperFunctionID> = num::FromPrimitive::from_i64(id);
+    match helper {
+        Some(helper) => format!("{:?}", helper)
+            .trim_end_matches("_IDX")
+            .to_lowercase(),
+        None => format!("helper_{:#x}", id),
+    }
+}
+
+impl fmt::Display for DecompiledFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parameters = if self.name == "prog" {
+            "u64 r1"
+        } else {
+            "u64 r1, u64 r2, u64 r3, u64 r4, u64 r5"
+        };
+        writeln!(f, "u64 {}({}) {{", self.name, parameters)?;
+
+        let mut labelled = HashSet::new();
+        for line in &self.lines {
+            if let Some(position) = line.position {
+                if self.labels.contains(&position) && labelled.insert(position) {
+                    writeln!(f, "L{}:", self.slots[position])?;
+                }
+            }
+            writeln!(f, "{}{}", "    ".repeat(line.depth), line.text)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+impl fmt::Display for Pseudocode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, function) in self.functions.iter().enumerate() {
+            if i > 0 {
+                writeln!(f, "\n")?;
+            }
+            write!(f, "{}", function)?;
+        }
+        Ok(())
+    }
+}
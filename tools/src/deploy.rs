@@ -1,19 +1,149 @@
-use micro_bpf_common::{HelperAccessListSource, HelperAccessVerification, TargetVM};
+use std::{
+    collections::BTreeMap,
+    fmt, fs, thread,
+    time::{Duration, Instant},
+};
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use micro_bpf_common::{
+    HelperAccessListSource, HelperAccessVerification, StorageBackend, TargetVM,
+    CAPABILITY_RODATA_COMPRESSION,
+};
+use micro_bpf_elf_utils::{compress_rodata, eliminate_dead_code};
 
 use crate::{
-    compile::compile, micro_bpf_common::BinaryFileLayout, postprocessing::apply_postprocessing,
-    pull::pull, sign::sign,
+    artifacts::ArtifactStore,
+    capabilities::query_capabilities,
+    compile::compile,
+    compression::{negotiate_compression, ArtifactCompression},
+    interface::resolve_interface,
+    layout::validate,
+    lint::{lint, report, LintOptions},
+    manifest::{generate_manifest, ManifestComponent},
+    micro_bpf_common::BinaryFileLayout,
+    postprocessing::{apply_postprocessing, entry_offsets, program_metadata, read_bytes_from_file},
+    provenance::Provenance,
+    publish::{fetch_location, Publisher},
+    pull::pull,
+    registry::Registry,
+    sign::sign,
+    slots::{allocate_slot, verify_installed_artifact, SuitSlot},
+    transport::{is_cancelled, send_request, TransportOptions},
 };
 
-const TEMP_FILE: &str = "program.bin";
+const BUNDLE_MANIFEST: &str = "suit_manifest_bundle";
+
+/// All binary layouts, used when deploying with `--layouts all`.
+pub const ALL_LAYOUTS: [BinaryFileLayout; 4] = [
+    BinaryFileLayout::OnlyTextSection,
+    BinaryFileLayout::FemtoContainersHeader,
+    BinaryFileLayout::ExtendedHeader,
+    BinaryFileLayout::RawObjectFile,
+];
+
+/// Time spent in each phase of a deployment. The host-side phases are measured
+/// by the tool, the device-side ones are reported by the device.
+#[derive(Debug, Clone, Default)]
+pub struct DeployTimings {
+    pub compile: Duration,
+    /// Postprocessing of the object file into the binary layout.
+    pub layout_transform: Duration,
+    /// Generating and signing the SUIT manifest.
+    pub sign: Duration,
+    /// Making the artifacts available on the file server, see [`Publisher`].
+    pub publish: Duration,
+    /// Round trip of the pull request notifying the device about the update.
+    pub notify: Duration,
+    /// Time it took the device to fetch the manifest and the payload, None
+    /// if the firmware doesn't report it.
+    pub device_fetch: Option<Duration>,
+    /// Time it took the device to verify the signature and install the payload.
+    pub device_verify: Option<Duration>,
+}
+
+impl DeployTimings {
+    pub fn total(&self) -> Duration {
+        self.compile
+            + self.layout_transform
+            + self.sign
+            + self.publish
+            + self.notify
+            + self.device_fetch.unwrap_or_default()
+            + self.device_verify.unwrap_or_default()
+    }
+}
+
+impl fmt::Display for DeployTimings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total().as_secs_f64().max(f64::EPSILON);
+        let phases = [
+            ("compile", Some(self.compile)),
+            ("layout transform", Some(self.layout_transform)),
+            ("sign", Some(self.sign)),
+            ("publish", Some(self.publish)),
+            ("notify", Some(self.notify)),
+            ("device fetch", self.device_fetch),
+            ("device verify", self.device_verify),
+        ];
+        for (phase, duration) in phases {
+            match duration {
+                Some(d) => writeln!(
+                    f,
+                    "{:>16}: {:>8.1}ms ({:>4.1}%)",
+                    phase,
+                    d.as_secs_f64() * 1000.0,
+                    d.as_secs_f64() / total * 100.0
+                )?,
+                None => writeln!(f, "{:>16}: {:>8}", phase, "n/a")?,
+            }
+        }
+        write!(f, "{:>16}: {:>8.1}ms", "total", total * 1000.0)
+    }
+}
+
+/// Optional settings of a deployment, the defaults deploy the program as it
+/// is compiled.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeployOptions<'a> {
+    /// Erases the SUIT storage slot before the program is loaded into it.
+    pub erase: bool,
+    /// Sequence number of the manifest, the next one recorded in the
+    /// registry for the device is used if None.
+    pub sequence_number: Option<u64>,
+    /// Allows for using a sequence number lower than the one recorded in the
+    /// registry, see [`Registry::next_sequence_number`].
+    pub force_seq: bool,
+    /// Aborts the deployment if the lints report any warnings.
+    pub deny_lint_warnings: bool,
+    /// Compresses the .rodata section of the binary if the device supports
+    /// it, see [`compress_rodata`].
+    pub compress: bool,
+    /// Removes the functions unreachable from the entry point, see
+    /// [`eliminate_dead_code`].
+    pub strip_dead_code: bool,
+    /// Embeds the program metadata into the binary, see
+    /// [`program_metadata`].
+    pub embed_metadata: bool,
+    /// Entry functions exported by the program, the first one is the one
+    /// that the program starts executing from by default.
+    pub entries: &'a [&'a str],
+    /// Compression of the artifact fetched by the device, see
+    /// [`negotiate_compression`].
+    pub artifact_compression: Option<ArtifactCompression>,
+}
 
+/// Compiles, signs and initiates the firmware pull of the program in one step.
+/// Returns the SUIT storage slot that the program was deployed to, this is
+/// useful when the slot is chosen automatically (see [`SuitSlot::Auto`]),
+/// together with the time spent in each phase of the deployment.
 pub async fn deploy(
     bpf_source_file: &str,
-    out_dir: &str,
+    artifacts: &ArtifactStore,
     target: TargetVM,
     binary_layout: BinaryFileLayout,
-    coap_root: &str,
-    suit_storage_slot: usize,
+    suit_storage_slot: SuitSlot,
+    storage: StorageBackend,
     riot_net_if: &str,
     riot_ip: &str,
     host_net_if: &str,
@@ -23,51 +153,915 @@ pub async fn deploy(
     helper_indices: Vec<u8>,
     helper_access_verification: HelperAccessVerification,
     helper_access_list_source: HelperAccessListSource,
-    erase: bool,
-) -> Result<(), String> {
-    let object_file_name = get_object_file_name(bpf_source_file, out_dir)?;
-    let suit_manifest = &format!("suit_manifest{}.signed", suit_storage_slot);
+    options: &DeployOptions,
+    publisher: &dyn Publisher,
+    transport: &TransportOptions,
+) -> Result<(usize, DeployTimings), String> {
+    let entries = options.entries;
+    if !entries.is_empty() && options.strip_dead_code {
+        return Err(
+            "The dead code can't be eliminated when the entry function is selected".to_string(),
+        );
+    }
+    let host_net_if = &resolve_interface(riot_ip, host_net_if)?;
+    // The manifest points the device to the file server, so it needs to be
+    // known before signing.
+    let fetch_base_uri = publisher.fetch_base_uri(host_ip)?;
+    let mut timings = DeployTimings::default();
+    clean_stale_artifacts(artifacts)?;
+    let suit_storage_slot = allocate_slot(suit_storage_slot, riot_ip, host_net_if, transport)?;
+    let mut registry = Registry::load()?;
+    let sequence_number =
+        registry.next_sequence_number(riot_ip, options.sequence_number, options.force_seq)?;
+    let object_file_name = artifacts.object_file(bpf_source_file)?;
+    let provenance = collect_provenance(bpf_source_file, micro_bpf_root_dir);
+    let suit_manifest = &artifacts.manifest(suit_storage_slot);
+    let binary = artifacts.slot_binary(suit_storage_slot);
+    let coap_root = &artifacts.manifest_dir;
 
-    compile(bpf_source_file, Some(TEMP_FILE), out_dir)?;
+    let start = Instant::now();
+    compile(bpf_source_file, Some(&binary), &artifacts.object_dir)?;
+    timings.compile = start.elapsed();
+    if transport.dry_run {
+        info!("[dry-run] compiled {} into {}", bpf_source_file, object_file_name);
+    }
+    lint_program(
+        &object_file_name,
+        target,
+        &helper_indices,
+        options.deny_lint_warnings,
+    )?;
+    validate(binary_layout, target, &read_bytes_from_file(&object_file_name))?;
+    let exported_entries = entry_offsets(&object_file_name, entries)?;
+    let start = Instant::now();
     apply_postprocessing(
         &object_file_name,
         binary_layout,
-        TEMP_FILE,
+        &binary,
         helper_indices.clone(),
         helper_access_verification,
+        options
+            .embed_metadata
+            .then(|| program_metadata(bpf_source_file, &object_file_name, &provenance)),
+        entries.first().copied(),
     )?;
-    sign(
+    if options.strip_dead_code {
+        remove_dead_code(&binary, binary_layout)?;
+    }
+    if options.compress {
+        compress_binary(&binary, binary_layout, riot_ip, host_net_if, transport)?;
+    }
+    timings.layout_transform = start.elapsed();
+    if transport.dry_run {
+        info!("[dry-run] wrote the {:?} binary to {}", binary_layout, binary);
+    }
+    transport.check_cancelled()?;
+    let compression =
+        negotiate_compression(options.artifact_compression, riot_ip, host_net_if, transport)?;
+    let start = Instant::now();
+    let compressed = sign(
         host_net_if,
+        Some(&fetch_base_uri),
         board,
         coap_root,
-        TEMP_FILE,
+        &binary,
         suit_storage_slot,
-        micro_bpf_root_dir,
+        storage,
+        Some(sequence_number),
+        compression,
     )?;
-    pull(
+    timings.sign = start.elapsed();
+    if transport.dry_run {
+        info!("[dry-run] moved {} into {}", binary, coap_root);
+        info!(
+            "[dry-run] generated the manifest {}/{} with sequence number {}",
+            coap_root, suit_manifest, sequence_number
+        );
+    }
+    transport.check_cancelled()?;
+    let mut state = DeployState {
+        phase: DeployPhase::Signed,
+        bpf_source_file: bpf_source_file.to_string(),
+        suit_storage_slot,
+        storage,
+        sequence_number,
+        suit_manifest: suit_manifest.to_string(),
+        served: [artifacts.served(&binary), artifacts.served(suit_manifest)]
+            .into_iter()
+            .chain(compressed)
+            .collect(),
+        target,
+        binary_layout,
+        riot_net_if: riot_net_if.to_string(),
+        riot_ip: riot_ip.to_string(),
+        host_net_if: host_net_if.to_string(),
+        host_ip: host_ip.to_string(),
+        helper_indices,
+        helper_access_verification,
+        helper_access_list_source,
+        erase: options.erase,
+        provenance,
+        entries: exported_entries,
+    };
+    finish_deployment(
+        &mut state,
+        artifacts,
+        &mut registry,
+        publisher,
+        transport,
+        &mut timings,
+    )
+    .await?;
+
+    Ok((suit_storage_slot, timings))
+}
+
+/// Step of a deployment which was completed last.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeployPhase {
+    /// The binary and the manifest are in the CoAP root.
+    Signed,
+    /// The artifacts are available on the file server.
+    Published,
+    /// The device was notified about the update (the pull request succeeded).
+    Notified,
+}
+
+/// Progress of a deployment persisted after each step which talks to the
+/// network (see [`ArtifactStore::deploy_state`]). If the deployment fails
+/// because of a transient network failure, `deploy --resume` continues from
+/// the failed step instead of compiling and signing the program again, e.g.
+/// only the trigger is re-sent if the publication succeeded but the pull
+/// request didn't reach the device. The state is removed once the deployment
+/// finishes.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeployState {
+    pub phase: DeployPhase,
+    pub bpf_source_file: String,
+    pub suit_storage_slot: usize,
+    #[serde(default)]
+    pub storage: StorageBackend,
+    /// Sequence number of the signed manifest, the resumed deployment needs
+    /// to deliver the same one.
+    pub sequence_number: u64,
+    pub suit_manifest: String,
+    /// The binary and the manifest as served from the CoAP root, followed by
+    /// the compressed binary if the device fetches it compressed.
+    pub served: Vec<String>,
+    pub target: TargetVM,
+    pub binary_layout: BinaryFileLayout,
+    pub riot_net_if: String,
+    pub riot_ip: String,
+    pub host_net_if: String,
+    pub host_ip: String,
+    pub helper_indices: Vec<u8>,
+    pub helper_access_verification: HelperAccessVerification,
+    pub helper_access_list_source: HelperAccessListSource,
+    pub erase: bool,
+    #[serde(default)]
+    pub provenance: Provenance,
+    /// Entry functions exported by the program, see [`entry_offsets`].
+    #[serde(default)]
+    pub entries: BTreeMap<String, u32>,
+}
+
+impl DeployState {
+    /// Loads the state of the unfinished deployment, None if there is none.
+    pub fn load(artifacts: &ArtifactStore) -> Result<Option<Self>, String> {
+        let path = artifacts.deploy_state();
+        let Ok(contents) = fs::read_to_string(&path) else {
+            return Ok(None);
+        };
+        serde_json::from_str(&contents)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse the deployment state {}: {}", path, e))
+    }
+
+    fn save(&self, artifacts: &ArtifactStore) -> Result<(), String> {
+        let path = artifacts.deploy_state();
+        fs::write(&path, serde_json::to_string_pretty(self).unwrap())
+            .map_err(|e| format!("Failed to write the deployment state {}: {}", path, e))
+    }
+
+    fn advance(&mut self, phase: DeployPhase, artifacts: &ArtifactStore) -> Result<(), String> {
+        self.phase = phase;
+        self.save(artifacts)
+    }
+
+    fn clear(artifacts: &ArtifactStore) {
+        let path = artifacts.deploy_state();
+        if let Err(e) = fs::remove_file(&path) {
+            debug!("Failed to remove the deployment state {}: {}", path, e);
+        }
+    }
+}
+
+/// Continues the last deployment that failed after the program was signed.
+/// Returns the slot that the program was deployed to and the time spent in
+/// the resumed phases.
+pub async fn resume_deploy(
+    artifacts: &ArtifactStore,
+    publisher: &dyn Publisher,
+    transport: &TransportOptions,
+) -> Result<(usize, DeployTimings), String> {
+    let Some(mut state) = DeployState::load(artifacts)? else {
+        return Err(format!(
+            "There is no unfinished deployment to resume ({} doesn't exist)",
+            artifacts.deploy_state()
+        ));
+    };
+    debug!(
+        "Resuming the deployment of {} into slot {} after the {:?} phase",
+        state.bpf_source_file, state.suit_storage_slot, state.phase
+    );
+    if let Some(missing) = state.served.iter().find(|a| fs::metadata(a).is_err()) {
+        return Err(format!(
+            "The artifact {} of the unfinished deployment is missing, deploy the program again",
+            missing
+        ));
+    }
+
+    let mut registry = Registry::load()?;
+    let mut timings = DeployTimings::default();
+    finish_deployment(
+        &mut state,
+        artifacts,
+        &mut registry,
+        publisher,
+        transport,
+        &mut timings,
+    )
+    .await?;
+    Ok((state.suit_storage_slot, timings))
+}
+
+/// Performs the steps of the deployment following the signing of the
+/// program, skipping the ones that were already completed.
+async fn finish_deployment(
+    state: &mut DeployState,
+    artifacts: &ArtifactStore,
+    registry: &mut Registry,
+    publisher: &dyn Publisher,
+    transport: &TransportOptions,
+    timings: &mut DeployTimings,
+) -> Result<(), String> {
+    // Dry runs don't leave anything behind that could be resumed.
+    let persist = !transport.dry_run;
+    if persist {
+        state.save(artifacts)?;
+    }
+
+    if state.phase == DeployPhase::Signed {
+        let start = Instant::now();
+        publish_artifacts(publisher, &state.served, transport.dry_run)?;
+        timings.publish = start.elapsed();
+        if persist {
+            state.advance(DeployPhase::Published, artifacts)?;
+        }
+    }
+
+    if state.phase == DeployPhase::Published {
+        let start = Instant::now();
+        let (fetch_host, suit_manifest) = fetch_location(
+            &publisher.fetch_base_uri(&state.host_ip)?,
+            &state.suit_manifest,
+        )?;
+        let pulled = pull(
+            &state.riot_ip,
+            &fetch_host,
+            &suit_manifest,
+            &state.host_net_if,
+            &state.riot_net_if,
+            state.target,
+            state.binary_layout,
+            state.suit_storage_slot,
+            state.storage,
+            state.helper_access_verification,
+            state.helper_access_list_source,
+            &state.helper_indices,
+            state.erase,
+            transport,
+        )
+        .await;
+        withdraw_if_cancelled(&pulled, publisher, &state.served, transport.dry_run);
+        if pulled.as_ref().is_err_and(|e| is_cancelled(e)) {
+            DeployState::clear(artifacts);
+        }
+        pulled?;
+        timings.notify = start.elapsed();
+        if persist {
+            state.advance(DeployPhase::Notified, artifacts)?;
+        }
+    }
+
+    if !transport.dry_run {
+        let (riot_ip, host_net_if, slot) = (
+            state.riot_ip.as_str(),
+            state.host_net_if.as_str(),
+            state.suit_storage_slot,
+        );
+        let verified = verify_installed_artifact(
+            riot_ip,
+            host_net_if,
+            slot,
+            state.sequence_number,
+            &state.served[0],
+            transport,
+        )
+        .await;
+        record_if_cancelled(&verified, registry, riot_ip, state.sequence_number)?;
+        verified?;
+        // The timings are only known once the update is installed, which the
+        // verification waits for.
+        let device_timings =
+            query_device_timings(riot_ip, host_net_if, slot, state.sequence_number, transport);
+        if let Some(device_timings) = device_timings {
+            timings.device_fetch = Some(Duration::from_millis(device_timings.fetch));
+            timings.device_verify = Some(Duration::from_millis(device_timings.verify));
+        }
+
+        registry.record_deployment(
+            riot_ip,
+            slot,
+            &state.bpf_source_file,
+            state.provenance.clone(),
+        );
+        registry.record_entries(riot_ip, slot, state.entries.clone());
+        registry.record_sequence_number(riot_ip, state.sequence_number);
+        registry.save()?;
+        DeployState::clear(artifacts);
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct DeviceTimings {
+    fetch: u64,
+    verify: u64,
+    /// Sequence number of the update that the timings belong to.
+    #[serde(default)]
+    sequence_number: Option<u64>,
+}
+
+/// Asks the device how long the last update of the slot took. The device
+/// responds to `GET /suit/timings?slot=<slot>` with the time (in milliseconds)
+/// spent fetching and verifying the update:
+/// `{"fetch": 120, "verify": 35, "sequence_number": 1718000000}`.
+/// It needs to be called once the update is installed (see
+/// [`verify_installed_artifact`]), otherwise the device reports the previous
+/// one. Older firmware doesn't provide the resource, in which case None is
+/// returned, as well as if the timings belong to a different update.
+fn query_device_timings(
+    riot_ip: &str,
+    host_net_if: &str,
+    suit_storage_slot: usize,
+    sequence_number: u64,
+    transport: &TransportOptions,
+) -> Option<DeviceTimings> {
+    let url = format!(
+        "coap://[{}%{}]/suit/timings?slot={}",
+        riot_ip, host_net_if, suit_storage_slot
+    );
+    let output = send_request("GET", &url, None, transport).ok()?;
+    if output.stderr.len() > 0 {
+        debug!(
+            "The device didn't report the update timings: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return None;
+    }
+    let response = String::from_utf8(output.stdout).ok()?;
+    let timings =
+        serde_json::from_str::<DeviceTimings>(response.trim_matches(char::from(0))).ok()?;
+    if timings
+        .sequence_number
+        .is_some_and(|reported| reported != sequence_number)
+    {
+        debug!(
+            "The device reported the timings of the update {:?} instead of {}",
+            timings.sequence_number, sequence_number
+        );
+        return None;
+    }
+    Some(timings)
+}
+
+/// Deploys several programs in one update. Each program is compiled and
+/// postprocessed separately and then delivered as a separate component of
+/// a single SUIT manifest, see [`generate_manifest`]. The programs are given
+/// as pairs of the source file and the SUIT storage slot to install it into.
+pub async fn deploy_bundle(
+    programs: &[(String, usize)],
+    artifacts: &ArtifactStore,
+    target: TargetVM,
+    binary_layout: BinaryFileLayout,
+    storage: StorageBackend,
+    riot_net_if: &str,
+    riot_ip: &str,
+    host_net_if: &str,
+    host_ip: &str,
+    micro_bpf_root_dir: &str,
+    helper_indices: Vec<u8>,
+    helper_access_verification: HelperAccessVerification,
+    helper_access_list_source: HelperAccessListSource,
+    erase: bool,
+    sequence_number: Option<u64>,
+    force_seq: bool,
+    deny_lint_warnings: bool,
+    compress: bool,
+    strip_dead_code: bool,
+    embed_metadata: bool,
+    publisher: &dyn Publisher,
+    transport: &TransportOptions,
+) -> Result<(), String> {
+    let Some((_, first_slot)) = programs.first() else {
+        return Err("No programs to deploy".to_string());
+    };
+    let fetch_base_uri = publisher.fetch_base_uri(host_ip)?;
+
+    clean_stale_artifacts(artifacts)?;
+    let mut registry = Registry::load()?;
+    let sequence_number = registry.next_sequence_number(riot_ip, sequence_number, force_seq)?;
+
+    let mut components = vec![];
+    let mut provenances = vec![];
+    for (bpf_source_file, slot) in programs {
+        let object_file_name = artifacts.object_file(bpf_source_file)?;
+        let provenance = collect_provenance(bpf_source_file, Some(micro_bpf_root_dir));
+        transport.check_cancelled()?;
+        let binary = artifacts.slot_binary(*slot);
+        compile(bpf_source_file, Some(&binary), &artifacts.object_dir)?;
+        lint_program(&object_file_name, target, &helper_indices, deny_lint_warnings)?;
+        validate(binary_layout, target, &read_bytes_from_file(&object_file_name))?;
+        apply_postprocessing(
+            &object_file_name,
+            binary_layout,
+            &binary,
+            helper_indices.clone(),
+            helper_access_verification,
+            embed_metadata
+                .then(|| program_metadata(bpf_source_file, &object_file_name, &provenance)),
+            None,
+        )?;
+        if strip_dead_code {
+            remove_dead_code(&binary, binary_layout)?;
+        }
+        if compress {
+            compress_binary(&binary, binary_layout, riot_ip, host_net_if, transport)?;
+        }
+        components.push(ManifestComponent {
+            binary,
+            slot: *slot,
+            storage,
+        });
+        provenances.push(provenance);
+    }
+
+    let manifest = generate_manifest(
+        &components,
+        &fetch_base_uri,
+        &artifacts.manifest_dir,
+        BUNDLE_MANIFEST,
+        sequence_number,
+    )?;
+    if transport.dry_run {
+        info!(
+            "[dry-run] generated the manifest {} with {} components",
+            manifest,
+            components.len()
+        );
+    }
+    let mut served = components
+        .iter()
+        .map(|c| artifacts.served(&c.binary))
+        .collect::<Vec<String>>();
+    served.push(manifest);
+    transport.check_cancelled()?;
+    publish_artifacts(publisher, &served, transport.dry_run)?;
+
+    // A single pull request is enough for the device to install all
+    // components of the manifest.
+    let (fetch_host, suit_manifest) =
+        fetch_location(&fetch_base_uri, &format!("{}.signed", BUNDLE_MANIFEST))?;
+    let pulled = pull(
         riot_ip,
-        host_ip,
-        suit_manifest,
+        &fetch_host,
+        &suit_manifest,
         host_net_if,
         riot_net_if,
         target,
         binary_layout,
-        suit_storage_slot,
+        *first_slot,
+        storage,
         helper_access_verification,
         helper_access_list_source,
         &helper_indices,
-        erase
+        erase,
+        transport,
     )
-    .await?;
+    .await;
+    withdraw_if_cancelled(&pulled, publisher, &served, transport.dry_run);
+    pulled?;
+
+    if !transport.dry_run {
+        for component in &components {
+            let verified = verify_installed_artifact(
+                riot_ip,
+                host_net_if,
+                component.slot,
+                sequence_number,
+                &artifacts.served(&component.binary),
+                transport,
+            )
+            .await;
+            record_if_cancelled(&verified, &mut registry, riot_ip, sequence_number)?;
+            verified?;
+        }
+        for ((bpf_source_file, slot), provenance) in programs.iter().zip(provenances) {
+            registry.record_deployment(riot_ip, *slot, bpf_source_file, provenance);
+        }
+        registry.record_sequence_number(riot_ip, sequence_number);
+        registry.save()?;
+    }
+
+    Ok(())
+}
+
+/// Compiles the program once and produces its binary in each of the given
+/// layouts. The object file is reused if it is newer than the source file
+/// (changes to the included headers aren't tracked), the layout variants are
+/// generated from it in parallel. The program metadata is only embedded if
+/// `embed_metadata` is set. Returns the paths of the produced binaries, see
+/// [`ArtifactStore::layout_binary`].
+pub fn build_layouts(
+    bpf_source_file: &str,
+    artifacts: &ArtifactStore,
+    layouts: &[BinaryFileLayout],
+    helper_indices: &[u8],
+    helper_access_verification: HelperAccessVerification,
+    provenance: &Provenance,
+    embed_metadata: bool,
+) -> Result<Vec<(BinaryFileLayout, String)>, String> {
+    let object_file_name = artifacts.object_file(bpf_source_file)?;
+    if is_up_to_date(&object_file_name, bpf_source_file) {
+        debug!("Reusing the object file {}", object_file_name);
+    } else {
+        compile(bpf_source_file, None, &artifacts.object_dir)?;
+    }
+
+    let metadata =
+        embed_metadata.then(|| program_metadata(bpf_source_file, &object_file_name, provenance));
+    let binaries = layouts
+        .iter()
+        .map(|layout| artifacts.layout_binary(bpf_source_file, *layout))
+        .collect::<Result<Vec<String>, String>>()?;
+    thread::scope(|scope| {
+        let workers = layouts
+            .iter()
+            .zip(binaries)
+            .map(|(layout, binary)| {
+                let metadata = metadata.clone();
+                let object_file_name = &object_file_name;
+                scope.spawn(move || {
+                    apply_postprocessing(
+                        object_file_name,
+                        *layout,
+                        &binary,
+                        helper_indices.to_vec(),
+                        helper_access_verification,
+                        metadata,
+                        None,
+                    )
+                    .map(|_| (*layout, binary))
+                })
+            })
+            .collect::<Vec<_>>();
+
+        workers
+            .into_iter()
+            .map(|worker| {
+                worker
+                    .join()
+                    .map_err(|_| "The postprocessing thread panicked".to_string())?
+            })
+            .collect()
+    })
+}
+
+/// Compresses the .rodata section of the binary (see [`compress_rodata`]) if
+/// the firmware of the device is able to decompress it, otherwise the binary
+/// is deployed uncompressed. Reports how many bytes the compression saved.
+fn compress_binary(
+    binary: &str,
+    binary_layout: BinaryFileLayout,
+    riot_ip: &str,
+    host_net_if: &str,
+    transport: &TransportOptions,
+) -> Result<(), String> {
+    if binary_layout != BinaryFileLayout::ExtendedHeader {
+        return Err(format!(
+            "The .rodata compression isn't supported by the {:?} layout",
+            binary_layout
+        ));
+    }
+
+    let capabilities = query_capabilities(riot_ip, host_net_if, transport)?;
+    if transport.dry_run {
+        info!("[dry-run] assuming that the device supports .rodata compression");
+    } else if !capabilities.supports(CAPABILITY_RODATA_COMPRESSION) {
+        warn!(
+            "The device doesn't support .rodata compression, deploying {} uncompressed",
+            binary
+        );
+        return Ok(());
+    }
 
+    let bytes = read_bytes_from_file(binary);
+    match compress_rodata(&bytes)? {
+        Some((compressed, stats)) => {
+            fs::write(binary, &compressed)
+                .map_err(|e| format!("Failed to write {}: {}", binary, e))?;
+            info!(
+                "Compressed the .rodata section of {}: {} -> {} bytes (saved {} bytes, binary size {} -> {} bytes)",
+                binary,
+                stats.original_len,
+                stats.compressed_len,
+                stats.saved(),
+                bytes.len(),
+                compressed.len()
+            );
+        }
+        None => info!(
+            "Compressing the .rodata section of {} wouldn't reduce its size, deploying it uncompressed",
+            binary
+        ),
+    }
     Ok(())
 }
 
-pub fn get_object_file_name(bpf_source_file: &str, out_dir: &str) -> Result<String, String> {
-    let base_name = bpf_source_file.split("/").last().unwrap().split(".").nth(0);
+/// Removes the functions unreachable from the entry point of the program
+/// from the binary (see [`eliminate_dead_code`]) and reports how many bytes
+/// it saved.
+pub fn remove_dead_code(binary: &str, binary_layout: BinaryFileLayout) -> Result<(), String> {
+    if binary_layout != BinaryFileLayout::ExtendedHeader {
+        return Err(format!(
+            "The dead code elimination isn't supported by the {:?} layout",
+            binary_layout
+        ));
+    }
 
-    return match base_name {
-        Some(name) => Ok(format!("{}/{}.o", out_dir, name)),
-        None => Err("File not found: You need to provide the .c source file".to_string()),
+    let bytes = read_bytes_from_file(binary);
+    match eliminate_dead_code(&bytes)? {
+        Some((stripped, stats)) => {
+            fs::write(binary, &stripped)
+                .map_err(|e| format!("Failed to write {}: {}", binary, e))?;
+            info!(
+                "Removed {} unreachable functions from {}: .text {} -> {} bytes (saved {} bytes)",
+                stats.removed_functions,
+                binary,
+                stats.original_len,
+                stats.text_len,
+                stats.saved()
+            );
+        }
+        None => info!(
+            "All functions of {} are reachable, nothing to remove",
+            binary
+        ),
+    }
+    Ok(())
+}
+
+/// Deploys the program in each of the given layouts into consecutive SUIT
+/// storage slots starting at `first_slot`, e.g. to compare the layouts on the
+/// same device. All variants are built in one pass, see [`build_layouts`].
+/// Returns the slot that each of the layouts was deployed to.
+pub async fn deploy_layouts(
+    bpf_source_file: &str,
+    artifacts: &ArtifactStore,
+    target: TargetVM,
+    layouts: &[BinaryFileLayout],
+    first_slot: usize,
+    storage: StorageBackend,
+    riot_net_if: &str,
+    riot_ip: &str,
+    host_net_if: &str,
+    host_ip: &str,
+    board: &str,
+    micro_bpf_root_dir: Option<&str>,
+    helper_indices: Vec<u8>,
+    helper_access_verification: HelperAccessVerification,
+    helper_access_list_source: HelperAccessListSource,
+    erase: bool,
+    deny_lint_warnings: bool,
+    embed_metadata: bool,
+    publisher: &dyn Publisher,
+    transport: &TransportOptions,
+) -> Result<Vec<(BinaryFileLayout, usize)>, String> {
+    let host_net_if = &resolve_interface(riot_ip, host_net_if)?;
+    let fetch_base_uri = publisher.fetch_base_uri(host_ip)?;
+    clean_stale_artifacts(artifacts)?;
+    let provenance = collect_provenance(bpf_source_file, micro_bpf_root_dir);
+    let binaries = build_layouts(
+        bpf_source_file,
+        artifacts,
+        layouts,
+        &helper_indices,
+        helper_access_verification,
+        &provenance,
+        embed_metadata,
+    )?;
+    lint_program(
+        &artifacts.object_file(bpf_source_file)?,
+        target,
+        &helper_indices,
+        deny_lint_warnings,
+    )?;
+
+    let mut registry = Registry::load()?;
+    let mut deployed = vec![];
+    for (slot, (layout, binary)) in (first_slot..).zip(binaries) {
+        // Signing moves the binary into the CoAP root, we keep the
+        // built variant in the out directory.
+        let slot_binary = artifacts.slot_binary(slot);
+        fs::copy(&binary, &slot_binary).map_err(|e| format!("Failed to copy {}: {}", binary, e))?;
+
+        transport.check_cancelled()?;
+        let sequence_number = registry.next_sequence_number(riot_ip, None, false)?;
+        let suit_manifest = artifacts.manifest(slot);
+        sign(
+            host_net_if,
+            Some(&fetch_base_uri),
+            board,
+            &artifacts.manifest_dir,
+            &slot_binary,
+            slot,
+            storage,
+            Some(sequence_number),
+            None,
+        )?;
+        let served = [
+            artifacts.served(&slot_binary),
+            artifacts.served(&suit_manifest),
+        ];
+        publish_artifacts(publisher, &served, transport.dry_run)?;
+        let (fetch_host, suit_manifest) = fetch_location(&fetch_base_uri, &suit_manifest)?;
+        let pulled = pull(
+            riot_ip,
+            &fetch_host,
+            &suit_manifest,
+            host_net_if,
+            riot_net_if,
+            target,
+            layout,
+            slot,
+            storage,
+            helper_access_verification,
+            helper_access_list_source,
+            &helper_indices,
+            erase,
+            transport,
+        )
+        .await;
+        withdraw_if_cancelled(&pulled, publisher, &served, transport.dry_run);
+        pulled?;
+
+        if !transport.dry_run {
+            let verified = verify_installed_artifact(
+                riot_ip,
+                host_net_if,
+                slot,
+                sequence_number,
+                &artifacts.served(&slot_binary),
+                transport,
+            )
+            .await;
+            record_if_cancelled(&verified, &mut registry, riot_ip, sequence_number)?;
+            verified?;
+            registry.record_deployment(riot_ip, slot, bpf_source_file, provenance.clone());
+            registry.record_sequence_number(riot_ip, sequence_number);
+            registry.save()?;
+        }
+        deployed.push((layout, slot));
+    }
+
+    Ok(deployed)
+}
+
+/// Checks the program for constructs that are known to break on the device
+/// before it gets deployed, see [`lint`].
+/// Determines the revisions of the sources that the program is built from,
+/// deploying uncommitted changes is allowed but reported.
+fn collect_provenance(bpf_source_file: &str, micro_bpf_root_dir: Option<&str>) -> Provenance {
+    let provenance = Provenance::collect(bpf_source_file, micro_bpf_root_dir);
+    if provenance.is_dirty() {
+        warn!(
+            "Deploying {} built from uncommitted changes ({}), it won't be possible \
+             to trace it back to the exact sources",
+            bpf_source_file, provenance
+        );
+    }
+    provenance
+}
+
+fn lint_program(
+    object_file_name: &str,
+    target: TargetVM,
+    helper_indices: &[u8],
+    deny_warnings: bool,
+) -> Result<(), String> {
+    let options = LintOptions {
+        target,
+        jit: false,
+        allowed_helpers: helper_indices.to_vec(),
     };
+    report(&lint(object_file_name, &options)?, deny_warnings)
+}
+
+/// Makes the artifacts written into the CoAP root directory available to the
+/// device, see [`Publisher`].
+fn publish_artifacts(
+    publisher: &dyn Publisher,
+    artifacts: &[String],
+    dry_run: bool,
+) -> Result<(), String> {
+    for artifact in artifacts {
+        if dry_run {
+            info!(
+                "[dry-run] published {} to {}",
+                artifact,
+                publisher.destination()
+            );
+            continue;
+        }
+        publisher.publish(artifact)?;
+    }
+    Ok(())
+}
+
+/// Removes the artifacts of a deployment which was cancelled before the device
+/// installed the update, both from the file server and the CoAP root
+/// directory. This way the device can't fetch them later on (e.g. when a
+/// pull request which was abandoned by the client reaches it) and the next
+/// deployment starts from a clean state.
+fn withdraw_if_cancelled<T>(
+    result: &Result<T, String>,
+    publisher: &dyn Publisher,
+    artifacts: &[String],
+    dry_run: bool,
+) {
+    match result {
+        Err(e) if is_cancelled(e) => {}
+        _ => return,
+    }
+    for artifact in artifacts {
+        if dry_run {
+            info!(
+                "[dry-run] withdrew {} from {}",
+                artifact,
+                publisher.destination()
+            );
+            continue;
+        }
+        if let Err(e) = publisher.unpublish(artifact) {
+            warn!("Failed to withdraw {}: {}", artifact, e);
+        }
+        if let Err(e) = fs::remove_file(artifact) {
+            debug!("Failed to remove {}: {}", artifact, e);
+        }
+    }
+}
+
+/// Once the device has installed the update, cancelling the verification
+/// doesn't undo it, so its sequence number needs to be recorded to avoid
+/// generating a manifest which the device would reject.
+fn record_if_cancelled<T>(
+    result: &Result<T, String>,
+    registry: &mut Registry,
+    riot_ip: &str,
+    sequence_number: u64,
+) -> Result<(), String> {
+    match result {
+        Err(e) if is_cancelled(e) => {
+            registry.record_sequence_number(riot_ip, sequence_number);
+            registry.save()
+        }
+        _ => Ok(()),
+    }
+}
+
+fn clean_stale_artifacts(artifacts: &ArtifactStore) -> Result<(), String> {
+    let removed = artifacts.clean_stale()?;
+    if removed > 0 {
+        debug!("Removed {} stale artifacts", removed);
+    }
+    Ok(())
+}
+
+fn is_up_to_date(output_file: &str, input_file: &str) -> bool {
+    let modified = |file: &str| fs::metadata(file).and_then(|m| m.modified()).ok();
+    matches!(
+        (modified(output_file), modified(input_file)),
+        (Some(output), Some(input)) if output >= input
+    )
 }
@@ -0,0 +1,116 @@
+use log::debug;
+use micro_bpf_common::DeviceInfoResponse;
+
+use crate::transport::{send_request, TransportOptions};
+
+/// Asks the device to reboot by sending a POST request to its `/reset`
+/// resource. The firmware stops all running VMs before rebooting the board,
+/// the programs stored in the SUIT storage slots are kept.
+pub fn reboot_device(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    transport: &TransportOptions,
+) -> Result<(), String> {
+    let url = format!(
+        "coap://[{}%{}]/reset",
+        riot_ipv6_addr, host_network_interface
+    );
+    debug!("Rebooting the device: {}", url);
+    post(&url, transport)
+}
+
+/// Stops the long-running VM executing the program from the given SUIT
+/// storage slot (all of them if no slot is specified) so that a wedged
+/// program can be replaced without rebooting the whole device.
+pub fn reset_vm(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    suit_storage_slot: Option<usize>,
+    transport: &TransportOptions,
+) -> Result<(), String> {
+    let mut url = format!(
+        "coap://[{}%{}]/long-running/reset",
+        riot_ipv6_addr, host_network_interface
+    );
+    if let Some(slot) = suit_storage_slot {
+        url.push_str(&format!("?slot={}", slot));
+    }
+    debug!("Resetting the VM: {}", url);
+    post(&url, transport)
+}
+
+/// Retrieves the runtime information about the device (firmware version,
+/// available VMs, storage slots, memory) from its `/info` resource. The tests
+/// can use it to skip the cases which the device under test can't run.
+pub fn query_device_info(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    transport: &TransportOptions,
+) -> Result<DeviceInfoResponse, String> {
+    let url = format!(
+        "coap://[{}%{}]/info",
+        riot_ipv6_addr, host_network_interface
+    );
+    debug!("Querying the device info: {}", url);
+
+    let output = send_request("GET", &url, None, transport)?;
+    if transport.dry_run {
+        return Ok(DeviceInfoResponse::default());
+    }
+    if !output.stderr.is_empty() {
+        let stderr = String::from_utf8(output.stderr)
+            .map_err(|e| format!("Failed to parse the stderr: {}", e))?;
+        return Err(format!("aiocoap-client failed with: {}", stderr));
+    }
+
+    let response = String::from_utf8(output.stdout)
+        .map_err(|e| format!("Failed to parse the response: {}", e))?;
+    serde_json::from_str::<DeviceInfoResponse>(&response)
+        .map_err(|e| format!("Failed to parse the device info: {}", e))
+}
+
+/// Renders the device info as a human-readable table.
+pub fn format_device_info(info: &DeviceInfoResponse) -> String {
+    let slots = info
+        .slots
+        .iter()
+        .map(|size| format!("{}B", size))
+        .collect::<Vec<String>>()
+        .join(", ");
+    let uptime = format!(
+        "{}d {:02}h {:02}m {:02}s",
+        info.uptime / 86400,
+        info.uptime / 3600 % 24,
+        info.uptime / 60 % 60,
+        info.uptime % 60
+    );
+    [
+        ("Firmware", info.firmware.clone()),
+        ("RIOT", info.riot.clone()),
+        ("VMs", info.vms.join(", ")),
+        ("Slots", format!("{} ({})", info.slots.len(), slots)),
+        (
+            "Free RAM",
+            format!("{:.1} KiB", info.free_ram as f64 / 1024.0),
+        ),
+        (
+            "Clock",
+            format!("{:.1} MHz", info.clock_hz as f64 / 1_000_000.0),
+        ),
+        ("Uptime", uptime),
+    ]
+    .iter()
+    .map(|(key, value)| format!("{:<10}{}", key, value))
+    .collect::<Vec<String>>()
+    .join("\n")
+}
+
+fn post(url: &str, transport: &TransportOptions) -> Result<(), String> {
+    let output = send_request("POST", url, None, transport)?;
+    if !output.stderr.is_empty() {
+        let stderr = String::from_utf8(output.stderr)
+            .map_err(|e| format!("Failed to parse the stderr: {}", e))?;
+        return Err(format!("aiocoap-client failed with: {}", stderr));
+    }
+    Ok(())
+}
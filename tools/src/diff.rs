@@ -0,0 +1,244 @@
+use std::fmt;
+
+use micro_bpf_common::BinaryFileLayout;
+use micro_bpf_elf_utils::extract_text_section;
+
+use crate::{
+    bytecode::{decode, Instruction, CALL_OPCODE, LDDWD_OPCODE, LDDWR_OPCODE},
+    postprocessing::read_bytes_from_file,
+};
+
+/// Single entry of the instruction-level diff between two artifacts.
+#[derive(Debug)]
+pub enum DiffEntry {
+    Unchanged(Instruction),
+    Added(Instruction),
+    Removed(Instruction),
+    /// An instruction was replaced by a different one at the same position.
+    Changed(Instruction, Instruction),
+}
+
+/// Result of comparing two compiled versions of the same program.
+#[derive(Debug)]
+pub struct ArtifactDiff {
+    pub entries: Vec<DiffEntry>,
+    pub old_size: usize,
+    pub new_size: usize,
+    pub old_text_size: usize,
+    pub new_text_size: usize,
+}
+
+impl ArtifactDiff {
+    pub fn added(&self) -> usize {
+        self.count(|e| matches!(e, DiffEntry::Added(_)))
+    }
+
+    pub fn removed(&self) -> usize {
+        self.count(|e| matches!(e, DiffEntry::Removed(_)))
+    }
+
+    pub fn changed(&self) -> usize {
+        self.count(|e| matches!(e, DiffEntry::Changed(_, _)))
+    }
+
+    fn count(&self, predicate: impl Fn(&DiffEntry) -> bool) -> usize {
+        self.entries.iter().filter(|e| predicate(e)).count()
+    }
+}
+
+/// Compares two compiled artifacts instruction-by-instruction.
+///
+/// The immediate operands of instructions that get patched during relocation
+/// (loads from .data/.rodata and calls to functions defined inside of the
+/// program) depend on the positions of the sections and functions in the
+/// binary. Because of this, they are normalized before comparing so that e.g.
+/// adding a string literal doesn't show up as a change in every single load
+/// from the .rodata section.
+pub fn diff_artifacts(
+    old_binary: &str,
+    new_binary: &str,
+    layout: BinaryFileLayout,
+) -> Result<ArtifactDiff, String> {
+    let old_bytes = read_bytes_from_file(old_binary);
+    let new_bytes = read_bytes_from_file(new_binary);
+
+    let old_text = extract_text_section(&old_bytes, layout)?;
+    let new_text = extract_text_section(&new_bytes, layout)?;
+
+    let old = decode(old_text)?;
+    let new = decode(new_text)?;
+
+    Ok(ArtifactDiff {
+        entries: diff_instructions(&old, &new),
+        old_size: old_bytes.len(),
+        new_size: new_bytes.len(),
+        old_text_size: old_text.len(),
+        new_text_size: new_text.len(),
+    })
+}
+
+/// Key used for comparing instructions, see [`diff_artifacts`]. The plain
+/// LDDW loads a 64-bit constant, its immediate isn't relocated.
+fn normalize(instruction: &Instruction) -> (u8, u8, u8, i16, i64) {
+    let relocated = matches!(instruction.opcode, LDDWD_OPCODE | LDDWR_OPCODE)
+        || (instruction.opcode == CALL_OPCODE && !instruction.is_helper_call());
+    let immediate = if relocated { 0 } else { instruction.immediate };
+    (
+        instruction.opcode,
+        instruction.dst,
+        instruction.src,
+        instruction.offset,
+        immediate,
+    )
+}
+
+/// Computes the diff using the longest common subsequence of the two
+/// instruction lists. Programs are small enough for the quadratic table.
+fn diff_instructions(old: &[Instruction], new: &[Instruction]) -> Vec<DiffEntry> {
+    let (n, m) = (old.len(), new.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if normalize(&old[i]) == normalize(&new[j]) {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut entries = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && normalize(&old[i]) == normalize(&new[j]) {
+            entries.push(DiffEntry::Unchanged(new[j]));
+            i += 1;
+            j += 1;
+        } else if i < n && j < m && lcs[i + 1][j + 1] == lcs[i][j] {
+            // Neither instruction is part of the common subsequence, so the
+            // old one was replaced by the new one.
+            entries.push(DiffEntry::Changed(old[i], new[j]));
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            entries.push(DiffEntry::Added(new[j]));
+            j += 1;
+        } else {
+            entries.push(DiffEntry::Removed(old[i]));
+            i += 1;
+        }
+    }
+    entries
+}
+
+impl fmt::Display for ArtifactDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            match entry {
+                DiffEntry::Unchanged(insn) => writeln!(f, "  {:4}: {}", insn.index, insn)?,
+                DiffEntry::Added(insn) => writeln!(f, "+ {:4}: {}", insn.index, insn)?,
+                DiffEntry::Removed(insn) => writeln!(f, "- {:4}: {}", insn.index, insn)?,
+                DiffEntry::Changed(old, new) => {
+                    writeln!(f, "~ {:4}: {} -> {}", new.index, old, new)?
+                }
+            }
+        }
+
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Instructions: {} added, {} removed, {} changed",
+            self.added(),
+            self.removed(),
+            self.changed()
+        )?;
+        writeln!(
+            f,
+            "Binary size: {} -> {} bytes ({:+})",
+            self.old_size,
+            self.new_size,
+            self.new_size as i64 - self.old_size as i64
+        )?;
+        write!(
+            f,
+            ".text size: {} -> {} bytes ({:+})",
+            self.old_text_size,
+            self.new_text_size,
+            self.new_text_size as i64 - self.old_text_size as i64
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::{EXIT_OPCODE, LDDW_OPCODE};
+
+    fn instruction(index: usize, opcode: u8, src: u8, immediate: i64) -> Instruction {
+        Instruction {
+            index,
+            opcode,
+            dst: 0,
+            src,
+            offset: 0,
+            immediate,
+        }
+    }
+
+    fn summary(entries: &[DiffEntry]) -> String {
+        entries
+            .iter()
+            .map(|entry| match entry {
+                DiffEntry::Unchanged(_) => ' ',
+                DiffEntry::Added(_) => '+',
+                DiffEntry::Removed(_) => '-',
+                DiffEntry::Changed(_, _) => '~',
+            })
+            .collect()
+    }
+
+    #[test]
+    fn changed_constant_is_reported() {
+        let old = [
+            instruction(0, LDDW_OPCODE, 0, 1 << 40),
+            instruction(2, EXIT_OPCODE, 0, 0),
+        ];
+        let new = [
+            instruction(0, LDDW_OPCODE, 0, 2 << 40),
+            instruction(2, EXIT_OPCODE, 0, 0),
+        ];
+        assert_eq!(summary(&diff_instructions(&old, &new)), "~ ");
+    }
+
+    #[test]
+    fn relocated_immediates_are_ignored() {
+        let old = [
+            instruction(0, LDDWR_OPCODE, 0, 0x10),
+            instruction(2, LDDWD_OPCODE, 0, 0x20),
+            instruction(4, CALL_OPCODE, 1, 3),
+            instruction(5, CALL_OPCODE, 0, 3),
+        ];
+        let new = [
+            instruction(0, LDDWR_OPCODE, 0, 0x18),
+            instruction(2, LDDWD_OPCODE, 0, 0x28),
+            instruction(4, CALL_OPCODE, 1, 4),
+            instruction(5, CALL_OPCODE, 0, 4),
+        ];
+        // Only the helper ID of the last call is a real change.
+        assert_eq!(summary(&diff_instructions(&old, &new)), "   ~");
+    }
+
+    #[test]
+    fn inserted_instruction_is_added() {
+        let old = [
+            instruction(0, 0xb7, 0, 1),
+            instruction(1, EXIT_OPCODE, 0, 0),
+        ];
+        let new = [
+            instruction(0, 0xb7, 0, 1),
+            instruction(1, 0x07, 0, 2),
+            instruction(2, EXIT_OPCODE, 0, 0),
+        ];
+        assert_eq!(summary(&diff_instructions(&old, &new)), " + ");
+    }
+}
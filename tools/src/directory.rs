@@ -0,0 +1,79 @@
+use log::debug;
+
+use crate::{
+    endpoints::Endpoints,
+    transport::{send_request, TransportOptions},
+};
+
+/// Path of the endpoint lookup interface of the resource directory.
+const ENDPOINT_LOOKUP: &str = "endpoint-lookup/";
+
+/// Looks up the IPv6 address of the device registered under the given
+/// endpoint name in a CoRE Resource Directory (RFC 9176). This allows for
+/// managing the devices on remote 6LoWPAN segments whose addresses aren't
+/// known upfront, e.g. because they are assigned by the border router.
+///
+/// The directory responds with the registrations in the link format:
+/// `</reg/1>;base="coap://[2001:db8::1]";ep="node-1";rt="core.rd-ep"`,
+/// the address is taken from the base URI of the matching registration.
+pub fn lookup_endpoint(
+    resource_directory: &str,
+    endpoint: &str,
+    transport: &TransportOptions,
+) -> Result<String, String> {
+    let url = format!(
+        "{}/{}?ep={}",
+        resource_directory.trim_end_matches('/'),
+        ENDPOINT_LOOKUP,
+        endpoint
+    );
+    debug!("Looking up the endpoint: {}", url);
+
+    // The credentials and the resource layout of the devices don't apply to
+    // the directory, only the proxy does.
+    let transport = TransportOptions {
+        oscore_context_dir: None,
+        access_token: None,
+        endpoints: Endpoints::default(),
+        ..transport.clone()
+    };
+    let output = send_request("GET", &url, None, &transport)?;
+
+    if output.stderr.len() > 0 {
+        let stderr = String::from_utf8(output.stderr)
+            .map_err(|e| format!("Failed to parse the stderr: {}", e))?;
+        Err(format!("aiocoap-client failed with: {}", stderr))?
+    }
+
+    let response = String::from_utf8(output.stdout)
+        .map_err(|e| format!("Failed to parse the response: {}", e))?;
+
+    parse_endpoint_address(&response, endpoint).ok_or_else(|| {
+        format!(
+            "The endpoint {} isn't registered in the resource directory {}",
+            endpoint, resource_directory
+        )
+    })
+}
+
+/// Finds the registration of the endpoint in the link-format response and
+/// extracts the IPv6 address from its base URI.
+fn parse_endpoint_address(response: &str, endpoint: &str) -> Option<String> {
+    response
+        .trim_matches(char::from(0))
+        .split(",<")
+        .map(|link| {
+            link.split(';')
+                .skip(1)
+                .filter_map(|attribute| attribute.split_once('='))
+                .map(|(key, value)| (key.trim(), value.trim().trim_matches('"')))
+                .collect::<Vec<(&str, &str)>>()
+        })
+        .find(|attributes| attributes.contains(&("ep", endpoint)))?
+        .iter()
+        .find(|(key, _)| *key == "base")
+        .and_then(|(_, base)| {
+            let (start, end) = (base.find('[')?, base.find(']')?);
+            Some(base[start + 1..end].to_string())
+        })
+}
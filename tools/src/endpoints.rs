@@ -0,0 +1,124 @@
+use log::debug;
+
+use crate::environment::Environment;
+
+/// Resources of the SUIT update procedure, all of them start with this
+/// prefix (e.g. `suit/pull`, `suit/slots`).
+const SUIT_RESOURCES: &str = "suit";
+
+/// Locations of the CoAP resources exposed by the device. Some firmware builds
+/// serve the SUIT resources and the VM execution resources on separate ports
+/// or under different paths, the tool builds all urls using the default
+/// layout and they are then resolved according to this configuration by
+/// [`crate::transport::send_request`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Endpoints {
+    /// Port of the SUIT resources, the default CoAP port is used if None.
+    pub suit_port: Option<u16>,
+    /// Port of the remaining resources (VM execution, logs, health checks).
+    pub execution_port: Option<u16>,
+    /// Paths of the resources which differ from the defaults, given as pairs
+    /// of the default path and the path used by the device, e.g.
+    /// (`suit/pull`, `fw/pull`). A prefix of a path can also be remapped, in
+    /// which case all resources under it are moved.
+    pub resource_paths: Vec<(String, String)>,
+}
+
+impl Endpoints {
+    /// Loads the endpoints from the device profile (SUIT_PORT, EXECUTION_PORT
+    /// and RESOURCE_PATHS).
+    pub fn from_env(env: &Environment) -> Self {
+        Endpoints {
+            suit_port: env.suit_port,
+            execution_port: env.execution_port,
+            resource_paths: env
+                .resource_paths
+                .as_deref()
+                .map(parse_resource_paths)
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Endpoints::default()
+    }
+
+    /// Rewrites the url of the form `coap://[ip%netif]/path?query` so that
+    /// it points to the port and path where the device serves the resource.
+    pub fn resolve(&self, url: &str) -> Result<String, String> {
+        if self.is_default() {
+            return Ok(url.to_string());
+        }
+
+        let Some(end) = url.find(']') else {
+            return Err(format!(
+                "Unable to extract the device address from: {}",
+                url
+            ));
+        };
+        let (authority, rest) = url.split_at(end + 1);
+        // The default urls don't specify the port, but we still strip it in
+        // case the caller did.
+        let rest = match rest.strip_prefix(':') {
+            Some(rest) => rest.trim_start_matches(|c: char| c.is_ascii_digit()),
+            None => rest,
+        };
+        let rest = rest.trim_start_matches('/');
+        let (path, query) = match rest.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (rest, None),
+        };
+
+        let is_suit_resource =
+            path == SUIT_RESOURCES || path.starts_with(&format!("{}/", SUIT_RESOURCES));
+        let port = if is_suit_resource {
+            self.suit_port
+        } else {
+            self.execution_port
+        };
+
+        let mut resolved = authority.to_string();
+        if let Some(port) = port {
+            resolved.push_str(&format!(":{}", port));
+        }
+        resolved.push('/');
+        resolved.push_str(&self.resolve_path(path));
+        if let Some(query) = query {
+            resolved.push('?');
+            resolved.push_str(query);
+        }
+
+        debug!("Resolved the url {} to {}", url, resolved);
+        Ok(resolved)
+    }
+
+    /// Applies the longest matching remapping of the path.
+    fn resolve_path(&self, path: &str) -> String {
+        let remapping = self
+            .resource_paths
+            .iter()
+            .filter(|(from, _)| path == from || path.starts_with(&format!("{}/", from)))
+            .max_by_key(|(from, _)| from.len());
+
+        match remapping {
+            Some((from, to)) => format!("{}{}", to, &path[from.len()..]),
+            None => path.to_string(),
+        }
+    }
+}
+
+/// Parses the remapped resource paths specified as a comma-separated list of
+/// `<default path>=<device path>` entries, e.g.
+/// `suit/pull=fw/pull,short-execution=vm/run`. Malformed entries are ignored.
+pub fn parse_resource_paths(spec: &str) -> Vec<(String, String)> {
+    spec.split(',')
+        .filter_map(|entry| entry.split_once('='))
+        .map(|(from, to)| {
+            (
+                from.trim().trim_matches('/').to_string(),
+                to.trim().trim_matches('/').to_string(),
+            )
+        })
+        .filter(|(from, to)| !from.is_empty() && !to.is_empty())
+        .collect()
+}
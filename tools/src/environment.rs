@@ -1,6 +1,8 @@
-use std::{env, path::Path};
+use std::{collections::HashMap, env, fmt, fs, path::Path, sync::OnceLock};
 
-#[derive(Debug)]
+use log::warn;
+
+#[derive(Debug, Clone)]
 pub struct Environment {
     /// Root directory of the mibpf repository.
     pub micro_bpf_root_dir: String,
@@ -8,36 +10,422 @@ pub struct Environment {
     pub coap_root_dir: String,
     /// Directory for the object files.
     pub out_dir: String,
+    /// Directory where the binaries are assembled before they are signed.
+    pub binary_dir: String,
+    /// Object files and binaries older than this are removed before
+    /// deploying, see [`crate::ArtifactStore`].
+    pub artifact_max_age_hours: Option<u64>,
     /// Directory with the source files.
     pub src_dir: String,
     /// Network interface of the RIOT instance.
     pub riot_instance_net_if: String,
     /// IPv6 address of the RIOT instance.
     pub riot_instance_ip: String,
-    /// Network interface of the host machine.
+    /// Network interface of the host machine, `auto` derives it from the
+    /// address of the RIOT instance.
     pub host_net_if: String,
     /// IPv6 address of the host machine.
     pub host_ip: String,
     /// Name of the target microcontroller board.
     pub board_name: String,
+    /// Directory containing the per-device OSCORE security contexts. If set,
+    /// all requests sent to the RIOT instance are protected using OSCORE.
+    pub oscore_context_dir: Option<String>,
+    /// Access token attached to the requests sent to the RIOT instance, see
+    /// [`crate::token`].
+    pub access_token: Option<String>,
+    /// Refresh token used to obtain a new access token once it expires.
+    pub refresh_token: Option<String>,
+    /// Maximum number of requests sent to the RIOT instance concurrently.
+    pub max_concurrent_requests: Option<usize>,
+    /// Maximum sustained number of requests per second sent to the RIOT instance.
+    pub requests_per_second: Option<f64>,
+    /// Port of the SUIT resources of the RIOT instance, see [`crate::Endpoints`].
+    pub suit_port: Option<u16>,
+    /// Port of the VM execution resources of the RIOT instance.
+    pub execution_port: Option<u16>,
+    /// Resource paths which differ from the defaults, e.g. `suit/pull=fw/pull`.
+    pub resource_paths: Option<String>,
+    /// Time-series store that the results are written to (only with the
+    /// `telemetry` feature), see [`crate::telemetry::TelemetrySink`].
+    pub telemetry_sink: Option<String>,
+    /// API token of the InfluxDB telemetry sink.
+    pub telemetry_token: Option<String>,
+    /// CoAP forward proxy used to reach the RIOT instance, see
+    /// [`crate::transport::TransportOptions::proxy`].
+    pub coap_proxy: Option<String>,
+    /// CoRE Resource Directory where the devices register themselves, see
+    /// [`crate::directory::lookup_endpoint`].
+    pub resource_directory: Option<String>,
+    /// Directory containing the pinned public keys of the devices, see
+    /// [`crate::identity::IdentityStore`].
+    pub identity_dir: Option<String>,
+    /// HTTP endpoint receiving the summaries of the deployments, executions
+    /// and benchmarks, see [`crate::webhook::result_summary`].
+    pub webhook_url: Option<String>,
+    /// Seconds that the executions wait for a busy device before failing,
+    /// see [`crate::transport::TransportOptions::queue_timeout`].
+    pub queue_timeout: Option<u64>,
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let optional = |value: &Option<String>| value.clone().unwrap_or_default();
+        // The tokens are secrets, we only show whether they are set.
+        let secret = |value: &Option<String>| match value {
+            Some(_) => "<set>".to_string(),
+            None => String::new(),
+        };
+        let variables = [
+            ("MIBPF_ROOT_DIR", self.micro_bpf_root_dir.clone()),
+            ("COAP_ROOT_DIR", self.coap_root_dir.clone()),
+            ("OUT_DIR", self.out_dir.clone()),
+            ("BINARY_DIR", self.binary_dir.clone()),
+            (
+                "ARTIFACT_MAX_AGE_HOURS",
+                optional(&self.artifact_max_age_hours.map(|v| v.to_string())),
+            ),
+            ("SRC_DIR", self.src_dir.clone()),
+            ("RIOT_INSTANCE_NET_IF", self.riot_instance_net_if.clone()),
+            ("RIOT_INSTANCE_IP", self.riot_instance_ip.clone()),
+            ("HOST_NET_IF", self.host_net_if.clone()),
+            ("HOST_IP", self.host_ip.clone()),
+            ("BOARD_NAME", self.board_name.clone()),
+            ("OSCORE_CONTEXT_DIR", optional(&self.oscore_context_dir)),
+            ("ACCESS_TOKEN", secret(&self.access_token)),
+            ("REFRESH_TOKEN", secret(&self.refresh_token)),
+            (
+                "MAX_CONCURRENT_REQUESTS",
+                optional(&self.max_concurrent_requests.map(|v| v.to_string())),
+            ),
+            (
+                "REQUESTS_PER_SECOND",
+                optional(&self.requests_per_second.map(|v| v.to_string())),
+            ),
+            (
+                "SUIT_PORT",
+                optional(&self.suit_port.map(|v| v.to_string())),
+            ),
+            (
+                "EXECUTION_PORT",
+                optional(&self.execution_port.map(|v| v.to_string())),
+            ),
+            ("RESOURCE_PATHS", optional(&self.resource_paths)),
+            ("TELEMETRY_SINK", optional(&self.telemetry_sink)),
+            ("TELEMETRY_TOKEN", secret(&self.telemetry_token)),
+            ("COAP_PROXY", optional(&self.coap_proxy)),
+            ("RESOURCE_DIRECTORY", optional(&self.resource_directory)),
+            ("DEVICE_IDENTITY_DIR", optional(&self.identity_dir)),
+            ("WEBHOOK_URL", optional(&self.webhook_url)),
+            (
+                "QUEUE_TIMEOUT",
+                optional(&self.queue_timeout.map(|v| v.to_string())),
+            ),
+        ];
+        for (key, value) in variables {
+            writeln!(f, "{}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Variables of the device profile together with their default values.
+const VARIABLES: &[(&str, Option<&str>)] = &[
+    ("MIBPF_ROOT_DIR", Some("..")),
+    ("COAP_ROOT_DIR", Some("../coaproot")),
+    ("OUT_DIR", Some("../out")),
+    ("BINARY_DIR", Some(".")),
+    ("ARTIFACT_MAX_AGE_HOURS", None),
+    ("SRC_DIR", Some("../bpf/tests")),
+    ("RIOT_INSTANCE_NET_IF", Some("6")),
+    ("RIOT_INSTANCE_IP", Some("fe80::a0d9:ebff:fed5:986b")),
+    ("HOST_NET_IF", Some("auto")),
+    ("HOST_IP", Some("fe80::cc9a:73ff:fe4a:47f6")),
+    ("BOARD_NAME", Some("native")),
+    ("OSCORE_CONTEXT_DIR", None),
+    ("ACCESS_TOKEN", None),
+    ("REFRESH_TOKEN", None),
+    ("MAX_CONCURRENT_REQUESTS", None),
+    ("REQUESTS_PER_SECOND", None),
+    ("SUIT_PORT", None),
+    ("EXECUTION_PORT", None),
+    ("RESOURCE_PATHS", None),
+    ("TELEMETRY_SINK", None),
+    ("TELEMETRY_TOKEN", None),
+    ("COAP_PROXY", None),
+    ("RESOURCE_DIRECTORY", None),
+    ("DEVICE_IDENTITY_DIR", None),
+    ("WEBHOOK_URL", None),
+    ("QUEUE_TIMEOUT", None),
+];
+
+/// Layer of the configuration that a value was taken from. The layers are
+/// listed in the order of increasing precedence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    Default,
+    /// The config.toml file (or the file specified by MIBPF_CONFIG).
+    ConfigFile(String),
+    /// The .env file of the device profile.
+    EnvFile(String),
+    /// Variables exported in the environment of the process, same as with
+    /// dotenv, they take precedence over the .env file.
+    ProcessEnvironment,
+    /// Overrides passed using `--set KEY=VALUE`.
+    CommandLine,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::ConfigFile(path) => write!(f, "config file {}", path),
+            ConfigSource::EnvFile(path) => write!(f, "env file {}", path),
+            ConfigSource::ProcessEnvironment => write!(f, "process environment"),
+            ConfigSource::CommandLine => write!(f, "command line"),
+        }
+    }
+}
+
+/// Effective value of a configuration variable.
+#[derive(Debug, Clone)]
+pub struct ResolvedValue {
+    pub key: &'static str,
+    pub value: Option<String>,
+    pub source: ConfigSource,
+}
+
+impl ResolvedValue {
+    /// Returns the value that can be shown to the user, the tokens are
+    /// secrets and so we only show whether they are set.
+    pub fn display_value(&self) -> String {
+        match &self.value {
+            Some(_) if self.key.ends_with("_TOKEN") => "<set>".to_string(),
+            Some(value) => value.clone(),
+            None => String::new(),
+        }
+    }
+}
+
+/// Configuration assembled from all layers: defaults < config.toml < .env
+/// (< process environment) < command line overrides.
+#[derive(Debug, Default)]
+pub struct Configuration {
+    config_file_path: String,
+    config_file: HashMap<String, String>,
+    env_file_path: String,
+    env_file: HashMap<String, String>,
+}
+
+impl Configuration {
+    pub fn load() -> Self {
+        let config_file_path = config_file_path();
+        let config_file = match read_config_file(&config_file_path) {
+            Ok(values) => values,
+            Err(e) => {
+                warn!("Ignoring the config file: {}", e);
+                HashMap::new()
+            }
+        };
+
+        let env_file_path = env_file_path();
+        let env_file = dotenv::from_path_iter(Path::new(&env_file_path))
+            .map(|iter| iter.filter_map(Result::ok).collect())
+            .unwrap_or_default();
+
+        Configuration {
+            config_file_path,
+            config_file,
+            env_file_path,
+            env_file,
+        }
+    }
+
+    /// Resolves the value of the variable, it is taken from the layer with
+    /// the highest precedence that specifies it.
+    pub fn resolve(&self, key: &'static str) -> ResolvedValue {
+        let resolved = |value: &str, source| ResolvedValue {
+            key,
+            value: Some(value.to_string()),
+            source,
+        };
+
+        if let Some(value) = cli_overrides().get(key) {
+            return resolved(value, ConfigSource::CommandLine);
+        }
+
+        // Loading the .env file exports its variables into the process
+        // environment, hence we only attribute the value to the process
+        // environment if it differs from the one in the file.
+        match (env::var(key).ok(), self.env_file.get(key)) {
+            (Some(value), Some(file_value)) if value == *file_value => {
+                return resolved(&value, ConfigSource::EnvFile(self.env_file_path.clone()))
+            }
+            (Some(value), _) => return resolved(&value, ConfigSource::ProcessEnvironment),
+            (None, Some(value)) => {
+                return resolved(value, ConfigSource::EnvFile(self.env_file_path.clone()))
+            }
+            (None, None) => {}
+        }
+
+        if let Some(value) = self.config_file.get(key) {
+            return resolved(
+                value,
+                ConfigSource::ConfigFile(self.config_file_path.clone()),
+            );
+        }
+
+        let default = VARIABLES
+            .iter()
+            .find(|(name, _)| *name == key)
+            .and_then(|(_, default)| *default);
+        ResolvedValue {
+            key,
+            value: default.map(str::to_string),
+            source: ConfigSource::Default,
+        }
+    }
+
+    /// Resolves all variables of the device profile.
+    pub fn resolve_all(&self) -> Vec<ResolvedValue> {
+        VARIABLES.iter().map(|(key, _)| self.resolve(key)).collect()
+    }
+
+    fn value(&self, key: &'static str) -> Option<String> {
+        self.resolve(key).value
+    }
+}
+
+/// Returns the path to the config file shared by all device profiles.
+pub fn config_file_path() -> String {
+    env::var("MIBPF_CONFIG").unwrap_or_else(|_| "config.toml".to_string())
+}
+
+/// Reads the `key = value` entries of the config file, the keys are the same as the
+/// names of the variables in the .env file but can also be written in
+/// lowercase, e.g. `riot_instance_ip = "fe80::1"`.
+fn read_config_file(path: &str) -> Result<HashMap<String, String>, String> {
+    let Ok(contents) = fs::read_to_string(path) else {
+        return Ok(HashMap::new());
+    };
+    let table = contents
+        .parse::<toml::Table>()
+        .map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+
+    // The tables hold other settings, e.g. the experiment presets (see
+    // [`crate::experiment::Preset`]), they aren't variables of the profile.
+    Ok(table
+        .into_iter()
+        .filter(|(_, value)| !value.is_table())
+        .map(|(key, value)| {
+            let value = match value {
+                toml::Value::String(s) => s,
+                other => other.to_string(),
+            };
+            (key.to_uppercase(), value)
+        })
+        .collect())
+}
+
+fn cli_overrides() -> &'static HashMap<String, String> {
+    CLI_OVERRIDES.get_or_init(HashMap::new)
+}
+
+static CLI_OVERRIDES: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// Registers the `--set KEY=VALUE` command line overrides, they take precedence
+/// over all other configuration layers. Needs to be called before the
+/// environment is first loaded.
+pub fn set_cli_overrides(overrides: &[String]) -> Result<(), String> {
+    let overrides = overrides
+        .iter()
+        .map(|o| {
+            let (key, value) = o
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid override {}, expected KEY=VALUE", o))?;
+            Ok((key.to_uppercase(), value.to_string()))
+        })
+        .collect::<Result<HashMap<String, String>, String>>()?;
+
+    CLI_OVERRIDES
+        .set(overrides)
+        .map_err(|_| "The command line overrides were already set".to_string())
+}
+
+/// Returns the path to the .env file describing the device profile that is
+/// currently in use.
+pub fn env_file_path() -> String {
+    env::var("DOTENV").unwrap_or_else(|_| ".env".to_string())
 }
 
 pub fn load_env() -> Environment {
-    let path_str = env::var("DOTENV").unwrap_or_else(|_| ".env".to_string());
-    let path = Path::new(&path_str);
-    let _ = dotenv::from_path(path);
+    let config = Configuration::load();
+    // The variables are still exported so that they are visible to the
+    // subprocesses (e.g. the build scripts of RIOT).
+    let _ = dotenv::from_path(Path::new(&env_file_path()));
 
+    let required = |key| config.value(key).unwrap_or_default();
     Environment {
-        micro_bpf_root_dir: dotenv::var("MIBPF_ROOT_DIR").unwrap_or_else(|_| "..".to_string()),
-        coap_root_dir: dotenv::var("COAP_ROOT_DIR").unwrap_or_else(|_| "../coaproot".to_string()),
-        out_dir: dotenv::var("OUT_DIR").unwrap_or_else(|_| "../out".to_string()),
-        riot_instance_net_if: dotenv::var("RIOT_INSTANCE_NET_IF")
-            .unwrap_or_else(|_| "6".to_string()),
-        riot_instance_ip: dotenv::var("RIOT_INSTANCE_IP")
-            .unwrap_or_else(|_| "fe80::a0d9:ebff:fed5:986b".to_string()),
-        host_net_if: dotenv::var("HOST_NET_IF").unwrap_or_else(|_| "tapbr0".to_string()),
-        host_ip: dotenv::var("HOST_IP").unwrap_or_else(|_| "fe80::cc9a:73ff:fe4a:47f6".to_string()),
-        board_name: dotenv::var("BOARD_NAME").unwrap_or_else(|_| "native".to_string()),
-        src_dir: dotenv::var("SRC_DIR").unwrap_or_else(|_| "../bpf/tests".to_string())
+        micro_bpf_root_dir: required("MIBPF_ROOT_DIR"),
+        coap_root_dir: required("COAP_ROOT_DIR"),
+        out_dir: required("OUT_DIR"),
+        binary_dir: required("BINARY_DIR"),
+        artifact_max_age_hours: config
+            .value("ARTIFACT_MAX_AGE_HOURS")
+            .and_then(|v| v.parse().ok()),
+        riot_instance_net_if: required("RIOT_INSTANCE_NET_IF"),
+        riot_instance_ip: required("RIOT_INSTANCE_IP"),
+        host_net_if: required("HOST_NET_IF"),
+        host_ip: required("HOST_IP"),
+        board_name: required("BOARD_NAME"),
+        src_dir: required("SRC_DIR"),
+        oscore_context_dir: config.value("OSCORE_CONTEXT_DIR"),
+        access_token: config.value("ACCESS_TOKEN"),
+        refresh_token: config.value("REFRESH_TOKEN"),
+        max_concurrent_requests: config
+            .value("MAX_CONCURRENT_REQUESTS")
+            .and_then(|v| v.parse().ok()),
+        requests_per_second: config
+            .value("REQUESTS_PER_SECOND")
+            .and_then(|v| v.parse().ok()),
+        suit_port: config.value("SUIT_PORT").and_then(|v| v.parse().ok()),
+        execution_port: config.value("EXECUTION_PORT").and_then(|v| v.parse().ok()),
+        resource_paths: config.value("RESOURCE_PATHS"),
+        telemetry_sink: config.value("TELEMETRY_SINK"),
+        telemetry_token: config.value("TELEMETRY_TOKEN"),
+        coap_proxy: config.value("COAP_PROXY"),
+        resource_directory: config.value("RESOURCE_DIRECTORY"),
+        identity_dir: config.value("DEVICE_IDENTITY_DIR"),
+        webhook_url: config.value("WEBHOOK_URL"),
+        queue_timeout: config.value("QUEUE_TIMEOUT").and_then(|v| v.parse().ok()),
     }
 }
+
+/// Sets the value of a variable in the .env file of the current device profile.
+/// If the variable is already present, its value gets replaced, otherwise it
+/// is appended at the end of the file.
+pub fn update_env_variable(key: &str, value: &str) -> Result<(), String> {
+    let path = env_file_path();
+    let contents = fs::read_to_string(&path).unwrap_or_default();
+
+    let prefix = format!("{}=", key);
+    let mut found = false;
+    let mut lines = contents
+        .lines()
+        .map(|line| {
+            if line.starts_with(&prefix) {
+                found = true;
+                format!("{}{}", prefix, value)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<String>>();
+
+    if !found {
+        lines.push(format!("{}{}", prefix, value));
+    }
+
+    fs::write(&path, lines.join("\n") + "\n")
+        .map_err(|e| format!("Failed to update the file {}: {}", path, e))
+}
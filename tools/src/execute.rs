@@ -1,13 +1,90 @@
-use std::process::Command;
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
+    path::PathBuf,
+    process::Output,
+};
 
-use enum_iterator::all;
-use log::debug;
+use flate2::{write::DeflateEncoder, Compression};
+use log::{debug, info, warn};
 use micro_bpf_common::{
-    ExecutionModel, HelperAccessListSource, HelperAccessVerification, HelperFunctionID,
+    ArgumentsEncoding, CapabilitiesResponse, ContentCoding, ContentFormat, ExecutionModel,
+    ExecutionResponse, HelperAccessListSource, HelperAccessVerification, VMExecutionRequest,
+    CAPABILITY_ARGUMENT_COMPRESSION, CAPABILITY_HELPER_BITMAP, CAPABILITY_MULTI_ENTRY,
+};
+
+use crate::{
+    capabilities::cached_capabilities,
+    keys::scratch_file,
+    micro_bpf_common::{BinaryFileLayout, TargetVM},
+    queue::{is_busy, wait_in_queue},
+    registry::Registry,
+    request::{build_execution_request, helper_bitmap_is_shorter},
+    transport::{send_request, send_request_accepting, TransportOptions},
 };
 
-use crate::micro_bpf_common::{BinaryFileLayout, TargetVM, VMConfiguration, VMExecutionRequest};
+/// Contents added to the CoAP packet that the programs executed using the
+/// `WithAccessToCoapPacket` model get access to. It allows for testing the
+/// packet-processing programs with crafted inputs.
+#[derive(Debug, Clone, Default)]
+pub struct PacketInjection {
+    /// Bytes placed in the payload of the packet after the execution request.
+    pub payload: Vec<u8>,
+    /// Uri-Query options added to the packet, e.g. `sensor=2`.
+    pub queries: Vec<String>,
+}
+
+/// Buffer passed to the program as its execution arguments, e.g. the weights
+/// of a TinyML model (see [`micro_bpf_common::ArgumentsEncoding`]).
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionArguments {
+    pub data: Vec<u8>,
+    /// Compresses the arguments using deflate if the device is able to
+    /// decompress them, otherwise they are sent as they are.
+    pub compress: bool,
+}
+
+/// Optional settings of an execution request, the defaults execute the
+/// program from its default entry without any additions to the request.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExecuteOptions<'a> {
+    /// Compiles the program with the JIT again before executing it, only
+    /// used together with `jit`.
+    pub jit_compile: bool,
+    /// Sends the request to the benchmark resource of the device.
+    pub benchmark: bool,
+    /// If the firmware was built without the requested VM (or its JIT), the
+    /// execution is retried on the rBPF interpreter and the substitution is
+    /// reported.
+    pub fallback_to_interpreter: bool,
+    /// Contents added to the packet of the `WithAccessToCoapPacket` model.
+    pub packet: Option<&'a PacketInjection>,
+    /// The VM stops the program after executing that many instructions and
+    /// the response reports the number of executed instructions (see
+    /// [`micro_bpf_common::ExecutionResponse`]).
+    pub gas_limit: Option<u64>,
+    /// Passed to the program as the execution arguments, see
+    /// [`crate::keystore`] for provisioning the keys they refer to.
+    pub key_handles: &'a [u32],
+    /// Initialises the random number generator of the execution. It is added
+    /// to the response unless the device reports it already, so that the
+    /// recorded results always carry the seed needed to reproduce them.
+    pub seed: Option<u64>,
+    /// The device records each helper call made by the program and lists
+    /// them in the `trace` field of the response, see
+    /// [`crate::report::format_helper_trace`].
+    pub trace_helpers: bool,
+    /// Sent together with the request, they can't be combined with the
+    /// payload injected into the packet.
+    pub arguments: Option<&'a ExecutionArguments>,
+    /// Selects one of the entry functions exported by the program when it
+    /// was deployed, the execution starts from the default entry if None.
+    pub entry: Option<&'a str>,
+}
 
+/// Requests the execution of the program loaded in the given SUIT storage
+/// slot, see [`ExecuteOptions`] for the optional settings of the request.
 pub async fn execute(
     riot_ipv6_addr: &str,
     target: TargetVM,
@@ -19,74 +96,559 @@ pub async fn execute(
     helper_access_list_source: HelperAccessListSource,
     helper_indices: &[u8],
     jit: bool,
-    jit_compile: bool,
-    benchmark: bool,
+    options: &ExecuteOptions,
+    transport: &TransportOptions,
 ) -> Result<String, String> {
-    // If the user doesn't specify any allowed helper indices, we allow all of them
-    // by default.
-    let helper_indices = if helper_indices.len() == 0 {
-        all::<HelperFunctionID>().collect::<Vec<HelperFunctionID>>()
-    } else {
-        helper_indices
-            .to_vec()
-            .into_iter()
-            .filter_map(|i| num::FromPrimitive::from_u8(i))
-            .collect::<Vec<HelperFunctionID>>()
+    // The capabilities are queried once and shared by the negotiations of
+    // the entry, the arguments and the helper bitmap. Only the ones which
+    // can't do without them fail if the query fails.
+    let capabilities = cached_capabilities(riot_ipv6_addr, host_network_interface, transport);
+    let entry = resolve_entry(
+        options.entry,
+        riot_ipv6_addr,
+        suit_storage_slot,
+        &capabilities,
+        transport,
+    )?;
+    let result = send_execution_request(
+        riot_ipv6_addr,
+        target,
+        binary_layout,
+        suit_storage_slot,
+        host_network_interface,
+        execution_model,
+        helper_access_verification,
+        helper_access_list_source,
+        helper_indices,
+        jit,
+        options.jit_compile,
+        options,
+        entry,
+        &capabilities,
+        transport,
+    );
+
+    let interpreter_requested = target == TargetVM::Rbpf && !jit;
+    let capability_mismatch = match &result {
+        Err(e) => !interpreter_requested && is_capability_mismatch(e),
+        Ok(_) => false,
     };
+    let response = match result {
+        Err(e) if capability_mismatch && options.fallback_to_interpreter => {
+            warn!(
+                "[fallback] {} is not available on the device, executing on the rBPF interpreter instead",
+                requested_vm(target, jit)
+            );
+            debug!("Original error response: {}", e);
+            send_execution_request(
+                riot_ipv6_addr,
+                TargetVM::Rbpf,
+                binary_layout,
+                suit_storage_slot,
+                host_network_interface,
+                execution_model,
+                helper_access_verification,
+                helper_access_list_source,
+                helper_indices,
+                false,
+                false,
+                options,
+                entry,
+                &capabilities,
+                transport,
+            )
+        }
+        Err(e) if capability_mismatch => Err(format!(
+            "{} is not available on the device (the firmware was most likely built without it), \
+             use --fallback-to-interpreter to execute on the rBPF interpreter instead: {}",
+            requested_vm(target, jit),
+            e
+        )),
+        result => result,
+    }?;
+    Ok(with_seed(response, options.seed))
+}
 
-    let request = VMExecutionRequest::new(
-        VMConfiguration::new(
-            target,
-            suit_storage_slot,
-            binary_layout,
-            helper_access_verification,
-            helper_access_list_source,
-            jit,
-            jit_compile,
-        ),
+/// Adds the requested seed to the JSON response if the device didn't report
+/// it, other responses are returned unchanged.
+fn with_seed(response: String, seed: Option<u64>) -> String {
+    let Some(seed) = seed else {
+        return response;
+    };
+    let trimmed = response.trim_matches(char::from(0)).trim();
+    match serde_json::from_str::<serde_json::Value>(trimmed) {
+        Ok(serde_json::Value::Object(mut fields)) if !fields.contains_key("seed") => {
+            fields.insert("seed".to_string(), seed.into());
+            serde_json::Value::Object(fields).to_string()
+        }
+        _ => response,
+    }
+}
+
+fn send_execution_request(
+    riot_ipv6_addr: &str,
+    target: TargetVM,
+    binary_layout: BinaryFileLayout,
+    suit_storage_slot: usize,
+    host_network_interface: &str,
+    execution_model: ExecutionModel,
+    helper_access_verification: HelperAccessVerification,
+    helper_access_list_source: HelperAccessListSource,
+    helper_indices: &[u8],
+    jit: bool,
+    jit_compile: bool,
+    options: &ExecuteOptions,
+    entry: Option<u32>,
+    capabilities: &Result<CapabilitiesResponse, String>,
+    transport: &TransportOptions,
+) -> Result<String, String> {
+    let arguments = match options.arguments {
+        Some(arguments) => Some(encode_arguments(arguments, capabilities, transport)?),
+        None => None,
+    };
+    let request = build_execution_request(
+        target,
+        binary_layout,
+        suit_storage_slot,
+        helper_access_verification,
+        helper_access_list_source,
         helper_indices,
-    );
+        jit,
+        jit_compile,
+    )
+    .with_gas_limit(options.gas_limit)
+    .with_key_handles(options.key_handles.to_vec())
+    .with_seed(options.seed)
+    .with_helper_tracing(options.trace_helpers)
+    .with_arguments(arguments.as_ref().map(|(encoding, _)| *encoding))
+    .with_entry(entry);
+    let helper_bitmap = negotiate_helper_bitmap(&request, capabilities, transport);
+    let request = request.with_helper_bitmap(helper_bitmap);
 
     debug!("Helper encoding: {:?}", request.allowed_helpers);
 
     let mut base_url = format!("coap://[{}%{}]", riot_ipv6_addr, host_network_interface);
 
-    if benchmark {
+    if options.benchmark {
         base_url.push_str("/benchmark");
     }
 
-
     let url = match execution_model {
         ExecutionModel::ShortLived => format!("{}/short-execution", base_url),
         ExecutionModel::WithAccessToCoapPacket => format!("{}/with_coap_pkt", base_url),
         ExecutionModel::LongRunning => format!("{}/long-running", base_url),
     };
 
+    let payload = request.encode();
+    // The file with the injected payload or the arguments is removed once it
+    // goes out of scope, i.e. after the request (and any retries) has been
+    // sent.
+    let (url, payload_file) = match (options.packet, arguments) {
+        (Some(packet), Some(_)) if !packet.payload.is_empty() => {
+            return Err(
+                "The execution arguments can't be combined with the injected packet payload"
+                    .to_string(),
+            )
+        }
+        (Some(packet), arguments) => {
+            let (url, payload_file) = inject(url, &payload, execution_model, packet)?;
+            match arguments {
+                Some((_, bytes)) => (url, Some(attach_arguments(&payload, &bytes)?)),
+                None => (url, payload_file),
+            }
+        }
+        (None, Some((_, bytes))) => (url, Some(attach_arguments(&payload, &bytes)?)),
+        (None, None) => (url, None),
+    };
+    let payload = match &payload_file {
+        Some(file) => file.argument(),
+        None => payload,
+    };
+
     debug!("Sending a request to the url: {}", url);
 
-    let payload = request.encode();
+    let result = post_request(&url, &payload, transport);
+    match (result, transport.queue_timeout) {
+        (Err(e), Some(timeout)) if is_busy(&e) && !transport.dry_run => {
+            debug!("The device is busy: {}", e);
+            wait_in_queue(
+                riot_ipv6_addr,
+                suit_storage_slot,
+                timeout,
+                transport,
+                || post_request(&url, &payload, transport),
+            )
+        }
+        (result, _) => result,
+    }
+}
 
-    // We use the aiocoap-client here as opposed to the rust coap library because
-    // that one didn't support overriding the network interface in the ipv6 urls
-    let Ok(output) = Command::new("aiocoap-client")
-        .arg("-m")
-        .arg("POST")
-        .arg(url.clone())
-        .arg("--payload")
-        .arg(&payload)
-        .output()
-    else {
-        return Err(format!("Failed to send request payload: {}", payload));
-    };
+/// Sends the encoded execution request and decodes the response.
+fn post_request(url: &str, payload: &str, transport: &TransportOptions) -> Result<String, String> {
+    if transport.accept.is_empty() {
+        let output = send_request("POST", url, Some(payload), transport)?;
+        return decode_response(output, ContentFormat::Json);
+    }
 
+    // Older firmware versions respond with 4.06 Not Acceptable to the
+    // formats they don't support, in which case the next one is tried.
+    let mut last_error = String::new();
+    for &format in &transport.accept {
+        let output = send_request_accepting("POST", url, Some(payload), Some(format), transport)?;
+        match decode_response(output, format) {
+            Err(e) if e.contains(NOT_ACCEPTABLE) => {
+                debug!("The device doesn't support the {:?} responses", format);
+                last_error = e;
+            }
+            result => return result,
+        }
+    }
+    Err(last_error)
+}
+
+/// Response code returned by the device if it can't respond using any of
+/// the accepted Content-Formats.
+const NOT_ACCEPTABLE: &str = "4.06";
+
+/// Prefix of the errors carrying the error response of the device.
+const CLIENT_FAILURE: &str = "aiocoap-client failed with:";
+
+/// Parses the response using the Content-Format that was requested and
+/// returns it as JSON with the format recorded in it, so that the callers
+/// don't depend on the wire format. The devices which ignore the Accept
+/// option respond using JSON, so JSON objects are always accepted.
+fn decode_response(output: Output, format: ContentFormat) -> Result<String, String> {
     if output.stderr.len() > 0 {
         let stderr = String::from_utf8(output.stderr)
             .map_err(|e| format!("Failed to parse the stderr: {}", e))?;
-        Err(format!("aiocoap-client failed with: {}", stderr))?
+        Err(format!("{} {}", CLIENT_FAILURE, stderr))?
+    }
+
+    if format == ContentFormat::Json || output.stdout.is_empty() {
+        let response = String::from_utf8(output.stdout)
+            .map_err(|e| format!("Failed to parse the response: {}", e))?;
+        return Ok(response);
+    }
+    if let Ok(response) = std::str::from_utf8(&output.stdout) {
+        let response = response.trim_matches(char::from(0));
+        if serde_json::from_str::<ExecutionResponse>(response).is_ok() {
+            return Ok(response.to_string());
+        }
+    }
+
+    let response = match format {
+        ContentFormat::Cbor => {
+            let mut response =
+                ciborium::from_reader::<ExecutionResponse, _>(output.stdout.as_slice())
+                    .map_err(|e| format!("Failed to parse the CBOR response: {}", e))?;
+            response.content_format = ContentFormat::Cbor;
+            response
+        }
+        ContentFormat::OctetStream => ExecutionResponse::decode_octet_stream(&output.stdout)?,
+        ContentFormat::Json => unreachable!(),
+    };
+    serde_json::to_string(&response).map_err(|e| format!("Failed to encode the response: {}", e))
+}
+
+/// Adds the injected contents to the execution request. The injected payload
+/// follows the encoded request, separated by a newline. The firmware decodes
+/// the request up to the first newline and leaves the packet untouched, so
+/// the program sees the injected bytes at the offset of the separator + 1.
+/// Because the payload can contain arbitrary bytes, it is written into a
+/// [`PayloadFile`] which aiocoap-client reads the payload from. Returns the
+/// updated url and the file if any payload was injected.
+fn inject(
+    url: String,
+    request: &str,
+    execution_model: ExecutionModel,
+    packet: &PacketInjection,
+) -> Result<(String, Option<PayloadFile>), String> {
+    if execution_model != ExecutionModel::WithAccessToCoapPacket {
+        return Err(format!(
+            "Only the programs executed with the WithAccessToCoapPacket model \
+             have access to the packet, the {:?} model was requested",
+            execution_model
+        ));
+    }
+
+    let url = if packet.queries.is_empty() {
+        url
+    } else {
+        format!("{}?{}", url, packet.queries.join("&"))
+    };
+
+    if packet.payload.is_empty() {
+        return Ok((url, None));
+    }
+
+    let payload_file = PayloadFile::write(request, &packet.payload)
+        .map_err(|e| format!("Failed to write the packet payload: {}", e))?;
+    debug!(
+        "Injecting {} bytes into the packet payload",
+        packet.payload.len()
+    );
+    Ok((url, Some(payload_file)))
+}
+
+/// Payload of a single request written into a file unique to the request,
+/// so that the concurrent executions don't overwrite each other's payloads.
+/// Only the user can read the file as the arguments may contain e.g. key
+/// handles, and it is removed when it is dropped.
+struct PayloadFile {
+    path: PathBuf,
+}
+
+impl PayloadFile {
+    /// Writes the encoded request followed by a newline and the appended
+    /// bytes (the injected packet payload or the execution arguments).
+    fn write(request: &str, appended: &[u8]) -> Result<Self, String> {
+        let path = scratch_file("bin");
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+            .map_err(|e| e.to_string())?;
+        let payload_file = PayloadFile { path };
+        file.write_all(request.as_bytes())
+            .and_then(|_| file.write_all(b"\n"))
+            .and_then(|_| file.write_all(appended))
+            .map_err(|e| e.to_string())?;
+        Ok(payload_file)
+    }
+
+    /// Argument instructing aiocoap-client to read the payload from the file.
+    fn argument(&self) -> String {
+        format!("@{}", self.path.display())
+    }
+}
+
+impl Drop for PayloadFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Looks up the offset of the entry function in the program deployed into
+/// the slot, the exported entry functions are recorded in the registry when
+/// the program is deployed. Selecting the entry requires the device to report
+/// [`CAPABILITY_MULTI_ENTRY`].
+fn resolve_entry(
+    entry: Option<&str>,
+    riot_ipv6_addr: &str,
+    suit_storage_slot: usize,
+    capabilities: &Result<CapabilitiesResponse, String>,
+    transport: &TransportOptions,
+) -> Result<Option<u32>, String> {
+    let Some(entry) = entry else {
+        return Ok(None);
+    };
+    let registry = Registry::load()?;
+    let entries = registry
+        .device(riot_ipv6_addr)
+        .and_then(|device| device.slots.get(&suit_storage_slot))
+        .map(|record| &record.entries);
+    let Some(offset) = entries.and_then(|entries| entries.get(entry)) else {
+        let exported = entries
+            .map(|entries| entries.keys().cloned().collect::<Vec<String>>())
+            .unwrap_or_default();
+        return Err(format!(
+            "The program in slot {} of {} doesn't export the entry function {} (exported: [{}]), \
+             deploy it with --entry {}",
+            suit_storage_slot,
+            riot_ipv6_addr,
+            entry,
+            exported.join(", "),
+            entry
+        ));
+    };
+
+    let capabilities = capabilities.clone()?;
+    if transport.dry_run {
+        info!("[dry-run] assuming that the device supports multiple entry functions");
+    } else if !capabilities.supports(CAPABILITY_MULTI_ENTRY) {
+        return Err(format!(
+            "The device {} doesn't support selecting the entry function",
+            riot_ipv6_addr
+        ));
+    }
+    debug!("Executing from the entry function {} at {}", entry, offset);
+    Ok(Some(*offset))
+}
+
+/// Checks whether the allowed helpers should be sent as a bitmap, which is
+/// the case if it is shorter than the list of their IDs (see
+/// [`helper_bitmap_is_shorter`]) and the device advertises
+/// [`CAPABILITY_HELPER_BITMAP`]. If the capabilities couldn't be queried, the
+/// list is sent as every device is able to decode it.
+fn negotiate_helper_bitmap(
+    request: &VMExecutionRequest,
+    capabilities: &Result<CapabilitiesResponse, String>,
+    transport: &TransportOptions,
+) -> bool {
+    if !helper_bitmap_is_shorter(request) {
+        return false;
+    }
+
+    let capabilities = match capabilities {
+        Ok(capabilities) => capabilities,
+        Err(e) => {
+            debug!(
+                "Failed to query the capabilities, sending the list of helpers: {}",
+                e
+            );
+            return false;
+        }
+    };
+    if transport.dry_run {
+        info!("[dry-run] assuming that the device decodes the helper bitmap");
+        return true;
+    }
+    if !capabilities.supports(CAPABILITY_HELPER_BITMAP) {
+        debug!("The device doesn't decode the helper bitmap, sending the list of helpers");
+        return false;
+    }
+    true
+}
+
+/// Encodes the arguments as a CBOR byte string and compresses them if it was
+/// requested and the device advertises [`CAPABILITY_ARGUMENT_COMPRESSION`].
+/// The arguments which don't get any smaller are sent uncompressed.
+fn encode_arguments(
+    arguments: &ExecutionArguments,
+    capabilities: &Result<CapabilitiesResponse, String>,
+    transport: &TransportOptions,
+) -> Result<(ArgumentsEncoding, Vec<u8>), String> {
+    let mut encoded = vec![];
+    ciborium::into_writer(
+        &ciborium::value::Value::Bytes(arguments.data.clone()),
+        &mut encoded,
+    )
+    .map_err(|e| format!("Failed to encode the execution arguments: {}", e))?;
+    let identity = ArgumentsEncoding {
+        coding: ContentCoding::Identity,
+        length: encoded.len() as u32,
+    };
+    if !arguments.compress {
+        return Ok((identity, encoded));
     }
 
-    let response = String::from_utf8(output.stdout)
-        .map_err(|e| format!("Failed to parse the response: {}", e))?;
+    let capabilities = capabilities.clone()?;
+    if transport.dry_run {
+        info!("[dry-run] assuming that the device supports compressed arguments");
+    } else if !capabilities.supports(CAPABILITY_ARGUMENT_COMPRESSION) {
+        warn!("The device doesn't support compressed arguments, sending them uncompressed");
+        return Ok((identity, encoded));
+    }
 
-    Ok(response)
+    let mut encoder = DeflateEncoder::new(vec![], Compression::best());
+    let compressed = encoder
+        .write_all(&encoded)
+        .and_then(|_| encoder.finish())
+        .map_err(|e| format!("Failed to compress the execution arguments: {}", e))?;
+    if compressed.len() >= encoded.len() {
+        info!("Compressing the arguments wouldn't reduce their size, sending them uncompressed");
+        return Ok((identity, encoded));
+    }
+    info!(
+        "Compressed the arguments: {} -> {} bytes",
+        encoded.len(),
+        compressed.len()
+    );
+    Ok((
+        ArgumentsEncoding {
+            coding: ContentCoding::Deflate,
+            ..identity
+        },
+        compressed,
+    ))
+}
+
+/// Appends the encoded arguments to the request, separated by a newline.
+/// Same as the injected packet payload, the arguments can contain arbitrary
+/// bytes, so they are written into a [`PayloadFile`] which aiocoap-client
+/// reads the payload from.
+fn attach_arguments(request: &str, arguments: &[u8]) -> Result<PayloadFile, String> {
+    let payload_file = PayloadFile::write(request, arguments)
+        .map_err(|e| format!("Failed to write the execution arguments: {}", e))?;
+    debug!("Attaching {} bytes of execution arguments", arguments.len());
+    Ok(payload_file)
+}
+
+/// Checks whether the error response of the device indicates that the requested
+/// VM isn't available in the firmware. Depending on the version, the device
+/// responds with 5.01 Not Implemented, or with 4.04 / 5.00 and a message
+/// naming the missing VM. A 4.04 without the message is also the response to
+/// an empty slot or a wrong resource path, so it isn't treated as a mismatch.
+fn is_capability_mismatch(error: &str) -> bool {
+    let Some((code, message)) = response_code(error) else {
+        return false;
+    };
+    let message = message.to_lowercase();
+    let vm_unavailable = message.contains("unsupported") || message.contains("not available");
+    code == "5.01" || (matches!(code, "4.04" | "5.00") && vm_unavailable)
+}
+
+/// Splits the error response reported by aiocoap-client into the response
+/// code (e.g. `4.04`) and the rest of the message.
+fn response_code(error: &str) -> Option<(&str, &str)> {
+    let (_, response) = error.rsplit_once(CLIENT_FAILURE)?;
+    let response = response.trim_start();
+    let (code, message) = response
+        .split_once(char::is_whitespace)
+        .unwrap_or((response, ""));
+    let is_code = code.len() == 4
+        && code.as_bytes()[1] == b'.'
+        && code
+            .chars()
+            .enumerate()
+            .all(|(i, c)| i == 1 || c.is_ascii_digit());
+    is_code.then_some((code, message))
+}
+
+fn requested_vm(target: TargetVM, jit: bool) -> String {
+    if jit {
+        format!("The JIT of the {} VM", target)
+    } else {
+        format!("The {} VM", target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure(response: &str) -> String {
+        format!("{} {}", CLIENT_FAILURE, response)
+    }
+
+    #[test]
+    fn missing_vm_is_a_capability_mismatch() {
+        assert!(is_capability_mismatch(&failure("5.01 Not Implemented")));
+        assert!(is_capability_mismatch(&failure(
+            "5.00 Internal Server Error\nThe FemtoContainer VM is not available"
+        )));
+        assert!(is_capability_mismatch(&failure(
+            "4.04 Not Found\nunsupported target VM"
+        )));
+    }
+
+    #[test]
+    fn other_errors_are_not_a_capability_mismatch() {
+        // An empty slot or a wrong resource path.
+        assert!(!is_capability_mismatch(&failure("4.04 Not Found")));
+        assert!(!is_capability_mismatch(&failure(
+            "5.00 Internal Server Error\nFailed to verify the program"
+        )));
+        assert!(!is_capability_mismatch(&failure(
+            "4.00 Bad Request\nunsupported helper"
+        )));
+        // The code needs to be the response of the device.
+        assert!(!is_capability_mismatch(
+            "Failed to send the request to coap://[fe80::1%tap0]/4.04: 5.01"
+        ));
+        assert!(!is_capability_mismatch(&failure(
+            "Network unreachable (4.04 not available)"
+        )));
+    }
 }
@@ -0,0 +1,82 @@
+use serde_json::json;
+
+use crate::{compile::COMPILATION_FAILED, transport::is_cancelled};
+
+/// Error reported when the result of the program doesn't match the value
+/// expected by the caller (see `execute --expect-result`).
+pub const ASSERTION_MISMATCH: &str = "Assertion mismatch";
+
+/// Class of the failure of the CLI. Each one has a distinct exit code so that
+/// the CI pipelines can branch on it without parsing stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureClass {
+    /// Invalid arguments, missing configuration and other failures which
+    /// don't fit into any of the classes below.
+    Other,
+    Compilation,
+    Deployment,
+    Execution,
+    AssertionMismatch,
+    /// The operation was interrupted using Ctrl-C.
+    Cancelled,
+}
+
+impl FailureClass {
+    /// Classifies the error returned by a command. The errors are plain
+    /// strings, so the failures which can occur in the middle of other
+    /// commands (e.g. compilation as part of deploy) are recognised by their
+    /// messages, the rest is attributed to the class of the command itself.
+    pub fn classify(error: &str, command_class: FailureClass) -> Self {
+        if is_cancelled(error) {
+            return FailureClass::Cancelled;
+        }
+        if error.contains(ASSERTION_MISMATCH) {
+            return FailureClass::AssertionMismatch;
+        }
+        if error.contains(COMPILATION_FAILED) {
+            return FailureClass::Compilation;
+        }
+        command_class
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            FailureClass::Other => 1,
+            FailureClass::Compilation => 2,
+            FailureClass::Deployment => 3,
+            FailureClass::Execution => 4,
+            FailureClass::AssertionMismatch => 5,
+            // Same exit code as if the tool was terminated by SIGINT.
+            FailureClass::Cancelled => 130,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            FailureClass::Other => "other",
+            FailureClass::Compilation => "compilation",
+            FailureClass::Deployment => "deployment",
+            FailureClass::Execution => "execution",
+            FailureClass::AssertionMismatch => "assertion-mismatch",
+            FailureClass::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// Prints the error and terminates the process with the exit code of its
+/// class. In the quiet mode the error is printed as a single line of JSON:
+/// `{"class": "execution", "exit_code": 4, "error": "..."}`
+pub fn exit_with_error(error: &str, command_class: FailureClass, quiet: bool) -> ! {
+    let class = FailureClass::classify(error, command_class);
+    if quiet {
+        let line = json!({
+            "class": class.name(),
+            "exit_code": class.exit_code(),
+            "error": error,
+        });
+        eprintln!("{}", line);
+    } else {
+        eprintln!("Error: {}", error);
+    }
+    std::process::exit(class.exit_code());
+}
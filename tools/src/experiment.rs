@@ -0,0 +1,493 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+};
+
+use log::{debug, info};
+use micro_bpf_common::{
+    BinaryFileLayout, HelperAccessListSource, HelperAccessVerification, StorageBackend, TargetVM,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    artifacts::ArtifactStore,
+    bench::{bench, BenchSample},
+    deploy::{deploy, DeployOptions},
+    environment::{config_file_path, Environment},
+    publish::LocalPublisher,
+    registry::now,
+    slots::SuitSlot,
+    transport::TransportOptions,
+};
+
+/// Description of an evaluation run, loaded from a YAML file:
+///
+/// ```yaml
+/// name: layouts
+/// repetitions: 50
+/// programs: [tests/test-sources/fletcher16.c]
+/// layouts: [OnlyTextSection, ExtendedHeader]
+/// vms:
+///   - target: rBPF
+///   - target: rBPF
+///     jit: true
+///   - target: FemtoContainers
+/// devices:
+///   - riot_ipv6_addr: fe80::a0d9:ebff:fed5:986b
+///     host_network_interface: tapbr0
+/// start_fileserver: true
+/// ```
+///
+/// Every program is benchmarked in every combination of the layouts, VMs and
+/// devices. If no devices are listed, the device of the current profile is
+/// used.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExperimentSpec {
+    pub name: String,
+    /// Number of executions of each combination.
+    pub repetitions: usize,
+    /// Source files of the benchmarked programs.
+    pub programs: Vec<String>,
+    pub layouts: Vec<String>,
+    pub vms: Vec<VmSpec>,
+    #[serde(default)]
+    pub devices: Vec<DeviceSpec>,
+    /// SUIT storage slot that the programs are deployed into.
+    #[serde(default)]
+    pub slot: usize,
+    /// Helpers that the programs are allowed to call, all if empty.
+    #[serde(default)]
+    pub helpers: Vec<u8>,
+    /// Serve the CoAP root directory using aiocoap-fileserver for the duration
+    /// of the run instead of relying on one started in another terminal.
+    #[serde(default)]
+    pub start_fileserver: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VmSpec {
+    /// Target VM, e.g. rBPF or FemtoContainers.
+    pub target: String,
+    #[serde(default)]
+    pub jit: bool,
+}
+
+impl fmt::Display for VmSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.jit {
+            write!(f, "{} (JIT)", self.target)
+        } else {
+            write!(f, "{}", self.target)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceSpec {
+    pub riot_ipv6_addr: String,
+    pub host_network_interface: String,
+    /// Network interface of the RIOT instance, defaults to the one of the
+    /// current profile.
+    #[serde(default)]
+    pub riot_network_interface: Option<String>,
+}
+
+impl ExperimentSpec {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read the experiment spec {}: {}", path, e))?;
+        let spec = serde_yaml::from_str::<ExperimentSpec>(&contents)
+            .map_err(|e| format!("Failed to parse the experiment spec {}: {}", path, e))?;
+        if spec.repetitions == 0 {
+            return Err("The experiment needs at least one repetition".to_string());
+        }
+        Ok(spec)
+    }
+}
+
+/// Experiment settings stored in the config file under a name so that the
+/// same comparison can be repeated for any program without spelling out the
+/// spec, e.g.:
+///
+/// ```toml
+/// [preset.jit-comparison]
+/// layouts = ["ExtendedHeader"]
+/// vms = [{ target = "rBPF" }, { target = "rBPF", jit = true }]
+/// helpers = [0, 1, 2]
+/// repetitions = 20
+/// devices = [{ riot_ipv6_addr = "fe80::1", host_network_interface = "tapbr0" }]
+/// ```
+///
+/// The omitted settings fall back to the defaults of the experiment spec.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preset {
+    #[serde(default = "default_repetitions")]
+    pub repetitions: usize,
+    #[serde(default = "default_layouts")]
+    pub layouts: Vec<String>,
+    #[serde(default = "default_vms")]
+    pub vms: Vec<VmSpec>,
+    #[serde(default)]
+    pub devices: Vec<DeviceSpec>,
+    #[serde(default)]
+    pub slot: usize,
+    #[serde(default)]
+    pub helpers: Vec<u8>,
+    #[serde(default)]
+    pub start_fileserver: bool,
+}
+
+fn default_repetitions() -> usize {
+    10
+}
+
+fn default_layouts() -> Vec<String> {
+    vec!["ExtendedHeader".to_string()]
+}
+
+fn default_vms() -> Vec<VmSpec> {
+    vec![VmSpec {
+        target: "rBPF".to_string(),
+        jit: false,
+    }]
+}
+
+impl Preset {
+    /// Loads the preset with the given name from the `[preset.<name>]`
+    /// section of the config file.
+    pub fn load(name: &str) -> Result<Self, String> {
+        let path = config_file_path();
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read the config file {}: {}", path, e))?;
+        let table = contents
+            .parse::<toml::Table>()
+            .map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+
+        let Some(preset) = table.get("preset").and_then(|p| p.get(name)) else {
+            return Err(format!("No preset named {} in {}", name, path));
+        };
+        let preset = preset
+            .clone()
+            .try_into::<Preset>()
+            .map_err(|e| format!("Invalid preset {}: {}", name, e))?;
+        if preset.repetitions == 0 {
+            return Err("The experiment needs at least one repetition".to_string());
+        }
+        Ok(preset)
+    }
+
+    /// Creates the experiment benchmarking the programs using the settings
+    /// of the preset.
+    pub fn into_spec(self, name: &str, programs: &[String]) -> ExperimentSpec {
+        ExperimentSpec {
+            name: name.to_string(),
+            repetitions: self.repetitions,
+            programs: programs.to_vec(),
+            layouts: self.layouts,
+            vms: self.vms,
+            devices: self.devices,
+            slot: self.slot,
+            helpers: self.helpers,
+            start_fileserver: self.start_fileserver,
+        }
+    }
+}
+
+/// Aggregated results of a single combination of the experiment.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExperimentRow {
+    pub device: String,
+    pub program: String,
+    pub layout: String,
+    pub vm: String,
+    pub samples: usize,
+    pub mean_execution_time: f64,
+    pub stddev_execution_time: f64,
+    pub min_execution_time: u32,
+    pub max_execution_time: u32,
+    pub mean_total_time: f64,
+    pub program_size: u32,
+}
+
+impl ExperimentRow {
+    fn new(
+        device: &str,
+        program: &str,
+        layout: &str,
+        vm: &VmSpec,
+        samples: &[BenchSample],
+    ) -> Self {
+        let execution_times = samples
+            .iter()
+            .map(|s| s.times.execution_time)
+            .collect::<Vec<u32>>();
+        let count = samples.len().max(1) as f64;
+        let mean = execution_times.iter().map(|t| *t as f64).sum::<f64>() / count;
+        let variance = execution_times
+            .iter()
+            .map(|t| (*t as f64 - mean).powi(2))
+            .sum::<f64>()
+            / count;
+
+        ExperimentRow {
+            device: device.to_string(),
+            program: program.to_string(),
+            layout: layout.to_string(),
+            vm: vm.to_string(),
+            samples: samples.len(),
+            mean_execution_time: mean,
+            stddev_execution_time: variance.sqrt(),
+            min_execution_time: execution_times.iter().copied().min().unwrap_or(0),
+            max_execution_time: execution_times.iter().copied().max().unwrap_or(0),
+            mean_total_time: samples
+                .iter()
+                .map(|s| s.times.total_time as f64)
+                .sum::<f64>()
+                / count,
+            program_size: samples.first().map(|s| s.times.program_size).unwrap_or(0),
+        }
+    }
+}
+
+/// Results of the whole experiment, one row per combination.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExperimentReport {
+    pub name: String,
+    pub rows: Vec<ExperimentRow>,
+    /// Directory containing the raw data and the aggregate tables.
+    pub output_dir: String,
+}
+
+/// Stops the fileserver started for the experiment once the run finishes.
+struct Fileserver(Child);
+
+impl Drop for Fileserver {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Runs the experiment: each program is deployed in every layout and then
+/// benchmarked on every VM and device. The raw samples are written into
+/// `<output_dir>/<name>-<timestamp>/raw.csv` as they are collected so that an
+/// interrupted run doesn't lose data, the aggregate tables are written into
+/// `summary.csv` and `summary.md` next to them, together with a copy of the
+/// spec.
+pub async fn run_experiment(
+    spec: &ExperimentSpec,
+    environment: &Environment,
+    output_dir: &str,
+    transport: &TransportOptions,
+) -> Result<ExperimentReport, String> {
+    let run_dir = PathBuf::from(output_dir).join(format!("{}-{}", spec.name, now()));
+    fs::create_dir_all(&run_dir)
+        .map_err(|e| format!("Failed to create {}: {}", run_dir.display(), e))?;
+    write_file(
+        &run_dir.join("spec.yaml"),
+        &serde_yaml::to_string(spec).map_err(|e| e.to_string())?,
+    )?;
+
+    let _fileserver = if spec.start_fileserver {
+        Some(start_fileserver(&environment.coap_root_dir)?)
+    } else {
+        None
+    };
+
+    let devices = if spec.devices.is_empty() {
+        vec![DeviceSpec {
+            riot_ipv6_addr: environment.riot_instance_ip.clone(),
+            host_network_interface: environment.host_net_if.clone(),
+            riot_network_interface: None,
+        }]
+    } else {
+        spec.devices.clone()
+    };
+    let layouts = spec
+        .layouts
+        .iter()
+        .map(|l| l.parse::<BinaryFileLayout>())
+        .collect::<Result<Vec<BinaryFileLayout>, String>>()?;
+    let targets = spec
+        .vms
+        .iter()
+        .map(|vm| vm.target.parse::<TargetVM>())
+        .collect::<Result<Vec<TargetVM>, String>>()?;
+
+    let mut raw = String::from(
+        "device,program,layout,vm,iteration,total,load,verification,execution,program_size,result,round_trip_us\n",
+    );
+    let raw_path = run_dir.join("raw.csv");
+    let artifacts = ArtifactStore::from_env(environment);
+    let publisher = LocalPublisher::new(&environment.coap_root_dir);
+    let mut rows = vec![];
+
+    for device in &devices {
+        let riot_net_if = device
+            .riot_network_interface
+            .as_deref()
+            .unwrap_or(&environment.riot_instance_net_if);
+        for program in &spec.programs {
+            for (layout, layout_name) in layouts.iter().zip(&spec.layouts) {
+                for (vm, target) in spec.vms.iter().zip(&targets) {
+                    info!(
+                        "[{}] {} / {} / {} on {}",
+                        spec.name, program, layout_name, vm, device.riot_ipv6_addr
+                    );
+                    deploy(
+                        program,
+                        &artifacts,
+                        *target,
+                        *layout,
+                        SuitSlot::Fixed(spec.slot),
+                        StorageBackend::Ram,
+                        riot_net_if,
+                        &device.riot_ipv6_addr,
+                        &device.host_network_interface,
+                        &environment.host_ip,
+                        &environment.board_name,
+                        Some(&environment.micro_bpf_root_dir),
+                        spec.helpers.clone(),
+                        HelperAccessVerification::AheadOfTime,
+                        HelperAccessListSource::ExecuteRequest,
+                        &DeployOptions {
+                            erase: true,
+                            ..Default::default()
+                        },
+                        &publisher,
+                        transport,
+                    )
+                    .await?;
+
+                    let report = bench(
+                        &device.riot_ipv6_addr,
+                        &device.host_network_interface,
+                        *target,
+                        *layout,
+                        spec.slot,
+                        &spec.helpers,
+                        vm.jit,
+                        spec.repetitions,
+                        None,
+                        Default::default(),
+                        None,
+                        transport,
+                    )
+                    .await?;
+
+                    for (i, sample) in report.samples.iter().enumerate() {
+                        raw.push_str(&format!(
+                            "{},{},{},{},{},{},{},{},{},{},{},{}\n",
+                            device.riot_ipv6_addr,
+                            program,
+                            layout_name,
+                            vm,
+                            i,
+                            sample.times.total_time,
+                            sample.times.load_time,
+                            sample.times.verification_time,
+                            sample.times.execution_time,
+                            sample.times.program_size,
+                            sample.times.result,
+                            sample.round_trip.as_micros()
+                        ));
+                    }
+                    write_file(&raw_path, &raw)?;
+                    rows.push(ExperimentRow::new(
+                        &device.riot_ipv6_addr,
+                        program,
+                        layout_name,
+                        vm,
+                        &report.samples,
+                    ));
+                }
+            }
+        }
+    }
+
+    let report = ExperimentReport {
+        name: spec.name.clone(),
+        rows,
+        output_dir: run_dir.display().to_string(),
+    };
+    write_file(&run_dir.join("summary.csv"), &report.to_csv())?;
+    write_file(&run_dir.join("summary.md"), &report.to_string())?;
+    Ok(report)
+}
+
+impl ExperimentReport {
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "device,program,layout,vm,samples,mean_execution_time,stddev_execution_time,\
+             min_execution_time,max_execution_time,mean_total_time,program_size\n",
+        );
+        for row in &self.rows {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{:.2},{:.2},{},{},{:.2},{}\n",
+                row.device,
+                row.program,
+                row.layout,
+                row.vm,
+                row.samples,
+                row.mean_execution_time,
+                row.stddev_execution_time,
+                row.min_execution_time,
+                row.max_execution_time,
+                row.mean_total_time,
+                row.program_size
+            ));
+        }
+        csv
+    }
+}
+
+/// Renders the aggregate table in markdown so that it can be pasted directly
+/// into the write-up.
+impl fmt::Display for ExperimentReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "## {}\n", self.name)?;
+        writeln!(
+            f,
+            "| Device | Program | Layout | VM | Samples | Execution time | Min | Max | Total time | Size |"
+        )?;
+        writeln!(f, "|---|---|---|---|---:|---:|---:|---:|---:|---:|")?;
+        for row in &self.rows {
+            let program = Path::new(&row.program)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| row.program.clone());
+            writeln!(
+                f,
+                "| {} | {} | {} | {} | {} | {:.2} ± {:.2} | {} | {} | {:.2} | {} |",
+                row.device,
+                program,
+                row.layout,
+                row.vm,
+                row.samples,
+                row.mean_execution_time,
+                row.stddev_execution_time,
+                row.min_execution_time,
+                row.max_execution_time,
+                row.mean_total_time,
+                row.program_size
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn start_fileserver(coap_root_dir: &str) -> Result<Fileserver, String> {
+    debug!("Starting aiocoap-fileserver for {}", coap_root_dir);
+    Command::new("aiocoap-fileserver")
+        .arg(coap_root_dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(Fileserver)
+        .map_err(|e| format!("Failed to start aiocoap-fileserver: {}", e))
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<(), String> {
+    fs::write(path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
@@ -0,0 +1,224 @@
+use std::{collections::BTreeMap, fs, path::Path, str::FromStr};
+
+use log::{debug, warn};
+use micro_bpf_common::{BinaryFileLayout, TargetVM};
+
+/// Build configuration files of the firmware, in the order of precedence: the
+/// Kconfig defaults are overridden by the Makefile, which is overridden by the
+/// application configuration.
+const CONFIG_FILES: [&str; 3] = ["Kconfig", "Makefile", "app.config"];
+
+/// Directories of the mibpf repo containing the build configuration.
+const CONFIG_DIRS: [&str; 2] = ["", "src"];
+
+/// Kconfig symbol (without the `CONFIG_` prefix) enabling the
+/// Femto-Containers VM.
+const FEMTO_CONTAINERS_SYMBOL: &str = "MIBPF_VM_FEMTO_CONTAINERS";
+
+/// Kconfig symbols enabling the VMs.
+const VM_SYMBOLS: [(&str, TargetVM); 2] = [
+    ("MIBPF_VM_RBPF", TargetVM::Rbpf),
+    (FEMTO_CONTAINERS_SYMBOL, TargetVM::FemtoContainer),
+];
+
+/// Kconfig symbols enabling the support for the binary layouts.
+const LAYOUT_SYMBOLS: [(&str, BinaryFileLayout); 4] = [
+    (
+        "MIBPF_LAYOUT_ONLY_TEXT_SECTION",
+        BinaryFileLayout::OnlyTextSection,
+    ),
+    (
+        "MIBPF_LAYOUT_FEMTO_CONTAINERS_HEADER",
+        BinaryFileLayout::FemtoContainersHeader,
+    ),
+    (
+        "MIBPF_LAYOUT_EXTENDED_HEADER",
+        BinaryFileLayout::ExtendedHeader,
+    ),
+    (
+        "MIBPF_LAYOUT_RAW_OBJECT_FILE",
+        BinaryFileLayout::RawObjectFile,
+    ),
+];
+
+/// RIOT module of the Femto-Containers VM, pulling it in with `USEMODULE`
+/// enables the VM same as its Kconfig symbol.
+const FEMTO_CONTAINERS_MODULE: &str = "femtocontainer";
+
+/// VMs and binary layouts that the firmware was compiled with, read from its
+/// build configuration in the mibpf repo. `None` means that the configuration
+/// doesn't mention any of them, in which case nothing is assumed about the
+/// firmware.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FirmwareBuild {
+    pub vms: Option<Vec<TargetVM>>,
+    pub layouts: Option<Vec<BinaryFileLayout>>,
+}
+
+impl FirmwareBuild {
+    /// Reads the build configuration of the firmware, the missing files are
+    /// skipped.
+    pub fn detect(mibpf_root_dir: &str) -> Self {
+        let mut flags = BTreeMap::new();
+        for file in CONFIG_FILES {
+            for dir in CONFIG_DIRS {
+                let path = Path::new(mibpf_root_dir).join(dir).join(file);
+                let Ok(contents) = fs::read_to_string(&path) else {
+                    continue;
+                };
+                debug!("Reading the firmware configuration: {}", path.display());
+                match file {
+                    "Kconfig" => parse_kconfig_defaults(&contents, &mut flags),
+                    "Makefile" => parse_makefile(&contents, &mut flags),
+                    _ => parse_config(&contents, &mut flags),
+                }
+            }
+        }
+        FirmwareBuild {
+            vms: enabled(&flags, &VM_SYMBOLS),
+            layouts: enabled(&flags, &LAYOUT_SYMBOLS),
+        }
+    }
+
+    /// Returns the requested VM, warning if the firmware wasn't compiled with
+    /// it. Without a request, rBPF is used unless the firmware only has the
+    /// Femto-Containers VM.
+    pub fn target(&self, requested: Option<&str>) -> Result<TargetVM, String> {
+        let Some(requested) = requested else {
+            return Ok(match self.vms.as_deref() {
+                Some([vm, ..]) if !self.supports_vm(TargetVM::Rbpf) => *vm,
+                _ => TargetVM::Rbpf,
+            });
+        };
+        let target = TargetVM::from_str(requested)?;
+        if !self.supports_vm(target) {
+            warn!(
+                "The firmware wasn't compiled with the {} VM (it has: {:?})",
+                target,
+                self.vms.as_deref().unwrap_or_default()
+            );
+        }
+        Ok(target)
+    }
+
+    /// Returns the requested binary layout, warning if the firmware wasn't
+    /// compiled with it. Without a request, the layout is picked based on the
+    /// target VM, the Femto-Containers VM only supports its own header.
+    pub fn layout(
+        &self,
+        requested: Option<&str>,
+        target: TargetVM,
+    ) -> Result<BinaryFileLayout, String> {
+        let Some(requested) = requested else {
+            if target == TargetVM::FemtoContainer {
+                return Ok(BinaryFileLayout::FemtoContainersHeader);
+            }
+            return Ok(match self.layouts.as_deref() {
+                Some([layout, ..]) if !self.supports_layout(BinaryFileLayout::ExtendedHeader) => {
+                    *layout
+                }
+                _ => BinaryFileLayout::ExtendedHeader,
+            });
+        };
+        let layout = requested.parse::<BinaryFileLayout>()?;
+        self.check_layout(layout);
+        Ok(layout)
+    }
+
+    /// Warns if the firmware wasn't compiled with the binary layout.
+    pub fn check_layout(&self, layout: BinaryFileLayout) {
+        if !self.supports_layout(layout) {
+            warn!(
+                "The firmware wasn't compiled with the {:?} layout (it has: {:?})",
+                layout,
+                self.layouts.as_deref().unwrap_or_default()
+            );
+        }
+    }
+
+    pub fn supports_vm(&self, vm: TargetVM) -> bool {
+        self.vms.as_ref().map_or(true, |vms| vms.contains(&vm))
+    }
+
+    pub fn supports_layout(&self, layout: BinaryFileLayout) -> bool {
+        self.layouts
+            .as_ref()
+            .map_or(true, |layouts| layouts.contains(&layout))
+    }
+}
+
+/// Values of the group of symbols that are enabled, `None` if the
+/// configuration doesn't set any of them.
+fn enabled<T: Copy>(flags: &BTreeMap<String, bool>, symbols: &[(&str, T)]) -> Option<Vec<T>> {
+    if !symbols
+        .iter()
+        .any(|(symbol, _)| flags.contains_key(*symbol))
+    {
+        return None;
+    }
+    Some(
+        symbols
+            .iter()
+            .filter(|(symbol, _)| flags.get(*symbol) == Some(&true))
+            .map(|(_, value)| *value)
+            .collect(),
+    )
+}
+
+/// Reads the `default` values of the boolean options of a Kconfig file.
+fn parse_kconfig_defaults(contents: &str, flags: &mut BTreeMap<String, bool>) {
+    let mut symbol = None;
+    for line in contents.lines().map(str::trim) {
+        if let Some(name) = line
+            .strip_prefix("config ")
+            .or_else(|| line.strip_prefix("menuconfig "))
+        {
+            symbol = Some(name.trim().to_string());
+        } else if let (Some(name), Some(value)) = (&symbol, line.strip_prefix("default ")) {
+            match value.split_whitespace().next() {
+                Some("y") => flags.insert(name.clone(), true),
+                Some("n") => flags.insert(name.clone(), false),
+                _ => None,
+            };
+        }
+    }
+}
+
+/// Reads the `-DCONFIG_<symbol>` definitions and the modules of a Makefile.
+fn parse_makefile(contents: &str, flags: &mut BTreeMap<String, bool>) {
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('#') {
+            continue;
+        }
+        for word in line.split_whitespace() {
+            if let Some(definition) = word.strip_prefix("-DCONFIG_") {
+                let (symbol, value) = definition.split_once('=').unwrap_or((definition, "1"));
+                flags.insert(symbol.to_string(), value != "0");
+            }
+        }
+        if line.starts_with("USEMODULE")
+            && line
+                .split_whitespace()
+                .any(|word| word == FEMTO_CONTAINERS_MODULE)
+        {
+            flags.insert(FEMTO_CONTAINERS_SYMBOL.to_string(), true);
+        }
+    }
+}
+
+/// Reads the `CONFIG_<symbol>=y` assignments of a Kconfig configuration file.
+fn parse_config(contents: &str, flags: &mut BTreeMap<String, bool>) {
+    for line in contents.lines().map(str::trim) {
+        if let Some(symbol) = line
+            .strip_prefix("# CONFIG_")
+            .and_then(|line| line.strip_suffix(" is not set"))
+        {
+            flags.insert(symbol.to_string(), false);
+        } else if let Some((symbol, value)) = line
+            .strip_prefix("CONFIG_")
+            .and_then(|line| line.split_once('='))
+        {
+            flags.insert(symbol.to_string(), value.trim() == "y");
+        }
+    }
+}
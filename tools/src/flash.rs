@@ -0,0 +1,146 @@
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+
+use crate::{
+    capabilities::forget_capabilities,
+    transport::{send_request, TransportOptions},
+};
+
+/// Number of lines of the build output included in the error message when
+/// flashing fails.
+const ERROR_CONTEXT_LINES: usize = 20;
+
+/// Controls how the micro_bpf firmware is built and flashed onto the board.
+#[derive(Debug, Clone)]
+pub struct FlashOptions {
+    /// Root directory of the mibpf repository.
+    pub micro_bpf_root_dir: String,
+    /// Directory of the RIOT application, defaults to `<root>/src`.
+    pub app_dir: Option<String>,
+    /// Name of the board, passed to the RIOT build system as BOARD.
+    pub board: String,
+    /// Serial port of the board, passed as PORT if the board isn't detected
+    /// automatically.
+    pub port: Option<String>,
+    /// Additional arguments passed to make, e.g. `PROGRAMMER=openocd`.
+    pub make_args: Vec<String>,
+}
+
+impl FlashOptions {
+    fn app_dir(&self) -> String {
+        self.app_dir
+            .clone()
+            .unwrap_or_else(|| format!("{}/src", self.micro_bpf_root_dir))
+    }
+}
+
+/// Builds the firmware and flashes it onto the board using the RIOT build
+/// system (`BOARD=<board> make flash`). The output of the build is printed as
+/// it is produced, if flashing fails, the error includes its last lines.
+pub fn flash(options: &FlashOptions) -> Result<(), String> {
+    let app_dir = options.app_dir();
+    let mut command = Command::new("make");
+    command
+        .arg("-C")
+        .arg(&app_dir)
+        .arg(format!("BOARD={}", options.board));
+    if let Some(port) = &options.port {
+        command.arg(format!("PORT={}", port));
+    }
+    command
+        .args(&options.make_args)
+        .arg("flash")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    debug!("Flashing the firmware: {:?}", command);
+
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("Failed to run make: {}", e))?;
+
+    // The errors are usually reported on stderr while the progress of the
+    // programmer goes to stdout, so both are monitored.
+    let stderr = child.stderr.take().unwrap();
+    let stderr_monitor = thread::spawn(move || monitor(BufReader::new(stderr), true));
+    let stdout = child.stdout.take().unwrap();
+    let mut output = monitor(BufReader::new(stdout), false);
+    output.extend(stderr_monitor.join().unwrap_or_default());
+
+    let status = child
+        .wait()
+        .map_err(|e| format!("Failed to wait for make: {}", e))?;
+    if !status.success() {
+        let context = output.into_iter().collect::<Vec<String>>().join("\n");
+        return Err(format!(
+            "Failed to flash the firmware in {} onto {} ({}):\n{}",
+            app_dir, options.board, status, context
+        ));
+    }
+    Ok(())
+}
+
+/// Prints the lines of the output as they arrive and returns the last ones.
+fn monitor(reader: impl BufRead, is_stderr: bool) -> VecDeque<String> {
+    let mut last_lines = VecDeque::with_capacity(ERROR_CONTEXT_LINES);
+    for line in reader.lines().map_while(Result::ok) {
+        if is_stderr {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+        if last_lines.len() == ERROR_CONTEXT_LINES {
+            last_lines.pop_front();
+        }
+        last_lines.push_back(line);
+    }
+    last_lines
+}
+
+/// Waits until the freshly flashed device joins the network and its CoAP
+/// server starts responding to `GET /.well-known/core`. Returns how long it
+/// took for the device to appear. The capabilities of the new firmware are
+/// queried again once they are needed.
+pub fn wait_for_device(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    timeout: Duration,
+    transport: &TransportOptions,
+) -> Result<Duration, String> {
+    forget_capabilities(riot_ipv6_addr);
+    let url = format!(
+        "coap://[{}%{}]/.well-known/core",
+        riot_ipv6_addr, host_network_interface
+    );
+    let start = Instant::now();
+    loop {
+        match send_request("GET", &url, None, transport) {
+            Ok(output) if output.status.success() && output.stderr.is_empty() => {
+                return Ok(start.elapsed());
+            }
+            Ok(output) => debug!(
+                "The device isn't reachable yet: {}",
+                String::from_utf8_lossy(&output.stderr)
+            ),
+            Err(e) => debug!("The device isn't reachable yet: {}", e),
+        }
+
+        transport.check_cancelled()?;
+        if transport.dry_run {
+            return Ok(start.elapsed());
+        }
+        if start.elapsed() > timeout {
+            return Err(format!(
+                "The device {} didn't appear on the network within {:?}",
+                riot_ipv6_addr, timeout
+            ));
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,372 @@
+use std::{
+    fs, thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use log::debug;
+use micro_bpf_common::{
+    BinaryFileLayout, HelperAccessListSource, HelperAccessVerification, StorageBackend, TargetVM,
+};
+use serde::Deserialize;
+
+use crate::{
+    request::build_execution_request,
+    transport::{send_request, TransportOptions},
+};
+
+/// A single device of the fleet.
+#[derive(Debug, Clone)]
+pub struct Device {
+    pub riot_ipv6_addr: String,
+    /// Network interface of the host machine used to reach the device.
+    pub host_network_interface: String,
+}
+
+impl Device {
+    fn base_url(&self) -> String {
+        format!(
+            "coap://[{}%{}]",
+            self.riot_ipv6_addr, self.host_network_interface
+        )
+    }
+}
+
+/// A program deployed to all devices of the fleet.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FleetProgram {
+    /// Source file of the program.
+    pub source: String,
+    /// SUIT storage slot that the program is deployed into.
+    pub slot: usize,
+    /// Set if the program needs to be re-deployed after the device reboots
+    /// and loses the contents of its RAM slots.
+    #[serde(default)]
+    pub persistent: bool,
+    #[serde(default)]
+    pub storage: StorageBackend,
+    #[serde(default = "default_target")]
+    pub target: String,
+    #[serde(default = "default_binary_layout")]
+    pub binary_layout: String,
+}
+
+fn default_target() -> String {
+    "rBPF".to_string()
+}
+
+fn default_binary_layout() -> String {
+    "ExtendedHeader".to_string()
+}
+
+/// A group of devices that the same program is executed on.
+#[derive(Debug, Clone, Default)]
+pub struct Fleet {
+    pub devices: Vec<Device>,
+    /// Programs deployed to the devices, only specified in the TOML fleet
+    /// configs.
+    pub programs: Vec<FleetProgram>,
+}
+
+/// Fleet config of the form:
+/// ```toml
+/// [[devices]]
+/// address = "fe80::a0d9:ebff:fed5:986b"
+/// interface = "tap0"
+///
+/// [[programs]]
+/// source = "bpf/sensor-processing.c"
+/// slot = 0
+/// persistent = true
+/// ```
+#[derive(Deserialize)]
+struct FleetConfig {
+    devices: Vec<DeviceEntry>,
+    #[serde(default)]
+    programs: Vec<FleetProgram>,
+}
+
+#[derive(Deserialize)]
+struct DeviceEntry {
+    address: String,
+    #[serde(default)]
+    interface: Option<String>,
+}
+
+impl Fleet {
+    /// Loads the fleet from a file listing one device per line in the form
+    /// `<ipv6 address>%<host network interface>`, the interface can be omitted
+    /// in which case the default one is used. Empty lines and lines starting
+    /// with '#' are ignored. Files with the `.toml` extension are loaded as
+    /// fleet configs which can also specify the programs of the devices.
+    pub fn load(path: &str, default_network_interface: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read the fleet file {}: {}", path, e))?;
+
+        if path.ends_with(".toml") {
+            let config = toml::from_str::<FleetConfig>(&contents)
+                .map_err(|e| format!("Failed to parse the fleet config {}: {}", path, e))?;
+            if config.devices.is_empty() {
+                return Err(format!(
+                    "The fleet config {} doesn't list any devices",
+                    path
+                ));
+            }
+            let devices = config
+                .devices
+                .into_iter()
+                .map(|entry| Device {
+                    riot_ipv6_addr: entry.address,
+                    host_network_interface: entry
+                        .interface
+                        .unwrap_or_else(|| default_network_interface.to_string()),
+                })
+                .collect();
+            return Ok(Fleet {
+                devices,
+                programs: config.programs,
+            });
+        }
+
+        let devices = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (ip, netif) = line
+                    .split_once('%')
+                    .unwrap_or((line, default_network_interface));
+                Device {
+                    riot_ipv6_addr: ip.to_string(),
+                    host_network_interface: netif.to_string(),
+                }
+            })
+            .collect::<Vec<Device>>();
+
+        if devices.is_empty() {
+            return Err(format!("The fleet file {} doesn't list any devices", path));
+        }
+        Ok(Fleet {
+            devices,
+            programs: vec![],
+        })
+    }
+}
+
+/// Watches the fleet file so that the long-lived commands (e.g. the
+/// dashboard) pick up the added and removed devices without restarting. The
+/// modification time of the file is polled, same as the devices themselves.
+pub struct FleetWatcher {
+    path: String,
+    default_network_interface: String,
+    modified: Option<SystemTime>,
+}
+
+impl FleetWatcher {
+    pub fn new(path: &str, default_network_interface: &str) -> Self {
+        FleetWatcher {
+            path: path.to_string(),
+            default_network_interface: default_network_interface.to_string(),
+            modified: modified_time(path),
+        }
+    }
+
+    /// Reloads the fleet if the file was modified since the last call. An
+    /// invalid file is only reported once, the callers keep using the
+    /// previous fleet until it gets fixed.
+    pub fn poll(&mut self) -> Option<Result<Fleet, String>> {
+        let modified = modified_time(&self.path);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        debug!("The fleet file {} changed, reloading it", self.path);
+        Some(Fleet::load(&self.path, &self.default_network_interface))
+    }
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Result of the synchronized execution on a single device.
+#[derive(Debug)]
+pub struct FleetResult {
+    pub device: Device,
+    /// Raw response of the device, of the form:
+    /// `{"execution_time": 10, "result": 0, "started_at": 1234}`
+    pub response: Result<String, String>,
+    /// Difference between the actual and the requested start of the execution
+    /// in milliseconds (according to the device clock).
+    pub skew_ms: Option<i64>,
+}
+
+/// Controls the timing of the synchronized execution.
+#[derive(Debug, Clone)]
+pub struct ScheduleOptions {
+    /// Delay between the moment when the requests start being distributed and
+    /// the trigger instant, needs to be long enough to reach all devices.
+    pub lead_time: Duration,
+    /// Extra time given to the devices to finish the execution before the
+    /// results are collected.
+    pub collection_delay: Duration,
+}
+
+#[derive(Deserialize)]
+struct ClockResponse {
+    /// Device time in milliseconds.
+    now: u64,
+}
+
+#[derive(Deserialize)]
+struct ScheduledResponse {
+    started_at: u64,
+}
+
+/// Schedules the program loaded in the given SUIT storage slot to execute at
+/// (approximately) the same instant on all devices of the fleet and collects
+/// the results once the execution has finished.
+///
+/// The devices don't share a common clock, because of this we first estimate
+/// the offset of the clock of each device relative to the host (compensating
+/// for half of the round-trip time of the request). The trigger instant is
+/// then translated into the time of each device before being distributed.
+/// If the lead time runs out before all devices are scheduled, the remaining
+/// ones are reported as failed.
+pub fn execute_synchronized(
+    fleet: &Fleet,
+    target: TargetVM,
+    binary_layout: BinaryFileLayout,
+    suit_storage_slot: usize,
+    helper_indices: &[u8],
+    jit: bool,
+    options: &ScheduleOptions,
+    transport: &TransportOptions,
+) -> Result<Vec<FleetResult>, String> {
+    let payload = build_execution_request(
+        target,
+        binary_layout,
+        suit_storage_slot,
+        HelperAccessVerification::AheadOfTime,
+        HelperAccessListSource::ExecuteRequest,
+        helper_indices,
+        jit,
+        jit,
+    )
+    .encode();
+
+    let trigger = now_ms() + options.lead_time.as_millis() as i64;
+    let mut scheduled = vec![];
+    for device in &fleet.devices {
+        // The devices which can't be reached before the trigger instant are
+        // left out, the ones already scheduled still execute and get
+        // collected.
+        if now_ms() >= trigger {
+            scheduled.push((
+                device,
+                Err(format!(
+                    "Not scheduled, distributing the schedule took longer than the lead \
+                     time of {}ms, increase the lead time",
+                    options.lead_time.as_millis()
+                )),
+            ));
+            continue;
+        }
+        let device_trigger = clock_offset(device, transport).and_then(|offset| {
+            let device_trigger = (trigger + offset).max(0) as u64;
+            let url = format!(
+                "{}/scheduled-execution?at={}",
+                device.base_url(),
+                device_trigger
+            );
+            debug!("Scheduling the execution: {}", url);
+            check_output(send_request("POST", &url, Some(&payload), transport)?)?;
+            Ok(device_trigger)
+        });
+        scheduled.push((device, device_trigger));
+    }
+
+    let remaining = (trigger - now_ms()).max(0);
+    thread::sleep(Duration::from_millis(remaining as u64) + options.collection_delay);
+
+    let results = scheduled
+        .into_iter()
+        .map(|(device, device_trigger)| {
+            let response = device_trigger
+                .clone()
+                .and_then(|_| collect_result(device, suit_storage_slot, transport));
+            let skew_ms = match (&response, device_trigger) {
+                (Ok(response), Ok(device_trigger)) => {
+                    serde_json::from_str::<ScheduledResponse>(response)
+                        .ok()
+                        .map(|r| r.started_at as i64 - device_trigger as i64)
+                }
+                _ => None,
+            };
+            FleetResult {
+                device: device.clone(),
+                response,
+                skew_ms,
+            }
+        })
+        .collect();
+
+    Ok(results)
+}
+
+/// Reads the clock of the device, i.e. the number of milliseconds since it
+/// booted.
+pub fn device_clock(device: &Device, transport: &TransportOptions) -> Result<u64, String> {
+    let url = format!("{}/time", device.base_url());
+    let response = check_output(send_request("GET", &url, None, transport)?)?;
+    serde_json::from_str::<ClockResponse>(&response)
+        .map(|clock| clock.now)
+        .map_err(|e| format!("Failed to parse the clock response: {}", e))
+}
+
+/// Estimates the offset (in milliseconds) that needs to be added to the host
+/// time to get the time of the device.
+fn clock_offset(device: &Device, transport: &TransportOptions) -> Result<i64, String> {
+    let start = Instant::now();
+    let sent_at = now_ms();
+    let now = device_clock(device, transport)?;
+    let round_trip = start.elapsed().as_millis() as i64;
+
+    let offset = now as i64 - (sent_at + round_trip / 2);
+    debug!(
+        "Clock offset of {}: {}ms (rtt: {}ms)",
+        device.riot_ipv6_addr, offset, round_trip
+    );
+    Ok(offset)
+}
+
+fn collect_result(
+    device: &Device,
+    suit_storage_slot: usize,
+    transport: &TransportOptions,
+) -> Result<String, String> {
+    let url = format!(
+        "{}/scheduled-execution/result?slot={}",
+        device.base_url(),
+        suit_storage_slot
+    );
+    check_output(send_request("GET", &url, None, transport)?)
+}
+
+fn check_output(output: std::process::Output) -> Result<String, String> {
+    if output.stderr.len() > 0 {
+        let stderr = String::from_utf8(output.stderr)
+            .map_err(|e| format!("Failed to parse the stderr: {}", e))?;
+        Err(format!("aiocoap-client failed with: {}", stderr))?
+    }
+
+    let response = String::from_utf8(output.stdout)
+        .map_err(|e| format!("Failed to parse the response: {}", e))?;
+    Ok(response.trim_matches(char::from(0)).to_string())
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
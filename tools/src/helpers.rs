@@ -0,0 +1,223 @@
+use std::{collections::BTreeSet, fmt, fs};
+
+use enum_iterator::all;
+use log::debug;
+use micro_bpf_common::{BinaryFileLayout, HelperFunctionID};
+use micro_bpf_elf_utils::extract_text_section;
+
+use crate::{
+    bytecode::decode,
+    environment::config_file_path,
+    postprocessing::read_bytes_from_file,
+    transport::{send_request, TransportOptions},
+};
+
+/// Queries the device for the IDs of the helpers compiled into its firmware.
+/// Depending on the configuration of the build, some of the helpers (e.g. the
+/// SAUL or the display ones) are left out.
+///
+/// The device is expected to respond with a JSON list of the IDs: `[0, 1, 2, 16]`
+pub fn query_helpers(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    transport: &TransportOptions,
+) -> Result<Vec<u8>, String> {
+    let url = format!(
+        "coap://[{}%{}]/helpers",
+        riot_ipv6_addr, host_network_interface
+    );
+    debug!("Querying the available helpers: {}", url);
+
+    let output = send_request("GET", &url, None, transport)?;
+
+    if output.stderr.len() > 0 {
+        let stderr = String::from_utf8(output.stderr)
+            .map_err(|e| format!("Failed to parse the stderr: {}", e))?;
+        Err(format!("aiocoap-client failed with: {}", stderr))?
+    }
+
+    let response = String::from_utf8(output.stdout)
+        .map_err(|e| format!("Failed to parse the response: {}", e))?;
+
+    serde_json::from_str::<Vec<u8>>(response.trim_matches(char::from(0)))
+        .map_err(|e| format!("Failed to parse the helper listing: {}", e))
+}
+
+/// Collects the IDs of the helpers called by the program in the binary.
+pub fn required_helpers(binary: &str, layout: BinaryFileLayout) -> Result<Vec<u8>, String> {
+    let bytes = read_bytes_from_file(binary);
+    let text = extract_text_section(&bytes, layout)?;
+    let helpers = decode(text)?
+        .iter()
+        .filter(|instruction| instruction.is_helper_call())
+        .map(|instruction| instruction.immediate as u8)
+        .collect::<BTreeSet<u8>>();
+    Ok(helpers.into_iter().collect())
+}
+
+/// Cross-reference of the helpers required by a program with the ones
+/// available on the device.
+#[derive(Debug, Clone)]
+pub struct HelperCompatibility {
+    pub required: Vec<u8>,
+    pub available: Vec<u8>,
+}
+
+impl HelperCompatibility {
+    pub fn new(required: Vec<u8>, available: Vec<u8>) -> Self {
+        HelperCompatibility {
+            required,
+            available,
+        }
+    }
+
+    /// Helpers called by the program which aren't compiled into the firmware.
+    pub fn missing(&self) -> Vec<u8> {
+        self.required
+            .iter()
+            .filter(|id| !self.available.contains(id))
+            .copied()
+            .collect()
+    }
+
+    pub fn is_compatible(&self) -> bool {
+        self.missing().is_empty()
+    }
+}
+
+fn helper_name(id: u8) -> String {
+    let helper: Option<HelperFunctionID> = num::FromPrimitive::from_u8(id);
+    match helper {
+        Some(helper) => format!("{:?}", helper),
+        None => "unknown".to_string(),
+    }
+}
+
+impl fmt::Display for HelperCompatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<6} {:<32} {:<10} {}",
+            "ID", "Helper", "Required", "Available"
+        )?;
+        let helpers = self
+            .required
+            .iter()
+            .chain(self.available.iter())
+            .copied()
+            .collect::<BTreeSet<u8>>();
+        let mark = |value: bool| if value { "yes" } else { "no" };
+        for id in helpers {
+            writeln!(
+                f,
+                "{:<6} {:<32} {:<10} {}",
+                format!("{:#04x}", id),
+                helper_name(id),
+                mark(self.required.contains(&id)),
+                mark(self.available.contains(&id))
+            )?;
+        }
+
+        writeln!(f)?;
+        let missing = self.missing();
+        if missing.is_empty() {
+            write!(
+                f,
+                "Compatible: all {} helpers called by the program are available on the device",
+                self.required.len()
+            )
+        } else {
+            let names = missing
+                .iter()
+                .map(|id| format!("{} ({:#04x})", helper_name(*id), id))
+                .collect::<Vec<String>>();
+            write!(
+                f,
+                "Incompatible: the firmware of the device doesn't include {}",
+                names.join(", ")
+            )
+        }
+    }
+}
+
+/// Named set of helpers that the programs are allowed to call, defined in the
+/// `[helper_profiles]` section of the config file:
+///
+/// ```toml
+/// [helper_profiles]
+/// sensing = ["saul_reg_read", "printf", "now_ms"]
+/// ```
+///
+/// This keeps the security-relevant helper grants reviewable in one place
+/// instead of scattering the numeric IDs across scripts. The helpers are
+/// referred to by their names (with or without the `BPF_` prefix and the
+/// `_IDX` suffix, in any case) or by their numeric IDs.
+pub fn load_helper_profile(name: &str) -> Result<Vec<u8>, String> {
+    let path = config_file_path();
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read the config file {}: {}", path, e))?;
+    let table = contents
+        .parse::<toml::Table>()
+        .map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+
+    let profiles = table.get("helper_profiles").and_then(|p| p.as_table());
+    let Some(profile) = profiles.and_then(|p| p.get(name)) else {
+        let available = profiles
+            .map(|p| p.keys().cloned().collect::<Vec<String>>().join(", "))
+            .unwrap_or_default();
+        return Err(format!(
+            "No helper profile named {} in {} (available: [{}])",
+            name, path, available
+        ));
+    };
+    let Some(helpers) = profile.as_array() else {
+        return Err(format!("Helper profile {} needs to be a list", name));
+    };
+
+    // An empty list of helpers would allow the program to call all of them.
+    if helpers.is_empty() {
+        return Err(format!("Helper profile {} doesn't list any helpers", name));
+    }
+
+    helpers
+        .iter()
+        .map(|helper| match helper {
+            toml::Value::String(name) => parse_helper(name),
+            toml::Value::Integer(id) => u8::try_from(*id)
+                .ok()
+                .filter(|id| helper_name(*id) != "unknown")
+                .ok_or_else(|| format!("Unknown helper ID: {}", id)),
+            other => Err(format!("Invalid helper: {}", other)),
+        })
+        .collect::<Result<Vec<u8>, String>>()
+        .map_err(|e| format!("Invalid helper profile {}: {}", name, e))
+}
+
+/// Parses the name of a helper, e.g. `printf` or `BPF_PRINTF_IDX`.
+pub fn parse_helper(name: &str) -> Result<u8, String> {
+    let normalize = |name: &str| {
+        let name = name.trim().to_uppercase();
+        let name = name.strip_prefix("BPF_").unwrap_or(&name);
+        name.strip_suffix("_IDX").unwrap_or(name).to_string()
+    };
+    let wanted = normalize(name);
+    all::<HelperFunctionID>()
+        .find(|helper| normalize(&format!("{:?}", helper)) == wanted)
+        .map(|helper| helper as u8)
+        .ok_or_else(|| format!("Unknown helper: {}", name))
+}
+
+/// Combines the helpers of the profile (if any) with the explicitly listed
+/// helper indices.
+pub fn resolve_helpers(helper_indices: &[u8], profile: Option<&str>) -> Result<Vec<u8>, String> {
+    let mut helpers = match profile {
+        Some(profile) => load_helper_profile(profile)?,
+        None => vec![],
+    };
+    for id in helper_indices {
+        if !helpers.contains(id) {
+            helpers.push(*id);
+        }
+    }
+    Ok(helpers)
+}
@@ -0,0 +1,257 @@
+use std::{
+    collections::BTreeMap,
+    env, fmt,
+    fs::{self, OpenOptions},
+    io::Write,
+};
+
+use micro_bpf_common::ExecutionResponse;
+use serde::{Deserialize, Serialize};
+
+use crate::registry::{now, Registry};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Result of a single execution, recorded together with the program that was
+/// deployed in the slot at the time.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionRecord {
+    pub device: String,
+    pub slot: usize,
+    /// Source file of the program according to the registry, unknown for the
+    /// programs deployed by other tools.
+    #[serde(default)]
+    pub program: Option<String>,
+    /// SHA-256 of the source file at the time of the deployment, it tells
+    /// apart the versions of the same program.
+    #[serde(default)]
+    pub program_hash: Option<String>,
+    /// Seconds since the UNIX epoch.
+    pub executed_at: u64,
+    pub execution_time: u32,
+    pub result: i64,
+    #[serde(default)]
+    pub instructions: Option<u64>,
+}
+
+/// History of the execution results, it extends the [`Registry`] with the
+/// information needed for tracking the performance of the programs over long
+/// periods of time. As it only ever grows, it is kept separately from the
+/// registry in an append-only JSON lines file. Its location can be overridden
+/// by setting the environment variable "MIBPF_RESULTS".
+pub struct ResultHistory {
+    path: String,
+}
+
+impl ResultHistory {
+    pub fn new(path: &str) -> Self {
+        ResultHistory {
+            path: path.to_string(),
+        }
+    }
+
+    pub fn default_path() -> String {
+        env::var("MIBPF_RESULTS").unwrap_or_else(|_| ".mibpf-results.jsonl".to_string())
+    }
+
+    pub fn append(&self, record: &ExecutionRecord) -> Result<(), String> {
+        let line = serde_json::to_string(record).unwrap() + "\n";
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|e| format!("Failed to append to the results {}: {}", self.path, e))
+    }
+
+    /// Returns the records matching the filter in the order they were
+    /// recorded.
+    pub fn query(&self, filter: &ResultFilter) -> Result<Vec<ExecutionRecord>, String> {
+        let Ok(contents) = fs::read_to_string(&self.path) else {
+            return Ok(vec![]);
+        };
+        let mut records = vec![];
+        for (i, line) in contents.lines().enumerate() {
+            let record = serde_json::from_str::<ExecutionRecord>(line)
+                .map_err(|e| format!("Invalid record at {}:{}: {}", self.path, i + 1, e))?;
+            if filter.matches(&record) {
+                records.push(record);
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// Records the result of the execution of the program in the slot of the
+/// device, the program is looked up in the registry.
+pub fn record_execution(device: &str, slot: usize, response: &str) -> Result<(), String> {
+    let response = serde_json::from_str::<ExecutionResponse>(response.trim_matches(char::from(0)))
+        .map_err(|e| format!("Failed to parse the execution response: {}", e))?;
+    let registry = Registry::load()?;
+    let deployed = registry
+        .device(device)
+        .and_then(|record| record.slots.get(&slot));
+    let record = ExecutionRecord {
+        device: device.to_string(),
+        slot,
+        program: deployed.map(|record| record.program.clone()),
+        program_hash: deployed.and_then(|record| record.program_hash.clone()),
+        executed_at: now(),
+        execution_time: response.execution_time,
+        result: response.result,
+        instructions: response.instructions,
+    };
+    ResultHistory::new(&ResultHistory::default_path()).append(&record)
+}
+
+/// Criteria of the records returned by [`ResultHistory::query`], the unset
+/// ones match all records.
+#[derive(Debug, Clone, Default)]
+pub struct ResultFilter {
+    pub device: Option<String>,
+    /// Matches either the source file of the program or the prefix of its
+    /// hash.
+    pub program: Option<String>,
+    /// Inclusive range of the execution times (seconds since the UNIX epoch).
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+}
+
+impl ResultFilter {
+    pub fn matches(&self, record: &ExecutionRecord) -> bool {
+        let program_matches = |program: &str| {
+            record.program.as_deref() == Some(program)
+                || record
+                    .program_hash
+                    .as_deref()
+                    .is_some_and(|hash| hash.starts_with(program))
+        };
+        self.device.as_ref().map_or(true, |d| *d == record.device)
+            && self.program.as_deref().map_or(true, program_matches)
+            && self.since.map_or(true, |since| record.executed_at >= since)
+            && self.until.map_or(true, |until| record.executed_at <= until)
+    }
+}
+
+/// Parses the bound of the date range, either a date (`2024-05-01`, in UTC)
+/// or seconds since the UNIX epoch. The date used as the upper bound covers
+/// the whole day.
+pub fn parse_date(date: &str, end_of_day: bool) -> Result<u64, String> {
+    if let Ok(timestamp) = date.parse::<u64>() {
+        return Ok(timestamp);
+    }
+    let parts = date
+        .split('-')
+        .map(|part| part.parse::<u64>())
+        .collect::<Result<Vec<u64>, _>>()
+        .map_err(|_| format!("Invalid date: {}, expected YYYY-MM-DD", date))?;
+    let [year, month, day] = parts[..] else {
+        return Err(format!("Invalid date: {}, expected YYYY-MM-DD", date));
+    };
+    if year < 1970 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(format!("Invalid date: {}", date));
+    }
+    let start = days_from_civil(year, month, day) * SECONDS_PER_DAY;
+    Ok(if end_of_day {
+        start + SECONDS_PER_DAY - 1
+    } else {
+        start
+    })
+}
+
+/// Number of days since the UNIX epoch of the date in the proleptic Gregorian
+/// calendar.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Statistics of the execution times of one version of a program on a device.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResultSummary {
+    pub device: String,
+    pub program: String,
+    pub program_hash: Option<String>,
+    pub executions: usize,
+    pub min: u32,
+    pub median: u32,
+    pub mean: f64,
+    pub max: u32,
+    /// Distinct return values of the program.
+    pub results: Vec<i64>,
+    pub first: u64,
+    pub last: u64,
+}
+
+/// Groups the records by the device and the version of the program, the
+/// summaries are ordered by the time of their first execution so that the
+/// versions of a program show its performance trend.
+pub fn summarize(records: &[ExecutionRecord]) -> Vec<ResultSummary> {
+    let mut groups = BTreeMap::<(String, String, Option<String>), Vec<&ExecutionRecord>>::new();
+    for record in records {
+        let program = record.program.clone().unwrap_or_else(|| "?".to_string());
+        groups
+            .entry((record.device.clone(), program, record.program_hash.clone()))
+            .or_default()
+            .push(record);
+    }
+
+    let mut summaries = groups
+        .into_iter()
+        .map(|((device, program, program_hash), records)| {
+            let mut times = records
+                .iter()
+                .map(|record| record.execution_time)
+                .collect::<Vec<u32>>();
+            times.sort_unstable();
+            let mut results = records
+                .iter()
+                .map(|record| record.result)
+                .collect::<Vec<i64>>();
+            results.sort_unstable();
+            results.dedup();
+            ResultSummary {
+                device,
+                program,
+                program_hash,
+                executions: records.len(),
+                min: times[0],
+                median: times[times.len() / 2],
+                mean: times.iter().map(|t| *t as f64).sum::<f64>() / times.len() as f64,
+                max: times[times.len() - 1],
+                results,
+                first: records.iter().map(|r| r.executed_at).min().unwrap_or(0),
+                last: records.iter().map(|r| r.executed_at).max().unwrap_or(0),
+            }
+        })
+        .collect::<Vec<ResultSummary>>();
+    summaries.sort_by_key(|summary| summary.first);
+    summaries
+}
+
+impl fmt::Display for ResultSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hash = self
+            .program_hash
+            .as_deref()
+            .map(|hash| format!(" ({})", &hash[..hash.len().min(12)]))
+            .unwrap_or_default();
+        writeln!(f, "{} on {}{}", self.program, self.device, hash)?;
+        writeln!(
+            f,
+            "  executions: {} between {} and {}",
+            self.executions, self.first, self.last
+        )?;
+        writeln!(
+            f,
+            "  execution time: min {} / median {} / mean {:.1} / max {}",
+            self.min, self.median, self.mean, self.max
+        )?;
+        write!(f, "  results: {:?}", self.results)
+    }
+}
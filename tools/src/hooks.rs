@@ -0,0 +1,128 @@
+use log::{debug, info};
+use micro_bpf_common::{HookInfo, NetworkHook, VMExecutionRequest, CAPABILITY_NETWORK_HOOKS};
+
+use crate::{
+    capabilities::query_capabilities,
+    transport::{send_request, TransportOptions},
+};
+
+/// Attaches the program deployed in the slot of the request to the network
+/// hook of the device. From then on the program runs on every packet passing
+/// through the hook until it is detached (see [`detach_program`]), its
+/// results are retrieved using [`query_hooks`].
+pub fn attach_program(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    hook: NetworkHook,
+    request: &VMExecutionRequest,
+    transport: &TransportOptions,
+) -> Result<(), String> {
+    let capabilities = query_capabilities(riot_ipv6_addr, host_network_interface, transport)?;
+    if transport.dry_run {
+        info!("[dry-run] assuming that the device supports network hooks");
+    } else if !capabilities.supports(CAPABILITY_NETWORK_HOOKS) {
+        return Err(format!(
+            "The firmware of the device doesn't support attaching programs to network hooks, \
+             can't attach to {}",
+            hook
+        ));
+    }
+
+    let url = format!(
+        "coap://[{}%{}]/hooks/{}/attach",
+        riot_ipv6_addr, host_network_interface, hook
+    );
+    debug!("Attaching the program to {}: {}", hook, url);
+    post(&url, Some(&request.encode()), transport)
+}
+
+/// Detaches the program from the network hook, the packets pass through the
+/// hook unfiltered afterwards. The program stays in its SUIT storage slot.
+pub fn detach_program(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    hook: NetworkHook,
+    transport: &TransportOptions,
+) -> Result<(), String> {
+    let url = format!(
+        "coap://[{}%{}]/hooks/{}/detach",
+        riot_ipv6_addr, host_network_interface, hook
+    );
+    debug!("Detaching the program from {}: {}", hook, url);
+    post(&url, None, transport)
+}
+
+/// Lists the network hooks of the device together with the programs
+/// attached to them, their counters and the values in their storage.
+pub fn query_hooks(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    transport: &TransportOptions,
+) -> Result<Vec<HookInfo>, String> {
+    let url = format!(
+        "coap://[{}%{}]/hooks",
+        riot_ipv6_addr, host_network_interface
+    );
+    debug!("Querying the network hooks: {}", url);
+
+    let output = send_request("GET", &url, None, transport)?;
+    if transport.dry_run {
+        return Ok(vec![]);
+    }
+    if !output.stderr.is_empty() {
+        let stderr = String::from_utf8(output.stderr)
+            .map_err(|e| format!("Failed to parse the stderr: {}", e))?;
+        return Err(format!("aiocoap-client failed with: {}", stderr));
+    }
+
+    let response = String::from_utf8(output.stdout)
+        .map_err(|e| format!("Failed to parse the response: {}", e))?;
+    serde_json::from_str::<Vec<HookInfo>>(&response)
+        .map_err(|e| format!("Failed to parse the hook listing: {}", e))
+}
+
+/// Renders the hook listing as a table followed by the storage of the
+/// attached programs.
+pub fn format_hooks(hooks: &[HookInfo]) -> String {
+    let mut lines = vec![format!(
+        "{:<10}{:<6}{:>12}{:>10}{:>10}{:>8}{:>8}",
+        "Hook", "Slot", "Invocations", "Accepted", "Dropped", "Errors", "Last"
+    )];
+    for hook in hooks {
+        let Some(slot) = hook.slot else {
+            lines.push(format!("{:<10}{:<6}", hook.hook, "-"));
+            continue;
+        };
+        let counters = &hook.counters;
+        lines.push(format!(
+            "{:<10}{:<6}{:>12}{:>10}{:>10}{:>8}{:>8}",
+            hook.hook,
+            slot,
+            counters.invocations,
+            counters.accepted,
+            counters.dropped,
+            counters.errors,
+            counters.last_result
+        ));
+    }
+    for hook in hooks.iter().filter(|hook| !hook.storage.is_empty()) {
+        lines.push(format!(
+            "\nStorage of the program attached to {}:",
+            hook.hook
+        ));
+        for (key, value) in &hook.storage {
+            lines.push(format!("  {:<8}{}", key, value));
+        }
+    }
+    lines.join("\n")
+}
+
+fn post(url: &str, payload: Option<&str>, transport: &TransportOptions) -> Result<(), String> {
+    let output = send_request("POST", url, payload, transport)?;
+    if !output.stderr.is_empty() {
+        let stderr = String::from_utf8(output.stderr)
+            .map_err(|e| format!("Failed to parse the stderr: {}", e))?;
+        return Err(format!("aiocoap-client failed with: {}", stderr));
+    }
+    Ok(())
+}
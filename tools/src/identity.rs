@@ -0,0 +1,147 @@
+use std::{
+    fmt, fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use log::debug;
+
+use crate::keys::verify_signature;
+
+/// Prefix of the last line of a signed response, followed by the hex-encoded
+/// signature.
+const SIGNATURE_PREFIX: &str = "sig:";
+
+/// Number of random bytes of the nonce sent with each request.
+const NONCE_LENGTH: usize = 16;
+
+/// Beginning of the messages of all [`ResponseAuthenticity`] errors, the
+/// errors are passed around as strings so this allows for recognising them.
+pub const UNAUTHENTIC_RESPONSE: &str = "Unable to authenticate the response";
+
+/// Reason why the response can't be attributed to the pinned device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResponseAuthenticity {
+    /// The response doesn't carry a signature even though the identity of
+    /// the device is pinned, e.g. it was sent by a different node.
+    Unsigned { device: String },
+    /// The signature doesn't match the pinned key or the nonce of the
+    /// request (e.g. a replayed response).
+    InvalidSignature { device: String, reason: String },
+}
+
+impl fmt::Display for ResponseAuthenticity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResponseAuthenticity::Unsigned { device } => write!(
+                f,
+                "{} of {}: the response isn't signed",
+                UNAUTHENTIC_RESPONSE, device
+            ),
+            ResponseAuthenticity::InvalidSignature { device, reason } => write!(
+                f,
+                "{} of {}: invalid signature ({})",
+                UNAUTHENTIC_RESPONSE, device, reason
+            ),
+        }
+    }
+}
+
+/// Checks whether the request failed because the response couldn't be
+/// authenticated.
+pub fn is_unauthentic(error: &str) -> bool {
+    error.contains(UNAUTHENTIC_RESPONSE)
+}
+
+/// Store of the pinned identities of the devices, i.e. the ed25519 public
+/// keys that the devices sign their responses with. Each key is stored in
+/// the DER format (same as the public keys of the
+/// [`crate::KeyStore`]) and named after the IPv6 address of the device:
+///
+/// ```text
+/// identities/
+///   fe80::a0d9:ebff:fed5:986b.pub.der
+/// ```
+///
+/// Only the firmware built with response signing supports this, the devices
+/// without a pinned key are trusted as before.
+pub struct IdentityStore {
+    root: PathBuf,
+}
+
+impl IdentityStore {
+    pub fn new(root: &str) -> Self {
+        IdentityStore {
+            root: PathBuf::from(root),
+        }
+    }
+
+    pub fn key_path(&self, device_ip: &str) -> PathBuf {
+        self.root.join(format!("{}.pub.der", device_ip))
+    }
+
+    /// Returns the pinned public key of the device if there is one.
+    pub fn pinned_key(&self, device_ip: &str) -> Option<PathBuf> {
+        Some(self.key_path(device_ip)).filter(|path| path.exists())
+    }
+
+    /// Pins the identity of the device to the given DER-encoded public key,
+    /// replacing the previously pinned one.
+    pub fn pin(&self, device_ip: &str, public_key: &str) -> Result<PathBuf, String> {
+        let key = fs::read(public_key)
+            .map_err(|e| format!("Failed to read the public key {}: {}", public_key, e))?;
+        fs::create_dir_all(&self.root)
+            .map_err(|e| format!("Failed to create the identity directory: {}", e))?;
+        let path = self.key_path(device_ip);
+        fs::write(&path, key)
+            .map_err(|e| format!("Failed to pin the identity of {}: {}", device_ip, e))?;
+        debug!("Pinned the identity of {} to {}", device_ip, public_key);
+        Ok(path)
+    }
+}
+
+/// Generates a random hex-encoded nonce, the device includes it in the signed
+/// part of the response so that old responses can't be replayed.
+pub fn new_nonce() -> Result<String, String> {
+    let mut nonce = [0u8; NONCE_LENGTH];
+    fs::File::open("/dev/urandom")
+        .and_then(|mut random| random.read_exact(&mut nonce))
+        .map_err(|e| format!("Failed to generate the nonce: {}", e))?;
+    Ok(nonce.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Checks the signature of the response and returns the response without it.
+///
+/// The signed responses end with a line `sig:<hex>` containing the ed25519
+/// signature of the nonce (as sent in the request) followed by the rest of
+/// the response.
+pub fn verify_response(
+    device: &str,
+    nonce: &str,
+    response: &[u8],
+    public_key: &Path,
+) -> Result<Vec<u8>, ResponseAuthenticity> {
+    let unsigned = || ResponseAuthenticity::Unsigned {
+        device: device.to_string(),
+    };
+    let text = std::str::from_utf8(response).map_err(|_| unsigned())?;
+    let text = text.trim_end_matches('\n');
+    let (payload, signature) = match text.rsplit_once('\n') {
+        Some((payload, last_line)) => (payload, last_line),
+        None => ("", text),
+    };
+    let signature = signature
+        .trim()
+        .strip_prefix(SIGNATURE_PREFIX)
+        .ok_or_else(unsigned)?;
+
+    let message = [nonce.as_bytes(), payload.as_bytes()].concat();
+    verify_signature(&message, signature, public_key).map_err(|reason| {
+        ResponseAuthenticity::InvalidSignature {
+            device: device.to_string(),
+            reason,
+        }
+    })?;
+    debug!("Authenticated the response of {}", device);
+    Ok(format!("{}\n", payload).into_bytes())
+}
@@ -0,0 +1,187 @@
+use std::{
+    collections::HashMap,
+    fs,
+    net::Ipv6Addr,
+    process::Command,
+    sync::{Mutex, OnceLock},
+};
+
+use log::debug;
+
+/// Value of the host network interface which makes the tools derive it from
+/// the address of the device instead of using the given interface.
+pub const AUTO_INTERFACE: &str = "auto";
+
+/// Addresses of the network interfaces of the host, see `man 5 proc`.
+const IF_INET6: &str = "/proc/net/if_inet6";
+
+/// Scope of the link-local addresses in [`IF_INET6`].
+const LINK_LOCAL_SCOPE: &str = "20";
+
+/// Returns the network interface of the host which is used to reach the
+/// device. The interface is returned as is unless it is [`AUTO_INTERFACE`],
+/// in which case it is looked up:
+///
+/// - for link-local devices the neighbour cache of the host is consulted
+///   first, if the device isn't in it the interfaces with a link-local
+///   address are probed until one of them reaches the device,
+/// - for other devices the interface is the one of the route that the
+///   kernel would use.
+///
+/// The lookups are cached for the lifetime of the process.
+pub fn resolve_interface(riot_ipv6_addr: &str, interface: &str) -> Result<String, String> {
+    if interface != AUTO_INTERFACE {
+        return Ok(interface.to_string());
+    }
+
+    static RESOLVED: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+    let resolved = RESOLVED.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some(interface) = resolved.lock().unwrap().get(riot_ipv6_addr) {
+        return Ok(interface.clone());
+    }
+
+    let address = riot_ipv6_addr
+        .parse::<Ipv6Addr>()
+        .map_err(|e| format!("Invalid device address {}: {}", riot_ipv6_addr, e))?;
+    let interface = if is_link_local(&address) {
+        link_local_interface(riot_ipv6_addr)?
+    } else {
+        route_interface(riot_ipv6_addr)?
+    };
+    debug!(
+        "Resolved the interface of {}: {}",
+        riot_ipv6_addr, interface
+    );
+    resolved
+        .lock()
+        .unwrap()
+        .insert(riot_ipv6_addr.to_string(), interface.clone());
+    Ok(interface)
+}
+
+/// Replaces the [`AUTO_INTERFACE`] zone of the device address in the url
+/// (e.g. `coap://[fe80::1%auto]/run`) with the resolved interface.
+pub fn resolve_url_zone(url: &str) -> Result<String, String> {
+    let zone = format!("%{}]", AUTO_INTERFACE);
+    let (Some(start), Some(end)) = (url.find('['), url.find(&zone)) else {
+        return Ok(url.to_string());
+    };
+    if start > end {
+        return Ok(url.to_string());
+    }
+    let interface = resolve_interface(&url[start + 1..end], AUTO_INTERFACE)?;
+    Ok(format!(
+        "{}%{}{}",
+        &url[..end],
+        interface,
+        &url[end + zone.len() - 1..]
+    ))
+}
+
+/// Finds the IPv6 address of the network interface that the CoAP fileserver
+/// is reachable on, the link-local address is preferred.
+pub fn interface_address(interface: &str) -> Result<String, String> {
+    let addresses = interface_addresses()?
+        .into_iter()
+        .filter(|(name, _, _)| name == interface)
+        .map(|(_, address, link_local)| (address, link_local))
+        .collect::<Vec<(Ipv6Addr, bool)>>();
+
+    addresses
+        .iter()
+        .find(|(_, link_local)| *link_local)
+        .or(addresses.first())
+        .map(|(address, _)| address.to_string())
+        .ok_or_else(|| format!("No IPv6 address assigned to {}", interface))
+}
+
+/// Returns the name, address and whether the address is link-local for
+/// every IPv6 address of the host.
+fn interface_addresses() -> Result<Vec<(String, Ipv6Addr, bool)>, String> {
+    let contents =
+        fs::read_to_string(IF_INET6).map_err(|e| format!("Failed to read {}: {}", IF_INET6, e))?;
+    // Each line is: <address> <index> <prefix length> <scope> <flags> <name>
+    Ok(contents
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>())
+        .filter(|fields| fields.len() == 6)
+        .filter_map(|fields| {
+            let address = u128::from_str_radix(fields[0], 16).ok()?;
+            Some((
+                fields[5].to_string(),
+                Ipv6Addr::from(address),
+                fields[3] == LINK_LOCAL_SCOPE,
+            ))
+        })
+        .collect())
+}
+
+fn is_link_local(address: &Ipv6Addr) -> bool {
+    address.segments()[0] & 0xffc0 == 0xfe80
+}
+
+/// The same link-local address can exist on every link, so the interface
+/// can't be derived from the routing table.
+fn link_local_interface(riot_ipv6_addr: &str) -> Result<String, String> {
+    if let Some(interface) = device_field(&["-6", "neigh", "show", "to", riot_ipv6_addr])? {
+        return Ok(interface);
+    }
+
+    let mut candidates = interface_addresses()?
+        .into_iter()
+        .filter(|(name, _, link_local)| *link_local && name != "lo")
+        .map(|(name, _, _)| name)
+        .collect::<Vec<String>>();
+    candidates.sort();
+    candidates.dedup();
+    if candidates.len() == 1 {
+        return Ok(candidates.remove(0));
+    }
+
+    for candidate in &candidates {
+        debug!("Probing {} for {}", candidate, riot_ipv6_addr);
+        let reachable = Command::new("ping")
+            .args(["-6", "-c", "1", "-W", "1"])
+            .arg(format!("{}%{}", riot_ipv6_addr, candidate))
+            .output()
+            .map(|output| output.status.success())
+            .unwrap_or(false);
+        if reachable {
+            return Ok(candidate.clone());
+        }
+    }
+    Err(format!(
+        "Failed to find the network interface which reaches {} (tried: {}), \
+         set it using HOST_NET_IF or --host-network-interface",
+        riot_ipv6_addr,
+        candidates.join(", ")
+    ))
+}
+
+fn route_interface(riot_ipv6_addr: &str) -> Result<String, String> {
+    device_field(&["-6", "route", "get", riot_ipv6_addr])?.ok_or_else(|| {
+        format!(
+            "No route to {}, set the network interface using HOST_NET_IF or \
+             --host-network-interface",
+            riot_ipv6_addr
+        )
+    })
+}
+
+/// Runs `ip` with the arguments and returns the interface following `dev`
+/// in its output, see `man 8 ip`.
+fn device_field(args: &[&str]) -> Result<Option<String>, String> {
+    let output = Command::new("ip")
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run ip: {}", e))?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let fields = stdout.split_whitespace().collect::<Vec<&str>>();
+    Ok(fields
+        .windows(2)
+        .find(|pair| pair[0] == "dev")
+        .map(|pair| pair[1].to_string()))
+}
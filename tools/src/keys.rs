@@ -0,0 +1,203 @@
+use std::{
+    env, fs,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use ed25519_dalek::{
+    pkcs8::{spki::der::pem::LineEnding, DecodePublicKey, EncodePrivateKey, EncodePublicKey},
+    Signature, SigningKey, Verifier, VerifyingKey, SECRET_KEY_LENGTH,
+};
+use log::debug;
+
+use crate::{
+    suit::{load_signing_key, resign_envelope},
+    transport::{send_request, TransportOptions},
+};
+
+/// Store of the ed25519 keypairs used for signing the SUIT manifests.
+///
+/// ```text
+/// keys/
+///   default.pem      <- private key (PKCS#8), same format as the keys of RIOT
+///   default.pub.der  <- public key (trust anchor installed on the devices)
+///   ACTIVE           <- name of the key currently used for signing
+/// ```
+///
+/// The location of the store can be overridden by setting the environment
+/// variable "MIBPF_KEY_DIR". If the store doesn't have an active key, the
+/// manifests are signed using the default key of the RIOT build system.
+pub struct KeyStore {
+    root: PathBuf,
+}
+
+impl KeyStore {
+    pub fn new(root: &str) -> Self {
+        KeyStore {
+            root: PathBuf::from(root),
+        }
+    }
+
+    pub fn default_root() -> String {
+        env::var("MIBPF_KEY_DIR").unwrap_or_else(|_| "keys".to_string())
+    }
+
+    pub fn private_key(&self, name: &str) -> PathBuf {
+        self.root.join(format!("{}.pem", name))
+    }
+
+    pub fn public_key(&self, name: &str) -> PathBuf {
+        self.root.join(format!("{}.pub.der", name))
+    }
+
+    /// Generates a new ed25519 keypair and returns the path to its private key.
+    pub fn generate(&self, name: &str) -> Result<PathBuf, String> {
+        fs::create_dir_all(&self.root)
+            .map_err(|e| format!("Failed to create the key directory: {}", e))?;
+
+        let private_key = self.private_key(name);
+        if private_key.exists() {
+            return Err(format!("The key {} already exists", private_key.display()));
+        }
+
+        let mut secret = [0u8; SECRET_KEY_LENGTH];
+        fs::File::open("/dev/urandom")
+            .and_then(|mut random| random.read_exact(&mut secret))
+            .map_err(|e| format!("Failed to generate the key: {}", e))?;
+        let key = SigningKey::from_bytes(&secret);
+        key.write_pkcs8_pem_file(&private_key, LineEnding::LF)
+            .map_err(|e| format!("Failed to write {}: {}", private_key.display(), e))?;
+        let public_key = self.public_key(name);
+        key.verifying_key()
+            .write_public_key_der_file(&public_key)
+            .map_err(|e| format!("Failed to write {}: {}", public_key.display(), e))?;
+
+        debug!("Generated the signing key: {}", private_key.display());
+        Ok(private_key)
+    }
+
+    /// Returns the name of the key currently used for signing.
+    pub fn active_key(&self) -> Option<String> {
+        fs::read_to_string(self.root.join("ACTIVE"))
+            .ok()
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+    }
+
+    pub fn set_active(&self, name: &str) -> Result<(), String> {
+        if !self.private_key(name).exists() {
+            return Err(format!("No key named {} in {}", name, self.root.display()));
+        }
+        fs::write(self.root.join("ACTIVE"), name)
+            .map_err(|e| format!("Failed to activate the key {}: {}", name, e))
+    }
+
+    /// Returns the raw public key that is installed on the devices as the
+    /// trust anchor.
+    pub fn public_key_bytes(&self, name: &str) -> Result<Vec<u8>, String> {
+        Ok(load_public_key(&self.public_key(name))?.to_bytes().to_vec())
+    }
+}
+
+/// Re-signs all signed manifests present in the CoAP root directory with the
+/// given key so that the artifacts which haven't been pulled by the devices
+/// yet remain valid after the rotation. The existing authentication wrapper of
+/// each envelope is replaced. Returns the list of re-signed files.
+pub fn resign_artifacts(coaproot_dir: &str, private_key: &Path) -> Result<Vec<String>, String> {
+    let key = load_signing_key(path_str(private_key)?)?;
+    let entries = fs::read_dir(coaproot_dir)
+        .map_err(|e| format!("Failed to read the directory {}: {}", coaproot_dir, e))?;
+
+    let mut resigned = vec![];
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        let manifest = path_str(&path)?;
+        if !manifest.ends_with(".signed") {
+            continue;
+        }
+
+        let envelope = fs::read(&path)
+            .map_err(|e| format!("Failed to read the manifest {}: {}", manifest, e))?;
+        let envelope = resign_envelope(&envelope, &key)
+            .map_err(|e| format!("Failed to re-sign {}: {}", manifest, e))?;
+        fs::write(&path, envelope)
+            .map_err(|e| format!("Failed to write the manifest {}: {}", manifest, e))?;
+        resigned.push(manifest.to_string());
+    }
+    Ok(resigned)
+}
+
+/// Installs the public key as the new trust anchor on devices that support
+/// key updates. The request needs to be authorized, and so it should be sent
+/// using OSCORE or an access token (see [`TransportOptions`]).
+pub fn provision_trust_anchor(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    public_key: &[u8],
+    transport: &TransportOptions,
+) -> Result<(), String> {
+    let url = format!(
+        "coap://[{}%{}]/suit/trust-anchor",
+        riot_ipv6_addr, host_network_interface
+    );
+    let payload = public_key
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+
+    let output = send_request("PUT", &url, Some(&payload), transport)?;
+
+    if output.stderr.len() > 0 {
+        let stderr = String::from_utf8(output.stderr)
+            .map_err(|e| format!("Failed to parse the stderr: {}", e))?;
+        Err(format!("aiocoap-client failed with: {}", stderr))?
+    }
+    Ok(())
+}
+
+pub(crate) fn path_str(path: &Path) -> Result<&str, String> {
+    path.to_str()
+        .ok_or_else(|| format!("Invalid path: {}", path.display()))
+}
+
+/// Verifies the hex-encoded ed25519 signature of the message using the DER
+/// public key.
+pub(crate) fn verify_signature(
+    message: &[u8],
+    signature: &str,
+    public_key: &Path,
+) -> Result<(), String> {
+    if signature.len() % 2 != 0 || !signature.is_ascii() {
+        return Err("malformed signature".to_string());
+    }
+    let signature = (0..signature.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&signature[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|e| format!("malformed signature: {}", e))?;
+
+    let signature =
+        Signature::from_slice(&signature).map_err(|e| format!("malformed signature: {}", e))?;
+    load_public_key(public_key)?
+        .verify(message, &signature)
+        .map_err(|_| "the signature doesn't match".to_string())
+}
+
+/// Loads the ed25519 public key stored in the DER format.
+fn load_public_key(path: &Path) -> Result<VerifyingKey, String> {
+    VerifyingKey::read_public_key_der_file(path)
+        .map_err(|e| format!("Failed to read the public key {}: {}", path.display(), e))
+}
+
+/// Temporary file for passing the data to the aiocoap-client, unique across
+/// the threads of the process.
+pub(crate) fn scratch_file(extension: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    env::temp_dir().join(format!(
+        "mibpf-{}-{}.{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed),
+        extension
+    ))
+}
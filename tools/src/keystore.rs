@@ -0,0 +1,197 @@
+use std::{fmt, str::FromStr};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::transport::{send_request, TransportOptions};
+
+/// Types of the keys that can be stored in the keystore of the device and
+/// used by the programs through the crypto (PSA) helpers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyType {
+    #[serde(rename = "aes-128")]
+    Aes128,
+    #[serde(rename = "aes-256")]
+    Aes256,
+    #[serde(rename = "hmac-sha256")]
+    HmacSha256,
+    #[serde(rename = "ecdsa-p256")]
+    EcdsaP256,
+}
+
+impl KeyType {
+    /// Expected length of the key material in bytes, None if the length
+    /// isn't fixed.
+    pub fn material_len(&self) -> Option<usize> {
+        match self {
+            KeyType::Aes128 => Some(16),
+            KeyType::Aes256 => Some(32),
+            KeyType::HmacSha256 => None,
+            KeyType::EcdsaP256 => Some(32),
+        }
+    }
+}
+
+impl FromStr for KeyType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "aes-128" => Ok(KeyType::Aes128),
+            "aes-256" => Ok(KeyType::Aes256),
+            "hmac-sha256" => Ok(KeyType::HmacSha256),
+            "ecdsa-p256" => Ok(KeyType::EcdsaP256),
+            _ => Err(format!("Unknown key type: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for KeyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            KeyType::Aes128 => "aes-128",
+            KeyType::Aes256 => "aes-256",
+            KeyType::HmacSha256 => "hmac-sha256",
+            KeyType::EcdsaP256 => "ecdsa-p256",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Key stored in the keystore of the device as listed by `GET /keystore`:
+/// `[{"handle": 1, "type": "aes-128"}]`. The key material never leaves the
+/// device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyInfo {
+    pub handle: u32,
+    #[serde(rename = "type")]
+    pub key_type: KeyType,
+}
+
+/// Returned when the program is to be executed with key handles which don't
+/// exist in the keystore of the device. Without this check the crypto helpers
+/// fail inside the program and the execution only reports an opaque result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingKeys {
+    pub device: String,
+    pub handles: Vec<u32>,
+}
+
+impl fmt::Display for MissingKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let handles = self
+            .handles
+            .iter()
+            .map(|handle| handle.to_string())
+            .collect::<Vec<String>>();
+        write!(
+            f,
+            "Missing keys: the keystore of the device {} doesn't contain the keys \
+             with handles {}, provision them using `keystore provision` first.",
+            self.device,
+            handles.join(", ")
+        )
+    }
+}
+
+impl From<MissingKeys> for String {
+    fn from(missing: MissingKeys) -> Self {
+        missing.to_string()
+    }
+}
+
+/// Stores the key material in the keystore of the device under the given
+/// handle. The request carries the raw key material, and so it should be
+/// sent using OSCORE (see [`TransportOptions`]).
+pub fn provision_key(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    handle: u32,
+    key_type: KeyType,
+    material: &[u8],
+    transport: &TransportOptions,
+) -> Result<(), String> {
+    if let Some(expected) = key_type.material_len() {
+        if material.len() != expected {
+            return Err(format!(
+                "Invalid {} key: expected {} bytes of key material, got {}",
+                key_type,
+                expected,
+                material.len()
+            ));
+        }
+    }
+    if material.is_empty() {
+        return Err("The key material is empty".to_string());
+    }
+
+    let url = format!(
+        "coap://[{}%{}]/keystore",
+        riot_ipv6_addr, host_network_interface
+    );
+    let payload = json!({
+        "handle": handle,
+        "type": key_type,
+        "material": material.iter().map(|b| format!("{:02x}", b)).collect::<String>(),
+    })
+    .to_string();
+    debug!("Provisioning the {} key {} into {}", key_type, handle, url);
+
+    let output = send_request("PUT", &url, Some(&payload), transport)?;
+
+    if output.stderr.len() > 0 {
+        let stderr = String::from_utf8(output.stderr)
+            .map_err(|e| format!("Failed to parse the stderr: {}", e))?;
+        Err(format!("aiocoap-client failed with: {}", stderr))?
+    }
+    Ok(())
+}
+
+/// Lists the keys present in the keystore of the device.
+pub fn list_keys(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    transport: &TransportOptions,
+) -> Result<Vec<KeyInfo>, String> {
+    let url = format!(
+        "coap://[{}%{}]/keystore",
+        riot_ipv6_addr, host_network_interface
+    );
+    debug!("Listing the keystore: {}", url);
+
+    let output = send_request("GET", &url, None, transport)?;
+
+    if output.stderr.len() > 0 {
+        let stderr = String::from_utf8(output.stderr)
+            .map_err(|e| format!("Failed to parse the stderr: {}", e))?;
+        Err(format!("aiocoap-client failed with: {}", stderr))?
+    }
+
+    let response = String::from_utf8(output.stdout)
+        .map_err(|e| format!("Failed to parse the response: {}", e))?;
+
+    serde_json::from_str::<Vec<KeyInfo>>(response.trim_matches(char::from(0)))
+        .map_err(|e| format!("Failed to parse the keystore listing: {}", e))
+}
+
+/// Checks that all key handles passed to the program refer to the keys
+/// present in the keystore.
+pub fn check_key_handles(
+    device: &str,
+    keys: &[KeyInfo],
+    handles: &[u32],
+) -> Result<(), MissingKeys> {
+    let missing = handles
+        .iter()
+        .filter(|handle| !keys.iter().any(|key| key.handle == **handle))
+        .copied()
+        .collect::<Vec<u32>>();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    Err(MissingKeys {
+        device: device.to_string(),
+        handles: missing,
+    })
+}
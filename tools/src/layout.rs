@@ -0,0 +1,209 @@
+use std::fmt;
+
+use goblin::elf::{
+    section_header::{SHF_ALLOC, SHF_EXECINSTR},
+    Elf,
+};
+use micro_bpf_common::{BinaryFileLayout, TargetVM};
+
+/// Relocation of the call of a function which isn't PC-relative.
+const R_BPF_64_32: u32 = 10;
+
+/// Properties of the programs that can be represented using a binary layout.
+/// The post-processing step silently drops everything that the layout can't
+/// represent, the binaries of such programs would fail on the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayoutConstraints {
+    pub layout: BinaryFileLayout,
+    /// Prefixes of the sections other than .text that the layout carries
+    /// over, None if all sections are preserved.
+    pub sections: Option<&'static [&'static str]>,
+    /// Set if the calls of the functions which aren't PC-relative get
+    /// resolved.
+    pub function_calls: bool,
+    /// VMs which are able to load the binaries using the layout.
+    pub vms: &'static [TargetVM],
+}
+
+impl LayoutConstraints {
+    pub const fn of(layout: BinaryFileLayout) -> Self {
+        match layout {
+            BinaryFileLayout::OnlyTextSection => LayoutConstraints {
+                layout,
+                sections: Some(&[]),
+                function_calls: false,
+                vms: &[TargetVM::Rbpf],
+            },
+            BinaryFileLayout::FemtoContainersHeader => LayoutConstraints {
+                layout,
+                sections: Some(&[".data", ".rodata"]),
+                function_calls: false,
+                vms: &[TargetVM::Rbpf, TargetVM::FemtoContainer],
+            },
+            BinaryFileLayout::ExtendedHeader => LayoutConstraints {
+                layout,
+                sections: Some(&[".data", ".rodata"]),
+                function_calls: true,
+                vms: &[TargetVM::Rbpf],
+            },
+            // The relocations are resolved on the device.
+            BinaryFileLayout::RawObjectFile => LayoutConstraints {
+                layout,
+                sections: None,
+                function_calls: true,
+                vms: &[TargetVM::Rbpf],
+            },
+        }
+    }
+
+    fn preserves_section(&self, name: &str) -> bool {
+        self.sections.map_or(true, |prefixes| {
+            prefixes.iter().any(|p| name.starts_with(p))
+        })
+    }
+}
+
+/// Single property of the program that the layout or the VM doesn't support.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Incompatibility {
+    /// The VM isn't able to load the binaries using the layout.
+    UnsupportedVm,
+    /// The layout drops the section that the program uses.
+    UnsupportedSection {
+        name: String,
+        size: u64,
+    },
+    /// The layout doesn't resolve the call of the function at the offset
+    /// (in bytes) of the .text section.
+    UnresolvedCall {
+        offset: u64,
+        function: String,
+    },
+    InvalidObjectFile(String),
+}
+
+impl fmt::Display for Incompatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Incompatibility::UnsupportedVm => write!(f, "the VM can't load the layout"),
+            Incompatibility::UnsupportedSection { name, size } => write!(
+                f,
+                "the {} section ({} bytes) isn't included in the binary",
+                name, size
+            ),
+            Incompatibility::UnresolvedCall { offset, function } => write!(
+                f,
+                "the call of {} at offset {} isn't PC-relative and won't be resolved",
+                function, offset
+            ),
+            Incompatibility::InvalidObjectFile(e) => write!(f, "invalid object file: {}", e),
+        }
+    }
+}
+
+/// Returned when the program can't be deployed using the binary layout to
+/// the VM, lists everything that is incompatible.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutConstraintViolation {
+    pub layout: BinaryFileLayout,
+    pub target: TargetVM,
+    pub incompatibilities: Vec<Incompatibility>,
+}
+
+impl fmt::Display for LayoutConstraintViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Layout constraint violation: the program can't be deployed to the {} VM \
+             using the {:?} layout:",
+            self.target, self.layout
+        )?;
+        for incompatibility in &self.incompatibilities {
+            write!(f, "\n  - {}", incompatibility)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<LayoutConstraintViolation> for String {
+    fn from(violation: LayoutConstraintViolation) -> Self {
+        violation.to_string()
+    }
+}
+
+/// Checks that the compiled object file can be represented using the binary
+/// layout and that the target VM is able to load it.
+pub fn validate(
+    layout: BinaryFileLayout,
+    target: TargetVM,
+    object: &[u8],
+) -> Result<(), LayoutConstraintViolation> {
+    let constraints = LayoutConstraints::of(layout);
+    let mut incompatibilities = vec![];
+    if !constraints.vms.contains(&target) {
+        incompatibilities.push(Incompatibility::UnsupportedVm);
+    }
+    match Elf::parse(object) {
+        Ok(binary) => incompatibilities.extend(check_object(&constraints, &binary)),
+        Err(e) => incompatibilities.push(Incompatibility::InvalidObjectFile(e.to_string())),
+    }
+
+    if incompatibilities.is_empty() {
+        return Ok(());
+    }
+    Err(LayoutConstraintViolation {
+        layout,
+        target,
+        incompatibilities,
+    })
+}
+
+fn check_object(constraints: &LayoutConstraints, binary: &Elf<'_>) -> Vec<Incompatibility> {
+    let section_name = |index: usize| {
+        binary
+            .section_headers
+            .get(index)
+            .and_then(|section| binary.shdr_strtab.get_at(section.sh_name))
+            .unwrap_or_default()
+    };
+
+    let mut incompatibilities = vec![];
+    for (index, section) in binary.section_headers.iter().enumerate() {
+        let name = section_name(index);
+        let is_data = section.sh_flags & SHF_ALLOC as u64 != 0
+            && section.sh_flags & SHF_EXECINSTR as u64 == 0;
+        if is_data && section.sh_size > 0 && !constraints.preserves_section(name) {
+            incompatibilities.push(Incompatibility::UnsupportedSection {
+                name: name.to_string(),
+                size: section.sh_size,
+            });
+        }
+    }
+
+    if constraints.function_calls {
+        return incompatibilities;
+    }
+    for (index, relocations) in &binary.shdr_relocs {
+        let Some(target) = binary.section_headers.get(*index) else {
+            continue;
+        };
+        if !section_name(target.sh_info as usize).starts_with(".text") {
+            continue;
+        }
+        for relocation in relocations.iter() {
+            if relocation.r_type != R_BPF_64_32 {
+                continue;
+            }
+            let function = binary
+                .syms
+                .get(relocation.r_sym)
+                .and_then(|symbol| binary.strtab.get_at(symbol.st_name))
+                .unwrap_or("?");
+            incompatibilities.push(Incompatibility::UnresolvedCall {
+                offset: relocation.r_offset,
+                function: function.to_string(),
+            });
+        }
+    }
+    incompatibilities
+}
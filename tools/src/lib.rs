@@ -8,21 +8,190 @@ extern crate env_logger;
 extern crate rbpf;
 extern crate micro_bpf_common;
 
+mod aggregate;
 mod args;
+mod artifacts;
+mod audit;
+mod bench;
+mod bytecode;
+mod capabilities;
+mod catalogue;
+mod chain;
+mod collect;
 mod compile;
+mod completion;
+mod compression;
+mod conformance;
+mod context;
+mod contracts;
+mod decompile;
 mod deploy;
+mod device;
+mod diff;
+mod directory;
+mod endpoints;
 mod execute;
+mod exit;
+mod experiment;
+mod firmware;
+mod flash;
+mod fleet;
+mod helpers;
+mod history;
+mod hooks;
+mod identity;
+mod interface;
+mod keys;
+mod keystore;
+mod layout;
+mod lint;
+mod logs;
+mod manifest;
+mod microbench;
+mod pipeline;
+mod proxy;
 mod pull;
+mod queue;
+mod quota;
+mod ratelimit;
+mod record;
+mod registry;
+mod report;
+pub mod request;
+mod rollout;
 mod postprocessing;
+mod power;
+mod provenance;
+mod publish;
 mod sign;
+mod simulate;
+mod slots;
+mod stress;
+mod suit;
+mod sweep;
+#[cfg(feature = "telemetry")]
+mod telemetry;
 mod environment;
+mod oscore;
+mod token;
+mod transport;
+#[cfg(feature = "tui")]
+mod tui;
+mod verify_server;
+mod watchdog;
+mod webhook;
 
-pub use compile::compile;
-pub use deploy::deploy;
-pub use execute::execute;
+pub use aggregate::{aggregate, FieldStats, FleetReport};
+pub use artifacts::{get_object_file_name, ArtifactStore};
+pub use audit::{AuditEntry, AuditIssue, AuditLog};
+pub use bench::{bench, BenchReport, BenchSample, BenchmarkTimes};
+pub use catalogue::{
+    diff_catalogue, find_helper_tables, generate_catalogue, load_helper_definitions,
+    parse_helper_table, regenerate_catalogue, CatalogueDiff, HelperDefinition,
+};
+pub use chain::{execute_chain, ChainStep, StateEncoding};
+pub use collect::{
+    collect, parse_sample, schedule, CollectConfig, Sample, SampleFormat, SampleWriter,
+};
+pub use compile::{compile, COMPILATION_FAILED};
+pub use completion::{candidates, with_dynamic_completion, CompletionKind};
+pub use compression::{negotiate_compression, ArtifactCompression};
+pub use conformance::{
+    run_conformance, Category, ConformanceProgram, ConformanceResult, Scorecard, PROGRAMS,
+};
+pub use context::generate_context_header;
+pub use contracts::{
+    run_contracts, untested_helpers, ContractOutcome, ContractReport, ContractResult,
+    HelperContract, Invariant, CONTRACTS,
+};
+pub use deploy::{
+    build_layouts, deploy, deploy_bundle, deploy_layouts, remove_dead_code, resume_deploy,
+    DeployOptions, DeployPhase, DeployState, DeployTimings, ALL_LAYOUTS,
+};
+pub use bytecode::Instruction;
+pub use device::{format_device_info, query_device_info, reboot_device, reset_vm};
+pub use capabilities::query_capabilities;
+pub use decompile::{decompile, decompile_artifact, DecompiledFunction, Pseudocode};
+pub use diff::{diff_artifacts, ArtifactDiff, DiffEntry};
+pub use directory::lookup_endpoint;
+pub use endpoints::{parse_resource_paths, Endpoints};
+pub use execute::{execute, ExecuteOptions, ExecutionArguments, PacketInjection};
+pub use exit::{exit_with_error, FailureClass, ASSERTION_MISMATCH};
+pub use experiment::{
+    run_experiment, DeviceSpec, ExperimentReport, ExperimentRow, ExperimentSpec, Preset, VmSpec,
+};
+pub use firmware::FirmwareBuild;
+pub use flash::{flash, wait_for_device, FlashOptions};
+pub use fleet::{
+    device_clock, execute_synchronized, Device, Fleet, FleetProgram, FleetResult, FleetWatcher,
+    ScheduleOptions,
+};
+pub use helpers::{
+    load_helper_profile, parse_helper, query_helpers, required_helpers, resolve_helpers,
+    HelperCompatibility,
+};
+pub use history::{
+    parse_date, record_execution, summarize, ExecutionRecord, ResultFilter, ResultHistory,
+    ResultSummary,
+};
+pub use hooks::{attach_program, detach_program, format_hooks, query_hooks};
+pub use identity::{is_unauthentic, IdentityStore, ResponseAuthenticity};
+pub use interface::{resolve_interface, AUTO_INTERFACE};
+pub use keys::{provision_trust_anchor, resign_artifacts, KeyStore};
+pub use keystore::{check_key_handles, list_keys, provision_key, KeyInfo, KeyType, MissingKeys};
+pub use layout::{validate, Incompatibility, LayoutConstraintViolation, LayoutConstraints};
+pub use lint::{lint, LintFinding, LintOptions, LintRule, Severity};
+pub use logs::{fetch_logs, print_logs, LogBatch};
+pub use pipeline::{Pipeline, Processor, Threshold, UnitConversion, WebhookNotifier};
+pub use proxy::{run_proxy, FaultConfig};
+pub use queue::{is_busy, wait_in_queue, ExecutionQueue, QueueEntry, QueueTicket};
+pub use quota::{SlotAccess, SlotGuard, SlotPolicy, UserQuota};
 pub use pull::pull;
-pub use postprocessing::apply_postprocessing;
+pub use ratelimit::RateLimits;
+pub use record::{ResultRecorder, RECORD_FILES_KEPT};
+pub use manifest::{generate_manifest, ManifestComponent};
+pub use microbench::{
+    bench_helpers, parse_vm, HelperLatency, HelperLatencyReport, MicroBenchmark,
+    CALLS_PER_EXECUTION, MICROBENCHMARKS,
+};
+pub use postprocessing::{apply_postprocessing, artifact_layout, program_metadata};
+pub use power::{Measurement, PowerProbe, ScriptProbe};
+pub use provenance::{GitRevision, Provenance};
+pub use publish::{
+    parse_publisher, CoapPublisher, HttpPublisher, LocalPublisher, Publisher, S3Publisher,
+};
 pub use sign::sign;
+#[cfg(feature = "telemetry")]
+pub use telemetry::TelemetrySink;
+#[cfg(feature = "tui")]
+pub use tui::{run_dashboard, DashboardConfig};
+pub use simulate::{
+    load_scenario, parse_memory_regions, simulate, AccessKind, HelperCall, HelperScript,
+    MemoryRegion, MemoryViolation, SimulationReport, StubConfig,
+};
+pub use registry::{
+    DeviceRecord, Registry, SequenceNumberConflict, SlotRecord, HAMMERING_THRESHOLD,
+    HAMMERING_WINDOW,
+};
+pub use report::{format_helper_trace, ExecutionReport};
+pub use request::build_execution_request;
+pub use rollout::{
+    roll_out, RolloutOutcome, RolloutProgram, RolloutReport, RolloutStatus, RolloutStrategy,
+};
+pub use stress::{run_stress, StressConfig, StressReport, StressStep};
+pub use suit::{build_manifest, resign_envelope, sign_manifest, SuitPayload};
+pub use sweep::{run_sweep, ExecutionTemplate, ParameterGrid, SweepRow, SweepSpec};
+pub use slots::{
+    allocate_slot, backup_slot, query_slots, restore_slot, slot_stats, verify_installed_artifact,
+    verify_stored_artifact, wait_for_installation, AllocationPolicy, SlotInfo, SlotStats, SuitSlot,
+};
 
-pub use environment::{Environment, load_env};
+pub use environment::{Configuration, ConfigSource, Environment, ResolvedValue, load_env};
+pub use oscore::SecurityContextStore;
+pub use token::{issue_token, refresh_token, AccessToken};
+pub use transport::{cancel_on_interrupt, is_cancelled, send_request, TransportOptions, CANCELLED};
+pub use verify_server::{
+    run_verify_server, verify_object, Verdict, VerdictFinding, VerifiedArtifact, VerifyOptions,
+};
+pub use watchdog::{probe_health, supervise_fleet, watch, WatchdogConfig};
 
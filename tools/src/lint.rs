@@ -0,0 +1,283 @@
+use std::fmt;
+
+use micro_bpf_common::{HelperFunctionID, TargetVM};
+use micro_bpf_elf_utils::extract_section;
+
+use crate::{
+    bytecode::{decode, Instruction, CALL_OPCODE},
+    postprocessing::read_bytes_from_file,
+};
+
+/// Size of the stack available to the programs in the VMs.
+const STACK_SIZE: i16 = 512;
+/// Stack usage above which we warn about large stack arrays, they often end up
+/// overflowing the stack once the program grows.
+const LARGE_STACK_USAGE: i16 = 256;
+
+const STACK_POINTER: u8 = 10;
+/// Register holding the pointer to the execution context (e.g. the CoAP
+/// packet) when the program starts.
+const CONTEXT_POINTER: u8 = 1;
+
+const CLASS_LDX: u8 = 0x01;
+const CLASS_ST: u8 = 0x02;
+const CLASS_STX: u8 = 0x03;
+const CLASS_ALU64: u8 = 0x07;
+const OP_DIV: u8 = 0x30;
+const OP_MOD: u8 = 0x90;
+const OP_ADD: u8 = 0x00;
+const OP_MOV: u8 = 0xb0;
+/// Source operand bit, set if the source is a register.
+const SOURCE_REGISTER: u8 = 0x08;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The program is likely to misbehave on some targets.
+    Warning,
+    /// The program is going to be rejected or crash on the device.
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintRule {
+    /// 64-bit division and modulo aren't supported by the JIT compiler and
+    /// the Femto-Containers VM.
+    Division64,
+    /// The program uses a large part of (or exceeds) the VM stack.
+    StackUsage,
+    /// Accesses to the packet or the stack that aren't aligned to the size of
+    /// the access fault on the Cortex-M0 cores.
+    UnalignedAccess,
+    /// Calls to helpers that aren't in the allowed set or that don't exist.
+    DisallowedHelper,
+}
+
+/// A single problem found in the program.
+#[derive(Debug, Clone)]
+pub struct LintFinding {
+    pub rule: LintRule,
+    pub severity: Severity,
+    /// Index of the offending instruction in the .text section.
+    pub instruction: usize,
+    pub message: String,
+}
+
+impl fmt::Display for LintFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(
+            f,
+            "{} [{:?}] at instruction {}: {}",
+            severity, self.rule, self.instruction, self.message
+        )
+    }
+}
+
+/// Describes the environment that the program is going to run in.
+#[derive(Debug, Clone)]
+pub struct LintOptions {
+    pub target: TargetVM,
+    pub jit: bool,
+    /// Helpers that the program is allowed to call, all helpers are allowed
+    /// if the list is empty (same as when deploying).
+    pub allowed_helpers: Vec<u8>,
+}
+
+/// Checks the compiled object file for constructs known to break on the device
+/// VMs. The checks are heuristic: the register tracking used for finding the
+/// packet and stack accesses follows the instructions linearly and ignores
+/// jumps.
+pub fn lint(object_file: &str, options: &LintOptions) -> Result<Vec<LintFinding>, String> {
+    let bytes = read_bytes_from_file(object_file);
+    let text = extract_section(".text", &bytes)?;
+    Ok(lint_instructions(&decode(text)?, options))
+}
+
+pub fn lint_instructions(instructions: &[Instruction], options: &LintOptions) -> Vec<LintFinding> {
+    let mut findings = vec![];
+    let mut finding = |rule, severity, instruction: &Instruction, message: String| {
+        findings.push(LintFinding {
+            rule,
+            severity,
+            instruction: instruction.index,
+            message,
+        })
+    };
+
+    // Registers that point into the packet (or the execution context).
+    let mut packet_pointers = [false; 11];
+    packet_pointers[CONTEXT_POINTER as usize] = true;
+    let mut max_stack_usage = 0;
+
+    for instruction in instructions {
+        let class = instruction.opcode & 0x07;
+        let operation = instruction.opcode & 0xf0;
+
+        if class == CLASS_ALU64 && (operation == OP_DIV || operation == OP_MOD) {
+            if options.jit || options.target == TargetVM::FemtoContainer {
+                finding(
+                    LintRule::Division64,
+                    Severity::Warning,
+                    instruction,
+                    format!(
+                        "64-bit {} isn't supported by the {}, use 32-bit operands",
+                        instruction.mnemonic(),
+                        if options.jit {
+                            "JIT compiler"
+                        } else {
+                            "Femto-Containers VM"
+                        }
+                    ),
+                );
+            }
+        }
+
+        if let Some(size) = access_size(instruction) {
+            let base = if class == CLASS_LDX {
+                instruction.src
+            } else {
+                instruction.dst
+            };
+            if base == STACK_POINTER && instruction.offset < 0 {
+                let usage = -instruction.offset;
+                if usage > STACK_SIZE {
+                    finding(
+                        LintRule::StackUsage,
+                        Severity::Error,
+                        instruction,
+                        format!(
+                            "access at r10{} is outside of the {}B stack",
+                            instruction.offset, STACK_SIZE
+                        ),
+                    );
+                }
+                max_stack_usage = max_stack_usage.max(usage);
+            }
+            let aligned_base = base == STACK_POINTER
+                || packet_pointers.get(base as usize).copied().unwrap_or(false);
+            if aligned_base && instruction.offset % size != 0 {
+                finding(
+                    LintRule::UnalignedAccess,
+                    Severity::Warning,
+                    instruction,
+                    format!(
+                        "{}-byte access at r{}{:+} isn't aligned",
+                        size, base, instruction.offset
+                    ),
+                );
+            }
+        }
+
+        if instruction.is_helper_call() {
+            let id = instruction.immediate as u8;
+            let helper: Option<HelperFunctionID> =
+                num::FromPrimitive::from_i64(instruction.immediate);
+            if helper.is_none() {
+                finding(
+                    LintRule::DisallowedHelper,
+                    Severity::Warning,
+                    instruction,
+                    format!("call to an unknown helper {:#x}", instruction.immediate),
+                );
+            } else if !options.allowed_helpers.is_empty() && !options.allowed_helpers.contains(&id)
+            {
+                finding(
+                    LintRule::DisallowedHelper,
+                    Severity::Warning,
+                    instruction,
+                    format!(
+                        "call to the helper {:#x} which isn't in the allowed set",
+                        id
+                    ),
+                );
+            }
+        }
+
+        track_packet_pointers(instruction, &mut packet_pointers);
+    }
+
+    if max_stack_usage > LARGE_STACK_USAGE && max_stack_usage <= STACK_SIZE {
+        findings.push(LintFinding {
+            rule: LintRule::StackUsage,
+            severity: Severity::Warning,
+            instruction: 0,
+            message: format!(
+                "the program uses {}B of the {}B stack, consider moving large arrays \
+                 to the global storage",
+                max_stack_usage, STACK_SIZE
+            ),
+        });
+    }
+
+    findings
+}
+
+/// Prints the findings and fails if any of them are errors (or warnings if
+/// `deny_warnings` is set).
+pub fn report(findings: &[LintFinding], deny_warnings: bool) -> Result<(), String> {
+    for finding in findings {
+        println!("[lint] {}", finding);
+    }
+
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error || deny_warnings)
+        .count();
+    if errors > 0 {
+        return Err(format!(
+            "The lint found {} problem(s) in the program",
+            errors
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the size of the memory access performed by the instruction.
+fn access_size(instruction: &Instruction) -> Option<i16> {
+    let class = instruction.opcode & 0x07;
+    if class != CLASS_LDX && class != CLASS_ST && class != CLASS_STX {
+        return None;
+    }
+    Some(match instruction.opcode & 0x18 {
+        0x00 => 4,
+        0x08 => 2,
+        0x10 => 1,
+        _ => 8,
+    })
+}
+
+/// Follows the copies of the packet pointer: moves between registers, constant
+/// offsets and loads of pointers from the execution context (e.g. the payload
+/// pointer of the packet). Any other write to the register clears it.
+fn track_packet_pointers(instruction: &Instruction, packet_pointers: &mut [bool; 11]) {
+    let class = instruction.opcode & 0x07;
+    let dst = instruction.dst as usize;
+    let src = instruction.src as usize;
+    if dst >= packet_pointers.len() || src >= packet_pointers.len() {
+        return;
+    }
+
+    match class {
+        CLASS_ALU64 => {
+            let operation = instruction.opcode & 0xf0;
+            let register_source = instruction.opcode & SOURCE_REGISTER != 0;
+            packet_pointers[dst] = match operation {
+                OP_MOV if register_source => packet_pointers[src],
+                OP_ADD if !register_source => packet_pointers[dst],
+                _ => false,
+            };
+        }
+        CLASS_LDX => {
+            packet_pointers[dst] = packet_pointers[src] && instruction.opcode & 0x18 == 0x18;
+        }
+        0x04 | 0x00 => packet_pointers[dst] = false,
+        _ if instruction.opcode == CALL_OPCODE => {
+            // The helpers clobber the caller-saved registers r0-r5.
+            packet_pointers[..6].fill(false);
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,87 @@
+use std::{thread, time::Duration};
+
+use log::debug;
+use serde::Deserialize;
+
+use crate::transport::{is_cancelled, send_request, TransportOptions};
+
+/// A batch of log lines retrieved from the device.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct LogBatch {
+    /// Sequence number of the next line that the device will log, it is
+    /// passed back to the device to only retrieve the new lines.
+    pub next: u64,
+    pub lines: Vec<String>,
+}
+
+/// Retrieves the log lines printed by the programs running on the device
+/// (e.g. using `bpf_printf`). The device keeps the recent lines in a ring
+/// buffer and responds to `GET /logs?since=<seq>` with a JSON object of the
+/// form: `{"next": 42, "lines": ["...", "..."]}`. If the slot is specified,
+/// only the lines logged by the program in that slot are returned.
+pub fn fetch_logs(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    suit_storage_slot: Option<usize>,
+    since: u64,
+    transport: &TransportOptions,
+) -> Result<LogBatch, String> {
+    let mut url = format!(
+        "coap://[{}%{}]/logs?since={}",
+        riot_ipv6_addr, host_network_interface, since
+    );
+    if let Some(slot) = suit_storage_slot {
+        url.push_str(&format!("&slot={}", slot));
+    }
+
+    debug!("Fetching the logs: {}", url);
+    let output = send_request("GET", &url, None, transport)?;
+    if transport.dry_run {
+        return Ok(LogBatch::default());
+    }
+
+    if output.stderr.len() > 0 {
+        let stderr = String::from_utf8(output.stderr)
+            .map_err(|e| format!("Failed to parse the stderr: {}", e))?;
+        Err(format!("aiocoap-client failed with: {}", stderr))?
+    }
+
+    let response = String::from_utf8(output.stdout)
+        .map_err(|e| format!("Failed to parse the response: {}", e))?;
+    serde_json::from_str::<LogBatch>(response.trim_matches(char::from(0)))
+        .map_err(|e| format!("Failed to parse the log response: {}", e))
+}
+
+/// Prints the logs of the device, if `follow` is set the device is polled
+/// for new lines until the transport is cancelled (e.g. by Ctrl-C).
+pub fn print_logs(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    suit_storage_slot: Option<usize>,
+    follow: bool,
+    interval: Duration,
+    transport: &TransportOptions,
+) -> Result<(), String> {
+    let mut since = 0;
+    loop {
+        let batch = match fetch_logs(
+            riot_ipv6_addr,
+            host_network_interface,
+            suit_storage_slot,
+            since,
+            transport,
+        ) {
+            Err(e) if follow && is_cancelled(&e) => return Ok(()),
+            batch => batch?,
+        };
+        for line in &batch.lines {
+            println!("{}", line);
+        }
+        since = batch.next;
+
+        if !follow || transport.dry_run {
+            return Ok(());
+        }
+        thread::sleep(interval);
+    }
+}
@@ -4,36 +4,186 @@ extern crate env_logger;
 extern crate micro_bpf_common;
 extern crate rbpf;
 
+mod aggregate;
 mod args;
+mod artifacts;
+mod audit;
+mod bench;
+mod bytecode;
+mod capabilities;
+mod catalogue;
+mod chain;
+mod collect;
 mod compile;
+mod completion;
+mod compression;
+mod conformance;
+mod context;
+mod contracts;
+mod decompile;
 mod deploy;
+mod device;
+mod diff;
+mod directory;
+mod endpoints;
 mod environment;
 mod execute;
+mod exit;
+mod experiment;
+mod firmware;
+mod flash;
+mod fleet;
+mod helpers;
+mod history;
+mod hooks;
+mod identity;
+mod interface;
+mod keys;
+mod keystore;
+mod layout;
+mod lint;
+mod logs;
+mod manifest;
+mod microbench;
+mod oscore;
+mod pipeline;
 mod postprocessing;
+mod power;
+mod provenance;
+mod publish;
+mod proxy;
 mod pull;
+mod queue;
+mod quota;
+mod ratelimit;
+mod record;
+mod registry;
+mod report;
+mod request;
+mod rollout;
 mod sign;
+mod simulate;
+mod slots;
+mod stress;
+mod suit;
+mod sweep;
+#[cfg(feature = "telemetry")]
+mod telemetry;
+mod token;
+mod transport;
+#[cfg(feature = "tui")]
+mod tui;
+mod verify_server;
+mod watchdog;
+mod webhook;
 
-use std::str::FromStr;
+use std::{path::PathBuf, str::FromStr, time::Duration};
 
-use args::Action;
-use clap::Parser;
+use aggregate::aggregate;
+use args::{
+    Action, AuditAction, BenchMode, ConfigAction, ConformanceAction, DeviceAction, FleetAction,
+    HelpersAction, HookAction, KeysAction, KeystoreAction, QueueAction, ResultsAction, SlotAction,
+    TokenAction,
+};
+use artifacts::{get_object_file_name, ArtifactStore};
+use audit::{AuditEntry, AuditLog};
+use bench::bench;
+use catalogue::{
+    diff_catalogue, find_helper_tables, load_helper_definitions, regenerate_catalogue,
+};
+use chain::{execute_chain, ChainStep, StateEncoding};
+use clap::{CommandFactory, Parser};
+use collect::{collect, CollectConfig, SampleFormat, SampleWriter};
 use compile::compile;
-use deploy::deploy;
-use environment::load_env;
-use execute::execute;
+use completion::{candidates, with_dynamic_completion, CompletionKind};
+use compression::ArtifactCompression;
+use conformance::{run_conformance, Category, PROGRAMS};
+use context::generate_context_header;
+use contracts::{run_contracts, CONTRACTS};
+use deploy::{
+    deploy, deploy_bundle, deploy_layouts, remove_dead_code, resume_deploy, DeployOptions,
+    ALL_LAYOUTS,
+};
+use decompile::decompile_artifact;
+use device::{format_device_info, query_device_info, reboot_device, reset_vm};
+use directory::lookup_endpoint;
+use diff::diff_artifacts;
+use environment::{
+    config_file_path, env_file_path, load_env, set_cli_overrides, update_env_variable,
+    Configuration,
+};
+use execute::{execute, ExecuteOptions, ExecutionArguments, PacketInjection};
+use exit::{exit_with_error, FailureClass, ASSERTION_MISMATCH};
+use experiment::{run_experiment, ExperimentSpec, Preset};
+use firmware::FirmwareBuild;
+use flash::{flash, wait_for_device, FlashOptions};
+use fleet::{execute_synchronized, Fleet, ScheduleOptions};
+use helpers::{
+    parse_helper, query_helpers, required_helpers, resolve_helpers, HelperCompatibility,
+};
+use history::{parse_date, record_execution, summarize, ResultFilter, ResultHistory};
+use hooks::{attach_program, detach_program, format_hooks, query_hooks};
+use identity::IdentityStore;
+use interface::resolve_interface;
+use keys::{provision_trust_anchor, resign_artifacts, KeyStore};
+use keystore::{check_key_handles, list_keys, provision_key, KeyType};
+use lint::{lint, report, LintOptions};
+use logs::print_logs;
+use micro_bpf_elf_utils::ProgramMetadata;
+use microbench::{bench_helpers, parse_vm};
 use micro_bpf_common::{
-    BinaryFileLayout, ExecutionModel, HelperAccessListSource, HelperAccessVerification, TargetVM,
+    BinaryFileLayout, ContentFormat, ExecutionModel, ExecutionResponse, HelperAccessListSource,
+    HelperAccessVerification, NetworkHook, ReturnBuffer, StorageBackend, TargetArch, TargetVM,
 };
-use postprocessing::apply_postprocessing;
+use oscore::SecurityContextStore;
+use pipeline::Pipeline;
+use postprocessing::{apply_postprocessing, artifact_layout, read_bytes_from_file};
+use power::{PowerProbe, ScriptProbe};
+use publish::{parse_publisher, LocalPublisher, Publisher};
+use proxy::{device_address, parse_rate, run_proxy, FaultConfig};
 use pull::pull;
+use queue::ExecutionQueue;
+use quota::{SlotGuard, SlotPolicy};
+use request::build_execution_request;
+use record::ResultRecorder;
+use rollout::{roll_out, RolloutProgram, RolloutStrategy};
+use report::{format_buffer, format_helper_trace, ExecutionReport};
 use sign::sign;
+use simulate::{
+    load_scenario, parse_memory_regions, parse_stub_return_values, simulate, StubConfig,
+};
+use stress::{run_stress, StressConfig};
+use sweep::{run_sweep, SweepSpec};
+use slots::{backup_slot, query_slots, restore_slot, slot_stats, SuitSlot};
+use token::{issue_token, refresh_token, AccessToken};
+#[cfg(feature = "telemetry")]
+use telemetry::TelemetrySink;
+use transport::{cancel_on_interrupt, TransportOptions};
+#[cfg(feature = "tui")]
+use tui::{run_dashboard, DashboardConfig};
+use verify_server::run_verify_server;
+use watchdog::{supervise_fleet, watch, WatchdogConfig};
 
 #[tokio::main]
 async fn main() {
-    env_logger::init();
     let args = args::Args::parse();
+    if args.quiet {
+        env_logger::Builder::new()
+            .filter_level(log::LevelFilter::Off)
+            .init();
+    } else {
+        env_logger::init();
+    }
+
+    cancel_on_interrupt();
 
     let use_env = args.use_env;
+    if let Some(profile) = &args.profile {
+        std::env::set_var("DOTENV", format!(".env-{}", profile));
+    }
+    if let Err(e) = set_cli_overrides(&args.overrides) {
+        exit_with_error(&e, FailureClass::Other, args.quiet);
+    }
 
     let result = match &args.command {
         Action::Compile { .. } => handle_compile(&args.command, use_env),
@@ -41,12 +191,210 @@ async fn main() {
         Action::Sign { .. } => handle_sign(&args.command, use_env),
         Action::Pull { .. } => handle_pull(&args.command, use_env).await,
         Action::Execute { .. } => handle_execute(&args.command, use_env).await,
+        Action::Chain { .. } => handle_chain(&args.command, use_env).await,
         Action::Deploy { .. } => handle_deploy(&args.command, use_env).await,
+        Action::Lint { .. } => handle_lint(&args.command),
+        Action::Simulate { .. } => handle_simulate(&args.command),
+        Action::OscoreContext { .. } => handle_oscore_context(&args.command),
+        Action::PinIdentity { .. } => handle_pin_identity(&args.command),
+        Action::Device { action } => handle_device(action, use_env),
+        Action::Hook { action } => handle_hook(action, use_env),
+        Action::Keystore { action } => handle_keystore(action, use_env),
+        Action::Helpers { action } => handle_helpers(action, use_env),
+        Action::Token { action } => handle_token(action, use_env),
+        Action::Diff { .. } => handle_diff(&args.command),
+        Action::Decompile { .. } => handle_decompile(&args.command),
+        Action::GenContext { .. } => handle_gen_context(&args.command),
+        Action::List { .. } => handle_list(&args.command, use_env),
+        Action::Slot { action } => handle_slot(action, use_env),
+        Action::Queue { action } => handle_queue(action),
+        Action::Results { action } => handle_results(action),
+        Action::Bench { .. } => handle_bench(&args.command, use_env).await,
+        Action::Stress { .. } => handle_stress(&args.command, use_env),
+        Action::Conformance { action } => handle_conformance(action).await,
+        Action::Watchdog { .. } => handle_watchdog(&args.command).await,
+        Action::Collect { .. } => handle_collect(&args.command).await,
+        #[cfg(feature = "tui")]
+        Action::Tui { .. } => handle_tui(&args.command),
+        Action::Keys { action } => handle_keys(action, use_env),
+        Action::Audit { action } => handle_audit(action),
+        Action::Fleet { action } => handle_fleet(action, use_env).await,
+        Action::Logs { .. } => handle_logs(&args.command, use_env),
+        Action::Proxy { .. } => handle_proxy(&args.command, use_env),
+        Action::VerifyServer { listen, work_dir } => run_verify_server(listen, work_dir),
+        Action::Flash { .. } => handle_flash(&args.command, use_env),
+        Action::Experiment { .. } => handle_experiment(&args.command).await,
+        Action::Run { .. } => handle_run(&args.command).await,
+        Action::Sweep { .. } => handle_sweep(&args.command).await,
+        Action::Config { action } => handle_config(action),
+        Action::Completions { shell } => {
+            let mut command = args::Args::command();
+            let name = command.get_name().to_string();
+            let mut script = vec![];
+            clap_complete::generate(*shell, &mut command, name.clone(), &mut script);
+            let script = String::from_utf8_lossy(&script).to_string();
+            print!("{}", with_dynamic_completion(*shell, &name, script));
+            Ok(())
+        }
+        Action::Complete { kind, descriptions } => {
+            CompletionKind::from_str(kind).map(|kind| {
+                for (value, description) in candidates(kind) {
+                    if *descriptions && !description.is_empty() {
+                        println!("{}\t{}", value, description);
+                    } else {
+                        println!("{}", value);
+                    }
+                }
+            })
+        }
+        Action::Manpages { out_dir } => handle_manpages(out_dir),
     };
 
+    audit_command(&args.command, use_env, &result);
+    notify_webhook(args.webhook.as_deref(), &args.command, use_env, &result);
+
     if let Err(e) = result {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
+        exit_with_error(&e, command_failure_class(&args.command), args.quiet);
+    }
+}
+
+/// Records the commands changing the state of the devices in the audit log
+/// (if one is configured, see [`AuditLog`]). Failing to write the entry
+/// doesn't fail the command.
+fn audit_command(command: &Action, use_env: bool, result: &Result<(), String>) {
+    let Some(log) = AuditLog::from_env() else {
+        return;
+    };
+    let (entry, riot_ipv6_addr) = match command {
+        Action::Deploy {
+            bpf_source_file,
+            suit_storage_slot,
+            riot_ipv6_addr,
+            dry_run: false,
+            ..
+        } => (
+            AuditEntry::new("deploy", riot_ipv6_addr, result)
+                .with_slot(suit_storage_slot)
+                .with_program(bpf_source_file),
+            riot_ipv6_addr,
+        ),
+        Action::Pull {
+            suit_manifest,
+            suit_storage_slot,
+            riot_ipv6_addr,
+            ..
+        } => (
+            AuditEntry::new("pull", riot_ipv6_addr, result)
+                .with_slot(suit_storage_slot)
+                .with_program(suit_manifest),
+            riot_ipv6_addr,
+        ),
+        Action::Execute {
+            suit_storage_slot,
+            riot_ipv6_addr,
+            dry_run: false,
+            ..
+        } => (
+            AuditEntry::new("execute", riot_ipv6_addr, result).with_slot(suit_storage_slot),
+            riot_ipv6_addr,
+        ),
+        _ => return,
+    };
+    let device = if use_env {
+        load_env().riot_instance_ip
+    } else {
+        riot_ipv6_addr.clone()
+    };
+
+    let keys = KeyStore::new(&KeyStore::default_root());
+    let signing_key = AuditLog::signing_key();
+    let signer = signing_key.as_deref().map(|name| (&keys, name));
+    if let Err(e) = log.append(AuditEntry { device, ..entry }, signer) {
+        log::warn!("Failed to record the command in the audit log: {}", e);
+    }
+}
+
+/// Posts the summary of the deployment, execution or benchmark to the webhook
+/// given by --webhook (or WEBHOOK_URL of the device profile). Similar to the
+/// audit log, failing to notify doesn't fail the command.
+fn notify_webhook(
+    webhook: Option<&str>,
+    command: &Action,
+    use_env: bool,
+    result: &Result<(), String>,
+) {
+    let env = use_env.then(load_env);
+    let Some(url) = webhook
+        .map(str::to_string)
+        .or_else(|| env.as_ref().and_then(|env| env.webhook_url.clone()))
+    else {
+        return;
+    };
+    let (action, riot_ipv6_addr, slot, program) = match command {
+        Action::Deploy {
+            bpf_source_file,
+            suit_storage_slot,
+            riot_ipv6_addr,
+            dry_run: false,
+            ..
+        } => (
+            "deploy",
+            riot_ipv6_addr,
+            suit_storage_slot.to_string(),
+            Some(bpf_source_file.as_str()),
+        ),
+        Action::Execute {
+            suit_storage_slot,
+            riot_ipv6_addr,
+            dry_run: false,
+            ..
+        } => ("execute", riot_ipv6_addr, suit_storage_slot.to_string(), None),
+        Action::Bench {
+            suit_storage_slot,
+            riot_ipv6_addr,
+            ..
+        } => ("bench", riot_ipv6_addr, suit_storage_slot.to_string(), None),
+        _ => return,
+    };
+    let device = match env {
+        Some(env) => env.riot_instance_ip,
+        None => riot_ipv6_addr.clone(),
+    };
+
+    let summary = webhook::result_summary(action, &device, slot, program, result);
+    if let Err(e) = webhook::post_json(&url, &summary) {
+        log::warn!("Failed to notify the webhook: {}", e);
+    }
+}
+
+/// Class of the failures of the command which aren't recognised by their
+/// messages (see [`FailureClass::classify`]).
+fn command_failure_class(command: &Action) -> FailureClass {
+    match command {
+        Action::Compile { .. } | Action::Postprocessing { .. } | Action::Lint { .. } => {
+            FailureClass::Compilation
+        }
+        Action::Sign { .. }
+        | Action::Pull { .. }
+        | Action::Deploy { .. }
+        | Action::Slot { .. }
+        | Action::Flash { .. }
+        | Action::Fleet {
+            action: FleetAction::Deploy { .. },
+        } => FailureClass::Deployment,
+        Action::Execute { .. }
+        | Action::Chain { .. }
+        | Action::Simulate { .. }
+        | Action::Bench { .. }
+        | Action::Stress { .. }
+        | Action::Conformance { .. }
+        | Action::Watchdog { .. }
+        | Action::Collect { .. }
+        | Action::Fleet { .. }
+        | Action::Experiment { .. }
+        | Action::Run { .. }
+        | Action::Sweep { .. } => FailureClass::Execution,
+        _ => FailureClass::Other,
     }
 }
 
@@ -76,32 +424,50 @@ fn handle_sign(args: &Action, use_env: bool) -> Result<(), String> {
         coaproot_dir,
         binary_name,
         suit_storage_slot,
+        storage,
+        sequence_number,
+        compression,
     } = args
     else {
         return Err(format!("Invalid subcommand args: {:?}", args));
     };
 
-    if use_env {
+    let storage = StorageBackend::from_str(storage)?;
+    let compression = compression
+        .as_deref()
+        .map(ArtifactCompression::from_str)
+        .transpose()?;
+    let compressed = if use_env {
         let env = load_env();
 
-        return sign(
-            &env.host_net_if,
+        sign(
+            &resolve_interface(&env.riot_instance_ip, &env.host_net_if)?,
+            None,
             &env.board_name,
             &env.coap_root_dir,
             binary_name,
             *suit_storage_slot as usize,
+            storage,
+            *sequence_number,
+            compression,
+        )?
+    } else {
+        sign(
+            host_network_interface,
             None,
-        );
+            board_name,
+            coaproot_dir,
+            binary_name,
+            *suit_storage_slot as usize,
+            storage,
+            *sequence_number,
+            compression,
+        )?
+    };
+    if let Some(compressed) = compressed {
+        println!("The manifest points the device to {}", compressed);
     }
-
-    sign(
-        host_network_interface,
-        board_name,
-        coaproot_dir,
-        binary_name,
-        *suit_storage_slot as usize,
-        None,
-    )
+    Ok(())
 }
 
 async fn handle_pull(args: &Action, use_env: bool) -> Result<(), String> {
@@ -114,10 +480,13 @@ async fn handle_pull(args: &Action, use_env: bool) -> Result<(), String> {
         target,
         binary_layout,
         suit_storage_slot,
+        storage,
         helper_indices,
         helper_access_verification,
         helper_access_list_source,
         erase,
+        oscore_context_dir,
+        access_token,
     } = args
     else {
         return Err(format!("Invalid subcommand args: {:?}", args));
@@ -125,6 +494,7 @@ async fn handle_pull(args: &Action, use_env: bool) -> Result<(), String> {
 
     let target_vm = TargetVM::from_str(target.as_str())?;
     let binary_file_layout = binary_layout.as_str().parse::<BinaryFileLayout>()?;
+    let storage = StorageBackend::from_str(storage)?;
     let helper_access_verification =
         HelperAccessVerification::from_str(helper_access_verification.as_str())?;
     let helper_access_list_source =
@@ -132,6 +502,7 @@ async fn handle_pull(args: &Action, use_env: bool) -> Result<(), String> {
 
     if use_env {
         let env = load_env();
+        let transport = TransportOptions::from_env(&env);
 
         return pull(
             &env.riot_instance_ip,
@@ -142,14 +513,22 @@ async fn handle_pull(args: &Action, use_env: bool) -> Result<(), String> {
             target_vm,
             binary_file_layout,
             *suit_storage_slot as usize,
+            storage,
             helper_access_verification,
             helper_access_list_source,
             helper_indices,
             *erase,
+            &transport,
         )
         .await;
     }
 
+    let transport = TransportOptions {
+        oscore_context_dir: oscore_context_dir.clone(),
+        access_token: access_token.clone(),
+        ..Default::default()
+    };
+
     pull(
         riot_ipv6_addr,
         host_ipv6_addr,
@@ -159,13 +538,26 @@ async fn handle_pull(args: &Action, use_env: bool) -> Result<(), String> {
         target_vm,
         binary_file_layout,
         *suit_storage_slot as usize,
+        storage,
         helper_access_verification,
         helper_access_list_source,
         helper_indices,
         *erase,
+        &transport,
     )
     .await
 }
+
+/// Reads the build configuration of the firmware from the mibpf repo, by
+/// default the tools are assumed to be run from its root.
+fn firmware_build(use_env: bool) -> FirmwareBuild {
+    if use_env {
+        FirmwareBuild::detect(&load_env().micro_bpf_root_dir)
+    } else {
+        FirmwareBuild::detect(".")
+    }
+}
+
 async fn handle_execute(args: &Action, use_env: bool) -> Result<(), String> {
     let Action::Execute {
         riot_ipv6_addr,
@@ -175,64 +567,323 @@ async fn handle_execute(args: &Action, use_env: bool) -> Result<(), String> {
         host_network_interface,
         execution_model,
         helper_indices,
+        helpers,
         helper_access_verification,
         helper_access_list_source,
         jit,
         jit_compile,
         benchmark,
+        fallback_to_interpreter,
+        oscore_context_dir,
+        access_token,
+        dry_run,
+        stages,
+        report,
+        packet_payload,
+        packet_payload_file,
+        packet_queries,
+        arguments_file,
+        compress_arguments,
+        entry,
+        buffer_type,
+        gas_limit,
+        expect_result,
+        key_handles,
+        seed,
+        trace_helpers,
+        queue_timeout,
+        accept,
+        identity_dir,
     } = args
     else {
         return Err(format!("Invalid subcommand args: {:?}", args));
     };
 
-    let target_vm = TargetVM::from_str(target.as_str())?;
+    let mut pipeline = Pipeline::parse(stages)?;
+    let firmware = firmware_build(use_env);
+    let target_vm = firmware.target(target.as_deref())?;
     let execution_model = ExecutionModel::from_str(execution_model)?;
-    let binary_file_layout = binary_layout.as_str().parse::<BinaryFileLayout>()?;
+    let binary_file_layout = firmware.layout(binary_layout.as_deref(), target_vm)?;
     let helper_access_verification =
         HelperAccessVerification::from_str(helper_access_verification.as_str())?;
     let helper_access_list_source =
         HelperAccessListSource::from_str(helper_access_list_source.as_str())?;
+    let packet = packet_injection(packet_payload, packet_payload_file, packet_queries)?;
+    let arguments = match arguments_file {
+        Some(file) => Some(ExecutionArguments {
+            data: std::fs::read(file)
+                .map_err(|e| format!("Failed to read the arguments from {}: {}", file, e))?,
+            compress: *compress_arguments,
+        }),
+        None => None,
+    };
+    let helper_indices = resolve_helpers(helper_indices, helpers.as_deref())?;
+    let accept = accept
+        .iter()
+        .map(|format| ContentFormat::from_str(format))
+        .collect::<Result<Vec<ContentFormat>, String>>()?;
 
-    let response = if use_env {
+    let (riot_ipv6_addr, host_network_interface, transport) = if use_env {
         let env = load_env();
-        execute(
-            &env.riot_instance_ip,
-            target_vm,
-            binary_file_layout,
-            *suit_storage_slot as usize,
-            &env.host_net_if,
-            execution_model,
-            helper_access_verification,
-            helper_access_list_source,
-            helper_indices,
-            *jit,
-            *jit_compile,
-            *benchmark,
-        )
-        .await?
+        let transport = TransportOptions {
+            dry_run: *dry_run,
+            accept,
+            ..TransportOptions::from_env(&env)
+        };
+        (env.riot_instance_ip, env.host_net_if, transport)
     } else {
-        execute(
-            riot_ipv6_addr,
-            target_vm,
+        let transport = TransportOptions {
+            oscore_context_dir: oscore_context_dir.clone(),
+            access_token: access_token.clone(),
+            dry_run: *dry_run,
+            accept,
+            identity_dir: identity_dir.clone(),
+            ..Default::default()
+        };
+        (
+            riot_ipv6_addr.clone(),
+            host_network_interface.clone(),
+            transport,
+        )
+    };
+
+    let transport = TransportOptions {
+        queue_timeout: queue_timeout.map(Duration::from_secs).or(transport.queue_timeout),
+        ..transport
+    };
+
+    // Executing with a missing key would only surface as an opaque error
+    // code returned by the crypto helpers inside the program.
+    if !key_handles.is_empty() && !*dry_run {
+        let keys = list_keys(&riot_ipv6_addr, &host_network_interface, &transport)?;
+        check_key_handles(&riot_ipv6_addr, &keys, key_handles)?;
+    }
+
+    let response = execute(
+        &riot_ipv6_addr,
+        target_vm,
+        binary_file_layout,
+        *suit_storage_slot as usize,
+        &host_network_interface,
+        execution_model,
+        helper_access_verification,
+        helper_access_list_source,
+        &helper_indices,
+        *jit,
+        &ExecuteOptions {
+            jit_compile: *jit_compile,
+            benchmark: *benchmark,
+            fallback_to_interpreter: *fallback_to_interpreter,
+            packet: packet.as_ref(),
+            gas_limit: *gas_limit,
+            key_handles,
+            seed: *seed,
+            trace_helpers: *trace_helpers,
+            arguments: arguments.as_ref(),
+            entry: entry.as_deref(),
+        },
+        &transport,
+    )
+    .await?;
+
+    if *dry_run {
+        return Ok(());
+    }
+
+    #[cfg(feature = "telemetry")]
+    if use_env {
+        export_result(&riot_ipv6_addr, *suit_storage_slot as usize, &response);
+    }
+    // Same as for the audit log, failing to record the result doesn't fail
+    // the execution.
+    if let Err(e) = record_execution(&riot_ipv6_addr, *suit_storage_slot as usize, &response) {
+        log::warn!("Failed to record the execution result: {}", e);
+    }
+
+    if *report {
+        // The metadata of the program is only used to make the report more
+        // readable, so we don't fail if the device doesn't provide it.
+        let slot = *suit_storage_slot as usize;
+        let slot_info = query_slots(&riot_ipv6_addr, &host_network_interface, &transport)
+            .ok()
+            .and_then(|slots| slots.into_iter().find(|info| info.slot == slot));
+        let report = ExecutionReport::new(
+            slot_info.as_ref(),
+            slot,
             binary_file_layout,
-            *suit_storage_slot as usize,
-            host_network_interface,
-            execution_model,
-            helper_access_verification,
-            helper_access_list_source,
-            helper_indices,
+            target_vm,
             *jit,
-            *jit_compile,
-            *benchmark,
-        )
-        .await?
+            helper_indices,
+            &response,
+        );
+        println!("Execution report:\n{}", report);
+    } else {
+        println!("Response received: \n{}", response);
+    }
+
+    if let Some(element_type) = buffer_type {
+        let response =
+            serde_json::from_str::<ExecutionResponse>(response.trim_matches(char::from(0)))
+                .map_err(|e| format!("Failed to parse the execution response: {}", e))?;
+        let Some(buffer) = response.buffer else {
+            return Err("The program didn't return a buffer".to_string());
+        };
+        println!("Returned buffer: {}", format_buffer(&buffer, element_type)?);
+    }
+
+    if *trace_helpers {
+        let response =
+            serde_json::from_str::<ExecutionResponse>(response.trim_matches(char::from(0)))
+                .map_err(|e| format!("Failed to parse the execution response: {}", e))?;
+        match response.trace {
+            Some(trace) if trace.is_empty() => println!("The program didn't call any helpers"),
+            Some(trace) => println!("Helper calls:\n{}", format_helper_trace(&trace)),
+            None => {
+                return Err(
+                    "The device didn't return the helper trace (the firmware was most likely \
+                     built without the support for tracing)"
+                        .to_string(),
+                )
+            }
+        }
+    }
+
+    if !pipeline.is_empty() {
+        let processed = pipeline.run(&response)?;
+        println!("Processed response: \n{}", processed);
+    }
+
+    if let Some(expected) = expect_result {
+        let response =
+            serde_json::from_str::<ExecutionResponse>(response.trim_matches(char::from(0)))
+                .map_err(|e| format!("Failed to parse the execution response: {}", e))?;
+        if response.result != *expected {
+            return Err(format!(
+                "{}: expected the program to return {}, got {}",
+                ASSERTION_MISMATCH, expected, response.result
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the execution result into the time-series store configured by the
+/// device profile, the execution itself succeeded so failures are only
+/// reported as warnings.
+#[cfg(feature = "telemetry")]
+fn export_result(riot_ipv6_addr: &str, suit_storage_slot: usize, response: &str) {
+    let sink = match TelemetrySink::from_env(&load_env()) {
+        Ok(Some(sink)) => sink,
+        Ok(None) => return,
+        Err(e) => {
+            log::warn!("Invalid telemetry sink: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = sink.write_result(riot_ipv6_addr, suit_storage_slot, response) {
+        log::warn!("Failed to write the result into the telemetry sink: {}", e);
+    }
+}
+
+async fn handle_chain(args: &Action, use_env: bool) -> Result<(), String> {
+    let Action::Chain {
+        steps,
+        target,
+        binary_layout,
+        jit,
+        state_encoding,
+        initial_state,
+        riot_ipv6_addr,
+        host_network_interface,
+        oscore_context_dir,
+        access_token,
+    } = args
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", args));
+    };
+
+    let (riot_ipv6_addr, host_network_interface, transport) = device(
+        use_env,
+        riot_ipv6_addr,
+        host_network_interface,
+        oscore_context_dir,
+        access_token,
+    );
+    let steps = steps
+        .iter()
+        .map(|step| ChainStep::parse(step, &riot_ipv6_addr, &host_network_interface))
+        .collect::<Result<Vec<ChainStep>, String>>()?;
+    let initial_state = match initial_state {
+        Some(hex) => ReturnBuffer {
+            len: hex.len() / 2,
+            data: hex.clone(),
+        }
+        .bytes()
+        .map_err(|e| format!("Invalid initial state {}: {}", hex, e))?,
+        None => vec![],
     };
 
-    println!("Response received: \n{}", response);
+    let responses = execute_chain(
+        &steps,
+        TargetVM::from_str(target)?,
+        binary_layout.as_str().parse::<BinaryFileLayout>()?,
+        *jit,
+        StateEncoding::from_str(state_encoding)?,
+        &initial_state,
+        &transport,
+    )
+    .await?;
 
+    for (step, response) in steps.iter().zip(&responses) {
+        let state = response
+            .buffer
+            .as_ref()
+            .map(|buffer| format!("{} bytes of state", buffer.len))
+            .unwrap_or_else(|| "no state".to_string());
+        println!(
+            "{}: returned {} in {}us, {}",
+            step, response.result, response.execution_time, state
+        );
+    }
+    if let Some(buffer) = responses.last().and_then(|r| r.buffer.as_ref()) {
+        println!("Final state: {}", buffer.data);
+    }
     Ok(())
 }
 
+/// Collects the contents injected into the packet of the executed program,
+/// returns None if nothing is injected.
+fn packet_injection(
+    payload: &Option<String>,
+    payload_file: &Option<String>,
+    queries: &[String],
+) -> Result<Option<PacketInjection>, String> {
+    let payload = match (payload, payload_file) {
+        (Some(hex), _) => {
+            if hex.len() % 2 != 0 || !hex.is_ascii() {
+                return Err(format!("Invalid hex payload: {}", hex));
+            }
+            (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|e| format!("Invalid hex payload {}: {}", hex, e))?
+        }
+        (None, Some(file)) => std::fs::read(file)
+            .map_err(|e| format!("Failed to read the packet payload from {}: {}", file, e))?,
+        (None, None) => vec![],
+    };
+
+    if payload.is_empty() && queries.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(PacketInjection {
+        payload,
+        queries: queries.to_vec(),
+    }))
+}
+
 fn handle_postprocessing(args: &Action) -> Result<(), String> {
     let Action::Postprocessing {
         source_object_file,
@@ -240,6 +891,10 @@ fn handle_postprocessing(args: &Action) -> Result<(), String> {
         binary_layout,
         helper_indices,
         helper_access_verification,
+        program_name,
+        program_version,
+        eliminate_dead_code,
+        entry,
     } = args
     else {
         return Err(format!("Invalid subcommand args: {:?}", args));
@@ -255,13 +910,42 @@ fn handle_postprocessing(args: &Action) -> Result<(), String> {
         "a.bin"
     };
 
+    if entry.is_some() && *eliminate_dead_code {
+        return Err(
+            "The dead code can't be eliminated when the entry function is selected".to_string(),
+        );
+    }
+
+    let metadata = if program_name.is_some() || program_version.is_some() {
+        let default_name = source_object_file
+            .split("/")
+            .last()
+            .unwrap()
+            .split(".")
+            .nth(0)
+            .unwrap_or_default();
+        Some(ProgramMetadata::new(
+            program_name.as_deref().unwrap_or(default_name),
+            program_version.as_deref().unwrap_or("0.0.0"),
+            &read_bytes_from_file(source_object_file),
+        ))
+    } else {
+        None
+    };
+
     apply_postprocessing(
         source_object_file,
         binary_layout,
         file_name,
         helper_indices.to_vec(),
         helper_access_verification,
-    )
+        metadata,
+        entry.as_deref(),
+    )?;
+    if *eliminate_dead_code {
+        remove_dead_code(file_name, binary_layout)?;
+    }
+    Ok(())
 }
 
 async fn handle_deploy(args: &Action, use_env: bool) -> Result<(), String> {
@@ -272,37 +956,254 @@ async fn handle_deploy(args: &Action, use_env: bool) -> Result<(), String> {
         board_name,
         coaproot_dir,
         suit_storage_slot,
+        storage,
         riot_ipv6_addr,
         host_ipv6_addr,
         binary_layout,
         riot_network_interface,
         helper_indices,
+        helpers,
         helper_access_verification,
         helper_access_list_source,
         target,
         erase,
+        oscore_context_dir,
+        access_token,
+        dry_run,
+        sequence_number,
+        force_seq,
+        bundled_programs,
+        publish,
+        layouts,
+        show_timings,
+        deny_warnings,
+        compress_rodata,
+        artifact_compression,
+        eliminate_dead_code,
+        embed_metadata,
+        entries,
+        resume,
     } = args
     else {
         return Err(format!("Invalid subcommand args: {:?}", args));
     };
 
-    let target_vm = TargetVM::from_str(target.as_str())?;
-    let binary_layout = binary_layout.as_str().parse::<BinaryFileLayout>()?;
+    if *resume {
+        let (artifacts, transport) = if use_env {
+            let env = load_env();
+            let transport = TransportOptions {
+                dry_run: *dry_run,
+                ..TransportOptions::from_env(&env)
+            };
+            (ArtifactStore::from_env(&env), transport)
+        } else {
+            let transport = TransportOptions {
+                oscore_context_dir: oscore_context_dir.clone(),
+                access_token: access_token.clone(),
+                dry_run: *dry_run,
+                ..Default::default()
+            };
+            (ArtifactStore::new(out_dir, coaproot_dir), transport)
+        };
+        let publisher = artifact_publisher(publish, &artifacts.manifest_dir);
+        let (slot, timings) = resume_deploy(&artifacts, publisher.as_ref(), &transport).await?;
+        println!("Program deployed to SUIT storage slot {}", slot);
+        if *show_timings {
+            println!("{}", timings);
+        }
+        return Ok(());
+    }
+
+    let firmware = firmware_build(use_env);
+    let target_vm = firmware.target(target.as_deref())?;
+    let suit_storage_slot = SuitSlot::from_str(suit_storage_slot)?;
+    let storage = StorageBackend::from_str(storage)?;
+    let binary_layout = firmware.layout(binary_layout.as_deref(), target_vm)?;
     let helper_access_verification =
         HelperAccessVerification::from_str(helper_access_verification.as_str())?;
     let helper_access_list_source =
         HelperAccessListSource::from_str(helper_access_list_source.as_str())?;
+    let helper_indices = resolve_helpers(helper_indices, helpers.as_deref())?;
+
+    let entries = entries.iter().map(String::as_str).collect::<Vec<&str>>();
+    let artifact_compression = artifact_compression
+        .as_deref()
+        .map(ArtifactCompression::from_str)
+        .transpose()?;
+
+    if !bundled_programs.is_empty() || !layouts.is_empty() {
+        if !entries.is_empty() {
+            return Err(
+                "The entry function can't be selected for bundled and multi-layout deployments"
+                    .to_string(),
+            );
+        }
+        if artifact_compression.is_some() {
+            return Err(
+                "The artifact compression isn't supported by bundled and multi-layout deployments"
+                    .to_string(),
+            );
+        }
+        let SuitSlot::Fixed(slot) = suit_storage_slot else {
+            return Err(
+                "Bundled and multi-layout deployments require a fixed SUIT storage slot"
+                    .to_string(),
+            );
+        };
+
+        let env = load_env();
+        let transport = if use_env {
+            TransportOptions {
+                dry_run: *dry_run,
+                ..TransportOptions::from_env(&env)
+            }
+        } else {
+            TransportOptions {
+                oscore_context_dir: oscore_context_dir.clone(),
+                access_token: access_token.clone(),
+                dry_run: *dry_run,
+                ..Default::default()
+            }
+        };
+        let (artifacts, riot_net_if, riot_ip, host_net_if, host_ip, board, root_dir) =
+            if use_env {
+                (
+                    ArtifactStore::from_env(&env),
+                    env.riot_instance_net_if.as_str(),
+                    env.riot_instance_ip.as_str(),
+                    env.host_net_if.as_str(),
+                    env.host_ip.as_str(),
+                    env.board_name.as_str(),
+                    env.micro_bpf_root_dir.as_str(),
+                )
+            } else {
+                // The default assumes that the tools are run from the root of
+                // the mibpf repo, same as when signing.
+                (
+                    ArtifactStore::new(out_dir, coaproot_dir),
+                    riot_network_interface.as_str(),
+                    riot_ipv6_addr.as_str(),
+                    host_network_interface.as_str(),
+                    host_ipv6_addr.as_str(),
+                    board_name.as_str(),
+                    ".",
+                )
+            };
+
+        if !layouts.is_empty() {
+            let layouts = if layouts.iter().any(|l| l == "all") {
+                ALL_LAYOUTS.to_vec()
+            } else {
+                let layouts = layouts
+                    .iter()
+                    .map(|l| l.parse::<BinaryFileLayout>())
+                    .collect::<Result<Vec<BinaryFileLayout>, String>>()?;
+                for layout in &layouts {
+                    firmware.check_layout(*layout);
+                }
+                layouts
+            };
+            if *compress_rodata {
+                return Err(
+                    "The .rodata compression can't be combined with multi-layout deployments"
+                        .to_string(),
+                );
+            }
+            if *eliminate_dead_code {
+                return Err(
+                    "The dead code elimination can't be combined with multi-layout deployments"
+                        .to_string(),
+                );
+            }
+
+            let publisher = artifact_publisher(publish, &artifacts.manifest_dir);
+            let deployed = deploy_layouts(
+                bpf_source_file,
+                &artifacts,
+                target_vm,
+                &layouts,
+                slot,
+                storage,
+                riot_net_if,
+                riot_ip,
+                host_net_if,
+                host_ip,
+                board,
+                Some(root_dir),
+                helper_indices.to_vec(),
+                helper_access_verification,
+                helper_access_list_source,
+                *erase,
+                *deny_warnings,
+                *embed_metadata,
+                publisher.as_ref(),
+                &transport,
+            )
+            .await?;
+
+            for (layout, slot) in deployed {
+                println!("{:?} deployed to SUIT storage slot {}", layout, slot);
+            }
+            return Ok(());
+        }
+
+        let mut programs = vec![(bpf_source_file.clone(), slot)];
+        for spec in bundled_programs {
+            let Some((source, slot)) = spec.rsplit_once(':') else {
+                return Err(format!("Invalid bundled program: {}", spec));
+            };
+            let slot = slot
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid SUIT storage slot in: {}", spec))?;
+            programs.push((source.to_string(), slot));
+        }
+
+        let publisher = artifact_publisher(publish, &artifacts.manifest_dir);
+        deploy_bundle(
+            &programs,
+            &artifacts,
+            target_vm,
+            binary_layout,
+            storage,
+            riot_net_if,
+            riot_ip,
+            host_net_if,
+            host_ip,
+            root_dir,
+            helper_indices.to_vec(),
+            helper_access_verification,
+            helper_access_list_source,
+            *erase,
+            *sequence_number,
+            *force_seq,
+            *deny_warnings,
+            *compress_rodata,
+            *eliminate_dead_code,
+            *embed_metadata,
+            publisher.as_ref(),
+            &transport,
+        )
+        .await?;
+
+        println!("Deployed {} programs in one update", programs.len());
+        return Ok(());
+    }
 
     if use_env {
         let env = environment::load_env();
+        let transport = TransportOptions {
+            dry_run: *dry_run,
+            ..TransportOptions::from_env(&env)
+        };
 
-        return deploy(
+        let publisher = artifact_publisher(publish, &env.coap_root_dir);
+        let (slot, timings) = deploy(
             bpf_source_file,
-            &env.out_dir,
+            &ArtifactStore::from_env(&env),
             target_vm,
             binary_layout,
-            &env.coap_root_dir,
-            *suit_storage_slot as usize,
+            suit_storage_slot,
+            storage,
             &env.riot_instance_net_if,
             &env.riot_instance_ip,
             &env.host_net_if,
@@ -312,18 +1213,44 @@ async fn handle_deploy(args: &Action, use_env: bool) -> Result<(), String> {
             helper_indices.to_vec(),
             helper_access_verification,
             helper_access_list_source,
-            *erase,
+            &DeployOptions {
+                erase: *erase,
+                sequence_number: *sequence_number,
+                force_seq: *force_seq,
+                deny_lint_warnings: *deny_warnings,
+                compress: *compress_rodata,
+                strip_dead_code: *eliminate_dead_code,
+                embed_metadata: *embed_metadata,
+                entries: &entries,
+                artifact_compression,
+            },
+            publisher.as_ref(),
+            &transport,
         )
-        .await;
+        .await?;
+
+        println!("Program deployed to SUIT storage slot {}", slot);
+        if *show_timings {
+            println!("{}", timings);
+        }
+        return Ok(());
     }
 
-    deploy(
+    let transport = TransportOptions {
+        oscore_context_dir: oscore_context_dir.clone(),
+        access_token: access_token.clone(),
+        dry_run: *dry_run,
+        ..Default::default()
+    };
+
+    let publisher = artifact_publisher(publish, coaproot_dir);
+    let (slot, timings) = deploy(
         bpf_source_file,
-        out_dir,
+        &ArtifactStore::new(out_dir, coaproot_dir),
         target_vm,
         binary_layout,
-        coaproot_dir,
-        *suit_storage_slot as usize,
+        suit_storage_slot,
+        storage,
         riot_network_interface,
         riot_ipv6_addr,
         host_network_interface,
@@ -333,7 +1260,1814 @@ async fn handle_deploy(args: &Action, use_env: bool) -> Result<(), String> {
         helper_indices.to_vec(),
         helper_access_verification,
         helper_access_list_source,
-        *erase,
+        &DeployOptions {
+            erase: *erase,
+            sequence_number: *sequence_number,
+            force_seq: *force_seq,
+            deny_lint_warnings: *deny_warnings,
+            compress: *compress_rodata,
+            strip_dead_code: *eliminate_dead_code,
+            embed_metadata: *embed_metadata,
+            entries: &entries,
+            artifact_compression,
+        },
+        publisher.as_ref(),
+        &transport,
     )
-    .await
+    .await?;
+
+    println!("Program deployed to SUIT storage slot {}", slot);
+    if *show_timings {
+        println!("{}", timings);
+    }
+    Ok(())
+}
+
+fn handle_lint(args: &Action) -> Result<(), String> {
+    let Action::Lint {
+        bpf_source_file,
+        out_dir,
+        target,
+        helper_indices,
+        jit,
+        deny_warnings,
+    } = args
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", args));
+    };
+
+    compile(bpf_source_file, None, out_dir)?;
+    let options = LintOptions {
+        target: TargetVM::from_str(target)?,
+        jit: *jit,
+        allowed_helpers: helper_indices.to_vec(),
+    };
+    let findings = lint(&get_object_file_name(bpf_source_file, out_dir)?, &options)?;
+    report(&findings, *deny_warnings)?;
+
+    if findings.is_empty() {
+        println!("No problems found");
+    }
+    Ok(())
+}
+
+fn handle_simulate(args: &Action) -> Result<(), String> {
+    let Action::Simulate {
+        bpf_source_file,
+        out_dir,
+        helper_indices,
+        stubs,
+        sensor_readings,
+        context_file,
+        regions,
+        scenario,
+    } = args
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", args));
+    };
+
+    let config = StubConfig {
+        return_values: parse_stub_return_values(stubs)?,
+        sensor_readings: sensor_readings.clone(),
+        scripts: scenario
+            .as_deref()
+            .map(load_scenario)
+            .transpose()?
+            .unwrap_or_default(),
+    };
+    let mut context = match context_file {
+        Some(file) => read_bytes_from_file(file),
+        None => vec![],
+    };
+
+    compile(bpf_source_file, None, out_dir)?;
+    let report = simulate(
+        &get_object_file_name(bpf_source_file, out_dir)?,
+        config,
+        helper_indices,
+        &mut context,
+        &parse_memory_regions(regions)?,
+    )?;
+    println!("{}", report);
+    Ok(())
+}
+
+/// Returns the publisher of the deployment artifacts, by default they are
+/// served directly from the local CoAP root directory.
+fn artifact_publisher(spec: &Option<String>, coap_root_dir: &str) -> Box<dyn Publisher> {
+    match spec {
+        Some(spec) => parse_publisher(spec),
+        None => Box::new(LocalPublisher::new(coap_root_dir)),
+    }
+}
+
+fn handle_pin_identity(args: &Action) -> Result<(), String> {
+    let Action::PinIdentity {
+        riot_ipv6_addr,
+        public_key,
+        identity_dir,
+    } = args
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", args));
+    };
+
+    let path = IdentityStore::new(identity_dir).pin(riot_ipv6_addr, public_key)?;
+    println!("Pinned the identity of {} ({})", riot_ipv6_addr, path.display());
+    Ok(())
+}
+
+fn handle_oscore_context(args: &Action) -> Result<(), String> {
+    let Action::OscoreContext {
+        riot_ipv6_addr,
+        oscore_context_dir,
+        sender_id,
+        recipient_id,
+        master_secret,
+    } = args
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", args));
+    };
+
+    SecurityContextStore::new(oscore_context_dir).create_context(
+        riot_ipv6_addr,
+        sender_id,
+        recipient_id,
+        master_secret,
+    )
+}
+
+fn handle_diff(args: &Action) -> Result<(), String> {
+    let Action::Diff {
+        old_binary,
+        new_binary,
+        binary_layout,
+    } = args
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", args));
+    };
+
+    let old_layout = artifact_layout(binary_layout, old_binary)?;
+    let new_layout = artifact_layout(binary_layout, new_binary)?;
+    if old_layout != new_layout {
+        return Err(format!(
+            "The binaries use different layouts: {:?} and {:?}",
+            old_layout, new_layout
+        ));
+    }
+    let diff = diff_artifacts(old_binary, new_binary, old_layout)?;
+    println!("{}", diff);
+    Ok(())
+}
+
+fn handle_decompile(args: &Action) -> Result<(), String> {
+    let Action::Decompile {
+        binary_file,
+        binary_layout,
+    } = args
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", args));
+    };
+
+    let binary_layout = artifact_layout(binary_layout, binary_file)?;
+    let pseudocode = decompile_artifact(binary_file, binary_layout)?;
+    println!("{}", pseudocode);
+    Ok(())
+}
+
+fn handle_gen_context(args: &Action) -> Result<(), String> {
+    let Action::GenContext {
+        execution_model,
+        arch,
+        output,
+    } = args
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", args));
+    };
+
+    let execution_model = ExecutionModel::from_str(execution_model)?;
+    let Some(arch) = TargetArch::find(arch) else {
+        return Err(format!("Unknown target architecture: {}", arch));
+    };
+    let header = generate_context_header(execution_model, arch)?;
+    match output {
+        Some(path) => {
+            std::fs::write(path, header).map_err(|e| format!("Failed to write {}: {}", path, e))?
+        }
+        None => print!("{}", header),
+    }
+    Ok(())
+}
+
+fn handle_device(action: &DeviceAction, use_env: bool) -> Result<(), String> {
+    match action {
+        DeviceAction::Reboot {
+            wait,
+            wait_timeout,
+            riot_ipv6_addr,
+            host_network_interface,
+            oscore_context_dir,
+            access_token,
+        } => {
+            let (riot_ipv6_addr, host_network_interface, transport) = device(
+                use_env,
+                riot_ipv6_addr,
+                host_network_interface,
+                oscore_context_dir,
+                access_token,
+            );
+            reboot_device(&riot_ipv6_addr, &host_network_interface, &transport)?;
+            println!("Rebooting the device {}", riot_ipv6_addr);
+
+            if *wait {
+                let elapsed = wait_for_device(
+                    &riot_ipv6_addr,
+                    &host_network_interface,
+                    Duration::from_secs(*wait_timeout),
+                    &transport,
+                )?;
+                println!(
+                    "The device {} is up after {:.1}s",
+                    riot_ipv6_addr,
+                    elapsed.as_secs_f64()
+                );
+            }
+            Ok(())
+        }
+        DeviceAction::ResetVm {
+            slot,
+            riot_ipv6_addr,
+            host_network_interface,
+            oscore_context_dir,
+            access_token,
+        } => {
+            let (riot_ipv6_addr, host_network_interface, transport) = device(
+                use_env,
+                riot_ipv6_addr,
+                host_network_interface,
+                oscore_context_dir,
+                access_token,
+            );
+            reset_vm(&riot_ipv6_addr, &host_network_interface, *slot, &transport)?;
+            match slot {
+                Some(slot) => println!("Stopped the VM executing the program from slot {}", slot),
+                None => println!("Stopped all VMs on the device {}", riot_ipv6_addr),
+            }
+            Ok(())
+        }
+        DeviceAction::Info {
+            riot_ipv6_addr,
+            host_network_interface,
+            oscore_context_dir,
+            access_token,
+            json,
+        } => {
+            let (riot_ipv6_addr, host_network_interface, transport) = device(
+                use_env,
+                riot_ipv6_addr,
+                host_network_interface,
+                oscore_context_dir,
+                access_token,
+            );
+            let info = query_device_info(&riot_ipv6_addr, &host_network_interface, &transport)?;
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&info).unwrap());
+            } else {
+                println!("{}", format_device_info(&info));
+            }
+            Ok(())
+        }
+        DeviceAction::Lookup {
+            endpoint,
+            resource_directory,
+            proxy,
+            save,
+        } => {
+            let (resource_directory, transport) = if use_env {
+                let env = load_env();
+                let transport = TransportOptions::from_env(&env);
+                (
+                    resource_directory.clone().or(env.resource_directory),
+                    transport,
+                )
+            } else {
+                (resource_directory.clone(), TransportOptions::default())
+            };
+            let Some(resource_directory) = resource_directory else {
+                return Err(
+                    "No resource directory specified, use --resource-directory or set \
+                     RESOURCE_DIRECTORY in the device profile"
+                        .to_string(),
+                );
+            };
+            let transport = TransportOptions {
+                proxy: proxy.clone().or(transport.proxy),
+                ..transport
+            };
+
+            let address = lookup_endpoint(&resource_directory, endpoint, &transport)?;
+            println!("{}", address);
+            if *save {
+                update_env_variable("RIOT_INSTANCE_IP", &address)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn handle_hook(action: &HookAction, use_env: bool) -> Result<(), String> {
+    match action {
+        HookAction::Attach {
+            hook,
+            suit_storage_slot,
+            target,
+            binary_layout,
+            helper_indices,
+            helper_access_verification,
+            jit,
+            riot_ipv6_addr,
+            host_network_interface,
+            oscore_context_dir,
+            access_token,
+        } => {
+            let hook = NetworkHook::from_str(hook)?;
+            let request = build_execution_request(
+                TargetVM::from_str(target)?,
+                BinaryFileLayout::from_str(binary_layout)?,
+                *suit_storage_slot,
+                HelperAccessVerification::from_str(helper_access_verification)?,
+                HelperAccessListSource::ExecuteRequest,
+                helper_indices,
+                *jit,
+                false,
+            );
+            let (riot_ipv6_addr, host_network_interface, transport) = device(
+                use_env,
+                riot_ipv6_addr,
+                host_network_interface,
+                oscore_context_dir,
+                access_token,
+            );
+            attach_program(
+                &riot_ipv6_addr,
+                &host_network_interface,
+                hook,
+                &request,
+                &transport,
+            )?;
+            println!(
+                "Attached the program from slot {} to {}",
+                suit_storage_slot, hook
+            );
+            Ok(())
+        }
+        HookAction::Detach {
+            hook,
+            riot_ipv6_addr,
+            host_network_interface,
+            oscore_context_dir,
+            access_token,
+        } => {
+            let hook = NetworkHook::from_str(hook)?;
+            let (riot_ipv6_addr, host_network_interface, transport) = device(
+                use_env,
+                riot_ipv6_addr,
+                host_network_interface,
+                oscore_context_dir,
+                access_token,
+            );
+            detach_program(&riot_ipv6_addr, &host_network_interface, hook, &transport)?;
+            println!("Detached the program from {}", hook);
+            Ok(())
+        }
+        HookAction::List {
+            hook,
+            json,
+            riot_ipv6_addr,
+            host_network_interface,
+            oscore_context_dir,
+            access_token,
+        } => {
+            let hook = hook.as_deref().map(NetworkHook::from_str).transpose()?;
+            let (riot_ipv6_addr, host_network_interface, transport) = device(
+                use_env,
+                riot_ipv6_addr,
+                host_network_interface,
+                oscore_context_dir,
+                access_token,
+            );
+            let hooks = query_hooks(&riot_ipv6_addr, &host_network_interface, &transport)?
+                .into_iter()
+                .filter(|info| hook.map_or(true, |hook| info.hook == hook.name()))
+                .collect::<Vec<_>>();
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&hooks).unwrap());
+            } else {
+                println!("{}", format_hooks(&hooks));
+            }
+            Ok(())
+        }
+    }
+}
+
+fn handle_keystore(action: &KeystoreAction, use_env: bool) -> Result<(), String> {
+    match action {
+        KeystoreAction::Provision {
+            handle,
+            key_type,
+            key_file,
+            riot_ipv6_addr,
+            host_network_interface,
+            oscore_context_dir,
+            access_token,
+        } => {
+            let (riot_ipv6_addr, host_network_interface, transport) = device(
+                use_env,
+                riot_ipv6_addr,
+                host_network_interface,
+                oscore_context_dir,
+                access_token,
+            );
+            let key_type = KeyType::from_str(key_type)?;
+            let material = std::fs::read(key_file)
+                .map_err(|e| format!("Failed to read the key file {}: {}", key_file, e))?;
+            provision_key(
+                &riot_ipv6_addr,
+                &host_network_interface,
+                *handle,
+                key_type,
+                &material,
+                &transport,
+            )?;
+            println!(
+                "Provisioned the {} key {} on the device {}",
+                key_type, handle, riot_ipv6_addr
+            );
+            Ok(())
+        }
+        KeystoreAction::List {
+            riot_ipv6_addr,
+            host_network_interface,
+            oscore_context_dir,
+            access_token,
+        } => {
+            let (riot_ipv6_addr, host_network_interface, transport) = device(
+                use_env,
+                riot_ipv6_addr,
+                host_network_interface,
+                oscore_context_dir,
+                access_token,
+            );
+            let keys = list_keys(&riot_ipv6_addr, &host_network_interface, &transport)?;
+            if keys.is_empty() {
+                println!("The keystore of the device {} is empty", riot_ipv6_addr);
+            }
+            for key in keys {
+                println!("{:>6} {}", key.handle, key.key_type);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn handle_helpers(action: &HelpersAction, use_env: bool) -> Result<(), String> {
+    let HelpersAction::Probe {
+        binary_file,
+        binary_layout,
+        riot_ipv6_addr,
+        host_network_interface,
+        oscore_context_dir,
+        access_token,
+    } = action
+    else {
+        return handle_helper_sync(action, use_env);
+    };
+
+    let (riot_ipv6_addr, host_network_interface, transport) = if use_env {
+        let env = load_env();
+        let transport = TransportOptions::from_env(&env);
+        (env.riot_instance_ip, env.host_net_if, transport)
+    } else {
+        let transport = TransportOptions {
+            oscore_context_dir: oscore_context_dir.clone(),
+            access_token: access_token.clone(),
+            ..Default::default()
+        };
+        (
+            riot_ipv6_addr.clone(),
+            host_network_interface.clone(),
+            transport,
+        )
+    };
+
+    let binary_layout = binary_layout.as_str().parse::<BinaryFileLayout>()?;
+    let required = required_helpers(binary_file, binary_layout)?;
+    let available = query_helpers(&riot_ipv6_addr, &host_network_interface, &transport)?;
+
+    let compatibility = HelperCompatibility::new(required, available);
+    println!("{}", compatibility);
+    if !compatibility.is_compatible() {
+        return Err(format!(
+            "{} cannot be executed on the device, {} helpers are missing",
+            binary_file,
+            compatibility.missing().len()
+        ));
+    }
+    Ok(())
+}
+
+fn handle_helper_sync(action: &HelpersAction, use_env: bool) -> Result<(), String> {
+    let HelpersAction::Sync {
+        mibpf_root_dir,
+        header,
+        catalogue,
+        check,
+    } = action
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", action));
+    };
+
+    let tables = if !header.is_empty() {
+        header.iter().map(PathBuf::from).collect()
+    } else if use_env {
+        find_helper_tables(&load_env().micro_bpf_root_dir)?
+    } else {
+        find_helper_tables(mibpf_root_dir)?
+    };
+    let helpers = load_helper_definitions(&tables)?;
+    let diff = diff_catalogue(&helpers);
+    println!("{}", diff);
+
+    if diff.is_empty() {
+        return Ok(());
+    }
+    if *check {
+        return Err(format!(
+            "The helper catalogue {} is out of sync with the firmware",
+            catalogue
+        ));
+    }
+    regenerate_catalogue(catalogue, &helpers)?;
+    println!(
+        "Regenerated {}, the simulator stubs of the new helpers need to be added by hand",
+        catalogue
+    );
+    Ok(())
+}
+
+fn handle_token(action: &TokenAction, use_env: bool) -> Result<(), String> {
+    let env = load_env();
+
+    let token = match action {
+        TokenAction::Issue {
+            authorization_server,
+            client_id,
+            client_secret,
+            riot_ipv6_addr,
+        } => {
+            let audience = if use_env {
+                &env.riot_instance_ip
+            } else {
+                riot_ipv6_addr
+            };
+            issue_token(authorization_server, client_id, client_secret, audience)?
+        }
+        TokenAction::Refresh {
+            authorization_server,
+        } => {
+            let Some(current) = &env.refresh_token else {
+                return Err("No REFRESH_TOKEN found in the device profile".to_string());
+            };
+            refresh_token(authorization_server, current)?
+        }
+    };
+
+    store_token(&token)
+}
+
+/// Saves the token in the .env file of the current device profile so that it
+/// is attached to all subsequent requests sent with --use-env.
+fn store_token(token: &AccessToken) -> Result<(), String> {
+    update_env_variable("ACCESS_TOKEN", &token.access_token)?;
+    if let Some(refresh_token) = &token.refresh_token {
+        update_env_variable("REFRESH_TOKEN", refresh_token)?;
+    }
+
+    match token.expires_in {
+        Some(seconds) => println!("Access token stored, it expires in {}s", seconds),
+        None => println!("Access token stored"),
+    }
+    Ok(())
+}
+
+async fn handle_conformance(action: &ConformanceAction) -> Result<(), String> {
+    let (target, binary_layout, categories, work_dir, jit) = match action {
+        ConformanceAction::List => {
+            for program in PROGRAMS {
+                println!("{:<12} {}", program.category, program.name);
+            }
+            for contract in CONTRACTS {
+                println!("{:<12} {} ({:?})", "contract", contract.name, contract.helper);
+            }
+            return Ok(());
+        }
+        ConformanceAction::ContractTest { .. } => return handle_contract_test(action).await,
+        ConformanceAction::Run {
+            target,
+            binary_layout,
+            categories,
+            work_dir,
+            jit,
+        } => (target, binary_layout, categories, work_dir, jit),
+    };
+
+    let target_vm = TargetVM::from_str(target.as_str())?;
+    let binary_layout = binary_layout.as_str().parse::<BinaryFileLayout>()?;
+    let categories = categories
+        .iter()
+        .map(|c| Category::from_str(c))
+        .collect::<Result<Vec<Category>, String>>()?;
+
+    let env = load_env();
+    let scorecard = run_conformance(
+        &env,
+        work_dir,
+        target_vm,
+        binary_layout,
+        &categories,
+        *jit,
+        &TransportOptions::from_env(&env),
+    )
+    .await?;
+
+    println!("{}", scorecard);
+    if scorecard.failed() > 0 {
+        return Err(format!("{} conformance programs failed", scorecard.failed()));
+    }
+    Ok(())
+}
+
+async fn handle_contract_test(action: &ConformanceAction) -> Result<(), String> {
+    let ConformanceAction::ContractTest {
+        target,
+        binary_layout,
+        helpers,
+        work_dir,
+        output,
+        jit,
+    } = action
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", action));
+    };
+
+    let target_vm = TargetVM::from_str(target.as_str())?;
+    let binary_layout = binary_layout.as_str().parse::<BinaryFileLayout>()?;
+    let helpers = helpers
+        .iter()
+        .map(|helper| parse_helper(helper))
+        .collect::<Result<Vec<u8>, String>>()?;
+
+    let env = load_env();
+    let report = run_contracts(
+        &env,
+        work_dir,
+        target_vm,
+        binary_layout,
+        &helpers,
+        *jit,
+        &TransportOptions::from_env(&env),
+    )
+    .await?;
+
+    println!("{}", report);
+    if let Some(output) = output {
+        std::fs::write(output, format!("{}\n", report))
+            .map_err(|e| format!("Failed to write the report to {}: {}", output, e))?;
+    }
+    if report.violated() > 0 {
+        return Err(format!("{} helper contracts were violated", report.violated()));
+    }
+    Ok(())
+}
+
+async fn handle_watchdog(args: &Action) -> Result<(), String> {
+    let Action::Watchdog {
+        suit_storage_slot,
+        target,
+        binary_layout,
+        interval,
+        failure_threshold,
+        max_restarts,
+        notify_url,
+        redeploy,
+        jit,
+        record,
+        record_max_size,
+    } = args
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", args));
+    };
+
+    let target_vm = TargetVM::from_str(target.as_str())?;
+    let binary_layout = binary_layout.as_str().parse::<BinaryFileLayout>()?;
+    let config = WatchdogConfig {
+        interval: Duration::from_secs(*interval),
+        failure_threshold: *failure_threshold,
+        max_restarts: *max_restarts,
+        notify_url: notify_url.clone(),
+        redeploy_source: redeploy.clone(),
+    };
+
+    let env = load_env();
+    let mut recorder = open_recorder(record, *record_max_size)?;
+    watch(
+        &env,
+        *suit_storage_slot,
+        target_vm,
+        binary_layout,
+        *jit,
+        &config,
+        recorder.as_mut(),
+        &TransportOptions::from_env(&env),
+    )
+    .await
+}
+
+async fn handle_collect(args: &Action) -> Result<(), String> {
+    let Action::Collect {
+        bpf_source_file,
+        target,
+        binary_layout,
+        suit_storage_slot,
+        jit,
+        period,
+        poll_interval,
+        duration,
+        devices,
+        format,
+        measurement,
+        output,
+    } = args
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", args));
+    };
+
+    let config = CollectConfig {
+        source: bpf_source_file.clone(),
+        target: TargetVM::from_str(target.as_str())?,
+        binary_layout: binary_layout.as_str().parse::<BinaryFileLayout>()?,
+        jit: *jit,
+        suit_storage_slot: *suit_storage_slot,
+        period: Duration::from_millis(*period),
+        poll_interval: Duration::from_millis(*poll_interval),
+        duration: duration.map(Duration::from_secs),
+    };
+    let env = load_env();
+    let mut writer = SampleWriter::open(
+        output.as_deref(),
+        SampleFormat::from_str(format)?,
+        measurement,
+        *suit_storage_slot,
+    )?;
+    #[cfg(feature = "telemetry")]
+    {
+        writer = writer.with_sink(TelemetrySink::from_env(&env)?);
+    }
+
+    let collected = collect(
+        &env,
+        devices,
+        &config,
+        &mut writer,
+        &TransportOptions::from_env(&env),
+    )
+    .await?;
+    println!("Collected {} samples", collected);
+    Ok(())
+}
+
+#[cfg(feature = "tui")]
+fn handle_tui(args: &Action) -> Result<(), String> {
+    let Action::Tui {
+        fleet_file,
+        refresh,
+        deploy,
+        target,
+        binary_layout,
+        jit,
+    } = args
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", args));
+    };
+
+    let env = load_env();
+    let mut devices = vec![fleet::Device {
+        riot_ipv6_addr: env.riot_instance_ip.clone(),
+        host_network_interface: env.host_net_if.clone(),
+    }];
+    let mut watcher = None;
+    if let Some(fleet_file) = fleet_file {
+        devices = Fleet::load(fleet_file, &env.host_net_if)?.devices;
+        watcher = Some(fleet::FleetWatcher::new(fleet_file, &env.host_net_if));
+    }
+    let config = DashboardConfig {
+        refresh: Duration::from_secs(*refresh),
+        deploy_source: deploy.clone(),
+        target: TargetVM::from_str(target.as_str())?,
+        binary_layout: binary_layout.as_str().parse::<BinaryFileLayout>()?,
+        jit: *jit,
+    };
+    run_dashboard(
+        &env,
+        devices,
+        watcher,
+        &config,
+        &TransportOptions::from_env(&env),
+    )
+}
+
+fn handle_audit(action: &AuditAction) -> Result<(), String> {
+    let AuditAction::Show {
+        log,
+        device,
+        limit,
+        verify,
+        key_dir,
+    } = action;
+
+    let log = match log {
+        Some(path) => AuditLog::new(path),
+        None => AuditLog::from_env()
+            .ok_or("No audit log specified and MIBPF_AUDIT_LOG isn't set".to_string())?,
+    };
+
+    let entries = log
+        .entries()?
+        .into_iter()
+        .filter(|entry| {
+            device
+                .as_ref()
+                .map_or(true, |device| entry.device == *device)
+        })
+        .collect::<Vec<AuditEntry>>();
+    let skipped = limit.map_or(0, |limit| entries.len().saturating_sub(limit));
+    for entry in entries.iter().skip(skipped) {
+        println!("{}", entry);
+    }
+
+    if *verify {
+        let keys = KeyStore::new(key_dir.as_deref().unwrap_or(&KeyStore::default_root()));
+        let issues = log.verify(&keys)?;
+        if !issues.is_empty() {
+            for issue in &issues {
+                eprintln!("{}", issue);
+            }
+            return Err(format!(
+                "The audit log failed verification with {} issues",
+                issues.len()
+            ));
+        }
+        println!("The audit log is intact");
+    }
+    Ok(())
+}
+
+fn handle_keys(action: &KeysAction, use_env: bool) -> Result<(), String> {
+    let open_store = |key_dir: &Option<String>| {
+        KeyStore::new(&key_dir.clone().unwrap_or_else(KeyStore::default_root))
+    };
+
+    match action {
+        KeysAction::Generate {
+            name,
+            key_dir,
+            activate,
+        } => {
+            let store = open_store(key_dir);
+            let path = store.generate(name)?;
+            println!("Generated the signing key {}", path.display());
+            if *activate {
+                store.set_active(name)?;
+            }
+            Ok(())
+        }
+        KeysAction::Rotate {
+            name,
+            key_dir,
+            coaproot_dir,
+        } => {
+            let store = open_store(key_dir);
+            let name = name
+                .clone()
+                .unwrap_or_else(|| format!("key-{}", registry::now()));
+            let path = store.generate(&name)?;
+            store.set_active(&name)?;
+            println!("Rotated the signing key to {}", path.display());
+
+            let coaproot_dir = if use_env {
+                load_env().coap_root_dir
+            } else {
+                coaproot_dir.clone()
+            };
+            for manifest in resign_artifacts(&coaproot_dir, &path)? {
+                println!("Re-signed {}", manifest);
+            }
+            Ok(())
+        }
+        KeysAction::Provision {
+            name,
+            key_dir,
+            riot_ipv6_addr,
+            host_network_interface,
+            oscore_context_dir,
+            access_token,
+        } => {
+            let store = open_store(key_dir);
+            let Some(name) = name.clone().or_else(|| store.active_key()) else {
+                return Err("No key specified and no active key in the key store".to_string());
+            };
+            let public_key = store.public_key_bytes(&name)?;
+
+            if use_env {
+                let env = load_env();
+                return provision_trust_anchor(
+                    &env.riot_instance_ip,
+                    &env.host_net_if,
+                    &public_key,
+                    &TransportOptions::from_env(&env),
+                );
+            }
+
+            let transport = TransportOptions {
+                oscore_context_dir: oscore_context_dir.clone(),
+                access_token: access_token.clone(),
+                ..Default::default()
+            };
+            provision_trust_anchor(
+                riot_ipv6_addr,
+                host_network_interface,
+                &public_key,
+                &transport,
+            )
+        }
+    }
+}
+
+async fn handle_fleet(action: &FleetAction, use_env: bool) -> Result<(), String> {
+    match action {
+        FleetAction::Execute { .. } => handle_fleet_execute(action, use_env),
+        FleetAction::Deploy { .. } => handle_fleet_deploy(action).await,
+        FleetAction::Supervise {
+            fleet_file,
+            interval,
+            notify_url,
+        } => {
+            let env = load_env();
+            let fleet = Fleet::load(fleet_file, &env.host_net_if)?;
+            supervise_fleet(
+                &env,
+                &fleet,
+                Duration::from_secs(*interval),
+                notify_url.as_deref(),
+                &TransportOptions::from_env(&env),
+            )
+            .await
+        }
+    }
+}
+
+async fn handle_fleet_deploy(action: &FleetAction) -> Result<(), String> {
+    let FleetAction::Deploy {
+        fleet_file,
+        bpf_source_file,
+        suit_storage_slot,
+        storage,
+        target,
+        binary_layout,
+        strategy,
+        expected_result,
+    } = action
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", action));
+    };
+
+    let env = load_env();
+    let firmware = firmware_build(true);
+    let target = firmware.target(target.as_deref())?;
+    let program = RolloutProgram {
+        source: bpf_source_file.clone(),
+        slot: *suit_storage_slot,
+        storage: StorageBackend::from_str(storage)?,
+        target,
+        binary_layout: firmware.layout(binary_layout.as_deref(), target)?,
+    };
+    let strategy = RolloutStrategy::from_str(strategy)?;
+    let fleet = Fleet::load(fleet_file, &env.host_net_if)?;
+
+    let report = roll_out(
+        &env,
+        &fleet,
+        &program,
+        strategy,
+        *expected_result,
+        &TransportOptions::from_env(&env),
+    )
+    .await?;
+    println!("{}", report);
+    match report.aborted {
+        Some(reason) => Err(format!("The rollout was aborted: {}", reason)),
+        None => Ok(()),
+    }
+}
+
+fn handle_fleet_execute(action: &FleetAction, use_env: bool) -> Result<(), String> {
+    let FleetAction::Execute {
+        fleet_file,
+        host_network_interface,
+        target,
+        binary_layout,
+        suit_storage_slot,
+        helper_indices,
+        lead_time,
+        collection_delay,
+        jit,
+        rounds,
+        fields,
+        bucket_width,
+        raw,
+        json,
+    } = action
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", action));
+    };
+
+    let target_vm = TargetVM::from_str(target.as_str())?;
+    let binary_layout = binary_layout.as_str().parse::<BinaryFileLayout>()?;
+    let bucket_width = bucket_width
+        .parse::<f64>()
+        .ok()
+        .filter(|width| *width > 0.0)
+        .ok_or_else(|| format!("Invalid bucket width: {}", bucket_width))?;
+    let options = ScheduleOptions {
+        lead_time: Duration::from_millis(*lead_time),
+        collection_delay: Duration::from_millis(*collection_delay),
+    };
+
+    let (fleet, transport) = if use_env {
+        let env = load_env();
+        let fleet = Fleet::load(fleet_file, &env.host_net_if)?;
+        (fleet, TransportOptions::from_env(&env))
+    } else {
+        let fleet = Fleet::load(fleet_file, host_network_interface)?;
+        (fleet, TransportOptions::default())
+    };
+
+    let mut results = vec![];
+    for _ in 0..*rounds {
+        results.extend(execute_synchronized(
+            &fleet,
+            target_vm,
+            binary_layout,
+            *suit_storage_slot,
+            helper_indices,
+            *jit,
+            &options,
+            &transport,
+        )?);
+    }
+
+    if !*raw {
+        let report = aggregate(&results, fields, bucket_width);
+        if *json {
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        } else {
+            print!("{}", report);
+        }
+        return Ok(());
+    }
+
+    for result in results {
+        match (&result.response, result.skew_ms) {
+            (Ok(response), Some(skew)) => println!(
+                "{}: {} (skew: {}ms)",
+                result.device.riot_ipv6_addr, response, skew
+            ),
+            (Ok(response), None) => println!("{}: {}", result.device.riot_ipv6_addr, response),
+            (Err(e), _) => println!("{}: failed: {}", result.device.riot_ipv6_addr, e),
+        }
+    }
+    Ok(())
+}
+
+async fn handle_bench(args: &Action, use_env: bool) -> Result<(), String> {
+    let Action::Bench {
+        riot_ipv6_addr,
+        host_network_interface,
+        target,
+        binary_layout,
+        suit_storage_slot,
+        helper_indices,
+        jit,
+        iterations,
+        power_probe,
+        idle_window,
+        record,
+        record_max_size,
+        mode,
+    } = args
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", args));
+    };
+
+    if let Some(mode) = mode {
+        return handle_bench_mode(mode).await;
+    }
+
+    let target_vm = TargetVM::from_str(target.as_str())?;
+    let binary_layout = binary_layout.as_str().parse::<BinaryFileLayout>()?;
+    let mut probe = power_probe.as_ref().map(|command| ScriptProbe {
+        command: command.clone(),
+    });
+    let probe = probe.as_mut().map(|p| p as &mut dyn PowerProbe);
+    let mut recorder = open_recorder(record, *record_max_size)?;
+
+    let report = if use_env {
+        let env = load_env();
+        bench(
+            &env.riot_instance_ip,
+            &env.host_net_if,
+            target_vm,
+            binary_layout,
+            *suit_storage_slot,
+            helper_indices,
+            *jit,
+            *iterations,
+            probe,
+            Duration::from_millis(*idle_window),
+            recorder.as_mut(),
+            &TransportOptions::from_env(&env),
+        )
+        .await?
+    } else {
+        bench(
+            riot_ipv6_addr,
+            host_network_interface,
+            target_vm,
+            binary_layout,
+            *suit_storage_slot,
+            helper_indices,
+            *jit,
+            *iterations,
+            probe,
+            Duration::from_millis(*idle_window),
+            recorder.as_mut(),
+            &TransportOptions::default(),
+        )
+        .await?
+    };
+
+    println!("{}", report);
+    Ok(())
+}
+
+fn handle_stress(args: &Action, use_env: bool) -> Result<(), String> {
+    let Action::Stress {
+        riot_ipv6_addr,
+        host_network_interface,
+        target,
+        binary_layout,
+        suit_storage_slot,
+        helper_indices,
+        jit,
+        start_rate,
+        rate_step,
+        max_rate,
+        step_duration,
+        error_threshold,
+        max_consecutive_failures,
+    } = args
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", args));
+    };
+
+    let target_vm = TargetVM::from_str(target.as_str())?;
+    let binary_layout = binary_layout.as_str().parse::<BinaryFileLayout>()?;
+    let config = StressConfig {
+        start_rate: *start_rate as f64,
+        rate_step: *rate_step as f64,
+        max_rate: *max_rate as f64,
+        step_duration: Duration::from_secs(*step_duration),
+        error_threshold: *error_threshold as f64 / 100.0,
+        max_consecutive_failures: *max_consecutive_failures,
+    };
+
+    let report = if use_env {
+        let env = load_env();
+        run_stress(
+            &env.riot_instance_ip,
+            &env.host_net_if,
+            target_vm,
+            binary_layout,
+            *suit_storage_slot,
+            helper_indices,
+            *jit,
+            &config,
+            &TransportOptions::from_env(&env),
+        )?
+    } else {
+        run_stress(
+            riot_ipv6_addr,
+            host_network_interface,
+            target_vm,
+            binary_layout,
+            *suit_storage_slot,
+            helper_indices,
+            *jit,
+            &config,
+            &TransportOptions::default(),
+        )?
+    };
+
+    println!("{}", report);
+    Ok(())
+}
+
+async fn handle_bench_mode(mode: &BenchMode) -> Result<(), String> {
+    let BenchMode::Helpers {
+        vms,
+        binary_layout,
+        iterations,
+        work_dir,
+    } = mode;
+
+    let vms = vms
+        .iter()
+        .map(|vm| parse_vm(vm))
+        .collect::<Result<Vec<_>, String>>()?;
+    let binary_layout = binary_layout.as_str().parse::<BinaryFileLayout>()?;
+
+    let env = load_env();
+    let report = bench_helpers(
+        &env,
+        work_dir,
+        &vms,
+        binary_layout,
+        *iterations,
+        &TransportOptions::from_env(&env),
+    )
+    .await?;
+
+    println!("{}", report);
+    Ok(())
+}
+
+fn open_recorder(
+    record: &Option<String>,
+    max_size_mib: u64,
+) -> Result<Option<ResultRecorder>, String> {
+    record
+        .as_deref()
+        .map(|path| ResultRecorder::open(path, max_size_mib * 1024 * 1024))
+        .transpose()
+}
+
+fn handle_list(args: &Action, use_env: bool) -> Result<(), String> {
+    let Action::List {
+        riot_ipv6_addr,
+        host_network_interface,
+    } = args
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", args));
+    };
+
+    let (device_ip, slots) = if use_env {
+        let env = load_env();
+        let slots = query_slots(
+            &env.riot_instance_ip,
+            &env.host_net_if,
+            &TransportOptions::from_env(&env),
+        )?;
+        (env.riot_instance_ip, slots)
+    } else {
+        let slots = query_slots(
+            riot_ipv6_addr,
+            host_network_interface,
+            &TransportOptions::default(),
+        )?;
+        (riot_ipv6_addr.clone(), slots)
+    };
+
+    // The device doesn't report where the program came from, the revisions
+    // of its sources are recorded in the registry at deploy time.
+    let registry = registry::Registry::load()?;
+    let records = registry.device(&device_ip).map(|d| &d.slots);
+    for slot in slots {
+        if slot.is_free() {
+            println!("{:4}: <empty> [{}]", slot.slot, slot.storage);
+            continue;
+        }
+        match (&slot.name, &slot.version) {
+            (Some(name), Some(version)) => println!(
+                "{:4}: {} {} ({} bytes, build {}) [{}]",
+                slot.slot,
+                name,
+                version,
+                slot.size,
+                slot.build_hash.as_deref().unwrap_or("unknown"),
+                slot.storage
+            ),
+            _ => println!("{:4}: {} bytes [{}]", slot.slot, slot.size, slot.storage),
+        }
+        if let Some(record) = records.and_then(|r| r.get(&slot.slot)) {
+            println!("      {} ({})", record.program, record.provenance);
+        }
+    }
+    Ok(())
+}
+
+/// Resolves the address of the device and the transport options either
+/// from the .env file or from the arguments of the command.
+fn device(
+    use_env: bool,
+    riot_ipv6_addr: &String,
+    host_network_interface: &String,
+    oscore_context_dir: &Option<String>,
+    access_token: &Option<String>,
+) -> (String, String, TransportOptions) {
+    if use_env {
+        let env = load_env();
+        let transport = TransportOptions::from_env(&env);
+        (env.riot_instance_ip, env.host_net_if, transport)
+    } else {
+        let transport = TransportOptions {
+            oscore_context_dir: oscore_context_dir.clone(),
+            access_token: access_token.clone(),
+            ..Default::default()
+        };
+        (
+            riot_ipv6_addr.clone(),
+            host_network_interface.clone(),
+            transport,
+        )
+    }
+}
+
+fn handle_queue(action: &QueueAction) -> Result<(), String> {
+    let queue = ExecutionQueue::new(&ExecutionQueue::default_root());
+    match action {
+        QueueAction::List { riot_ipv6_addr } => {
+            let entries = queue.list(riot_ipv6_addr.as_deref());
+            if entries.is_empty() {
+                println!("No queued executions");
+            }
+            let mut device = None;
+            for (position, entry) in entries.iter().enumerate() {
+                if device != Some(&entry.device) {
+                    println!("{}:", entry.device);
+                    device = Some(&entry.device);
+                }
+                let ahead = entries[..position]
+                    .iter()
+                    .filter(|other| other.device == entry.device)
+                    .count();
+                println!("  {:>3}  {}", ahead, entry);
+            }
+            Ok(())
+        }
+        QueueAction::Cancel { ticket } => {
+            let entry = queue.cancel(ticket)?;
+            println!("Cancelled the execution of slot {} on {}", entry.slot, entry.device);
+            Ok(())
+        }
+    }
+}
+
+fn handle_results(action: &ResultsAction) -> Result<(), String> {
+    let ResultsAction::Query {
+        riot_ipv6_addr,
+        program,
+        since,
+        until,
+        summary,
+    } = action;
+    let filter = ResultFilter {
+        device: riot_ipv6_addr.clone(),
+        program: program.clone(),
+        since: since.as_deref().map(|d| parse_date(d, false)).transpose()?,
+        until: until.as_deref().map(|d| parse_date(d, true)).transpose()?,
+    };
+    let records = ResultHistory::new(&ResultHistory::default_path()).query(&filter)?;
+    if records.is_empty() {
+        println!("No recorded executions");
+        return Ok(());
+    }
+    if !*summary {
+        for record in &records {
+            println!(
+                "{} {} slot {} {}: result {}, execution time {}",
+                record.executed_at,
+                record.device,
+                record.slot,
+                record.program.as_deref().unwrap_or("?"),
+                record.result,
+                record.execution_time
+            );
+        }
+        println!();
+    }
+    for summary in summarize(&records) {
+        println!("{}", summary);
+    }
+    Ok(())
+}
+
+fn handle_slot(action: &SlotAction, use_env: bool) -> Result<(), String> {
+    match action {
+        SlotAction::Backup {
+            slot,
+            out,
+            riot_ipv6_addr,
+            host_network_interface,
+            oscore_context_dir,
+            access_token,
+        } => {
+            let (riot_ipv6_addr, host_network_interface, transport) = device(
+                use_env,
+                riot_ipv6_addr,
+                host_network_interface,
+                oscore_context_dir,
+                access_token,
+            );
+            let contents =
+                backup_slot(&riot_ipv6_addr, &host_network_interface, *slot, &transport)?;
+            if contents.is_empty() {
+                return Err(format!("Slot {} is empty", slot));
+            }
+            std::fs::write(out, &contents)
+                .map_err(|e| format!("Failed to write the backup {}: {}", out, e))?;
+            println!(
+                "Saved slot {} ({} bytes) into {}",
+                slot,
+                contents.len(),
+                out
+            );
+            Ok(())
+        }
+        SlotAction::Restore {
+            slot,
+            input,
+            riot_ipv6_addr,
+            host_network_interface,
+            oscore_context_dir,
+            access_token,
+            dry_run,
+        } => {
+            let (riot_ipv6_addr, host_network_interface, transport) = device(
+                use_env,
+                riot_ipv6_addr,
+                host_network_interface,
+                oscore_context_dir,
+                access_token,
+            );
+            let transport = TransportOptions {
+                dry_run: *dry_run,
+                ..transport
+            };
+            restore_slot(
+                &riot_ipv6_addr,
+                &host_network_interface,
+                *slot,
+                input,
+                &transport,
+            )?;
+            if !*dry_run {
+                println!("Restored {} into slot {}", input, slot);
+            }
+            Ok(())
+        }
+        SlotAction::Stats {
+            riot_ipv6_addr,
+            host_network_interface,
+            oscore_context_dir,
+            access_token,
+        } => {
+            let (riot_ipv6_addr, host_network_interface, transport) = device(
+                use_env,
+                riot_ipv6_addr,
+                host_network_interface,
+                oscore_context_dir,
+                access_token,
+            );
+            // The registry alone is still useful if the device is offline.
+            let slots = query_slots(&riot_ipv6_addr, &host_network_interface, &transport)
+                .unwrap_or_else(|e| {
+                    log::warn!("Failed to query the slots of the device: {}", e);
+                    vec![]
+                });
+            let registry = registry::Registry::load()?;
+            let stats = slot_stats(&registry, &riot_ipv6_addr, &slots);
+            if stats.is_empty() {
+                println!("No deployments recorded for {}", riot_ipv6_addr);
+                return Ok(());
+            }
+
+            println!(
+                "{:>4} {:>11} {:>8} {:>14} {:>12}  {}",
+                "Slot",
+                "Deployments",
+                format!("Last {}m", registry::HAMMERING_WINDOW / 60),
+                "Last deployed",
+                "Erase cycles",
+                "Program"
+            );
+            for slot in &stats {
+                let last_deployed = slot
+                    .last_deployed_at
+                    .map(|t| format!("{}s ago", registry::now().saturating_sub(t)))
+                    .unwrap_or_else(|| "-".to_string());
+                let erase_cycles = slot
+                    .erase_cycles
+                    .map(|c| c.to_string())
+                    .unwrap_or_else(|| "-".to_string());
+                let hammered = if slot.is_hammered() {
+                    " (hammered)"
+                } else {
+                    ""
+                };
+                println!(
+                    "{:>4} {:>11} {:>8} {:>14} {:>12}  {}{}",
+                    slot.slot,
+                    slot.deployments,
+                    slot.recent_deployments,
+                    last_deployed,
+                    erase_cycles,
+                    slot.program.as_deref().unwrap_or("-"),
+                    hammered
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+fn handle_logs(args: &Action, use_env: bool) -> Result<(), String> {
+    let Action::Logs {
+        riot_ipv6_addr,
+        host_network_interface,
+        suit_storage_slot,
+        follow,
+        interval,
+    } = args
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", args));
+    };
+
+    let interval = Duration::from_millis(*interval);
+    if use_env {
+        let env = load_env();
+        print_logs(
+            &env.riot_instance_ip,
+            &env.host_net_if,
+            *suit_storage_slot,
+            *follow,
+            interval,
+            &TransportOptions::from_env(&env),
+        )
+    } else {
+        print_logs(
+            riot_ipv6_addr,
+            host_network_interface,
+            *suit_storage_slot,
+            *follow,
+            interval,
+            &TransportOptions::default(),
+        )
+    }
+}
+
+async fn handle_experiment(args: &Action) -> Result<(), String> {
+    let Action::Experiment {
+        spec,
+        output_dir,
+        dry_run,
+    } = args
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", args));
+    };
+
+    let spec = ExperimentSpec::load(spec)?;
+    let env = load_env();
+    let transport = TransportOptions {
+        dry_run: *dry_run,
+        ..TransportOptions::from_env(&env)
+    };
+
+    let report = run_experiment(&spec, &env, output_dir, &transport).await?;
+    println!("{}", report);
+    println!("Raw data and tables written to {}", report.output_dir);
+    Ok(())
+}
+
+async fn handle_run(args: &Action) -> Result<(), String> {
+    let Action::Run {
+        preset,
+        programs,
+        output_dir,
+        dry_run,
+    } = args
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", args));
+    };
+
+    let spec = Preset::load(preset)?.into_spec(preset, programs);
+    let env = load_env();
+    let transport = TransportOptions {
+        dry_run: *dry_run,
+        ..TransportOptions::from_env(&env)
+    };
+
+    let report = run_experiment(&spec, &env, output_dir, &transport).await?;
+    println!("{}", report);
+    println!("Raw data and tables written to {}", report.output_dir);
+    Ok(())
+}
+
+async fn handle_sweep(args: &Action) -> Result<(), String> {
+    let Action::Sweep {
+        spec,
+        output,
+        dry_run,
+    } = args
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", args));
+    };
+
+    let spec = SweepSpec::load(spec)?;
+    let env = load_env();
+    let transport = TransportOptions {
+        dry_run: *dry_run,
+        ..TransportOptions::from_env(&env)
+    };
+
+    let csv = run_sweep(&spec, &env, output.as_deref(), &transport).await?;
+    match output {
+        Some(output) => println!(
+            "Results of {} executions written to {}",
+            spec.executions(),
+            output
+        ),
+        None => print!("{}", csv),
+    }
+    Ok(())
+}
+
+fn handle_flash(args: &Action, use_env: bool) -> Result<(), String> {
+    let Action::Flash {
+        board_name,
+        mibpf_root_dir,
+        app_dir,
+        port,
+        make_args,
+        wait,
+        wait_timeout,
+        riot_ipv6_addr,
+        host_network_interface,
+    } = args
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", args));
+    };
+
+    let (options, riot_ipv6_addr, host_network_interface, transport) = if use_env {
+        let env = load_env();
+        let options = FlashOptions {
+            micro_bpf_root_dir: env.micro_bpf_root_dir.clone(),
+            app_dir: app_dir.clone(),
+            board: env.board_name.clone(),
+            port: port.clone(),
+            make_args: make_args.clone(),
+        };
+        let transport = TransportOptions::from_env(&env);
+        (options, env.riot_instance_ip, env.host_net_if, transport)
+    } else {
+        let options = FlashOptions {
+            micro_bpf_root_dir: mibpf_root_dir.clone(),
+            app_dir: app_dir.clone(),
+            board: board_name.clone(),
+            port: port.clone(),
+            make_args: make_args.clone(),
+        };
+        (
+            options,
+            riot_ipv6_addr.clone(),
+            host_network_interface.clone(),
+            TransportOptions::default(),
+        )
+    };
+
+    flash(&options)?;
+    println!("Flashed the firmware onto {}", options.board);
+
+    if *wait {
+        let elapsed = wait_for_device(
+            &riot_ipv6_addr,
+            &host_network_interface,
+            Duration::from_secs(*wait_timeout),
+            &transport,
+        )?;
+        println!(
+            "The device {} is up after {:.1}s",
+            riot_ipv6_addr,
+            elapsed.as_secs_f64()
+        );
+    }
+    Ok(())
+}
+
+fn handle_proxy(args: &Action, use_env: bool) -> Result<(), String> {
+    let Action::Proxy {
+        listen,
+        riot_ipv6_addr,
+        riot_port,
+        host_network_interface,
+        loss,
+        duplicate,
+        reorder,
+        delay_ms,
+        jitter_ms,
+        seed,
+        slot_policy,
+        device_tags,
+    } = args
+    else {
+        return Err(format!("Invalid subcommand args: {:?}", args));
+    };
+
+    let config = FaultConfig {
+        loss: parse_rate("loss", loss)?,
+        duplicate: parse_rate("duplicate", duplicate)?,
+        reorder: parse_rate("reorder", reorder)?,
+        delay: Duration::from_millis(*delay_ms),
+        jitter: Duration::from_millis(*jitter_ms),
+        seed: *seed,
+    };
+
+    let device = if use_env {
+        let env = load_env();
+        device_address(&env.riot_instance_ip, &env.host_net_if, *riot_port)?
+    } else {
+        device_address(riot_ipv6_addr, host_network_interface, *riot_port)?
+    };
+
+    let guard = match slot_policy {
+        Some(path) => Some(SlotGuard {
+            policy: SlotPolicy::load(path)?,
+            device_tags: device_tags.clone(),
+        }),
+        None => None,
+    };
+
+    run_proxy(listen, device, &config, guard.as_ref())
+}
+
+fn handle_config(action: &ConfigAction) -> Result<(), String> {
+    match action {
+        ConfigAction::Show { resolved: false } => {
+            println!("# {}", env_file_path());
+            print!("{}", load_env());
+            Ok(())
+        }
+        ConfigAction::Show { resolved: true } => {
+            println!("# defaults < {} < {} < --set", config_file_path(), env_file_path());
+            for value in Configuration::load().resolve_all() {
+                println!("{}={}  ({})", value.key, value.display_value(), value.source);
+            }
+            Ok(())
+        }
+        ConfigAction::Set { key, value } => update_env_variable(key, value),
+    }
+}
+
+/// Writes the man page of the tool and a separate page for each of its
+/// subcommands (e.g. micro-bpf-tools-deploy.1) into the output directory.
+fn handle_manpages(out_dir: &str) -> Result<(), String> {
+    std::fs::create_dir_all(out_dir)
+        .map_err(|e| format!("Failed to create the directory {}: {}", out_dir, e))?;
+
+    let command = args::Args::command();
+    let name = command.get_name().to_string();
+    write_manpage(out_dir, &name, command.clone())?;
+    for subcommand in command.get_subcommands() {
+        let page_name = format!("{}-{}", name, subcommand.get_name());
+        write_manpage(out_dir, &page_name, subcommand.clone().name(page_name.clone()))?;
+    }
+    Ok(())
+}
+
+fn write_manpage(out_dir: &str, page_name: &str, command: clap::Command) -> Result<(), String> {
+    let mut buffer = vec![];
+    clap_mangen::Man::new(command)
+        .render(&mut buffer)
+        .map_err(|e| format!("Failed to render the man page {}: {}", page_name, e))?;
+
+    let path = format!("{}/{}.1", out_dir, page_name);
+    std::fs::write(&path, buffer).map_err(|e| format!("Failed to write {}: {}", path, e))
 }
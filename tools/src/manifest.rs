@@ -0,0 +1,58 @@
+use std::fs;
+
+use log::debug;
+use micro_bpf_common::StorageBackend;
+
+use crate::suit::{write_signed_manifest, SuitPayload};
+
+/// A single program delivered by a multi-component SUIT manifest.
+#[derive(Debug, Clone)]
+pub struct ManifestComponent {
+    /// Binary file of the program, it is moved into the CoAP root directory.
+    pub binary: String,
+    /// SUIT storage slot that the program is installed into.
+    pub slot: usize,
+    /// Storage backend of the slot.
+    pub storage: StorageBackend,
+}
+
+/// Generates and signs a SUIT manifest which delivers several programs as
+/// separate components of one update. The device then installs all of them
+/// after a single pull request instead of fetching one manifest per program.
+///
+/// The binaries are moved into the CoAP root directory and the manifest is
+/// written next to them, the device fetches them from `fetch_base_uri` (see
+/// [`crate::Publisher::fetch_base_uri`]). Returns the path to the signed
+/// manifest.
+pub fn generate_manifest(
+    components: &[ManifestComponent],
+    fetch_base_uri: &str,
+    coaproot_dir: &str,
+    manifest_name: &str,
+    sequence_number: u64,
+) -> Result<String, String> {
+    let signed = format!("{}/{}.signed", coaproot_dir, manifest_name);
+
+    let mut payloads = vec![];
+    for component in components {
+        let file_name = component.binary.split('/').last().unwrap();
+        let served = format!("{}/{}", coaproot_dir, file_name);
+        let contents = fs::read(&component.binary)
+            .map_err(|e| format!("Failed to read {}: {}", component.binary, e))?;
+        fs::write(&served, &contents)
+            .and_then(|_| fs::remove_file(&component.binary))
+            .map_err(|e| format!("Failed to move {} into {}: {}", file_name, coaproot_dir, e))?;
+        payloads.push(SuitPayload {
+            uri: format!("{}/{}", fetch_base_uri, file_name),
+            // The component id identifies the SUIT storage location on the
+            // device.
+            component_id: component.storage.component_id(component.slot),
+            contents,
+            compression: None,
+        });
+    }
+
+    write_signed_manifest(&payloads, sequence_number, None, &signed)?;
+    debug!("Generated the multi-component manifest: {}", signed);
+    Ok(signed)
+}
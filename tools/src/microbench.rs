@@ -0,0 +1,291 @@
+use std::{fmt, str::FromStr, thread, time::Duration};
+
+use enum_iterator::all;
+use log::debug;
+use micro_bpf_common::{
+    BinaryFileLayout, HelperAccessListSource, HelperAccessVerification, HelperFunctionID,
+    StorageBackend, TargetVM,
+};
+
+use crate::{
+    artifacts::ArtifactStore,
+    bench::bench,
+    conformance::write_sources,
+    deploy::{deploy, DeployOptions},
+    environment::Environment,
+    experiment::VmSpec,
+    publish::LocalPublisher,
+    slots::SuitSlot,
+    transport::TransportOptions,
+};
+
+/// Number of helper calls made by a single execution of each microbenchmark,
+/// it is passed to the programs through the generated `microbench.h`.
+pub const CALLS_PER_EXECUTION: u32 = 64;
+
+/// A program of the built-in microbenchmark set, it calls the helper
+/// [`CALLS_PER_EXECUTION`] times in a loop.
+pub struct MicroBenchmark {
+    pub name: &'static str,
+    /// The helper being measured, None for the baseline loop which doesn't
+    /// call any helpers.
+    pub helper: Option<HelperFunctionID>,
+    pub source: &'static str,
+}
+
+impl MicroBenchmark {
+    pub fn file_name(&self) -> String {
+        format!("{}.c", self.name)
+    }
+}
+
+macro_rules! microbenchmark {
+    ($name:literal, $helper:expr) => {
+        MicroBenchmark {
+            name: $name,
+            helper: $helper,
+            source: include_str!(concat!("../microbench/", $name, ".c")),
+        }
+    };
+}
+
+/// Same as the conformance corpus, the microbenchmarks are embedded in the
+/// binary so that they can be run against any firmware build. The baseline
+/// has to come first as the other ones are compared against it.
+pub const MICROBENCHMARKS: &[MicroBenchmark] = &[
+    microbenchmark!("baseline", None),
+    microbenchmark!("bpf_printf", Some(HelperFunctionID::BPF_PRINTF_IDX)),
+    microbenchmark!("bpf_now_ms", Some(HelperFunctionID::BPF_NOW_MS_IDX)),
+    microbenchmark!("bpf_ztimer_now", Some(HelperFunctionID::BPF_ZTIMER_NOW_IDX)),
+    microbenchmark!(
+        "bpf_saul_reg_read",
+        Some(HelperFunctionID::BPF_SAUL_REG_READ_IDX)
+    ),
+];
+
+/// When deploying to physical boards, we need to give them some time to
+/// fetch the firmware before requesting execution.
+const FIRMWARE_PULL_TIMEOUT: u64 = 1;
+
+/// Parses the VM configuration given as `<target>[:jit]`, e.g. `rBPF:jit`.
+pub fn parse_vm(vm: &str) -> Result<VmSpec, String> {
+    let (target, jit) = match vm.split_once(':') {
+        Some((target, "jit")) => (target, true),
+        Some(_) => return Err(format!("Invalid VM configuration: {}", vm)),
+        None => (vm, false),
+    };
+    TargetVM::from_str(target)?;
+    Ok(VmSpec {
+        target: target.to_string(),
+        jit,
+    })
+}
+
+/// Per-call overhead of a helper on each of the benchmarked VMs.
+pub struct HelperLatency {
+    pub benchmark: &'static MicroBenchmark,
+    /// Mean execution time per helper call with the time of the baseline
+    /// loop subtracted, in the same order as the VMs of the report.
+    pub per_call: Vec<Result<f64, String>>,
+}
+
+pub struct HelperLatencyReport {
+    pub vms: Vec<VmSpec>,
+    /// Mean execution time of the baseline loop on each of the VMs.
+    pub baseline: Vec<Result<f64, String>>,
+    pub helpers: Vec<HelperLatency>,
+}
+
+/// Measures the overhead of the helper calls on the device described by the
+/// environment. Each microbenchmark is deployed and executed `iterations`
+/// times on each of the VMs, the per-call overhead is the difference between
+/// its mean execution time and the one of the baseline loop divided by the
+/// number of calls.
+pub async fn bench_helpers(
+    environment: &Environment,
+    work_dir: &str,
+    vms: &[VmSpec],
+    binary_layout: BinaryFileLayout,
+    iterations: usize,
+    transport: &TransportOptions,
+) -> Result<HelperLatencyReport, String> {
+    let header = format!("#define CALLS {}\n", CALLS_PER_EXECUTION);
+    write_sources(
+        work_dir,
+        &environment.micro_bpf_root_dir,
+        MICROBENCHMARKS
+            .iter()
+            .map(|b| (b.file_name(), b.source.to_string()))
+            .chain(std::iter::once(("microbench.h".to_string(), header))),
+    )?;
+
+    // Failures of individual microbenchmarks (e.g. a helper missing from the
+    // firmware) are reported in the table instead of aborting the whole run.
+    let mut times = vec![];
+    for benchmark in MICROBENCHMARKS {
+        let mut row = vec![];
+        for vm in vms {
+            debug!("Running the microbenchmark {} on {}", benchmark.name, vm);
+            let time = run_microbenchmark(
+                benchmark,
+                environment,
+                work_dir,
+                vm,
+                binary_layout,
+                iterations,
+                transport,
+            )
+            .await;
+            transport.check_cancelled()?;
+            row.push(time);
+        }
+        times.push(row);
+    }
+
+    let mut times = times.into_iter();
+    let baseline = times.next().unwrap_or_default();
+    let helpers = MICROBENCHMARKS[1..]
+        .iter()
+        .zip(times)
+        .map(|(benchmark, row)| HelperLatency {
+            benchmark,
+            per_call: row
+                .into_iter()
+                .zip(&baseline)
+                .map(|(time, baseline)| match (time, baseline) {
+                    (Ok(time), Ok(baseline)) => Ok((time - baseline) / CALLS_PER_EXECUTION as f64),
+                    (Err(e), _) => Err(e),
+                    (_, Err(_)) => Err("baseline failed".to_string()),
+                })
+                .collect(),
+        })
+        .collect();
+
+    Ok(HelperLatencyReport {
+        vms: vms.to_vec(),
+        baseline,
+        helpers,
+    })
+}
+
+/// Returns the mean execution time of the microbenchmark.
+async fn run_microbenchmark(
+    benchmark: &MicroBenchmark,
+    environment: &Environment,
+    work_dir: &str,
+    vm: &VmSpec,
+    binary_layout: BinaryFileLayout,
+    iterations: usize,
+    transport: &TransportOptions,
+) -> Result<f64, String> {
+    let target = TargetVM::from_str(&vm.target)?;
+    let helpers = all::<HelperFunctionID>()
+        .map(|h| h as u8)
+        .collect::<Vec<u8>>();
+
+    let (slot, _) = deploy(
+        &format!("{}/{}", work_dir, benchmark.file_name()),
+        &ArtifactStore {
+            object_dir: format!("{}/out", work_dir),
+            ..ArtifactStore::from_env(environment)
+        },
+        target,
+        binary_layout,
+        SuitSlot::Fixed(0),
+        StorageBackend::Ram,
+        &environment.riot_instance_net_if,
+        &environment.riot_instance_ip,
+        &environment.host_net_if,
+        &environment.host_ip,
+        &environment.board_name,
+        Some(&environment.micro_bpf_root_dir),
+        helpers.clone(),
+        HelperAccessVerification::AheadOfTime,
+        HelperAccessListSource::ExecuteRequest,
+        &DeployOptions {
+            erase: true,
+            ..Default::default()
+        },
+        &LocalPublisher::new(&environment.coap_root_dir),
+        transport,
+    )
+    .await?;
+
+    if environment.board_name != "native" {
+        thread::sleep(Duration::from_secs(FIRMWARE_PULL_TIMEOUT));
+    }
+
+    let report = bench(
+        &environment.riot_instance_ip,
+        &environment.host_net_if,
+        target,
+        binary_layout,
+        slot,
+        &helpers,
+        vm.jit,
+        iterations,
+        None,
+        Duration::ZERO,
+        None,
+        transport,
+    )
+    .await?;
+
+    let samples = report.samples.len().max(1) as f64;
+    Ok(report
+        .samples
+        .iter()
+        .map(|s| s.times.execution_time as f64)
+        .sum::<f64>()
+        / samples)
+}
+
+impl fmt::Display for HelperLatencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cell = |time: &Result<f64, String>| match time {
+            Ok(time) => format!("{:.2}", time),
+            Err(_) => "failed".to_string(),
+        };
+
+        write!(f, "{:<24}", "Helper (us per call)")?;
+        for vm in &self.vms {
+            write!(f, " {:>16}", vm.to_string())?;
+        }
+        writeln!(f)?;
+
+        for helper in &self.helpers {
+            write!(f, "{:<24}", helper.benchmark.name)?;
+            for time in &helper.per_call {
+                write!(f, " {:>16}", cell(time))?;
+            }
+            writeln!(f)?;
+        }
+
+        write!(f, "{:<24}", "baseline loop (total)")?;
+        for time in &self.baseline {
+            write!(f, " {:>16}", cell(time))?;
+        }
+
+        // The reasons of the failures don't fit into the table.
+        let failures = self
+            .helpers
+            .iter()
+            .flat_map(|h| {
+                h.per_call
+                    .iter()
+                    .zip(&self.vms)
+                    .map(move |(t, vm)| (h.benchmark.name, vm, t))
+            })
+            .chain(
+                self.baseline
+                    .iter()
+                    .zip(&self.vms)
+                    .map(|(t, vm)| ("baseline", vm, t)),
+            )
+            .filter_map(|(name, vm, time)| time.as_ref().err().map(|e| (name, vm, e)));
+        for (name, vm, error) in failures {
+            write!(f, "\n{} on {} failed: {}", name, vm, error)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+use std::{fs, path::PathBuf};
+
+use log::debug;
+use serde_json::json;
+
+/// Per-device store of OSCORE (RFC 8613) security contexts.
+///
+/// Each device gets its own directory named after its IPv6 address inside of
+/// the root directory of the store. The layout of the directory follows the
+/// format of the aiocoap `FilesystemSecurityContext` so that it can be used
+/// directly by the aiocoap-client that we use for sending the requests:
+///
+/// ```text
+/// oscore/
+///   fe80::a0d9:ebff:fed5:986b/
+///     settings.json    <- sender/recipient IDs and the master secret
+///     sequence.json    <- maintained by aiocoap (replay protection)
+///     credentials.json <- generated, maps the device URLs to the context
+/// ```
+pub struct SecurityContextStore {
+    root: PathBuf,
+}
+
+impl SecurityContextStore {
+    pub fn new(root: &str) -> Self {
+        SecurityContextStore {
+            root: PathBuf::from(root),
+        }
+    }
+
+    /// Returns the directory holding the security context of a given device.
+    pub fn context_dir(&self, device_ip: &str) -> PathBuf {
+        self.root.join(device_ip)
+    }
+
+    pub fn has_context(&self, device_ip: &str) -> bool {
+        self.context_dir(device_ip).join("settings.json").exists()
+    }
+
+    /// Creates a new security context for the device. The sender and recipient
+    /// IDs as well as the master secret need to be hex-encoded and have to
+    /// match the ones that the device was provisioned with.
+    pub fn create_context(
+        &self,
+        device_ip: &str,
+        sender_id: &str,
+        recipient_id: &str,
+        master_secret: &str,
+    ) -> Result<(), String> {
+        let dir = self.context_dir(device_ip);
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create the OSCORE context directory: {}", e))?;
+
+        let settings = json!({
+            "sender-id_hex": sender_id,
+            "recipient-id_hex": recipient_id,
+            "secret_hex": master_secret,
+        });
+
+        fs::write(
+            dir.join("settings.json"),
+            serde_json::to_string_pretty(&settings).unwrap(),
+        )
+        .map_err(|e| format!("Failed to write the OSCORE context settings: {}", e))
+    }
+}
+
+/// Generates the aiocoap credentials file which instructs the client to protect
+/// all requests sent to the device targeted by the `url` using the security
+/// context of that device. Returns the path to the generated file.
+pub fn prepare_credentials(context_dir: &str, url: &str) -> Result<String, String> {
+    let store = SecurityContextStore::new(context_dir);
+    let (authority, device_ip) = parse_authority(url)?;
+
+    if !store.has_context(device_ip) {
+        return Err(format!(
+            "No OSCORE security context found for the device {} in {}",
+            device_ip, context_dir
+        ));
+    }
+
+    let dir = fs::canonicalize(store.context_dir(device_ip))
+        .map_err(|e| format!("Failed to resolve the OSCORE context directory: {}", e))?;
+
+    let credentials = json!({
+        format!("{}/*", authority): {
+            "oscore": { "contextfile": format!("{}/", dir.display()) }
+        }
+    });
+
+    let credentials_file = dir.join("credentials.json");
+    fs::write(
+        &credentials_file,
+        serde_json::to_string_pretty(&credentials).unwrap(),
+    )
+    .map_err(|e| format!("Failed to write the OSCORE credentials file: {}", e))?;
+
+    debug!("OSCORE credentials: {}", credentials);
+    Ok(credentials_file.display().to_string())
+}
+
+/// Splits the request url of the form `coap://[ip%netif]:port/path` into the
+/// authority part (`coap://[ip%netif]:port`) and the IPv6 address of the
+/// device. The port is optional.
+pub(crate) fn parse_authority(url: &str) -> Result<(&str, &str), String> {
+    let (Some(start), Some(end)) = (url.find('['), url.find(']')) else {
+        return Err(format!("Unable to extract the device address from: {}", url));
+    };
+
+    let host = &url[start + 1..end];
+    let device_ip = host.split('%').next().unwrap_or(host);
+    let port_len = match url[end + 1..].strip_prefix(':') {
+        Some(rest) => 1 + rest.chars().take_while(|c| c.is_ascii_digit()).count(),
+        None => 0,
+    };
+    Ok((&url[..end + 1 + port_len], device_ip))
+}
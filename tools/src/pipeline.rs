@@ -0,0 +1,179 @@
+use log::debug;
+use serde_json::{json, Value};
+
+use crate::webhook::post_json;
+
+/// A host-side post-processing stage that the response of a device execution
+/// is piped through. Each stage receives the (possibly modified) response
+/// produced by the previous one.
+///
+/// The responses are represented as JSON values, e.g. the short-lived
+/// execution model responds with: `{"execution_time": 10, "result": 0}`
+pub trait Processor {
+    fn name(&self) -> String;
+    fn process(&mut self, response: Value) -> Result<Value, String>;
+}
+
+/// Applies a linear transformation `value * factor + offset` to a numeric
+/// field of the response, e.g. to convert raw sensor readings into proper units.
+pub struct UnitConversion {
+    pub field: String,
+    pub factor: f64,
+    pub offset: f64,
+}
+
+impl Processor for UnitConversion {
+    fn name(&self) -> String {
+        format!("scale({})", self.field)
+    }
+
+    fn process(&mut self, mut response: Value) -> Result<Value, String> {
+        let value = numeric_field(&response, &self.field)?;
+        response[&self.field] = json!(value * self.factor + self.offset);
+        Ok(response)
+    }
+}
+
+/// Checks that a numeric field of the response lies within the specified bounds.
+/// The verdict is added to the response as the `alert` field so that the
+/// following stages (e.g. [`WebhookNotifier`]) can act on it.
+pub struct Threshold {
+    pub field: String,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl Processor for Threshold {
+    fn name(&self) -> String {
+        format!("threshold({})", self.field)
+    }
+
+    fn process(&mut self, mut response: Value) -> Result<Value, String> {
+        let value = numeric_field(&response, &self.field)?;
+        let below = self.min.map_or(false, |min| value < min);
+        let above = self.max.map_or(false, |max| value > max);
+        let alert = below || above || response["alert"].as_bool().unwrap_or(false);
+        response["alert"] = json!(alert);
+        Ok(response)
+    }
+}
+
+/// Posts the response to a webhook, optionally only when one of the previous
+/// stages raised an alert.
+pub struct WebhookNotifier {
+    pub url: String,
+    pub only_alerts: bool,
+}
+
+impl Processor for WebhookNotifier {
+    fn name(&self) -> String {
+        format!("webhook({})", self.url)
+    }
+
+    fn process(&mut self, response: Value) -> Result<Value, String> {
+        if !self.only_alerts || response["alert"].as_bool().unwrap_or(false) {
+            post_json(&self.url, &response)?;
+        }
+        Ok(response)
+    }
+}
+
+fn numeric_field(response: &Value, field: &str) -> Result<f64, String> {
+    response[field]
+        .as_f64()
+        .ok_or_else(|| format!("The response doesn't contain a numeric field '{}'", field))
+}
+
+/// A sequence of post-processing stages applied to the execution responses.
+#[derive(Default)]
+pub struct Pipeline {
+    stages: Vec<Box<dyn Processor>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Pipeline::default()
+    }
+
+    pub fn add_stage(mut self, stage: Box<dyn Processor>) -> Self {
+        self.stages.push(stage);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Builds the pipeline from the stage specifications passed on the command
+    /// line. The supported stages are:
+    /// - `scale:<field>:<factor>[:<offset>]`
+    /// - `threshold:<field>:<min>:<max>` (either bound can be left empty)
+    /// - `webhook:<url>` and `alert-webhook:<url>`
+    pub fn parse(specs: &[String]) -> Result<Self, String> {
+        let mut pipeline = Pipeline::new();
+        for spec in specs {
+            pipeline = pipeline.add_stage(parse_stage(spec)?);
+        }
+        Ok(pipeline)
+    }
+
+    /// Runs the raw response received from the device through all stages.
+    pub fn run(&mut self, response: &str) -> Result<Value, String> {
+        let mut value: Value = serde_json::from_str(response.trim_matches(char::from(0)))
+            .map_err(|e| format!("Failed to parse the json response: {}", e))?;
+
+        for stage in self.stages.iter_mut() {
+            debug!("Applying the pipeline stage: {}", stage.name());
+            value = stage
+                .process(value)
+                .map_err(|e| format!("Stage {} failed: {}", stage.name(), e))?;
+        }
+        Ok(value)
+    }
+}
+
+fn parse_stage(spec: &str) -> Result<Box<dyn Processor>, String> {
+    let Some((kind, arguments)) = spec.split_once(':') else {
+        return Err(format!("Invalid pipeline stage: {}", spec));
+    };
+
+    let parse_number = |s: &str| {
+        s.parse::<f64>()
+            .map_err(|_| format!("Invalid number '{}' in the stage: {}", s, spec))
+    };
+    let parse_bound = |s: &str| {
+        if s.is_empty() {
+            Ok(None)
+        } else {
+            parse_number(s).map(Some)
+        }
+    };
+
+    let parts = arguments.split(':').collect::<Vec<&str>>();
+    match (kind, parts.as_slice()) {
+        ("scale", [field, factor]) => Ok(Box::new(UnitConversion {
+            field: field.to_string(),
+            factor: parse_number(*factor)?,
+            offset: 0.0,
+        })),
+        ("scale", [field, factor, offset]) => Ok(Box::new(UnitConversion {
+            field: field.to_string(),
+            factor: parse_number(*factor)?,
+            offset: parse_number(*offset)?,
+        })),
+        ("threshold", [field, min, max]) => Ok(Box::new(Threshold {
+            field: field.to_string(),
+            min: parse_bound(*min)?,
+            max: parse_bound(*max)?,
+        })),
+        ("webhook", _) => Ok(Box::new(WebhookNotifier {
+            url: arguments.to_string(),
+            only_alerts: false,
+        })),
+        ("alert-webhook", _) => Ok(Box::new(WebhookNotifier {
+            url: arguments.to_string(),
+            only_alerts: true,
+        })),
+        _ => Err(format!("Invalid pipeline stage: {}", spec)),
+    }
+}
@@ -1,41 +1,69 @@
 use std::{
+    collections::BTreeMap,
     fs::{self, File},
     io::{Read, Write as _},
     process::Command,
 };
 
+use log::debug;
 use micro_bpf_common::{BinaryFileLayout, HelperAccessVerification};
 use micro_bpf_elf_utils::{
-    assemble_binary_specifying_helpers, assemble_femtocontainer_binary, extract_section,
+    assemble_binary_with_entry, assemble_femtocontainer_binary_with_entry, extract_section,
+    function_offset, prepend_entry_jump, ProgramMetadata,
 };
 
+use crate::provenance::{GitRevision, Provenance};
+
 // This module is responsible for applying different post-processing steps
 // to the input ELF file to transform it into a corresponding binary layout
-// that the VM expects to when loading the program.
+// that the VM expects to when loading the program. The program metadata is
+// only embedded in the binaries using the ExtendedHeader layout. If the entry
+// function is given, the program starts executing from it instead of the
+// first function of the .text section.
 pub fn apply_postprocessing(
     source_object_file: &str,
     binary_layout: BinaryFileLayout,
     output_file_name: &str,
     helper_indices: Vec<u8>,
     helper_access_verification: HelperAccessVerification,
+    metadata: Option<ProgramMetadata>,
+    entry: Option<&str>,
 ) -> Result<(), String> {
     let processed_program_bytes = match binary_layout {
         BinaryFileLayout::OnlyTextSection => {
             let program_bytes = read_bytes_from_file(source_object_file);
             let text_section_bytes = extract_section(".text", &program_bytes)?;
-            Vec::from(text_section_bytes)
+            let mut text = Vec::from(text_section_bytes);
+            if let Some(entry) = entry {
+                let binary = goblin::elf::Elf::parse(&program_bytes)
+                    .map_err(|e| format!("Failed to parse the ELF binary: {}", e))?;
+                prepend_entry_jump(&mut text, &binary, entry)?;
+            }
+            text
         }
         BinaryFileLayout::ExtendedHeader => {
             let program_bytes = read_bytes_from_file(source_object_file);
-            let relocated_program =
-                assemble_binary_specifying_helpers(&program_bytes, helper_indices.clone())?;
+            let relocated_program = assemble_binary_with_entry(
+                &program_bytes,
+                helper_indices.clone(),
+                metadata,
+                entry,
+            )?;
             relocated_program
         }
         BinaryFileLayout::FemtoContainersHeader => {
             let program_bytes = read_bytes_from_file(source_object_file);
-            let relocated_program = assemble_femtocontainer_binary(&program_bytes)?;
+            let relocated_program =
+                assemble_femtocontainer_binary_with_entry(&program_bytes, entry)?;
             relocated_program
         }
+        // The relocations are resolved on the device, which always starts
+        // from the first function.
+        BinaryFileLayout::RawObjectFile if entry.is_some() => {
+            return Err(
+                "The entry function can't be selected for the RawObjectFile layout".to_string(),
+            );
+        }
         BinaryFileLayout::RawObjectFile => {
             strip_binary(&source_object_file, Some(&output_file_name.to_string()))?;
             read_bytes_from_file(output_file_name)
@@ -56,6 +84,65 @@ pub fn apply_postprocessing(
     write_binary(&processed_program_bytes, output_file_name)
 }
 
+/// Returns the offsets (in instructions) of the entry functions in the
+/// binary produced by [`apply_postprocessing`] when the first one of them is
+/// selected as the entry function, keyed by their names.
+pub fn entry_offsets(
+    source_object_file: &str,
+    entries: &[&str],
+) -> Result<BTreeMap<String, u32>, String> {
+    if entries.is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    let program_bytes = read_bytes_from_file(source_object_file);
+    let binary = goblin::elf::Elf::parse(&program_bytes)
+        .map_err(|e| format!("Failed to parse the ELF binary: {}", e))?;
+    let offsets = entries
+        .iter()
+        .map(|entry| function_offset(&binary, entry).map(|offset| (*entry, offset)))
+        .collect::<Result<Vec<(&str, usize)>, String>>()?;
+    // The jump to the first entry takes up one instruction, it is only
+    // inserted if the entry isn't the first function already.
+    let shift = match offsets.first() {
+        Some((_, offset)) if *offset != 0 => 1,
+        _ => 0,
+    };
+    Ok(offsets
+        .into_iter()
+        .map(|(entry, offset)| (entry.to_string(), (offset / 8) as u32 + shift))
+        .collect())
+}
+
+/// Creates the metadata embedded into the binary produced from the given
+/// source file. The version of the program can be specified using an
+/// annotation at the top of the source file: `// VERSION: 1.2.0`, otherwise
+/// it defaults to 0.0.0. The git revisions of the sources are embedded as
+/// well, see [`Provenance`].
+pub fn program_metadata(
+    bpf_source_file: &str,
+    object_file: &str,
+    provenance: &Provenance,
+) -> ProgramMetadata {
+    let name = bpf_source_file
+        .split("/")
+        .last()
+        .unwrap()
+        .split(".")
+        .nth(0)
+        .unwrap_or_default();
+
+    let version = fs::read_to_string(bpf_source_file)
+        .unwrap_or_default()
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("// VERSION:").map(|v| v.trim().to_string()))
+        .unwrap_or_else(|| "0.0.0".to_string());
+
+    ProgramMetadata::new(name, &version, &read_bytes_from_file(object_file)).with_revisions(
+        provenance.source.as_ref().map(GitRevision::encode),
+        provenance.mibpf_root.as_ref().map(GitRevision::encode),
+    )
+}
+
 pub fn map_interpreter(layout: BinaryFileLayout) -> rbpf::InterpreterVariant {
     match layout {
         BinaryFileLayout::FemtoContainersHeader => rbpf::InterpreterVariant::FemtoContainersHeader,
@@ -81,6 +168,21 @@ pub fn read_bytes_from_file(source_object_file: &str) -> Vec<u8> {
     buffer
 }
 
+/// Parses the layout of an artifact given on the command line, `auto`
+/// detects it from the contents of the file (see
+/// [`BinaryFileLayout::detect`]).
+pub fn artifact_layout(binary_layout: &str, binary_file: &str) -> Result<BinaryFileLayout, String> {
+    if binary_layout != "auto" {
+        return binary_layout.parse::<BinaryFileLayout>();
+    }
+    let layout = BinaryFileLayout::detect(&read_bytes_from_file(binary_file)).ok_or(format!(
+        "Unable to detect the layout of {}, specify it using --binary-layout",
+        binary_file
+    ))?;
+    debug!("Detected the {:?} layout of {}", layout, binary_file);
+    Ok(layout)
+}
+
 /// Uses the strip command to remove all of the debug and .BTF info from the
 /// ELF object file. It is required in order to decrease the binary size so that
 /// it can be sent directly to the target device where the relocations can be
@@ -0,0 +1,86 @@
+use std::{process::Command, thread, time::Duration};
+
+use log::debug;
+use serde::Deserialize;
+
+/// Energy consumed by the device over a measurement window.
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct Measurement {
+    /// Energy consumed during the window in microjoules.
+    pub energy_uj: f64,
+    /// Length of the measurement window in milliseconds.
+    pub duration_ms: f64,
+}
+
+impl Measurement {
+    /// Average power over the window in milliwatts.
+    pub fn average_power_mw(&self) -> f64 {
+        if self.duration_ms == 0.0 {
+            return 0.0;
+        }
+        self.energy_uj / self.duration_ms
+    }
+}
+
+/// External power measurement instrument (e.g. a Nordic PPK2 or an INA226
+/// current sensor) that records the energy consumed by the device between
+/// calls to [`PowerProbe::start`] and [`PowerProbe::stop`].
+pub trait PowerProbe {
+    fn name(&self) -> String;
+    fn start(&mut self) -> Result<(), String>;
+    fn stop(&mut self) -> Result<Measurement, String>;
+
+    /// Measures the power consumption of the idle device over the given
+    /// window. It is subtracted from the measurements taken around the
+    /// executions so that only the energy caused by the execution is reported.
+    fn measure_idle(&mut self, window: Duration) -> Result<Measurement, String> {
+        self.start()?;
+        thread::sleep(window);
+        self.stop()
+    }
+}
+
+/// Controls the instrument through a local script, this way we don't need to
+/// link the vendor libraries and serial protocols of each instrument into the
+/// tool. The script is invoked as `<command> start` and `<command> stop`, the
+/// latter needs to print the measurement in the form:
+/// `{"energy_uj": 120.5, "duration_ms": 15.2}`
+pub struct ScriptProbe {
+    pub command: String,
+}
+
+impl ScriptProbe {
+    fn run(&self, action: &str) -> Result<String, String> {
+        debug!("Running the power probe: {} {}", self.command, action);
+        let output = Command::new(&self.command)
+            .arg(action)
+            .output()
+            .map_err(|e| format!("Failed to run the power probe {}: {}", self.command, e))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "Power probe {} {} failed: {}",
+                self.command,
+                action,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+}
+
+impl PowerProbe for ScriptProbe {
+    fn name(&self) -> String {
+        format!("script({})", self.command)
+    }
+
+    fn start(&mut self) -> Result<(), String> {
+        self.run("start").map(|_| ())
+    }
+
+    fn stop(&mut self) -> Result<Measurement, String> {
+        let output = self.run("stop")?;
+        serde_json::from_str(output.trim())
+            .map_err(|e| format!("Failed to parse the power measurement: {}", e))
+    }
+}
@@ -0,0 +1,109 @@
+use std::{fmt, path::Path, process::Command};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+/// Commit of a git repository that an artifact was built from.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GitRevision {
+    pub commit: String,
+    /// Set if the working tree had uncommitted changes, in which case the
+    /// commit doesn't identify the sources exactly.
+    pub dirty: bool,
+}
+
+impl GitRevision {
+    /// Determines the revision of the repository containing the given file or
+    /// directory. A file also makes the revision dirty if it isn't committed
+    /// itself, e.g. a new program that was never added to the repository.
+    /// Returns None if the path isn't inside of a git repository.
+    pub fn of(path: &str) -> Option<Self> {
+        let path = Path::new(path);
+        let (dir, file) = if path.is_dir() {
+            (path, None)
+        } else {
+            let dir = path.parent().filter(|p| !p.as_os_str().is_empty());
+            (dir.unwrap_or(Path::new(".")), path.file_name())
+        };
+
+        let git = |args: &[&str]| {
+            let output = Command::new("git").arg("-C").arg(dir).args(args).output();
+            match output {
+                Ok(output) if output.status.success() => {
+                    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+                }
+                _ => None,
+            }
+        };
+
+        let commit = git(&["rev-parse", "HEAD"])?;
+        let mut dirty = !git(&["status", "--porcelain", "--untracked-files=no"])?.is_empty();
+        if let Some(file) = file.and_then(|f| f.to_str()) {
+            dirty |= !git(&["status", "--porcelain", "--", file])?.is_empty();
+        }
+        debug!(
+            "Revision of {}: {} (dirty: {})",
+            dir.display(),
+            commit,
+            dirty
+        );
+        Some(GitRevision { commit, dirty })
+    }
+
+    /// Full form of the revision embedded in the program metadata.
+    pub fn encode(&self) -> String {
+        format!("{}{}", self.commit, if self.dirty { "-dirty" } else { "" })
+    }
+}
+
+impl fmt::Display for GitRevision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let commit = &self.commit[..self.commit.len().min(12)];
+        write!(f, "{}{}", commit, if self.dirty { "-dirty" } else { "" })
+    }
+}
+
+/// Revisions of the sources that a deployed program was built from: the
+/// repository containing the program itself and the micro-bpf repository
+/// (RIOT, the SUIT tooling and the firmware) used to build and sign it. It
+/// is recorded in the registry and embedded in the program metadata so that
+/// every program on a device can be traced back to the exact sources.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Provenance {
+    #[serde(default)]
+    pub source: Option<GitRevision>,
+    #[serde(default)]
+    pub mibpf_root: Option<GitRevision>,
+}
+
+impl Provenance {
+    pub fn collect(bpf_source_file: &str, mibpf_root_dir: Option<&str>) -> Self {
+        Provenance {
+            source: GitRevision::of(bpf_source_file),
+            // Same default as used when signing the manifests.
+            mibpf_root: GitRevision::of(mibpf_root_dir.unwrap_or(".")),
+        }
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        [&self.source, &self.mibpf_root]
+            .iter()
+            .any(|revision| revision.as_ref().is_some_and(|r| r.dirty))
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |revision: &Option<GitRevision>| {
+            revision
+                .as_ref()
+                .map_or("untracked".to_string(), GitRevision::to_string)
+        };
+        write!(
+            f,
+            "source {}, mibpf {}",
+            show(&self.source),
+            show(&self.mibpf_root)
+        )
+    }
+}
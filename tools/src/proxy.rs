@@ -0,0 +1,399 @@
+use std::{
+    io::{self, ErrorKind},
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::debug;
+
+use crate::{
+    interface::resolve_interface,
+    quota::{forbidden_response, SlotGuard},
+};
+
+/// Faults injected into the traffic passing through the proxy. The rates are
+/// probabilities (0.0 - 1.0) applied independently to each datagram.
+#[derive(Debug, Clone, Default)]
+pub struct FaultConfig {
+    /// Probability that a datagram is dropped.
+    pub loss: f64,
+    /// Probability that a datagram is delivered twice.
+    pub duplicate: f64,
+    /// Probability that a datagram is held back so that the datagrams sent
+    /// after it overtake it.
+    pub reorder: f64,
+    /// Delay added to every datagram.
+    pub delay: Duration,
+    /// Upper bound of the random delay added on top of `delay`.
+    pub jitter: Duration,
+    /// Seed of the random number generator, allows for reproducing a run.
+    pub seed: Option<u64>,
+}
+
+/// Datagrams that are reordered are held back for this long in addition to
+/// their delay.
+const REORDER_HOLD: Duration = Duration::from_millis(200);
+
+/// Large enough for any UDP datagram, so that none of them gets truncated.
+const MAX_DATAGRAM_SIZE: usize = 65536;
+
+/// Small xorshift generator, the quality of the randomness isn't important
+/// here, but being able to reproduce a run from its seed is.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Returns true with the given probability.
+    fn chance(&mut self, probability: f64) -> bool {
+        // The top 53 bits are mapped onto [0, 1).
+        let sample = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+        sample < probability
+    }
+
+    fn up_to(&mut self, max: Duration) -> Duration {
+        match max.as_micros() as u64 {
+            0 => Duration::ZERO,
+            max => Duration::from_micros(self.next() % max),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    ToDevice,
+    ToClient,
+}
+
+impl Direction {
+    fn arrow(&self) -> &'static str {
+        match self {
+            Direction::ToDevice => "client -> device",
+            Direction::ToClient => "device -> client",
+        }
+    }
+}
+
+/// Resolves the address of the device, link-local addresses need to specify
+/// the network interface, e.g. `fe80::1%tap0`.
+pub fn device_address(
+    riot_ipv6_addr: &str,
+    network_interface: &str,
+    port: u16,
+) -> Result<SocketAddr, String> {
+    let host = if riot_ipv6_addr.starts_with("fe80") {
+        let network_interface = resolve_interface(riot_ipv6_addr, network_interface)?;
+        format!("{}%{}", riot_ipv6_addr, network_interface)
+    } else {
+        riot_ipv6_addr.to_string()
+    };
+    (host.as_str(), port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Failed to resolve the device address {}: {}", host, e))?
+        .next()
+        .ok_or_else(|| format!("No address found for {}", host))
+}
+
+/// Runs a UDP proxy which forwards the datagrams sent to `listen_addr` to the
+/// device and the responses of the device back to the client, injecting the
+/// configured faults in both directions. It allows for testing how the
+/// retransmissions of CoAP and the blockwise transfers cope with a degraded
+/// network. The requests need to be sent to the proxy instead of the device,
+/// e.g. by setting the address of the RIOT instance to the one of the proxy.
+///
+/// The proxy serves a single client at a time, the responses of the device
+/// are sent to the client that sent the most recent request. It runs until
+/// the process is terminated.
+///
+/// If the `guard` is given, the requests violating the slot ownership of the
+/// testbed are answered with 4.03 Forbidden instead of being forwarded.
+pub fn run_proxy(
+    listen_addr: &str,
+    device_addr: SocketAddr,
+    config: &FaultConfig,
+    guard: Option<&SlotGuard>,
+) -> Result<(), String> {
+    let client_socket = UdpSocket::bind(listen_addr)
+        .map_err(|e| format!("Failed to bind to {}: {}", listen_addr, e))?;
+    let unspecified = if device_addr.is_ipv6() {
+        "[::]:0"
+    } else {
+        "0.0.0.0:0"
+    };
+    let device_socket = UdpSocket::bind(unspecified)
+        .map_err(|e| format!("Failed to create the upstream socket: {}", e))?;
+    device_socket
+        .connect(device_addr)
+        .map_err(|e| format!("Failed to connect to {}: {}", device_addr, e))?;
+
+    let seed = config.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(1)
+    });
+    println!(
+        "[proxy] {} <-> {} (loss: {}, duplicate: {}, reorder: {}, delay: {:?}, jitter: {:?}, seed: {})",
+        listen_addr,
+        device_addr,
+        config.loss,
+        config.duplicate,
+        config.reorder,
+        config.delay,
+        config.jitter,
+        seed
+    );
+    // The xorshift state must not be zero.
+    let random = Arc::new(Mutex::new(Random(seed.max(1))));
+    let client: Arc<Mutex<Option<SocketAddr>>> = Arc::new(Mutex::new(None));
+
+    let upstream = {
+        let client_socket = clone_socket(&client_socket)?;
+        let device_socket = clone_socket(&device_socket)?;
+        let (random, client, config) = (random.clone(), client.clone(), config.clone());
+        thread::spawn(move || -> Result<(), String> {
+            let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+            loop {
+                // The ICMP errors (e.g. port unreachable while the device is
+                // rebooting) are reported by the following receive on the
+                // connected socket, the proxy needs to outlive them.
+                let len = match device_socket.recv(&mut buffer) {
+                    Ok(len) => len,
+                    Err(e) if is_transient(&e) => {
+                        println!("[proxy] failed to receive from the device: {}", e);
+                        continue;
+                    }
+                    Err(e) => return Err(format!("Failed to receive from the device: {}", e)),
+                };
+                let Some(client_addr) = *client.lock().unwrap() else {
+                    debug!("Dropping a datagram from the device, no client connected yet");
+                    continue;
+                };
+                let socket = clone_socket(&client_socket)?;
+                forward(
+                    &buffer[..len],
+                    Direction::ToClient,
+                    &config,
+                    &random,
+                    move |datagram| socket.send_to(datagram, client_addr).map(|_| ()),
+                );
+            }
+        })
+    };
+
+    let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+    loop {
+        if upstream.is_finished() {
+            return match upstream.join() {
+                Ok(result) => result,
+                Err(_) => Err("The device side of the proxy panicked".to_string()),
+            };
+        }
+        let (len, client_addr) = match client_socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(e) if is_transient(&e) => {
+                println!("[proxy] failed to receive from the client: {}", e);
+                continue;
+            }
+            Err(e) => return Err(format!("Failed to receive from the client: {}", e)),
+        };
+        *client.lock().unwrap() = Some(client_addr);
+        if let Some(Err(reason)) = guard.map(|guard| guard.check(&buffer[..len])) {
+            println!("[proxy] rejected a request of {}: {}", client_addr, reason);
+            if let Some(response) = forbidden_response(&buffer[..len], &reason) {
+                if let Err(e) = client_socket.send_to(&response, client_addr) {
+                    debug!("Failed to send the rejection to {}: {}", client_addr, e);
+                }
+            }
+            continue;
+        }
+        let socket = clone_socket(&device_socket)?;
+        forward(
+            &buffer[..len],
+            Direction::ToDevice,
+            config,
+            &random,
+            move |datagram| socket.send(datagram).map(|_| ()),
+        );
+    }
+}
+
+/// Decides which faults apply to the datagram and schedules its delivery.
+fn forward<F>(
+    datagram: &[u8],
+    direction: Direction,
+    config: &FaultConfig,
+    random: &Mutex<Random>,
+    send: F,
+) where
+    F: Fn(&[u8]) -> std::io::Result<()> + Send + 'static,
+{
+    let (dropped, copies, reordered, delay) = {
+        let mut random = random.lock().unwrap();
+        let dropped = random.chance(config.loss);
+        let copies = if random.chance(config.duplicate) {
+            2
+        } else {
+            1
+        };
+        let reordered = random.chance(config.reorder);
+        let delay = config.delay + random.up_to(config.jitter);
+        (dropped, copies, reordered, delay)
+    };
+
+    let arrow = direction.arrow();
+    if dropped {
+        println!("[proxy] {}: dropped {} bytes", arrow, datagram.len());
+        return;
+    }
+    if copies > 1 {
+        println!("[proxy] {}: duplicated {} bytes", arrow, datagram.len());
+    }
+    let delay = if reordered {
+        println!("[proxy] {}: reordered {} bytes", arrow, datagram.len());
+        delay + REORDER_HOLD
+    } else {
+        delay
+    };
+
+    let datagram = datagram.to_vec();
+    let deliver = move || {
+        for _ in 0..copies {
+            if let Err(e) = send(&datagram) {
+                debug!("Failed to forward the datagram ({}): {}", arrow, e);
+            }
+        }
+    };
+    if delay.is_zero() {
+        deliver();
+    } else {
+        thread::spawn(move || {
+            thread::sleep(delay);
+            deliver();
+        });
+    }
+}
+
+/// Errors which only affect a single datagram, the proxy keeps running.
+fn is_transient(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::Interrupted
+            | ErrorKind::TimedOut
+            | ErrorKind::WouldBlock
+    )
+}
+
+fn clone_socket(socket: &UdpSocket) -> Result<UdpSocket, String> {
+    socket
+        .try_clone()
+        .map_err(|e| format!("Failed to clone the socket: {}", e))
+}
+
+/// Parses a probability given on the command line.
+pub fn parse_rate(name: &str, rate: &str) -> Result<f64, String> {
+    rate.parse::<f64>()
+        .ok()
+        .filter(|rate| (0.0..=1.0).contains(rate))
+        .ok_or_else(|| {
+            format!(
+                "Invalid {} rate {}, expected a value in 0.0 - 1.0",
+                name, rate
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Device answering each datagram with its reversed contents.
+    fn start_device(addr: SocketAddr) {
+        let socket = UdpSocket::bind(addr).unwrap();
+        thread::spawn(move || {
+            let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+            while let Ok((len, client)) = socket.recv_from(&mut buffer) {
+                buffer[..len].reverse();
+                let _ = socket.send_to(&buffer[..len], client);
+            }
+        });
+    }
+
+    fn unused_address() -> SocketAddr {
+        UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+    }
+
+    fn start_proxy(device_addr: SocketAddr) -> UdpSocket {
+        let listen_addr = unused_address();
+        thread::spawn(move || {
+            run_proxy(
+                &listen_addr.to_string(),
+                device_addr,
+                &FaultConfig::default(),
+                None,
+            )
+        });
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.connect(listen_addr).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+        client
+    }
+
+    fn exchange(client: &UdpSocket, datagram: &[u8]) -> Option<Vec<u8>> {
+        client.send(datagram).ok()?;
+        let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+        let len = client.recv(&mut buffer).ok()?;
+        Some(buffer[..len].to_vec())
+    }
+
+    /// Retries the exchange while the proxy or the device are starting up.
+    fn exchange_eventually(client: &UdpSocket, datagram: &[u8]) -> Option<Vec<u8>> {
+        (0..20).find_map(|_| {
+            exchange(client, datagram).or_else(|| {
+                thread::sleep(Duration::from_millis(100));
+                None
+            })
+        })
+    }
+
+    #[test]
+    fn proxy_survives_an_unreachable_device() {
+        let device_addr = unused_address();
+        let client = start_proxy(device_addr);
+
+        // Nothing listens on the device port, the proxy gets the ICMP error.
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(exchange(&client, b"lost"), None);
+
+        start_device(device_addr);
+        let response = exchange_eventually(&client, b"abc");
+        assert_eq!(response, Some(b"cba".to_vec()));
+    }
+
+    #[test]
+    fn large_datagrams_are_not_truncated() {
+        let device_addr = unused_address();
+        start_device(device_addr);
+        let client = start_proxy(device_addr);
+
+        let datagram = (0..10000).map(|i| i as u8).collect::<Vec<u8>>();
+        let mut expected = datagram.clone();
+        expected.reverse();
+        let response = exchange_eventually(&client, &datagram);
+        assert_eq!(response, Some(expected));
+    }
+}
@@ -0,0 +1,375 @@
+use std::{fs, io::ErrorKind, path::Path, process::Command};
+
+use log::debug;
+use tokio_util::sync::CancellationToken;
+
+use crate::transport::{send_request, TransportOptions};
+
+/// Makes the artifacts produced by a deployment (binaries and signed SUIT
+/// manifests) available on the file server that the device fetches them from.
+///
+/// The artifacts are always first written into the local CoAP root directory,
+/// the publisher then moves them to wherever the file server lives. This allows
+/// for hosting the artifacts on a different machine than the one running the
+/// tools.
+pub trait Publisher {
+    /// Uploads the local file, it is stored under its file name relative to
+    /// the root of the file server.
+    fn publish(&self, file: &str) -> Result<(), String>;
+
+    /// Removes the previously published file, it is used for cleaning up
+    /// after deployments which didn't finish. Removing a file which isn't
+    /// published isn't an error.
+    fn unpublish(&self, file: &str) -> Result<(), String>;
+
+    /// Describes where the artifacts are published to.
+    fn destination(&self) -> String;
+
+    /// Base URI that the device fetches the published artifacts from, the
+    /// signed manifests and the pull requests point the device to it. The
+    /// local CoAP file server is reachable at `host_ipv6_addr`. The devices
+    /// only fetch the artifacts over CoAP, hence the publishers which can't
+    /// be fetched from return an error.
+    fn fetch_base_uri(&self, host_ipv6_addr: &str) -> Result<String, String>;
+}
+
+/// Publishes the artifacts into a local directory served by the file server.
+/// If the directory is the CoAP root directory itself, there is nothing to do.
+pub struct LocalPublisher {
+    pub dir: String,
+}
+
+impl LocalPublisher {
+    pub fn new(dir: &str) -> Self {
+        LocalPublisher {
+            dir: dir.to_string(),
+        }
+    }
+}
+
+impl Publisher for LocalPublisher {
+    fn publish(&self, file: &str) -> Result<(), String> {
+        let destination = Path::new(&self.dir).join(file_name(file));
+        if Path::new(file) == destination {
+            return Ok(());
+        }
+        fs::copy(file, &destination)
+            .map(|_| ())
+            .map_err(|e| format!("Failed to copy {} to {}: {}", file, self.dir, e))
+    }
+
+    fn unpublish(&self, file: &str) -> Result<(), String> {
+        let destination = Path::new(&self.dir).join(file_name(file));
+        match fs::remove_file(&destination) {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                Err(format!("Failed to remove {}: {}", destination.display(), e))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn destination(&self) -> String {
+        self.dir.clone()
+    }
+
+    fn fetch_base_uri(&self, host_ipv6_addr: &str) -> Result<String, String> {
+        Ok(format!("coap://[{}]", host_ipv6_addr))
+    }
+}
+
+/// Uploads the artifacts to a remote HTTP file server using PUT requests, e.g.
+/// to a WebDAV share backing a CoAP-HTTP proxy.
+pub struct HttpPublisher {
+    pub base_url: String,
+}
+
+impl Publisher for HttpPublisher {
+    fn publish(&self, file: &str) -> Result<(), String> {
+        let url = format!(
+            "{}/{}",
+            self.base_url.trim_end_matches('/'),
+            file_name(file)
+        );
+        debug!("Uploading {} to {}", file, url);
+
+        let output = Command::new("curl")
+            .arg("--silent")
+            .arg("--show-error")
+            .arg("--fail")
+            .arg("--upload-file")
+            .arg(file)
+            .arg(&url)
+            .output()
+            .map_err(|e| format!("Failed to run curl: {}", e))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "Upload to {} failed: {}",
+                url,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        Ok(())
+    }
+
+    fn unpublish(&self, file: &str) -> Result<(), String> {
+        let url = format!(
+            "{}/{}",
+            self.base_url.trim_end_matches('/'),
+            file_name(file)
+        );
+        debug!("Removing {}", url);
+
+        let output = Command::new("curl")
+            .arg("--silent")
+            .arg("--show-error")
+            .arg("--request")
+            .arg("DELETE")
+            .arg(&url)
+            .output()
+            .map_err(|e| format!("Failed to run curl: {}", e))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "Removing {} failed: {}",
+                url,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        Ok(())
+    }
+
+    fn destination(&self) -> String {
+        self.base_url.clone()
+    }
+
+    fn fetch_base_uri(&self, _host_ipv6_addr: &str) -> Result<String, String> {
+        Err(not_fetchable(&self.base_url))
+    }
+}
+
+/// Uploads the artifacts to a remote CoAP file server using PUT requests.
+pub struct CoapPublisher {
+    pub base_url: String,
+}
+
+impl Publisher for CoapPublisher {
+    fn publish(&self, file: &str) -> Result<(), String> {
+        let url = format!(
+            "{}/{}",
+            self.base_url.trim_end_matches('/'),
+            file_name(file)
+        );
+        debug!("Uploading {} to {}", file, url);
+
+        // The file server isn't one of the devices and so we don't protect
+        // the request using the device credentials.
+        let payload = format!("@{}", file);
+        let output = send_request("PUT", &url, Some(&payload), &TransportOptions::default())?;
+
+        if output.stderr.len() > 0 {
+            return Err(format!(
+                "Upload to {} failed: {}",
+                url,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        Ok(())
+    }
+
+    fn unpublish(&self, file: &str) -> Result<(), String> {
+        let url = format!(
+            "{}/{}",
+            self.base_url.trim_end_matches('/'),
+            file_name(file)
+        );
+        debug!("Removing {}", url);
+
+        // The artifacts are withdrawn after the deployment has been
+        // cancelled, so the request can't use the default cancellation token.
+        let transport = TransportOptions {
+            cancellation: CancellationToken::new(),
+            ..Default::default()
+        };
+        let output = send_request("DELETE", &url, None, &transport)?;
+        // 4.04 means that the file isn't there in the first place.
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.is_empty() && !stderr.contains("4.04") {
+            return Err(format!("Removing {} failed: {}", url, stderr));
+        }
+        Ok(())
+    }
+
+    fn destination(&self) -> String {
+        self.base_url.clone()
+    }
+
+    fn fetch_base_uri(&self, _host_ipv6_addr: &str) -> Result<String, String> {
+        // The pull request carries the address of the file server.
+        if !self.base_url.starts_with("coap://[") {
+            return Err(format!(
+                "The devices fetch the artifacts from an IPv6 address, {} doesn't specify one",
+                self.base_url
+            ));
+        }
+        Ok(self.base_url.trim_end_matches('/').to_string())
+    }
+}
+
+/// Uploads the artifacts into an S3 bucket using the AWS CLI, the credentials
+/// are picked up from its usual configuration.
+pub struct S3Publisher {
+    /// Destination of the form `s3://<bucket>/<prefix>`.
+    pub url: String,
+}
+
+impl Publisher for S3Publisher {
+    fn publish(&self, file: &str) -> Result<(), String> {
+        let url = format!("{}/{}", self.url.trim_end_matches('/'), file_name(file));
+        debug!("Uploading {} to {}", file, url);
+
+        let output = Command::new("aws")
+            .arg("s3")
+            .arg("cp")
+            .arg(file)
+            .arg(&url)
+            .output()
+            .map_err(|e| format!("Failed to run the aws cli: {}", e))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "Upload to {} failed: {}",
+                url,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        Ok(())
+    }
+
+    fn unpublish(&self, file: &str) -> Result<(), String> {
+        let url = format!("{}/{}", self.url.trim_end_matches('/'), file_name(file));
+        debug!("Removing {}", url);
+
+        let output = Command::new("aws")
+            .arg("s3")
+            .arg("rm")
+            .arg(&url)
+            .output()
+            .map_err(|e| format!("Failed to run the aws cli: {}", e))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "Removing {} failed: {}",
+                url,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        Ok(())
+    }
+
+    fn destination(&self) -> String {
+        self.url.clone()
+    }
+
+    fn fetch_base_uri(&self, _host_ipv6_addr: &str) -> Result<String, String> {
+        Err(not_fetchable(&self.url))
+    }
+}
+
+/// Creates the publisher from its specification, the kind of the publisher
+/// is determined by the scheme:
+/// - `http://` and `https://` - [`HttpPublisher`]
+/// - `coap://` - [`CoapPublisher`]
+/// - `s3://` - [`S3Publisher`]
+/// - anything else is treated as a local directory ([`LocalPublisher`])
+pub fn parse_publisher(spec: &str) -> Box<dyn Publisher> {
+    if spec.starts_with("http://") || spec.starts_with("https://") {
+        Box::new(HttpPublisher {
+            base_url: spec.to_string(),
+        })
+    } else if spec.starts_with("coap://") {
+        Box::new(CoapPublisher {
+            base_url: spec.to_string(),
+        })
+    } else if spec.starts_with("s3://") {
+        Box::new(S3Publisher {
+            url: spec.to_string(),
+        })
+    } else {
+        Box::new(LocalPublisher::new(spec))
+    }
+}
+
+/// Splits the URI of the artifact published under the base URI returned by
+/// [`Publisher::fetch_base_uri`] into the address of the file server and the
+/// path of the artifact, as expected by the pull request. E.g. the manifest
+/// `suit_manifest0.signed` under `coap://[fe80::1]/fw` is fetched from
+/// `fe80::1` as `fw/suit_manifest0.signed`.
+pub fn fetch_location(fetch_base_uri: &str, file: &str) -> Result<(String, String), String> {
+    let invalid = || format!("Invalid CoAP file server URI: {}", fetch_base_uri);
+    let authority = fetch_base_uri
+        .strip_prefix("coap://[")
+        .ok_or_else(invalid)?;
+    let (address, path) = authority.split_once(']').ok_or_else(invalid)?;
+    let path = path.trim_matches('/');
+    let file = file_name(file);
+    if path.is_empty() {
+        Ok((address.to_string(), file.to_string()))
+    } else {
+        Ok((address.to_string(), format!("{}/{}", path, file)))
+    }
+}
+
+fn not_fetchable(destination: &str) -> String {
+    format!(
+        "The devices can't fetch the artifacts from {}, only CoAP file servers are supported",
+        destination
+    )
+}
+
+fn file_name(file: &str) -> &str {
+    file.split('/').last().unwrap_or(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn artifacts_are_fetched_from_the_coap_publishers_only() {
+        let local = parse_publisher("/tmp/coaproot");
+        assert_eq!(local.fetch_base_uri("fe80::1").unwrap(), "coap://[fe80::1]");
+
+        let coap = parse_publisher("coap://[2001:db8::1]/fw/");
+        assert_eq!(
+            coap.fetch_base_uri("fe80::1").unwrap(),
+            "coap://[2001:db8::1]/fw"
+        );
+
+        assert!(parse_publisher("coap://files.local/fw")
+            .fetch_base_uri("fe80::1")
+            .is_err());
+        assert!(parse_publisher("https://files.local/fw")
+            .fetch_base_uri("fe80::1")
+            .is_err());
+        assert!(parse_publisher("s3://bucket/fw")
+            .fetch_base_uri("fe80::1")
+            .is_err());
+    }
+
+    #[test]
+    fn fetch_location_splits_the_address_and_the_path() {
+        assert_eq!(
+            fetch_location("coap://[fe80::1]", "out/suit_manifest0.signed").unwrap(),
+            ("fe80::1".to_string(), "suit_manifest0.signed".to_string())
+        );
+        assert_eq!(
+            fetch_location("coap://[2001:db8::1]/fw", "suit_manifest0.signed").unwrap(),
+            (
+                "2001:db8::1".to_string(),
+                "fw/suit_manifest0.signed".to_string()
+            )
+        );
+        assert!(fetch_location("coap://files.local", "program.bin").is_err());
+    }
+}
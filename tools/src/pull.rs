@@ -1,11 +1,12 @@
-use std::process::Command;
-
 use log::{debug, error};
 use micro_bpf_common::{
-    BinaryFileLayout, HelperAccessListSource, HelperAccessVerification, TargetVM, VMConfiguration,
+    BinaryFileLayout, HelperAccessListSource, HelperAccessVerification, StorageBackend, TargetVM,
 };
 
-use crate::micro_bpf_common::SuitPullRequest;
+use crate::{
+    request::build_pull_request,
+    transport::{send_request, TransportOptions},
+};
 
 pub async fn pull(
     riot_ipv6_addr: &str,
@@ -16,10 +17,12 @@ pub async fn pull(
     target: TargetVM,
     binary_layout: BinaryFileLayout,
     suit_storage_slot: usize,
+    storage: StorageBackend,
     helper_access_verification: HelperAccessVerification,
     helper_access_list_source: HelperAccessListSource,
     helper_indices: &[u8],
     erase: bool,
+    transport: &TransportOptions,
 ) -> Result<(), String> {
     let url = format!(
         "coap://[{}%{}]/suit/pull",
@@ -27,47 +30,24 @@ pub async fn pull(
     );
     debug!("Sending a request to the url: {}", url);
 
-    let configuration = VMConfiguration::new(
+    let request = build_pull_request(
+        host_ipv6_addr,
+        suit_manifest,
+        riot_network_interface,
         target,
-        suit_storage_slot,
         binary_layout,
+        suit_storage_slot,
+        storage,
         helper_access_verification,
         helper_access_list_source,
-        false,
-        false,
+        helper_indices,
+        erase,
     );
 
-    let config_encoded = configuration.encode();
-
-    let request = SuitPullRequest {
-        ip: host_ipv6_addr.to_string(),
-        manifest: suit_manifest.to_string(),
-        // We need to tell the microcontroller which network interface (usually 5 or
-        // 6) needs to be used to access the CoAP fileserver on the remote host.
-        // the reason for this is that this interface changes based on the target
-        // architecture (stm32/native) and so it can't be hard-coded.
-        riot_netif: riot_network_interface.to_string(),
-        config: config_encoded,
-        helpers: helper_indices
-            .iter()
-            .map(|i| format!("{:02x}", i))
-            .collect::<String>(),
-        erase
-    };
-
     let req_str = request.encode();
     debug!("Sending the request payload: {}", req_str);
 
-    let Ok(output) = Command::new("aiocoap-client")
-        .arg("-m")
-        .arg("POST")
-        .arg(url.clone())
-        .arg("--payload")
-        .arg(&req_str)
-        .output()
-    else {
-        return Err(format!("Failed to send the request payload: {}", req_str));
-    };
+    let output = send_request("POST", &url, Some(&req_str), transport)?;
 
     debug!(
         "Response from the pull request: \n{}",
@@ -0,0 +1,242 @@
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    registry::now,
+    transport::{TransportOptions, CANCELLED},
+};
+
+/// Time between the retries of the request at the head of the queue.
+const RETRY_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Response code of the device that is busy executing another program.
+const SERVICE_UNAVAILABLE: &str = "5.03";
+
+/// Checks whether the request failed because the device is busy.
+pub fn is_busy(error: &str) -> bool {
+    error.contains(SERVICE_UNAVAILABLE)
+}
+
+/// Execution waiting for the device to become available.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueueEntry {
+    /// Identifier of the entry, the entries are served in its order.
+    pub ticket: String,
+    pub device: String,
+    pub slot: usize,
+    /// Process waiting for the execution.
+    pub pid: u32,
+    /// Seconds since the UNIX epoch.
+    pub enqueued_at: u64,
+}
+
+impl fmt::Display for QueueEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} slot {} (pid {}, waiting for {}s)",
+            self.ticket,
+            self.slot,
+            self.pid,
+            now().saturating_sub(self.enqueued_at)
+        )
+    }
+}
+
+/// Client-side queue of the executions waiting for a busy device (e.g. one
+/// running a long-running program, which responds with 5.03 Service
+/// Unavailable). The queue is shared by all processes of the tool on the host
+/// so that it can be inspected and its entries cancelled from another
+/// terminal. Each entry is a file in the directory of the device:
+///
+/// ```text
+/// .mibpf-queue/
+///   fe80__a0d9_ebff_fed5_986b/
+///     00001718000000000000-4242.json
+/// ```
+///
+/// The location can be overridden by setting "MIBPF_QUEUE_DIR".
+pub struct ExecutionQueue {
+    root: PathBuf,
+}
+
+impl ExecutionQueue {
+    pub fn new(root: &str) -> Self {
+        ExecutionQueue {
+            root: PathBuf::from(root),
+        }
+    }
+
+    pub fn default_root() -> String {
+        env::var("MIBPF_QUEUE_DIR").unwrap_or_else(|_| ".mibpf-queue".to_string())
+    }
+
+    fn device_dir(&self, device: &str) -> PathBuf {
+        self.root.join(device.replace([':', '%'], "_"))
+    }
+
+    /// Adds the execution at the end of the queue of the device.
+    pub fn enqueue(&self, device: &str, slot: usize) -> Result<QueueTicket, String> {
+        let dir = self.device_dir(device);
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create the queue {}: {}", dir.display(), e))?;
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        let entry = QueueEntry {
+            ticket: format!("{:020}-{}", nanos, std::process::id()),
+            device: device.to_string(),
+            slot,
+            pid: std::process::id(),
+            enqueued_at: now(),
+        };
+        let path = dir.join(format!("{}.json", entry.ticket));
+        fs::write(&path, serde_json::to_string(&entry).unwrap())
+            .map_err(|e| format!("Failed to enqueue the execution: {}", e))?;
+        debug!("Enqueued {}", path.display());
+        Ok(QueueTicket { path, dir, entry })
+    }
+
+    /// Lists the waiting executions of the device (or of all devices) in the
+    /// order they will be served.
+    pub fn list(&self, device: Option<&str>) -> Vec<QueueEntry> {
+        let dirs = match device {
+            Some(device) => vec![self.device_dir(device)],
+            None => match fs::read_dir(&self.root) {
+                Ok(entries) => entries.flatten().map(|entry| entry.path()).collect(),
+                Err(_) => vec![],
+            },
+        };
+        let mut entries = vec![];
+        for dir in dirs {
+            entries.extend(read_entries(&dir));
+        }
+        entries.sort_by(|a, b| (&a.device, &a.ticket).cmp(&(&b.device, &b.ticket)));
+        entries
+    }
+
+    /// Removes the entry from the queue, the process waiting for it gives up
+    /// the execution.
+    pub fn cancel(&self, ticket: &str) -> Result<QueueEntry, String> {
+        let entry = self
+            .list(None)
+            .into_iter()
+            .find(|entry| entry.ticket == ticket)
+            .ok_or_else(|| format!("No queued execution {}", ticket))?;
+        let path = self
+            .device_dir(&entry.device)
+            .join(format!("{}.json", entry.ticket));
+        fs::remove_file(&path).map_err(|e| format!("Failed to cancel {}: {}", ticket, e))?;
+        Ok(entry)
+    }
+}
+
+/// Reads the entries of the queue of a single device, skipping the ones left
+/// behind by the processes which no longer exist.
+fn read_entries(dir: &Path) -> Vec<QueueEntry> {
+    let Ok(files) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut entries = files
+        .flatten()
+        .filter_map(|file| fs::read_to_string(file.path()).ok())
+        .filter_map(|contents| serde_json::from_str::<QueueEntry>(&contents).ok())
+        .filter(|entry| is_alive(entry.pid))
+        .collect::<Vec<QueueEntry>>();
+    entries.sort_by(|a, b| a.ticket.cmp(&b.ticket));
+    entries
+}
+
+/// Only checked on Linux, the entries are assumed to be alive elsewhere.
+fn is_alive(pid: u32) -> bool {
+    !cfg!(target_os = "linux") || Path::new(&format!("/proc/{}", pid)).exists()
+}
+
+/// Place of the execution in the queue, the entry is removed once the ticket
+/// is dropped.
+pub struct QueueTicket {
+    path: PathBuf,
+    dir: PathBuf,
+    pub entry: QueueEntry,
+}
+
+impl QueueTicket {
+    /// Number of executions ahead of this one, None if the entry has been
+    /// cancelled.
+    pub fn position(&self) -> Option<usize> {
+        if !self.path.exists() {
+            return None;
+        }
+        Some(
+            read_entries(&self.dir)
+                .iter()
+                .take_while(|entry| entry.ticket != self.entry.ticket)
+                .count(),
+        )
+    }
+}
+
+impl Drop for QueueTicket {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Waits in the queue of the busy device until the execution is at its head
+/// and then retries it until the device accepts it. Gives up once the
+/// `timeout` elapses, or when the entry is cancelled (see
+/// [`ExecutionQueue::cancel`]) or the transport is cancelled.
+pub fn wait_in_queue<F>(
+    device: &str,
+    slot: usize,
+    timeout: Duration,
+    transport: &TransportOptions,
+    mut attempt: F,
+) -> Result<String, String>
+where
+    F: FnMut() -> Result<String, String>,
+{
+    let queue = ExecutionQueue::new(&ExecutionQueue::default_root());
+    let ticket = queue.enqueue(device, slot)?;
+    info!(
+        "[queue] {} is busy, queued as {}",
+        device, ticket.entry.ticket
+    );
+
+    let deadline = Instant::now() + timeout;
+    let mut last_position = None;
+    loop {
+        transport.check_cancelled()?;
+        let Some(position) = ticket.position() else {
+            return Err(format!(
+                "{}: the queued execution {} was removed from the queue",
+                CANCELLED, ticket.entry.ticket
+            ));
+        };
+        if last_position != Some(position) {
+            info!("[queue] position {} in the queue of {}", position, device);
+            last_position = Some(position);
+        }
+        if position == 0 {
+            match attempt() {
+                Err(e) if is_busy(&e) => debug!("{} is still busy", device),
+                result => return result,
+            }
+        }
+        if Instant::now() >= deadline {
+            return Err(format!(
+                "{} is still busy after waiting in the queue for {:?}",
+                device, timeout
+            ));
+        }
+        thread::sleep(RETRY_INTERVAL);
+    }
+}
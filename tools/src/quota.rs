@@ -0,0 +1,456 @@
+use std::{collections::BTreeMap, fs};
+
+use coap_lite::{CoapOption, MessageClass, MessageType, Packet, ResponseType};
+use log::debug;
+use micro_bpf_common::{SuitPullRequest, VMConfiguration, VMExecutionRequest};
+use serde::Deserialize;
+
+/// Slots that a user may use on the devices with the given tags.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserQuota {
+    /// Access token that the requests of the user carry (the `token=`
+    /// Uri-Query, see
+    /// [`crate::transport::TransportOptions::access_token`]).
+    pub token: String,
+    pub slots: Vec<usize>,
+    /// Tags of the devices the quota applies to, all devices if empty.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl UserQuota {
+    fn applies_to(&self, device_tags: &[String]) -> bool {
+        self.tags.is_empty() || self.tags.iter().any(|tag| device_tags.contains(tag))
+    }
+}
+
+/// Slot ownership of a shared testbed, e.g.:
+///
+/// ```toml
+/// [users.alice]
+/// token = "<access token of alice>"
+/// slots = [2, 3]
+/// tags = ["shared"]
+/// ```
+///
+/// On the devices that any of the quotas applies to, the requests deploying
+/// into, executing or erasing a slot are only let through if they come from
+/// the user owning that slot. Each slot is owned by at most one user. The
+/// requests affecting all slots (e.g. rebooting the device or replacing its
+/// trust anchor) and the requests to unknown resources are always rejected.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SlotPolicy {
+    #[serde(default)]
+    pub users: BTreeMap<String, UserQuota>,
+}
+
+impl SlotPolicy {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read the slot policy {}: {}", path, e))?;
+        let policy = toml::from_str::<SlotPolicy>(&contents)
+            .map_err(|e| format!("Invalid slot policy {}: {}", path, e))?;
+        policy.check_ownership()?;
+        Ok(policy)
+    }
+
+    /// Fails if two users on the same devices claim the same slot.
+    fn check_ownership(&self) -> Result<(), String> {
+        let users = self.users.iter().collect::<Vec<_>>();
+        for (i, (user, quota)) in users.iter().enumerate() {
+            for (other, other_quota) in &users[i + 1..] {
+                let shared_devices = quota.tags.is_empty()
+                    || other_quota.tags.is_empty()
+                    || quota.tags.iter().any(|tag| other_quota.tags.contains(tag));
+                let shared_slot = quota
+                    .slots
+                    .iter()
+                    .find(|slot| other_quota.slots.contains(slot));
+                if let (true, Some(slot)) = (shared_devices, shared_slot) {
+                    return Err(format!(
+                        "Slot {} is claimed by both {} and {}",
+                        slot, user, other
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Slots that a request operates on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotAccess {
+    /// Doesn't touch the programs, e.g. querying the slots or the logs.
+    None,
+    Slot(usize),
+    /// Affects the programs in all slots, e.g. rebooting the device.
+    All,
+}
+
+/// Enforces the [`SlotPolicy`] on the requests forwarded to a device with
+/// the given tags.
+#[derive(Debug, Clone)]
+pub struct SlotGuard {
+    pub policy: SlotPolicy,
+    pub device_tags: Vec<String>,
+}
+
+impl SlotGuard {
+    /// Checks the CoAP request, the error names the reason for rejecting it.
+    /// The datagrams which aren't requests (e.g. the acknowledgements) are
+    /// always let through.
+    pub fn check(&self, datagram: &[u8]) -> Result<(), String> {
+        let quotas = self
+            .policy
+            .users
+            .iter()
+            .filter(|(_, quota)| quota.applies_to(&self.device_tags))
+            .collect::<Vec<_>>();
+        if quotas.is_empty() {
+            return Ok(());
+        }
+        let Ok(packet) = Packet::from_bytes(datagram) else {
+            return Err("Malformed CoAP message".to_string());
+        };
+        if !matches!(packet.header.code, MessageClass::Request(_)) {
+            return Ok(());
+        }
+        // The path and the payload of the OSCORE requests are encrypted, so
+        // the slot can't be checked.
+        if packet.get_option(CoapOption::Oscore).is_some() {
+            return Err(
+                "OSCORE-protected requests can't be checked against the slot policy".to_string(),
+            );
+        }
+
+        let slot = match slot_access(&packet)? {
+            SlotAccess::None => return Ok(()),
+            SlotAccess::Slot(slot) => slot,
+            SlotAccess::All => return Err("The request affects the slots of all users".to_string()),
+        };
+        let token = queries(&packet)
+            .into_iter()
+            .find_map(|query| query.strip_prefix("token=").map(str::to_string));
+        let user = token.and_then(|token| {
+            quotas
+                .iter()
+                .find(|(_, quota)| quota.token == token)
+                .map(|(user, quota)| (user.as_str(), *quota))
+        });
+        match user {
+            Some((user, quota)) if quota.slots.contains(&slot) => {
+                debug!("{} may use slot {}", user, slot);
+                Ok(())
+            }
+            Some((user, quota)) => Err(format!(
+                "{} may only use the slots {:?} of this device, not slot {}",
+                user, quota.slots, slot
+            )),
+            None => Err(format!(
+                "Slot {} requires the access token of its owner",
+                slot
+            )),
+        }
+    }
+}
+
+fn options(packet: &Packet, option: CoapOption) -> Vec<String> {
+    packet
+        .get_option(option)
+        .map(|values| {
+            values
+                .iter()
+                .map(|value| String::from_utf8_lossy(value).to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn queries(packet: &Packet) -> Vec<String> {
+    options(packet, CoapOption::UriQuery)
+}
+
+/// Finds the slot that the request operates on from its resource and either
+/// the encoded request in its payload or the `slot=` query. The resources
+/// which aren't known to the tool are rejected, they could operate on any of
+/// the slots.
+pub fn slot_access(packet: &Packet) -> Result<SlotAccess, String> {
+    let path = options(packet, CoapOption::UriPath).join("/");
+    let payload = String::from_utf8_lossy(&packet.payload);
+    let slot_query = queries(packet)
+        .into_iter()
+        .find_map(|query| query.strip_prefix("slot=").map(str::to_string))
+        .map(|slot| {
+            slot.parse::<usize>()
+                .map_err(|e| format!("Invalid slot {}: {}", slot, e))
+        })
+        .transpose()?;
+    let required_slot_query = || match slot_query {
+        Some(slot) => Ok(SlotAccess::Slot(slot)),
+        None => Err("The slot of the request isn't specified".to_string()),
+    };
+
+    let execution = ["short-execution", "with_coap_pkt", "long-running"];
+    let read_only = [
+        ".well-known/core",
+        "capabilities",
+        "helpers",
+        "hooks",
+        "info",
+        "logs",
+        "long-running/health",
+        "scheduled-execution/result",
+        "suit/digest",
+        "suit/slots",
+        "suit/timings",
+        "time",
+    ];
+    let access = match path.as_str() {
+        path if read_only.contains(&path) => SlotAccess::None,
+        "suit/pull" => {
+            let request = payload.trim_matches(char::from(0)).to_string();
+            let request = SuitPullRequest::decode(request)?;
+            SlotAccess::Slot(VMConfiguration::decode(request.config).suit_slot)
+        }
+        path if execution.iter().any(|resource| path.ends_with(resource)) => {
+            // The packet-processing programs get the injected payload after
+            // the request, separated by a newline.
+            let request = payload.split('\n').next().unwrap_or_default();
+            let request = VMExecutionRequest::decode(request.to_string())?;
+            SlotAccess::Slot(request.configuration.suit_slot)
+        }
+        "scheduled-execution" => {
+            let request = VMExecutionRequest::decode(payload.trim().to_string())?;
+            SlotAccess::Slot(request.configuration.suit_slot)
+        }
+        "suit/slot" | "long-running/schedule" => required_slot_query()?,
+        "long-running/reset" => slot_query.map_or(SlotAccess::All, SlotAccess::Slot),
+        // The program attached to a network hook runs on the packets of all
+        // users, detaching it isn't tied to the slot it came from.
+        path if path.starts_with("hooks/") && path.ends_with("/attach") => {
+            let request = VMExecutionRequest::decode(payload.trim().to_string())?;
+            SlotAccess::Slot(request.configuration.suit_slot)
+        }
+        path if path.starts_with("hooks/") && path.ends_with("/detach") => SlotAccess::All,
+        // The trust anchor and the keys are shared by the programs in all
+        // slots.
+        "reset" | "suit/trust-anchor" | "keystore" => SlotAccess::All,
+        path => return Err(format!("Unknown resource: /{}", path)),
+    };
+    Ok(access)
+}
+
+/// Builds the 4.03 Forbidden response to the rejected request so that the
+/// client fails right away instead of retransmitting it.
+pub fn forbidden_response(datagram: &[u8], reason: &str) -> Option<Vec<u8>> {
+    let request = Packet::from_bytes(datagram).ok()?;
+    let mut response = Packet::new();
+    response.header.set_type(match request.header.get_type() {
+        MessageType::Confirmable => MessageType::Acknowledgement,
+        _ => MessageType::NonConfirmable,
+    });
+    response.header.code = MessageClass::Response(ResponseType::Forbidden);
+    response.header.message_id = request.header.message_id;
+    response.set_token(request.get_token().to_vec());
+    response.payload = reason.as_bytes().to_vec();
+    response.to_bytes().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use coap_lite::RequestType;
+    use micro_bpf_common::{
+        BinaryFileLayout, HelperAccessListSource, HelperAccessVerification, StorageBackend,
+        TargetVM,
+    };
+
+    use super::*;
+    use crate::request::{build_execution_request, build_pull_request};
+
+    fn request(path: &str, queries: &[&str], payload: &str) -> Packet {
+        let mut packet = Packet::new();
+        packet.header.code = MessageClass::Request(RequestType::Post);
+        for segment in path.split('/') {
+            packet.add_option(CoapOption::UriPath, segment.as_bytes().to_vec());
+        }
+        for query in queries {
+            packet.add_option(CoapOption::UriQuery, query.as_bytes().to_vec());
+        }
+        packet.payload = payload.as_bytes().to_vec();
+        packet
+    }
+
+    fn execution(slot: usize) -> String {
+        build_execution_request(
+            TargetVM::Rbpf,
+            BinaryFileLayout::ExtendedHeader,
+            slot,
+            HelperAccessVerification::AheadOfTime,
+            HelperAccessListSource::ExecuteRequest,
+            &[],
+            false,
+            false,
+        )
+        .encode()
+    }
+
+    fn pull(slot: usize) -> String {
+        build_pull_request(
+            "fe80::1",
+            "suit_manifest0.signed",
+            "5",
+            TargetVM::Rbpf,
+            BinaryFileLayout::ExtendedHeader,
+            slot,
+            StorageBackend::default(),
+            HelperAccessVerification::AheadOfTime,
+            HelperAccessListSource::ExecuteRequest,
+            &[],
+            true,
+        )
+        .encode()
+    }
+
+    fn access(path: &str, queries: &[&str], payload: &str) -> Result<SlotAccess, String> {
+        slot_access(&request(path, queries, payload))
+    }
+
+    fn guard() -> SlotGuard {
+        let quota = |token: &str, slots: Vec<usize>| UserQuota {
+            token: token.to_string(),
+            slots,
+            tags: vec!["shared".to_string()],
+        };
+        SlotGuard {
+            policy: SlotPolicy {
+                users: BTreeMap::from([
+                    ("alice".to_string(), quota("alice-token", vec![2, 3])),
+                    ("bob".to_string(), quota("bob-token", vec![4])),
+                ]),
+            },
+            device_tags: vec!["shared".to_string()],
+        }
+    }
+
+    fn check(guard: &SlotGuard, path: &str, queries: &[&str], payload: &str) -> Result<(), String> {
+        guard.check(&request(path, queries, payload).to_bytes().unwrap())
+    }
+
+    #[test]
+    fn slot_is_taken_from_the_payload_of_the_programs_requests() {
+        let slot = Ok(SlotAccess::Slot(2));
+        assert_eq!(access("short-execution", &[], &execution(2)), slot);
+        assert_eq!(access("benchmark/long-running", &[], &execution(2)), slot);
+        let with_packet = format!("{}\npacket payload", execution(2));
+        assert_eq!(access("with_coap_pkt", &[], &with_packet), slot);
+        assert_eq!(
+            access("scheduled-execution", &["at=1000"], &execution(2)),
+            slot
+        );
+        assert_eq!(access("hooks/xdp/attach", &[], &execution(2)), slot);
+        assert_eq!(access("suit/pull", &[], &pull(2)), slot);
+    }
+
+    #[test]
+    fn slot_is_taken_from_the_query_of_the_slot_requests() {
+        assert_eq!(
+            access("suit/slot", &["slot=3"], ""),
+            Ok(SlotAccess::Slot(3))
+        );
+        assert_eq!(
+            access("long-running/schedule", &["slot=4", "period=100"], ""),
+            Ok(SlotAccess::Slot(4))
+        );
+        assert_eq!(
+            access("long-running/reset", &["slot=1"], ""),
+            Ok(SlotAccess::Slot(1))
+        );
+        assert!(access("long-running/schedule", &["period=100"], "").is_err());
+        assert!(access("suit/slot", &["slot=x"], "").is_err());
+    }
+
+    #[test]
+    fn shared_resources_affect_all_slots() {
+        let shared = [
+            "reset",
+            "long-running/reset",
+            "hooks/xdp/detach",
+            "suit/trust-anchor",
+            "keystore",
+        ];
+        for path in shared {
+            assert_eq!(access(path, &[], ""), Ok(SlotAccess::All), "{}", path);
+        }
+    }
+
+    #[test]
+    fn read_only_resources_dont_touch_the_slots() {
+        assert_eq!(access("suit/slots", &[], ""), Ok(SlotAccess::None));
+        assert_eq!(access("logs", &["since=0"], ""), Ok(SlotAccess::None));
+        assert_eq!(
+            access("scheduled-execution/result", &["slot=2"], ""),
+            Ok(SlotAccess::None)
+        );
+    }
+
+    #[test]
+    fn unknown_resources_are_rejected() {
+        assert!(access("native/exec", &[], "").is_err());
+        assert!(access("suit/unknown", &["slot=2"], "").is_err());
+        assert!(check(&guard(), "native/exec", &["token=alice-token"], "").is_err());
+    }
+
+    #[test]
+    fn owners_may_only_use_their_slots() {
+        let guard = guard();
+        assert!(check(
+            &guard,
+            "short-execution",
+            &["token=alice-token"],
+            &execution(2)
+        )
+        .is_ok());
+        assert!(check(
+            &guard,
+            "short-execution",
+            &["token=bob-token"],
+            &execution(2)
+        )
+        .is_err());
+        assert!(check(&guard, "short-execution", &[], &execution(2)).is_err());
+        assert!(check(
+            &guard,
+            "long-running/schedule",
+            &["slot=4", "period=100", "token=bob-token"],
+            ""
+        )
+        .is_ok());
+        assert!(check(
+            &guard,
+            "scheduled-execution",
+            &["at=1000", "token=bob-token"],
+            &execution(3)
+        )
+        .is_err());
+        assert!(check(&guard, "suit/trust-anchor", &["token=alice-token"], "").is_err());
+        assert!(check(&guard, "keystore", &["token=alice-token"], "").is_err());
+        assert!(check(&guard, "suit/slots", &[], "").is_ok());
+    }
+
+    #[test]
+    fn devices_without_quotas_are_not_guarded() {
+        let mut private = guard();
+        private.device_tags = vec!["private".to_string()];
+        assert!(check(&private, "native/exec", &[], "").is_ok());
+    }
+
+    #[test]
+    fn responses_are_let_through() {
+        let mut acknowledgement = Packet::new();
+        acknowledgement
+            .header
+            .set_type(MessageType::Acknowledgement);
+        assert!(guard().check(&acknowledgement.to_bytes().unwrap()).is_ok());
+    }
+}
@@ -0,0 +1,231 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Condvar, Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+/// Limits applied to the requests sent to a single device. Constrained boards
+/// can't handle multiple requests at once (e.g. the CoAP server has a single
+/// request buffer), and so the client needs to avoid overwhelming them.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RateLimits {
+    /// Maximum number of requests in flight to the device at the same time.
+    pub max_concurrent_requests: Option<usize>,
+    /// Sustained number of requests per second sent to the device.
+    pub requests_per_second: Option<f64>,
+}
+
+impl RateLimits {
+    pub fn is_unlimited(&self) -> bool {
+        self.max_concurrent_requests.is_none() && self.requests_per_second.is_none()
+    }
+}
+
+/// Token bucket allowing short bursts (up to the bucket capacity) while
+/// enforcing the sustained request rate.
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        // We allow a burst of at most one second worth of requests.
+        let capacity = rate.max(1.0);
+        TokenBucket {
+            rate,
+            capacity,
+            state: Mutex::new((capacity, Instant::now())),
+        }
+    }
+
+    fn take(&self) {
+        loop {
+            let mut state = self.state.lock().unwrap();
+            let (tokens, last_refill) = *state;
+            let now = Instant::now();
+            let tokens = (tokens + now.duration_since(last_refill).as_secs_f64() * self.rate)
+                .min(self.capacity);
+
+            if tokens >= 1.0 {
+                *state = (tokens - 1.0, now);
+                return;
+            }
+            *state = (tokens, now);
+            drop(state);
+
+            let wait = Duration::from_secs_f64((1.0 - tokens) / self.rate);
+            debug!("Rate limit reached, waiting for {:?}", wait);
+            thread::sleep(wait);
+        }
+    }
+}
+
+/// Limiter shared by all requests to a device. The limits are updated in
+/// place when they change, so that the requests in flight still count
+/// towards the new ones.
+struct DeviceLimiter {
+    state: Mutex<LimiterState>,
+    released: Condvar,
+}
+
+struct LimiterState {
+    limits: RateLimits,
+    /// Requests whose permits haven't been dropped yet.
+    in_flight: usize,
+    bucket: Option<Arc<TokenBucket>>,
+}
+
+/// Permit to send a request to the device, the concurrency slot is released
+/// once the permit is dropped.
+pub struct Permit {
+    limiter: Arc<DeviceLimiter>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.limiter.state.lock().unwrap().in_flight -= 1;
+        self.limiter.released.notify_one();
+    }
+}
+
+fn limiters() -> &'static Mutex<HashMap<String, Arc<DeviceLimiter>>> {
+    static LIMITERS: OnceLock<Mutex<HashMap<String, Arc<DeviceLimiter>>>> = OnceLock::new();
+    LIMITERS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Blocks until sending another request to the device is allowed by the
+/// limits. The limiters are shared across the whole process so that all
+/// requests to the same device are accounted for, regardless of which part
+/// of the tool sends them.
+///
+/// The requests are sent from the async operations as well, when called on a
+/// worker of the multi-threaded tokio runtime the worker hands its other
+/// tasks over to the rest of the runtime before waiting.
+pub fn acquire(device: &str, limits: RateLimits) -> Permit {
+    let limiter = limiters()
+        .lock()
+        .unwrap()
+        .entry(device.to_string())
+        .or_insert_with(|| Arc::new(DeviceLimiter::new(limits)))
+        .clone();
+
+    block_in_place(|| {
+        let mut state = limiter.state.lock().unwrap();
+        // The limits could have been changed (e.g. by loading another device
+        // profile), the waiting requests need to check the new ones.
+        if state.limits != limits {
+            state.update(limits);
+            limiter.released.notify_all();
+        }
+        while state
+            .limits
+            .max_concurrent_requests
+            .is_some_and(|max| state.in_flight >= max.max(1))
+        {
+            state = limiter.released.wait(state).unwrap();
+        }
+        state.in_flight += 1;
+        let bucket = state.bucket.clone();
+        drop(state);
+
+        if let Some(bucket) = bucket {
+            bucket.take();
+        }
+    });
+
+    Permit { limiter }
+}
+
+/// The current-thread runtime can't move its tasks elsewhere, the same as
+/// the threads outside of the runtime it simply waits.
+fn block_in_place(wait: impl FnOnce()) {
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(wait)
+        }
+        _ => wait(),
+    }
+}
+
+impl DeviceLimiter {
+    fn new(limits: RateLimits) -> Self {
+        DeviceLimiter {
+            state: Mutex::new(LimiterState {
+                limits,
+                in_flight: 0,
+                bucket: token_bucket(limits),
+            }),
+            released: Condvar::new(),
+        }
+    }
+}
+
+impl LimiterState {
+    fn update(&mut self, limits: RateLimits) {
+        if limits.requests_per_second != self.limits.requests_per_second {
+            self.bucket = token_bucket(limits);
+        }
+        self.limits = limits;
+    }
+}
+
+fn token_bucket(limits: RateLimits) -> Option<Arc<TokenBucket>> {
+    limits
+        .requests_per_second
+        .filter(|rate| *rate > 0.0)
+        .map(|rate| Arc::new(TokenBucket::new(rate)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    fn concurrency_limit(max: usize) -> RateLimits {
+        RateLimits {
+            max_concurrent_requests: Some(max),
+            requests_per_second: None,
+        }
+    }
+
+    #[test]
+    fn requests_in_flight_count_towards_the_changed_limits() {
+        let device = "changed-limits";
+        let first = acquire(device, concurrency_limit(1));
+        let second = acquire(device, concurrency_limit(2));
+
+        let (sender, receiver) = mpsc::channel();
+        let waiting = thread::spawn(move || {
+            let _permit = acquire(device, concurrency_limit(2));
+            sender.send(()).unwrap();
+        });
+        assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+        drop(first);
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        waiting.join().unwrap();
+        drop(second);
+    }
+
+    #[test]
+    fn raising_the_limit_wakes_up_the_waiting_requests() {
+        let device = "raised-limit";
+        let _first = acquire(device, concurrency_limit(1));
+
+        let (sender, receiver) = mpsc::channel();
+        let waiting = thread::spawn(move || {
+            let _permit = acquire(device, concurrency_limit(1));
+            sender.send(()).unwrap();
+        });
+        assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+        let _second = acquire(device, concurrency_limit(3));
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        waiting.join().unwrap();
+    }
+}
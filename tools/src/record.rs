@@ -0,0 +1,112 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::debug;
+use serde::Serialize;
+use serde_json::json;
+
+/// Number of rotated files kept next to the active one (`<path>.1` is the
+/// most recent one), the older ones are removed.
+pub const RECORD_FILES_KEPT: usize = 5;
+
+/// Appends the results produced by the long-running modes (bench, watchdog)
+/// to a file as newline-delimited JSON, e.g.:
+/// `{"timestamp_ms": 1718000000000, "device": "fe80::1", "kind": "bench", "result": {...}}`
+///
+/// Each line is flushed to disk as soon as it is recorded so that the
+/// results collected during an overnight run survive the tool being killed.
+/// Once the file exceeds the maximum size, it is rotated to `<path>.1`.
+pub struct ResultRecorder {
+    path: String,
+    max_size: u64,
+    file: File,
+    size: u64,
+}
+
+impl ResultRecorder {
+    /// Opens the file for appending, the results recorded by previous runs
+    /// are kept.
+    pub fn open(path: &str, max_size: u64) -> Result<Self, String> {
+        let file = open_for_append(path)?;
+        let size = file
+            .metadata()
+            .map_err(|e| format!("Failed to read the metadata of {}: {}", path, e))?
+            .len();
+        Ok(ResultRecorder {
+            path: path.to_string(),
+            max_size,
+            file,
+            size,
+        })
+    }
+
+    /// Appends the result produced by the device as a single line.
+    pub fn record(
+        &mut self,
+        device: &str,
+        kind: &str,
+        result: impl Serialize,
+    ) -> Result<(), String> {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let line = json!({
+            "timestamp_ms": timestamp_ms,
+            "device": device,
+            "kind": kind,
+            "result": result,
+        })
+        .to_string()
+            + "\n";
+
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        self.file
+            .write_all(line.as_bytes())
+            .and_then(|_| self.file.sync_data())
+            .map_err(|e| format!("Failed to record the result into {}: {}", self.path, e))?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shifts the rotated files by one (dropping the oldest one) and starts a
+    /// new active file.
+    fn rotate(&mut self) -> Result<(), String> {
+        debug!("Rotating the result file {}", self.path);
+        let rotated = |i: usize| format!("{}.{}", self.path, i);
+        for i in (1..RECORD_FILES_KEPT).rev() {
+            if Path::new(&rotated(i)).exists() {
+                fs::rename(rotated(i), rotated(i + 1))
+                    .map_err(|e| format!("Failed to rotate {}: {}", rotated(i), e))?;
+            }
+        }
+        fs::rename(&self.path, rotated(1))
+            .map_err(|e| format!("Failed to rotate {}: {}", self.path, e))?;
+
+        self.file = open_for_append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn open_for_append(path: &str) -> Result<File, String> {
+    if let Some(dir) = Path::new(path)
+        .parent()
+        .filter(|d| !d.as_os_str().is_empty())
+    {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path, e))
+}
@@ -0,0 +1,232 @@
+use std::{
+    collections::BTreeMap,
+    env, fmt, fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::provenance::Provenance;
+
+/// Deployments into the same slot within this many seconds are considered to
+/// be a part of a single burst, e.g. a test suite redeploying in a loop.
+pub const HAMMERING_WINDOW: u64 = 600;
+
+/// Number of deployments within [`HAMMERING_WINDOW`] after which a warning is
+/// printed. Each deployment erases the flash pages of the slot, and so the
+/// boards with a low erase endurance wear out quickly in tight test loops.
+pub const HAMMERING_THRESHOLD: usize = 20;
+
+/// Local registry of the state of the devices managed by the tool. It keeps
+/// track of the information that can't be queried from the devices directly,
+/// e.g. when a given SUIT storage slot was last written to.
+///
+/// The registry is stored as a JSON file, its location can be overridden by
+/// setting the environment variable "MIBPF_REGISTRY".
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Registry {
+    /// Records of the devices keyed by their IPv6 address.
+    pub devices: BTreeMap<String, DeviceRecord>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct DeviceRecord {
+    /// Programs deployed into the SUIT storage slots of the device.
+    pub slots: BTreeMap<usize, SlotRecord>,
+    /// Sequence number of the last SUIT manifest sent to the device. The device
+    /// rejects manifests whose sequence number isn't greater than the stored one.
+    #[serde(default)]
+    pub sequence_number: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SlotRecord {
+    /// Source file of the deployed program.
+    pub program: String,
+    /// Time of the deployment (seconds since the UNIX epoch).
+    pub deployed_at: u64,
+    /// Total number of deployments into the slot recorded by the registry.
+    #[serde(default)]
+    pub deployments: u64,
+    /// Times of the deployments within the last [`HAMMERING_WINDOW`].
+    #[serde(default)]
+    pub recent_deployments: Vec<u64>,
+    /// Git revisions of the sources that the program was built from.
+    #[serde(default)]
+    pub provenance: Provenance,
+    /// Hex-encoded SHA-256 of the source file at the time of the deployment.
+    #[serde(default)]
+    pub program_hash: Option<String>,
+    /// Entry functions exported by the program, keyed by their name, with
+    /// their offsets (in instructions) from the start of the .text section.
+    #[serde(default)]
+    pub entries: BTreeMap<String, u32>,
+}
+
+impl SlotRecord {
+    /// Number of deployments into the slot within the last [`HAMMERING_WINDOW`].
+    pub fn recent_deployment_count(&self) -> usize {
+        let since = now().saturating_sub(HAMMERING_WINDOW);
+        self.recent_deployments
+            .iter()
+            .filter(|t| **t >= since)
+            .count()
+    }
+
+    pub fn is_hammered(&self) -> bool {
+        self.recent_deployment_count() >= HAMMERING_THRESHOLD
+    }
+}
+
+impl Registry {
+    pub fn path() -> String {
+        env::var("MIBPF_REGISTRY").unwrap_or_else(|_| ".mibpf-registry.json".to_string())
+    }
+
+    /// Loads the registry from disk, if the file doesn't exist yet an empty
+    /// registry is returned.
+    pub fn load() -> Result<Self, String> {
+        let path = Self::path();
+        let Ok(contents) = fs::read_to_string(&path) else {
+            return Ok(Registry::default());
+        };
+        serde_json::from_str(&contents)
+            .map_err(|e| format!("Failed to parse the registry {}: {}", path, e))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path();
+        fs::write(&path, serde_json::to_string_pretty(self).unwrap())
+            .map_err(|e| format!("Failed to write the registry {}: {}", path, e))
+    }
+
+    pub fn device(&self, device_ip: &str) -> Option<&DeviceRecord> {
+        self.devices.get(device_ip)
+    }
+
+    pub fn device_mut(&mut self, device_ip: &str) -> &mut DeviceRecord {
+        self.devices.entry(device_ip.to_string()).or_default()
+    }
+
+    /// Determines the sequence number of the next SUIT manifest sent to the
+    /// device. Unless it is specified explicitly, the last recorded sequence
+    /// number gets incremented. For devices that aren't in the registry yet we
+    /// start from the current timestamp which is also the default used by the
+    /// RIOT build system, this way we remain ahead of the manifests that were
+    /// generated before the registry existed.
+    ///
+    /// An explicitly specified sequence number needs to be greater than the
+    /// recorded one unless `force` is set.
+    pub fn next_sequence_number(
+        &self,
+        device_ip: &str,
+        requested: Option<u64>,
+        force: bool,
+    ) -> Result<u64, SequenceNumberConflict> {
+        let stored = self.device(device_ip).and_then(|d| d.sequence_number);
+        match (requested, stored) {
+            (Some(requested), Some(stored)) if requested <= stored && !force => {
+                Err(SequenceNumberConflict {
+                    device: device_ip.to_string(),
+                    requested,
+                    stored,
+                })
+            }
+            (Some(requested), _) => Ok(requested),
+            (None, Some(stored)) => Ok(stored + 1),
+            (None, None) => Ok(now()),
+        }
+    }
+
+    pub fn record_sequence_number(&mut self, device_ip: &str, sequence_number: u64) {
+        self.device_mut(device_ip).sequence_number = Some(sequence_number);
+    }
+
+    /// Records the deployment of the program into the slot and warns if the
+    /// slot is being redeployed too often.
+    pub fn record_deployment(
+        &mut self,
+        device_ip: &str,
+        slot: usize,
+        program: &str,
+        provenance: Provenance,
+    ) {
+        let deployed_at = now();
+        let since = deployed_at.saturating_sub(HAMMERING_WINDOW);
+        let previous = self.device_mut(device_ip).slots.remove(&slot);
+        let (deployments, mut recent_deployments) = previous
+            .map(|r| (r.deployments, r.recent_deployments))
+            .unwrap_or_default();
+        recent_deployments.retain(|t| *t >= since);
+        recent_deployments.push(deployed_at);
+
+        let record = SlotRecord {
+            program: program.to_string(),
+            deployed_at,
+            deployments: deployments + 1,
+            recent_deployments,
+            provenance,
+            program_hash: fs::read(program)
+                .ok()
+                .map(|source| format!("{:x}", Sha256::digest(source))),
+            entries: BTreeMap::new(),
+        };
+        if record.is_hammered() {
+            warn!(
+                "Slot {} of {} was written {} times in the last {} minutes, \
+                 consider spreading the deployments over more slots to reduce the flash wear",
+                slot,
+                device_ip,
+                record.recent_deployment_count(),
+                HAMMERING_WINDOW / 60
+            );
+        }
+        self.device_mut(device_ip).slots.insert(slot, record);
+    }
+
+    /// Records the entry functions exported by the program deployed into the
+    /// slot, it needs to be called after [`Registry::record_deployment`].
+    pub fn record_entries(&mut self, device_ip: &str, slot: usize, entries: BTreeMap<String, u32>) {
+        if let Some(record) = self.device_mut(device_ip).slots.get_mut(&slot) {
+            record.entries = entries;
+        }
+    }
+}
+
+/// Error returned when the sequence number of a SUIT manifest isn't greater
+/// than the one that the device has already accepted. Such manifests are
+/// rejected by the device without any response that the tool could surface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequenceNumberConflict {
+    pub device: String,
+    pub requested: u64,
+    pub stored: u64,
+}
+
+impl fmt::Display for SequenceNumberConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Sequence number conflict: the device {} has already accepted a \
+             manifest with sequence number {}, the requested sequence number {} \
+             would be rejected. Use --force-seq to override.",
+            self.device, self.stored, self.requested
+        )
+    }
+}
+
+impl From<SequenceNumberConflict> for String {
+    fn from(conflict: SequenceNumberConflict) -> Self {
+        conflict.to_string()
+    }
+}
+
+/// Returns the current time in seconds since the UNIX epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,203 @@
+use std::fmt;
+
+use micro_bpf_common::{
+    BinaryFileLayout, BufferElement, HelperCall, HelperFunctionID, ReturnBuffer, TargetVM,
+};
+use serde_json::Value;
+
+use crate::slots::SlotInfo;
+
+/// Summary of a single execution printed by `execute --report`, it combines
+/// the parameters of the request with the information decoded from the
+/// response of the device.
+#[derive(Debug, Clone)]
+pub struct ExecutionReport {
+    /// Name and version of the program, only known if the binary carries the
+    /// program metadata.
+    pub program: Option<String>,
+    pub slot: usize,
+    pub layout: BinaryFileLayout,
+    pub target: TargetVM,
+    pub jit: bool,
+    /// Indices of the helpers that the program was allowed to call, empty if
+    /// all helpers were allowed.
+    pub helpers: Vec<u8>,
+    /// Execution time as reported by the device.
+    pub execution_time: Option<u64>,
+    /// Number of executed instructions, only reported if the execution was
+    /// requested with a gas limit.
+    pub instructions: Option<u64>,
+    /// Seed of the random number generator used in the execution.
+    pub seed: Option<u64>,
+    /// Return value of the program, None if the response doesn't contain it
+    /// (e.g. programs that write their own CoAP response).
+    pub result: Option<i64>,
+    /// Buffer returned by the program using `bpf_set_return_buffer`.
+    pub buffer: Option<ReturnBuffer>,
+    /// Response as received from the device.
+    pub response: String,
+}
+
+impl ExecutionReport {
+    /// Builds the report from the response of the device. The execution
+    /// models which don't write their own CoAP response reply with:
+    /// `{"execution_time": 10, "result": 0}`, other responses are shown as-is.
+    pub fn new(
+        slot_info: Option<&SlotInfo>,
+        slot: usize,
+        layout: BinaryFileLayout,
+        target: TargetVM,
+        jit: bool,
+        helpers: &[u8],
+        response: &str,
+    ) -> Self {
+        let json = serde_json::from_str::<Value>(response).ok();
+        let field = |name: &str| json.as_ref().and_then(|json| json[name].as_i64());
+        let seed = json.as_ref().and_then(|json| json["seed"].as_u64());
+
+        ExecutionReport {
+            program: slot_info.and_then(|info| match (&info.name, &info.version) {
+                (Some(name), Some(version)) => Some(format!("{} {}", name, version)),
+                (Some(name), None) => Some(name.clone()),
+                _ => None,
+            }),
+            slot,
+            layout,
+            target,
+            jit,
+            helpers: helpers.to_vec(),
+            execution_time: field("execution_time").map(|time| time as u64),
+            instructions: field("instructions").map(|count| count as u64),
+            seed,
+            result: field("result"),
+            buffer: json
+                .as_ref()
+                .and_then(|json| serde_json::from_value(json["buffer"].clone()).ok()),
+            response: response.to_string(),
+        }
+    }
+}
+
+fn helper_name(index: u8) -> String {
+    let helper: Option<HelperFunctionID> = num::FromPrimitive::from_u8(index);
+    match helper {
+        Some(helper) => format!("{:?}", helper),
+        None => format!("unknown ({:#x})", index),
+    }
+}
+
+impl fmt::Display for ExecutionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let row = |f: &mut fmt::Formatter<'_>, key: &str, value: &str| {
+            writeln!(f, "  {:<16} | {}", key, value)
+        };
+
+        row(
+            f,
+            "Program",
+            self.program.as_deref().unwrap_or("<no metadata>"),
+        )?;
+        row(f, "Slot", &self.slot.to_string())?;
+        row(f, "Layout", &format!("{:?}", self.layout))?;
+        let vm = if self.jit {
+            format!("{} (JIT)", self.target)
+        } else {
+            self.target.to_string()
+        };
+        row(f, "VM", &vm)?;
+
+        if self.helpers.is_empty() {
+            row(f, "Helpers", "all")?;
+        }
+        for (i, helper) in self.helpers.iter().enumerate() {
+            let key = if i == 0 { "Helpers" } else { "" };
+            row(f, key, &helper_name(*helper))?;
+        }
+
+        let execution_time = match self.execution_time {
+            Some(time) => time.to_string(),
+            None => "-".to_string(),
+        };
+        row(f, "Execution time", &execution_time)?;
+        if let Some(instructions) = self.instructions {
+            row(f, "Instructions", &instructions.to_string())?;
+        }
+        if let Some(seed) = self.seed {
+            row(f, "Seed", &seed.to_string())?;
+        }
+
+        match self.result {
+            Some(result) => row(f, "Result", &format!("{} ({:#x})", result, result as u32))?,
+            None => row(f, "Response", &self.response)?,
+        }
+
+        match &self.buffer {
+            Some(buffer) => row(
+                f,
+                "Buffer",
+                &format!("{} bytes: {}", buffer.len, buffer.data),
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Decodes the returned buffer as an array of the given element type (u8, i8,
+/// u16, i16, u32, i32, u64, i64) and formats it as a list of values.
+pub fn format_buffer(buffer: &ReturnBuffer, element_type: &str) -> Result<String, String> {
+    fn format<T: BufferElement + fmt::Display>(buffer: &ReturnBuffer) -> Result<String, String> {
+        let values = buffer
+            .decode::<T>()?
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<String>>();
+        Ok(format!("[{}]", values.join(", ")))
+    }
+
+    match element_type {
+        "u8" => format::<u8>(buffer),
+        "i8" => format::<i8>(buffer),
+        "u16" => format::<u16>(buffer),
+        "i16" => format::<i16>(buffer),
+        "u32" => format::<u32>(buffer),
+        "i32" => format::<i32>(buffer),
+        "u64" => format::<u64>(buffer),
+        "i64" => format::<i64>(buffer),
+        _ => Err(format!("Unsupported buffer element type: {}", element_type)),
+    }
+}
+
+/// Formats the helper calls traced by the device, one per line, naming the
+/// helpers and showing their arguments and return values, e.g.:
+///
+/// ```text
+///   #0  BPF_SAUL_REG_FIND_NTH(0x1) = 0x2000a3c0
+///   #1  BPF_SAUL_REG_READ(0x2000a3c0, 0x20001f40) = 0x1
+/// ```
+///
+/// The trailing zero arguments are omitted as the device always reports all
+/// five argument registers regardless of how many the helper takes.
+pub fn format_helper_trace(trace: &[HelperCall]) -> String {
+    trace
+        .iter()
+        .enumerate()
+        .map(|(i, call)| {
+            let used = call
+                .args
+                .iter()
+                .rposition(|arg| *arg != 0)
+                .map_or(0, |last| last + 1);
+            let args = call.args[..used]
+                .iter()
+                .map(|arg| format!("{:#x}", arg))
+                .collect::<Vec<String>>();
+            format!(
+                "  #{:<3} {}({}) = {:#x}\n",
+                i,
+                helper_name(call.id),
+                args.join(", "),
+                call.ret
+            )
+        })
+        .collect()
+}
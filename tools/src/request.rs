@@ -0,0 +1,234 @@
+//! Wire format of the requests sent to the devices. Tools talking to the
+//! devices directly (and the servers impersonating them) should build and
+//! parse the requests using these functions, so that the encoding stays in
+//! sync with the firmware.
+
+use enum_iterator::all;
+use micro_bpf_common::{
+    encode_helper_bitmap, BinaryFileLayout, HelperAccessListSource, HelperAccessVerification,
+    HelperFunctionID, StorageBackend, SuitPullRequest, TargetVM, VMConfiguration,
+    VMExecutionRequest,
+};
+
+/// Builds the request instructing the VM how the program should be executed.
+pub fn build_execution_request(
+    target: TargetVM,
+    binary_layout: BinaryFileLayout,
+    suit_storage_slot: usize,
+    helper_access_verification: HelperAccessVerification,
+    helper_access_list_source: HelperAccessListSource,
+    helper_indices: &[u8],
+    jit: bool,
+    jit_compile: bool,
+) -> VMExecutionRequest {
+    // If the user doesn't specify any allowed helper indices, we allow all of them
+    // by default.
+    let helper_indices = if helper_indices.len() == 0 {
+        all::<HelperFunctionID>().collect::<Vec<HelperFunctionID>>()
+    } else {
+        helper_indices
+            .to_vec()
+            .into_iter()
+            .filter_map(|i| num::FromPrimitive::from_u8(i))
+            .collect::<Vec<HelperFunctionID>>()
+    };
+
+    VMExecutionRequest::new(
+        VMConfiguration::new(
+            target,
+            suit_storage_slot,
+            binary_layout,
+            helper_access_verification,
+            helper_access_list_source,
+            jit,
+            jit_compile,
+        ),
+        helper_indices,
+    )
+}
+
+/// Checks whether the allowed helpers of the request take up less space when
+/// sent as a bitmap (see [`VMExecutionRequest::with_helper_bitmap`]) than as
+/// the list of their IDs, e.g. when all helpers are allowed. Only the devices
+/// reporting [`micro_bpf_common::CAPABILITY_HELPER_BITMAP`] decode the bitmap.
+pub fn helper_bitmap_is_shorter(request: &VMExecutionRequest) -> bool {
+    // Both encodings use two hex characters per byte, the bitmap is preceded
+    // by a separator.
+    let bitmap_length = 1 + 2 * encode_helper_bitmap(&request.allowed_helpers).len();
+    bitmap_length < 2 * request.allowed_helpers.len()
+}
+
+/// Parses the payload of an execution request, the inverse of
+/// [`VMExecutionRequest::encode`].
+pub fn decode_execution_request(payload: &str) -> Result<VMExecutionRequest, String> {
+    VMExecutionRequest::decode(payload.trim().to_string())
+}
+
+/// Builds the request instructing the device to pull the binary described by
+/// the SUIT manifest from the CoAP fileserver on the host.
+pub fn build_pull_request(
+    host_ipv6_addr: &str,
+    suit_manifest: &str,
+    riot_network_interface: &str,
+    target: TargetVM,
+    binary_layout: BinaryFileLayout,
+    suit_storage_slot: usize,
+    storage: StorageBackend,
+    helper_access_verification: HelperAccessVerification,
+    helper_access_list_source: HelperAccessListSource,
+    helper_indices: &[u8],
+    erase: bool,
+) -> SuitPullRequest {
+    let configuration = VMConfiguration::new(
+        target,
+        suit_storage_slot,
+        binary_layout,
+        helper_access_verification,
+        helper_access_list_source,
+        false,
+        false,
+    )
+    .with_storage(storage);
+
+    SuitPullRequest {
+        ip: host_ipv6_addr.to_string(),
+        manifest: suit_manifest.to_string(),
+        // We need to tell the microcontroller which network interface (usually 5 or
+        // 6) needs to be used to access the CoAP fileserver on the remote host.
+        // the reason for this is that this interface changes based on the target
+        // architecture (stm32/native) and so it can't be hard-coded.
+        riot_netif: riot_network_interface.to_string(),
+        config: configuration.encode(),
+        helpers: encode_helper_list(helper_indices),
+        erase,
+    }
+}
+
+/// Parses the payload of a pull request, the inverse of
+/// [`SuitPullRequest::encode`].
+pub fn decode_pull_request(payload: &str) -> Result<SuitPullRequest, String> {
+    SuitPullRequest::decode(payload.trim().to_string())
+}
+
+/// Encodes the list of helper indices as concatenated two-digit hex numbers,
+/// e.g. `[1, 16]` becomes `0110`.
+pub fn encode_helper_list(helper_indices: &[u8]) -> String {
+    helper_indices
+        .iter()
+        .map(|i| format!("{:02x}", i))
+        .collect::<String>()
+}
+
+/// Parses the list of helper indices encoded by [`encode_helper_list`].
+pub fn decode_helper_list(encoding: &str) -> Result<Vec<u8>, String> {
+    if encoding.len() % 2 != 0 || !encoding.is_ascii() {
+        return Err(format!("Invalid helper list encoding: {}", encoding));
+    }
+    (0..encoding.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&encoding[i..i + 2], 16)
+                .map_err(|e| format!("Invalid helper index {}: {}", &encoding[i..i + 2], e))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn helper_list_round_trips() {
+        let helpers = vec![0, 1, 16, 255];
+        assert_eq!(encode_helper_list(&helpers), "000110ff");
+        assert_eq!(decode_helper_list("000110ff").unwrap(), helpers);
+        assert_eq!(decode_helper_list("").unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn malformed_helper_list_is_rejected() {
+        assert!(decode_helper_list("011").is_err());
+        assert!(decode_helper_list("0g").is_err());
+    }
+
+    #[test]
+    fn execution_request_defaults_to_all_helpers() {
+        let request = build_execution_request(
+            TargetVM::Rbpf,
+            BinaryFileLayout::ExtendedHeader,
+            2,
+            HelperAccessVerification::AheadOfTime,
+            HelperAccessListSource::ExecuteRequest,
+            &[],
+            false,
+            false,
+        );
+        assert_eq!(
+            request.allowed_helpers,
+            all::<HelperFunctionID>().collect::<Vec<HelperFunctionID>>()
+        );
+
+        let decoded = decode_execution_request(&request.encode()).unwrap();
+        assert_eq!(decoded.configuration, request.configuration);
+        assert_eq!(decoded.allowed_helpers, request.allowed_helpers);
+    }
+
+    #[test]
+    fn helper_bitmap_is_only_shorter_for_many_helpers() {
+        let request = |helper_indices: &[u8]| {
+            build_execution_request(
+                TargetVM::Rbpf,
+                BinaryFileLayout::ExtendedHeader,
+                0,
+                HelperAccessVerification::Runtime,
+                HelperAccessListSource::ExecuteRequest,
+                helper_indices,
+                false,
+                false,
+            )
+        };
+
+        let all_helpers = request(&[]);
+        assert!(helper_bitmap_is_shorter(&all_helpers));
+        let decoded =
+            decode_execution_request(&all_helpers.clone().with_helper_bitmap(true).encode())
+                .unwrap();
+        let mut expected = all_helpers.allowed_helpers.clone();
+        expected.sort();
+        assert_eq!(decoded.allowed_helpers, expected);
+
+        assert!(!helper_bitmap_is_shorter(&request(&[0x01, 0x90])));
+    }
+
+    #[test]
+    fn truncated_execution_request_is_rejected() {
+        assert!(decode_execution_request("").is_err());
+        assert!(decode_execution_request("00").is_err());
+        assert!(decode_execution_request("00010").is_err());
+    }
+
+    #[test]
+    fn pull_request_round_trips() {
+        let request = build_pull_request(
+            "fe80::1234:5678:9abc:def0",
+            "suit_manifest0.signed",
+            "6",
+            TargetVM::Rbpf,
+            BinaryFileLayout::ExtendedHeader,
+            1,
+            StorageBackend::Flash,
+            HelperAccessVerification::LoadTime,
+            HelperAccessListSource::BinaryMetadata,
+            &[1, 2],
+            true,
+        );
+
+        let decoded = decode_pull_request(&request.encode()).unwrap();
+        assert_eq!(decoded.ip, request.ip);
+        assert_eq!(decoded.manifest, request.manifest);
+        assert_eq!(decoded.riot_netif, request.riot_netif);
+        assert_eq!(decoded.config, request.config);
+        assert_eq!(decode_helper_list(&decoded.helpers).unwrap(), vec![1, 2]);
+        assert!(decoded.erase);
+    }
+}
@@ -0,0 +1,345 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+use enum_iterator::all;
+use log::{info, warn};
+use micro_bpf_common::{
+    BinaryFileLayout, ExecutionModel, ExecutionResponse, HelperAccessListSource,
+    HelperAccessVerification, HelperFunctionID, StorageBackend, TargetVM,
+};
+
+use crate::{
+    artifacts::ArtifactStore,
+    deploy::{deploy, DeployOptions},
+    environment::Environment,
+    execute::{execute, ExecuteOptions},
+    fleet::{Device, Fleet},
+    publish::LocalPublisher,
+    registry::Registry,
+    slots::{wait_for_installation, SuitSlot},
+    transport::{is_cancelled, TransportOptions},
+};
+
+/// How long the validation waits before executing the program if the device
+/// can't report whether it has installed the update.
+const UNCONFIRMED_INSTALLATION_DELAY: Duration = Duration::from_secs(5);
+
+/// Order in which a program is rolled out to the devices of the fleet. The
+/// devices are deployed to in batches, each batch is validated by executing
+/// the program before the next one is started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RolloutStrategy {
+    /// All devices in a single batch.
+    All,
+    /// The percentage of the fleet (at least one device) first, and the
+    /// remaining devices once the canaries pass the validation.
+    Canary(u32),
+    /// Batches of the given number of devices.
+    Rolling(usize),
+}
+
+impl FromStr for RolloutStrategy {
+    type Err = String;
+
+    /// Parses the strategies of the form `all`, `canary:10%` or `rolling:2`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid rollout strategy: {}, expected all, canary:<percent>% or rolling:<batch size>",
+                s
+            )
+        };
+        match s.split_once(':') {
+            None if s == "all" => Ok(RolloutStrategy::All),
+            Some(("canary", percent)) => {
+                let percent = percent
+                    .trim_end_matches('%')
+                    .parse::<u32>()
+                    .map_err(|_| invalid())?;
+                if !(1..=100).contains(&percent) {
+                    return Err(format!(
+                        "The canary percentage must be between 1 and 100, got: {}",
+                        percent
+                    ));
+                }
+                Ok(RolloutStrategy::Canary(percent))
+            }
+            Some(("rolling", batch)) => match batch.parse::<usize>() {
+                Ok(batch) if batch > 0 => Ok(RolloutStrategy::Rolling(batch)),
+                _ => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl RolloutStrategy {
+    /// Sizes of the consecutive batches that the devices are split into.
+    pub fn batches(&self, devices: usize) -> Vec<usize> {
+        if devices == 0 {
+            return vec![];
+        }
+        let first = match self {
+            RolloutStrategy::All => devices,
+            RolloutStrategy::Canary(percent) => {
+                ((devices * *percent as usize).div_ceil(100)).clamp(1, devices)
+            }
+            RolloutStrategy::Rolling(batch) => (*batch).min(devices),
+        };
+        let mut batches = vec![first];
+        let mut remaining = devices - first;
+        while remaining > 0 {
+            let batch = match self {
+                RolloutStrategy::Rolling(batch) => (*batch).min(remaining),
+                _ => remaining,
+            };
+            batches.push(batch);
+            remaining -= batch;
+        }
+        batches
+    }
+}
+
+/// Program rolled out to the fleet.
+#[derive(Debug, Clone)]
+pub struct RolloutProgram {
+    /// Source file of the program.
+    pub source: String,
+    pub slot: usize,
+    pub storage: StorageBackend,
+    pub target: TargetVM,
+    pub binary_layout: BinaryFileLayout,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RolloutStatus {
+    /// Deployed and returned the expected result in the validation
+    /// execution.
+    Validated(i64),
+    Failed(String),
+    /// Not deployed because the rollout was aborted.
+    Skipped,
+}
+
+#[derive(Debug, Clone)]
+pub struct RolloutOutcome {
+    pub device: Device,
+    /// Index of the batch that the device belongs to.
+    pub batch: usize,
+    pub status: RolloutStatus,
+}
+
+#[derive(Debug, Clone)]
+pub struct RolloutReport {
+    pub outcomes: Vec<RolloutOutcome>,
+    /// Reason why the remaining batches weren't deployed.
+    pub aborted: Option<String>,
+}
+
+/// Deploys the program to the fleet batch by batch according to the strategy.
+/// After each batch, the program is executed on its devices and the rollout
+/// only proceeds if all of them return the expected result. Without an
+/// explicit expectation, all devices need to return the same result as the
+/// first validated device.
+///
+/// The failed validation doesn't return an error, it is reported in
+/// [`RolloutReport::aborted`] together with the devices which had already
+/// received the program.
+pub async fn roll_out(
+    environment: &Environment,
+    fleet: &Fleet,
+    program: &RolloutProgram,
+    strategy: RolloutStrategy,
+    expected_result: Option<i64>,
+    transport: &TransportOptions,
+) -> Result<RolloutReport, String> {
+    let mut expected_result = expected_result;
+    let mut outcomes = vec![];
+    let mut aborted = None;
+    let mut devices = fleet.devices.iter();
+    for (batch, size) in strategy
+        .batches(fleet.devices.len())
+        .into_iter()
+        .enumerate()
+    {
+        let batch_devices = devices.by_ref().take(size).collect::<Vec<&Device>>();
+        if aborted.is_some() {
+            outcomes.extend(batch_devices.into_iter().map(|device| RolloutOutcome {
+                device: device.clone(),
+                batch,
+                status: RolloutStatus::Skipped,
+            }));
+            continue;
+        }
+
+        info!(
+            "[rollout] batch {}: deploying to {} device(s)",
+            batch + 1,
+            size
+        );
+        let mut failures = 0;
+        for device in batch_devices {
+            transport.check_cancelled()?;
+            let status = match deploy_and_validate(environment, device, program, transport).await {
+                Err(e) if is_cancelled(&e) => return Err(e),
+                Err(e) => RolloutStatus::Failed(e),
+                Ok(result) => match expected_result {
+                    Some(expected) if result != expected => RolloutStatus::Failed(format!(
+                        "returned {} instead of the expected {}",
+                        result, expected
+                    )),
+                    _ => {
+                        expected_result = Some(result);
+                        RolloutStatus::Validated(result)
+                    }
+                },
+            };
+            if let RolloutStatus::Failed(e) = &status {
+                warn!("[rollout] {}: {}", device.riot_ipv6_addr, e);
+                failures += 1;
+            }
+            outcomes.push(RolloutOutcome {
+                device: device.clone(),
+                batch,
+                status,
+            });
+        }
+        if failures > 0 {
+            aborted = Some(format!(
+                "{} device(s) of batch {} failed the validation",
+                failures,
+                batch + 1
+            ));
+        }
+    }
+    Ok(RolloutReport { outcomes, aborted })
+}
+
+/// Deploys the program to the device and executes it once the device has
+/// installed it, returning its result.
+async fn deploy_and_validate(
+    environment: &Environment,
+    device: &Device,
+    program: &RolloutProgram,
+    transport: &TransportOptions,
+) -> Result<i64, String> {
+    let helpers = all::<HelperFunctionID>()
+        .map(|h| h as u8)
+        .collect::<Vec<u8>>();
+
+    deploy(
+        &program.source,
+        &ArtifactStore::from_env(environment),
+        program.target,
+        program.binary_layout,
+        SuitSlot::Fixed(program.slot),
+        program.storage,
+        &environment.riot_instance_net_if,
+        &device.riot_ipv6_addr,
+        &device.host_network_interface,
+        &environment.host_ip,
+        &environment.board_name,
+        Some(&environment.micro_bpf_root_dir),
+        helpers.clone(),
+        HelperAccessVerification::AheadOfTime,
+        HelperAccessListSource::ExecuteRequest,
+        &DeployOptions::default(),
+        &LocalPublisher::new(&environment.coap_root_dir),
+        transport,
+    )
+    .await
+    .map_err(|e| format!("deployment failed: {}", e))?;
+    wait_until_installed(device, program.slot, transport).await?;
+
+    let response = execute(
+        &device.riot_ipv6_addr,
+        program.target,
+        program.binary_layout,
+        program.slot,
+        &device.host_network_interface,
+        ExecutionModel::ShortLived,
+        HelperAccessVerification::AheadOfTime,
+        HelperAccessListSource::ExecuteRequest,
+        &helpers,
+        false,
+        &ExecuteOptions::default(),
+        transport,
+    )
+    .await
+    .map_err(|e| format!("validation execution failed: {}", e))?;
+    serde_json::from_str::<ExecutionResponse>(response.trim_matches(char::from(0)))
+        .map(|response| response.result)
+        .map_err(|e| format!("invalid validation response: {}", e))
+}
+
+/// Makes sure that the validation doesn't execute the previous program (or an
+/// empty slot) while the device is still installing the update. The deployment
+/// records the sequence number of the update in the registry.
+async fn wait_until_installed(
+    device: &Device,
+    slot: usize,
+    transport: &TransportOptions,
+) -> Result<(), String> {
+    if transport.dry_run {
+        return Ok(());
+    }
+    let sequence_number = Registry::load()?
+        .device(&device.riot_ipv6_addr)
+        .and_then(|d| d.sequence_number)
+        .ok_or_else(|| "the sequence number of the update isn't recorded".to_string())?;
+    let installed = wait_for_installation(
+        &device.riot_ipv6_addr,
+        &device.host_network_interface,
+        slot,
+        sequence_number,
+        transport,
+    )
+    .await
+    .map_err(|e| format!("installation failed: {}", e))?;
+    if !installed {
+        warn!(
+            "{} doesn't report the installed updates, waiting {:?} before the validation",
+            device.riot_ipv6_addr, UNCONFIRMED_INSTALLATION_DELAY
+        );
+        tokio::time::sleep(UNCONFIRMED_INSTALLATION_DELAY).await;
+    }
+    Ok(())
+}
+
+impl fmt::Display for RolloutReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for outcome in &self.outcomes {
+            let status = match &outcome.status {
+                RolloutStatus::Validated(result) => format!("ok (result: {})", result),
+                RolloutStatus::Failed(e) => format!("failed: {}", e),
+                RolloutStatus::Skipped => "skipped".to_string(),
+            };
+            writeln!(
+                f,
+                "batch {}: {}: {}",
+                outcome.batch + 1,
+                outcome.device.riot_ipv6_addr,
+                status
+            )?;
+        }
+        let validated = self
+            .outcomes
+            .iter()
+            .filter(|o| matches!(o.status, RolloutStatus::Validated(_)))
+            .count();
+        match &self.aborted {
+            Some(reason) => write!(
+                f,
+                "Aborted the rollout after {}/{} devices: {}",
+                validated,
+                self.outcomes.len(),
+                reason
+            ),
+            None => write!(
+                f,
+                "Rolled out to {}/{} devices",
+                validated,
+                self.outcomes.len()
+            ),
+        }
+    }
+}
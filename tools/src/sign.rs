@@ -1,59 +1,104 @@
-use std::process::Command;
+use std::fs;
 
-use log::{debug, error};
+use log::{debug, info};
+use micro_bpf_common::StorageBackend;
 
+use crate::{
+    compression::ArtifactCompression,
+    interface::interface_address,
+    registry::now,
+    suit::{write_signed_manifest, SuitPayload},
+};
+
+/// Moves the binary into the CoAP root directory and generates the SUIT
+/// manifest `suit_manifest<slot>.signed` which installs it into the slot of
+/// the board. The manifest is signed using the active key of the key store,
+/// or the default key of RIOT if there isn't one.
+///
+/// The manifest points the device to the binary under `fetch_base_uri` (see
+/// [`crate::Publisher::fetch_base_uri`]), by default the CoAP file server at
+/// the address of the host network interface.
+///
+/// If the compression is given, the compressed binary is served next to the
+/// original one and the manifest points the device to it. Returns the path
+/// of the compressed binary in the CoAP root directory, None if the binary
+/// is served uncompressed (e.g. because the compression wouldn't reduce its
+/// size).
 pub fn sign(
     host_network_interface: &str,
+    fetch_base_uri: Option<&str>,
     board_name: &str,
     coaproot_dir: &str,
     binary_name: &str,
     suit_storage_slot: usize,
-    mibpf_root_dir: Option<&str>,
-) -> Result<(), String> {
-    place_binary_in_coap_root(coaproot_dir, binary_name);
-
+    storage: StorageBackend,
+    sequence_number: Option<u64>,
+    compression: Option<ArtifactCompression>,
+) -> Result<Option<String>, String> {
     let file_name = binary_name.split("/").last().unwrap();
+    let served = format!("{}/{}", coaproot_dir, file_name);
+    let contents =
+        fs::read(binary_name).map_err(|e| format!("Failed to read {}: {}", binary_name, e))?;
+    fs::write(&served, &contents)
+        .and_then(|_| fs::remove_file(binary_name))
+        .map_err(|e| format!("Failed to move {} into {}: {}", file_name, coaproot_dir, e))?;
 
-    let mibpf_home = match mibpf_root_dir {
-        Some(dir) => dir,
-        // The default assumes that the tools script is run from the root of the mibpf repo.
-        None => ".",
+    let compressed = match compression {
+        Some(compression) => compress_artifact(&served, &contents, compression)?,
+        None => None,
     };
+    let uri_file_name = compressed
+        .as_ref()
+        .map(|(path, _)| path.split('/').last().unwrap())
+        .unwrap_or(file_name);
 
-    let Ok(output) = Command::new("bash")
-        .env("RIOT_HOME", &format!("{}/RIOT", mibpf_home))
-        .arg(&format!("{}/scripts/sign-binary.sh", mibpf_home))
-        .arg(host_network_interface)
-        .arg(board_name)
-        .arg(coaproot_dir)
-        // The file should have been copied to coaproot by now.
-        .arg(format!("{}/{}", coaproot_dir, file_name))
-        .arg(suit_storage_slot.to_string())
-        .output()
-    else {
-        return Err("Failed to sign the binary".to_string());
+    let fetch_base_uri = match fetch_base_uri {
+        Some(uri) => uri.to_string(),
+        None => format!("coap://[{}]", interface_address(host_network_interface)?),
+    };
+    let payload = SuitPayload {
+        uri: format!("{}/{}", fetch_base_uri, uri_file_name),
+        // The component ID determines where the device stores the payload.
+        component_id: storage.component_id(suit_storage_slot),
+        contents,
+        compression: compressed.as_ref().map(|(_, compression)| *compression),
     };
+    // Same as in the RIOT build system, the sequence number defaults to the
+    // current timestamp.
+    let sequence_number = sequence_number.unwrap_or_else(now);
+    let manifest = format!("{}/suit_manifest{}.signed", coaproot_dir, suit_storage_slot);
+    write_signed_manifest(&[payload], sequence_number, Some(board_name), &manifest)?;
 
     debug!(
-        "sign.sh output: \n{}",
-        String::from_utf8(output.stdout).unwrap()
+        "Signed {} for {} with sequence number {}: {}",
+        file_name, board_name, sequence_number, manifest
     );
-    if output.stderr.len() > 0 {
-        error!(
-            "Error while signing: \n{}",
-            String::from_utf8(output.stderr).unwrap()
-        );
-
-    }
-
-    Ok(())
+    Ok(compressed.map(|(path, _)| path))
 }
 
-fn place_binary_in_coap_root(coaproot_dir: &str, binary_name: &str) {
-    let _ = Command::new("mv")
-        .arg(binary_name)
-        .arg(coaproot_dir)
-        .spawn()
-        .expect("Failed to copy the binary file.")
-        .wait();
+/// Writes the compressed artifact next to the served one, unless the
+/// compression doesn't reduce its size.
+fn compress_artifact(
+    served: &str,
+    contents: &[u8],
+    compression: ArtifactCompression,
+) -> Result<Option<(String, ArtifactCompression)>, String> {
+    let compressed = compression.compress(contents)?;
+    if compressed.len() >= contents.len() {
+        info!(
+            "Compressing {} using {} wouldn't reduce its size, serving it uncompressed",
+            served, compression
+        );
+        return Ok(None);
+    }
+    let path = format!("{}.{}", served, compression.extension());
+    fs::write(&path, &compressed).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    info!(
+        "Compressed {} using {}: {} -> {} bytes",
+        served,
+        compression,
+        contents.len(),
+        compressed.len()
+    );
+    Ok(Some((path, compression)))
 }
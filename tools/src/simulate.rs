@@ -0,0 +1,867 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use enum_iterator::all;
+use log::debug;
+use micro_bpf_common::{HelperFunctionID, MAX_RETURN_BUFFER_SIZE};
+use micro_bpf_elf_utils::extract_section;
+use serde::Deserialize;
+
+use crate::{bytecode, helpers::parse_helper, postprocessing::read_bytes_from_file};
+
+/// Size of the stack of the rbpf VM, the frame pointer (r10) points to its end.
+const STACK_SIZE: u64 = 512;
+
+/// ID of the helper which records the location of the stack, it isn't used by
+/// any of the RIOT helpers.
+const STACK_PROBE_ID: u32 = 0xff;
+
+/// Instructions prepended to the program which pass the frame pointer to the
+/// stack probe, so that the stubs can check the pointers into the stack. The
+/// registers are restored to the values expected by the program: r1 points
+/// to the context, r0 and r6 are zero.
+const STACK_PROBE_PROLOGUE: [[u8; 8]; 5] = [
+    [0xbf, 0x16, 0, 0, 0, 0, 0, 0],    // mov r6, r1
+    [0xbf, 0xa1, 0, 0, 0, 0, 0, 0],    // mov r1, r10
+    [0x85, 0x00, 0, 0, 0xff, 0, 0, 0], // call STACK_PROBE_ID
+    [0xbf, 0x61, 0, 0, 0, 0, 0, 0],    // mov r1, r6
+    [0xb7, 0x06, 0, 0, 0, 0, 0, 0],    // mov r6, 0
+];
+
+/// Configures the behaviour of the stub implementations of the RIOT helpers.
+#[derive(Debug, Clone, Default)]
+pub struct StubConfig {
+    /// Fixed return values of the helpers, they take precedence over the
+    /// default behaviour of the stubs.
+    pub return_values: BTreeMap<HelperFunctionID, u64>,
+    /// Fake sensor readings returned (in a round-robin fashion) by the SAUL
+    /// helpers reading the sensor values.
+    pub sensor_readings: Vec<u64>,
+    /// Scripted behaviour of the helpers loaded from a scenario file (see
+    /// [`load_scenario`]), the fixed return values take precedence over it.
+    pub scripts: BTreeMap<HelperFunctionID, HelperScript>,
+}
+
+/// Scripted results of the consecutive calls of a helper, used for testing
+/// the time- and sensor-dependent programs deterministically.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HelperScript {
+    /// Every call returns the same value.
+    Constant(u64),
+    /// The calls return the values in order, once they run out the last one
+    /// is returned (or the sequence starts over if it repeats).
+    Sequence { values: Vec<u64>, repeat: bool },
+    /// The first call returns `start` and each following one advances the
+    /// value by `step`, e.g. a clock ticking 100ms between the calls.
+    Counter { start: u64, step: u64 },
+}
+
+impl HelperScript {
+    /// Result of the n-th call of the helper (starting from 0).
+    fn result(&self, call: usize) -> u64 {
+        match self {
+            HelperScript::Constant(value) => *value,
+            HelperScript::Sequence { values, repeat } => {
+                let index = if *repeat {
+                    call % values.len()
+                } else {
+                    call.min(values.len() - 1)
+                };
+                values[index]
+            }
+            HelperScript::Counter { start, step } => {
+                start.wrapping_add(step.wrapping_mul(call as u64))
+            }
+        }
+    }
+}
+
+/// Behaviour of a single helper in the scenario file, exactly one of
+/// `value`, `values` or `start` needs to be specified.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HelperScriptEntry {
+    value: Option<u64>,
+    values: Option<Vec<u64>>,
+    #[serde(default)]
+    repeat: bool,
+    start: Option<u64>,
+    #[serde(default)]
+    step: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Scenario {
+    helpers: BTreeMap<String, HelperScriptEntry>,
+}
+
+/// A single helper call made by the simulated program.
+#[derive(Debug, Clone)]
+pub struct HelperCall {
+    pub helper: HelperFunctionID,
+    pub args: [u64; 5],
+    pub result: u64,
+}
+
+/// Outcome of the simulated execution.
+#[derive(Debug)]
+pub struct SimulationReport {
+    pub return_value: u64,
+    pub helper_calls: Vec<HelperCall>,
+    /// Buffer set by the program using `bpf_set_return_buffer`.
+    pub return_buffer: Option<Vec<u8>>,
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for call in &self.helper_calls {
+            writeln!(
+                f,
+                "{:?}({:#x}, {:#x}, {:#x}, {:#x}, {:#x}) -> {}",
+                call.helper,
+                call.args[0],
+                call.args[1],
+                call.args[2],
+                call.args[3],
+                call.args[4],
+                call.result
+            )?;
+        }
+        write!(f, "Program returned: {}", self.return_value)?;
+        if let Some(buffer) = &self.return_buffer {
+            write!(
+                f,
+                "\nReturn buffer ({} bytes): {:02x?}",
+                buffer.len(),
+                buffer
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Named memory region that the program may access, e.g. the context or the
+/// stack. The regions declared by the user cover parts of the context (e.g.
+/// the header of a packet), their start is then relative to the context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub name: String,
+    pub start: u64,
+    pub len: u64,
+}
+
+impl MemoryRegion {
+    fn end(&self) -> u64 {
+        self.start + self.len
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Load,
+    Store,
+}
+
+/// Load or store outside of all memory regions of the program, caught by the
+/// bounds check of the VM.
+#[derive(Debug, Clone)]
+pub struct MemoryViolation {
+    pub kind: AccessKind,
+    /// Index of the offending instruction in the .text section (the wide
+    /// instructions occupy two slots).
+    pub instruction: usize,
+    /// Disassembly of the offending instruction.
+    pub disassembly: Option<String>,
+    pub address: u64,
+    pub size: u64,
+    /// Regions of the program at the time of the access.
+    pub regions: Vec<MemoryRegion>,
+}
+
+impl MemoryViolation {
+    /// Describes the access relative to the closest region, the smaller
+    /// (declared) regions are preferred so that the diagnostic names the
+    /// field of the context rather than the context itself.
+    fn location(&self) -> Option<String> {
+        let mut regions = self.regions.iter().collect::<Vec<_>>();
+        regions.sort_by_key(|region| region.len);
+        let distance = |region: &MemoryRegion| {
+            if self.address < region.start {
+                region.start - self.address
+            } else {
+                (self.address + self.size).saturating_sub(region.end())
+            }
+        };
+        let region = regions.into_iter().min_by_key(|region| distance(region))?;
+        let description = if self.address < region.start {
+            format!(
+                "{} bytes before the start of {}",
+                region.start - self.address,
+                region.name
+            )
+        } else if self.address >= region.end() {
+            format!(
+                "offset {} of {}, {} bytes past its end",
+                self.address - region.start,
+                region.name,
+                self.address - region.end()
+            )
+        } else {
+            format!(
+                "offset {} of {}, the access overflows its end by {} bytes",
+                self.address - region.start,
+                region.name,
+                self.address + self.size - region.end()
+            )
+        };
+        Some(format!("{} ({} bytes)", description, region.len))
+    }
+}
+
+impl fmt::Display for MemoryViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            AccessKind::Load => "load",
+            AccessKind::Store => "store",
+        };
+        write!(
+            f,
+            "Out-of-bounds {} of {} bytes at instruction #{}",
+            kind, self.size, self.instruction
+        )?;
+        if let Some(disassembly) = &self.disassembly {
+            write!(f, " ({})", disassembly)?;
+        }
+        write!(f, "\n  address: {:#x}", self.address)?;
+        if let Some(location) = self.location() {
+            write!(f, " at {}", location)?;
+        }
+        for region in &self.regions {
+            write!(
+                f,
+                "\n  {:<12} {:#x}..{:#x}",
+                region.name,
+                region.start,
+                region.end()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// State shared by the stubs. The helpers registered in rbpf are plain
+/// function pointers, hence the state can't be captured and needs to be global.
+#[derive(Default)]
+struct StubState {
+    config: StubConfig,
+    next_reading: usize,
+    /// Number of calls of each scripted helper so far.
+    script_calls: HashMap<HelperFunctionID, usize>,
+    key_value_store: HashMap<u32, u32>,
+    calls: Vec<HelperCall>,
+    return_buffer: Option<Vec<u8>>,
+    /// Memory of the program that the helpers may access: the context and
+    /// the stack (once it has been recorded by the stack probe).
+    mapped_regions: Vec<MemoryRegion>,
+    /// First invalid helper argument, it fails the simulation.
+    fault: Option<String>,
+}
+
+impl StubState {
+    /// Returns the mapped region containing the whole buffer, a fault is
+    /// recorded if there isn't one. The stubs need to check the pointers
+    /// passed by the program before dereferencing them, as the VM doesn't.
+    fn guest_region(
+        &mut self,
+        helper: HelperFunctionID,
+        address: u64,
+        len: u64,
+    ) -> Option<&MemoryRegion> {
+        let region = self.mapped_regions.iter().position(|region| {
+            address >= region.start
+                && address
+                    .checked_add(len)
+                    .is_some_and(|end| end <= region.end())
+        });
+        if region.is_none() && self.fault.is_none() {
+            self.fault = Some(format!(
+                "{:?} was passed {} bytes at {:#x}, outside of the context and the stack",
+                helper, len, address
+            ));
+        }
+        region.map(|i| &self.mapped_regions[i])
+    }
+}
+
+fn state() -> &'static Mutex<StubState> {
+    static STATE: OnceLock<Mutex<StubState>> = OnceLock::new();
+    STATE.get_or_init(|| Mutex::new(StubState::default()))
+}
+
+/// Runs the compiled program locally using the host rbpf VM, with the RIOT
+/// helpers replaced by stubs (see [`StubConfig`]). This allows for iterating
+/// on the logic of the program without any device.
+///
+/// Only the .text section of the program is executed (same as with the
+/// OnlyTextSection layout), so the programs need to avoid accessing .data and
+/// .rodata (e.g. use the `print` macro instead of `bpf_printf`). The context
+/// is the memory passed to the program in r1, e.g. the contents of the CoAP
+/// packet.
+///
+/// Every load and store is checked against the regions of the program (the
+/// context and the stack), the out-of-bounds accesses are reported as a
+/// [`MemoryViolation`] naming the instruction, the region and the offset.
+/// The `regions` declared by the user split the context into named parts,
+/// their start is relative to the start of the context.
+pub fn simulate(
+    object_file: &str,
+    config: StubConfig,
+    allowed_helpers: &[u8],
+    context: &mut [u8],
+    regions: &[MemoryRegion],
+) -> Result<SimulationReport, String> {
+    let bytes = read_bytes_from_file(object_file);
+    let text = extract_section(".text", &bytes)?;
+    if let Some(region) = regions
+        .iter()
+        .find(|region| region.end() > context.len() as u64)
+    {
+        return Err(format!(
+            "The region {} ends at {} which is outside of the {}-byte context",
+            region.name,
+            region.end(),
+            context.len()
+        ));
+    }
+
+    let probed_text = STACK_PROBE_PROLOGUE
+        .iter()
+        .flatten()
+        .chain(text)
+        .copied()
+        .collect::<Vec<u8>>();
+    let mut vm = rbpf::EbpfVmRaw::new(Some(&probed_text))
+        .map_err(|e| format!("Failed to load the program: {:?}", e))?;
+
+    for (helper, stub) in stubs() {
+        let id = helper as u32;
+        if !allowed_helpers.is_empty() && !allowed_helpers.contains(&(id as u8)) {
+            continue;
+        }
+        vm.register_helper(id, stub)
+            .map_err(|e| format!("Failed to register the helper {:?}: {:?}", helper, e))?;
+    }
+
+    vm.register_helper(STACK_PROBE_ID, stack_probe)
+        .map_err(|e| format!("Failed to register the stack probe: {:?}", e))?;
+
+    let context_start = context.as_ptr() as u64;
+    *state().lock().unwrap() = StubState {
+        config,
+        mapped_regions: vec![MemoryRegion {
+            name: "context".to_string(),
+            start: context_start,
+            len: context.len() as u64,
+        }],
+        ..Default::default()
+    };
+
+    let return_value = vm.execute_program(context).map_err(|e| {
+        let error = format!("{:?}", e);
+        match parse_memory_violation(&error, text) {
+            Some(mut violation) => {
+                violation
+                    .regions
+                    .extend(regions.iter().map(|region| MemoryRegion {
+                        start: context_start + region.start,
+                        ..region.clone()
+                    }));
+                format!("The program failed: {}", violation)
+            }
+            None => format!("The program failed: {}", error),
+        }
+    })?;
+
+    let mut state = state().lock().unwrap();
+    if let Some(fault) = state.fault.take() {
+        return Err(format!("The program failed: {}", fault));
+    }
+    Ok(SimulationReport {
+        return_value,
+        helper_calls: std::mem::take(&mut state.calls),
+        return_buffer: state.return_buffer.take(),
+    })
+}
+
+/// Extracts the details of the access from the error returned by the bounds
+/// check of rbpf, which has the following format:
+///
+/// `out of bounds memory store (insn #13), addr 0x..., size 4
+/// mbuff: 0x.../0x0, mem: 0x.../0x40, stack: 0x.../0x200`
+///
+/// The instruction pointer in the message has already been advanced past the
+/// offending instruction, it also counts the [`STACK_PROBE_PROLOGUE`].
+fn parse_memory_violation(error: &str, text: &[u8]) -> Option<MemoryViolation> {
+    let after = |pattern: &str| error.find(pattern).map(|i| &error[i + pattern.len()..]);
+    let number = |s: &str| -> Option<u64> {
+        match s.strip_prefix("0x") {
+            Some(hex) => {
+                let end = hex
+                    .find(|c: char| !c.is_ascii_hexdigit())
+                    .unwrap_or(hex.len());
+                u64::from_str_radix(&hex[..end], 16).ok()
+            }
+            None => {
+                let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+                s[..end].parse().ok()
+            }
+        }
+    };
+
+    let kind = after("out of bounds memory ")?;
+    let kind = if kind.starts_with("load") {
+        AccessKind::Load
+    } else if kind.starts_with("store") {
+        AccessKind::Store
+    } else {
+        return None;
+    };
+    let instruction =
+        (number(after("insn #")?)? as usize).saturating_sub(1 + STACK_PROBE_PROLOGUE.len());
+    let address = number(after("addr ")?)?;
+    let size = number(after("size ")?)?;
+
+    let regions = [
+        ("mbuff", "metadata"),
+        ("mem", "context"),
+        ("stack", "stack"),
+    ]
+    .into_iter()
+    .filter_map(|(label, name)| {
+        let (start, len) = after(&format!("{}: ", label))?.split_once('/')?;
+        Some(MemoryRegion {
+            name: name.to_string(),
+            start: number(start)?,
+            len: number(len)?,
+        })
+    })
+    .filter(|region| region.len > 0)
+    .collect();
+
+    let disassembly = bytecode::decode(text).ok().and_then(|instructions| {
+        instructions
+            .iter()
+            .find(|i| i.index == instruction)
+            .map(|i| i.to_string())
+    });
+
+    Some(MemoryViolation {
+        kind,
+        instruction,
+        disassembly,
+        address,
+        size,
+        regions,
+    })
+}
+
+/// Records the stack of the program, called by the [`STACK_PROBE_PROLOGUE`]
+/// with the frame pointer.
+fn stack_probe(frame_pointer: u64, _: u64, _: u64, _: u64, _: u64) -> u64 {
+    state().lock().unwrap().mapped_regions.push(MemoryRegion {
+        name: "stack".to_string(),
+        start: frame_pointer - STACK_SIZE,
+        len: STACK_SIZE,
+    });
+    0
+}
+
+/// Default behaviour of the stubs, the helpers without any meaningful host
+/// equivalent just return 0. The pointers passed by the program are checked
+/// against its context and stack, the invalid ones fail the simulation.
+fn call_stub(helper: HelperFunctionID, args: [u64; 5]) -> u64 {
+    let mut state = state().lock().unwrap();
+    let script_result = state.config.scripts.get(&helper).cloned().map(|script| {
+        let call = state.script_calls.entry(helper).or_insert(0);
+        *call += 1;
+        script.result(*call - 1)
+    });
+    let result = if let Some(value) = state.config.return_values.get(&helper) {
+        *value
+    } else if let Some(value) = script_result {
+        value
+    } else {
+        match helper {
+            HelperFunctionID::BPF_NOW_MS_IDX | HelperFunctionID::BPF_ZTIMER_NOW_IDX => {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or(0)
+            }
+            HelperFunctionID::BPF_SAUL_REG_READ_TEMP | HelperFunctionID::BPF_SAUL_REG_READ_IDX => {
+                let readings = &state.config.sensor_readings;
+                if readings.is_empty() {
+                    0
+                } else {
+                    let reading = readings[state.next_reading % readings.len()];
+                    state.next_reading += 1;
+                    reading
+                }
+            }
+            HelperFunctionID::BPF_STORE_LOCAL_IDX | HelperFunctionID::BPF_STORE_GLOBAL_IDX => {
+                state.key_value_store.insert(args[0] as u32, args[1] as u32);
+                0
+            }
+            HelperFunctionID::BPF_FETCH_LOCAL_IDX | HelperFunctionID::BPF_FETCH_GLOBAL_IDX => {
+                let value = state
+                    .key_value_store
+                    .get(&(args[0] as u32))
+                    .copied()
+                    .unwrap_or(0);
+                // Same as on the device, the second argument is the address
+                // that the value is written to.
+                if args[1] == 0 {
+                    0
+                } else if state.guest_region(helper, args[1], 4).is_some() {
+                    unsafe { (args[1] as *mut u32).write_unaligned(value) };
+                    0
+                } else {
+                    u64::MAX
+                }
+            }
+            HelperFunctionID::BPF_STRLEN_IDX if args[0] != 0 => {
+                // The string needs to be terminated within the region it
+                // starts in.
+                let terminated = state.guest_region(helper, args[0], 1).and_then(|region| {
+                    let bytes = unsafe {
+                        std::slice::from_raw_parts(
+                            args[0] as *const u8,
+                            (region.end() - args[0]) as usize,
+                        )
+                    };
+                    bytes.iter().position(|byte| *byte == 0)
+                });
+                match terminated {
+                    Some(len) => len as u64,
+                    None => {
+                        state.fault.get_or_insert_with(|| {
+                            format!(
+                                "{:?} was passed an unterminated string at {:#x}",
+                                helper, args[0]
+                            )
+                        });
+                        u64::MAX
+                    }
+                }
+            }
+            HelperFunctionID::BPF_SET_RETURN_BUFFER_IDX if args[0] != 0 => {
+                // Same as the firmware, the buffers exceeding the limit are rejected.
+                let len = args[1] as usize;
+                if len > MAX_RETURN_BUFFER_SIZE {
+                    u64::MAX
+                } else if state.guest_region(helper, args[0], len as u64).is_some() {
+                    let buffer = unsafe { std::slice::from_raw_parts(args[0] as *const u8, len) };
+                    state.return_buffer = Some(buffer.to_vec());
+                    0
+                } else {
+                    u64::MAX
+                }
+            }
+            _ => 0,
+        }
+    };
+
+    debug!("Stub {:?}{:?} -> {}", helper, args, result);
+    state.calls.push(HelperCall {
+        helper,
+        args,
+        result,
+    });
+    result
+}
+
+/// Generates a separate stub function for each helper, they all forward to
+/// [`call_stub`] which implements the actual behaviour.
+macro_rules! stubs {
+    ($($helper:ident),* $(,)?) => {
+        vec![$(
+            (HelperFunctionID::$helper, {
+                fn stub(a1: u64, a2: u64, a3: u64, a4: u64, a5: u64) -> u64 {
+                    call_stub(HelperFunctionID::$helper, [a1, a2, a3, a4, a5])
+                }
+                stub as fn(u64, u64, u64, u64, u64) -> u64
+            }),
+        )*]
+    };
+}
+
+fn stubs() -> Vec<(HelperFunctionID, fn(u64, u64, u64, u64, u64) -> u64)> {
+    let stubs = stubs![
+        BPF_PRINTF_IDX,
+        BPF_DEBUG_PRINT_IDX,
+        BPF_MEMCPY_IDX,
+        BPF_STORE_LOCAL_IDX,
+        BPF_STORE_GLOBAL_IDX,
+        BPF_FETCH_LOCAL_IDX,
+        BPF_FETCH_GLOBAL_IDX,
+        BPF_SAUL_REG_FIND_NTH_IDX,
+        BPF_SAUL_REG_FIND_TYPE_IDX,
+        BPF_SAUL_REG_READ_IDX,
+        BPF_SAUL_REG_WRITE_IDX,
+        BPF_SAUL_REG_READ_TEMP,
+        BPF_GCOAP_RESP_INIT_IDX,
+        BPF_COAP_OPT_FINISH_IDX,
+        BPF_COAP_ADD_FORMAT_IDX,
+        BPF_COAP_GET_PDU_IDX,
+        BPF_STRLEN_IDX,
+        BPF_FMT_S16_DFP_IDX,
+        BPF_FMT_U32_DEC_IDX,
+        BPF_NOW_MS_IDX,
+        BPF_ZTIMER_NOW_IDX,
+        BPF_PERIODIC_WAKEUP_IDX,
+        BPF_GPIO_READ_INPUT,
+        BPF_GPIO_READ_RAW,
+        BPF_GPIO_WRITE,
+        BPF_HD44780_INIT,
+        BPF_HD44780_CLEAR,
+        BPF_HD44780_PRINT,
+        BPF_HD44780_SET_CURSOR,
+        BPF_KEYPAD_GET_INPUT,
+        BPF_SET_RETURN_BUFFER_IDX,
+    ];
+    debug_assert_eq!(stubs.len(), all::<HelperFunctionID>().count());
+    stubs
+}
+
+/// Parses the `--stub <helper>=<value>` arguments, the helper is specified
+/// using its index (e.g. 0x34 or 52).
+pub fn parse_stub_return_values(
+    specs: &[String],
+) -> Result<BTreeMap<HelperFunctionID, u64>, String> {
+    specs
+        .iter()
+        .map(|spec| {
+            let (helper, value) = spec
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid stub {}, expected <helper>=<value>", spec))?;
+            let index = match helper.strip_prefix("0x") {
+                Some(hex) => u8::from_str_radix(hex, 16),
+                None => helper.parse::<u8>(),
+            }
+            .map_err(|_| format!("Invalid helper index: {}", helper))?;
+            let helper = num::FromPrimitive::from_u8(index)
+                .ok_or_else(|| format!("Unknown helper index: {}", helper))?;
+            let value = value
+                .parse::<u64>()
+                .map_err(|_| format!("Invalid return value: {}", value))?;
+            Ok((helper, value))
+        })
+        .collect()
+}
+
+/// Parses the `--region <name>:<offset>:<length>` arguments declaring the
+/// parts of the context, e.g. --region header:0:4
+pub fn parse_memory_regions(specs: &[String]) -> Result<Vec<MemoryRegion>, String> {
+    specs
+        .iter()
+        .map(|spec| {
+            let parts = spec.split(':').collect::<Vec<&str>>();
+            let [name, start, len] = parts[..] else {
+                return Err(format!(
+                    "Invalid region {}, expected <name>:<offset>:<length>",
+                    spec
+                ));
+            };
+            let parse = |value: &str| {
+                value
+                    .parse::<u64>()
+                    .map_err(|_| format!("Invalid region bound {} in {}", value, spec))
+            };
+            Ok(MemoryRegion {
+                name: name.to_string(),
+                start: parse(start)?,
+                len: parse(len)?,
+            })
+        })
+        .collect()
+}
+
+/// Loads the scripted behaviour of the helpers from a TOML scenario file.
+/// The helpers are named the same as in the helper profiles, e.g.:
+///
+/// ```toml
+/// [helpers.saul_reg_read_temp]
+/// values = [2150, 2175, 2200]
+/// repeat = true
+///
+/// [helpers.now_ms]
+/// start = 1000
+/// step = 100
+///
+/// [helpers.gpio_read_input]
+/// value = 1
+/// ```
+pub fn load_scenario(path: &str) -> Result<BTreeMap<HelperFunctionID, HelperScript>, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read the scenario {}: {}", path, e))?;
+    let scenario = toml::from_str::<Scenario>(&contents)
+        .map_err(|e| format!("Failed to parse the scenario {}: {}", path, e))?;
+
+    scenario
+        .helpers
+        .into_iter()
+        .map(|(name, entry)| {
+            let helper = num::FromPrimitive::from_u8(parse_helper(&name)?)
+                .ok_or_else(|| format!("Unknown helper: {}", name))?;
+            let script = match (entry.value, entry.values, entry.start) {
+                (Some(value), None, None) => HelperScript::Constant(value),
+                (None, Some(values), None) if !values.is_empty() => HelperScript::Sequence {
+                    values,
+                    repeat: entry.repeat,
+                },
+                (None, None, Some(start)) => HelperScript::Counter {
+                    start,
+                    step: entry.step,
+                },
+                _ => {
+                    return Err(format!(
+                        "Invalid script of {} in {}, expected exactly one of value, values (non-empty) or start",
+                        name, path
+                    ))
+                }
+            };
+            Ok((helper, script))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, io, process};
+
+    use super::*;
+
+    fn write_scenario(name: &str, contents: &str) -> String {
+        let path = env::temp_dir().join(format!("mibpf-scenario-{}-{}.toml", process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn memory_violation_is_parsed_from_the_rbpf_error() {
+        // Formatted the same way as the bounds check of rbpf and the error
+        // handling of `simulate`.
+        let error = format!(
+            "{:?}",
+            io::Error::other(
+                format!(
+                    "Error: out of bounds memory {} (insn #{:?}), addr {:#x}, size {:?}\nmbuff: {:#x}/{:#x}, mem: {:#x}/{:#x}, stack: {:#x}/{:#x}",
+                    "store", 13, 0x7ffd3a2c1044u64, 4, 0x1, 0, 0x7ffd3a2c1000u64, 0x40,
+                    0x7ffd3a2c0e00u64, 0x200
+                )
+            )
+        );
+        let mut text = [[0xb7, 0, 0, 0, 0, 0, 0, 0]; 8];
+        text[7] = [0x63, 0x21, 0x44, 0, 0, 0, 0, 0];
+
+        let violation = parse_memory_violation(&error, &text.concat()).unwrap();
+        assert_eq!(violation.kind, AccessKind::Store);
+        assert_eq!(violation.instruction, 7);
+        assert_eq!(violation.disassembly.as_deref(), Some("stxw [r1+68], r2"));
+        assert_eq!(violation.address, 0x7ffd3a2c1044);
+        assert_eq!(violation.size, 4);
+        assert_eq!(
+            violation.regions,
+            vec![
+                MemoryRegion {
+                    name: "context".to_string(),
+                    start: 0x7ffd3a2c1000,
+                    len: 0x40,
+                },
+                MemoryRegion {
+                    name: "stack".to_string(),
+                    start: 0x7ffd3a2c0e00,
+                    len: 0x200,
+                },
+            ]
+        );
+        assert_eq!(
+            violation.location().as_deref(),
+            Some("offset 68 of context, 4 bytes past its end (64 bytes)")
+        );
+
+        assert!(parse_memory_violation("Error: division by 0 (insn #3)", &[]).is_none());
+    }
+
+    #[test]
+    fn scripts_return_the_results_of_consecutive_calls() {
+        let sequence = |repeat| HelperScript::Sequence {
+            values: vec![1, 2, 3],
+            repeat,
+        };
+        let results =
+            |script: HelperScript| (0..5).map(|call| script.result(call)).collect::<Vec<_>>();
+
+        assert_eq!(results(HelperScript::Constant(7)), vec![7; 5]);
+        assert_eq!(results(sequence(false)), vec![1, 2, 3, 3, 3]);
+        assert_eq!(results(sequence(true)), vec![1, 2, 3, 1, 2]);
+        assert_eq!(
+            results(HelperScript::Counter {
+                start: 1000,
+                step: 100
+            }),
+            vec![1000, 1100, 1200, 1300, 1400]
+        );
+        let wrapping = HelperScript::Counter {
+            start: u64::MAX,
+            step: 1,
+        };
+        assert_eq!(wrapping.result(1), 0);
+    }
+
+    #[test]
+    fn scenario_is_loaded() {
+        let path = write_scenario(
+            "valid",
+            "[helpers.now_ms]\nstart = 1000\nstep = 100\n\n[helpers.gpio_read_input]\nvalues = [0, 1]\nrepeat = true\n",
+        );
+        let scripts = load_scenario(&path).unwrap();
+        assert_eq!(
+            scripts.get(&HelperFunctionID::BPF_NOW_MS_IDX),
+            Some(&HelperScript::Counter {
+                start: 1000,
+                step: 100
+            })
+        );
+        assert_eq!(
+            scripts.get(&HelperFunctionID::BPF_GPIO_READ_INPUT),
+            Some(&HelperScript::Sequence {
+                values: vec![0, 1],
+                repeat: true
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_scenario_entries_are_rejected() {
+        let scenarios = [
+            ("both", "[helpers.now_ms]\nvalue = 1\nstart = 1000\n"),
+            ("none", "[helpers.now_ms]\nstep = 100\n"),
+            ("empty", "[helpers.now_ms]\nvalues = []\n"),
+            ("field", "[helpers.now_ms]\nvalue = 1\nvalu = 2\n"),
+            ("helper", "[helpers.no_such_helper]\nvalue = 1\n"),
+        ];
+        for (name, contents) in scenarios {
+            assert!(
+                load_scenario(&write_scenario(name, contents)).is_err(),
+                "{}",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,454 @@
+use std::{
+    fs,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use log::{debug, info, warn};
+use micro_bpf_common::StorageBackend;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    registry::{Registry, HAMMERING_THRESHOLD},
+    transport::{is_cancelled, send_request, TransportOptions},
+};
+
+/// How long the deployment waits for the device to fetch and install the
+/// update once it has accepted the pull request.
+const INSTALLATION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Time between two consecutive checks whether the update was installed.
+const INSTALLATION_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Information about a single SUIT storage slot reported by the device.
+#[derive(Deserialize, Debug, Clone)]
+pub struct SlotInfo {
+    pub slot: usize,
+    /// Size of the program stored in the slot, 0 if the slot is free.
+    pub size: usize,
+    /// Metadata embedded in the program binary (see
+    /// [`micro_bpf_elf_utils::ProgramMetadata`]), only reported for programs
+    /// deployed with `--embed-metadata`.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Hex-encoded build hash of the program.
+    #[serde(default)]
+    pub build_hash: Option<String>,
+    /// Number of times the flash pages of the slot were erased, only reported
+    /// by the firmware which keeps track of it.
+    #[serde(default)]
+    pub erase_cycles: Option<u64>,
+    /// Storage backend of the slot, the firmware without the flash regions
+    /// doesn't report it.
+    #[serde(default)]
+    pub storage: StorageBackend,
+    /// Sequence number of the manifest which installed the program (0 if the
+    /// slot was never written to), only reported by the firmware which keeps
+    /// track of it.
+    #[serde(default)]
+    pub sequence_number: Option<u64>,
+}
+
+impl SlotInfo {
+    pub fn is_free(&self) -> bool {
+        self.size == 0
+    }
+}
+
+/// Controls how the SUIT storage slot is chosen when deploying with `--slot auto`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocationPolicy {
+    /// Picks the first slot that doesn't contain a program.
+    FirstFree,
+    /// Picks the first free slot, if all slots are occupied, the one that was
+    /// written to the longest time ago (according to the local registry) gets
+    /// overwritten.
+    LeastRecentlyUsed,
+}
+
+/// Specifies the SUIT storage slot that the program should be deployed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuitSlot {
+    Fixed(usize),
+    Auto(AllocationPolicy),
+}
+
+impl FromStr for SuitSlot {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(SuitSlot::Auto(AllocationPolicy::FirstFree)),
+            "auto:lru" => Ok(SuitSlot::Auto(AllocationPolicy::LeastRecentlyUsed)),
+            _ => s
+                .parse::<usize>()
+                .map(SuitSlot::Fixed)
+                .map_err(|_| format!("Invalid SUIT storage slot: {}", s)),
+        }
+    }
+}
+
+/// Queries the device for the occupancy of its SUIT storage slots.
+///
+/// The device is expected to respond with a JSON list of the form:
+/// `[{"slot": 0, "size": 412, "name": "counter", "version": "1.0.0",
+/// "build_hash": "9f1c2a0b3d4e5f60", "erase_cycles": 118, "storage": "flash",
+/// "sequence_number": 1718000000}, {"slot": 1, "size": 0, "storage": "ram",
+/// "sequence_number": 0}]`
+pub fn query_slots(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    transport: &TransportOptions,
+) -> Result<Vec<SlotInfo>, String> {
+    let url = format!(
+        "coap://[{}%{}]/suit/slots",
+        riot_ipv6_addr, host_network_interface
+    );
+    debug!("Querying SUIT storage slots: {}", url);
+
+    let output = send_request("GET", &url, None, transport)?;
+
+    if output.stderr.len() > 0 {
+        let stderr = String::from_utf8(output.stderr)
+            .map_err(|e| format!("Failed to parse the stderr: {}", e))?;
+        Err(format!("aiocoap-client failed with: {}", stderr))?
+    }
+
+    let response = String::from_utf8(output.stdout)
+        .map_err(|e| format!("Failed to parse the response: {}", e))?;
+
+    serde_json::from_str::<Vec<SlotInfo>>(&response)
+        .map_err(|e| format!("Failed to parse the slot listing: {}", e))
+}
+
+/// Resolves the SUIT storage slot that the program should be deployed to.
+pub fn allocate_slot(
+    slot: SuitSlot,
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    transport: &TransportOptions,
+) -> Result<usize, String> {
+    let policy = match slot {
+        SuitSlot::Fixed(slot) => return Ok(slot),
+        SuitSlot::Auto(policy) => policy,
+    };
+
+    if transport.dry_run {
+        let _ = query_slots(riot_ipv6_addr, host_network_interface, transport);
+        info!("[dry-run] assuming that slot 0 is free");
+        return Ok(0);
+    }
+
+    let slots = query_slots(riot_ipv6_addr, host_network_interface, transport)?;
+
+    if let Some(free) = slots.iter().find(|s| s.is_free()) {
+        debug!("Allocated free slot: {}", free.slot);
+        return Ok(free.slot);
+    }
+
+    match policy {
+        AllocationPolicy::FirstFree => Err("All SUIT storage slots are occupied".to_string()),
+        AllocationPolicy::LeastRecentlyUsed => {
+            let registry = Registry::load()?;
+            let device = registry.device(riot_ipv6_addr);
+            // Slots that aren't in the registry were written by someone else,
+            // we treat them as the oldest ones.
+            let last_used = |slot: usize| {
+                device
+                    .and_then(|d| d.slots.get(&slot))
+                    .map(|r| r.deployed_at)
+                    .unwrap_or(0)
+            };
+            slots
+                .iter()
+                .map(|s| s.slot)
+                .min_by_key(|slot| last_used(*slot))
+                .ok_or_else(|| "The device doesn't report any SUIT storage slots".to_string())
+        }
+    }
+}
+
+/// Usage of a single SUIT storage slot combining the deployments recorded
+/// by the local registry with the erase-cycle counter reported by the device.
+#[derive(Debug, Clone)]
+pub struct SlotStats {
+    pub slot: usize,
+    /// Program deployed into the slot the last time, None if the registry
+    /// doesn't know about any deployment.
+    pub program: Option<String>,
+    pub deployments: u64,
+    /// Deployments within the last [`crate::registry::HAMMERING_WINDOW`].
+    pub recent_deployments: usize,
+    pub last_deployed_at: Option<u64>,
+    pub erase_cycles: Option<u64>,
+}
+
+impl SlotStats {
+    pub fn is_hammered(&self) -> bool {
+        self.recent_deployments >= HAMMERING_THRESHOLD
+    }
+}
+
+/// Collects the usage statistics of the slots of the device. The slots
+/// reported by the device are listed even if they were never written to
+/// through the tool, `slots` is empty if the device couldn't be queried.
+pub fn slot_stats(registry: &Registry, device_ip: &str, slots: &[SlotInfo]) -> Vec<SlotStats> {
+    let records = registry.device(device_ip).map(|d| &d.slots);
+    let mut indices = slots.iter().map(|s| s.slot).collect::<Vec<usize>>();
+    indices.extend(records.into_iter().flat_map(|r| r.keys().copied()));
+    indices.sort();
+    indices.dedup();
+
+    indices
+        .into_iter()
+        .map(|slot| {
+            let record = records.and_then(|r| r.get(&slot));
+            SlotStats {
+                slot,
+                program: record.map(|r| r.program.clone()),
+                deployments: record.map(|r| r.deployments).unwrap_or(0),
+                recent_deployments: record.map(|r| r.recent_deployment_count()).unwrap_or(0),
+                last_deployed_at: record.map(|r| r.deployed_at),
+                erase_cycles: slots
+                    .iter()
+                    .find(|s| s.slot == slot)
+                    .and_then(|s| s.erase_cycles),
+            }
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct SlotDigest {
+    sha256: String,
+}
+
+/// Checks that the device stored exactly the artifact that was deployed. The
+/// device responds to `GET /suit/digest?slot=<slot>` with the SHA-256 digest
+/// of the program stored in the slot: `{"sha256": "9f86d081884c7d65..."}`.
+/// A mismatch means that the slot still contains a stale program, e.g.
+/// because the update was rejected without the error reaching the client.
+/// Firmware which doesn't provide the digest only produces a warning.
+pub fn verify_stored_artifact(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    suit_storage_slot: usize,
+    artifact: &str,
+    transport: &TransportOptions,
+) -> Result<(), String> {
+    let bytes =
+        fs::read(artifact).map_err(|e| format!("Failed to read the artifact {}: {}", artifact, e))?;
+    let expected = format!("{:x}", Sha256::digest(&bytes));
+
+    let url = format!(
+        "coap://[{}%{}]/suit/digest?slot={}",
+        riot_ipv6_addr, host_network_interface, suit_storage_slot
+    );
+    let output = send_request("GET", &url, None, transport)?;
+    if output.stderr.len() > 0 {
+        warn!(
+            "Unable to verify the program stored in slot {}, the device didn't report its digest: {}",
+            suit_storage_slot,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Ok(());
+    }
+
+    let response = String::from_utf8(output.stdout)
+        .map_err(|e| format!("Failed to parse the response: {}", e))?;
+    let digest = serde_json::from_str::<SlotDigest>(response.trim_matches(char::from(0)))
+        .map_err(|e| format!("Failed to parse the digest response: {}", e))?;
+
+    if !digest.sha256.eq_ignore_ascii_case(&expected) {
+        return Err(format!(
+            "The program stored in slot {} doesn't match the deployed artifact {} \
+             (device: {}, local: {}), the device is most likely running a stale program",
+            suit_storage_slot, artifact, digest.sha256, expected
+        ));
+    }
+    debug!("Slot {} contains {} ({})", suit_storage_slot, artifact, expected);
+    Ok(())
+}
+
+/// Waits until the device has installed the update with the given sequence
+/// number into the slot. The device only accepts the pull request and then
+/// fetches and installs the update in the background, until it is done the
+/// slot still contains the previous program.
+///
+/// Returns false if the installation can't be observed because the firmware
+/// doesn't report the sequence numbers of the slots (see
+/// [`SlotInfo::sequence_number`]).
+pub async fn wait_for_installation(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    suit_storage_slot: usize,
+    sequence_number: u64,
+    transport: &TransportOptions,
+) -> Result<bool, String> {
+    let deadline = Instant::now() + INSTALLATION_TIMEOUT;
+    let mut listed = false;
+    loop {
+        transport.check_cancelled()?;
+        match query_slots(riot_ipv6_addr, host_network_interface, transport) {
+            Ok(slots) => {
+                listed = true;
+                let installed = slots
+                    .iter()
+                    .find(|s| s.slot == suit_storage_slot)
+                    .map(|s| s.sequence_number);
+                match installed {
+                    Some(Some(installed)) if installed == sequence_number => return Ok(true),
+                    Some(Some(installed)) => debug!(
+                        "Slot {} still contains the update {}, waiting for {}",
+                        suit_storage_slot, installed, sequence_number
+                    ),
+                    _ => return Ok(false),
+                }
+            }
+            Err(e) if is_cancelled(&e) => return Err(e),
+            // The device might not respond while it is installing the update.
+            Err(e) => debug!("Failed to query the slots: {}", e),
+        }
+
+        if Instant::now() >= deadline {
+            if !listed {
+                warn!(
+                    "Unable to confirm the installation of the update, the device                      didn't report its slots"
+                );
+                return Ok(false);
+            }
+            return Err(format!(
+                "The device didn't install the update {} into slot {} within {:?}",
+                sequence_number, suit_storage_slot, INSTALLATION_TIMEOUT
+            ));
+        }
+        tokio::time::sleep(INSTALLATION_POLL_INTERVAL).await;
+    }
+}
+
+/// Verifies the program stored in the slot (see [`verify_stored_artifact`])
+/// once the device has installed the update, see [`wait_for_installation`].
+/// If the firmware doesn't report the sequence numbers, the digest of the
+/// slot is checked repeatedly until it matches the artifact instead.
+pub async fn verify_installed_artifact(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    suit_storage_slot: usize,
+    sequence_number: u64,
+    artifact: &str,
+    transport: &TransportOptions,
+) -> Result<(), String> {
+    let installed = wait_for_installation(
+        riot_ipv6_addr,
+        host_network_interface,
+        suit_storage_slot,
+        sequence_number,
+        transport,
+    )
+    .await?;
+    let deadline = Instant::now() + INSTALLATION_TIMEOUT;
+    loop {
+        let verified = verify_stored_artifact(
+            riot_ipv6_addr,
+            host_network_interface,
+            suit_storage_slot,
+            artifact,
+            transport,
+        );
+        match verified {
+            Err(e) if !installed && !is_cancelled(&e) && Instant::now() < deadline => {
+                debug!("The update isn't installed yet: {}", e);
+                tokio::time::sleep(INSTALLATION_POLL_INTERVAL).await;
+            }
+            verified => return verified,
+        }
+    }
+}
+
+/// Downloads the raw contents of the SUIT storage slot, e.g. to capture the
+/// programs of a reference device. The device responds to
+/// `GET /suit/slot?slot=<slot>` with the bytes stored in the slot (using
+/// block-wise transfer for larger programs). Firmware which doesn't allow
+/// reading the slots back responds with 4.04 or 4.05.
+pub fn backup_slot(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    suit_storage_slot: usize,
+    transport: &TransportOptions,
+) -> Result<Vec<u8>, String> {
+    let url = format!(
+        "coap://[{}%{}]/suit/slot?slot={}",
+        riot_ipv6_addr, host_network_interface, suit_storage_slot
+    );
+    debug!(
+        "Downloading the contents of slot {}: {}",
+        suit_storage_slot, url
+    );
+
+    let output = send_request("GET", &url, None, transport)?;
+    if output.stderr.len() > 0 {
+        return Err(format!(
+            "Failed to read slot {}, the firmware might not permit reading the \
+             slots back: {}",
+            suit_storage_slot,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    // The response is binary, so unlike in the case of the JSON responses
+    // we can't trim the trailing null bytes.
+    Ok(output.stdout)
+}
+
+/// Uploads the raw contents of a slot captured using [`backup_slot`] into
+/// the SUIT storage slot of the device using `PUT /suit/slot?slot=<slot>`.
+/// The upload bypasses the SUIT update procedure, because of this the stored
+/// program is verified against the uploaded file afterwards (see
+/// [`verify_stored_artifact`]).
+pub fn restore_slot(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    suit_storage_slot: usize,
+    backup_file: &str,
+    transport: &TransportOptions,
+) -> Result<(), String> {
+    let size = fs::metadata(backup_file)
+        .map_err(|e| format!("Failed to read the backup {}: {}", backup_file, e))?
+        .len();
+    if size == 0 {
+        return Err(format!("The backup {} is empty", backup_file));
+    }
+
+    let url = format!(
+        "coap://[{}%{}]/suit/slot?slot={}",
+        riot_ipv6_addr, host_network_interface, suit_storage_slot
+    );
+    debug!(
+        "Uploading {} ({} bytes) into slot {}: {}",
+        backup_file, size, suit_storage_slot, url
+    );
+
+    let payload = format!("@{}", backup_file);
+    let output = send_request("PUT", &url, Some(&payload), transport)?;
+    if output.stderr.len() > 0 {
+        return Err(format!(
+            "Failed to write slot {}, the firmware might not permit writing the \
+             slots directly: {}",
+            suit_storage_slot,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    if transport.dry_run {
+        return Ok(());
+    }
+    verify_stored_artifact(
+        riot_ipv6_addr,
+        host_network_interface,
+        suit_storage_slot,
+        backup_file,
+        transport,
+    )
+}
@@ -0,0 +1,312 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{debug, warn};
+use micro_bpf_common::{
+    BinaryFileLayout, HelperAccessListSource, HelperAccessVerification, TargetVM,
+};
+
+use crate::{
+    ratelimit::RateLimits,
+    request::build_execution_request,
+    transport::{send_request, TransportOptions},
+};
+
+/// Parameters of the stress test, the request rate starts at `start_rate`
+/// and is increased by `rate_step` after each step until it reaches
+/// `max_rate` or the device stops keeping up.
+#[derive(Debug, Clone)]
+pub struct StressConfig {
+    /// Requests per second sent during the first step.
+    pub start_rate: f64,
+    /// Increase of the request rate between the steps.
+    pub rate_step: f64,
+    /// Request rate after which the test stops even if the device keeps up.
+    pub max_rate: f64,
+    /// How long the requests are sent at each rate.
+    pub step_duration: Duration,
+    /// Fraction of the failed requests above which the rate isn't
+    /// considered sustainable.
+    pub error_threshold: f64,
+    /// Number of failures in a row after which no more requests are sent, so
+    /// that the firmware isn't pushed into a state it can't recover from.
+    pub max_consecutive_failures: usize,
+}
+
+impl Default for StressConfig {
+    fn default() -> Self {
+        StressConfig {
+            start_rate: 1.0,
+            rate_step: 1.0,
+            max_rate: 50.0,
+            step_duration: Duration::from_secs(10),
+            error_threshold: 0.05,
+            max_consecutive_failures: 5,
+        }
+    }
+}
+
+/// Outcome of sending the requests at a single rate.
+#[derive(Debug, Clone)]
+pub struct StressStep {
+    /// Requests per second the device was asked to handle.
+    pub rate: f64,
+    pub sent: usize,
+    pub failed: usize,
+    /// Number of the failed requests of each kind, see [`classify_error`].
+    pub errors: BTreeMap<String, usize>,
+    pub mean_round_trip: Duration,
+}
+
+impl StressStep {
+    pub fn error_rate(&self) -> f64 {
+        if self.sent == 0 {
+            0.0
+        } else {
+            self.failed as f64 / self.sent as f64
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct StressReport {
+    pub steps: Vec<StressStep>,
+    /// Highest rate at which the fraction of the failed requests stayed
+    /// within the threshold.
+    pub max_sustainable_rate: Option<f64>,
+    /// Set if the test was stopped because of the consecutive failures.
+    pub aborted: bool,
+}
+
+/// Sends execution requests for the program in the given SUIT storage slot
+/// at an increasing rate until the errors appear, in order to find out how
+/// many executions per second the scheduler of the firmware can handle.
+///
+/// The requests of each step are sent concurrently at evenly spaced
+/// intervals (the rate limits of the transport are bypassed for that). The
+/// test stops after the first step which exceeds the error threshold, or
+/// immediately after the given number of consecutive failures.
+pub fn run_stress(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    target: TargetVM,
+    binary_layout: BinaryFileLayout,
+    suit_storage_slot: usize,
+    helper_indices: &[u8],
+    jit: bool,
+    config: &StressConfig,
+    transport: &TransportOptions,
+) -> Result<StressReport, String> {
+    if config.start_rate <= 0.0 || config.rate_step <= 0.0 {
+        Err("The request rate and its increase need to be positive")?
+    }
+
+    let url = format!(
+        "coap://[{}%{}]/short-execution",
+        riot_ipv6_addr, host_network_interface
+    );
+    let payload = build_execution_request(
+        target,
+        binary_layout,
+        suit_storage_slot,
+        HelperAccessVerification::AheadOfTime,
+        HelperAccessListSource::ExecuteRequest,
+        helper_indices,
+        jit,
+        jit,
+    )
+    .encode();
+    let transport = TransportOptions {
+        rate_limits: RateLimits::default(),
+        ..transport.clone()
+    };
+
+    let mut steps = vec![];
+    let mut max_sustainable_rate = None;
+    let mut aborted = false;
+    let mut rate = config.start_rate;
+    while rate <= config.max_rate {
+        debug!("Stress testing at {:.2} requests per second", rate);
+        let (step, step_aborted) = run_step(&url, &payload, rate, config, &transport)?;
+        debug!(
+            "{:.2} requests per second: {} sent, {} failed",
+            step.rate, step.sent, step.failed
+        );
+
+        let sustainable = !step_aborted && step.error_rate() <= config.error_threshold;
+        if sustainable {
+            max_sustainable_rate = Some(rate);
+        }
+        steps.push(step);
+
+        if step_aborted {
+            warn!(
+                "{} consecutive requests failed, stopping the stress test",
+                config.max_consecutive_failures
+            );
+            aborted = true;
+            break;
+        }
+        if !sustainable {
+            break;
+        }
+        rate += config.rate_step;
+    }
+
+    Ok(StressReport {
+        steps,
+        max_sustainable_rate,
+        aborted,
+    })
+}
+
+/// Sends the requests at the given rate for the duration of the step.
+/// Returns the outcome and whether the step was aborted.
+fn run_step(
+    url: &str,
+    payload: &str,
+    rate: f64,
+    config: &StressConfig,
+    transport: &TransportOptions,
+) -> Result<(StressStep, bool), String> {
+    let interval = Duration::from_secs_f64(1.0 / rate);
+    let count = (config.step_duration.as_secs_f64() * rate).ceil().max(1.0) as usize;
+
+    let consecutive_failures = AtomicUsize::new(0);
+    let aborted = AtomicBool::new(false);
+    let outcomes = Mutex::new(vec![]);
+
+    thread::scope(|scope| {
+        let start = Instant::now();
+        for i in 0..count {
+            if aborted.load(Ordering::SeqCst) || transport.check_cancelled().is_err() {
+                break;
+            }
+            let due = start + interval * i as u32;
+            thread::sleep(due.saturating_duration_since(Instant::now()));
+
+            let (consecutive_failures, aborted, outcomes) =
+                (&consecutive_failures, &aborted, &outcomes);
+            scope.spawn(move || {
+                let sent_at = Instant::now();
+                let outcome = send_request("POST", url, Some(payload), transport)
+                    .and_then(check_response)
+                    .map_err(|e| classify_error(&e));
+                let round_trip = sent_at.elapsed();
+
+                if outcome.is_ok() {
+                    consecutive_failures.store(0, Ordering::SeqCst);
+                } else if consecutive_failures.fetch_add(1, Ordering::SeqCst) + 1
+                    >= config.max_consecutive_failures
+                {
+                    aborted.store(true, Ordering::SeqCst);
+                }
+                outcomes.lock().unwrap().push((outcome, round_trip));
+            });
+        }
+    });
+    transport.check_cancelled()?;
+
+    let outcomes = outcomes.into_inner().unwrap();
+    let mut errors = BTreeMap::new();
+    for (outcome, _) in &outcomes {
+        if let Err(kind) = outcome {
+            *errors.entry(kind.clone()).or_insert(0) += 1;
+        }
+    }
+    let succeeded = outcomes
+        .iter()
+        .filter(|(outcome, _)| outcome.is_ok())
+        .map(|(_, round_trip)| *round_trip)
+        .collect::<Vec<Duration>>();
+    let mean_round_trip = if succeeded.is_empty() {
+        Duration::ZERO
+    } else {
+        succeeded.iter().sum::<Duration>() / succeeded.len() as u32
+    };
+
+    let step = StressStep {
+        rate,
+        sent: outcomes.len(),
+        failed: outcomes.len() - succeeded.len(),
+        errors,
+        mean_round_trip,
+    };
+    Ok((step, aborted.into_inner()))
+}
+
+fn check_response(output: std::process::Output) -> Result<(), String> {
+    if output.stderr.len() > 0 || !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(format!("aiocoap-client failed with: {}", stderr))?
+    }
+    Ok(())
+}
+
+/// Groups the errors into kinds so that they can be counted, e.g. all
+/// timeouts are reported together regardless of the request that timed out.
+/// The CoAP error responses (e.g. `5.03 Service Unavailable`) are grouped by
+/// their response code.
+fn classify_error(error: &str) -> String {
+    let lowercase = error.to_lowercase();
+    if lowercase.contains("timeout") || lowercase.contains("timed out") {
+        return "timeout".to_string();
+    }
+    if let Some(code) = error.split_whitespace().find(|word| is_response_code(word)) {
+        return code.to_string();
+    }
+    if lowercase.contains("failed to send the request") {
+        return "client".to_string();
+    }
+    "other".to_string()
+}
+
+fn is_response_code(word: &str) -> bool {
+    let bytes = word.as_bytes();
+    bytes.len() == 4
+        && matches!(bytes[0], b'4' | b'5')
+        && bytes[1] == b'.'
+        && bytes[2].is_ascii_digit()
+        && bytes[3].is_ascii_digit()
+}
+
+impl fmt::Display for StressReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            write!(
+                f,
+                "{:8.2} req/s: {:4} sent, {:4} failed ({:.1}%), mean round trip: {}ms",
+                step.rate,
+                step.sent,
+                step.failed,
+                step.error_rate() * 100.0,
+                step.mean_round_trip.as_millis()
+            )?;
+            if !step.errors.is_empty() {
+                let errors = step
+                    .errors
+                    .iter()
+                    .map(|(kind, count)| format!("{}: {}", kind, count))
+                    .collect::<Vec<String>>();
+                write!(f, " [{}]", errors.join(", "))?;
+            }
+            writeln!(f)?;
+        }
+        match self.max_sustainable_rate {
+            Some(rate) => write!(f, "Max sustainable rate: {:.2} req/s", rate)?,
+            None => write!(f, "Max sustainable rate: none, the first step failed")?,
+        }
+        if self.aborted {
+            write!(f, "\nAborted after consecutive failures")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,456 @@
+use std::{env, fs, path::PathBuf};
+
+use ciborium::value::{Integer, Value};
+use ed25519_dalek::{pkcs8::DecodePrivateKey, Signer, SigningKey};
+use log::debug;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::{compression::ArtifactCompression, keys::KeyStore};
+
+// Keys and identifiers of the SUIT manifest format (RFC 9124 and
+// draft-ietf-suit-manifest), the same subset as the one emitted by the RIOT
+// suit-tool and understood by the SUIT worker of the devices.
+const CBOR_TAG_SUIT_ENVELOPE: u64 = 107;
+const CBOR_TAG_COSE_SIGN1: u64 = 18;
+
+const SUIT_AUTHENTICATION_WRAPPER: i64 = 2;
+const SUIT_MANIFEST: i64 = 3;
+
+const SUIT_MANIFEST_VERSION: i64 = 1;
+const SUIT_MANIFEST_SEQUENCE_NUMBER: i64 = 2;
+const SUIT_COMMON: i64 = 3;
+const SUIT_VALIDATE: i64 = 7;
+const SUIT_INSTALL: i64 = 17;
+
+const SUIT_COMPONENTS: i64 = 2;
+const SUIT_COMMON_SEQUENCE: i64 = 4;
+
+const SUIT_CONDITION_VENDOR_IDENTIFIER: i64 = 1;
+const SUIT_CONDITION_CLASS_IDENTIFIER: i64 = 2;
+const SUIT_CONDITION_IMAGE_MATCH: i64 = 3;
+const SUIT_DIRECTIVE_SET_COMPONENT_INDEX: i64 = 12;
+const SUIT_DIRECTIVE_OVERRIDE_PARAMETERS: i64 = 20;
+const SUIT_DIRECTIVE_FETCH: i64 = 21;
+
+const SUIT_PARAMETER_VENDOR_IDENTIFIER: i64 = 1;
+const SUIT_PARAMETER_CLASS_IDENTIFIER: i64 = 2;
+const SUIT_PARAMETER_IMAGE_DIGEST: i64 = 3;
+const SUIT_PARAMETER_IMAGE_SIZE: i64 = 14;
+const SUIT_PARAMETER_COMPRESSION_INFO: i64 = 19;
+const SUIT_PARAMETER_URI: i64 = 21;
+
+const SUIT_COMPRESSION_ALGORITHM: i64 = 1;
+const SUIT_COMPRESSION_ALGORITHM_ZLIB: i64 = 1;
+const SUIT_COMPRESSION_ALGORITHM_ZSTD: i64 = 3;
+
+/// Reporting policy of the conditions, the failures are reported.
+const SUIT_REPORTING_POLICY: i64 = 15;
+
+const COSE_ALGORITHM: i64 = 1;
+const COSE_ALGORITHM_SHA256: i64 = -16;
+const COSE_ALGORITHM_EDDSA: i64 = -8;
+
+/// Vendor name of the RIOT SUIT implementation, its UUID is the vendor
+/// identifier checked by the devices.
+const SUIT_VENDOR: &str = "riot-os.org";
+
+/// UUID of the DNS namespace (RFC 4122).
+const NAMESPACE_DNS: [u8; 16] = [
+    0x6b, 0xa7, 0xb8, 0x10, 0x9d, 0xad, 0x11, 0xd1, 0x80, 0xb4, 0x00, 0xc0, 0x4f, 0xd4, 0x30, 0xc8,
+];
+
+/// Payload delivered by the manifest.
+#[derive(Debug, Clone)]
+pub struct SuitPayload {
+    /// URI that the device fetches the payload from.
+    pub uri: String,
+    /// Identifies the storage location on the device, e.g. `ram:0` (see
+    /// [`micro_bpf_common::StorageBackend::component_id`]).
+    pub component_id: String,
+    /// Uncompressed contents, the digest and the size in the manifest
+    /// describe the installed image.
+    pub contents: Vec<u8>,
+    /// Compression of the payload served at the URI, the device decompresses
+    /// it while fetching.
+    pub compression: Option<ArtifactCompression>,
+}
+
+/// Builds the CBOR-encoded SUIT manifest which installs the payloads. If the
+/// board is given, the devices check that the manifest was built for them
+/// using the vendor and class identifiers derived the same way as in RIOT.
+pub fn build_manifest(
+    payloads: &[SuitPayload],
+    sequence_number: u64,
+    board: Option<&str>,
+) -> Vec<u8> {
+    let components = payloads
+        .iter()
+        .map(|payload| {
+            Value::Array(
+                payload
+                    .component_id
+                    .split(':')
+                    .map(|part| Value::Bytes(part.as_bytes().to_vec()))
+                    .collect(),
+            )
+        })
+        .collect();
+
+    let mut common_sequence = vec![];
+    let mut install = vec![];
+    let mut validate = vec![];
+    for (index, payload) in payloads.iter().enumerate() {
+        let mut parameters = vec![];
+        let mut conditions = vec![];
+        if let Some(board) = board {
+            let vendor_id = uuid5(&NAMESPACE_DNS, SUIT_VENDOR);
+            let class_id = uuid5(&vendor_id, board);
+            parameters.push((
+                int(SUIT_PARAMETER_VENDOR_IDENTIFIER),
+                Value::Bytes(vendor_id.to_vec()),
+            ));
+            parameters.push((
+                int(SUIT_PARAMETER_CLASS_IDENTIFIER),
+                Value::Bytes(class_id.to_vec()),
+            ));
+            conditions.push(SUIT_CONDITION_VENDOR_IDENTIFIER);
+            conditions.push(SUIT_CONDITION_CLASS_IDENTIFIER);
+        }
+        parameters.push((
+            int(SUIT_PARAMETER_IMAGE_DIGEST),
+            Value::Bytes(encode(&digest(&payload.contents))),
+        ));
+        parameters.push((
+            int(SUIT_PARAMETER_IMAGE_SIZE),
+            int(payload.contents.len() as i64),
+        ));
+
+        common_sequence.extend([
+            int(SUIT_DIRECTIVE_SET_COMPONENT_INDEX),
+            int(index as i64),
+            int(SUIT_DIRECTIVE_OVERRIDE_PARAMETERS),
+            Value::Map(parameters),
+        ]);
+        for condition in conditions {
+            common_sequence.extend([int(condition), int(SUIT_REPORTING_POLICY)]);
+        }
+
+        let mut fetch_parameters =
+            vec![(int(SUIT_PARAMETER_URI), Value::Text(payload.uri.clone()))];
+        if let Some(compression) = payload.compression {
+            let algorithm = match compression {
+                ArtifactCompression::Deflate => SUIT_COMPRESSION_ALGORITHM_ZLIB,
+                ArtifactCompression::Zstd => SUIT_COMPRESSION_ALGORITHM_ZSTD,
+            };
+            fetch_parameters.push((
+                int(SUIT_PARAMETER_COMPRESSION_INFO),
+                Value::Bytes(encode(&Value::Map(vec![(
+                    int(SUIT_COMPRESSION_ALGORITHM),
+                    int(algorithm),
+                )]))),
+            ));
+        }
+        install.extend([
+            int(SUIT_DIRECTIVE_SET_COMPONENT_INDEX),
+            int(index as i64),
+            int(SUIT_DIRECTIVE_OVERRIDE_PARAMETERS),
+            Value::Map(fetch_parameters),
+            int(SUIT_DIRECTIVE_FETCH),
+            int(SUIT_REPORTING_POLICY),
+            int(SUIT_CONDITION_IMAGE_MATCH),
+            int(SUIT_REPORTING_POLICY),
+        ]);
+        validate.extend([
+            int(SUIT_DIRECTIVE_SET_COMPONENT_INDEX),
+            int(index as i64),
+            int(SUIT_CONDITION_IMAGE_MATCH),
+            int(SUIT_REPORTING_POLICY),
+        ]);
+    }
+
+    let common = Value::Map(vec![
+        (int(SUIT_COMPONENTS), Value::Array(components)),
+        (
+            int(SUIT_COMMON_SEQUENCE),
+            Value::Bytes(encode(&Value::Array(common_sequence))),
+        ),
+    ]);
+    encode(&Value::Map(vec![
+        (int(SUIT_MANIFEST_VERSION), int(1)),
+        (
+            int(SUIT_MANIFEST_SEQUENCE_NUMBER),
+            Value::Integer(Integer::from(sequence_number)),
+        ),
+        (int(SUIT_COMMON), Value::Bytes(encode(&common))),
+        (
+            int(SUIT_VALIDATE),
+            Value::Bytes(encode(&Value::Array(validate))),
+        ),
+        (
+            int(SUIT_INSTALL),
+            Value::Bytes(encode(&Value::Array(install))),
+        ),
+    ]))
+}
+
+/// Wraps the manifest into a SUIT envelope authenticated by a COSE_Sign1
+/// signature of its digest.
+pub fn sign_manifest(manifest: &[u8], key: &SigningKey) -> Vec<u8> {
+    let manifest_digest = encode(&digest(manifest));
+    let protected = encode(&Value::Map(vec![(
+        int(COSE_ALGORITHM),
+        int(COSE_ALGORITHM_EDDSA),
+    )]));
+    let signature_input = encode(&Value::Array(vec![
+        Value::Text("Signature1".to_string()),
+        Value::Bytes(protected.clone()),
+        Value::Bytes(vec![]),
+        Value::Bytes(manifest_digest.clone()),
+    ]));
+    let signature = key.sign(&signature_input);
+    let cose_sign1 = Value::Tag(
+        CBOR_TAG_COSE_SIGN1,
+        Box::new(Value::Array(vec![
+            Value::Bytes(protected),
+            Value::Map(vec![]),
+            Value::Bytes(manifest_digest.clone()),
+            Value::Bytes(signature.to_bytes().to_vec()),
+        ])),
+    );
+    let authentication_wrapper = Value::Array(vec![
+        Value::Bytes(manifest_digest),
+        Value::Bytes(encode(&cose_sign1)),
+    ]);
+    encode(&Value::Tag(
+        CBOR_TAG_SUIT_ENVELOPE,
+        Box::new(Value::Map(vec![
+            (
+                int(SUIT_AUTHENTICATION_WRAPPER),
+                Value::Bytes(encode(&authentication_wrapper)),
+            ),
+            (int(SUIT_MANIFEST), Value::Bytes(manifest.to_vec())),
+        ])),
+    ))
+}
+
+/// Replaces the signature of the signed envelope, the manifest itself is
+/// left unchanged.
+pub fn resign_envelope(envelope: &[u8], key: &SigningKey) -> Result<Vec<u8>, String> {
+    let value = ciborium::from_reader::<Value, _>(envelope)
+        .map_err(|e| format!("Invalid SUIT envelope: {}", e))?;
+    let Value::Tag(CBOR_TAG_SUIT_ENVELOPE, envelope) = value else {
+        return Err("Invalid SUIT envelope: missing the envelope tag".to_string());
+    };
+    let manifest = envelope
+        .as_map()
+        .and_then(|members| {
+            members
+                .iter()
+                .find(|(member, _)| *member == int(SUIT_MANIFEST))
+                .and_then(|(_, manifest)| manifest.as_bytes())
+        })
+        .ok_or("Invalid SUIT envelope: missing the manifest")?;
+    Ok(sign_manifest(manifest, key))
+}
+
+/// Loads the ed25519 signing key stored in the PKCS#8 PEM format (as
+/// generated by openssl and used by the RIOT build system).
+pub fn load_signing_key(path: &str) -> Result<SigningKey, String> {
+    SigningKey::read_pkcs8_pem_file(path)
+        .map_err(|e| format!("Failed to load the signing key {}: {}", path, e))
+}
+
+/// Returns the key that the manifests are signed with, the active key of the
+/// key store takes precedence over the default key of RIOT.
+pub fn signing_key() -> String {
+    let key_store = KeyStore::new(&KeyStore::default_root());
+    if let Some(key) = key_store.active_key() {
+        debug!("Signing the manifest using the key: {}", key);
+        return key_store.private_key(&key).display().to_string();
+    }
+
+    let data_home = env::var("XDG_DATA_HOME")
+        .unwrap_or_else(|_| format!("{}/.local/share", env::var("HOME").unwrap_or_default()));
+    PathBuf::from(data_home)
+        .join("RIOT/keys/default.pem")
+        .display()
+        .to_string()
+}
+
+/// Builds the manifest delivering the payloads, signs it using the
+/// [`signing_key`] and writes the signed envelope to `output`.
+pub fn write_signed_manifest(
+    payloads: &[SuitPayload],
+    sequence_number: u64,
+    board: Option<&str>,
+    output: &str,
+) -> Result<(), String> {
+    let key = load_signing_key(&signing_key())?;
+    let manifest = build_manifest(payloads, sequence_number, board);
+    fs::write(output, sign_manifest(&manifest, &key))
+        .map_err(|e| format!("Failed to write the manifest {}: {}", output, e))
+}
+
+/// SUIT_Digest of the data: `[algorithm, digest]`.
+fn digest(data: &[u8]) -> Value {
+    Value::Array(vec![
+        int(COSE_ALGORITHM_SHA256),
+        Value::Bytes(Sha256::digest(data).to_vec()),
+    ])
+}
+
+/// Name-based UUID (version 5) as used by RIOT for the vendor and class
+/// identifiers.
+fn uuid5(namespace: &[u8; 16], name: &str) -> [u8; 16] {
+    let hash = Sha1::new()
+        .chain_update(namespace)
+        .chain_update(name.as_bytes())
+        .finalize();
+    let mut uuid = [0; 16];
+    uuid.copy_from_slice(&hash[..16]);
+    uuid[6] = (uuid[6] & 0x0f) | 0x50;
+    uuid[8] = (uuid[8] & 0x3f) | 0x80;
+    uuid
+}
+
+fn int(value: i64) -> Value {
+    Value::Integer(Integer::from(value))
+}
+
+fn encode(value: &Value) -> Vec<u8> {
+    let mut bytes = vec![];
+    ciborium::into_writer(value, &mut bytes).unwrap();
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+    use super::*;
+
+    fn payload() -> SuitPayload {
+        SuitPayload {
+            uri: "coap://[fe80::1]/program.bin".to_string(),
+            component_id: "ram:0".to_string(),
+            contents: b"program".to_vec(),
+            compression: None,
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> Value {
+        ciborium::from_reader(bytes).unwrap()
+    }
+
+    fn member(map: &Value, key: i64) -> &Value {
+        map.as_map()
+            .unwrap()
+            .iter()
+            .find(|(member, _)| *member == int(key))
+            .map(|(_, value)| value)
+            .unwrap()
+    }
+
+    fn bytes(value: &Value) -> &[u8] {
+        value.as_bytes().unwrap()
+    }
+
+    /// Decodes the envelope and returns the manifest, the SUIT_Digest from
+    /// the authentication wrapper and the COSE_Sign1 structure.
+    fn open_envelope(envelope: &[u8]) -> (Vec<u8>, Vec<u8>, Vec<Value>) {
+        let Value::Tag(CBOR_TAG_SUIT_ENVELOPE, envelope) = decode(envelope) else {
+            panic!("missing the envelope tag");
+        };
+        let wrapper = decode(bytes(member(&envelope, SUIT_AUTHENTICATION_WRAPPER)));
+        let wrapper = wrapper.as_array().unwrap();
+        let Value::Tag(CBOR_TAG_COSE_SIGN1, cose_sign1) = decode(bytes(&wrapper[1])) else {
+            panic!("missing the COSE_Sign1 tag");
+        };
+        (
+            bytes(member(&envelope, SUIT_MANIFEST)).to_vec(),
+            bytes(&wrapper[0]).to_vec(),
+            cose_sign1.into_array().unwrap(),
+        )
+    }
+
+    /// Checks the signature over the Sig_structure of RFC 9052.
+    fn verify(cose_sign1: &[Value], key: &VerifyingKey) -> bool {
+        let signature_input = encode(&Value::Array(vec![
+            Value::Text("Signature1".to_string()),
+            cose_sign1[0].clone(),
+            Value::Bytes(vec![]),
+            cose_sign1[2].clone(),
+        ]));
+        let signature = Signature::from_slice(bytes(&cose_sign1[3])).unwrap();
+        key.verify(&signature_input, &signature).is_ok()
+    }
+
+    #[test]
+    fn envelope_is_signed_over_the_manifest_digest() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let manifest = build_manifest(&[payload()], 5, None);
+        let (signed_manifest, manifest_digest, cose_sign1) =
+            open_envelope(&sign_manifest(&manifest, &key));
+
+        assert_eq!(signed_manifest, manifest);
+        assert_eq!(manifest_digest, encode(&digest(&manifest)));
+        let protected = decode(bytes(&cose_sign1[0]));
+        assert_eq!(
+            member(&protected, COSE_ALGORITHM),
+            &int(COSE_ALGORITHM_EDDSA)
+        );
+        assert_eq!(bytes(&cose_sign1[2]), manifest_digest);
+        assert!(verify(&cose_sign1, &key.verifying_key()));
+    }
+
+    #[test]
+    fn manifest_describes_the_payload() {
+        let manifest = decode(&build_manifest(&[payload()], 5, Some("native")));
+
+        assert_eq!(member(&manifest, SUIT_MANIFEST_VERSION), &int(1));
+        assert_eq!(member(&manifest, SUIT_MANIFEST_SEQUENCE_NUMBER), &int(5));
+
+        let common = decode(bytes(member(&manifest, SUIT_COMMON)));
+        assert_eq!(
+            member(&common, SUIT_COMPONENTS),
+            &Value::Array(vec![Value::Array(vec![
+                Value::Bytes(b"ram".to_vec()),
+                Value::Bytes(b"0".to_vec()),
+            ])])
+        );
+        let sequence = decode(bytes(member(&common, SUIT_COMMON_SEQUENCE)));
+        let parameters = &sequence.as_array().unwrap()[3];
+        assert_eq!(
+            decode(bytes(member(parameters, SUIT_PARAMETER_IMAGE_DIGEST))),
+            Value::Array(vec![
+                int(COSE_ALGORITHM_SHA256),
+                Value::Bytes(Sha256::digest(b"program").to_vec()),
+            ])
+        );
+        assert_eq!(member(parameters, SUIT_PARAMETER_IMAGE_SIZE), &int(7));
+        // Vendor identifier of RIOT, UUID5 of riot-os.org in the DNS namespace.
+        assert_eq!(
+            bytes(member(parameters, SUIT_PARAMETER_VENDOR_IDENTIFIER)),
+            [
+                0x54, 0x7d, 0x0d, 0x74, 0x6d, 0x3a, 0x5a, 0x92, 0x96, 0x62, 0x48, 0x81, 0xaf, 0xd9,
+                0x40, 0x7b
+            ]
+        );
+    }
+
+    #[test]
+    fn resigning_replaces_only_the_signature() {
+        let (old_key, new_key) = (
+            SigningKey::from_bytes(&[7; 32]),
+            SigningKey::from_bytes(&[9; 32]),
+        );
+        let manifest = build_manifest(&[payload()], 5, None);
+        let envelope = resign_envelope(&sign_manifest(&manifest, &old_key), &new_key).unwrap();
+
+        let (signed_manifest, _, cose_sign1) = open_envelope(&envelope);
+        assert_eq!(signed_manifest, manifest);
+        assert!(verify(&cose_sign1, &new_key.verifying_key()));
+        assert!(!verify(&cose_sign1, &old_key.verifying_key()));
+    }
+}
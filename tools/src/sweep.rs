@@ -0,0 +1,265 @@
+use std::{fs, str::FromStr};
+
+use log::debug;
+use micro_bpf_common::{
+    BinaryFileLayout, ExecutionModel, HelperAccessListSource, HelperAccessVerification, TargetVM,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    environment::Environment,
+    execute::{execute, ExecuteOptions},
+    transport::TransportOptions,
+};
+
+/// Execution parameters shared by all combinations of the sweep, named and
+/// defaulted the same as the options of the `execute` command.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionTemplate {
+    #[serde(default = "default_target")]
+    pub target: String,
+    #[serde(default = "default_binary_layout")]
+    pub binary_layout: String,
+    #[serde(default = "default_execution_model")]
+    pub execution_model: String,
+    #[serde(default = "default_helper_access_verification")]
+    pub helper_access_verification: String,
+    #[serde(default = "default_helper_access_list_source")]
+    pub helper_access_list_source: String,
+    #[serde(default)]
+    pub jit: bool,
+    #[serde(default)]
+    pub jit_compile: bool,
+    #[serde(default)]
+    pub gas_limit: Option<u64>,
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+fn default_target() -> String {
+    "rBPF".to_string()
+}
+
+fn default_binary_layout() -> String {
+    "ExtendedHeader".to_string()
+}
+
+fn default_execution_model() -> String {
+    "ShortLived".to_string()
+}
+
+fn default_helper_access_verification() -> String {
+    "Runtime".to_string()
+}
+
+fn default_helper_access_list_source() -> String {
+    "ExecuteRequest".to_string()
+}
+
+/// Values of the parameters that the sweep iterates over, every combination
+/// of them is executed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParameterGrid {
+    /// SUIT storage slots containing the (already deployed) programs.
+    pub slots: Vec<usize>,
+    /// Execution arguments passed to the program, see
+    /// [`micro_bpf_common::VMExecutionRequest::key_handles`].
+    #[serde(default = "no_arguments")]
+    pub args: Vec<Vec<u32>>,
+    /// Sets of the helpers that the program is allowed to call, an empty set
+    /// allows all helpers.
+    #[serde(default = "no_arguments")]
+    pub helpers: Vec<Vec<u8>>,
+}
+
+fn no_arguments<T>() -> Vec<Vec<T>> {
+    vec![vec![]]
+}
+
+/// Description of a parameter sweep, loaded from a YAML file:
+///
+/// ```yaml
+/// name: helper-sets
+/// repetitions: 5
+/// template:
+///   target: rBPF
+///   jit: true
+/// grid:
+///   slots: [0, 1]
+///   args: [[], [1, 2]]
+///   helpers: [[], [1, 2, 3]]
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepSpec {
+    pub name: String,
+    /// Number of executions of each combination.
+    #[serde(default = "default_repetitions")]
+    pub repetitions: usize,
+    pub template: ExecutionTemplate,
+    pub grid: ParameterGrid,
+}
+
+fn default_repetitions() -> usize {
+    1
+}
+
+impl SweepSpec {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read the sweep spec {}: {}", path, e))?;
+        let spec = serde_yaml::from_str::<SweepSpec>(&contents)
+            .map_err(|e| format!("Failed to parse the sweep spec {}: {}", path, e))?;
+        if spec.repetitions == 0 {
+            return Err("The sweep needs at least one repetition".to_string());
+        }
+        if spec.grid.slots.is_empty() || spec.grid.args.is_empty() || spec.grid.helpers.is_empty() {
+            return Err("Each parameter of the grid needs at least one value".to_string());
+        }
+        Ok(spec)
+    }
+
+    /// Number of executions performed by the sweep.
+    pub fn executions(&self) -> usize {
+        self.grid.slots.len() * self.grid.args.len() * self.grid.helpers.len() * self.repetitions
+    }
+}
+
+/// Single execution of the sweep, the rows of the resulting CSV.
+#[derive(Debug, Clone, Serialize)]
+pub struct SweepRow {
+    pub slot: usize,
+    pub args: Vec<u32>,
+    pub helpers: Vec<u8>,
+    pub repetition: usize,
+    pub execution_time: Option<u64>,
+    pub result: Option<i64>,
+    pub instructions: Option<u64>,
+    /// Error of the failed execution, the sweep carries on with the next one.
+    pub error: Option<String>,
+}
+
+const CSV_HEADER: &str = "slot,args,helpers,repetition,execution_time,result,instructions,error\n";
+
+impl SweepRow {
+    /// Formats the row in the long format: one execution per line, with the
+    /// lists of arguments and helpers separated by spaces.
+    pub fn to_csv(&self) -> String {
+        let join = |values: Vec<String>| values.join(" ");
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        // Only the first line of the error is kept, quoted as it can contain
+        // commas.
+        let error = self.error.as_ref().map(|e| {
+            let line = e.lines().next().unwrap_or_default();
+            format!("\"{}\"", line.replace('"', "\"\""))
+        });
+        format!(
+            "{},{},{},{},{},{},{},{}\n",
+            self.slot,
+            join(self.args.iter().map(|arg| arg.to_string()).collect()),
+            join(self.helpers.iter().map(|id| id.to_string()).collect()),
+            self.repetition,
+            optional(self.execution_time.map(|time| time.to_string())),
+            optional(self.result.map(|result| result.to_string())),
+            optional(self.instructions.map(|count| count.to_string())),
+            optional(error),
+        )
+    }
+}
+
+/// Executes every combination of the parameter grid on the device of the
+/// environment and returns the results as a long-format CSV. If `output` is
+/// given, the CSV is rewritten after each execution so that an interrupted
+/// sweep doesn't lose the collected results.
+pub async fn run_sweep(
+    spec: &SweepSpec,
+    environment: &Environment,
+    output: Option<&str>,
+    transport: &TransportOptions,
+) -> Result<String, String> {
+    let template = &spec.template;
+    let target = TargetVM::from_str(&template.target)?;
+    let binary_layout = template.binary_layout.parse::<BinaryFileLayout>()?;
+    let execution_model = ExecutionModel::from_str(&template.execution_model)?;
+    let helper_access_verification =
+        HelperAccessVerification::from_str(&template.helper_access_verification)?;
+    let helper_access_list_source =
+        HelperAccessListSource::from_str(&template.helper_access_list_source)?;
+
+    let mut csv = String::from(CSV_HEADER);
+    let mut done = 0;
+    for slot in &spec.grid.slots {
+        for args in &spec.grid.args {
+            for helpers in &spec.grid.helpers {
+                for repetition in 0..spec.repetitions {
+                    done += 1;
+                    // The progress goes to stderr so that the CSV can be piped.
+                    eprintln!(
+                        "[{}] {}/{}: slot {}, args {:?}, helpers {:?}",
+                        spec.name,
+                        done,
+                        spec.executions(),
+                        slot,
+                        args,
+                        helpers
+                    );
+                    let response = execute(
+                        &environment.riot_instance_ip,
+                        target,
+                        binary_layout,
+                        *slot,
+                        &environment.host_net_if,
+                        execution_model,
+                        helper_access_verification,
+                        helper_access_list_source,
+                        helpers,
+                        template.jit,
+                        &ExecuteOptions {
+                            jit_compile: template.jit_compile,
+                            gas_limit: template.gas_limit,
+                            key_handles: args,
+                            seed: template.seed,
+                            ..Default::default()
+                        },
+                        transport,
+                    )
+                    .await;
+
+                    let row = sweep_row(*slot, args, helpers, repetition, response);
+                    if let Some(e) = &row.error {
+                        debug!("The execution failed: {}", e);
+                    }
+                    csv.push_str(&row.to_csv());
+                    if let Some(output) = output {
+                        fs::write(output, &csv)
+                            .map_err(|e| format!("Failed to write {}: {}", output, e))?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(csv)
+}
+
+fn sweep_row(
+    slot: usize,
+    args: &[u32],
+    helpers: &[u8],
+    repetition: usize,
+    response: Result<String, String>,
+) -> SweepRow {
+    let json = response.as_ref().ok().and_then(|response| {
+        serde_json::from_str::<Value>(response.trim_matches(char::from(0))).ok()
+    });
+    let field = |name: &str| json.as_ref().and_then(|json| json[name].as_i64());
+    SweepRow {
+        slot,
+        args: args.to_vec(),
+        helpers: helpers.to_vec(),
+        repetition,
+        execution_time: field("execution_time").map(|time| time as u64),
+        result: field("result"),
+        instructions: field("instructions").map(|count| count as u64),
+        error: response.err(),
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use log::debug;
+use serde_json::Value;
+
+use crate::{
+    collect::{influx_line, Sample},
+    environment::Environment,
+    registry::now,
+};
+
+/// Table that the rows are inserted into unless the sink url specifies
+/// another one using `?table=<name>`.
+const DEFAULT_TABLE: &str = "mibpf_telemetry";
+
+/// Time-series store that the execution results and the collected samples
+/// are written to, configured per device profile using TELEMETRY_SINK (and
+/// TELEMETRY_TOKEN for InfluxDB). Same as the webhooks, the requests are sent
+/// by external clients (curl and psql), so no database drivers are needed.
+///
+/// - `influx+http://<host>:8086/api/v2/write?org=<org>&bucket=<bucket>` -
+///   the points are sent using the line protocol, the device and the slot
+///   are tags.
+/// - `postgres://<user>@<host>/<db>?table=<table>` - the rows are inserted
+///   into a table (e.g. a TimescaleDB hypertable) with the columns
+///   `time timestamptz, device text, measurement text, field text,
+///   value double precision`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TelemetrySink {
+    Influx { url: String, token: Option<String> },
+    Postgres { url: String, table: String },
+}
+
+impl TelemetrySink {
+    pub fn parse(spec: &str, token: Option<&str>) -> Result<Self, String> {
+        if let Some(url) = spec.strip_prefix("influx+") {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(format!("Invalid InfluxDB url: {}", spec));
+            }
+            return Ok(TelemetrySink::Influx {
+                url: url.to_string(),
+                token: token.map(str::to_string),
+            });
+        }
+        if spec.starts_with("postgres://") || spec.starts_with("postgresql://") {
+            // psql rejects the connection parameters it doesn't know about.
+            let (url, query) = spec.split_once('?').unwrap_or((spec, ""));
+            let (tables, params): (Vec<&str>, Vec<&str>) = query
+                .split('&')
+                .filter(|param| !param.is_empty())
+                .partition(|param| param.starts_with("table="));
+            let table = tables
+                .last()
+                .map(|param| param.trim_start_matches("table="))
+                .unwrap_or(DEFAULT_TABLE);
+            let url = if params.is_empty() {
+                url.to_string()
+            } else {
+                format!("{}?{}", url, params.join("&"))
+            };
+            if table.is_empty()
+                || !table
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+            {
+                return Err(format!("Invalid table name: {}", table));
+            }
+            return Ok(TelemetrySink::Postgres {
+                url,
+                table: table.to_string(),
+            });
+        }
+        Err(format!("Unknown telemetry sink: {}", spec))
+    }
+
+    /// Returns the sink configured by the device profile, None if it isn't
+    /// configured.
+    pub fn from_env(environment: &Environment) -> Result<Option<Self>, String> {
+        environment
+            .telemetry_sink
+            .as_deref()
+            .map(|spec| Self::parse(spec, environment.telemetry_token.as_deref()))
+            .transpose()
+    }
+
+    /// Writes the samples collected from a long-running program, see
+    /// [`crate::collect::collect`].
+    pub fn write_samples(
+        &self,
+        measurement: &str,
+        slot: usize,
+        samples: &[Sample],
+    ) -> Result<(), String> {
+        let slot = slot.to_string();
+        let points = samples
+            .iter()
+            .map(|sample| Point {
+                timestamp_ms: sample.timestamp_ms,
+                device: &sample.device,
+                slot: &slot,
+                fields: sample.fields.clone(),
+            })
+            .collect::<Vec<Point>>();
+        self.write(measurement, &points)
+    }
+
+    /// Writes the numeric fields of the response to an execution request,
+    /// e.g. the return value and the execution time.
+    pub fn write_result(&self, device: &str, slot: usize, response: &str) -> Result<(), String> {
+        let response = serde_json::from_str::<Value>(response.trim_matches(char::from(0)))
+            .map_err(|e| format!("Failed to parse the execution response: {}", e))?;
+        let fields = response
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter_map(|(key, value)| value.as_f64().map(|value| (key.clone(), value)))
+            .collect::<Vec<(String, f64)>>();
+        if fields.is_empty() {
+            return Ok(());
+        }
+
+        let slot = slot.to_string();
+        let point = Point {
+            timestamp_ms: now() * 1000,
+            device,
+            slot: &slot,
+            fields,
+        };
+        self.write("execution", &[point])
+    }
+
+    fn write(&self, measurement: &str, points: &[Point]) -> Result<(), String> {
+        if points.is_empty() {
+            return Ok(());
+        }
+        match self {
+            TelemetrySink::Influx { url, token } => {
+                let body = points
+                    .iter()
+                    .map(|p| {
+                        influx_line(
+                            measurement,
+                            &[("device", p.device), ("slot", p.slot)],
+                            &p.fields,
+                            p.timestamp_ms,
+                        ) + "\n"
+                    })
+                    .collect::<String>();
+                let mut command = Command::new("curl");
+                command
+                    .arg("--silent")
+                    .arg("--show-error")
+                    .arg("--fail")
+                    .arg("-X")
+                    .arg("POST")
+                    .arg("-H")
+                    .arg("Content-Type: text/plain; charset=utf-8");
+                if let Some(token) = token {
+                    command
+                        .arg("-H")
+                        .arg(format!("Authorization: Token {}", token));
+                }
+                command.arg("--data-binary").arg("@-").arg(url);
+                debug!("Writing {} points to {}", points.len(), url);
+                run_with_input(command, &body, "curl")
+            }
+            TelemetrySink::Postgres { url, table } => {
+                let rows = points
+                    .iter()
+                    .flat_map(|p| {
+                        p.fields.iter().map(move |(field, value)| {
+                            format!(
+                                "(to_timestamp({}), {}, {}, {}, {})",
+                                p.timestamp_ms as f64 / 1000.0,
+                                quote(p.device),
+                                quote(measurement),
+                                quote(field),
+                                value
+                            )
+                        })
+                    })
+                    .collect::<Vec<String>>();
+                let statement = format!(
+                    "INSERT INTO {} (time, device, measurement, field, value) VALUES {};",
+                    table,
+                    rows.join(", ")
+                );
+                let mut command = Command::new("psql");
+                command
+                    .arg("--quiet")
+                    .arg("--no-psqlrc")
+                    .arg("-v")
+                    .arg("ON_ERROR_STOP=1")
+                    .arg(url);
+                debug!("Inserting {} rows into {}", rows.len(), table);
+                run_with_input(command, &statement, "psql")
+            }
+        }
+    }
+}
+
+struct Point<'a> {
+    timestamp_ms: u64,
+    device: &'a str,
+    slot: &'a str,
+    fields: Vec<(String, f64)>,
+}
+
+/// Quotes the string as an SQL literal.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// The data is passed through stdin so that large batches don't hit the
+/// limit on the length of the command line.
+fn run_with_input(mut command: Command, input: &str, client: &str) -> Result<(), String> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", client, e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input.as_bytes())
+            .map_err(|e| format!("Failed to write to {}: {}", client, e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to run {}: {}", client, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Writing the telemetry using {} failed: {}",
+            client,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
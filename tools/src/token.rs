@@ -0,0 +1,69 @@
+use log::debug;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::transport::{send_request, TransportOptions};
+
+/// Access token issued by the authorization server of the testbed. Shared
+/// testbeds use those tokens to restrict which users may execute programs on
+/// which devices. The token is attached to every request sent to the device
+/// (see [`TransportOptions::access_token`]) and the device checks it before
+/// handling the request.
+#[derive(Debug, Deserialize)]
+pub struct AccessToken {
+    pub access_token: String,
+    /// Token that can be exchanged for a new access token once the current one
+    /// expires.
+    pub refresh_token: Option<String>,
+    /// Lifetime of the access token in seconds.
+    pub expires_in: Option<u64>,
+}
+
+/// Requests a new access token for the device with a given IPv6 address
+/// (the audience of the token) from the authorization server.
+pub fn issue_token(
+    authorization_server: &str,
+    client_id: &str,
+    client_secret: &str,
+    audience: &str,
+) -> Result<AccessToken, String> {
+    let request = json!({
+        "grant_type": "client_credentials",
+        "client_id": client_id,
+        "client_secret": client_secret,
+        "audience": audience,
+    });
+
+    request_token(authorization_server, &request.to_string())
+}
+
+/// Exchanges the refresh token for a new access token.
+pub fn refresh_token(authorization_server: &str, refresh_token: &str) -> Result<AccessToken, String> {
+    let request = json!({
+        "grant_type": "refresh_token",
+        "refresh_token": refresh_token,
+    });
+
+    request_token(authorization_server, &request.to_string())
+}
+
+fn request_token(authorization_server: &str, payload: &str) -> Result<AccessToken, String> {
+    let url = format!("{}/token", authorization_server);
+    debug!("Requesting an access token from: {}", url);
+
+    // The authorization server isn't one of the devices, so we don't protect
+    // the request using the device security contexts.
+    let output = send_request("POST", &url, Some(payload), &TransportOptions::default())?;
+
+    if output.stderr.len() > 0 {
+        let stderr = String::from_utf8(output.stderr)
+            .map_err(|e| format!("Failed to parse the stderr: {}", e))?;
+        Err(format!("aiocoap-client failed with: {}", stderr))?
+    }
+
+    let response = String::from_utf8(output.stdout)
+        .map_err(|e| format!("Failed to parse the response: {}", e))?;
+
+    serde_json::from_str::<AccessToken>(&response)
+        .map_err(|e| format!("Invalid response from the authorization server: {}", e))
+}
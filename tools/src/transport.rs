@@ -0,0 +1,337 @@
+use std::{
+    io::Read,
+    process::{Command, ExitStatus, Output, Stdio},
+    sync::OnceLock,
+    thread,
+    time::Duration,
+};
+
+use log::debug;
+use micro_bpf_common::ContentFormat;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    endpoints::Endpoints,
+    environment::Environment,
+    identity::{new_nonce, verify_response, IdentityStore},
+    interface::resolve_url_zone,
+    oscore::{parse_authority, prepare_credentials},
+    ratelimit::{acquire, RateLimits},
+};
+
+/// How often the client process is checked for completion while waiting for
+/// the response, this bounds the delay of cancelling the request.
+const CANCELLATION_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Error reported by the operations aborted using the cancellation token of
+/// the [`TransportOptions`], the callers may add context to it.
+pub const CANCELLED: &str = "The operation has been cancelled";
+
+/// Controls how the CoAP requests are sent to the RIOT instance. All requests
+/// issued by the tool (pull, execute) go through [`send_request`] so that the
+/// options specified here apply to every one of them.
+#[derive(Debug, Clone)]
+pub struct TransportOptions {
+    /// Directory containing the per-device OSCORE security contexts
+    /// (see [`crate::oscore::SecurityContextStore`]). If it is set, the requests
+    /// are protected end-to-end using OSCORE and so they remain authenticated
+    /// even when traversing untrusted CoAP proxies.
+    pub oscore_context_dir: Option<String>,
+    /// Access token proving that the user is allowed to operate on the device
+    /// (see [`crate::token`]). It is attached to the request as the `token`
+    /// URI query parameter.
+    pub access_token: Option<String>,
+    /// If set, the requests aren't sent to the device. Instead, the exact
+    /// client invocation (url, method and payload) is printed to stdout. This
+    /// allows for debugging the configuration before risking state on a flaky
+    /// physical board.
+    pub dry_run: bool,
+    /// Per-device limits on the number of concurrent requests and the
+    /// request rate, see [`crate::ratelimit`].
+    pub rate_limits: RateLimits,
+    /// Ports and paths of the resources on the device, the urls passed to
+    /// [`send_request`] are resolved against them.
+    pub endpoints: Endpoints,
+    /// Once cancelled, the request in flight is aborted and no further
+    /// requests are sent. By default it is a child of the token cancelled
+    /// when the user interrupts the tool, see [`cancel_on_interrupt`].
+    pub cancellation: CancellationToken,
+    /// Content-Formats of the execution responses that the tool accepts, in
+    /// the order of preference. If it is empty, no Accept option is sent and
+    /// the device responds using JSON.
+    pub accept: Vec<ContentFormat>,
+    /// CoAP forward proxy that the requests are sent through (e.g. the border
+    /// router of a remote 6LoWPAN segment). The device is then addressed by
+    /// the Proxy-Scheme and Uri-Host options and so the network interface is
+    /// dropped from its address.
+    pub proxy: Option<String>,
+    /// Directory containing the pinned public keys of the devices (see
+    /// [`crate::identity::IdentityStore`]). The responses of the devices with
+    /// a pinned key need to be signed by that key, otherwise the request
+    /// fails with a [`crate::identity::ResponseAuthenticity`] error.
+    pub identity_dir: Option<String>,
+    /// If set, the executions rejected by a busy device (5.03) wait in the
+    /// client-side queue of the device for at most this long instead of
+    /// failing right away, see [`crate::queue::ExecutionQueue`].
+    pub queue_timeout: Option<Duration>,
+}
+
+impl Default for TransportOptions {
+    fn default() -> Self {
+        TransportOptions {
+            oscore_context_dir: None,
+            access_token: None,
+            dry_run: false,
+            rate_limits: RateLimits::default(),
+            endpoints: Endpoints::default(),
+            cancellation: shutdown_token().child_token(),
+            accept: vec![],
+            proxy: None,
+            identity_dir: None,
+            queue_timeout: None,
+        }
+    }
+}
+
+impl TransportOptions {
+    /// Configures the transport using the settings from the .env file.
+    pub fn from_env(env: &Environment) -> Self {
+        TransportOptions {
+            oscore_context_dir: env.oscore_context_dir.clone(),
+            access_token: env.access_token.clone(),
+            dry_run: false,
+            rate_limits: RateLimits {
+                max_concurrent_requests: env.max_concurrent_requests,
+                requests_per_second: env.requests_per_second,
+            },
+            endpoints: Endpoints::from_env(env),
+            proxy: env.coap_proxy.clone(),
+            identity_dir: env.identity_dir.clone(),
+            queue_timeout: env.queue_timeout.map(Duration::from_secs),
+            ..Default::default()
+        }
+    }
+
+    /// Returns an error if the operation using this transport has been
+    /// cancelled, it should be checked before every step which changes the
+    /// state of the device or the file server.
+    pub fn check_cancelled(&self) -> Result<(), String> {
+        if self.cancellation.is_cancelled() {
+            return Err(CANCELLED.to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Checks whether the error was caused by cancelling the operation.
+pub fn is_cancelled(error: &str) -> bool {
+    error.contains(CANCELLED)
+}
+
+fn shutdown_token() -> &'static CancellationToken {
+    static TOKEN: OnceLock<CancellationToken> = OnceLock::new();
+    TOKEN.get_or_init(CancellationToken::new)
+}
+
+/// Cancels all operations using the default cancellation token once the user
+/// presses Ctrl-C so that they can finish cleanly. Pressing it again
+/// terminates the tool immediately. Needs to be called from within the tokio
+/// runtime.
+pub fn cancel_on_interrupt() {
+    tokio::spawn(async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        eprintln!("Interrupted, cancelling the operations in progress (press Ctrl-C again to exit immediately)");
+        shutdown_token().cancel();
+
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
+        }
+    });
+}
+
+/// Sends a CoAP request to the specified url and returns the raw output of the
+/// client process.
+///
+/// We use the aiocoap-client here as opposed to the rust coap library because
+/// that one didn't support overriding the network interface in the ipv6 urls.
+pub fn send_request(
+    method: &str,
+    url: &str,
+    payload: Option<&str>,
+    options: &TransportOptions,
+) -> Result<Output, String> {
+    send_request_accepting(method, url, payload, None, options)
+}
+
+/// Same as [`send_request`] but asks the device to respond using the given
+/// Content-Format (sent as the Accept option).
+pub fn send_request_accepting(
+    method: &str,
+    url: &str,
+    payload: Option<&str>,
+    accept: Option<ContentFormat>,
+    options: &TransportOptions,
+) -> Result<Output, String> {
+    options.check_cancelled()?;
+    let url = resolve_url_zone(&options.endpoints.resolve(url)?)?;
+    let url = match &options.proxy {
+        Some(_) => strip_zone(&url),
+        None => url,
+    };
+    let token = options
+        .access_token
+        .as_deref()
+        .map(|token| format!("token={}", percent_encode(token)));
+    let url = match &token {
+        Some(token) if url.contains('?') => format!("{}&{}", url, token),
+        Some(token) => format!("{}?{}", url, token),
+        None => url.to_string(),
+    };
+    // The token must not leak into the output or the error messages, those
+    // end up in the audit log, the webhooks and the history.
+    let redact = |text: &str| match &token {
+        Some(token) => text.replace(token.as_str(), "token=***"),
+        None => text.to_string(),
+    };
+
+    // The device signs the nonce together with the response, which proves
+    // that the response is fresh and comes from the pinned device rather
+    // than from another node answering on a shared (e.g. multicast) link.
+    let pinned_key = match &options.identity_dir {
+        Some(dir) => {
+            let (_, device_ip) = parse_authority(&url).map_err(|e| redact(&e))?;
+            IdentityStore::new(dir)
+                .pinned_key(device_ip)
+                .map(|key| (device_ip.to_string(), key))
+        }
+        None => None,
+    };
+    let nonce = match pinned_key {
+        Some(_) => Some(new_nonce()?),
+        None => None,
+    };
+    let url = match &nonce {
+        Some(nonce) if url.contains('?') => format!("{}&nonce={}", url, nonce),
+        Some(nonce) => format!("{}?nonce={}", url, nonce),
+        None => url,
+    };
+
+    let mut command = Command::new("aiocoap-client");
+    command.arg("-m").arg(method).arg(&url);
+
+    if let Some(proxy) = &options.proxy {
+        command.arg("--proxy").arg(proxy);
+    }
+
+    if let Some(payload) = payload {
+        command.arg("--payload").arg(payload);
+    }
+
+    if let Some(format) = accept {
+        command.arg("--accept").arg((format as u16).to_string());
+    }
+
+    if let Some(context_dir) = &options.oscore_context_dir {
+        let credentials = prepare_credentials(context_dir, &url).map_err(|e| redact(&e))?;
+        debug!("Protecting the request using OSCORE credentials: {}", credentials);
+        command.arg("--credentials").arg(credentials);
+    }
+
+    if options.dry_run {
+        println!("[dry-run] {}", redact(&format!("{:?}", command)));
+        return Ok(Output {
+            status: ExitStatus::default(),
+            stdout: vec![],
+            stderr: vec![],
+        });
+    }
+
+    let _permit = if options.rate_limits.is_unlimited() {
+        None
+    } else {
+        let (_, device_ip) = parse_authority(&url).map_err(|e| redact(&e))?;
+        Some(acquire(device_ip, options.rate_limits))
+    };
+
+    let mut output = run_cancellable(command, &options.cancellation)
+        .map_err(|e| format!("Failed to send the request to {}: {}", redact(&url), e))?;
+
+    // Failed requests are reported by the client on stderr, there is no
+    // response to authenticate.
+    if let (Some((device_ip, key)), Some(nonce)) = (pinned_key, nonce) {
+        if output.status.success() && output.stderr.is_empty() {
+            output.stdout = verify_response(&device_ip, &nonce, &output.stdout, &key)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(output)
+}
+
+/// Percent-encodes all characters of the query parameter value apart from
+/// the unreserved ones (RFC 3986).
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Removes the zone index (the network interface) from the IPv6 address in
+/// the url, it is only meaningful to the host and the proxy would reject it.
+fn strip_zone(url: &str) -> String {
+    match (url.find('%'), url.find(']')) {
+        (Some(start), Some(end)) if start < end => format!("{}{}", &url[..start], &url[end..]),
+        _ => url.to_string(),
+    }
+}
+
+/// Runs the client process to completion unless the token is cancelled in
+/// the meantime, in which case the process is killed so that the exchange
+/// with the device is abandoned.
+fn run_cancellable(
+    mut command: Command,
+    cancellation: &CancellationToken,
+) -> Result<Output, String> {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    // The output needs to be consumed while waiting, otherwise the client
+    // blocks once the pipe buffers fill up.
+    let read_all = |mut reader: Box<dyn Read + Send>| {
+        thread::spawn(move || {
+            let mut buffer = vec![];
+            let _ = reader.read_to_end(&mut buffer);
+            buffer
+        })
+    };
+    let stdout = read_all(Box::new(child.stdout.take().unwrap()));
+    let stderr = read_all(Box::new(child.stderr.take().unwrap()));
+
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+            break status;
+        }
+        if cancellation.is_cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(CANCELLED.to_string());
+        }
+        thread::sleep(CANCELLATION_POLL_INTERVAL);
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
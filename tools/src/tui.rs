@@ -0,0 +1,720 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io::{self, Stdout},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use enum_iterator::all;
+use micro_bpf_common::{
+    BinaryFileLayout, ExecutionModel, HelperAccessListSource, HelperAccessVerification,
+    HelperFunctionID, StorageBackend, TargetVM,
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Row, Table, TableState, Wrap},
+    Frame, Terminal,
+};
+use tokio::runtime::Handle;
+
+use crate::{
+    artifacts::ArtifactStore,
+    deploy::{deploy, DeployOptions},
+    device::reset_vm,
+    environment::Environment,
+    execute::{execute, ExecuteOptions},
+    fleet::{Device, FleetWatcher},
+    logs::fetch_logs,
+    publish::LocalPublisher,
+    slots::{query_slots, SlotInfo, SuitSlot},
+    transport::TransportOptions,
+    watchdog::probe_health,
+};
+
+/// Number of log lines kept for each device.
+const LOG_LINES_KEPT: usize = 200;
+
+/// How often the keyboard is checked for input while waiting for updates
+/// from the devices.
+const INPUT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often the fleet file is checked for changes.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Controls what the dashboard polls and what the keybindings do.
+#[derive(Debug, Clone)]
+pub struct DashboardConfig {
+    /// Time between two consecutive polls of each device.
+    pub refresh: Duration,
+    /// Source file of the program deployed into the selected slot using the
+    /// `d` key, deploying is disabled if it isn't specified.
+    pub deploy_source: Option<String>,
+    pub target: TargetVM,
+    pub binary_layout: BinaryFileLayout,
+    pub jit: bool,
+}
+
+/// Operation triggered from the dashboard on the selected slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Execute(ExecutionModel),
+    Stop,
+    Deploy,
+}
+
+impl Command {
+    fn name(&self) -> &'static str {
+        match self {
+            Command::Execute(ExecutionModel::LongRunning) => "start",
+            Command::Execute(_) => "execute",
+            Command::Stop => "stop",
+            Command::Deploy => "deploy",
+        }
+    }
+}
+
+/// Messages sent to the UI by the poller and the threads running the
+/// commands, so that the UI never waits for the devices. The devices are
+/// identified by their address as the fleet can change in the meantime.
+enum Update {
+    Status {
+        device: String,
+        status: Result<(Vec<SlotInfo>, BTreeMap<usize, bool>), String>,
+    },
+    Logs {
+        device: String,
+        lines: Vec<String>,
+    },
+    Finished {
+        device: String,
+        command: Command,
+        result: Result<String, String>,
+    },
+}
+
+/// State of a single device as last reported by the poller.
+#[derive(Default)]
+struct DeviceView {
+    /// Set once the device was polled for the first time.
+    polled: bool,
+    slots: Vec<SlotInfo>,
+    /// Whether the long-running VMs of the slots are alive, only known for
+    /// the slots that the device reports the health of.
+    running: BTreeMap<usize, bool>,
+    error: Option<String>,
+    /// Command in progress, only one command per device is allowed.
+    busy: Option<Command>,
+    last_result: Option<Result<String, String>>,
+    logs: VecDeque<String>,
+}
+
+struct Dashboard {
+    devices: Vec<Device>,
+    views: Vec<DeviceView>,
+    selected_device: usize,
+    selected_slot: usize,
+    status: String,
+}
+
+impl Dashboard {
+    fn index(&self, device: &str) -> Option<usize> {
+        self.devices.iter().position(|d| d.riot_ipv6_addr == device)
+    }
+
+    /// The updates of the devices that were removed from the fleet are
+    /// dropped.
+    fn apply(&mut self, update: Update) {
+        match update {
+            Update::Status { device, status } => {
+                let Some(index) = self.index(&device) else {
+                    return;
+                };
+                let view = &mut self.views[index];
+                view.polled = true;
+                match status {
+                    Ok((slots, running)) => {
+                        view.slots = slots;
+                        view.running = running;
+                        view.error = None;
+                    }
+                    Err(e) => view.error = Some(e),
+                }
+            }
+            Update::Logs { device, lines } => {
+                let Some(index) = self.index(&device) else {
+                    return;
+                };
+                let logs = &mut self.views[index].logs;
+                logs.extend(lines);
+                while logs.len() > LOG_LINES_KEPT {
+                    logs.pop_front();
+                }
+            }
+            Update::Finished {
+                device,
+                command,
+                result,
+            } => {
+                if let Some(index) = self.index(&device) {
+                    let view = &mut self.views[index];
+                    view.busy = None;
+                    view.last_result = Some(result);
+                }
+                self.status = format!("{} on {} finished", command.name(), device);
+            }
+        }
+    }
+
+    /// Replaces the devices with the reloaded fleet. The devices which remain
+    /// in the fleet keep their state, including the commands in progress.
+    fn reload(&mut self, devices: Vec<Device>) {
+        let selected = self.devices[self.selected_device].riot_ipv6_addr.clone();
+        let mut views = self
+            .devices
+            .drain(..)
+            .map(|device| device.riot_ipv6_addr)
+            .zip(self.views.drain(..))
+            .collect::<BTreeMap<String, DeviceView>>();
+        let (added, kept) = devices
+            .iter()
+            .partition::<Vec<&Device>, _>(|device| !views.contains_key(&device.riot_ipv6_addr));
+        self.status = format!(
+            "Reloaded the fleet: {} added, {} removed",
+            added.len(),
+            views.len() - kept.len()
+        );
+        self.views = devices
+            .iter()
+            .map(|device| views.remove(&device.riot_ipv6_addr).unwrap_or_default())
+            .collect();
+        self.devices = devices;
+        match self.index(&selected) {
+            Some(index) => self.selected_device = index,
+            None => {
+                self.selected_device = 0;
+                self.selected_slot = 0;
+            }
+        }
+    }
+
+    /// Number and storage backend of the selected slot, the programs are
+    /// redeployed into the same backend.
+    fn selected_slot_location(&self) -> Option<(usize, StorageBackend)> {
+        self.views[self.selected_device]
+            .slots
+            .get(self.selected_slot)
+            .map(|s| (s.slot, s.storage))
+    }
+
+    fn select_device(&mut self, offset: isize) {
+        let count = self.devices.len() as isize;
+        self.selected_device = (self.selected_device as isize + offset).rem_euclid(count) as usize;
+        self.selected_slot = 0;
+    }
+
+    fn select_slot(&mut self, offset: isize) {
+        let count = self.views[self.selected_device].slots.len() as isize;
+        if count > 0 {
+            self.selected_slot = (self.selected_slot as isize + offset).rem_euclid(count) as usize;
+        }
+    }
+}
+
+/// Restores the terminal when the dashboard exits, also on errors.
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen);
+    }
+}
+
+/// Runs the interactive console dashboard showing the devices, the programs
+/// in their SUIT storage slots, whether the long-running ones are alive, the
+/// results of the last executions and the live log streams of the devices.
+/// The selected slot can be executed (`e`), started as a long-running
+/// program (`l`), stopped (`s`) and redeployed (`d`).
+///
+/// The devices are polled on a background thread and the commands run on
+/// their own threads, so that an unreachable device doesn't freeze the
+/// dashboard. The log messages of the tool are written to stderr, it should
+/// be redirected (e.g. `2> dashboard.log`) so that they don't garble the
+/// screen. If the `watcher` is given, the changes of the fleet file are
+/// applied while the dashboard is running. Needs to be called from within the
+/// tokio runtime.
+pub fn run_dashboard(
+    environment: &Environment,
+    devices: Vec<Device>,
+    watcher: Option<FleetWatcher>,
+    config: &DashboardConfig,
+    transport: &TransportOptions,
+) -> Result<(), String> {
+    if devices.is_empty() {
+        return Err("No devices to show".to_string());
+    }
+    let runtime = Handle::current();
+    // Quitting the dashboard aborts the requests in flight.
+    let cancellation = transport.cancellation.child_token();
+    let transport = TransportOptions {
+        cancellation: cancellation.clone(),
+        ..transport.clone()
+    };
+
+    let (updates, received) = mpsc::channel();
+    // Shared with the poller so that it picks up the reloaded fleet.
+    let polled = Arc::new(Mutex::new(devices.clone()));
+    let poller = {
+        let (devices, updates, transport) = (polled.clone(), updates.clone(), transport.clone());
+        let refresh = config.refresh;
+        thread::spawn(move || poll_devices(&devices, refresh, &updates, &transport))
+    };
+
+    enable_raw_mode().map_err(|e| format!("Failed to set up the terminal: {}", e))?;
+    let guard = TerminalGuard;
+    execute!(io::stdout(), EnterAlternateScreen)
+        .map_err(|e| format!("Failed to set up the terminal: {}", e))?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))
+        .map_err(|e| format!("Failed to set up the terminal: {}", e))?;
+
+    let mut dashboard = Dashboard {
+        views: devices.iter().map(|_| DeviceView::default()).collect(),
+        devices,
+        selected_device: 0,
+        selected_slot: 0,
+        status: "Polling the devices...".to_string(),
+    };
+    let result = run_ui(
+        &mut terminal,
+        &mut dashboard,
+        &received,
+        &updates,
+        watcher.map(|watcher| (watcher, polled)),
+        environment,
+        config,
+        &transport,
+        &runtime,
+    );
+
+    cancellation.cancel();
+    drop(guard);
+    let _ = poller.join();
+    result
+}
+
+fn run_ui(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    dashboard: &mut Dashboard,
+    received: &Receiver<Update>,
+    updates: &Sender<Update>,
+    mut reload: Option<(FleetWatcher, Arc<Mutex<Vec<Device>>>)>,
+    environment: &Environment,
+    config: &DashboardConfig,
+    transport: &TransportOptions,
+    runtime: &Handle,
+) -> Result<(), String> {
+    let mut last_reload = Instant::now();
+    loop {
+        while let Ok(update) = received.try_recv() {
+            dashboard.apply(update);
+        }
+        if let Some((watcher, polled)) = reload.as_mut() {
+            if last_reload.elapsed() >= RELOAD_INTERVAL {
+                last_reload = Instant::now();
+                match watcher.poll() {
+                    Some(Ok(fleet)) => {
+                        *polled.lock().unwrap() = fleet.devices.clone();
+                        dashboard.reload(fleet.devices);
+                    }
+                    Some(Err(e)) => dashboard.status = format!("Keeping the previous fleet: {}", e),
+                    None => {}
+                }
+            }
+        }
+        terminal
+            .draw(|frame| draw(frame, dashboard, config))
+            .map_err(|e| format!("Failed to draw the dashboard: {}", e))?;
+
+        let has_input = event::poll(INPUT_POLL_INTERVAL)
+            .map_err(|e| format!("Failed to read the input: {}", e))?;
+        if !has_input {
+            continue;
+        }
+        let Event::Key(key) =
+            event::read().map_err(|e| format!("Failed to read the input: {}", e))?
+        else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        let command = match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+            KeyCode::Up | KeyCode::Char('k') => {
+                dashboard.select_device(-1);
+                None
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                dashboard.select_device(1);
+                None
+            }
+            KeyCode::Left => {
+                dashboard.select_slot(-1);
+                None
+            }
+            KeyCode::Right => {
+                dashboard.select_slot(1);
+                None
+            }
+            KeyCode::Char('e') => Some(Command::Execute(ExecutionModel::ShortLived)),
+            KeyCode::Char('l') => Some(Command::Execute(ExecutionModel::LongRunning)),
+            KeyCode::Char('s') => Some(Command::Stop),
+            KeyCode::Char('d') => Some(Command::Deploy),
+            _ => None,
+        };
+        let Some(command) = command else {
+            continue;
+        };
+
+        let device = dashboard.selected_device;
+        let Some((slot, storage)) = dashboard.selected_slot_location() else {
+            dashboard.status = "No slot selected".to_string();
+            continue;
+        };
+        if let Some(busy) = dashboard.views[device].busy {
+            dashboard.status = format!("Waiting for {} to finish", busy.name());
+            continue;
+        }
+        dashboard.views[device].busy = Some(command);
+        dashboard.status = format!(
+            "{} slot {} on {}",
+            command.name(),
+            slot,
+            dashboard.devices[device].riot_ipv6_addr
+        );
+
+        let (environment, selected, config) = (
+            environment.clone(),
+            dashboard.devices[device].clone(),
+            config.clone(),
+        );
+        let device = selected.riot_ipv6_addr.clone();
+        let (updates, transport, runtime) = (updates.clone(), transport.clone(), runtime.clone());
+        thread::spawn(move || {
+            let result = runtime.block_on(run_command(
+                command,
+                &environment,
+                &selected,
+                slot,
+                storage,
+                &config,
+                &transport,
+            ));
+            let _ = updates.send(Update::Finished {
+                device,
+                command,
+                result,
+            });
+        });
+    }
+}
+
+/// Polls the slots, their health and the new log lines of all devices until
+/// the transport is cancelled. The devices are re-read before each round.
+fn poll_devices(
+    devices: &Mutex<Vec<Device>>,
+    refresh: Duration,
+    updates: &Sender<Update>,
+    transport: &TransportOptions,
+) {
+    let mut next_log_lines = BTreeMap::new();
+    while transport.check_cancelled().is_ok() {
+        let start = Instant::now();
+        let devices = devices.lock().unwrap().clone();
+        for device in &devices {
+            let (ip, netif) = (&device.riot_ipv6_addr, &device.host_network_interface);
+            let status = query_slots(ip, netif, transport).map(|slots| {
+                // Only the long-running programs report their health, the
+                // other slots are left out.
+                let running = slots
+                    .iter()
+                    .filter(|s| !s.is_free())
+                    .filter_map(|s| {
+                        probe_health(ip, netif, s.slot, transport)
+                            .ok()
+                            .map(|running| (s.slot, running))
+                    })
+                    .collect::<BTreeMap<usize, bool>>();
+                (slots, running)
+            });
+            let next_log_line = next_log_lines.get(ip).copied().unwrap_or(0);
+            let logs = fetch_logs(ip, netif, None, next_log_line, transport);
+            if transport.check_cancelled().is_err() {
+                return;
+            }
+
+            let mut sent = updates.send(Update::Status {
+                device: ip.clone(),
+                status,
+            });
+            if let Ok(batch) = logs {
+                next_log_lines.insert(ip.clone(), batch.next);
+                if !batch.lines.is_empty() {
+                    sent = sent.and(updates.send(Update::Logs {
+                        device: ip.clone(),
+                        lines: batch.lines,
+                    }));
+                }
+            }
+            if sent.is_err() {
+                return;
+            }
+        }
+
+        while start.elapsed() < refresh {
+            if transport.check_cancelled().is_err() {
+                return;
+            }
+            thread::sleep(INPUT_POLL_INTERVAL);
+        }
+    }
+}
+
+async fn run_command(
+    command: Command,
+    environment: &Environment,
+    device: &Device,
+    slot: usize,
+    storage: StorageBackend,
+    config: &DashboardConfig,
+    transport: &TransportOptions,
+) -> Result<String, String> {
+    let helpers = all::<HelperFunctionID>()
+        .map(|h| h as u8)
+        .collect::<Vec<u8>>();
+    let (ip, netif) = (&device.riot_ipv6_addr, &device.host_network_interface);
+
+    match command {
+        Command::Execute(execution_model) => {
+            execute(
+                ip,
+                config.target,
+                config.binary_layout,
+                slot,
+                netif,
+                execution_model,
+                HelperAccessVerification::AheadOfTime,
+                HelperAccessListSource::ExecuteRequest,
+                &helpers,
+                config.jit,
+                &ExecuteOptions {
+                    jit_compile: config.jit,
+                    ..Default::default()
+                },
+                transport,
+            )
+            .await
+        }
+        Command::Stop => reset_vm(ip, netif, Some(slot), transport)
+            .map(|_| format!("Stopped the program in slot {}", slot)),
+        Command::Deploy => {
+            let Some(source) = &config.deploy_source else {
+                return Err("No program to deploy, use --deploy <source file>".to_string());
+            };
+            deploy(
+                source,
+                &ArtifactStore::from_env(environment),
+                config.target,
+                config.binary_layout,
+                SuitSlot::Fixed(slot),
+                storage,
+                &environment.riot_instance_net_if,
+                ip,
+                netif,
+                &environment.host_ip,
+                &environment.board_name,
+                Some(&environment.micro_bpf_root_dir),
+                helpers,
+                HelperAccessVerification::AheadOfTime,
+                HelperAccessListSource::ExecuteRequest,
+                &DeployOptions::default(),
+                &LocalPublisher::new(&environment.coap_root_dir),
+                transport,
+            )
+            .await
+            .map(|(slot, _)| format!("Deployed {} into slot {}", source, slot))
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, dashboard: &Dashboard, config: &DashboardConfig) {
+    let [main, footer] = split(
+        Direction::Vertical,
+        frame.size(),
+        [Constraint::Min(0), Constraint::Length(1)],
+    );
+    let [devices, details] = split(
+        Direction::Horizontal,
+        main,
+        [Constraint::Length(34), Constraint::Min(0)],
+    );
+    let view = &dashboard.views[dashboard.selected_device];
+    let [slots, result, logs] = split(
+        Direction::Vertical,
+        details,
+        [
+            Constraint::Length(view.slots.len().max(1) as u16 + 3),
+            Constraint::Length(5),
+            Constraint::Min(0),
+        ],
+    );
+
+    draw_devices(frame, devices, dashboard);
+    draw_slots(frame, slots, dashboard);
+    draw_result(frame, result, view);
+
+    // Only the most recent lines that fit are shown.
+    let visible = logs.height.saturating_sub(2) as usize;
+    let lines = view
+        .logs
+        .iter()
+        .skip(view.logs.len().saturating_sub(visible))
+        .map(|line| Line::from(line.as_str()))
+        .collect::<Vec<Line>>();
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Logs")),
+        logs,
+    );
+
+    let deploy = if config.deploy_source.is_some() {
+        "  d deploy"
+    } else {
+        ""
+    };
+    let help = format!(
+        "↑/↓ device  ←/→ slot  e execute  l start  s stop{}  q quit | {}",
+        deploy, dashboard.status
+    );
+    frame.render_widget(
+        Paragraph::new(help).style(Style::default().add_modifier(Modifier::REVERSED)),
+        footer,
+    );
+}
+
+fn draw_devices(frame: &mut Frame, area: Rect, dashboard: &Dashboard) {
+    let items = dashboard
+        .devices
+        .iter()
+        .zip(&dashboard.views)
+        .map(|(device, view)| {
+            let color = match (view.polled, &view.error) {
+                (false, _) => Color::DarkGray,
+                (true, None) => Color::Green,
+                (true, Some(_)) => Color::Red,
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled("● ", Style::default().fg(color)),
+                Span::raw(device.riot_ipv6_addr.as_str()),
+            ]))
+        })
+        .collect::<Vec<ListItem>>();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Devices"))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default().with_selected(Some(dashboard.selected_device));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_slots(frame: &mut Frame, area: Rect, dashboard: &Dashboard) {
+    let view = &dashboard.views[dashboard.selected_device];
+    let rows = view.slots.iter().map(|slot| {
+        let program = match (&slot.name, &slot.version) {
+            _ if slot.is_free() => "<empty>".to_string(),
+            (Some(name), Some(version)) => format!("{} {}", name, version),
+            _ => "unknown".to_string(),
+        };
+        let running = match view.running.get(&slot.slot) {
+            Some(true) => "running",
+            Some(false) => "stopped",
+            None => "-",
+        };
+        Row::new(vec![
+            slot.slot.to_string(),
+            program,
+            slot.size.to_string(),
+            slot.storage.to_string(),
+            running.to_string(),
+            slot.build_hash.clone().unwrap_or_default(),
+        ])
+    });
+    let widths = [
+        Constraint::Length(5),
+        Constraint::Min(20),
+        Constraint::Length(8),
+        Constraint::Length(7),
+        Constraint::Length(8),
+        Constraint::Length(18),
+    ];
+    let title = match &view.error {
+        Some(e) => format!("Slots (unreachable: {})", e.trim()),
+        None => "Slots".to_string(),
+    };
+    let table = Table::new(rows, widths)
+        .header(
+            Row::new(vec!["Slot", "Program", "Size", "Storage", "VM", "Build"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = TableState::default()
+        .with_selected((!view.slots.is_empty()).then_some(dashboard.selected_slot));
+    frame.render_stateful_widget(table, area, &mut state);
+}
+
+fn draw_result(frame: &mut Frame, area: Rect, view: &DeviceView) {
+    let (text, style) = match (&view.busy, &view.last_result) {
+        (Some(command), _) => (
+            format!("{} in progress...", command.name()),
+            Style::default(),
+        ),
+        (None, Some(Ok(response))) => (
+            response.trim_matches(char::from(0)).trim().to_string(),
+            Style::default(),
+        ),
+        (None, Some(Err(e))) => (e.trim().to_string(), Style::default().fg(Color::Red)),
+        (None, None) => (String::new(), Style::default()),
+    };
+    frame.render_widget(
+        Paragraph::new(text)
+            .style(style)
+            .wrap(Wrap { trim: false })
+            .block(Block::default().borders(Borders::ALL).title("Last result")),
+        area,
+    );
+}
+
+fn split<const N: usize>(
+    direction: Direction,
+    area: Rect,
+    constraints: [Constraint; N],
+) -> [Rect; N] {
+    let chunks = Layout::default()
+        .direction(direction)
+        .constraints(constraints)
+        .split(area);
+    core::array::from_fn(|i| chunks[i])
+}
@@ -0,0 +1,627 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use enum_iterator::all;
+use log::debug;
+use micro_bpf_common::{BinaryFileLayout, HelperAccessVerification, HelperFunctionID, TargetVM};
+use micro_bpf_elf_utils::extract_section;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    bytecode::decode,
+    layout::validate,
+    lint::{lint_instructions, LintOptions, Severity},
+    postprocessing::{apply_postprocessing, read_bytes_from_file},
+};
+
+/// Largest object file accepted by the server, the programs running on the
+/// devices are a few kilobytes at most.
+const MAX_OBJECT_SIZE: usize = 1 << 20;
+/// Time after which a client that stopped sending its request is dropped.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// Limits of the request line and the headers, the clients only send a few
+/// short headers.
+const MAX_LINE_LENGTH: usize = 8192;
+const MAX_HEADERS: usize = 64;
+/// Connections handled at the same time, the ones above the limit are
+/// answered with 503 Service Unavailable.
+const MAX_CONNECTIONS: usize = 16;
+/// Number of the most recent artifacts kept in the work directory, the older
+/// ones are removed.
+const MAX_ARTIFACTS: usize = 256;
+
+/// Checks requested for the object file, parsed from the query of the
+/// request, e.g. `POST /verify?layout=ExtendedHeader&target=rBPF&helpers=1,2`.
+#[derive(Debug, Clone)]
+pub struct VerifyOptions {
+    pub layout: BinaryFileLayout,
+    pub target: TargetVM,
+    pub jit: bool,
+    /// Helpers that the program is allowed to call, all helpers are allowed
+    /// if the list is empty (same as when deploying).
+    pub allowed_helpers: Vec<u8>,
+    /// Entry function of a multi-function program.
+    pub entry: Option<String>,
+    /// Reject the program if the lint finds any warnings.
+    pub deny_warnings: bool,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        VerifyOptions {
+            layout: BinaryFileLayout::ExtendedHeader,
+            target: TargetVM::Rbpf,
+            jit: false,
+            allowed_helpers: vec![],
+            entry: None,
+            deny_warnings: false,
+        }
+    }
+}
+
+impl VerifyOptions {
+    pub fn from_query(query: &str) -> Result<Self, String> {
+        let mut options = VerifyOptions::default();
+        for parameter in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = parameter.split_once('=').unwrap_or((parameter, "true"));
+            let flag = || match value {
+                "true" | "1" => Ok(true),
+                "false" | "0" => Ok(false),
+                _ => Err(format!("Invalid value of {}: {}", key, value)),
+            };
+            match key {
+                "layout" => options.layout = BinaryFileLayout::from_str(value)?,
+                "target" => options.target = TargetVM::from_str(value)?,
+                "jit" => options.jit = flag()?,
+                "deny_warnings" => options.deny_warnings = flag()?,
+                "entry" => options.entry = Some(value.to_string()),
+                "helpers" => {
+                    options.allowed_helpers = value
+                        .split(',')
+                        .filter(|helper| !helper.is_empty())
+                        .map(|helper| {
+                            helper
+                                .parse::<u8>()
+                                .map_err(|e| format!("Invalid helper index {}: {}", helper, e))
+                        })
+                        .collect::<Result<_, _>>()?
+                }
+                _ => return Err(format!("Unknown verification option: {}", key)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VerdictFinding {
+    pub severity: String,
+    pub rule: String,
+    pub instruction: usize,
+    pub message: String,
+}
+
+/// Binary produced by the post-processing, downloadable from
+/// `GET /artifacts/<sha256>.bin` until it is evicted by newer artifacts (see
+/// [`MAX_ARTIFACTS`]) or the server is restarted with a different work
+/// directory.
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifiedArtifact {
+    pub path: String,
+    pub sha256: String,
+    pub size: usize,
+}
+
+/// Result of checking an object file, the program is accepted only if it can
+/// be represented using the layout, the lint finds no errors and the
+/// post-processing (including the ahead-of-time helper check) succeeds.
+#[derive(Debug, Clone, Serialize)]
+pub struct Verdict {
+    pub accepted: bool,
+    pub layout: String,
+    pub target: String,
+    pub incompatibilities: Vec<String>,
+    pub findings: Vec<VerdictFinding>,
+    /// Reason why the post-processing failed.
+    pub error: Option<String>,
+    pub artifact: Option<VerifiedArtifact>,
+}
+
+/// Runs the local verification pipeline on the object file, the transformed
+/// artifact is stored in the work directory.
+pub fn verify_object(
+    object: &[u8],
+    options: &VerifyOptions,
+    work_dir: &str,
+) -> Result<Verdict, String> {
+    let mut verdict = Verdict {
+        accepted: false,
+        layout: format!("{:?}", options.layout),
+        target: options.target.to_string(),
+        incompatibilities: vec![],
+        findings: vec![],
+        error: None,
+        artifact: None,
+    };
+
+    if let Err(violation) = validate(options.layout, options.target, object) {
+        verdict.incompatibilities = violation
+            .incompatibilities
+            .iter()
+            .map(|incompatibility| incompatibility.to_string())
+            .collect();
+        return Ok(verdict);
+    }
+
+    let lint_options = LintOptions {
+        target: options.target,
+        jit: options.jit,
+        allowed_helpers: options.allowed_helpers.clone(),
+    };
+    let findings = match extract_section(".text", object).and_then(decode) {
+        Ok(instructions) => lint_instructions(&instructions, &lint_options),
+        Err(e) => {
+            verdict.error = Some(e);
+            return Ok(verdict);
+        }
+    };
+    let rejected = findings
+        .iter()
+        .any(|f| f.severity == Severity::Error || options.deny_warnings);
+    verdict.findings = findings
+        .iter()
+        .map(|finding| VerdictFinding {
+            severity: format!("{:?}", finding.severity),
+            rule: format!("{:?}", finding.rule),
+            instruction: finding.instruction,
+            message: finding.message.clone(),
+        })
+        .collect();
+    if rejected {
+        return Ok(verdict);
+    }
+
+    fs::create_dir_all(work_dir)
+        .map_err(|e| format!("Failed to create the directory {}: {}", work_dir, e))?;
+    // The requests are handled concurrently, each of them needs its own
+    // intermediate files.
+    static REQUESTS: AtomicUsize = AtomicUsize::new(0);
+    let request = REQUESTS.fetch_add(1, Ordering::Relaxed);
+    let object_file = format!("{}/request-{}.o", work_dir, request);
+    let output_file = format!("{}/request-{}.out", work_dir, request);
+    fs::write(&object_file, object)
+        .map_err(|e| format!("Failed to write {}: {}", object_file, e))?;
+
+    let allowed_helpers = if options.allowed_helpers.is_empty() {
+        all::<HelperFunctionID>()
+            .map(|helper| helper as u8)
+            .collect()
+    } else {
+        options.allowed_helpers.clone()
+    };
+    let postprocessing = apply_postprocessing(
+        &object_file,
+        options.layout,
+        &output_file,
+        allowed_helpers,
+        HelperAccessVerification::AheadOfTime,
+        None,
+        options.entry.as_deref(),
+    );
+    let _ = fs::remove_file(&object_file);
+    if let Err(e) = postprocessing {
+        let _ = fs::remove_file(&output_file);
+        verdict.error = Some(e);
+        return Ok(verdict);
+    }
+
+    let artifact = read_bytes_from_file(&output_file);
+    let sha256 = format!("{:x}", Sha256::digest(&artifact));
+    let artifact_file = format!("{}/{}.bin", work_dir, sha256);
+    fs::rename(&output_file, &artifact_file)
+        .map_err(|e| format!("Failed to store the artifact {}: {}", artifact_file, e))?;
+    evict_artifacts(work_dir, MAX_ARTIFACTS);
+    verdict.artifact = Some(VerifiedArtifact {
+        path: format!("/artifacts/{}.bin", sha256),
+        sha256,
+        size: artifact.len(),
+    });
+    verdict.accepted = true;
+    Ok(verdict)
+}
+
+/// Serves the verification pipeline over HTTP so that the CI of other
+/// projects can check their programs without installing the toolchain:
+///
+/// - `POST /verify?<options>` with the object file as the body responds with
+///   the [`Verdict`] as JSON (see [`VerifyOptions::from_query`] for the
+///   options), the status is 200 also if the program is rejected,
+/// - `GET /artifacts/<sha256>.bin` downloads the transformed binary,
+/// - `GET /health` responds with `ok`.
+///
+/// Each connection is handled on its own thread (at most [`MAX_CONNECTIONS`]
+/// at a time), the server runs until the process is terminated.
+pub fn run_verify_server(listen_addr: &str, work_dir: &str) -> Result<(), String> {
+    let listener = TcpListener::bind(listen_addr)
+        .map_err(|e| format!("Failed to bind to {}: {}", listen_addr, e))?;
+    fs::create_dir_all(work_dir)
+        .map_err(|e| format!("Failed to create the directory {}: {}", work_dir, e))?;
+    println!(
+        "[verify-server] listening on {}, artifacts stored in {}",
+        listen_addr, work_dir
+    );
+
+    let connections = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                debug!("Failed to accept a connection: {}", e);
+                continue;
+            }
+        };
+        let Some(connection) = ConnectionSlot::acquire(&connections) else {
+            let _ = stream.set_write_timeout(Some(READ_TIMEOUT));
+            let response = Response::text(503, "Too many concurrent requests");
+            if let Err(e) = write_response(stream, &response) {
+                debug!("Failed to reject the connection: {}", e);
+            }
+            continue;
+        };
+        let work_dir = work_dir.to_string();
+        thread::spawn(move || {
+            let _connection = connection;
+            if let Err(e) = handle_connection(stream, &work_dir) {
+                debug!("Failed to handle the request: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// One of the [`MAX_CONNECTIONS`] connections handled at the same time, it is
+/// released once dropped.
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    fn acquire(connections: &Arc<AtomicUsize>) -> Option<Self> {
+        connections
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |active| {
+                (active < MAX_CONNECTIONS).then_some(active + 1)
+            })
+            .ok()
+            .map(|_| ConnectionSlot(connections.clone()))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Removes the least recently stored artifacts so that at most `keep` of them
+/// remain in the work directory.
+fn evict_artifacts(work_dir: &str, keep: usize) {
+    let Ok(entries) = fs::read_dir(work_dir) else {
+        return;
+    };
+    let mut artifacts = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| is_artifact_name(&entry.file_name().to_string_lossy()))
+        .filter_map(|entry| {
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+            Some((modified, entry.path()))
+        })
+        .collect::<Vec<_>>();
+    if artifacts.len() <= keep {
+        return;
+    }
+    artifacts.sort();
+    for (_, path) in &artifacts[..artifacts.len() - keep] {
+        debug!("Evicting the artifact {}", path.display());
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// Only the names of the stored artifacts (`<sha256>.bin`) are served, so
+/// that the requests can't reach outside of the work directory.
+fn is_artifact_name(name: &str) -> bool {
+    name.strip_suffix(".bin")
+        .is_some_and(|digest| !digest.is_empty() && digest.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn text(status: u16, body: impl ToString) -> Self {
+        Response {
+            status,
+            content_type: "text/plain",
+            body: body.to_string().into_bytes(),
+        }
+    }
+}
+
+fn handle_connection(stream: TcpStream, work_dir: &str) -> Result<(), String> {
+    stream
+        .set_read_timeout(Some(READ_TIMEOUT))
+        .map_err(|e| format!("Failed to set the read timeout: {}", e))?;
+    let peer = stream
+        .peer_addr()
+        .map(|addr| addr.to_string())
+        .unwrap_or_default();
+    let mut reader = BufReader::new(
+        stream
+            .try_clone()
+            .map_err(|e| format!("Failed to clone the stream: {}", e))?,
+    );
+
+    let (method, target, response) = match read_request(&mut reader) {
+        Ok((method, target, body)) => {
+            let response = route(&method, &target, &body, work_dir);
+            (method, target, response)
+        }
+        Err(response) => (String::new(), String::new(), response),
+    };
+    println!(
+        "[verify-server] {} {} {} -> {}",
+        peer, method, target, response.status
+    );
+    write_response(stream, &response)
+}
+
+/// Reads the method, the target and the body of the request, the requests
+/// exceeding the limits are answered with the returned error response.
+fn read_request(reader: &mut impl BufRead) -> Result<(String, String, Vec<u8>), Response> {
+    let request_line = read_line(reader)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    let mut headers = 0;
+    loop {
+        let header = read_line(reader)?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        headers += 1;
+        if headers > MAX_HEADERS {
+            return Err(Response::text(
+                431,
+                format!("The request has more than {} headers", MAX_HEADERS),
+            ));
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse::<usize>().map_err(|e| {
+                    Response::text(400, format!("Invalid Content-Length {}: {}", value, e))
+                })?;
+            }
+        }
+    }
+
+    if content_length > MAX_OBJECT_SIZE {
+        return Err(Response::text(
+            413,
+            format!("The object file exceeds {} bytes", MAX_OBJECT_SIZE),
+        ));
+    }
+    let mut body = vec![0; content_length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|e| Response::text(400, format!("Failed to read the request body: {}", e)))?;
+    Ok((method, target, body))
+}
+
+/// Reads a line of at most [`MAX_LINE_LENGTH`] bytes.
+fn read_line(reader: &mut impl BufRead) -> Result<String, Response> {
+    let mut line = String::new();
+    reader
+        .take(MAX_LINE_LENGTH as u64)
+        .read_line(&mut line)
+        .map_err(|e| Response::text(400, format!("Failed to read the request: {}", e)))?;
+    if line.len() == MAX_LINE_LENGTH && !line.ends_with('\n') {
+        return Err(Response::text(
+            431,
+            format!(
+                "The request line or header exceeds {} bytes",
+                MAX_LINE_LENGTH
+            ),
+        ));
+    }
+    Ok(line)
+}
+
+fn route(method: &str, target: &str, body: &[u8], work_dir: &str) -> Response {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    match (method, path) {
+        ("GET", "/health") => Response::text(200, "ok"),
+        ("POST", "/verify") => {
+            let options = match VerifyOptions::from_query(query) {
+                Ok(options) => options,
+                Err(e) => return Response::text(400, e),
+            };
+            match verify_object(body, &options, work_dir) {
+                Ok(verdict) => Response {
+                    status: 200,
+                    content_type: "application/json",
+                    body: serde_json::to_vec_pretty(&verdict).unwrap_or_default(),
+                },
+                Err(e) => Response::text(500, e),
+            }
+        }
+        ("GET", path) if path.starts_with("/artifacts/") => {
+            let name = &path["/artifacts/".len()..];
+            if !is_artifact_name(name) {
+                return Response::text(404, "Not found");
+            }
+            match fs::read(format!("{}/{}", work_dir, name)) {
+                Ok(artifact) => Response {
+                    status: 200,
+                    content_type: "application/octet-stream",
+                    body: artifact,
+                },
+                Err(_) => Response::text(404, "Not found"),
+            }
+        }
+        _ => Response::text(404, "Not found"),
+    }
+}
+
+fn write_response(mut stream: TcpStream, response: &Response) -> Result<(), String> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    let header = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason,
+        response.content_type,
+        response.body.len()
+    );
+    stream
+        .write_all(header.as_bytes())
+        .and_then(|_| stream.write_all(&response.body))
+        .map_err(|e| format!("Failed to send the response: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs::File, path::PathBuf, process, time::SystemTime};
+
+    use super::*;
+
+    fn work_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("mibpf-verify-server-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn request(text: &str) -> Result<(String, String, Vec<u8>), u16> {
+        read_request(&mut text.as_bytes()).map_err(|response| response.status)
+    }
+
+    #[test]
+    fn options_are_parsed_from_the_query() {
+        let options = VerifyOptions::from_query(
+            "layout=RawObjectFile&target=FemtoContainer&jit=1&helpers=1,16&entry=main&deny_warnings",
+        )
+        .unwrap();
+        assert_eq!(options.layout, BinaryFileLayout::RawObjectFile);
+        assert_eq!(options.target, TargetVM::FemtoContainer);
+        assert!(options.jit);
+        assert_eq!(options.allowed_helpers, vec![1, 16]);
+        assert_eq!(options.entry.as_deref(), Some("main"));
+        assert!(options.deny_warnings);
+
+        let defaults = VerifyOptions::from_query("").unwrap();
+        assert_eq!(defaults.layout, BinaryFileLayout::ExtendedHeader);
+        assert_eq!(defaults.target, TargetVM::Rbpf);
+        assert!(defaults.allowed_helpers.is_empty());
+    }
+
+    #[test]
+    fn invalid_options_are_rejected() {
+        for query in ["jit=yes", "helpers=1,300", "layout=Unknown", "verbose=1"] {
+            assert!(VerifyOptions::from_query(query).is_err(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn requests_are_routed() {
+        let dir = work_dir("route");
+        let dir = dir.to_str().unwrap();
+        assert_eq!(route("GET", "/health", &[], dir).status, 200);
+        assert_eq!(route("GET", "/verify", &[], dir).status, 404);
+        assert_eq!(route("POST", "/verify?jit=yes", &[], dir).status, 400);
+        assert_eq!(route("DELETE", "/artifacts/ab.bin", &[], dir).status, 404);
+    }
+
+    #[test]
+    fn only_stored_artifacts_are_served() {
+        let dir = work_dir("artifacts");
+        fs::write(dir.join("ab12.bin"), b"artifact").unwrap();
+        fs::write(dir.join("secret.bin"), b"secret").unwrap();
+        let dir = dir.to_str().unwrap();
+
+        let response = route("GET", "/artifacts/ab12.bin", &[], dir);
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"artifact");
+        for path in [
+            "/artifacts/secret.bin",
+            "/artifacts/../ab12.bin",
+            "/artifacts/.bin",
+            "/artifacts/ab12",
+            "/artifacts/cd34.bin",
+        ] {
+            assert_eq!(route("GET", path, &[], dir).status, 404, "{}", path);
+        }
+    }
+
+    #[test]
+    fn request_is_read_within_the_limits() {
+        let (method, target, body) =
+            request("POST /verify?jit=1 HTTP/1.1\r\nContent-Length: 4\r\n\r\nbody").unwrap();
+        assert_eq!(
+            (method.as_str(), target.as_str()),
+            ("POST", "/verify?jit=1")
+        );
+        assert_eq!(body, b"body");
+
+        let long_line = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE_LENGTH));
+        assert_eq!(request(&long_line), Err(431));
+        let many_headers = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "X-Header: 1\r\n".repeat(MAX_HEADERS + 1)
+        );
+        assert_eq!(request(&many_headers), Err(431));
+        let large_body = format!(
+            "POST /verify HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_OBJECT_SIZE + 1
+        );
+        assert_eq!(request(&large_body), Err(413));
+    }
+
+    #[test]
+    fn oldest_artifacts_are_evicted() {
+        let dir = work_dir("eviction");
+        let now = SystemTime::now();
+        for (i, name) in ["aa.bin", "bb.bin", "cc.bin"].iter().enumerate() {
+            let file = File::create(dir.join(name)).unwrap();
+            file.set_modified(now - Duration::from_secs(10 - i as u64))
+                .unwrap();
+        }
+        fs::write(dir.join("request-0.o"), b"").unwrap();
+
+        evict_artifacts(dir.to_str().unwrap(), 2);
+        assert!(!dir.join("aa.bin").exists());
+        assert!(dir.join("bb.bin").exists());
+        assert!(dir.join("cc.bin").exists());
+        assert!(dir.join("request-0.o").exists());
+    }
+}
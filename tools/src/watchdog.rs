@@ -0,0 +1,414 @@
+use std::{str::FromStr, thread, time::Duration};
+
+use enum_iterator::all;
+use log::{debug, warn};
+use micro_bpf_common::{
+    BinaryFileLayout, ExecutionModel, HelperAccessListSource, HelperAccessVerification,
+    HelperFunctionID, StorageBackend, TargetVM,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    artifacts::ArtifactStore,
+    deploy::{deploy, DeployOptions},
+    publish::LocalPublisher,
+    record::ResultRecorder,
+    environment::Environment,
+    execute::{execute, ExecuteOptions},
+    fleet::{device_clock, Device, Fleet, FleetProgram},
+    registry::Registry,
+    slots::{query_slots, SuitSlot},
+    transport::{is_cancelled, send_request, TransportOptions},
+    webhook::post_json,
+};
+
+/// Controls how often the long-running program is probed and when the
+/// watchdog decides to restart it.
+#[derive(Debug, Clone)]
+pub struct WatchdogConfig {
+    /// Time between two consecutive health probes.
+    pub interval: Duration,
+    /// Number of consecutive failed probes after which the program is
+    /// considered dead and gets restarted.
+    pub failure_threshold: u32,
+    /// Maximum number of restarts before the watchdog gives up, None means
+    /// that the program is restarted indefinitely.
+    pub max_restarts: Option<u32>,
+    /// Webhook notified whenever the program dies or gets restarted.
+    pub notify_url: Option<String>,
+    /// If specified, the program is compiled and deployed again from this
+    /// source file before being restarted (e.g. in case the device rebooted
+    /// and lost the contents of its SUIT storage).
+    pub redeploy_source: Option<String>,
+}
+
+/// Response of the health resource of the long-running VMs:
+/// `{"slot": 0, "running": true}`
+#[derive(Deserialize, Debug)]
+struct HealthResponse {
+    running: bool,
+}
+
+/// Asks the device whether the long-running VM executing the program from
+/// the given SUIT storage slot is still alive.
+pub fn probe_health(
+    riot_ipv6_addr: &str,
+    host_network_interface: &str,
+    suit_storage_slot: usize,
+    transport: &TransportOptions,
+) -> Result<bool, String> {
+    let url = format!(
+        "coap://[{}%{}]/long-running/health?slot={}",
+        riot_ipv6_addr, host_network_interface, suit_storage_slot
+    );
+    debug!("Probing the health resource: {}", url);
+
+    let output = send_request("GET", &url, None, transport)?;
+
+    if output.stderr.len() > 0 {
+        let stderr = String::from_utf8(output.stderr)
+            .map_err(|e| format!("Failed to parse the stderr: {}", e))?;
+        Err(format!("aiocoap-client failed with: {}", stderr))?
+    }
+
+    let response = String::from_utf8(output.stdout)
+        .map_err(|e| format!("Failed to parse the response: {}", e))?;
+
+    serde_json::from_str::<HealthResponse>(response.trim_matches(char::from(0)))
+        .map(|r| r.running)
+        .map_err(|e| format!("Failed to parse the health response: {}", e))
+}
+
+/// Periodically probes the long-running program deployed on the device
+/// described by the environment and restarts it once it fails to respond to
+/// `failure_threshold` consecutive probes. Returns only if the watchdog gives
+/// up after exceeding the maximum number of restarts.
+pub async fn watch(
+    environment: &Environment,
+    suit_storage_slot: usize,
+    target: TargetVM,
+    binary_layout: BinaryFileLayout,
+    jit: bool,
+    config: &WatchdogConfig,
+    mut recorder: Option<&mut ResultRecorder>,
+    transport: &TransportOptions,
+) -> Result<(), String> {
+    // Both the probes and the events are recorded, a failure to record them
+    // shouldn't stop the watchdog.
+    let record = |recorder: &mut Option<&mut ResultRecorder>, result: serde_json::Value| {
+        if let Some(recorder) = recorder.as_deref_mut() {
+            if let Err(e) = recorder.record(&environment.riot_instance_ip, "watchdog", result) {
+                warn!("Failed to record the watchdog result: {}", e);
+            }
+        }
+    };
+    let notify =
+        |recorder: &mut Option<&mut ResultRecorder>, event: &str, details: serde_json::Value| {
+            println!("[watchdog] {}: {}", event, details);
+            record(
+                recorder,
+                json!({ "slot": suit_storage_slot, "event": event, "details": details }),
+            );
+            let Some(url) = &config.notify_url else {
+                return;
+            };
+            let body = json!({
+                "event": event,
+                "device": environment.riot_instance_ip,
+                "slot": suit_storage_slot,
+                "details": details,
+            });
+            if let Err(e) = post_json(url, &body) {
+                warn!("Failed to send the watchdog notification: {}", e);
+            }
+        };
+
+    let mut failed_probes = 0;
+    let mut restarts = 0;
+    loop {
+        thread::sleep(config.interval);
+        transport.check_cancelled()?;
+
+        let probe = probe_health(
+            &environment.riot_instance_ip,
+            &environment.host_net_if,
+            suit_storage_slot,
+            transport,
+        );
+        if let Ok(running) = &probe {
+            record(
+                &mut recorder,
+                json!({ "slot": suit_storage_slot, "running": running }),
+            );
+        }
+        match probe {
+            Ok(true) => failed_probes = 0,
+            Ok(false) => failed_probes += 1,
+            // The probe failing because the watchdog is stopped mustn't
+            // trigger a restart.
+            Err(e) if is_cancelled(&e) => return Err(e),
+            Err(e) => {
+                debug!("Health probe failed: {}", e);
+                failed_probes += 1;
+            }
+        }
+
+        if failed_probes < config.failure_threshold {
+            continue;
+        }
+
+        notify(
+            &mut recorder,
+            "died",
+            json!({ "failed_probes": failed_probes }),
+        );
+
+        if config.max_restarts.map_or(false, |max| restarts >= max) {
+            notify(&mut recorder, "gave-up", json!({ "restarts": restarts }));
+            return Err(format!(
+                "The program in slot {} died after {} restarts",
+                suit_storage_slot, restarts
+            ));
+        }
+
+        restarts += 1;
+        match restart(
+            environment,
+            suit_storage_slot,
+            target,
+            binary_layout,
+            jit,
+            config,
+            transport,
+        )
+        .await
+        {
+            Ok(()) => notify(&mut recorder, "restarted", json!({ "restarts": restarts })),
+            Err(e) => notify(&mut recorder, "restart-failed", json!({ "error": e })),
+        }
+        failed_probes = 0;
+    }
+}
+
+async fn restart(
+    environment: &Environment,
+    suit_storage_slot: usize,
+    target: TargetVM,
+    binary_layout: BinaryFileLayout,
+    jit: bool,
+    config: &WatchdogConfig,
+    transport: &TransportOptions,
+) -> Result<(), String> {
+    let helpers = all::<HelperFunctionID>()
+        .map(|h| h as u8)
+        .collect::<Vec<u8>>();
+
+    if let Some(source) = &config.redeploy_source {
+        deploy(
+            source,
+            &ArtifactStore::from_env(environment),
+            target,
+            binary_layout,
+            SuitSlot::Fixed(suit_storage_slot),
+            StorageBackend::Ram,
+            &environment.riot_instance_net_if,
+            &environment.riot_instance_ip,
+            &environment.host_net_if,
+            &environment.host_ip,
+            &environment.board_name,
+            Some(&environment.micro_bpf_root_dir),
+            helpers.clone(),
+            HelperAccessVerification::AheadOfTime,
+            HelperAccessListSource::ExecuteRequest,
+            &DeployOptions::default(),
+            &LocalPublisher::new(&environment.coap_root_dir),
+            transport,
+        )
+        .await?;
+    }
+
+    execute(
+        &environment.riot_instance_ip,
+        target,
+        binary_layout,
+        suit_storage_slot,
+        &environment.host_net_if,
+        ExecutionModel::LongRunning,
+        HelperAccessVerification::AheadOfTime,
+        HelperAccessListSource::ExecuteRequest,
+        &helpers,
+        jit,
+        &ExecuteOptions {
+            jit_compile: jit,
+            ..Default::default()
+        },
+        transport,
+    )
+    .await
+    .map(|_| ())
+}
+
+/// Monitors the devices of the fleet and re-deploys the programs marked as
+/// persistent in the fleet config once a device reboots and loses the
+/// contents of its RAM slots. A reboot is detected by the device clock (time
+/// since boot) going backwards between two consecutive probes.
+///
+/// Only the programs that the registry records as deployed on the device are
+/// re-deployed, the other devices of the fleet might have never received
+/// them. Returns only if the supervision is cancelled.
+pub async fn supervise_fleet(
+    environment: &Environment,
+    fleet: &Fleet,
+    interval: Duration,
+    notify_url: Option<&str>,
+    transport: &TransportOptions,
+) -> Result<(), String> {
+    // The programs in the flash regions survive the reboots.
+    let persistent = fleet
+        .programs
+        .iter()
+        .filter(|p| p.persistent && p.storage == StorageBackend::Ram)
+        .collect::<Vec<&FleetProgram>>();
+    if persistent.is_empty() {
+        return Err("The fleet config doesn't specify any persistent programs".to_string());
+    }
+
+    let notify = |device: &Device, event: &str, details: serde_json::Value| {
+        println!(
+            "[watchdog] {}: {}: {}",
+            device.riot_ipv6_addr, event, details
+        );
+        let Some(url) = notify_url else {
+            return;
+        };
+        let body = json!({
+            "event": event,
+            "device": device.riot_ipv6_addr,
+            "details": details,
+        });
+        if let Err(e) = post_json(url, &body) {
+            warn!("Failed to send the watchdog notification: {}", e);
+        }
+    };
+
+    let mut uptimes = vec![None; fleet.devices.len()];
+    loop {
+        for (device, uptime) in fleet.devices.iter().zip(uptimes.iter_mut()) {
+            transport.check_cancelled()?;
+            let now = match device_clock(device, transport) {
+                Ok(now) => now,
+                Err(e) if is_cancelled(&e) => return Err(e),
+                // The device might be in the middle of rebooting.
+                Err(e) => {
+                    debug!(
+                        "Failed to read the clock of {}: {}",
+                        device.riot_ipv6_addr, e
+                    );
+                    continue;
+                }
+            };
+            let rebooted = uptime.is_some_and(|previous| now < previous);
+            *uptime = Some(now);
+            if !rebooted {
+                continue;
+            }
+
+            notify(device, "rebooted", json!({ "uptime_ms": now }));
+            let programs = match programs_to_redeploy(device, &persistent, transport) {
+                Ok(programs) => programs,
+                Err(e) => {
+                    notify(device, "redeploy-failed", json!({ "error": e }));
+                    continue;
+                }
+            };
+            for program in programs {
+                match redeploy(environment, device, program, transport).await {
+                    Ok(slot) => notify(
+                        device,
+                        "redeployed",
+                        json!({ "program": program.source, "slot": slot }),
+                    ),
+                    Err(e) if is_cancelled(&e) => return Err(e),
+                    Err(e) => notify(
+                        device,
+                        "redeploy-failed",
+                        json!({ "program": program.source, "error": e }),
+                    ),
+                }
+            }
+        }
+
+        thread::sleep(interval);
+    }
+}
+
+/// Selects the persistent programs whose RAM slots are empty after the
+/// reboot of the device.
+fn programs_to_redeploy<'a>(
+    device: &Device,
+    persistent: &[&'a FleetProgram],
+    transport: &TransportOptions,
+) -> Result<Vec<&'a FleetProgram>, String> {
+    let slots = query_slots(
+        &device.riot_ipv6_addr,
+        &device.host_network_interface,
+        transport,
+    )?;
+    let registry = Registry::load()?;
+    let records = registry.device(&device.riot_ipv6_addr).map(|d| &d.slots);
+
+    Ok(persistent
+        .iter()
+        .filter(|program| {
+            let empty = slots
+                .iter()
+                .any(|s| s.slot == program.slot && s.is_free() && s.storage == StorageBackend::Ram);
+            let deployed = records
+                .and_then(|r| r.get(&program.slot))
+                .is_some_and(|r| r.program == program.source);
+            if empty && !deployed {
+                debug!(
+                    "Skipping {}, the registry doesn't record it in slot {} of {}",
+                    program.source, program.slot, device.riot_ipv6_addr
+                );
+            }
+            empty && deployed
+        })
+        .copied()
+        .collect())
+}
+
+async fn redeploy(
+    environment: &Environment,
+    device: &Device,
+    program: &FleetProgram,
+    transport: &TransportOptions,
+) -> Result<usize, String> {
+    let helpers = all::<HelperFunctionID>()
+        .map(|h| h as u8)
+        .collect::<Vec<u8>>();
+
+    deploy(
+        &program.source,
+        &ArtifactStore::from_env(environment),
+        TargetVM::from_str(&program.target)?,
+        program.binary_layout.parse::<BinaryFileLayout>()?,
+        SuitSlot::Fixed(program.slot),
+        program.storage,
+        &environment.riot_instance_net_if,
+        &device.riot_ipv6_addr,
+        &device.host_network_interface,
+        &environment.host_ip,
+        &environment.board_name,
+        Some(&environment.micro_bpf_root_dir),
+        helpers,
+        HelperAccessVerification::AheadOfTime,
+        HelperAccessListSource::ExecuteRequest,
+        &DeployOptions::default(),
+        &LocalPublisher::new(&environment.coap_root_dir),
+        transport,
+    )
+    .await
+    .map(|(slot, _)| slot)
+}
@@ -0,0 +1,65 @@
+use std::process::Command;
+
+use log::debug;
+use serde_json::{json, Value};
+
+use crate::registry::now;
+
+/// Sends the JSON value to the HTTP endpoint using a POST request. Similar
+/// to the CoAP requests, we shell out to an external client (curl) instead of
+/// pulling an HTTP client library into the tool.
+pub fn post_json(url: &str, body: &Value) -> Result<(), String> {
+    debug!("Posting to the webhook {}: {}", url, body);
+
+    let output = Command::new("curl")
+        .arg("--silent")
+        .arg("--show-error")
+        .arg("--fail")
+        .arg("-X")
+        .arg("POST")
+        .arg("-H")
+        .arg("Content-Type: application/json")
+        .arg("--data")
+        .arg(body.to_string())
+        .arg(url)
+        .output()
+        .map_err(|e| format!("Failed to run curl: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Webhook request to {} failed: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(())
+}
+
+/// Builds the JSON summary of the result of a command performed on a device
+/// (deployment, execution or benchmark). The `text` field is a one-line
+/// description so that the summary can be posted directly to a Slack
+/// incoming webhook, the remaining fields are meant for CI integrations.
+pub fn result_summary(
+    action: &str,
+    device: &str,
+    slot: impl ToString,
+    program: Option<&str>,
+    result: &Result<(), String>,
+) -> Value {
+    let slot = slot.to_string();
+    let outcome = match result {
+        Ok(()) => "ok".to_string(),
+        Err(e) => e.lines().next().unwrap_or_default().to_string(),
+    };
+    json!({
+        "text": format!("{} of slot {} on {}: {}", action, slot, device, outcome),
+        "action": action,
+        "device": device,
+        "slot": slot,
+        "program": program,
+        "success": result.is_ok(),
+        "outcome": outcome,
+        "timestamp": now(),
+    })
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::{
+    arithmetic::{expression, write_program, GENERATED_PROGRAM},
+    deploy_test_script_into_slot, execute_deployed_program,
+    lease::lease_slot,
+    load_test_env,
+    tags::skip_unless_selected,
+    timing::assert_within_time_bound,
+};
+use enum_iterator::all;
+use micro_bpf_common::{BinaryFileLayout, HelperFunctionID, TargetVM};
+use proptest::prelude::*;
+
+// Property-based counterpart of the arithmetic tests in `jit_tests.rs`. Each
+// case generates a small C program evaluating a random arithmetic expression,
+// computes the expected result on the host, deploys the program and checks
+// that both the interpreter and the JIT return the expected value. Failing
+// cases are shrunk to the smallest expression that still fails, the last
+// generated program is left in tests/test-sources/generated_arithmetic.c.
+//
+// Every case deploys a program to the device, because of this the number of
+// cases is kept low by default, it can be changed by setting PROPTEST_CASES.
+
+fn cases() -> u32 {
+    std::env::var("PROPTEST_CASES")
+        .ok()
+        .and_then(|cases| cases.parse().ok())
+        .unwrap_or(32)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: cases(),
+        max_shrink_iters: 64,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn arithmetic_expressions(expression in expression()) {
+        let expected = expression.evaluate() as i32;
+        write_program(&expression).map_err(TestCaseError::fail)?;
+        if skip_unless_selected(GENERATED_PROGRAM) {
+            return Ok(());
+        }
+
+        let lease = lease_slot(&load_test_env());
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let helpers = all::<HelperFunctionID>()
+            .map(|e| e as u8)
+            .collect::<Vec<u8>>();
+        runtime
+            .block_on(deploy_test_script_into_slot(
+                GENERATED_PROGRAM,
+                BinaryFileLayout::RawObjectFile,
+                &lease.environment,
+                helpers,
+                lease.slot,
+            ))
+            .map_err(TestCaseError::fail)?;
+
+        for jit in [false, true] {
+            let response = runtime
+                .block_on(execute_deployed_program(
+                    lease.slot,
+                    BinaryFileLayout::RawObjectFile,
+                    TargetVM::Rbpf,
+                    &lease.environment,
+                    jit,
+                ))
+                .map_err(TestCaseError::fail)?;
+            prop_assert_eq!(response.result, expected, "jit: {}", jit);
+            assert_within_time_bound(GENERATED_PROGRAM, response.execution_time);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::{fmt::Write, fs};
+
+use proptest::prelude::*;
+
+use super::TEST_SOURCES_DIR;
+
+/// Name of the source file that the generated programs are written into.
+pub const GENERATED_PROGRAM: &str = "generated_arithmetic.c";
+
+/// Binary operators used in the generated expressions.
+#[derive(Debug, Clone, Copy)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    And,
+    Or,
+    Xor,
+    ShiftLeft,
+    ShiftRight,
+}
+
+/// Arithmetic expression over unsigned 32-bit integers. We only use unsigned
+/// arithmetic so that none of the generated programs has undefined behaviour
+/// (e.g. signed overflow) which the compiler could exploit.
+#[derive(Debug, Clone)]
+pub enum Expression {
+    Constant(u32),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    /// Computes the result the way the C code emitted by [`Self::to_c`] does.
+    pub fn evaluate(&self) -> u32 {
+        let (operator, lhs, rhs) = match self {
+            Expression::Constant(value) => return *value,
+            Expression::Binary(operator, lhs, rhs) => (operator, lhs, rhs),
+        };
+        let (lhs, rhs) = (lhs.evaluate(), rhs.evaluate());
+        match operator {
+            Operator::Add => lhs.wrapping_add(rhs),
+            Operator::Subtract => lhs.wrapping_sub(rhs),
+            Operator::Multiply => lhs.wrapping_mul(rhs),
+            // The divisor is made odd to avoid division by zero.
+            Operator::Divide => lhs / (rhs | 1),
+            Operator::Modulo => lhs % (rhs | 1),
+            Operator::And => lhs & rhs,
+            Operator::Or => lhs | rhs,
+            Operator::Xor => lhs ^ rhs,
+            Operator::ShiftLeft => lhs << (rhs & 31),
+            Operator::ShiftRight => lhs >> (rhs & 31),
+        }
+    }
+
+    /// Emits the expression as C code. Every constant is first stored in a
+    /// volatile variable, otherwise the compiler would fold the whole
+    /// expression into a single constant and no arithmetic instructions
+    /// would end up in the program.
+    pub fn to_c(&self, declarations: &mut String) -> String {
+        match self {
+            Expression::Constant(value) => {
+                let variable = format!("v{}", declarations.lines().count());
+                let _ = writeln!(
+                    declarations,
+                    "    volatile uint32_t {} = {}u;",
+                    variable, value
+                );
+                variable
+            }
+            Expression::Binary(operator, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.to_c(declarations), rhs.to_c(declarations));
+                match operator {
+                    Operator::Add => format!("({} + {})", lhs, rhs),
+                    Operator::Subtract => format!("({} - {})", lhs, rhs),
+                    Operator::Multiply => format!("({} * {})", lhs, rhs),
+                    Operator::Divide => format!("({} / ({} | 1u))", lhs, rhs),
+                    Operator::Modulo => format!("({} % ({} | 1u))", lhs, rhs),
+                    Operator::And => format!("({} & {})", lhs, rhs),
+                    Operator::Or => format!("({} | {})", lhs, rhs),
+                    Operator::Xor => format!("({} ^ {})", lhs, rhs),
+                    Operator::ShiftLeft => format!("({} << ({} & 31u))", lhs, rhs),
+                    Operator::ShiftRight => format!("({} >> ({} & 31u))", lhs, rhs),
+                }
+            }
+        }
+    }
+}
+
+fn operator() -> impl Strategy<Value = Operator> {
+    prop_oneof![
+        Just(Operator::Add),
+        Just(Operator::Subtract),
+        Just(Operator::Multiply),
+        Just(Operator::Divide),
+        Just(Operator::Modulo),
+        Just(Operator::And),
+        Just(Operator::Or),
+        Just(Operator::Xor),
+        Just(Operator::ShiftLeft),
+        Just(Operator::ShiftRight),
+    ]
+}
+
+/// Generates expressions of depth at most 4. Small constants are preferred
+/// as they are more likely to produce results which are easy to reason about
+/// once the failing case is shrunk.
+pub fn expression() -> impl Strategy<Value = Expression> {
+    let constant = prop_oneof![3 => 0..100u32, 1 => any::<u32>()].prop_map(Expression::Constant);
+    constant.prop_recursive(4, 16, 2, |inner| {
+        (operator(), inner.clone(), inner).prop_map(|(operator, lhs, rhs)| {
+            Expression::Binary(operator, Box::new(lhs), Box::new(rhs))
+        })
+    })
+}
+
+/// Writes the program computing the expression into the test sources
+/// directory. The expected result is recorded on the first line in the same
+/// format as the hand-written tests use, the return value is the result
+/// reinterpreted as a signed integer.
+pub fn write_program(expression: &Expression) -> Result<(), String> {
+    let mut declarations = String::new();
+    let body = expression.to_c(&mut declarations);
+    let program = format!(
+        "// TEST_RESULT: {}\n\
+         // TEST_TAGS: arithmetic, generated\n\
+         // TEST_MAX_TIME_MS: 1\n\
+         #include <stdint.h>\n\
+         int generated_arithmetic(void *ctx) {{\n\
+         {}    uint32_t ret = {};\n\
+         \x20   return (int)ret;\n\
+         }}\n",
+        expression.evaluate() as i32,
+        declarations,
+        body
+    );
+    let path = format!("{}/{}", TEST_SOURCES_DIR, GENERATED_PROGRAM);
+    fs::write(&path, program).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
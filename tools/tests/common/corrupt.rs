@@ -0,0 +1,163 @@
+use std::fs;
+
+use enum_iterator::all;
+use micro_bpf_common::{
+    BinaryFileLayout, HelperAccessListSource, HelperAccessVerification, HelperFunctionID,
+    StorageBackend, TargetVM,
+};
+use micro_bpf_elf_utils::extract_text_section;
+use micro_bpf_tools::{
+    apply_postprocessing, compile, pull, resolve_interface, sign, Environment, TransportOptions,
+};
+
+use super::{
+    execute_deployed_program,
+    lease::{lease_slot, lock_deployments},
+    tags::skip_unless_selected,
+    test_artifacts, TEST_SOURCES_DIR,
+};
+
+const CORRUPTED_BINARY: &str = "corrupted.bin";
+
+/// Ways in which a binary can be damaged before it is deployed, used for
+/// checking that the verifier on the device rejects malformed programs.
+#[derive(Debug, Clone, Copy)]
+pub enum Corruption {
+    /// Inverts `count` bytes of the .text section starting at `offset`.
+    FlipTextBytes { offset: usize, count: usize },
+    /// Cuts off everything after the first `length` bytes of the binary.
+    Truncate { length: usize },
+    /// Overwrites the magic number at the start of the header (or the ELF
+    /// magic in case of the raw object files).
+    BreakHeaderMagic,
+}
+
+/// Applies the corruption to the binary produced for the given layout.
+pub fn corrupt(
+    binary: &mut Vec<u8>,
+    layout: BinaryFileLayout,
+    corruption: Corruption,
+) -> Result<(), String> {
+    match corruption {
+        Corruption::FlipTextBytes { offset, count } => {
+            let text = extract_text_section(binary, layout)?;
+            let text_start = text.as_ptr() as usize - binary.as_ptr() as usize;
+            if offset + count > text.len() {
+                return Err(format!(
+                    "Can't flip {} bytes at offset {} of the {}B .text section",
+                    count,
+                    offset,
+                    text.len()
+                ));
+            }
+            for byte in &mut binary[text_start + offset..text_start + offset + count] {
+                *byte = !*byte;
+            }
+        }
+        Corruption::Truncate { length } => binary.truncate(length),
+        Corruption::BreakHeaderMagic => {
+            if layout == BinaryFileLayout::OnlyTextSection {
+                return Err("The OnlyTextSection binaries don't have a header".to_string());
+            }
+            binary[..4].copy_from_slice(&0xdeadbeef_u32.to_le_bytes());
+        }
+    }
+    Ok(())
+}
+
+/// Compiles the test program, corrupts the resulting binary and deploys it
+/// into the given SUIT storage slot. The slot is erased first so that a previously
+/// deployed program can't be mistaken for the corrupted one.
+pub async fn deploy_corrupted_test_script(
+    file_name: &str,
+    layout: BinaryFileLayout,
+    environment: &Environment,
+    suit_storage_slot: usize,
+    corruption: Corruption,
+) -> Result<(), String> {
+    let file_path = format!("{}/{}", TEST_SOURCES_DIR, file_name);
+    let artifacts = test_artifacts(environment);
+    let _deployment = lock_deployments(environment);
+    let helpers = all::<HelperFunctionID>()
+        .map(|e| e as u8)
+        .collect::<Vec<u8>>();
+
+    compile(&file_path, None, &artifacts.object_dir)?;
+    let object_file = artifacts.object_file(&file_path)?;
+    apply_postprocessing(
+        &object_file,
+        layout,
+        CORRUPTED_BINARY,
+        helpers.clone(),
+        // The program is verified when it gets loaded so that the verifier on
+        // the device is the one that has to catch the corruption.
+        HelperAccessVerification::LoadTime,
+        None,
+        None,
+    )?;
+
+    let mut binary = fs::read(CORRUPTED_BINARY).map_err(|e| e.to_string())?;
+    corrupt(&mut binary, layout, corruption)?;
+    fs::write(CORRUPTED_BINARY, binary).map_err(|e| e.to_string())?;
+
+    sign(
+        &resolve_interface(&environment.riot_instance_ip, &environment.host_net_if)?,
+        None,
+        &environment.board_name,
+        &environment.coap_root_dir,
+        CORRUPTED_BINARY,
+        suit_storage_slot,
+        StorageBackend::Ram,
+        None,
+        None,
+    )?;
+    pull(
+        &environment.riot_instance_ip,
+        &environment.host_ip,
+        &artifacts.manifest(suit_storage_slot),
+        &environment.host_net_if,
+        &environment.riot_instance_net_if,
+        TargetVM::Rbpf,
+        layout,
+        suit_storage_slot,
+        StorageBackend::Ram,
+        HelperAccessVerification::LoadTime,
+        HelperAccessListSource::ExecuteRequest,
+        &helpers,
+        true,
+        &TransportOptions::from_env(environment),
+    )
+    .await
+}
+
+/// Deploys the corrupted program and checks that the device refuses to
+/// execute it.
+pub async fn test_corrupted_artifact_rejected(
+    test_program: &str,
+    layout: BinaryFileLayout,
+    environment: &Environment,
+    corruption: Corruption,
+) {
+    if skip_unless_selected(test_program) {
+        return;
+    }
+    let lease = lease_slot(environment);
+    let environment = &lease.environment;
+    let result =
+        deploy_corrupted_test_script(test_program, layout, environment, lease.slot, corruption)
+            .await;
+    if let Err(string) = &result {
+        println!("{}", string);
+    }
+    assert!(result.is_ok());
+
+    let execution_result =
+        execute_deployed_program(lease.slot, layout, TargetVM::Rbpf, environment, false).await;
+    if let Ok(response) = &execution_result {
+        println!(
+            "The corrupted program ({:?}) was executed and returned {}",
+            corruption, response.result
+        );
+    }
+    assert!(execution_result.is_err());
+}
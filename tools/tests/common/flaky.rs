@@ -0,0 +1,173 @@
+use std::{
+    any::Any,
+    env,
+    fs::OpenOptions,
+    io::Write,
+    os::raw::c_int,
+    panic,
+    sync::{Mutex, Once, OnceLock},
+    thread,
+};
+
+use serde::Serialize;
+
+/// Number of times a failed test is re-run unless overridden by
+/// MIBPF_TEST_RETRIES. By default a failure fails the test straight away.
+const DEFAULT_TEST_RETRIES: usize = 0;
+
+pub fn test_retries() -> usize {
+    env::var("MIBPF_TEST_RETRIES")
+        .ok()
+        .and_then(|retries| retries.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_TEST_RETRIES)
+}
+
+/// Classification of a test run by the harness. A test which fails and then
+/// passes when it is re-run is flaky, it passes but is reported separately so
+/// that the unreliable tests (or devices) don't go unnoticed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum TestOutcome {
+    Passed,
+    Flaky,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct TestRecord {
+    test: String,
+    outcome: TestOutcome,
+    attempts: usize,
+    /// Failure messages of the attempts which didn't pass.
+    failures: Vec<String>,
+}
+
+/// Runs the attempt until it passes or [`test_retries`] re-runs have failed.
+/// The outcome is recorded for the flakiness summary printed once the test
+/// binary exits, if all attempts fail the failure of the last one is
+/// propagated.
+pub fn run_with_retries<A>(mut attempt: A) -> TestOutcome
+where
+    A: FnMut() -> Result<(), Box<dyn Any + Send>>,
+{
+    let test = thread::current()
+        .name()
+        .unwrap_or("unnamed test")
+        .to_string();
+    let max_attempts = test_retries() + 1;
+    let mut failures = vec![];
+    for attempt_number in 1..=max_attempts {
+        let failure = match attempt() {
+            Ok(()) => {
+                let outcome = if failures.is_empty() {
+                    TestOutcome::Passed
+                } else {
+                    TestOutcome::Flaky
+                };
+                record(TestRecord {
+                    test,
+                    outcome,
+                    attempts: attempt_number,
+                    failures,
+                });
+                return outcome;
+            }
+            Err(failure) => failure,
+        };
+        failures.push(panic_message(failure.as_ref()));
+        if attempt_number < max_attempts {
+            println!(
+                "Attempt {} of {} failed, re-running {}",
+                attempt_number, max_attempts, test
+            );
+            continue;
+        }
+        record(TestRecord {
+            test,
+            outcome: TestOutcome::Failed,
+            attempts: attempt_number,
+            failures,
+        });
+        panic::resume_unwind(failure);
+    }
+    unreachable!("the test is attempted at least once")
+}
+
+fn panic_message(failure: &(dyn Any + Send)) -> String {
+    if let Some(message) = failure.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = failure.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown failure".to_string()
+    }
+}
+
+fn records() -> &'static Mutex<Vec<TestRecord>> {
+    static RECORDS: OnceLock<Mutex<Vec<TestRecord>>> = OnceLock::new();
+    RECORDS.get_or_init(|| Mutex::new(vec![]))
+}
+
+extern "C" {
+    fn atexit(callback: extern "C" fn()) -> c_int;
+}
+
+/// Stores the outcome for the summary and appends it to test-outcomes.jsonl
+/// if SAVE_RESULTS is set.
+fn record(record: TestRecord) {
+    // The test harness doesn't allow for running code after the suite, so the
+    // summary is printed when the process exits.
+    static REGISTER_SUMMARY: Once = Once::new();
+    REGISTER_SUMMARY.call_once(|| unsafe {
+        atexit(print_summary);
+    });
+
+    if env::var("SAVE_RESULTS").unwrap_or_else(|_| "False".to_string()) != "False" {
+        let line = serde_json::to_string(&record).unwrap() + "\n";
+        let _ = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open("test-outcomes.jsonl")
+            .and_then(|mut file| file.write_all(line.as_bytes()));
+    }
+    records()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .push(record);
+}
+
+extern "C" fn print_summary() {
+    let records = records()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let count = |outcome: TestOutcome| records.iter().filter(|r| r.outcome == outcome).count();
+    let flaky = count(TestOutcome::Flaky);
+    // Nothing can be flaky without retries, the summary would only repeat
+    // the results reported by the test harness.
+    if records.is_empty() || (flaky == 0 && test_retries() == 0) {
+        return;
+    }
+
+    println!(
+        "\nFlakiness summary: {} tests, {} passed, {} flaky, {} failed (retries: {})",
+        records.len(),
+        count(TestOutcome::Passed),
+        flaky,
+        count(TestOutcome::Failed),
+        test_retries()
+    );
+    for record in records.iter().filter(|r| r.outcome != TestOutcome::Passed) {
+        match record.outcome {
+            TestOutcome::Flaky => println!(
+                "  flaky:  {} passed on attempt {}, earlier failures:",
+                record.test, record.attempts
+            ),
+            _ => println!(
+                "  failed: {} failed all {} attempts:",
+                record.test, record.attempts
+            ),
+        }
+        for failure in &record.failures {
+            println!("    - {}", failure);
+        }
+    }
+}
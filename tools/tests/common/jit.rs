@@ -0,0 +1,104 @@
+use std::{env, fs::OpenOptions, io::Write};
+
+use micro_bpf_tools::Environment;
+use serde::Serialize;
+
+use super::flaky::TestOutcome;
+
+/// Boards with a RISC-V (RV32) core, all other boards are assumed to use an
+/// ARMv7-M core (including RIOT native, as the tests always treated it).
+const RV32_BOARDS: &[&str] = &[
+    "hifive1",
+    "hifive1b",
+    "esp32c3-devkit",
+    "esp32c3-wroom-02",
+    "seeedstudio-gd32",
+    "sipeed-longan-nano",
+    "redboard-red-v",
+];
+
+/// Architecture targeted by the JIT compiler of the firmware. The jit_* tests
+/// run the same corpus against each of them, the results are recorded
+/// separately for each architecture (see [`record_jit_result`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum JitArchitecture {
+    ArmV7M,
+    Rv32,
+}
+
+impl JitArchitecture {
+    /// Architecture of the board under test. It is derived from BOARD_NAME
+    /// unless MIBPF_JIT_ARCH (armv7m or rv32) overrides it, e.g. for boards
+    /// not listed in [`RV32_BOARDS`].
+    pub fn of(environment: &Environment) -> JitArchitecture {
+        match env::var("MIBPF_JIT_ARCH").ok().as_deref() {
+            Some("armv7m") => return JitArchitecture::ArmV7M,
+            Some("rv32") => return JitArchitecture::Rv32,
+            Some(other) => panic!(
+                "Unknown JIT architecture {}, expected armv7m or rv32",
+                other
+            ),
+            None => {}
+        }
+        if RV32_BOARDS.contains(&environment.board_name.as_str()) {
+            JitArchitecture::Rv32
+        } else {
+            JitArchitecture::ArmV7M
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            JitArchitecture::ArmV7M => "armv7m",
+            JitArchitecture::Rv32 => "rv32",
+        }
+    }
+
+    /// Whether the firmware includes a JIT for the architecture. Only the
+    /// Cortex-M JIT exists at the moment, the tests targeting RV32 are
+    /// skipped unless MIBPF_RV32_JIT is set (i.e. the firmware under test
+    /// was built with the RISC-V JIT).
+    pub fn has_jit(&self) -> bool {
+        match self {
+            JitArchitecture::ArmV7M => true,
+            JitArchitecture::Rv32 => env::var("MIBPF_RV32_JIT").is_ok(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JitResult<'a> {
+    architecture: JitArchitecture,
+    board: &'a str,
+    program: &'a str,
+    passed: bool,
+    /// The test passed only after being re-run.
+    flaky: bool,
+}
+
+/// Appends the outcome of the test to jit-results-<architecture>.jsonl if
+/// SAVE_RESULTS is set, so that the results can be compared across the
+/// architectures.
+pub fn record_jit_result(
+    architecture: JitArchitecture,
+    environment: &Environment,
+    program: &str,
+    outcome: TestOutcome,
+) {
+    if env::var("SAVE_RESULTS").unwrap_or_else(|_| "False".to_string()) == "False" {
+        return;
+    }
+    let result = JitResult {
+        architecture,
+        board: &environment.board_name,
+        program,
+        passed: outcome != TestOutcome::Failed,
+        flaky: outcome == TestOutcome::Flaky,
+    };
+    let line = serde_json::to_string(&result).unwrap() + "\n";
+    let _ = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(format!("jit-results-{}.jsonl", architecture.name()))
+        .and_then(|mut file| file.write_all(line.as_bytes()));
+}
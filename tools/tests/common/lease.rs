@@ -0,0 +1,152 @@
+use std::{
+    collections::BTreeSet,
+    sync::{Condvar, Mutex, MutexGuard, OnceLock},
+    time::Duration,
+};
+
+use micro_bpf_tools::Environment;
+
+/// Number of SUIT storage slots of each device used by the tests unless
+/// overridden by MIBPF_TEST_SLOTS.
+const DEFAULT_SLOTS_PER_DEVICE: usize = 2;
+
+/// Allows the tests to run concurrently (without `--test-threads=1`) by
+/// handing out distinct SUIT storage slots to them. The slots are leased from
+/// the device configured by the environment and from the additional devices
+/// listed in MIBPF_TEST_DEVICES (comma-separated IPv6 addresses reachable
+/// through the same host interface), so that the suite runs faster the more
+/// devices are available.
+///
+/// The test blocks until a slot becomes available, the slot is returned to
+/// the pool once the lease is dropped (also if the test panics).
+pub struct SlotLease {
+    pub slot: usize,
+    /// Environment of the test with the address of the leased device.
+    pub environment: Environment,
+    device: usize,
+}
+
+impl Drop for SlotLease {
+    fn drop(&mut self) {
+        let pool = pool(&self.environment);
+        lock(&pool.state).free.insert((self.slot, self.device));
+        pool.released.notify_all();
+    }
+}
+
+/// Held while a test deploys its program. The deployments are serialised
+/// because the test programs are compiled in the same directory (the build
+/// system moves all produced binaries out of it afterwards) and the signed
+/// artifacts are named after the slots only.
+pub struct DeploymentLock {
+    environment: Environment,
+}
+
+impl Drop for DeploymentLock {
+    fn drop(&mut self) {
+        let pool = pool(&self.environment);
+        lock(&pool.state).deploying = false;
+        pool.released.notify_all();
+    }
+}
+
+struct Pool {
+    /// Addresses of the devices, the configured one first.
+    devices: Vec<String>,
+    state: Mutex<PoolState>,
+    released: Condvar,
+}
+
+struct PoolState {
+    /// Pairs of the slot and the index of the device which aren't leased,
+    /// ordered by the slot first so that the released slots are handed out
+    /// in the same order as the initial ones.
+    free: BTreeSet<(usize, usize)>,
+    deploying: bool,
+}
+
+/// Leases a slot that no other concurrently running test uses.
+pub fn lease_slot(environment: &Environment) -> SlotLease {
+    let pool = pool(environment);
+    let mut state = lock(&pool.state);
+    loop {
+        // The slots are handed out in order so that the tests running alone
+        // keep using slot 0 of the configured device.
+        if let Some((slot, device)) = state.free.pop_first() {
+            let environment = Environment {
+                riot_instance_ip: pool.devices[device].clone(),
+                ..environment.clone()
+            };
+            return SlotLease {
+                slot,
+                environment,
+                device,
+            };
+        }
+        state = wait(pool, state);
+    }
+}
+
+/// Waits until no other test is deploying its program.
+pub fn lock_deployments(environment: &Environment) -> DeploymentLock {
+    let pool = pool(environment);
+    let mut state = lock(&pool.state);
+    while state.deploying {
+        state = wait(pool, state);
+    }
+    state.deploying = true;
+    DeploymentLock {
+        environment: environment.clone(),
+    }
+}
+
+/// The pool is set up by the first test that leases a slot, all tests of the
+/// suite use the same environment.
+fn pool(environment: &Environment) -> &'static Pool {
+    static POOL: OnceLock<Pool> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let slots = std::env::var("MIBPF_TEST_SLOTS")
+            .ok()
+            .and_then(|slots| slots.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_SLOTS_PER_DEVICE);
+        let mut devices = vec![environment.riot_instance_ip.clone()];
+        if let Ok(additional) = std::env::var("MIBPF_TEST_DEVICES") {
+            devices.extend(
+                additional
+                    .split(',')
+                    .map(|device| device.trim().to_string())
+                    .filter(|device| !device.is_empty()),
+            );
+        }
+
+        // Interleaving the devices spreads the concurrent tests over all of
+        // them before the second slot of any device is used.
+        let free = (0..slots)
+            .flat_map(|slot| (0..devices.len()).map(move |device| (slot, device)))
+            .collect();
+        Pool {
+            devices,
+            state: Mutex::new(PoolState {
+                free,
+                deploying: false,
+            }),
+            released: Condvar::new(),
+        }
+    })
+}
+
+/// A test panicking while holding the lock mustn't prevent the others from
+/// releasing their leases.
+fn lock(state: &Mutex<PoolState>) -> MutexGuard<'_, PoolState> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn wait<'a>(pool: &Pool, state: MutexGuard<'a, PoolState>) -> MutexGuard<'a, PoolState> {
+    // The timeout only guards against a missed notification.
+    pool.released
+        .wait_timeout(state, Duration::from_secs(1))
+        .map(|(state, _)| state)
+        .unwrap_or_else(|poisoned| poisoned.into_inner().0)
+}
@@ -1,18 +1,52 @@
-use std::{env, process::Command, collections::HashMap};
+use std::{collections::HashMap, env, process::Command};
 
 use enum_iterator::all;
-use micro_bpf_tools::{self, deploy, execute, Environment};
+use micro_bpf_tools::{
+    self, deploy, execute, load_env, resolve_interface, ArtifactStore, DeployOptions,
+    Environment, ExecuteOptions, LocalPublisher, SuitSlot, TransportOptions, AUTO_INTERFACE,
+};
 
 use micro_bpf_common::{
     BinaryFileLayout, ExecutionModel, HelperAccessListSource, HelperAccessVerification,
-    HelperFunctionID, TargetVM, VMConfiguration, VMExecutionRequest,
+    HelperFunctionID, StorageBackend, TargetVM, VMConfiguration, VMExecutionRequest,
 };
 use serde::{Deserialize, Serialize};
 
+use lease::{lease_slot, lock_deployments};
+use response::assert_response_matches;
+use tags::skip_unless_selected;
+use timing::{assert_response_within_time_bound, assert_within_time_bound};
+
+pub mod arithmetic;
+pub mod corrupt;
+pub mod flaky;
+pub mod jit;
+pub mod lease;
+pub mod native;
+pub mod response;
+pub mod tags;
+pub mod timeout;
+pub mod timing;
+
 /// When communicating with target board sometimes it takes longer to get the request processed
 /// we need to wait a bit longer to give the device time to respons
 const NUCLEO_EXECUTION_REQUEST_TIMEOUT: u64 = 1;
 
+/// Loads the environment of the tests, if it describes a RIOT native
+/// instance, the instance is booted automatically (see [`native::NativeInstance`]).
+pub fn load_test_env() -> Environment {
+    let mut environment = load_env();
+    if environment.board_name == "native" && environment.host_net_if == AUTO_INTERFACE {
+        // The harness creates the tap bridge itself, so it knows its name.
+        environment.host_net_if = native::DEFAULT_BRIDGE.to_string();
+    }
+    native::ensure_running(&environment);
+    environment.host_net_if =
+        resolve_interface(&environment.riot_instance_ip, &environment.host_net_if)
+            .unwrap_or_else(|e| panic!("Failed to resolve the network interface: {}", e));
+    environment
+}
+
 pub async fn test_execution(
     test_program: &str,
     layout: BinaryFileLayout,
@@ -76,8 +110,20 @@ pub async fn test_jit_execution_specifying_helpers(
     environment: &Environment,
     available_helpers: Vec<u8>,
 ) {
+    if skip_unless_selected(test_program) {
+        return;
+    }
     // We first deploy the program on the tested microcontroller
-    let result = deploy_test_script(test_program, layout, environment, available_helpers).await;
+    let lease = lease_slot(environment);
+    let environment = &lease.environment;
+    let result = deploy_test_script_into_slot(
+        test_program,
+        layout,
+        environment,
+        available_helpers,
+        lease.slot,
+    )
+    .await;
     if let Err(string) = &result {
         println!("{}", string);
     }
@@ -93,15 +139,17 @@ pub async fn test_jit_execution_specifying_helpers(
 
     // Then we request execution and check that the return value is what we
     // expected
-    let execution_result = execute_deployed_program(0, layout, target_vm, environment, true).await;
+    let execution_result =
+        execute_deployed_program(lease.slot, layout, target_vm, environment, true).await;
     if let Err(string) = &execution_result {
         println!("{}", string);
     }
     assert!(execution_result.is_ok());
-    let return_value = execution_result.unwrap();
+    let response = execution_result.unwrap();
 
     let expected_return = extract_expected_return(test_program);
-    assert!(return_value == expected_return);
+    assert!(response.result == expected_return);
+    assert_within_time_bound(test_program, response.execution_time);
 }
 
 pub async fn test_execution_specifying_helpers(
@@ -111,8 +159,20 @@ pub async fn test_execution_specifying_helpers(
     environment: &Environment,
     available_helpers: Vec<u8>,
 ) {
+    if skip_unless_selected(test_program) {
+        return;
+    }
     // We first deploy the program on the tested microcontroller
-    let result = deploy_test_script(test_program, layout, environment, available_helpers).await;
+    let lease = lease_slot(environment);
+    let environment = &lease.environment;
+    let result = deploy_test_script_into_slot(
+        test_program,
+        layout,
+        environment,
+        available_helpers,
+        lease.slot,
+    )
+    .await;
     if let Err(string) = &result {
         println!("{}", string);
     }
@@ -128,15 +188,17 @@ pub async fn test_execution_specifying_helpers(
 
     // Then we request execution and check that the return value is what we
     // expected
-    let execution_result = execute_deployed_program(0, layout, target_vm, environment, false).await;
+    let execution_result =
+        execute_deployed_program(lease.slot, layout, target_vm, environment, false).await;
     if let Err(string) = &execution_result {
         println!("{}", string);
     }
     assert!(execution_result.is_ok());
-    let return_value = execution_result.unwrap();
+    let response = execution_result.unwrap();
 
     let expected_return = extract_expected_return(test_program);
-    assert!(return_value == expected_return);
+    assert!(response.result == expected_return);
+    assert_within_time_bound(test_program, response.execution_time);
 }
 
 pub async fn benchmark_fletcher_16(
@@ -151,8 +213,16 @@ pub async fn benchmark_fletcher_16(
     let bytes = 80 * base.pow((data_size - 1) as u32);
     let test_source = format!("jit_fletcher16_checksum_{}B_data.c", bytes);
 
-    let result =
-        deploy_test_script(&test_source, layout, environment, available_helpers.clone()).await;
+    let lease = lease_slot(environment);
+    let environment = &lease.environment;
+    let result = deploy_test_script_into_slot(
+        &test_source,
+        layout,
+        environment,
+        available_helpers.clone(),
+        lease.slot,
+    )
+    .await;
     if let Err(string) = &result {
         println!("{}", string);
     }
@@ -173,20 +243,25 @@ pub async fn benchmark_fletcher_16(
         &environment.riot_instance_ip,
         target_vm,
         layout,
-        0,
+        lease.slot,
         &environment.host_net_if,
         ExecutionModel::ShortLived,
         HelperAccessVerification::AheadOfTime,
         HelperAccessListSource::ExecuteRequest,
         &available_helpers,
         jit,
-        true,
-        true,
+        &ExecuteOptions {
+            jit_compile: true,
+            benchmark: true,
+            ..Default::default()
+        },
+        &TransportOptions::from_env(environment),
     )
     .await
     .unwrap();
     let response = serde_json::from_str::<BenchmarkResponse>(&response).unwrap();
     println!("({}, {})", bytes, response.execution_time);
+    assert_within_time_bound(&test_source, response.execution_time);
     return response;
 }
 
@@ -213,9 +288,11 @@ pub async fn benchmark_fletcher_16_native(
         available_helpers,
     );
 
+    let host_net_if = resolve_interface(&environment.riot_instance_ip, &environment.host_net_if)
+        .expect("Failed to resolve the network interface");
     let mut base_url = format!(
         "coap://[{}%{}]",
-        environment.riot_instance_ip, environment.host_net_if
+        environment.riot_instance_ip, host_net_if
     );
     // We need to point to the native benchmark endpoint.
     base_url.push_str("/native/exec");
@@ -252,6 +329,8 @@ pub async fn benchmark_fletcher_16_native(
     let bytes = 80 * base.pow((data_size - 1) as u32);
     let response = serde_json::from_str::<SimpleResponse>(&response).unwrap();
     println!("({}, {})", bytes, response.execution_time);
+    let test_source = format!("jit_fletcher16_checksum_{}B_data.c", bytes);
+    assert_within_time_bound(&test_source, response.execution_time);
     response
 }
 
@@ -284,7 +363,9 @@ pub struct SimpleResponse {
 /// Because of the above setup, running the memory checks without caching should
 /// cause the second program to execute much slower as the number of allowed
 /// regions grows.
-pub async fn benchmark_memory_access_checks(environment: &Environment) -> HashMap<&'static str, HashMap<usize, BenchmarkResponse>> {
+pub async fn benchmark_memory_access_checks(
+    environment: &Environment,
+) -> HashMap<&'static str, HashMap<usize, BenchmarkResponse>> {
     let region_list_sizes = vec![1, 4, 8, 12, 16];
     // The number of memory regions is controlled by the size of available helpers
     // (this is an instrumentation hack, in normal applications the list of
@@ -356,8 +437,11 @@ pub async fn benchmark_memory_access_checks(environment: &Environment) -> HashMa
             HelperAccessListSource::ExecuteRequest,
             &available_helpers,
             false,
-            false,
-            true,
+            &ExecuteOptions {
+                benchmark: true,
+                ..Default::default()
+            },
+            &TransportOptions::from_env(environment),
         )
         .await
         .unwrap();
@@ -369,6 +453,7 @@ pub async fn benchmark_memory_access_checks(environment: &Environment) -> HashMa
             .map_err(|e| format!("Failed to parse the json response: {}", e))
             .unwrap();
 
+        assert_within_time_bound("looping.c", response.execution_time);
         stack_memory_access_benches.insert(size, response);
 
         // When running on embedded targets we need to give them enough time
@@ -390,8 +475,11 @@ pub async fn benchmark_memory_access_checks(environment: &Environment) -> HashMa
             HelperAccessListSource::ExecuteRequest,
             &available_helpers,
             false,
-            false,
-            true,
+            &ExecuteOptions {
+                benchmark: true,
+                ..Default::default()
+            },
+            &TransportOptions::from_env(environment),
         )
         .await
         .unwrap();
@@ -403,12 +491,16 @@ pub async fn benchmark_memory_access_checks(environment: &Environment) -> HashMa
             .map_err(|e| format!("Failed to parse the json response: {}", e))
             .unwrap();
 
+        assert_within_time_bound("data_relocations-looping.c", response.execution_time);
         data_section_memory_access_benches.insert(size, response);
     }
 
     let mut result = HashMap::new();
     result.insert("stack_memory_access", stack_memory_access_benches);
-    result.insert("data_section_memory_access", data_section_memory_access_benches);
+    result.insert(
+        "data_section_memory_access",
+        data_section_memory_access_benches,
+    );
     result
 }
 
@@ -432,7 +524,16 @@ pub async fn benchmark_execution(
         .map(|e| e as u8)
         .collect::<Vec<u8>>();
     // We first deploy the program on the tested microcontroller
-    let result = deploy_test_script(test_program, layout, environment, available_helpers).await;
+    let lease = lease_slot(environment);
+    let environment = &lease.environment;
+    let result = deploy_test_script_into_slot(
+        test_program,
+        layout,
+        environment,
+        available_helpers,
+        lease.slot,
+    )
+    .await;
     if let Err(string) = &result {
         println!("{}", string);
     }
@@ -455,15 +556,18 @@ pub async fn benchmark_execution(
         &environment.riot_instance_ip,
         target,
         layout,
-        0,
+        lease.slot,
         &environment.host_net_if,
         ExecutionModel::ShortLived,
         HelperAccessVerification::AheadOfTime,
         HelperAccessListSource::ExecuteRequest,
         &available_helpers,
         false,
-        false,
-        true,
+        &ExecuteOptions {
+            benchmark: true,
+            ..Default::default()
+        },
+        &TransportOptions::from_env(environment),
     )
     .await
     .unwrap();
@@ -474,6 +578,7 @@ pub async fn benchmark_execution(
     let response = serde_json::from_str::<BenchmarkResponse>(&response)
         .map_err(|e| format!("Failed to parse the json response: {}", e))
         .unwrap();
+    assert_within_time_bound(test_program, response.execution_time);
 
     response
 }
@@ -505,7 +610,16 @@ pub async fn benchmark_jit_execution(
         .collect::<Vec<u8>>();
     // We first deploy the program on the tested microcontroller
     let layout = BinaryFileLayout::RawObjectFile;
-    let result = deploy_test_script(test_program, layout, environment, available_helpers).await;
+    let lease = lease_slot(environment);
+    let environment = &lease.environment;
+    let result = deploy_test_script_into_slot(
+        test_program,
+        layout,
+        environment,
+        available_helpers,
+        lease.slot,
+    )
+    .await;
     if let Err(string) = &result {
         println!("{}", string);
     }
@@ -528,15 +642,19 @@ pub async fn benchmark_jit_execution(
         &environment.riot_instance_ip,
         TargetVM::Rbpf,
         layout,
-        0,
+        lease.slot,
         &environment.host_net_if,
         ExecutionModel::ShortLived,
         HelperAccessVerification::AheadOfTime,
         HelperAccessListSource::ExecuteRequest,
         &available_helpers,
         true,
-        true,
-        true,
+        &ExecuteOptions {
+            jit_compile: true,
+            benchmark: true,
+            ..Default::default()
+        },
+        &TransportOptions::from_env(environment),
     )
     .await
     .unwrap();
@@ -545,6 +663,7 @@ pub async fn benchmark_jit_execution(
     let response = serde_json::from_str::<BenchmarkResponse>(&response)
         .map_err(|e| format!("Failed to parse the json response: {}", e))
         .unwrap();
+    assert_within_time_bound(test_program, response.execution_time);
     response
 }
 
@@ -596,8 +715,20 @@ pub async fn test_execution_accessing_coap_pkt_specifying_helpers(
     available_helpers: Vec<u8>,
     jit: bool,
 ) {
+    if skip_unless_selected(test_program) {
+        return;
+    }
     // We first deploy the program on the tested microcontroller
-    let result = deploy_test_script(test_program, layout, environment, available_helpers).await;
+    let lease = lease_slot(environment);
+    let environment = &lease.environment;
+    let result = deploy_test_script_into_slot(
+        test_program,
+        layout,
+        environment,
+        available_helpers,
+        lease.slot,
+    )
+    .await;
     if let Err(string) = &result {
         println!("{}", string);
     }
@@ -614,7 +745,7 @@ pub async fn test_execution_accessing_coap_pkt_specifying_helpers(
     // Then we request execution and check that the return value is what we
     // expected
     let execution_result =
-        execute_deployed_program_on_coap(0, layout, target_vm, environment, jit).await;
+        execute_deployed_program_on_coap(lease.slot, layout, target_vm, environment, jit).await;
     if let Err(string) = &execution_result {
         println!("{}", string);
     }
@@ -623,42 +754,26 @@ pub async fn test_execution_accessing_coap_pkt_specifying_helpers(
     let response = execution_result.unwrap();
 
     let expected = extract_expected_response(test_program);
-    assert!(response == expected);
+    assert_response_matches(&response, &expected);
+    assert_response_within_time_bound(test_program, &response);
 }
 
 const TEST_SOURCES_DIR: &'static str = "tests/test-sources";
 
-/// Test utility funciton used for sending the eBPF scripts to the device given
-/// the environment configuration.
-pub async fn deploy_test_script(
-    file_name: &str,
-    layout: BinaryFileLayout,
-    environment: &Environment,
-    allowed_helpers: Vec<u8>,
-) -> Result<(), String> {
-    let file_path = format!("{}/{}", TEST_SOURCES_DIR, file_name);
-    let out_dir = format!("{}/out", TEST_SOURCES_DIR);
-    deploy(
-        &file_path,
-        &out_dir,
-        TargetVM::Rbpf,
-        layout,
-        &environment.coap_root_dir,
-        0,
-        &environment.riot_instance_net_if,
-        &environment.riot_instance_ip,
-        &environment.host_net_if,
-        &environment.host_ip,
-        &environment.board_name,
-        Some(environment.micro_bpf_root_dir.as_str()),
-        allowed_helpers,
-        HelperAccessVerification::AheadOfTime,
-        HelperAccessListSource::ExecuteRequest,
-        true,
-    )
-    .await
+/// The object files of the test programs are kept next to their sources,
+/// everything else follows the device profile.
+pub fn test_artifacts(environment: &Environment) -> ArtifactStore {
+    ArtifactStore {
+        object_dir: format!("{}/out", TEST_SOURCES_DIR),
+        ..ArtifactStore::from_env(environment)
+    }
 }
 
+/// Test utility function used for sending the eBPF scripts to the device given
+/// the environment configuration. The slot needs to be leased (see
+/// [`lease_slot`]) so that the concurrently running tests don't overwrite each
+/// other's programs, they also deploy their programs one at a time (see
+/// [`lease::DeploymentLock`]).
 pub async fn deploy_test_script_into_slot(
     file_name: &str,
     layout: BinaryFileLayout,
@@ -667,14 +782,14 @@ pub async fn deploy_test_script_into_slot(
     suit_slot: usize,
 ) -> Result<(), String> {
     let file_path = format!("{}/{}", TEST_SOURCES_DIR, file_name);
-    let out_dir = format!("{}/out", TEST_SOURCES_DIR);
+    let _deployment = lock_deployments(environment);
     deploy(
         &file_path,
-        &out_dir,
+        &test_artifacts(environment),
         TargetVM::Rbpf,
         layout,
-        &environment.coap_root_dir,
-        suit_slot,
+        SuitSlot::Fixed(suit_slot),
+        StorageBackend::Ram,
         &environment.riot_instance_net_if,
         &environment.riot_instance_ip,
         &environment.host_net_if,
@@ -684,9 +799,15 @@ pub async fn deploy_test_script_into_slot(
         allowed_helpers,
         HelperAccessVerification::AheadOfTime,
         HelperAccessListSource::ExecuteRequest,
-        true,
+        &DeployOptions {
+            erase: true,
+            ..Default::default()
+        },
+        &LocalPublisher::new(&environment.coap_root_dir),
+        &TransportOptions::from_env(environment),
     )
     .await
+    .map(|_| ())
 }
 
 /// Reads the annotation present at the top of test source files that specifies
@@ -756,8 +877,11 @@ pub async fn execute_deployed_program_on_coap(
         HelperAccessListSource::ExecuteRequest,
         &available_helpers,
         jit,
-        true,
-        false,
+        &ExecuteOptions {
+            jit_compile: true,
+            ..Default::default()
+        },
+        &TransportOptions::from_env(environment),
     )
     .await?;
 
@@ -767,6 +891,17 @@ pub async fn execute_deployed_program_on_coap(
     Ok(response.to_string())
 }
 
+/// Short lived executions always return responses of this form:
+/// {"execution_time": 10, "result": 0}
+#[derive(Debug, Deserialize)]
+pub struct ShortLivedResponse {
+    /// Return value of the program
+    pub result: i32,
+    /// Execution time of the program in microseconds, bounded by the
+    /// TEST_MAX_TIME_MS annotation (see [`timing::assert_within_time_bound`]).
+    pub execution_time: u32,
+}
+
 pub async fn execute_deployed_program_specifying_helpers(
     suit_storage_slot: usize,
     layout: BinaryFileLayout,
@@ -774,7 +909,7 @@ pub async fn execute_deployed_program_specifying_helpers(
     environment: &Environment,
     available_helpers: Vec<u8>,
     jit: bool,
-) -> Result<i32, String> {
+) -> Result<ShortLivedResponse, String> {
     let response = execute(
         &environment.riot_instance_ip,
         target_vm,
@@ -786,24 +921,17 @@ pub async fn execute_deployed_program_specifying_helpers(
         HelperAccessListSource::ExecuteRequest,
         &available_helpers,
         jit,
-        true,
-        false,
+        &ExecuteOptions {
+            jit_compile: true,
+            ..Default::default()
+        },
+        &TransportOptions::from_env(environment),
     )
     .await?;
 
-    // Short lived executions always return responses of this form:
-    // {"execution_time": 10, "result": 0}
-    #[derive(Deserialize)]
-    struct Response {
-        // Return value of the program
-        result: i32,
-    }
-
     println!("Response: {}", response);
-    let response = serde_json::from_str::<Response>(&response)
-        .map_err(|e| format!("Failed to parse the json response: {}", e))?;
-
-    Ok(response.result)
+    serde_json::from_str::<ShortLivedResponse>(&response)
+        .map_err(|e| format!("Failed to parse the json response: {}", e))
 }
 
 pub async fn execute_deployed_program(
@@ -812,7 +940,7 @@ pub async fn execute_deployed_program(
     target_vm: TargetVM,
     environment: &Environment,
     jit: bool,
-) -> Result<i32, String> {
+) -> Result<ShortLivedResponse, String> {
     let available_helpers = all::<HelperFunctionID>()
         .map(|e| e as u8)
         .collect::<Vec<u8>>();
@@ -0,0 +1,171 @@
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    sync::{Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use micro_bpf_tools::Environment;
+
+/// How long we wait for the CoAP server of the instance to start responding.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Name of the tap bridge created for the instance unless the profile sets
+/// HOST_NET_IF explicitly.
+pub const DEFAULT_BRIDGE: &str = "tapbr0";
+
+/// A RIOT native instance of the micro_bpf firmware running on the host
+/// machine. It allows for running the integration tests without any boards
+/// attached, all that is needed is a checkout of the micro_bpf repository
+/// (pointed to by MIBPF_ROOT_DIR).
+///
+/// The instance is run by a supervisor shell script which creates the tap
+/// bridge, launches the firmware and tears both down once either the firmware
+/// or the test process exits. This is needed because the test harness never
+/// runs the destructors of statics, so the instance shared by all tests of a
+/// test binary can't be stopped from a `Drop` implementation.
+pub struct NativeInstance {
+    supervisor: Child,
+}
+
+static INSTANCE: OnceLock<Mutex<Option<NativeInstance>>> = OnceLock::new();
+
+/// Makes sure that the native instance described by the environment is up
+/// before the test starts. It only does something if the board of the
+/// profile is 'native', the instance isn't already running (e.g. started
+/// manually) and MIBPF_EXTERNAL_INSTANCE isn't set. The instance is started
+/// once and then shared by all tests of the test binary.
+pub fn ensure_running(environment: &Environment) {
+    if environment.board_name != "native" || std::env::var("MIBPF_EXTERNAL_INSTANCE").is_ok() {
+        return;
+    }
+
+    INSTANCE.get_or_init(|| {
+        if is_reachable(environment) {
+            return Mutex::new(None);
+        }
+        let instance = NativeInstance::start(environment)
+            .unwrap_or_else(|e| panic!("Failed to start the RIOT native instance: {}", e));
+        Mutex::new(Some(instance))
+    });
+}
+
+/// Restarts the native instance if it was started by the harness. Returns
+/// false if there is no such instance, e.g. when the tests run against a
+/// physical board or an instance started manually.
+pub fn restart(environment: &Environment) -> Result<bool, String> {
+    let Some(instance) = INSTANCE.get() else {
+        return Ok(false);
+    };
+    let mut instance = instance.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(running) = instance.as_mut() else {
+        return Ok(false);
+    };
+    running.stop();
+    *instance = Some(NativeInstance::start(environment)?);
+    Ok(true)
+}
+
+impl NativeInstance {
+    /// Builds the firmware, launches it and waits until its CoAP server
+    /// responds.
+    pub fn start(environment: &Environment) -> Result<Self, String> {
+        let app_dir = std::env::var("MIBPF_NATIVE_APP_DIR")
+            .unwrap_or_else(|_| format!("{}/src", environment.micro_bpf_root_dir));
+
+        let status = Command::new("make")
+            .arg("-C")
+            .arg(&app_dir)
+            .arg("BOARD=native")
+            .arg("all")
+            .status()
+            .map_err(|e| format!("Failed to run make: {}", e))?;
+        if !status.success() {
+            return Err(format!("Failed to build the firmware in {}", app_dir));
+        }
+        let firmware = find_firmware(&app_dir)?;
+
+        let tapsetup = format!(
+            "{}/RIOT/dist/tools/tapsetup/tapsetup",
+            environment.micro_bpf_root_dir
+        );
+        // The supervisor exits once the test process (its parent) is gone, the
+        // EXIT trap then stops the firmware and removes the tap interfaces.
+        let script = format!(
+            "sudo {tapsetup} -c 1 -b {bridge} || exit 1
+             {firmware} tap0 > native-instance.log 2>&1 &
+             riot=$!
+             trap 'kill $riot 2>/dev/null; sudo {tapsetup} -d -b {bridge}' EXIT
+             trap 'exit' TERM INT
+             while kill -0 $PPID 2>/dev/null && kill -0 $riot 2>/dev/null; do
+                 sleep 1
+             done",
+            tapsetup = tapsetup,
+            bridge = environment.host_net_if,
+            firmware = firmware.display(),
+        );
+        let supervisor = Command::new("bash")
+            .arg("-c")
+            .arg(script)
+            .stdin(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to launch the firmware: {}", e))?;
+        let mut instance = NativeInstance { supervisor };
+
+        let start = Instant::now();
+        while !is_reachable(environment) {
+            if let Ok(Some(status)) = instance.supervisor.try_wait() {
+                return Err(format!(
+                    "The instance exited with {}, see native-instance.log",
+                    status
+                ));
+            }
+            if start.elapsed() > STARTUP_TIMEOUT {
+                instance.stop();
+                return Err(format!(
+                    "The CoAP server didn't respond within {:?}",
+                    STARTUP_TIMEOUT
+                ));
+            }
+            thread::sleep(Duration::from_millis(500));
+        }
+        Ok(instance)
+    }
+
+    /// Stops the firmware and removes the tap bridge.
+    pub fn stop(&mut self) {
+        let _ = Command::new("kill")
+            .arg(self.supervisor.id().to_string())
+            .status();
+        let _ = self.supervisor.wait();
+    }
+}
+
+impl Drop for NativeInstance {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn find_firmware(app_dir: &str) -> Result<PathBuf, String> {
+    let bin_dir = format!("{}/bin/native", app_dir);
+    fs::read_dir(&bin_dir)
+        .map_err(|e| format!("Failed to read {}: {}", bin_dir, e))?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .find(|path| path.extension().is_some_and(|ext| ext == "elf"))
+        .ok_or_else(|| format!("No firmware found in {}", bin_dir))
+}
+
+fn is_reachable(environment: &Environment) -> bool {
+    let url = format!(
+        "coap://[{}%{}]/.well-known/core",
+        environment.riot_instance_ip, environment.host_net_if
+    );
+    Command::new("aiocoap-client")
+        .arg(url)
+        .output()
+        .is_ok_and(|output| output.status.success() && output.stderr.is_empty())
+}
@@ -0,0 +1,126 @@
+use std::fmt;
+
+use serde_json::Value;
+
+/// Difference between a single field of the expected and the actual
+/// response, the path is of the form `.readings[2].value`.
+#[derive(Debug)]
+pub enum FieldDiff {
+    Missing {
+        path: String,
+        expected: Value,
+    },
+    Unexpected {
+        path: String,
+        actual: Value,
+    },
+    Changed {
+        path: String,
+        expected: Value,
+        actual: Value,
+    },
+}
+
+impl fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldDiff::Missing { path, expected } => {
+                write!(f, "{}: missing (expected {})", path, expected)
+            }
+            FieldDiff::Unexpected { path, actual } => {
+                write!(f, "{}: unexpected (got {})", path, actual)
+            }
+            FieldDiff::Changed {
+                path,
+                expected,
+                actual,
+            } => write!(f, "{}: expected {}, got {}", path, expected, actual),
+        }
+    }
+}
+
+/// Compares the response of a program which writes its own CoAP response
+/// with the expected one. The JSON responses are compared structurally, so
+/// the order of the fields, the whitespace and the formatting of the numbers
+/// (e.g. `1.50` and `1.5`) don't matter. The CBOR responses are decoded into
+/// JSON by [`micro_bpf_tools::execute`] and so are compared the same way. Any
+/// other responses are compared ignoring the whitespace.
+///
+/// Panics with a field-level diff if the responses don't match.
+pub fn assert_response_matches(actual: &str, expected: &str) {
+    let actual = actual.trim_matches(char::from(0)).trim();
+    let expected = expected.trim();
+
+    let (Ok(actual_json), Ok(expected_json)) = (
+        serde_json::from_str::<Value>(actual),
+        serde_json::from_str::<Value>(expected),
+    ) else {
+        let normalize = |s: &str| s.split_whitespace().collect::<String>();
+        assert!(
+            normalize(actual) == normalize(expected),
+            "The response doesn't match the expected one:\n  expected: {}\n  actual:   {}",
+            expected,
+            actual
+        );
+        return;
+    };
+
+    let mut diffs = vec![];
+    diff_values("", &expected_json, &actual_json, &mut diffs);
+    if !diffs.is_empty() {
+        let diffs = diffs
+            .iter()
+            .map(|diff| format!("  {}", diff))
+            .collect::<Vec<String>>();
+        panic!(
+            "The response doesn't match the expected one:\n{}\n  expected: {}\n  actual:   {}",
+            diffs.join("\n"),
+            expected,
+            actual
+        );
+    }
+}
+
+fn diff_values(path: &str, expected: &Value, actual: &Value, diffs: &mut Vec<FieldDiff>) {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (key, expected) in expected {
+                let path = format!("{}.{}", path, key);
+                match actual.get(key) {
+                    Some(actual) => diff_values(&path, expected, actual, diffs),
+                    None => diffs.push(FieldDiff::Missing {
+                        path,
+                        expected: expected.clone(),
+                    }),
+                }
+            }
+            for (key, actual) in actual {
+                if !expected.contains_key(key) {
+                    diffs.push(FieldDiff::Unexpected {
+                        path: format!("{}.{}", path, key),
+                        actual: actual.clone(),
+                    });
+                }
+            }
+        }
+        (Value::Array(expected), Value::Array(actual)) if expected.len() == actual.len() => {
+            for (i, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+                diff_values(&format!("{}[{}]", path, i), expected, actual, diffs);
+            }
+        }
+        // Integers and floats are equal if they represent the same value,
+        // e.g. the firmware printing `12.0` instead of `12`.
+        (Value::Number(expected), Value::Number(actual))
+            if expected.as_f64() == actual.as_f64() => {}
+        (expected, actual) if expected == actual => {}
+        (expected, actual) => diffs.push(FieldDiff::Changed {
+            path: if path.is_empty() {
+                ".".to_string()
+            } else {
+                path.to_string()
+            },
+            expected: expected.clone(),
+            actual: actual.clone(),
+        }),
+    }
+}
@@ -0,0 +1,56 @@
+use std::{collections::BTreeSet, env, fs};
+
+use super::TEST_SOURCES_DIR;
+
+/// Prefix of the comment line listing the tags of a test program, e.g.
+/// `// TEST_TAGS: jit, arithmetic`
+const TAGS_PREFIX: &str = "// TEST_TAGS:";
+
+/// Returns the tags declared in the header comment of the test program.
+pub fn test_tags(file_name: &str) -> BTreeSet<String> {
+    let path = format!("{}/{}", TEST_SOURCES_DIR, file_name);
+    let source = fs::read_to_string(&path).unwrap_or_default();
+    source
+        .lines()
+        .take_while(|line| line.starts_with("//"))
+        .filter_map(|line| line.strip_prefix(TAGS_PREFIX))
+        .flat_map(|tags| tags.split(','))
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+/// Checks whether the test program is selected by MIBPF_TEST_TAGS, a
+/// comma-separated list of tags. The program needs to have at least one of
+/// the listed tags and none of the tags prefixed with '!', e.g.
+/// `MIBPF_TEST_TAGS=jit,!slow` runs the JIT tests except for the slow ones.
+/// All programs are selected if the variable isn't set.
+pub fn is_selected(file_name: &str) -> bool {
+    let Ok(filter) = env::var("MIBPF_TEST_TAGS") else {
+        return true;
+    };
+    let (excluded, included): (Vec<&str>, Vec<&str>) = filter
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .partition(|tag| tag.starts_with('!'));
+
+    let tags = test_tags(file_name);
+    let has_tag = |tag: &str| tags.contains(tag);
+    (included.is_empty() || included.iter().any(|tag| has_tag(tag)))
+        && !excluded.iter().any(|tag| has_tag(&tag[1..]))
+}
+
+/// Returns true (and prints why) if the test of the program should be
+/// skipped because its tags don't match MIBPF_TEST_TAGS.
+pub fn skip_unless_selected(file_name: &str) -> bool {
+    if is_selected(file_name) {
+        return false;
+    }
+    println!(
+        "Skipping {}, its tags {:?} don't match MIBPF_TEST_TAGS",
+        file_name,
+        test_tags(file_name)
+    );
+    true
+}
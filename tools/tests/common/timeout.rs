@@ -0,0 +1,99 @@
+use std::{
+    any::Any,
+    future::Future,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use micro_bpf_tools::{reboot_device, Environment, TransportOptions};
+
+use super::{
+    flaky::{run_with_retries, TestOutcome},
+    native,
+};
+
+/// Time limit of a single test unless overridden by MIBPF_TEST_TIMEOUT (in
+/// seconds).
+const DEFAULT_TEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+pub fn test_timeout() -> Duration {
+    std::env::var("MIBPF_TEST_TIMEOUT")
+        .ok()
+        .and_then(|timeout| timeout.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_TEST_TIMEOUT)
+}
+
+/// Runs the test and fails it if it doesn't finish within [`test_timeout`].
+///
+/// The test is executed on a separate thread with its own runtime, this is
+/// because the requests to the device block until aiocoap-client exits and so
+/// they can't be cancelled from within the async code. Once the time runs
+/// out, the device is recovered (see [`recover_device`]) so that the
+/// abandoned test fails on its pending request instead of keeping the device
+/// busy while the remaining tests of the suite run.
+///
+/// A failed (or timed out) test is re-run according to MIBPF_TEST_RETRIES,
+/// see [`run_with_retries`].
+pub fn run_with_timeout<F, Fut>(environment: &Environment, test: F) -> TestOutcome
+where
+    F: FnOnce(Environment) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = ()>,
+{
+    run_with_retries(|| run_attempt(environment, test.clone()))
+}
+
+fn run_attempt<F, Fut>(environment: &Environment, test: F) -> Result<(), Box<dyn Any + Send>>
+where
+    F: FnOnce(Environment) -> Fut + Send + 'static,
+    Fut: Future<Output = ()>,
+{
+    let timeout = test_timeout();
+    let (sender, receiver) = mpsc::channel();
+    let test_environment = environment.clone();
+    let handle = thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to create the runtime of the test");
+        runtime.block_on(test(test_environment));
+        let _ = sender.send(());
+    });
+
+    match receiver.recv_timeout(timeout) {
+        Ok(()) => {
+            let _ = handle.join();
+            Ok(())
+        }
+        // The sender is dropped without sending anything only if the test
+        // panicked, in which case we propagate the failure.
+        Err(RecvTimeoutError::Disconnected) => handle.join(),
+        Err(RecvTimeoutError::Timeout) => {
+            let recovery = match recover_device(environment) {
+                Ok(()) => "the device has been recovered".to_string(),
+                Err(e) => format!("failed to recover the device: {}", e),
+            };
+            Err(Box::new(format!(
+                "The test didn't finish within {:?}, {}",
+                timeout, recovery
+            )))
+        }
+    }
+}
+
+/// Brings the device back into a usable state after a test got stuck. The
+/// native instance started by the harness is restarted, otherwise the device
+/// is asked to reset itself by sending a POST request to its `/reset`
+/// resource, which stops all running VMs and reboots the board.
+pub fn recover_device(environment: &Environment) -> Result<(), String> {
+    if native::restart(environment)? {
+        return Ok(());
+    }
+
+    reboot_device(
+        &environment.riot_instance_ip,
+        &environment.host_net_if,
+        &TransportOptions::from_env(environment),
+    )
+}
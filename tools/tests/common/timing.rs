@@ -0,0 +1,58 @@
+use std::fs;
+
+use serde_json::Value;
+
+use super::TEST_SOURCES_DIR;
+
+/// Prefix of the comment line bounding the execution time of a test
+/// program in milliseconds, e.g. `// TEST_MAX_TIME_MS: 5`
+const MAX_TIME_PREFIX: &str = "// TEST_MAX_TIME_MS:";
+
+/// The devices report the execution time in microseconds.
+const EXECUTION_TIME_UNITS_PER_MS: u64 = 1000;
+
+/// Returns the bound on the execution time declared in the header comment of
+/// the test program, None if the program doesn't declare one.
+pub fn max_execution_time_ms(file_name: &str) -> Option<u64> {
+    let path = format!("{}/{}", TEST_SOURCES_DIR, file_name);
+    let source = fs::read_to_string(&path).unwrap_or_default();
+    source
+        .lines()
+        .take_while(|line| line.starts_with("//"))
+        .find_map(|line| line.strip_prefix(MAX_TIME_PREFIX))
+        .map(|bound| {
+            bound
+                .trim()
+                .parse::<u64>()
+                .unwrap_or_else(|e| panic!("Invalid TEST_MAX_TIME_MS in {}: {}", file_name, e))
+        })
+}
+
+/// Fails the test if the execution time reported by the device exceeds the
+/// bound declared by the test program, so that the performance regressions
+/// of the firmware or the JIT are caught along with the incorrect results.
+pub fn assert_within_time_bound(file_name: &str, execution_time: u32) {
+    let Some(bound) = max_execution_time_ms(file_name) else {
+        return;
+    };
+    assert!(
+        execution_time as u64 <= bound * EXECUTION_TIME_UNITS_PER_MS,
+        "The execution of {} took {}us, longer than its bound of {}ms",
+        file_name,
+        execution_time,
+        bound
+    );
+}
+
+/// Same as [`assert_within_time_bound`] for the programs which write their
+/// own CoAP response, the bound is only checked if the response reports the
+/// `execution_time`.
+pub fn assert_response_within_time_bound(file_name: &str, response: &str) {
+    let response = response.trim_matches(char::from(0)).trim();
+    let execution_time = serde_json::from_str::<Value>(response)
+        .ok()
+        .and_then(|response| response["execution_time"].as_u64());
+    if let Some(execution_time) = execution_time {
+        assert_within_time_bound(file_name, execution_time as u32);
+    }
+}
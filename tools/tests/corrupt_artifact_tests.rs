@@ -0,0 +1,90 @@
+mod common;
+
+use common::{
+    corrupt::{test_corrupted_artifact_rejected, Corruption},
+    load_test_env,
+    timeout::run_with_timeout,
+};
+
+use micro_bpf_common::BinaryFileLayout;
+
+// This module contains negative end-to-end tests of the verification performed
+// by the firmware. Each test compiles a valid program, damages the resulting
+// binary (after postprocessing but before signing, so that the SUIT manifest
+// digest still matches) and then checks that the device refuses to load and
+// execute it.
+
+#[tokio::test]
+async fn flipped_text_bytes_extended_header() {
+    test_rejected(
+        BinaryFileLayout::ExtendedHeader,
+        Corruption::FlipTextBytes {
+            offset: 0,
+            count: 8,
+        },
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn truncated_extended_header() {
+    test_rejected(
+        BinaryFileLayout::ExtendedHeader,
+        Corruption::Truncate { length: 12 },
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn broken_magic_extended_header() {
+    test_rejected(
+        BinaryFileLayout::ExtendedHeader,
+        Corruption::BreakHeaderMagic,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn flipped_text_bytes_femtocontainers_header() {
+    test_rejected(
+        BinaryFileLayout::FemtoContainersHeader,
+        Corruption::FlipTextBytes {
+            offset: 0,
+            count: 8,
+        },
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn truncated_femtocontainers_header() {
+    test_rejected(
+        BinaryFileLayout::FemtoContainersHeader,
+        Corruption::Truncate { length: 12 },
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn broken_magic_femtocontainers_header() {
+    test_rejected(
+        BinaryFileLayout::FemtoContainersHeader,
+        Corruption::BreakHeaderMagic,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn broken_magic_raw_object_file() {
+    test_rejected(
+        BinaryFileLayout::RawObjectFile,
+        Corruption::BreakHeaderMagic,
+    )
+    .await;
+}
+
+async fn test_rejected(layout: BinaryFileLayout, corruption: Corruption) {
+    run_with_timeout(&load_test_env(), move |env| async move {
+        test_corrupted_artifact_rejected("printf.c", layout, &env, corruption).await;
+    });
+}
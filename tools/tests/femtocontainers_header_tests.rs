@@ -1,10 +1,11 @@
 mod common;
 
 use common::{
+    load_test_env,
+    timeout::run_with_timeout,
     test_execution, test_execution_accessing_coap_pkt,
     test_execution_femtocontainer_vm, test_execution_accessing_coap_pkt_femtocontainer_vm,
 };
-use micro_bpf_tools::load_env;
 
 use micro_bpf_common::BinaryFileLayout;
 
@@ -74,19 +75,21 @@ async fn gcoap_response_format() {
 }
 
 async fn test_femtocontainers_header(test_program: &str) {
-    let env = load_env();
-    test_execution(
-        test_program,
-        BinaryFileLayout::FemtoContainersHeader,
-        &env,
-    )
-    .await;
-    test_execution_femtocontainer_vm(
-        test_program,
-        BinaryFileLayout::FemtoContainersHeader,
-        &env,
-    )
-    .await;
+    let test_program = test_program.to_string();
+    run_with_timeout(&load_test_env(), move |env| async move {
+        test_execution(
+            &test_program,
+            BinaryFileLayout::FemtoContainersHeader,
+            &env,
+        )
+        .await;
+        test_execution_femtocontainer_vm(
+            &test_program,
+            BinaryFileLayout::FemtoContainersHeader,
+            &env,
+        )
+        .await;
+    });
 }
 
 /// Tests execution of a given eBPF program which is expected to have access to
@@ -94,19 +97,21 @@ async fn test_femtocontainers_header(test_program: &str) {
 /// then tests whether the response received matches the one specified on the
 /// first line of the test file.
 async fn test_femtocontainers_header_accessing_coap_pkt(test_program: &str) {
-    let env = load_env();
-    test_execution_accessing_coap_pkt(
-        test_program,
-        BinaryFileLayout::FemtoContainersHeader,
-        &env,
-    )
-    .await;
+    let test_program = test_program.to_string();
+    run_with_timeout(&load_test_env(), move |env| async move {
+        test_execution_accessing_coap_pkt(
+            &test_program,
+            BinaryFileLayout::FemtoContainersHeader,
+            &env,
+        )
+        .await;
 
-    test_execution_accessing_coap_pkt_femtocontainer_vm(
-        test_program,
-        BinaryFileLayout::FemtoContainersHeader,
-        &env,
-    )
-    .await;
+        test_execution_accessing_coap_pkt_femtocontainer_vm(
+            &test_program,
+            BinaryFileLayout::FemtoContainersHeader,
+            &env,
+        )
+        .await;
+    });
 }
 
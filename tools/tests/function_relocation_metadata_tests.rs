@@ -1,10 +1,11 @@
 mod common;
 
 use common::{
+    load_test_env,
+    timeout::run_with_timeout,
     test_execution, test_execution_accessing_coap_pkt,
     test_execution_accessing_coap_pkt_specifying_helpers, test_execution_specifying_helpers,
 };
-use micro_bpf_tools::load_env;
 
 use micro_bpf_common::{BinaryFileLayout, HelperFunctionID, TargetVM};
 
@@ -220,8 +221,10 @@ async fn printf_no_required_helpers_panics() {
 /// that terminate quickly enough so that the microcontroller can send the CoAP
 /// response with the return value of the program within the request timeout.
 async fn test_function_relocation_metadata(test_program: &str) {
-    let env = load_env();
-    test_execution(test_program, BinaryFileLayout::ExtendedHeader, &env).await;
+    let test_program = test_program.to_string();
+    run_with_timeout(&load_test_env(), move |env| async move {
+        test_execution(&test_program, BinaryFileLayout::ExtendedHeader, &env).await;
+    });
 }
 
 // Similar to `test_function_relocation_metadata` but allows for restricting access
@@ -230,15 +233,17 @@ async fn test_function_relocation_metadata_with_helpers(
     test_program: &str,
     allowed_helpers: Vec<u8>,
 ) {
-    let env = load_env();
-    test_execution_specifying_helpers(
-        test_program,
-        BinaryFileLayout::ExtendedHeader,
-        TargetVM::Rbpf,
-        &env,
-        allowed_helpers,
-    )
-    .await;
+    let test_program = test_program.to_string();
+    run_with_timeout(&load_test_env(), move |env| async move {
+        test_execution_specifying_helpers(
+            &test_program,
+            BinaryFileLayout::ExtendedHeader,
+            TargetVM::Rbpf,
+            &env,
+            allowed_helpers,
+        )
+        .await;
+    });
 }
 
 /// Tests execution of a given eBPF program which is expected to have access to
@@ -246,22 +251,26 @@ async fn test_function_relocation_metadata_with_helpers(
 /// then tests whether the response received matches the one specified on the
 /// first line of the test file.
 async fn test_function_relocation_metadata_accessing_coap_pkt(test_program: &str) {
-    let env = load_env();
-    test_execution_accessing_coap_pkt(test_program, BinaryFileLayout::ExtendedHeader, &env).await;
+    let test_program = test_program.to_string();
+    run_with_timeout(&load_test_env(), move |env| async move {
+        test_execution_accessing_coap_pkt(&test_program, BinaryFileLayout::ExtendedHeader, &env).await;
+    });
 }
 
 async fn test_function_relocation_metadata_accessing_coap_pkt_with_helpers(
     test_program: &str,
     allowed_helpers: Vec<u8>,
 ) {
-    let env = load_env();
-    test_execution_accessing_coap_pkt_specifying_helpers(
-        test_program,
-        BinaryFileLayout::ExtendedHeader,
-        TargetVM::Rbpf,
-        &env,
-        allowed_helpers,
-        false,
-    )
-    .await;
+    let test_program = test_program.to_string();
+    run_with_timeout(&load_test_env(), move |env| async move {
+        test_execution_accessing_coap_pkt_specifying_helpers(
+            &test_program,
+            BinaryFileLayout::ExtendedHeader,
+            TargetVM::Rbpf,
+            &env,
+            allowed_helpers,
+            false,
+        )
+        .await;
+    });
 }
@@ -1,8 +1,16 @@
 mod common;
 
-use common::test_jit_execution;
+use std::panic::{self, AssertUnwindSafe};
+
+use common::{
+    flaky::TestOutcome,
+    jit::{record_jit_result, JitArchitecture},
+    load_test_env,
+    tags::skip_unless_selected,
+    test_jit_execution,
+    timeout::run_with_timeout,
+};
 use micro_bpf_common::BinaryFileLayout;
-use micro_bpf_tools::load_env;
 
 /// Tests for the simple programs to ensure that the jit compiler works correctly.
 
@@ -201,7 +209,34 @@ async fn jit_rodata() {
     test_jit("jit_rodata.c").await;
 }
 
+/// Runs the program on the JIT of the architecture of the board under test
+/// (see [`JitArchitecture::of`]) and records the outcome for that architecture.
 async fn test_jit(test_program: &str) {
-    let env = load_env();
-    test_jit_execution(test_program, BinaryFileLayout::RawObjectFile, &env).await;
+    if skip_unless_selected(test_program) {
+        return;
+    }
+    let environment = load_test_env();
+    let architecture = JitArchitecture::of(&environment);
+    if !architecture.has_jit() {
+        println!(
+            "Skipping {}, the firmware has no {} JIT",
+            test_program,
+            architecture.name()
+        );
+        return;
+    }
+
+    let program = test_program.to_string();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        run_with_timeout(&environment, move |env| async move {
+            test_jit_execution(&program, BinaryFileLayout::RawObjectFile, &env).await;
+        })
+    }));
+    let outcome = result
+        .as_ref()
+        .map_or(TestOutcome::Failed, |outcome| *outcome);
+    record_jit_result(architecture, &environment, test_program, outcome);
+    if let Err(panic) = result {
+        panic::resume_unwind(panic);
+    }
 }
@@ -1,7 +1,8 @@
 mod common;
 
-use micro_bpf_tools::load_env;
-use common::{test_execution, test_execution_accessing_coap_pkt};
+use common::{
+    load_test_env, test_execution, test_execution_accessing_coap_pkt, timeout::run_with_timeout,
+};
 use micro_bpf_common::BinaryFileLayout;
 
 // This module tests the VM executions with the OnlyTextSection binary file layout.
@@ -82,11 +83,15 @@ async fn gcoap_response_format() {
 ///
 /// These tests also use the original version of the rbpf VM interpreter.
 async fn test_only_text_section(test_program: &str) {
-    let env = load_env();
-    test_execution(test_program, BinaryFileLayout::OnlyTextSection, &env).await;
+    let test_program = test_program.to_string();
+    run_with_timeout(&load_test_env(), move |env| async move {
+        test_execution(&test_program, BinaryFileLayout::OnlyTextSection, &env).await;
+    });
 }
 
 async fn test_only_text_section_accessing_coap_pkt(test_program: &str) {
-    let env = load_env();
-    test_execution_accessing_coap_pkt(test_program, BinaryFileLayout::OnlyTextSection, &env).await;
+    let test_program = test_program.to_string();
+    run_with_timeout(&load_test_env(), move |env| async move {
+        test_execution_accessing_coap_pkt(&test_program, BinaryFileLayout::OnlyTextSection, &env).await;
+    });
 }
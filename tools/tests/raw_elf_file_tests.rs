@@ -1,7 +1,8 @@
 mod common;
 
-use micro_bpf_tools::load_env;
-use common::{test_execution, test_execution_accessing_coap_pkt};
+use common::{
+    load_test_env, test_execution, test_execution_accessing_coap_pkt, timeout::run_with_timeout,
+};
 use micro_bpf_common::BinaryFileLayout;
 
 // This module contains end-to-end integration tests of the compile-upload-
@@ -98,8 +99,10 @@ async fn gcoap_response_format() {
 /// that terminate quickly enough so that the microcontroller can send the CoAP
 /// response with the return value of the program within the request timeout.
 async fn test_raw_object_file(test_program: &str) {
-    let env = load_env();
-    test_execution(test_program, BinaryFileLayout::RawObjectFile, &env).await;
+    let test_program = test_program.to_string();
+    run_with_timeout(&load_test_env(), move |env| async move {
+        test_execution(&test_program, BinaryFileLayout::RawObjectFile, &env).await;
+    });
 }
 
 /// Tests execution of a given eBPF program which is expected to have access to
@@ -107,6 +110,8 @@ async fn test_raw_object_file(test_program: &str) {
 /// then tests whether the response received matches the one specified on the
 /// first line of the test file.
 async fn test_raw_object_file_accessing_coap_pkt(test_program: &str) {
-    let env = load_env();
-    test_execution_accessing_coap_pkt(test_program, BinaryFileLayout::RawObjectFile, &env).await;
+    let test_program = test_program.to_string();
+    run_with_timeout(&load_test_env(), move |env| async move {
+        test_execution_accessing_coap_pkt(&test_program, BinaryFileLayout::RawObjectFile, &env).await;
+    });
 }
@@ -1,4 +1,5 @@
 // TEST_RESULT: 0
+// TEST_TAGS: helpers
 #include "helpers.h"
 
 int test_bpf_fetch() {
@@ -1,4 +1,5 @@
 // TEST_RESULT: 5
+// TEST_TAGS: helpers
 #include <stdint.h>
 #include "helpers.h"
 
@@ -1,4 +1,5 @@
 // TEST_RESULT: 21
+// TEST_TAGS: helpers
 #include "helpers.h"
 
 int test_bpf_strlen()
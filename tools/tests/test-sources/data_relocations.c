@@ -1,4 +1,5 @@
 // TEST_RESULT: 123
+// TEST_TAGS: relocations
 #include <stdint.h>
 #include "helpers.h"
 
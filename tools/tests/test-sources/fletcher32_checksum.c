@@ -1,4 +1,5 @@
 // TEST_RESULT: 829540382
+// TEST_TAGS: checksum
 #include "helpers.h"
 
 // A random 320B string
@@ -1,4 +1,5 @@
 // TEST_RESULT: {"temperature": -12.3}
+// TEST_TAGS: coap, helpers
 #include <stdint.h>
 #include "helpers.h"
 
@@ -1,3 +1,4 @@
+// TEST_TAGS: coap, helpers, sensors
 #include <stdint.h>
 #include "helpers.h"
 
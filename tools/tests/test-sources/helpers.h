@@ -117,4 +117,11 @@ static uint64_t (*bpf_hd44780_set_cursor)(uint32_t dev, uint32_t row,
 static uint64_t (*bpf_keypad_get_input)(uint32_t adc_index) = (void *)
     BPF_KEYPAD_GET_INPUT;
 
+/* Return values */
+// Copies the buffer into the execution response, it allows for returning
+// arrays instead of a single value. The buffer can be at most 256 bytes long,
+// returns 0 on success.
+static int (*bpf_set_return_buffer)(const void *data, uint32_t len) = (void *)
+    BPF_FUNC_BPF_SET_RETURN_BUFFER;
+
 #endif /* BPF_APPLICATION_CALL_H */
@@ -1,4 +1,5 @@
 // TEST_RESULT: 12345
+// TEST_TAGS: relocations
 #include "helpers.h"
 inline int helper_function_1();
 inline int helper_function_2(int x);
@@ -1,4 +1,6 @@
 // TEST_RESULT: 32
+// TEST_TAGS: jit, arithmetic
+// TEST_MAX_TIME_MS: 1
 #include <stdint.h>
 int asr_immediate(void *ctx) {
     volatile int8_t x = -64;
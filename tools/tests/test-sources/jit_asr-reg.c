@@ -1,4 +1,6 @@
 // TEST_RESULT: 16
+// TEST_TAGS: jit, arithmetic
+// TEST_MAX_TIME_MS: 1
 #include <stdint.h>
 int asr_reg(void *ctx) {
     volatile int16_t a = -64;
@@ -1,4 +1,6 @@
 // TEST_RESULT: 7
+// TEST_TAGS: jit, arithmetic
+// TEST_MAX_TIME_MS: 1
 #include <stdint.h>
 int divide_reg(void *ctx) {
     volatile uint16_t x = 49;
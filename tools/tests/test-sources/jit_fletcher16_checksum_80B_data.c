@@ -1,4 +1,6 @@
 // TEST_RESULT: 32742
+// TEST_TAGS: jit, checksum
+// TEST_MAX_TIME_MS: 5
 #include "helpers.h"
 #include <stdint.h>
 
@@ -1,4 +1,6 @@
 // TEST_RESULT: 123
+// TEST_TAGS: jit, control-flow
+// TEST_MAX_TIME_MS: 5
 #include "helpers.h"
 #include <stdint.h>
 
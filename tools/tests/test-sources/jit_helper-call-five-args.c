@@ -1,4 +1,6 @@
 // TEST_RESULT: 1234
+// TEST_TAGS: jit, helpers
+// TEST_MAX_TIME_MS: 10
 #include "helpers.h"
 #include <stdint.h>
 const char fmt[] = "This is a test of three helper args: %d %d %d %d\n";
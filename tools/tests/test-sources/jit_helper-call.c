@@ -1,4 +1,6 @@
 // TEST_RESULT: 1000
+// TEST_TAGS: jit, helpers
+// TEST_MAX_TIME_MS: 5
 #include "helpers.h"
 #include <stdint.h>
 int helper_call(void *ctx) {
@@ -1,4 +1,6 @@
 // TEST_RESULT: 500500
+// TEST_TAGS: jit, control-flow
+// TEST_MAX_TIME_MS: 1
 #include "helpers.h"
 #include <stdint.h>
 int if_jump(void *ctx) {
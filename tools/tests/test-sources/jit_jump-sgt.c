@@ -1,4 +1,6 @@
 // TEST_RESULT: 123
+// TEST_TAGS: jit, control-flow
+// TEST_MAX_TIME_MS: 1
 #include <stdint.h>
 int jump_sgt(void *ctx) {
     // We need to use 16 bit ints here, otherwise lddw is used which is
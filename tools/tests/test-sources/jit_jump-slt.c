@@ -1,4 +1,6 @@
 // TEST_RESULT: 20
+// TEST_TAGS: jit, control-flow
+// TEST_MAX_TIME_MS: 1
 #include <stdint.h>
 int jump_slt(void *ctx) {
     volatile int16_t x = -100;
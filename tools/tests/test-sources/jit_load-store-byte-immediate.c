@@ -1,4 +1,6 @@
 // TEST_RESULT: 118
+// TEST_TAGS: jit, memory
+// TEST_MAX_TIME_MS: 1
 #include <stdint.h>
 int load_store_byte_immediate(void *ctx) {
     volatile uint8_t x = 100;
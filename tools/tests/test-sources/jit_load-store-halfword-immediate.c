@@ -1,4 +1,6 @@
 // TEST_RESULT: 118
+// TEST_TAGS: jit, memory
+// TEST_MAX_TIME_MS: 1
 #include <stdint.h>
 int load_store_word_immediate(void *ctx) {
     // The code below after compilation moves 100 into r1, then it stores
@@ -1,4 +1,6 @@
 // TEST_RESULT: 52
+// TEST_TAGS: jit, arithmetic
+// TEST_MAX_TIME_MS: 1
 #include <stdint.h>
 int lsl_immediate(void *ctx) {
     volatile int x = 13;
@@ -1,4 +1,6 @@
 // TEST_RESULT: 16
+// TEST_TAGS: jit, arithmetic
+// TEST_MAX_TIME_MS: 1
 #include <stdint.h>
 int lsr_immediate(void *ctx) {
     volatile int x = 64;
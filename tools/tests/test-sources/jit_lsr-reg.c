@@ -1,4 +1,6 @@
 // TEST_RESULT: 8
+// TEST_TAGS: jit, arithmetic
+// TEST_MAX_TIME_MS: 1
 #include <stdint.h>
 int lsr_reg(void *ctx) {
     volatile uint32_t x = 64;
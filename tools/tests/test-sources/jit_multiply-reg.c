@@ -1,4 +1,6 @@
 // TEST_RESULT: 150
+// TEST_TAGS: jit, arithmetic
+// TEST_MAX_TIME_MS: 1
 #include <stdint.h>
 int multiply_reg(void *ctx) {
     volatile int x = 10;
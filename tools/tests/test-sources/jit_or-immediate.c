@@ -1,4 +1,6 @@
 // TEST_RESULT: 15
+// TEST_TAGS: jit, arithmetic
+// TEST_MAX_TIME_MS: 1
 #include <stdint.h>
 int or_immediate(void *ctx) {
     volatile int x = 0b1100;
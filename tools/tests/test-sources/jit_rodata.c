@@ -1,4 +1,6 @@
 // TEST_RESULT: 5
+// TEST_TAGS: jit, relocations
+// TEST_MAX_TIME_MS: 1
 #include <stdint.h>
 #include "helpers.h"
 char rodata[] = "hello";
@@ -1,4 +1,6 @@
 // TEST_RESULT: 77
+// TEST_TAGS: jit, arithmetic
+// TEST_MAX_TIME_MS: 1
 #include <stdint.h>
 int subtract_reg(void *ctx) {
     volatile int x = 100;
@@ -1,3 +1,5 @@
+// TEST_TAGS: jit, helpers, sensors
+// TEST_MAX_TIME_MS: 100
 #include <stdint.h>
 #include "helpers.h"
 
@@ -1,4 +1,6 @@
 // TEST_RESULT: 5050
+// TEST_TAGS: jit, control-flow
+// TEST_MAX_TIME_MS: 500
 #include "helpers.h"
 #include <stdint.h>
 int while_loop_test(void *ctx) {
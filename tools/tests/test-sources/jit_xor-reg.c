@@ -1,4 +1,6 @@
 // TEST_RESULT: 27
+// TEST_TAGS: jit, arithmetic
+// TEST_MAX_TIME_MS: 1
 #include <stdint.h>
 int xor_reg(void *ctx) {
     volatile int x = 0b10101;
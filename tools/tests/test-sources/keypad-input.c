@@ -1,3 +1,4 @@
+// TEST_TAGS: helpers, sensors
 #include "helpers.h"
 
 #define NO_INPUT 4
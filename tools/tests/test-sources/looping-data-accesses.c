@@ -1,4 +1,5 @@
 // TEST_RESULT: 32742
+// TEST_TAGS: control-flow
 #include "helpers.h"
 #include <stdint.h>
 
@@ -1,4 +1,5 @@
 // TEST_RESULT: 12345
+// TEST_TAGS: relocations
 #include "helpers.h"
 static int __attribute__((noinline)) helper_function_1();
 static int __attribute__((noinline)) helper_function_2(int x);
@@ -1,4 +1,5 @@
 // TEST_RESULT: 0
+// TEST_TAGS: helpers, bench
 #include "helpers.h"
 
 // This string should go into the .rodata section
@@ -1,4 +1,5 @@
 // TEST_RESULT: 100
+// TEST_TAGS: helpers
 #include "helpers.h"
 
 // This testcase checks if the printing functionality works when using the
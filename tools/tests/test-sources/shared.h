@@ -71,6 +71,9 @@ enum {
 
   BPF_KEYPAD_GET_INPUT = 0x84,
 
+  /* Return values */
+  BPF_FUNC_BPF_SET_RETURN_BUFFER = 0x90,
+
 };
 
 /* Helper structs */
@@ -1,3 +1,4 @@
+// TEST_TAGS: helpers, sensors, bench
 #include <stdint.h>
 #include "helpers.h"
 